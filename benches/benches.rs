@@ -10,11 +10,13 @@ mod benchmarks;
 criterion_main!(
     benchmarks::scripted::scripted, //
     benchmarks::rhythm::rhythm,
+    benchmarks::sequence::sequence,
     benchmarks::cycle::cycle,
 );
 
 #[cfg(not(feature = "scripting"))]
 criterion_main!(
     benchmarks::rhythm::rhythm, //
+    benchmarks::sequence::sequence,
     benchmarks::cycle::cycle,
 );
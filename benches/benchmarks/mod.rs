@@ -1,4 +1,5 @@
 pub(crate) mod cycle;
 pub(crate) mod rhythm;
+pub(crate) mod sequence;
 #[cfg(feature = "scripting")]
 pub(crate) mod scripted;
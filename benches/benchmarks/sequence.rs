@@ -0,0 +1,120 @@
+use criterion::{black_box, criterion_group, Criterion};
+
+use afseq::prelude::*;
+
+// ---------------------------------------------------------------------------------------------
+
+fn create_sequence() -> Sequence {
+    let beat_time = BeatTimeBase {
+        samples_per_sec: 44100,
+        beats_per_min: 130.0,
+        beats_per_bar: 4,
+    };
+
+    let kick_cycle =
+        new_cycle_event("bd [~ bd] ~ ~ bd [~ bd] _ ~ bd [~ bd] ~ ~ bd [~ bd] [_ bd2] ~")
+            .unwrap()
+            .with_mappings(&[
+                ("bd", vec![new_note("c4")]),
+                ("bd2", vec![new_note(("c4", None, 0.5))]),
+            ]);
+    let kick_pattern = beat_time.every_nth_beat(16.0).trigger(kick_cycle);
+
+    let snare_pattern = beat_time
+        .every_nth_beat(2.0)
+        .with_offset(BeatTimeStep::Beats(1.0))
+        .trigger(new_note_event("C_5"));
+
+    let hihat_pattern = beat_time
+        .every_nth_sixteenth(1.0)
+        .with_pattern([1, 0, 1, 1].to_pattern())
+        .trigger(new_note_event_sequence(vec![
+            new_note("C_5"),
+            new_note(("C_5", None, 0.5)),
+        ]));
+
+    let bass_notes = Scale::try_from((Note::C5, "aeolian")).unwrap().notes();
+    let bass_pattern = beat_time
+        .every_nth_eighth(1.0)
+        .with_pattern([1, 0, 1, 0, 0, 1, 0, 0, 1, 0, 1, 0, 0, 1, 0, 1].to_pattern())
+        .trigger(new_note_event_sequence(
+            bass_notes
+                .iter()
+                .map(|note| new_note((*note, None, 0.5)))
+                .collect::<Vec<_>>(),
+        ));
+
+    let chord_pattern = beat_time
+        .every_nth_bar(1.0)
+        .trigger(new_polyphonic_note_sequence_event(vec![
+            vec![
+                new_note(("C 4", None, 0.3)),
+                new_note(("D#4", None, 0.3)),
+                new_note(("G 4", None, 0.3)),
+            ],
+            vec![
+                new_note(("C 4", None, 0.3)),
+                new_note(("D#4", None, 0.3)),
+                new_note(("F 4", None, 0.3)),
+            ],
+        ]));
+
+    let verse = Phrase::new(
+        beat_time,
+        vec![
+            RhythmSlot::from(kick_pattern),
+            RhythmSlot::from(snare_pattern),
+            RhythmSlot::from(hihat_pattern),
+            RhythmSlot::from(bass_pattern),
+        ],
+        BeatTimeStep::Bar(8.0),
+    );
+    let chorus = Phrase::new(
+        beat_time,
+        vec![
+            RhythmSlot::Continue,
+            RhythmSlot::Continue,
+            RhythmSlot::Stop,
+            RhythmSlot::Continue,
+            RhythmSlot::from(chord_pattern),
+        ],
+        BeatTimeStep::Bar(8.0),
+    );
+    Sequence::new(beat_time, vec![verse, chorus])
+}
+
+// ---------------------------------------------------------------------------------------------
+
+pub fn run(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Rust Sequence");
+    group.measurement_time(std::time::Duration::from_secs(10));
+    let mut sequence = create_sequence();
+    let samples_per_sec = sequence.time_base().samples_per_sec as SampleTime;
+    // run in blocks of typical audio buffer sizes, like a player does
+    let block_size = 512;
+    let run_time = 60 * samples_per_sec;
+    group.bench_function("Run", |b| {
+        b.iter(|| {
+            sequence.reset();
+            let mut sample_time = 0;
+            while sample_time < run_time {
+                sample_time += block_size;
+                sequence.consume_events_until_time(
+                    sample_time,
+                    &mut |rhythm_index, time, event, duration| {
+                        black_box((rhythm_index, time, event, duration));
+                    },
+                );
+            }
+        })
+    });
+    group.finish();
+}
+
+// ---------------------------------------------------------------------------------------------
+
+criterion_group! {
+    name = sequence;
+    config = Criterion::default();
+    targets = run
+}
@@ -0,0 +1,21 @@
+fn main() {
+    #[cfg(feature = "capi")]
+    generate_c_header();
+}
+
+/// Generate the C API header into cargo's `OUT_DIR`.
+#[cfg(feature = "capi")]
+fn generate_c_header() {
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("missing cargo manifest dir");
+    let out_dir = std::env::var("OUT_DIR").expect("missing cargo out dir");
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+        .expect("invalid cbindgen config");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate the C API header")
+        .write_to_file(format!("{out_dir}/afseq.h"));
+}
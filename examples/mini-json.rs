@@ -0,0 +1,22 @@
+//! Evaluates a mini-notation string and prints the evaluated events as JSON.
+//!
+//! Usage: `cargo run --example mini-json -- "<mini-notation>" [cycle-count]`
+
+use afseq::tidal::mini_to_json;
+
+// -------------------------------------------------------------------------------------------------
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let input = args
+        .next()
+        .ok_or("Usage: mini-json \"<mini-notation>\" [cycle-count]")?;
+    let cycle_count = match args.next() {
+        Some(count) => count
+            .parse::<usize>()
+            .map_err(|err| format!("Invalid cycle count '{}': {}", count, err))?,
+        None => 1,
+    };
+    println!("{}", mini_to_json(&input, cycle_count)?);
+    Ok(())
+}
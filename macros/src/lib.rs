@@ -0,0 +1,546 @@
+//! Compile-time pattern macros for [afseq](https://github.com/emuell/afseq).
+//!
+//! Enable afseq's `macros` feature to use them as `afseq::pattern!` and `afseq::emitter!`.
+//! Patterns get parsed and validated while compiling, so static patterns have no runtime parse
+//! costs and typos show up as compile errors.
+
+use pest::{iterators::Pair, Parser};
+use pest_derive::Parser;
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Parse a step grid or a pulse mini-notation string into a `FixedPattern`.
+///
+/// Step grids only consist of `x` or `X` pulses and `.`, `-` or `~` rests. Whitespace and `|`
+/// bar lines in grids are ignored.
+///
+/// All other strings get parsed with afseq's cycle mini-notation grammar: steps are separated by
+/// whitespace. `x`, `X` or numbers in range \[0 - 1\] are pulses, `~` or `-` are rests. `[...]`
+/// subdivides a step, `.` splits steps into subdivisions, `!` repeats the previous step, `a!N`
+/// repeats a step N times and `a*N` plays a step N times within the step. Other cycle features,
+/// such as alternations, polymeters or random choices, can't be expressed as fixed pulses and
+/// are compile errors.
+///
+/// ```rust,ignore
+/// use afseq::prelude::*;
+/// // a single bar step grid
+/// let pattern = afseq::pattern!("x..x | x.x.");
+/// // a bar with a 16th note triplet at the end
+/// let pattern = afseq::pattern!("x 0.5 x*2 [x x x]");
+/// ```
+#[proc_macro]
+pub fn pattern(input: TokenStream) -> TokenStream {
+    expand(input, |string| {
+        parse_pulses(string)
+            .map(|pulses| pulse_vector_code(&pulses))
+            .map(|pulses| {
+                format!(
+                    "::afseq::pattern::fixed::FixedPattern::from_pulses({})",
+                    pulses
+                )
+            })
+    })
+}
+
+/// Parse a note sequence in cycle mini-notation into a `FixedEventIter`.
+///
+/// Steps are separated by whitespace. Notes are written as note names like `c4`, `f#5` or `bb3`
+/// or as MIDI note numbers, chords as stacked notes like `[c4,e4,g4]`. `off` stops playing notes,
+/// `~` or `-` are rests and `_` holds the previous step's notes. `!` repeats the previous step and
+/// `a!N` repeats a step N times. Note names use afseq's default octave numbering, where `c4` is
+/// MIDI note 48. Other cycle features are compile errors.
+///
+/// ```rust,ignore
+/// use afseq::prelude::*;
+/// let emitter = afseq::emitter!("c4 [e4,g4] _ ~ off");
+/// ```
+#[proc_macro]
+pub fn emitter(input: TokenStream) -> TokenStream {
+    expand(input, |string| {
+        parse_steps(string).map(|steps| {
+            let steps = steps.iter().map(step_code).collect::<Vec<_>>().join(", ");
+            format!(
+                "::afseq::event::fixed::FixedEventIter::from_steps(::std::vec![{}])",
+                steps
+            )
+        })
+    })
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Parser for afseq's cycle mini-notation, which shares its grammar with afseq's `Cycle`.
+#[derive(Parser)]
+#[grammar = "cycle.pest"]
+struct CycleParser;
+
+// -------------------------------------------------------------------------------------------------
+
+/// A parsed pattern pulse.
+#[derive(Clone, Debug, PartialEq)]
+enum Pulse {
+    Pulse(f32),
+    SubDivision(Vec<Pulse>),
+}
+
+/// A parsed note value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Note {
+    On(u8),
+    Off,
+}
+
+/// A parsed note sequence step.
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Notes(Vec<Note>),
+    Rest,
+    Hold,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn expand<F>(input: TokenStream, generator: F) -> TokenStream
+where
+    F: FnOnce(&str) -> Result<String, String>,
+{
+    let code = match string_literal(input).and_then(|string| generator(&string)) {
+        Ok(code) => code,
+        Err(err) => format!("::core::compile_error!({:?})", err),
+    };
+    code.parse().expect("generated code should be valid")
+}
+
+fn string_literal(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(literal)), None) => unquote(&literal.to_string()),
+        // literals which got passed through declarative macros
+        (Some(TokenTree::Group(group)), None) if group.delimiter() == Delimiter::None => {
+            string_literal(group.stream())
+        }
+        _ => Err("expected a single string literal".to_string()),
+    }
+}
+
+fn unquote(literal: &str) -> Result<String, String> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        if raw.len() >= 2 * hashes {
+            let raw = &raw[hashes..raw.len() - hashes];
+            if let Some(string) = raw.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                return Ok(string.to_string());
+            }
+        }
+    } else if let Some(string) = literal.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        if string.contains('\\') {
+            return Err("escape sequences are not supported in patterns".to_string());
+        }
+        return Ok(string.to_string());
+    }
+    Err(format!("expected a string literal, got '{}'", literal))
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn parse_mini(string: &str) -> Result<Vec<Pair<Rule>>, String> {
+    let mut pairs = CycleParser::parse(Rule::mini, string).map_err(|err| err.to_string())?;
+    let mini = pairs.next().ok_or("empty mini-notation")?;
+    Ok(mini
+        .into_inner()
+        .filter(|pair| pair.as_rule() != Rule::EOI)
+        .collect())
+}
+
+fn unsupported(pair: &Pair<Rule>) -> String {
+    format!(
+        "'{}' can't be expressed as a fixed sequence of steps",
+        pair.as_str()
+    )
+}
+
+/// Parse a sequence of steps in a cycle group with the given step parser, resolving repeats.
+/// `.` splits get converted to subdivisions with the given split function, when present.
+fn parse_group<T, F>(
+    pairs: Vec<Pair<Rule>>,
+    parse_step: &F,
+    split: Option<fn(Vec<T>) -> T>,
+) -> Result<Vec<T>, String>
+where
+    T: Clone,
+    F: Fn(Pair<Rule>) -> Result<Vec<T>, String>,
+{
+    let mut sections = vec![Vec::new()];
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::split_op if split.is_some() => sections.push(Vec::new()),
+            Rule::repeat => {
+                let steps = sections.last_mut().expect("sections should not be empty");
+                let step = steps
+                    .last()
+                    .cloned()
+                    .ok_or("'!' needs a preceding step to repeat")?;
+                steps.push(step);
+            }
+            Rule::stack_op | Rule::choice_op | Rule::split_op => return Err(unsupported(&pair)),
+            _ => {
+                let steps = parse_step(pair)?;
+                sections
+                    .last_mut()
+                    .expect("sections should not be empty")
+                    .extend(steps);
+            }
+        }
+    }
+    if sections.len() > 1 && sections.iter().any(Vec::is_empty) {
+        return Err("empty split in mini-notation".to_string());
+    }
+    match split {
+        Some(split) if sections.len() > 1 => Ok(sections.into_iter().map(split).collect()),
+        _ => Ok(sections.pop().unwrap_or_default()),
+    }
+}
+
+/// Parse the step count of `!N` and `*N` operators.
+fn parse_count(pair: Pair<Rule>) -> Result<usize, String> {
+    let text = pair.as_str();
+    let count = pair
+        .into_inner()
+        .next()
+        .map(|single| single.as_str())
+        .and_then(|count| count.parse::<usize>().ok())
+        .filter(|count| (1..=256).contains(count))
+        .ok_or_else(|| format!("invalid step count in '{}'", text))?;
+    Ok(count)
+}
+
+fn parse_pulses(string: &str) -> Result<Vec<Pulse>, String> {
+    let is_grid_char = |c: char| matches!(c, 'x' | 'X' | '.' | '-' | '~' | '|');
+    if !string.trim().is_empty() && string.chars().all(|c| is_grid_char(c) || c.is_whitespace()) {
+        // step grid
+        let pulses = string
+            .chars()
+            .filter(|c| !(c.is_whitespace() || *c == '|'))
+            .map(|c| Pulse::Pulse(if matches!(c, 'x' | 'X') { 1.0 } else { 0.0 }))
+            .collect::<Vec<_>>();
+        if pulses.is_empty() {
+            return Err("pattern is empty".to_string());
+        }
+        return Ok(pulses);
+    }
+    let pulses = parse_group(parse_mini(string)?, &parse_pulse, Some(Pulse::SubDivision))?;
+    if pulses.is_empty() {
+        return Err("pattern is empty".to_string());
+    }
+    Ok(pulses)
+}
+
+fn parse_pulse(pair: Pair<Rule>) -> Result<Vec<Pulse>, String> {
+    match pair.as_rule() {
+        Rule::single => {
+            let value = pair.clone().into_inner().next().ok_or("empty step")?;
+            let pulse = match value.as_rule() {
+                Rule::rest => 0.0,
+                Rule::name if matches!(value.as_str(), "x" | "X") => 1.0,
+                Rule::number => {
+                    let pulse = value
+                        .as_str()
+                        .parse::<f32>()
+                        .map_err(|_| format!("invalid pattern step '{}'", value.as_str()))?;
+                    if !(0.0..=1.0).contains(&pulse) {
+                        return Err(format!(
+                            "invalid pattern step '{}': values must be in range [0 - 1]",
+                            value.as_str()
+                        ));
+                    }
+                    pulse
+                }
+                _ => return Err(format!("invalid pattern step '{}'", pair.as_str())),
+            };
+            Ok(vec![Pulse::Pulse(pulse)])
+        }
+        Rule::subdivision => {
+            let pairs = pair.into_inner().collect();
+            let pulses = parse_group(pairs, &parse_pulse, Some(Pulse::SubDivision))?;
+            if pulses.is_empty() {
+                return Err("empty sub division '[]' in pattern".to_string());
+            }
+            Ok(vec![Pulse::SubDivision(pulses)])
+        }
+        Rule::expression => {
+            let text = pair.as_str().to_string();
+            let mut inner = pair.into_inner();
+            let mut pulses = parse_pulse(inner.next().ok_or("empty expression")?)?;
+            for op in inner {
+                pulses = match op.as_rule() {
+                    Rule::op_replicate => pulses.repeat(parse_count(op)?),
+                    Rule::op_fast => vec![Pulse::SubDivision(pulses.repeat(parse_count(op)?))],
+                    _ => return Err(format!("unsupported operator in '{}'", text)),
+                };
+            }
+            Ok(pulses)
+        }
+        _ => Err(unsupported(&pair)),
+    }
+}
+
+fn parse_pitch(pair: Pair<Rule>) -> Result<Note, String> {
+    let text = pair.as_str();
+    let mut key = 0;
+    let mut octave = 4;
+    for p in pair.into_inner() {
+        match p.as_rule() {
+            Rule::note => {
+                key = match p.as_str().to_ascii_lowercase().as_str() {
+                    "c" => 0,
+                    "d" => 2,
+                    "e" => 4,
+                    "f" => 5,
+                    "g" => 7,
+                    "a" => 9,
+                    _ => 11,
+                }
+            }
+            Rule::mark => key += if p.as_str() == "#" { 1 } else { -1 },
+            Rule::octave => {
+                octave = p
+                    .as_str()
+                    .parse::<i32>()
+                    .map_err(|_| format!("invalid octave in note '{}'", text))?
+            }
+            _ => (),
+        }
+    }
+    let note = octave * 12 + key;
+    if !(0..=127).contains(&note) {
+        return Err(format!("note '{}' is out of range", text));
+    }
+    Ok(Note::On(note as u8))
+}
+
+fn parse_note(pair: Pair<Rule>) -> Result<Option<Note>, String> {
+    let text = pair.as_str();
+    match pair.as_rule() {
+        Rule::pitch => parse_pitch(pair).map(Some),
+        Rule::name if text.eq_ignore_ascii_case("off") => Ok(Some(Note::Off)),
+        Rule::number => match text.parse::<u8>() {
+            Ok(note) if note <= 127 => Ok(Some(Note::On(note))),
+            _ => Err(format!("invalid note number '{}'", text)),
+        },
+        _ => Ok(None),
+    }
+}
+
+fn parse_steps(string: &str) -> Result<Vec<Step>, String> {
+    let steps = parse_group(parse_mini(string)?, &parse_step, None)?;
+    if steps.is_empty() {
+        return Err("note sequence is empty".to_string());
+    }
+    Ok(steps)
+}
+
+fn parse_step(pair: Pair<Rule>) -> Result<Vec<Step>, String> {
+    match pair.as_rule() {
+        Rule::single => {
+            let value = pair.clone().into_inner().next().ok_or("empty step")?;
+            let step = match value.as_rule() {
+                Rule::rest => Step::Rest,
+                Rule::hold => Step::Hold,
+                _ => match parse_note(value)? {
+                    Some(note) => Step::Notes(vec![note]),
+                    None => return Err(format!("invalid note '{}'", pair.as_str())),
+                },
+            };
+            Ok(vec![step])
+        }
+        Rule::subdivision => {
+            // only stacks of single notes, aka chords, can be expressed as fixed steps
+            let mut notes = Vec::new();
+            let mut expect_note = true;
+            for p in pair.clone().into_inner() {
+                match p.as_rule() {
+                    Rule::single if expect_note => {
+                        let value = p.into_inner().next().ok_or("empty step")?;
+                        let text = value.as_str().to_string();
+                        notes.push(parse_note(value)?.ok_or(format!("invalid note '{}'", text))?);
+                        expect_note = false;
+                    }
+                    Rule::stack_op if !expect_note => expect_note = true,
+                    _ => {
+                        return Err(format!(
+                            "'{}': only chords like '[c4,e4,g4]' can be used as sub divisions",
+                            pair.as_str()
+                        ))
+                    }
+                }
+            }
+            if notes.is_empty() || expect_note {
+                return Err(format!("invalid chord '{}'", pair.as_str()));
+            }
+            Ok(vec![Step::Notes(notes)])
+        }
+        Rule::expression => {
+            let text = pair.as_str().to_string();
+            let mut inner = pair.into_inner();
+            let mut steps = parse_step(inner.next().ok_or("empty expression")?)?;
+            for op in inner {
+                steps = match op.as_rule() {
+                    Rule::op_replicate => steps.repeat(parse_count(op)?),
+                    _ => return Err(format!("unsupported operator in '{}'", text)),
+                };
+            }
+            Ok(steps)
+        }
+        _ => Err(unsupported(&pair)),
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn pulse_vector_code(pulses: &[Pulse]) -> String {
+    let pulses = pulses.iter().map(pulse_code).collect::<Vec<_>>().join(", ");
+    format!("::std::vec![{}]", pulses)
+}
+
+fn pulse_code(pulse: &Pulse) -> String {
+    match pulse {
+        Pulse::Pulse(value) => format!("::afseq::Pulse::Pulse({:?}f32)", value),
+        Pulse::SubDivision(pulses) => {
+            format!("::afseq::Pulse::SubDivision({})", pulse_vector_code(pulses))
+        }
+    }
+}
+
+fn step_code(step: &Step) -> String {
+    const STEP: &str = "::afseq::event::fixed::FixedSequenceStep";
+    match step {
+        Step::Notes(notes) => {
+            let notes = notes
+                .iter()
+                .map(|note| match note {
+                    Note::On(note) => {
+                        format!("::afseq::event::new_note(::afseq::Note::from({}u8))", note)
+                    }
+                    Note::Off => "::afseq::event::new_note(::afseq::Note::OFF)".to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{}::Notes(::std::vec![{}])", STEP, notes)
+        }
+        Step::Rest => format!("{}::Rest", STEP),
+        Step::Hold => format!("{}::Hold", STEP),
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        assert_eq!(unquote(r#""x . x""#), Ok("x . x".to_string()));
+        assert_eq!(unquote(r###"r#"x "x"#"###), Ok(r#"x "x"#.to_string()));
+        assert!(unquote(r#""x\n""#).is_err());
+        assert!(unquote("42").is_err());
+    }
+
+    #[test]
+    fn pulses() {
+        // step grids
+        assert_eq!(
+            parse_pulses("x..x | -~X"),
+            Ok([1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]
+                .into_iter()
+                .map(Pulse::Pulse)
+                .collect::<Vec<_>>())
+        );
+        // mini-notation
+        assert_eq!(
+            parse_pulses("x ~ 0.5 [x x]"),
+            Ok(vec![
+                Pulse::Pulse(1.0),
+                Pulse::Pulse(0.0),
+                Pulse::Pulse(0.5),
+                Pulse::SubDivision(vec![Pulse::Pulse(1.0), Pulse::Pulse(1.0)]),
+            ])
+        );
+        assert_eq!(
+            parse_pulses("[x [x ~]]"),
+            Ok(vec![Pulse::SubDivision(vec![
+                Pulse::Pulse(1.0),
+                Pulse::SubDivision(vec![Pulse::Pulse(1.0), Pulse::Pulse(0.0)]),
+            ])])
+        );
+        assert_eq!(
+            parse_pulses("1 ! 0!2 x*2"),
+            Ok(vec![
+                Pulse::Pulse(1.0),
+                Pulse::Pulse(1.0),
+                Pulse::Pulse(0.0),
+                Pulse::Pulse(0.0),
+                Pulse::SubDivision(vec![Pulse::Pulse(1.0), Pulse::Pulse(1.0)]),
+            ])
+        );
+        assert_eq!(
+            parse_pulses("1 0 . 1"),
+            Ok(vec![
+                Pulse::SubDivision(vec![Pulse::Pulse(1.0), Pulse::Pulse(0.0)]),
+                Pulse::SubDivision(vec![Pulse::Pulse(1.0)]),
+            ])
+        );
+        assert!(parse_pulses("").is_err());
+        assert!(parse_pulses("x [x").is_err());
+        assert!(parse_pulses("x ]").is_err());
+        assert!(parse_pulses("x []").is_err());
+        assert!(parse_pulses("x 2").is_err());
+        assert!(parse_pulses("x y").is_err());
+        assert!(parse_pulses("x <x ~>").is_err());
+        assert!(parse_pulses("x | ~ 1").is_err());
+        assert!(parse_pulses("x(3,8)").is_err());
+        assert_eq!(
+            pulse_vector_code(&[Pulse::SubDivision(vec![Pulse::Pulse(0.5)])]),
+            "::std::vec![::afseq::Pulse::SubDivision(::std::vec![::afseq::Pulse::Pulse(0.5f32)])]"
+        );
+    }
+
+    #[test]
+    fn steps() {
+        let note = |string: &str| -> Result<Vec<Step>, String> { parse_steps(string) };
+        let single = |note: u8| Ok(vec![Step::Notes(vec![Note::On(note)])]);
+        assert_eq!(note("c4"), single(48));
+        assert_eq!(note("C#4"), single(49));
+        assert_eq!(note("bb3"), single(46));
+        assert_eq!(note("g"), single(55));
+        assert_eq!(note("60"), single(60));
+        assert_eq!(note("OFF"), Ok(vec![Step::Notes(vec![Note::Off])]));
+        assert!(note("h4").is_err());
+        assert!(note("c4x").is_err());
+        assert!(note("200").is_err());
+        assert_eq!(
+            parse_steps("c4 [e4,g4] _ ~ off d4!2"),
+            Ok(vec![
+                Step::Notes(vec![Note::On(48)]),
+                Step::Notes(vec![Note::On(52), Note::On(55)]),
+                Step::Hold,
+                Step::Rest,
+                Step::Notes(vec![Note::Off]),
+                Step::Notes(vec![Note::On(50)]),
+                Step::Notes(vec![Note::On(50)]),
+            ])
+        );
+        assert!(parse_steps(" ").is_err());
+        assert!(parse_steps("c4 x").is_err());
+        assert!(parse_steps("c4 [e4 g4]").is_err());
+        assert!(parse_steps("c4, e4").is_err());
+        assert!(parse_steps("c4 . e4").is_err());
+        assert!(parse_steps("c4*2").is_err());
+        assert_eq!(
+            step_code(&Step::Notes(vec![Note::Off])),
+            "::afseq::event::fixed::FixedSequenceStep::Notes(\
+            ::std::vec![::afseq::event::new_note(::afseq::Note::OFF)])"
+        );
+    }
+}
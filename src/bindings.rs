@@ -1,6 +1,13 @@
 //! Lua bindings for the entire crate.
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
 use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
@@ -10,18 +17,30 @@ use lazy_static::lazy_static;
 use mlua::prelude::*;
 
 use self::{
+    callback::CONTEXT_METATABLE,
     cycle::CycleUserData,
+    groove::groove_from_values,
     note::NoteUserData,
-    rhythm::rhythm_from_userdata,
+    parameter::{parameters_from_table, AutoParameterRhythm, AutoParameters},
+    performance::{performance_macro_from_table, performance_macros_from_value},
+    pool::PoolUserData,
+    rhythm::{rhythm_from_userdata, RegisteredRhythm},
     sequence::SequenceUserData,
     unwrap::{bad_argument_error, validate_table_properties},
 };
 
 use crate::{
     event::InstrumentId,
-    rhythm::{beat_time::BeatTimeRhythm, second_time::SecondTimeRhythm, Rhythm},
+    parameter::RhythmParameterValues,
+    performance::PerformanceMacro,
+    rhythm::{
+        beat_time::BeatTimeRhythm, chain::ChainRhythm, second_time::SecondTimeRhythm,
+        seed_from_number, Rhythm,
+    },
+    shared::SharedValues,
     time::BeatTimeBase,
-    Scale,
+    warning::ValueRangePolicy,
+    Groove, Scale,
 };
 
 // ---------------------------------------------------------------------------------------------
@@ -29,23 +48,38 @@ use crate::{
 // private binding impls
 mod callback;
 mod cycle;
+mod groove;
 mod note;
+mod parameter;
+mod performance;
+mod policy;
+mod pool;
 mod rhythm;
 mod scale;
 mod sequence;
+mod shared;
 mod timeout;
 mod unwrap;
+mod validate;
+
+// public re-exports
+pub use parameter::AutoParameter;
 
 // public re-exports
 pub use callback::{
     add_lua_callback_error, clear_lua_callback_errors, has_lua_callback_errors, lua_callback_errors,
 };
 
+// public re-exports
+pub use validate::{validate_script, ScriptIssue, ScriptIssueKind, ValidationReport};
+
 // internal re-exports
-pub(crate) use callback::LuaCallback;
+pub(crate) use callback::{LuaCallback, LuaScriptErrors};
+pub(crate) use policy::LuaValueRange;
 pub(crate) use timeout::LuaTimeoutHook;
 pub(crate) use unwrap::{
-    gate_trigger_from_value, note_events_from_value, pattern_pulse_from_value,
+    cycle_map_events_from_value, gate_trigger_from_value, instrument_from_cycle_target,
+    note_events_from_value, parameter_change_event_from_value, pattern_pulse_from_value,
 };
 
 // ---------------------------------------------------------------------------------------------
@@ -58,15 +92,35 @@ pub(crate) struct LuaAppData {
     pub(crate) rand_seed: Option<[u8; 32]>,
     /// Global random number generator, used for our math.random() impl.
     pub(crate) rand_rgn: Xoshiro256PlusPlus,
+    /// Auto parameter state, when numeric constants of rhythms should be lifted into parameters.
+    pub(crate) auto_parameters: Option<AutoParameters>,
+    /// Default shared values of all callbacks, until a sequence passes its shared values.
+    pub(crate) shared_values: SharedValues,
+    /// Parameter values of the rhythm which currently gets created, passed to its callbacks.
+    pub(crate) parameter_values: Option<RhythmParameterValues>,
+    /// Value range policy of the Lua instance and its clamp warnings.
+    pub(crate) value_range: LuaValueRange,
+    /// Callback errors of the Lua instance, until the instance's rhythms take them.
+    pub(crate) script_errors: LuaScriptErrors,
 }
 
 impl LuaAppData {
     fn new() -> Self {
         let rand_seed = None;
         let rand_rgn = Xoshiro256PlusPlus::from_seed(rand::thread_rng().gen());
+        let auto_parameters = None;
+        let shared_values = SharedValues::new();
+        let parameter_values = None;
+        let value_range = LuaValueRange::default();
+        let script_errors = LuaScriptErrors::default();
         Self {
             rand_seed,
             rand_rgn,
+            auto_parameters,
+            shared_values,
+            parameter_values,
+            value_range,
+            script_errors,
         }
     }
 }
@@ -103,11 +157,31 @@ pub fn new_rhythm_from_file(
     time_base: BeatTimeBase,
     instrument: Option<InstrumentId>,
     file_name: &str,
+) -> Result<Rc<RefCell<dyn Rhythm>>, Box<dyn std::error::Error>> {
+    new_rhythm_from_file_with_policy(time_base, instrument, file_name, ValueRangePolicy::Strict)
+}
+
+/// Evaluate a lua script file which creates and returns a rhythm, applying the given policy
+/// to out of range values in the script and its callbacks. Clamped values get reported as
+/// the rhythm's warnings.
+///
+/// ### Errors
+/// Will return `Err` if `file_name` does not exist, failed to load or the lua file at the given
+/// path fails to evaulate to a valid rhythm.
+pub fn new_rhythm_from_file_with_policy(
+    time_base: BeatTimeBase,
+    instrument: Option<InstrumentId>,
+    file_name: &str,
+    policy: ValueRangePolicy,
 ) -> Result<Rc<RefCell<dyn Rhythm>>, Box<dyn std::error::Error>> {
     // create a new engine and register bindings
     let (mut lua, mut timeout_hook) =
         new_engine().map_err(Into::<Box<dyn std::error::Error>>::into)?;
     register_bindings(&mut lua, &timeout_hook, &time_base)?;
+    // apply the value range policy
+    let value_range = LuaValueRange::of_engine(&lua);
+    value_range.set_policy(policy);
+    let _scope = value_range.enter();
     // restart the timeout hook
     timeout_hook.reset();
     // compile and evaluate script
@@ -119,6 +193,9 @@ pub fn new_rhythm_from_file(
 
 /// Evaluate a Lua string expression which creates and returns a rhythm.
 ///
+/// Compiled scripts are cached by their content, so creating multiple rhythms from the same
+/// script only compiles the script once. Each rhythm still runs in its own Lua engine.
+///
 /// ### Errors
 /// Will return `Err` if the lua string contents fail to evaluate to a valid rhythm.
 pub fn new_rhythm_from_string(
@@ -126,7 +203,133 @@ pub fn new_rhythm_from_string(
     instrument: Option<InstrumentId>,
     script: &str,
     script_name: &str,
+) -> Result<Rc<RefCell<dyn Rhythm>>, Box<dyn std::error::Error>> {
+    new_rhythm_from_string_with_policy(
+        time_base,
+        instrument,
+        script,
+        script_name,
+        ValueRangePolicy::Strict,
+    )
+}
+
+/// Evaluate a Lua string expression which creates and returns a rhythm, applying the given
+/// policy to out of range values in the script and its callbacks. Clamped values get reported
+/// as the rhythm's warnings.
+///
+/// ### Errors
+/// Will return `Err` if the lua string contents fail to evaluate to a valid rhythm.
+pub fn new_rhythm_from_string_with_policy(
+    time_base: BeatTimeBase,
+    instrument: Option<InstrumentId>,
+    script: &str,
+    script_name: &str,
+    policy: ValueRangePolicy,
 ) -> Result<Rc<RefCell<dyn Rhythm>>, Box<dyn std::error::Error>> {
+    // create a new engine and register bindings
+    let (mut lua, mut timeout_hook) =
+        new_engine().map_err(Into::<Box<dyn std::error::Error>>::into)?;
+    register_bindings(&mut lua, &timeout_hook, &time_base)?;
+    // apply the value range policy
+    let value_range = LuaValueRange::of_engine(&lua);
+    value_range.set_policy(policy);
+    let _scope = value_range.enter();
+    // restart the timeout hook
+    timeout_hook.reset();
+    // compile or fetch cached bytecode and evaluate script
+    let bytecode = cached_script_bytecode(script, script_name)?;
+    let chunk = lua
+        .load(bytecode.as_slice())
+        .set_name(script_name)
+        .set_mode(mlua::ChunkMode::Binary);
+    let result = chunk.eval::<LuaValue>()?;
+    // convert result
+    rhythm_from_userdata(&result, instrument).map_err(Into::into)
+}
+
+/// Evaluate a Lua string expression which creates and returns a rhythm, lifting all numeric
+/// constants of the script's `rhythm{}` tables into [`AutoParameter`]S. See [`AutoParameter`]
+/// for how ids of scripts with multiple rhythm tables, e.g. chains, are scoped.
+///
+/// Passed parameter values override the script's constants with the same parameter id. Lifted
+/// parameters are part of the returned rhythm's parameters: setting new values via the rhythm's
+/// `set_parameter_value` re-evaluates the script with the new values, and the new rhythm then
+/// continues playing at the position of the old one. Compiled scripts are cached.
+///
+/// ### Errors
+/// Will return `Err` if the lua string contents fail to evaluate to a valid rhythm, or when
+/// lifted constants have ambiguous parameter ids.
+pub fn new_rhythm_from_string_with_auto_parameters(
+    time_base: BeatTimeBase,
+    instrument: Option<InstrumentId>,
+    script: &str,
+    script_name: &str,
+    parameter_values: &[(Cow<str>, f64)],
+) -> Result<(Rc<RefCell<dyn Rhythm>>, Vec<AutoParameter>), Box<dyn std::error::Error>> {
+    let (rhythm, parameters) = evaluate_rhythm_with_auto_parameters(
+        time_base,
+        instrument,
+        script,
+        script_name,
+        parameter_values,
+    )?;
+    let rhythm = AutoParameterRhythm::new(
+        time_base,
+        instrument,
+        script,
+        script_name,
+        &parameters,
+        rhythm,
+    );
+    Ok((Rc::new(RefCell::new(rhythm)), parameters))
+}
+
+fn evaluate_rhythm_with_auto_parameters(
+    time_base: BeatTimeBase,
+    instrument: Option<InstrumentId>,
+    script: &str,
+    script_name: &str,
+    parameter_values: &[(Cow<str>, f64)],
+) -> Result<(Rc<RefCell<dyn Rhythm>>, Vec<AutoParameter>), Box<dyn std::error::Error>> {
+    // create a new engine and register bindings
+    let (mut lua, mut timeout_hook) =
+        new_engine().map_err(Into::<Box<dyn std::error::Error>>::into)?;
+    register_bindings(&mut lua, &timeout_hook, &time_base)?;
+    // enable auto parameters
+    lua.app_data_mut::<LuaAppData>()
+        .expect("Failed to access Lua app data")
+        .auto_parameters = Some(AutoParameters::new(parameter_values));
+    // restart the timeout hook
+    timeout_hook.reset();
+    // compile or fetch cached bytecode and evaluate script
+    let bytecode = cached_script_bytecode(script, script_name)?;
+    let chunk = lua
+        .load(bytecode.as_slice())
+        .set_name(script_name)
+        .set_mode(mlua::ChunkMode::Binary);
+    let result = chunk.eval::<LuaValue>()?;
+    // convert result and fetch lifted parameters
+    let rhythm = rhythm_from_userdata(&result, instrument)?;
+    let parameters = lua
+        .app_data_mut::<LuaAppData>()
+        .expect("Failed to access Lua app data")
+        .auto_parameters
+        .take()
+        .map(AutoParameters::into_parameters)
+        .unwrap_or_default();
+    Ok((rhythm, parameters))
+}
+
+/// Evaluate a Lua string expression which creates and returns one or more performance macros:
+/// a `performance_macro{}` or an array of macros or macro definition tables.
+///
+/// ### Errors
+/// Will return `Err` if the lua string contents fail to evaluate to valid macros.
+pub fn new_performance_macros_from_string(
+    time_base: BeatTimeBase,
+    script: &str,
+    script_name: &str,
+) -> Result<Vec<PerformanceMacro>, Box<dyn std::error::Error>> {
     // create a new engine and register bindings
     let (mut lua, mut timeout_hook) =
         new_engine().map_err(Into::<Box<dyn std::error::Error>>::into)?;
@@ -137,7 +340,120 @@ pub fn new_rhythm_from_string(
     let chunk = lua.load(script).set_name(script_name);
     let result = chunk.eval::<LuaValue>()?;
     // convert result
-    rhythm_from_userdata(&result, instrument).map_err(Into::into)
+    performance_macros_from_value(&result).map_err(Into::into)
+}
+
+/// Clear the compiled script cache which is used by [`new_rhythm_from_string`].
+pub fn clear_rhythm_script_cache() {
+    SCRIPT_BYTECODE_CACHE
+        .lock()
+        .expect("Failed to access script cache")
+        .clear();
+}
+
+/// Estimated memory usage of the compiled script cache in bytes, see
+/// [`clear_rhythm_script_cache`].
+pub fn rhythm_script_cache_memory_usage() -> usize {
+    SCRIPT_BYTECODE_CACHE
+        .lock()
+        .expect("Failed to access script cache")
+        .memory_usage()
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Max number of compiled scripts in the script cache.
+const SCRIPT_CACHE_CAPACITY: usize = 64;
+
+struct CachedScript {
+    script: String,
+    script_name: String,
+    bytecode: Arc<Vec<u8>>,
+    last_used: u64,
+}
+
+/// Compiled script bytecode, keyed by the script's content and name hash. Sources are compared
+/// on lookups, so hash collisions don't return the wrong bytecode. When the cache is full, the
+/// least recently used script gets evicted.
+#[derive(Default)]
+struct ScriptBytecodeCache {
+    scripts: HashMap<u64, CachedScript>,
+    use_count: u64,
+}
+
+impl ScriptBytecodeCache {
+    fn key(script: &str, script_name: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        script.hash(&mut hasher);
+        script_name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn get(&mut self, script: &str, script_name: &str) -> Option<Arc<Vec<u8>>> {
+        self.use_count += 1;
+        let use_count = self.use_count;
+        self.scripts
+            .get_mut(&Self::key(script, script_name))
+            .filter(|cached| cached.script == script && cached.script_name == script_name)
+            .map(|cached| {
+                cached.last_used = use_count;
+                Arc::clone(&cached.bytecode)
+            })
+    }
+
+    fn insert(&mut self, script: &str, script_name: &str, bytecode: Arc<Vec<u8>>) {
+        let key = Self::key(script, script_name);
+        if !self.scripts.contains_key(&key) && self.scripts.len() >= SCRIPT_CACHE_CAPACITY {
+            let least_recently_used = self
+                .scripts
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| *key);
+            if let Some(key) = least_recently_used {
+                self.scripts.remove(&key);
+            }
+        }
+        self.use_count += 1;
+        self.scripts.insert(
+            key,
+            CachedScript {
+                script: script.to_string(),
+                script_name: script_name.to_string(),
+                bytecode,
+                last_used: self.use_count,
+            },
+        );
+    }
+
+    fn clear(&mut self) {
+        self.scripts.clear();
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.scripts
+            .values()
+            .map(|cached| cached.script.len() + cached.script_name.len() + cached.bytecode.len())
+            .sum()
+    }
+}
+
+lazy_static! {
+    static ref SCRIPT_BYTECODE_CACHE: Mutex<ScriptBytecodeCache> =
+        Mutex::new(ScriptBytecodeCache::default());
+}
+
+// Compile the given script or fetch its bytecode from the script cache.
+fn cached_script_bytecode(script: &str, script_name: &str) -> LuaResult<Arc<Vec<u8>>> {
+    let mut cache = SCRIPT_BYTECODE_CACHE
+        .lock()
+        .expect("Failed to access script cache");
+    if let Some(bytecode) = cache.get(script, script_name) {
+        Ok(bytecode)
+    } else {
+        let bytecode = Arc::new(compile_named_chunk(script, script_name)?);
+        cache.insert(script, script_name, Arc::clone(&bytecode));
+        Ok(bytecode)
+    }
 }
 
 // -------------------------------------------------------------------------------------------------
@@ -151,6 +467,7 @@ pub(crate) fn register_bindings(
 ) -> LuaResult<()> {
     register_global_bindings(lua, timeout_hook, time_base)?;
     register_math_bindings(lua)?;
+    register_context_bindings(lua)?;
     register_table_bindings(lua)?;
     register_pattern_module(lua)?;
     Ok(())
@@ -212,6 +529,16 @@ fn register_global_bindings(
         )?,
     )?;
 
+    // function groove(name|positions, length?)
+    globals.raw_set(
+        "groove",
+        lua.create_function(
+            |_lua, (value, length): (LuaValue, Option<LuaNumber>)| -> LuaResult<Groove> {
+                groove_from_values(&value, length)
+            },
+        )?,
+    )?;
+
     // function note(args...)
     globals.raw_set(
         "note",
@@ -230,6 +557,17 @@ fn register_global_bindings(
         )?,
     )?;
 
+    // function chain { rhythm, repeats?, ... }
+    globals.raw_set(
+        "chain",
+        lua.create_function({
+            let time_base = *time_base;
+            move |_lua, table: LuaTable| -> LuaResult<ChainRhythm> {
+                ChainRhythm::from_table(&time_base, &table)
+            }
+        })?,
+    )?;
+
     // function sequence(args...)
     globals.raw_set(
         "sequence",
@@ -238,56 +576,170 @@ fn register_global_bindings(
         })?,
     )?;
 
-    // function cycle(input)
+    // function cycle(input, options?)
     globals.raw_set(
         "cycle",
-        lua.create_function(|lua, arg: LuaString| -> LuaResult<CycleUserData> {
+        lua.create_function(
+            |lua, (arg, options): (LuaString, Option<LuaTable>)| -> LuaResult<CycleUserData> {
+                // NB: don't keep borrowing app_data_ref here
+                let rand_seed = {
+                    lua.app_data_ref::<LuaAppData>()
+                        .expect("Failed to access Lua app data")
+                        .rand_seed
+                };
+                CycleUserData::from(lua, arg, options, rand_seed)
+            },
+        )?,
+    )?;
+
+    // function performance_macro { name, value?, targets }
+    globals.raw_set(
+        "performance_macro",
+        lua.create_function(|_lua, table: LuaTable| -> LuaResult<PerformanceMacro> {
+            performance_macro_from_table(&table)
+        })?,
+    )?;
+
+    // function pool { entries... }
+    globals.raw_set(
+        "pool",
+        lua.create_function(|lua, table: LuaTable| -> LuaResult<PoolUserData> {
             // NB: don't keep borrowing app_data_ref here
             let rand_seed = {
                 lua.app_data_ref::<LuaAppData>()
                     .expect("Failed to access Lua app data")
                     .rand_seed
             };
-            CycleUserData::from(arg, rand_seed)
+            PoolUserData::from(table, rand_seed)
         })?,
     )?;
 
-    // function rhythm { args... }
+    // function rhythm { args... } or rhythm("registered.name")
     globals.raw_set(
         "rhythm",
         lua.create_function({
             let timeout_hook = timeout_hook.clone();
             let time_base = *time_base;
-            move |lua, table: LuaTable| -> LuaResult<LuaValue> {
+            move |lua, value: LuaValue| -> LuaResult<LuaValue> {
+                // create registered rhythm implementations by name
+                let table = match value {
+                    LuaValue::String(name) => {
+                        let rand_seed = lua
+                            .app_data_ref::<LuaAppData>()
+                            .expect("Failed to access Lua app data")
+                            .rand_seed;
+                        return RegisteredRhythm::from_name(
+                            &time_base,
+                            &name.to_string_lossy(),
+                            rand_seed,
+                        )?
+                        .into_lua(lua);
+                    }
+                    value => LuaTable::from_lua(value, lua)?,
+                };
                 // error on unknown option keys
-                const RHYTHM_PROPERTIES: [&str; 7] = [
+                const RHYTHM_PROPERTIES: [&str; 18] = [
                     "unit",
                     "resolution",
                     "offset",
                     "pattern",
                     "gate",
                     "repeats",
+                    "groove",
+                    "humanize",
+                    "panning",
+                    "echo",
+                    "transpose",
+                    "degree_shift",
+                    "relative_notes",
+                    "seed_morph",
+                    "pulse_volume",
                     "emit",
+                    "parameters",
+                    "seed",
                 ];
                 validate_table_properties(&table, &RHYTHM_PROPERTIES)?;
+                // parse explicitly declared parameters
+                let mut declared_parameters = match table.get::<_, LuaValue>("parameters")? {
+                    LuaValue::Nil => Vec::new(),
+                    LuaValue::Table(parameters) => parameters_from_table(&parameters)?,
+                    value => {
+                        return Err(LuaError::runtime(format!(
+                            "rhythm 'parameters' must be a table, but is a '{}'",
+                            value.type_name()
+                        )))
+                    }
+                };
+                // lift numeric constants into auto parameters, when enabled
+                let auto_parameters = {
+                    lua.app_data_mut::<LuaAppData>()
+                        .expect("Failed to access Lua app data")
+                        .auto_parameters
+                        .take()
+                };
+                let mut parameters = Vec::new();
+                if let Some(mut auto_parameters) = auto_parameters {
+                    let result = auto_parameters.lift(&table);
+                    lua.app_data_mut::<LuaAppData>()
+                        .expect("Failed to access Lua app data")
+                        .auto_parameters = Some(auto_parameters);
+                    parameters = result?;
+                    // apply overridden values to declared parameters too
+                    let auto_parameters = lua
+                        .app_data_ref::<LuaAppData>()
+                        .expect("Failed to access Lua app data");
+                    if let Some(auto_parameters) = &auto_parameters.auto_parameters {
+                        for parameter in declared_parameters.iter_mut() {
+                            if let Some(value) = auto_parameters.value_override(parameter.id()) {
+                                *parameter = parameter.clone().with_value(value);
+                            }
+                        }
+                    }
+                }
+                parameters.retain(|parameter| {
+                    !declared_parameters
+                        .iter()
+                        .any(|declared| declared.id() == parameter.id())
+                });
+                parameters.extend(declared_parameters);
+                // pass parameter values to all callbacks of the new rhythm
+                let parameter_values = RhythmParameterValues::new(parameters);
+                lua.app_data_mut::<LuaAppData>()
+                    .expect("Failed to access Lua app data")
+                    .parameter_values = Some(parameter_values.clone());
                 // check which time unit is specified
                 let second_time_unit = match table.get::<&str, String>("unit") {
                     Ok(unit) => matches!(unit.as_str(), "seconds" | "ms"),
                     Err(_) => false,
                 };
-                // NB: don't keep borrowing app_data_ref here: Rhytm constructos may use random functions
-                let rand_seed = {
-                    lua.app_data_ref::<LuaAppData>()
-                        .expect("Failed to access Lua app data")
-                        .rand_seed
+                // use the rhythm's own seed or the global seed
+                let rand_seed = match table.get::<_, LuaValue>("seed")? {
+                    LuaValue::Nil => {
+                        // NB: don't keep borrowing app_data_ref here: Rhytm constructos may use random functions
+                        lua.app_data_ref::<LuaAppData>()
+                            .expect("Failed to access Lua app data")
+                            .rand_seed
+                    }
+                    value => {
+                        let seed = value.as_f64().ok_or_else(|| {
+                            bad_argument_error("rhythm", "seed", 1, "seed must be a number")
+                        })?;
+                        Some(seed_from_number(seed))
+                    }
                 };
-                if second_time_unit {
-                    SecondTimeRhythm::from_table(lua, &timeout_hook, &time_base, &table, rand_seed)?
-                        .into_lua(lua)
+                let result = if second_time_unit {
+                    SecondTimeRhythm::from_table(lua, &timeout_hook, &time_base, &table, rand_seed)
+                        .map(|rhythm| rhythm.with_parameter_values(parameter_values))
+                        .and_then(|rhythm| rhythm.into_lua(lua))
                 } else {
-                    BeatTimeRhythm::from_table(lua, &timeout_hook, &time_base, &table, rand_seed)?
-                        .into_lua(lua)
-                }
+                    BeatTimeRhythm::from_table(lua, &timeout_hook, &time_base, &table, rand_seed)
+                        .map(|rhythm| rhythm.with_parameter_values(parameter_values))
+                        .and_then(|rhythm| rhythm.into_lua(lua))
+                };
+                lua.app_data_mut::<LuaAppData>()
+                    .expect("Failed to access Lua app data")
+                    .parameter_values = None;
+                result
             }
         })?,
     )?;
@@ -392,11 +844,7 @@ fn register_math_bindings(lua: &mut Lua) -> LuaResult<()> {
     math.raw_set(
         "randomseed",
         lua.create_function(|lua, arg: LuaNumber| -> LuaResult<()> {
-            let bytes = arg.to_le_bytes();
-            let mut new_seed = [0; 32];
-            for i in 0..32 {
-                new_seed[i] = bytes[i % 8];
-            }
+            let new_seed = seed_from_number(arg);
             let mut app_data = lua
                 .app_data_mut::<LuaAppData>()
                 .expect("Failed to access Lua app data");
@@ -409,6 +857,63 @@ fn register_math_bindings(lua: &mut Lua) -> LuaResult<()> {
     Ok(())
 }
 
+fn register_context_bindings(lua: &mut Lua) -> LuaResult<()> {
+    // time conversion helpers of callback contexts, which use the context's current time base
+    type Conversion = fn(&BeatTimeBase, f64) -> f64;
+    let conversions: [(&str, Conversion); 6] = [
+        ("beats_to_seconds", BeatTimeBase::beats_to_seconds),
+        ("seconds_to_beats", BeatTimeBase::seconds_to_beats),
+        ("beats_to_samples", BeatTimeBase::beats_to_samples),
+        ("samples_to_beats", BeatTimeBase::samples_to_beats),
+        ("seconds_to_samples", |time_base, seconds| {
+            seconds * time_base.samples_per_sec as f64
+        }),
+        ("samples_to_seconds", |time_base, samples| {
+            samples / time_base.samples_per_sec as f64
+        }),
+    ];
+    let helpers = lua.create_table()?;
+    for (name, conversion) in conversions {
+        // function context:beats_to_seconds(value) and friends
+        helpers.raw_set(
+            name,
+            lua.create_function(
+                move |_lua, (context, value): (LuaTable, f64)| -> LuaResult<f64> {
+                    let time_base = BeatTimeBase {
+                        beats_per_min: context.raw_get("beats_per_min")?,
+                        beats_per_bar: context.raw_get("beats_per_bar")?,
+                        samples_per_sec: context.raw_get("samples_per_sec")?,
+                    };
+                    Ok(conversion(&time_base, value))
+                },
+            )?,
+        )?;
+    }
+    // function context:set_parameter(id, value)
+    helpers.raw_set(
+        "set_parameter",
+        lua.create_function(
+            |_lua, (context, id, value): (LuaTable, String, f64)| -> LuaResult<f64> {
+                let parameters = context
+                    .raw_get::<_, Option<LuaAnyUserData>>("parameters")?
+                    .ok_or_else(|| {
+                        LuaError::runtime(format!(
+                            "can't set parameter '{}': the rhythm has no parameters",
+                            id
+                        ))
+                    })?;
+                let parameters = parameters.borrow::<RhythmParameterValues>()?;
+                parameters
+                    .set_value(&id, value)
+                    .map_err(LuaError::RuntimeError)
+            },
+        )?,
+    )?;
+    let metatable = lua.create_table()?;
+    metatable.raw_set("__index", helpers)?;
+    lua.set_named_registry_value(CONTEXT_METATABLE, metatable)
+}
+
 fn register_table_bindings(lua: &mut Lua) -> LuaResult<()> {
     // cache module bytecode to speed up initialization
     lazy_static! {
@@ -459,11 +964,27 @@ fn compile_chunk(chunk: &'static str) -> LuaResult<Vec<u8>> {
     Ok(mlua::Compiler::new().compile(chunk))
 }
 
+#[cfg(any(feature = "lua", feature = "lua-jit"))]
+fn compile_named_chunk(chunk: &str, name: &str) -> LuaResult<Vec<u8>> {
+    let strip = false;
+    Lua::new_with(LuaStdLib::NONE, LuaOptions::default())?
+        .load(chunk)
+        .set_name(name)
+        .into_function()
+        .map(|x| x.dump(strip))
+}
+
+#[cfg(any(feature = "luau", feature = "luau-jit"))]
+fn compile_named_chunk(chunk: &str, _name: &str) -> LuaResult<Vec<u8>> {
+    Ok(mlua::Compiler::new().compile(chunk))
+}
+
 // --------------------------------------------------------------------------------------------------
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{event::NoteEvent, pattern::euclidean::euclidean_accents, Event, Note, RhythmIter};
 
     #[test]
     fn extensions() -> LuaResult<()> {
@@ -491,6 +1012,12 @@ mod test {
             .load(r#"return pattern.new()"#)
             .eval::<LuaTable>()
             .is_ok());
+        assert_eq!(
+            lua.load(r#"return pattern.euclidean_accents(5, 8, 2, 3)"#)
+                .eval::<Vec<f32>>()
+                .unwrap(),
+            euclidean_accents(5, 8, 0, 2, 3, 0.5)
+        );
 
         // timeout hook is installed and does its job
         assert!(lua
@@ -519,4 +1046,129 @@ mod test {
             .is_ok());
         Ok(())
     }
+
+    #[test]
+    fn script_cache() -> Result<(), Box<dyn std::error::Error>> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        let script = r#"return rhythm { pattern = {1, 0}, emit = "c4" }"#;
+
+        // bytecode is shared across instances
+        let bytecode = cached_script_bytecode(script, "cached_script")?;
+        assert!(Arc::ptr_eq(
+            &bytecode,
+            &cached_script_bytecode(script, "cached_script")?
+        ));
+        assert!(!Arc::ptr_eq(
+            &bytecode,
+            &cached_script_bytecode(script, "other_script")?
+        ));
+
+        // but state is not
+        let rhythm1 = new_rhythm_from_string(time_base, None, script, "cached_script")?;
+        let rhythm2 = new_rhythm_from_string(time_base, None, script, "cached_script")?;
+        let event = rhythm1.borrow_mut().run();
+        assert!(event.is_some());
+        assert_eq!(event, rhythm2.borrow_mut().run());
+
+        // errors are not cached
+        assert!(new_rhythm_from_string(time_base, None, "return rhythm {", "invalid").is_err());
+        assert!(new_rhythm_from_string(time_base, None, "return rhythm {", "invalid").is_err());
+
+        // cached bytecode is reported
+        assert!(rhythm_script_cache_memory_usage() >= bytecode.len());
+
+        // least recently used scripts get evicted
+        let mut cache = ScriptBytecodeCache::default();
+        let first = Arc::new(vec![0]);
+        cache.insert("first", "script", Arc::clone(&first));
+        cache.insert("second", "script", Arc::new(vec![1]));
+        for index in 0..SCRIPT_CACHE_CAPACITY - 2 {
+            cache.insert(&index.to_string(), "script", Arc::new(vec![2]));
+        }
+        assert!(cache.get("first", "script").is_some());
+        cache.insert("overflow", "script", Arc::new(vec![3]));
+        assert_eq!(cache.scripts.len(), SCRIPT_CACHE_CAPACITY);
+        assert!(cache.get("second", "script").is_none());
+        assert!(Arc::ptr_eq(&cache.get("first", "script").unwrap(), &first));
+        // sources are compared along with hashes
+        let key = ScriptBytecodeCache::key("first", "script");
+        cache.scripts.get_mut(&key).unwrap().script = "other".to_string();
+        assert!(cache.get("first", "script").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn memory_usage() -> Result<(), Box<dyn std::error::Error>> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        let script = r#"
+          return rhythm {
+            pattern = function(context)
+              local garbage = {}
+              for i = 1, 1000 do garbage[i] = tostring(i) end
+              return 1
+            end,
+            emit = cycle("c4 [e4 g4]")
+          }"#;
+        let rhythm = new_rhythm_from_string(time_base, None, script, "memory_usage")?;
+        for _ in 0..16 {
+            rhythm.borrow_mut().run();
+        }
+        let usage = rhythm.borrow().memory_usage();
+        assert!(usage.lua_heap > 0);
+        assert!(usage.cycles > 0);
+        // trimming collects the Lua engine's garbage
+        rhythm.borrow_mut().trim_memory();
+        assert!(rhythm.borrow().memory_usage().lua_heap < usage.lua_heap);
+        Ok(())
+    }
+
+    #[test]
+    fn value_range_policy() -> Result<(), Box<dyn std::error::Error>> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        let script = r#"
+          return rhythm {
+            emit = function(context)
+              return { key = 200, volume = 2.0 }
+            end
+          }"#;
+        let new_rhythm =
+            |policy| new_rhythm_from_string_with_policy(time_base, None, script, "policy", policy);
+        // rhythms apply their own policies
+        let strict_rhythm = new_rhythm(ValueRangePolicy::Strict)?;
+        let clamped_rhythm = new_rhythm(ValueRangePolicy::Clamp)?;
+        assert_eq!(strict_rhythm.borrow_mut().run().unwrap().event, None);
+        let mut note_event = NoteEvent::from(Note::from(0x7f_u8));
+        note_event.volume = 1.0;
+        assert_eq!(
+            clamped_rhythm.borrow_mut().run().unwrap().event,
+            Some(Event::NoteEvents(vec![Some(note_event)]))
+        );
+        // clamped values get reported as the rhythm's warnings
+        assert!(strict_rhythm.borrow_mut().take_warnings().is_empty());
+        assert_eq!(
+            clamped_rhythm
+                .borrow_mut()
+                .take_warnings()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "clamped: volume value '2' got clamped to '1'",
+                "clamped: note value '200' got clamped to '127'"
+            ]
+        );
+        Ok(())
+    }
 }
@@ -1,11 +1,28 @@
-use std::{borrow::Cow, fmt::Debug};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    fmt::Debug,
+    rc::Rc,
+};
 
 use mlua::prelude::*;
 
 use lazy_static::lazy_static;
 use std::sync::RwLock;
 
-use crate::{time::BeatTimeBase, PulseIterItem};
+use crate::{
+    bindings::{LuaAppData, LuaValueRange},
+    parameter::RhythmParameterValues,
+    shared::SharedValues,
+    tidal::Target as CycleTarget,
+    time::BeatTimeBase,
+    PulseIterItem,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Name of the Lua registry value which holds the metatable of all callback contexts.
+pub(crate) const CONTEXT_METATABLE: &str = "afseq_context_metatable";
 
 // -------------------------------------------------------------------------------------------------
 
@@ -16,6 +33,10 @@ lazy_static! {
 /// Returns some error if there are any Lua callback errors, with the !first! error that happened.
 /// Use `lua_callback_errors` to get fetch all errors since the errors got cleared.
 ///
+/// To get notified about errors instead of polling them, subscribe to
+/// [`NotificationKind::ScriptError`](crate::notification::NotificationKind::ScriptError)
+/// notifications of a sequence's notification bus.
+///
 /// ### Panics
 /// Panics if accessing the global lua callback error vector fails.
 pub fn has_lua_callback_errors() -> Option<LuaError> {
@@ -63,8 +84,52 @@ pub fn add_lua_callback_error(name: &str, err: &LuaError) {
 
 // -------------------------------------------------------------------------------------------------
 
+/// Maximum number of pending errors in [`LuaScriptErrors`]. Further errors get ignored until
+/// the pending errors got taken, so engines of rhythms which run without a sequence can't grow
+/// their errors without bounds.
+const MAX_PENDING_SCRIPT_ERRORS: usize = 256;
+
+/// Callback errors of a single Lua engine, until they get taken by the engine's rhythms, e.g. to
+/// publish them in the rhythm's sequence only. Cloned instances share the same errors.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LuaScriptErrors {
+    pending: Rc<RefCell<Vec<String>>>,
+}
+
+impl LuaScriptErrors {
+    /// Access the script errors of the given engine.
+    pub fn of_engine(lua: &Lua) -> Self {
+        lua.app_data_ref::<LuaAppData>()
+            .map(|app_data| app_data.script_errors.clone())
+            .unwrap_or_default()
+    }
+
+    /// Log the given error and add it to the engine's and the global callback errors.
+    pub fn add(&self, name: &str, err: &LuaError) {
+        add_lua_callback_error(name, err);
+        let mut pending = self.pending.borrow_mut();
+        if pending.len() < MAX_PENDING_SCRIPT_ERRORS {
+            pending.push(err.to_string());
+        }
+    }
+
+    /// Fetch and clear all pending errors of the engine.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut self.pending.borrow_mut())
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
 /// Lazily evaluates a lua function the first time it's called, to either use it as a iterator,
-/// a function which returns a function, or directly as it is.
+/// a function which returns a function, a function which returns a coroutine, or directly as
+/// it is.
+///
+/// Coroutines get resumed with the context on each call and should yield their values, so
+/// they can keep their local state between calls without closing over external tables. When
+/// the coroutine finished, nil is returned. Resuming a finished coroutine gets reported as error
+/// once, so scripts notice that their generator ended. Failed coroutines only report their
+/// failure.
 ///
 /// When calling the function the signature of the function is `fn(context): LuaResult`;
 /// The passed context is created as an empty table with the callback, and should be filled up
@@ -83,8 +148,16 @@ pub fn add_lua_callback_error(name: &str, err: &LuaError) {
 pub(crate) struct LuaCallback {
     environment: Option<LuaOwnedTable>,
     context: LuaOwnedTable,
+    shared_values: Option<LuaOwnedAnyUserData>,
+    parameter_values: Option<LuaOwnedAnyUserData>,
     generator: Option<LuaOwnedFunction>,
     function: LuaOwnedFunction,
+    thread: Option<LuaOwnedThread>,
+    thread_finished: Cell<bool>,
+    memory_function: LuaOwnedFunction,
+    context_copy_function: LuaOwnedFunction,
+    value_range: LuaValueRange,
+    script_errors: LuaScriptErrors,
     initialized: bool,
 }
 
@@ -97,17 +170,75 @@ impl LuaCallback {
     /// Create a new Callback from an owned lua function.
     pub fn with_owned(lua: &Lua, function: LuaOwnedFunction) -> LuaResult<Self> {
         // create an empty context and memorize the function without calling it
-        let context = lua.create_table()?.into_owned();
+        let context = lua.create_table()?;
+        // add time conversion helpers to the context, when they got registered
+        if let Ok(metatable) = lua.named_registry_value::<LuaTable>(CONTEXT_METATABLE) {
+            context.set_metatable(Some(metatable));
+        }
+        let context = context.into_owned();
         let environment = function.to_ref().environment().map(LuaTable::into_owned);
+        let shared_values = None;
+        let parameter_values = None;
         let generator = None;
+        let thread = None;
+        let thread_finished = Cell::new(false);
+        // memory diagnostics for the callback's engine: optionally collects garbage first
+        let memory_function = lua
+            .create_function(|lua, collect_garbage: bool| {
+                if collect_garbage {
+                    lua.gc_collect()?;
+                }
+                Ok(lua.used_memory())
+            })?
+            .into_owned();
+        // creates copies of contexts in the callback's engine, for duplicates
+        let context_copy_function = lua
+            .create_function(|lua, context: LuaTable| {
+                let copy = lua.create_table()?;
+                for pair in context.clone().pairs::<LuaValue, LuaValue>() {
+                    let (key, value) = pair?;
+                    copy.raw_set(key, value)?;
+                }
+                copy.set_metatable(context.get_metatable());
+                Ok(copy)
+            })?
+            .into_owned();
+        let value_range = LuaValueRange::of_engine(lua);
+        let script_errors = LuaScriptErrors::of_engine(lua);
         let initialized = false;
-        Ok(Self {
+        let mut callback = Self {
             environment,
             context,
+            shared_values,
+            parameter_values,
             generator,
             function,
+            thread,
+            thread_finished,
+            memory_function,
+            context_copy_function,
+            value_range,
+            script_errors,
             initialized,
-        })
+        };
+        // use the engine's shared values, until a sequence passes its own values
+        let (shared_values, parameter_values) = lua
+            .app_data_ref::<LuaAppData>()
+            .map(|app_data| {
+                (
+                    Some(app_data.shared_values.clone()),
+                    app_data.parameter_values.clone(),
+                )
+            })
+            .unwrap_or_default();
+        if let Some(shared_values) = shared_values {
+            callback.set_context_shared_values(&shared_values)?;
+        }
+        // use the parameters of the rhythm which currently gets created
+        if let Some(parameter_values) = parameter_values {
+            callback.set_context_parameter_values(&parameter_values)?;
+        }
+        Ok(callback)
     }
 
     /// Sets the emitter time base context for the callback.
@@ -128,6 +259,31 @@ impl LuaCallback {
         Ok(())
     }
 
+    /// Sets the sequence's shared values for the callback.
+    pub fn set_context_shared_values(&mut self, values: &SharedValues) -> LuaResult<()> {
+        let table = self.context.to_ref();
+        table.raw_set("shared", values.clone())?;
+        // memorize the values: duplicated callbacks share their context table
+        let shared_values = table.raw_get::<_, LuaAnyUserData>("shared")?.into_owned();
+        self.shared_values = Some(shared_values);
+        Ok(())
+    }
+
+    /// Sets the rhythm's parameter values for the callback.
+    pub fn set_context_parameter_values(
+        &mut self,
+        values: &RhythmParameterValues,
+    ) -> LuaResult<()> {
+        let table = self.context.to_ref();
+        table.raw_set("parameters", values.clone())?;
+        // memorize the values: duplicated callbacks share their context table
+        let parameter_values = table
+            .raw_get::<_, LuaAnyUserData>("parameters")?
+            .into_owned();
+        self.parameter_values = Some(parameter_values);
+        Ok(())
+    }
+
     /// Sets the pulse value emitter context for the callback.
     pub fn set_context_pulse_value(&mut self, pulse: PulseIterItem) -> LuaResult<()> {
         let table = self.context.to_ref();
@@ -169,6 +325,22 @@ impl LuaCallback {
         Ok(())
     }
 
+    /// Sets the cycle context values of the cycle event which gets mapped next.
+    pub fn set_context_cycle_event(
+        &mut self,
+        step_start: f64,
+        target: &CycleTarget,
+    ) -> LuaResult<()> {
+        let table = self.context.to_ref();
+        table.raw_set("step_start", step_start)?;
+        match target {
+            CycleTarget::None => table.raw_set("target", LuaValue::Nil)?,
+            CycleTarget::Index(index) => table.raw_set("target", *index)?,
+            CycleTarget::Name(name) => table.raw_set("target", name.as_ref())?,
+        }
+        Ok(())
+    }
+
     /// Sets the emitter context for the callback.
     pub fn set_pattern_context(
         &mut self,
@@ -230,6 +402,31 @@ impl LuaCallback {
             .unwrap_or("annonymous function".to_string())
     }
 
+    /// Current heap size of the callback's Lua engine in bytes. The engine may be shared with
+    /// other callbacks.
+    pub fn used_memory(&self) -> usize {
+        self.memory_function.call::<_, usize>(false).unwrap_or(0)
+    }
+
+    /// Run a full garbage collection cycle in the callback's Lua engine.
+    pub fn collect_garbage(&self) {
+        if let Err(err) = self.memory_function.call::<_, usize>(true) {
+            log::warn!("Failed to collect Lua garbage: {}", err);
+        }
+    }
+
+    /// Value range policy and warnings of the callback's engine, which may be shared with other
+    /// callbacks. Callback calls apply the policy automatically.
+    pub fn value_range(&self) -> &LuaValueRange {
+        &self.value_range
+    }
+
+    /// Errors of the callback's engine, which may be shared with other callbacks. Errors get
+    /// added via [`handle_error`](Self::handle_error).
+    pub fn script_errors(&self) -> &LuaScriptErrors {
+        &self.script_errors
+    }
+
     /// Invoke the Lua function callback or generator.
     pub fn call(&mut self) -> LuaResult<LuaValue> {
         self.call_with_arg(LuaValue::Nil)
@@ -240,8 +437,37 @@ impl LuaCallback {
         &'lua mut self,
         arg: A,
     ) -> LuaResult<LuaValue<'lua>> {
+        let _scope = self.value_range.enter();
+        #[cfg(feature = "profiling")]
+        {
+            crate::profiling::measure_callback(move || self.invoke_with_arg(arg))
+        }
+        #[cfg(not(feature = "profiling"))]
+        {
+            self.invoke_with_arg(arg)
+        }
+    }
+
+    fn invoke_with_arg<'lua, A: IntoLua<'lua> + Clone>(
+        &'lua mut self,
+        arg: A,
+    ) -> LuaResult<LuaValue<'lua>> {
+        if let Some(shared_values) = &self.shared_values {
+            self.context
+                .to_ref()
+                .raw_set("shared", shared_values.to_ref())?;
+        }
+        if let Some(parameter_values) = &self.parameter_values {
+            self.context
+                .to_ref()
+                .raw_set("parameters", parameter_values.to_ref())?;
+        }
         if self.initialized {
-            self.function.call((self.context.to_ref(), arg))
+            if self.thread.is_some() {
+                self.resume_thread(arg)
+            } else {
+                self.function.call((self.context.to_ref(), arg))
+            }
         } else {
             self.initialized = true;
             let result = {
@@ -259,21 +485,108 @@ impl LuaCallback {
                     .map(LuaTable::into_owned);
                 self.environment = environment;
                 self.generator = Some(std::mem::replace(&mut self.function, inner_function));
+                self.thread = None;
                 self.function
                     .call::<_, LuaValue>((self.context.to_ref(), arg))
+            } else if let Some(thread) = result.as_thread().cloned().map(|t| t.into_owned()) {
+                // function returned a coroutine -> is a generator. resume the coroutine instead.
+                let environment = self
+                    .function
+                    .to_ref()
+                    .environment()
+                    .map(LuaTable::into_owned);
+                self.environment = environment;
+                self.generator = Some(self.function.clone());
+                self.thread = Some(thread);
+                self.thread_finished.set(false);
+                self.resume_thread(arg)
             } else {
                 // function returned some value. use this function directly.
                 self.environment = None;
                 self.generator = None;
+                self.thread = None;
                 Ok(result)
             }
         }
     }
 
-    /// Report a Lua callback errors. The error will be logged and usually cleared after
-    /// the next callback call.
+    // Resume the callback's coroutine with the context and argument. Returns nil when the
+    // coroutine finished or failed before, and an error the first time a finished coroutine
+    // gets resumed.
+    fn resume_thread<'lua, A: IntoLua<'lua>>(&'lua self, arg: A) -> LuaResult<LuaValue<'lua>> {
+        let thread = self
+            .thread
+            .as_ref()
+            .expect("Expecting a coroutine")
+            .to_ref();
+        if thread.status() != LuaThreadStatus::Resumable {
+            if self.thread_finished.replace(true) {
+                return Ok(LuaValue::Nil);
+            }
+            return Err(LuaError::runtime(format!(
+                "Coroutine of custom generator function '{}' finished: \
+                 it no longer generates values until it gets reset",
+                self.name()
+            )));
+        }
+        let result = thread.resume((self.context.to_ref(), arg));
+        if result.is_err() {
+            // failed coroutines are dead, but got reported with this error already
+            self.thread_finished.set(true);
+        }
+        result
+    }
+
+    /// Report a Lua callback error. The error gets logged and added to the engine's errors,
+    /// which the callback's rhythm passes to its sequence, and to the global callback errors.
     pub fn handle_error(&self, err: &LuaError) {
-        add_lua_callback_error(&self.name(), err)
+        self.script_errors.add(&self.name(), err)
+    }
+
+    /// Create a deep copy of the callback, which doesn't share its context and generator state
+    /// with this callback: `clone` shares both. Generators get called again with the copy's
+    /// own context, so duplicated generator functions start from their initial state. Upvalues
+    /// of plain functions are still shared. Coroutines can't be copied, so duplicating the
+    /// callback fails when it got initialized with a coroutine.
+    pub fn duplicate(&self) -> LuaResult<Self> {
+        if self.thread.is_some() {
+            return Err(LuaError::runtime(format!(
+                "Failed to duplicate custom generator function '{}': \
+                 the state of coroutines can't be duplicated",
+                self.name()
+            )));
+        }
+        let context = self
+            .context_copy_function
+            .call::<_, LuaTable>(self.context.to_ref())?
+            .into_owned();
+        let mut duplicate = Self {
+            context,
+            ..self.clone()
+        };
+        if self.initialized {
+            if let Some(function_generator) = &self.generator {
+                // restore generator environment
+                if let Some(env) = &self.environment {
+                    function_generator.to_ref().set_environment(env.to_ref())?;
+                }
+                // then fetch a new fresh function from the generator with the new context
+                let value = function_generator
+                    .to_ref()
+                    .call::<_, LuaValue>(duplicate.context.to_ref())?;
+                if let Some(function) = value.as_function() {
+                    duplicate.function = function.clone().into_owned();
+                } else {
+                    return Err(LuaError::runtime(format!(
+                        "Failed to duplicate custom generator function '{}' \
+                         Expected a function as return value, got a '{}'",
+                        self.name(),
+                        value.type_name()
+                    )));
+                }
+            }
+        }
+        Ok(duplicate)
     }
 
     /// Reset the callback function or iterator to its initial state.
@@ -285,11 +598,23 @@ impl LuaCallback {
                 if let Some(env) = &self.environment {
                     function_generator.to_ref().set_environment(env.to_ref())?;
                 }
-                // then fetch a new fresh function from the generator
+                // then fetch a new fresh function or coroutine from the generator
                 let value = function_generator
                     .to_ref()
                     .call::<_, LuaValue>(self.context.to_ref())?;
-                if let Some(function) = value.as_function() {
+                if self.thread.is_some() {
+                    if let Some(thread) = value.as_thread() {
+                        self.thread = Some(thread.clone().into_owned());
+                        self.thread_finished.set(false);
+                    } else {
+                        return Err(LuaError::runtime(format!(
+                            "Failed to reset custom generator function '{}' \
+                             Expected a coroutine as return value, got a '{}'",
+                            self.name(),
+                            value.type_name()
+                        )));
+                    }
+                } else if let Some(function) = value.as_function() {
                     self.function = function.clone().into_owned();
                 } else {
                     return Err(LuaError::runtime(format!(
@@ -312,7 +637,13 @@ mod test {
     use std::borrow::BorrowMut;
 
     use super::*;
-    use crate::{bindings::*, Event, Note, RhythmIterItem};
+    use crate::{
+        bindings::*,
+        notification::{Notification, NotificationKind},
+        phrase::RhythmSlot,
+        time::BeatTimeStep,
+        Event, Note, Phrase, RhythmIterItem, Sequence,
+    };
 
     fn new_test_engine(
         beats_per_min: f32,
@@ -395,4 +726,265 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn coroutines() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        let function = lua
+            .load(
+                r#"
+                return function(context)
+                  return coroutine.create(function(context, arg)
+                    local step = 0
+                    while step < 3 do
+                      step = step + 1
+                      _, arg = coroutine.yield(step * 10 + arg)
+                    end
+                  end)
+                end
+            "#,
+            )
+            .eval::<LuaFunction>()?;
+        let mut callback = LuaCallback::new(&lua, function)?;
+        let values = |callback: &mut LuaCallback| -> LuaResult<Vec<Option<LuaInteger>>> {
+            (1..=4)
+                .map(|arg| Ok(callback.call_with_arg(arg)?.as_integer()))
+                .collect()
+        };
+        // finished coroutines return nil
+        assert_eq!(
+            values(&mut callback)?,
+            vec![Some(11), Some(22), Some(33), None]
+        );
+        // and get reported once
+        assert!(callback.call().is_err());
+        assert!(callback.call()?.is_nil());
+        // resets restart the coroutine
+        callback.reset()?;
+        assert_eq!(
+            values(&mut callback)?,
+            vec![Some(11), Some(22), Some(33), None]
+        );
+
+        // errors in coroutines are reported
+        let function = lua
+            .load(
+                r#"
+                return function(context)
+                  return coroutine.create(function(context)
+                    coroutine.yield(1)
+                    error("failed")
+                  end)
+                end
+            "#,
+            )
+            .eval::<LuaFunction>()?;
+        let mut callback = LuaCallback::new(&lua, function)?;
+        assert_eq!(callback.call()?.as_integer(), Some(1));
+        assert!(callback.call().is_err());
+        assert!(callback.call()?.is_nil());
+        Ok(())
+    }
+
+    #[test]
+    fn coroutine_timeouts() -> LuaResult<()> {
+        let (lua, mut timeout_hook) = new_test_engine(120.0, 4, 44100)?;
+
+        let function = lua
+            .load(
+                r#"
+                return function(context)
+                  return coroutine.create(function(context)
+                    coroutine.yield(1)
+                    local i = 0
+                    while true do
+                      i = i + 1
+                    end
+                  end)
+                end
+            "#,
+            )
+            .eval::<LuaFunction>()?;
+        let mut callback = LuaCallback::new(&lua, function)?;
+        timeout_hook.reset();
+        assert_eq!(callback.call()?.as_integer(), Some(1));
+        // the timeout hook also aborts never ending loops in coroutines
+        timeout_hook.reset();
+        assert!(callback
+            .call()
+            .is_err_and(|err| err.to_string().contains("Script timeout")));
+        // which are dead then, without getting reported again
+        assert!(callback.call()?.is_nil());
+        Ok(())
+    }
+
+    #[test]
+    fn duplicates() -> LuaResult<()> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let new_sequence = |script: &str| -> LuaResult<Sequence> {
+            let rhythm = new_rhythm_from_string(time_base, None, script, "[duplicates]")
+                .map_err(|err| LuaError::runtime(err.to_string()))?;
+            Ok(Sequence::new(
+                time_base,
+                vec![Phrase::new(
+                    time_base,
+                    vec![RhythmSlot::from(rhythm)],
+                    BeatTimeStep::Bar(1.0),
+                )],
+            ))
+        };
+        let run = |sequence: &mut Sequence, run_until_time| {
+            let mut notes = Vec::new();
+            sequence.consume_events_until_time(run_until_time, &mut |_, _, event, _| {
+                if let Some(Event::NoteEvents(note_events)) = event {
+                    notes.extend(note_events.into_iter().flatten().map(|event| event.note));
+                }
+            });
+            notes
+        };
+
+        // generators get called again for duplicates
+        let script = r#"
+            return rhythm {
+                unit = "1/4",
+                emit = function(context)
+                  local notes = {"c4", "d4", "e4", "f4", "g4"}
+                  local step = 0
+                  return function(context)
+                    step = step + 1
+                    return notes[(step - 1) % #notes + 1]
+                  end
+                end
+            }
+        "#;
+        let mut sequence = new_sequence(script)?;
+        let mut reference = new_sequence(script)?;
+        assert_eq!(run(&mut sequence, 1500), run(&mut reference, 1500));
+        let mut duplicate = sequence.duplicate().map_err(LuaError::runtime)?;
+        let duplicate_notes = run(&mut duplicate, 3000);
+        // running duplicates does not change the original's output
+        let notes = run(&mut sequence, 3000);
+        assert_eq!(notes, run(&mut reference, 3000));
+        assert_eq!(run(&mut sequence, 6000), run(&mut reference, 6000));
+        // while the duplicate's generator started from scratch
+        assert!(!duplicate_notes.is_empty());
+        assert_ne!(duplicate_notes, notes);
+
+        // coroutines can't be duplicated
+        let mut sequence = new_sequence(
+            r#"
+            return rhythm {
+                unit = "1/4",
+                emit = function(context)
+                  return coroutine.create(function(context)
+                    while true do
+                      coroutine.yield("c4")
+                    end
+                  end)
+                end
+            }
+            "#,
+        )?;
+        assert!(sequence.duplicate().is_ok());
+        run(&mut sequence, 1000);
+        assert!(sequence.duplicate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn script_errors() -> LuaResult<()> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let new_sequence = |emit: &str| -> LuaResult<Sequence> {
+            let script = format!("return rhythm {{ unit = \"1/4\", emit = {} }}", emit);
+            let rhythm = new_rhythm_from_string(time_base, None, &script, "[script_errors]")
+                .map_err(|err| LuaError::runtime(err.to_string()))?;
+            Ok(Sequence::new(
+                time_base,
+                vec![Phrase::new(
+                    time_base,
+                    vec![RhythmSlot::from(rhythm)],
+                    BeatTimeStep::Bar(1.0),
+                )],
+            ))
+        };
+        let mut failing_sequence = new_sequence(r#"function(context) error("emit failed") end"#)?;
+        let mut sequence = new_sequence(r#"function(context) return "c4" end"#)?;
+        let (failing_sender, failing_receiver) = std::sync::mpsc::channel();
+        failing_sequence
+            .notifications()
+            .subscribe_channel(&[NotificationKind::ScriptError], failing_sender);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        sequence
+            .notifications()
+            .subscribe_channel(&[NotificationKind::ScriptError], sender);
+
+        failing_sequence.consume_events_until_time(1000, &mut |_, _, _, _| {});
+        sequence.consume_events_until_time(1000, &mut |_, _, _, _| {});
+        // errors get published by the sequence of the failing rhythm only
+        let errors = failing_receiver.try_iter().collect::<Vec<_>>();
+        assert!(!errors.is_empty());
+        assert!(errors.iter().all(|notification| matches!(
+            notification,
+            Notification::ScriptError { message } if message.contains("emit failed")
+        )));
+        assert_eq!(receiver.try_iter().count(), 0);
+        // previously published errors don't get published again
+        sequence.consume_events_until_time(1001, &mut |_, _, _, _| {});
+        failing_sequence.consume_events_until_time(1001, &mut |_, _, _, _| {});
+        assert!(failing_receiver.try_iter().count() <= 1);
+        Ok(())
+    }
+
+    #[test]
+    fn context_time_conversions() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        let function = lua
+            .load(
+                r#"
+                return function(context)
+                  return {
+                    context:beats_to_seconds(1), context:seconds_to_beats(1),
+                    context:beats_to_samples(1), context:samples_to_beats(22050),
+                    context:seconds_to_samples(1), context:samples_to_seconds(22050)
+                  }
+                end
+            "#,
+            )
+            .eval::<LuaFunction>()?;
+        let mut callback = LuaCallback::new(&lua, function)?;
+        let mut conversions = |beats_per_min| -> LuaResult<Vec<f64>> {
+            callback.set_context_time_base(&BeatTimeBase {
+                beats_per_min,
+                beats_per_bar: 4,
+                samples_per_sec: 44100,
+            })?;
+            callback
+                .call()?
+                .as_table()
+                .unwrap()
+                .clone()
+                .sequence_values::<f64>()
+                .collect()
+        };
+        assert_eq!(
+            conversions(120.0)?,
+            vec![0.5, 2.0, 22050.0, 1.0, 44100.0, 0.5]
+        );
+        // follows tempo changes
+        assert_eq!(
+            conversions(60.0)?,
+            vec![1.0, 1.0, 44100.0, 0.5, 44100.0, 0.5]
+        );
+        Ok(())
+    }
 }
@@ -1,8 +1,17 @@
 use mlua::prelude::*;
 
-use crate::{event::NoteEvent, tidal::Cycle};
+use crate::{
+    event::{
+        target::{TargetDefinition, TargetKind, TargetSchema, UnknownTargetAction},
+        InstrumentId, NoteEvent,
+    },
+    tidal::Cycle,
+};
 
-use super::unwrap::{bad_argument_error, note_events_from_value};
+use super::unwrap::{
+    bad_argument_error, instrument_array_from_value, note_events_from_value,
+    validate_table_properties,
+};
 
 // ---------------------------------------------------------------------------------------------
 
@@ -12,20 +21,105 @@ pub struct CycleUserData {
     pub cycle: Cycle,
     pub mappings: Vec<(String, Vec<Option<NoteEvent>>)>,
     pub mapping_function: Option<LuaOwnedFunction>,
+    pub instruments: Vec<InstrumentId>,
+    pub fractional_notes: bool,
+    pub target_schema: Option<TargetSchema>,
 }
 
 impl CycleUserData {
-    pub fn from(arg: LuaString, seed: Option<[u8; 32]>) -> LuaResult<Self> {
+    pub fn from(
+        lua: &Lua,
+        arg: LuaString,
+        options: Option<LuaTable>,
+        seed: Option<[u8; 32]>,
+    ) -> LuaResult<Self> {
         let mut cycle = Cycle::from(&arg.to_string_lossy()).map_err(LuaError::runtime)?;
         if let Some(seed) = seed {
             cycle = cycle.with_seed(seed);
         }
         let mappings = Vec::new();
         let mapping_function = None;
+        let mut instruments = Vec::new();
+        let mut fractional_notes = false;
+        let mut target_schema = None;
+        if let Some(options) = options {
+            const CYCLE_OPTIONS: [&str; 4] = [
+                "instruments",
+                "fractional_notes",
+                "targets",
+                "unknown_targets",
+            ];
+            validate_table_properties(&options, &CYCLE_OPTIONS)?;
+            if options.contains_key("instruments")? {
+                let value = options.get::<_, LuaValue>("instruments")?;
+                if !value.is_table() {
+                    return Err(bad_argument_error(
+                        "cycle",
+                        "instruments",
+                        2,
+                        "instruments must be an array of instrument numbers",
+                    ));
+                }
+                instruments = instrument_array_from_value(lua, value, 0)?
+                    .into_iter()
+                    .map(|instrument| InstrumentId::from(instrument as usize))
+                    .collect();
+            }
+            if options.contains_key("fractional_notes")? {
+                let value = options.get::<_, LuaValue>("fractional_notes")?;
+                if let LuaValue::Boolean(enabled) = value {
+                    fractional_notes = enabled;
+                } else {
+                    return Err(bad_argument_error(
+                        "cycle",
+                        "fractional_notes",
+                        2,
+                        "fractional_notes must be a boolean",
+                    ));
+                }
+            }
+            if options.contains_key("targets")? {
+                let value = options.get::<_, LuaValue>("targets")?;
+                if let LuaValue::Table(targets) = value {
+                    target_schema = Some(target_schema_from_table(targets)?);
+                } else {
+                    return Err(bad_argument_error(
+                        "cycle",
+                        "targets",
+                        2,
+                        "targets must be a table of target definitions",
+                    ));
+                }
+            }
+            if options.contains_key("unknown_targets")? {
+                let value = options.get::<_, LuaValue>("unknown_targets")?;
+                let unknown_targets = match value.as_str() {
+                    Some("ignore") => UnknownTargetAction::Ignore,
+                    Some("warn") => UnknownTargetAction::Warn,
+                    Some("error") => UnknownTargetAction::Error,
+                    _ => {
+                        return Err(bad_argument_error(
+                            "cycle",
+                            "unknown_targets",
+                            2,
+                            "unknown_targets must be one of 'ignore', 'warn' or 'error'",
+                        ))
+                    }
+                };
+                target_schema = Some(
+                    target_schema
+                        .unwrap_or_default()
+                        .with_unknown_targets(unknown_targets),
+                );
+            }
+        }
         Ok(CycleUserData {
             cycle,
             mappings,
             mapping_function,
+            instruments,
+            fractional_notes,
+            target_schema,
         })
     }
 }
@@ -37,10 +131,16 @@ impl LuaUserData for CycleUserData {
                 let cycle = this.cycle.clone();
                 let mappings = Vec::new();
                 let mapping_function = Some(func.into_owned());
+                let instruments = this.instruments.clone();
+                let fractional_notes = this.fractional_notes;
+                let target_schema = this.target_schema.clone();
                 Ok(CycleUserData {
                     cycle,
                     mappings,
                     mapping_function,
+                    instruments,
+                    fractional_notes,
+                    target_schema,
                 })
             }
             LuaValue::Table(table) => {
@@ -50,10 +150,16 @@ impl LuaUserData for CycleUserData {
                     mappings.push((k.to_string()?, note_events_from_value(&v, None)?));
                 }
                 let mapping_function = None;
+                let instruments = this.instruments.clone();
+                let fractional_notes = this.fractional_notes;
+                let target_schema = this.target_schema.clone();
                 Ok(CycleUserData {
                     cycle,
                     mappings,
                     mapping_function,
+                    instruments,
+                    fractional_notes,
+                    target_schema,
                 })
             }
             _ => Err(bad_argument_error(
@@ -67,7 +173,69 @@ impl LuaUserData for CycleUserData {
                 .as_str(),
             )),
         });
+        methods.add_method("reverse", |_lua, this, ()| {
+            let mut cycle = this.clone();
+            cycle.cycle.set_reversed(true);
+            Ok(cycle)
+        });
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn target_schema_from_table(targets: LuaTable) -> LuaResult<TargetSchema> {
+    let mut schema = TargetSchema::new();
+    for (name, definition) in targets.pairs::<LuaString, LuaValue>().flatten() {
+        let name = name.to_string_lossy().to_string();
+        let definition = match definition {
+            LuaValue::Table(table) => table,
+            _ => {
+                return Err(bad_argument_error(
+                    "cycle",
+                    "targets",
+                    2,
+                    &format!("target definition of '{}' must be a table", name),
+                ))
+            }
+        };
+        const TARGET_PROPERTIES: [&str; 3] = ["type", "default", "range"];
+        validate_table_properties(&definition, &TARGET_PROPERTIES)?;
+        let kind = match definition.get::<_, Option<String>>("type")?.as_deref() {
+            None | Some("number") => TargetKind::Number,
+            Some("integer") => TargetKind::Integer,
+            Some(kind) => {
+                return Err(bad_argument_error(
+                    "cycle",
+                    "targets",
+                    2,
+                    &format!(
+                        "invalid type '{}' for target '{}': expected 'number' or 'integer'",
+                        kind, name
+                    ),
+                ))
+            }
+        };
+        let mut target = TargetDefinition::new(kind);
+        if let Some(default) = definition.get::<_, Option<f64>>("default")? {
+            target = target.with_default(default);
+        }
+        if let Some(range) = definition.get::<_, Option<Vec<f64>>>("range")? {
+            if range.len() != 2 || range[0] > range[1] {
+                return Err(bad_argument_error(
+                    "cycle",
+                    "targets",
+                    2,
+                    &format!(
+                        "range of target '{}' must be a table with min and max values",
+                        name
+                    ),
+                ));
+            }
+            target = target.with_range(range[0]..=range[1]);
+        }
+        schema = schema.with_target(name, target);
     }
+    Ok(schema)
 }
 
 // --------------------------------------------------------------------------------------------------
@@ -76,12 +244,20 @@ impl LuaUserData for CycleUserData {
 mod test {
     use std::collections::HashMap;
 
+    use fraction::Fraction;
+
     use super::*;
 
     use crate::{
         bindings::*,
-        event::{cycle::CycleEventIter, new_note, scripted_cycle::ScriptedCycleEventIter},
-        Event, EventIter, Note, PulseIterItem,
+        event::{
+            cycle::{CycleEventIter, DETUNE_KEY},
+            new_note, new_parameter_change,
+            scripted_cycle::ScriptedCycleEventIter,
+            EventData, EventDataValue, ParameterId,
+        },
+        warning::{ValueRangePolicy, WarningKind},
+        Event, EventIter, Note, PulseIterItem, Warning,
     };
 
     fn new_test_engine() -> LuaResult<(Lua, LuaTimeoutHook)> {
@@ -173,6 +349,221 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn reverse() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        let reversed_cycle = evaluate_cycle_userdata(&lua, r#"cycle("c4 d4 e4"):reverse()"#)?;
+        assert!(reversed_cycle.cycle.is_reversed());
+        let mut event_iter = CycleEventIter::new(reversed_cycle.cycle);
+        assert_eq!(
+            event_iter
+                .run(PulseIterItem::default(), true)
+                .map(|events| events.into_iter().map(|e| e.event).collect::<Vec<_>>()),
+            Some(vec![
+                Event::NoteEvents(vec![new_note(Note::E4)]),
+                Event::NoteEvents(vec![new_note(Note::D4)]),
+                Event::NoteEvents(vec![new_note(Note::C4)])
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_identifiers() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        // unknown identifiers are errors, which skip the unmapped events only
+        let cycle = evaluate_cycle_userdata(&lua, r#"cycle("c4 wurstbrot e4")"#)?;
+        let mut event_iter = ScriptedCycleEventIter::with_mappings(cycle.cycle, cycle.mappings);
+        assert_eq!(
+            event_iter
+                .run(PulseIterItem::default(), true)
+                .map(|events| events.into_iter().map(|e| e.event).collect::<Vec<_>>()),
+            Some(vec![
+                Event::NoteEvents(vec![new_note(Note::C4)]),
+                Event::NoteEvents(vec![new_note(Note::E4)]),
+            ])
+        );
+        let error = "unknown identifier in cycle: 'wurstbrot'";
+        assert!(lua_callback_errors()
+            .iter()
+            .any(|err| err.to_string().contains(error)));
+        Ok(())
+    }
+
+    #[test]
+    fn value_range_policy() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        // out of range notes are errors in strict mode
+        let cycle = evaluate_cycle_userdata(&lua, r#"cycle("c4 200")"#)?;
+        let mut event_iter =
+            ScriptedCycleEventIter::with_mappings(cycle.cycle.clone(), cycle.mappings.clone());
+        assert_eq!(
+            event_iter
+                .run(PulseIterItem::default(), true)
+                .map(|events| events.into_iter().map(|e| e.event).collect::<Vec<_>>()),
+            Some(vec![Event::NoteEvents(vec![new_note(Note::C4)])])
+        );
+        // and get clamped and reported in clamp mode
+        let value_range = LuaValueRange::default();
+        value_range.set_policy(ValueRangePolicy::Clamp);
+        let mut event_iter = ScriptedCycleEventIter::with_mappings(cycle.cycle, cycle.mappings)
+            .with_value_range(value_range);
+        assert_eq!(
+            event_iter
+                .run(PulseIterItem::default(), true)
+                .map(|events| events.into_iter().map(|e| e.event).collect::<Vec<_>>()),
+            Some(vec![
+                Event::NoteEvents(vec![new_note(Note::C4)]),
+                Event::NoteEvents(vec![new_note(Note::from(0x7f_u8))]),
+            ])
+        );
+        assert_eq!(
+            event_iter.take_warnings(),
+            vec![Warning::new(
+                WarningKind::Clamped,
+                "cycle note value 200 got clamped to 127"
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fractional_notes() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        assert!(
+            evaluate_cycle_userdata(&lua, r#"cycle("60.5", { fractional_notes = 1 })"#).is_err()
+        );
+
+        let fractional_cycle = evaluate_cycle_userdata(
+            &lua,
+            r#"cycle("60.5 60", { fractional_notes = true }):map({})"#,
+        )?;
+        assert!(fractional_cycle.fractional_notes);
+        let mut event_iter = ScriptedCycleEventIter::with_mappings(
+            fractional_cycle.cycle,
+            fractional_cycle.mappings,
+        )
+        .with_fractional_notes(fractional_cycle.fractional_notes);
+        let mut detuned_note = NoteEvent::from(Note::from(60_u8));
+        detuned_note.extra = Some(EventData::from([(
+            DETUNE_KEY.to_string(),
+            EventDataValue::Number(50.0),
+        )]));
+        assert_eq!(
+            event_iter
+                .run(PulseIterItem::default(), true)
+                .map(|events| events.into_iter().map(|e| e.event).collect::<Vec<_>>()),
+            Some(vec![
+                Event::NoteEvents(vec![Some(detuned_note)]),
+                Event::NoteEvents(vec![new_note(Note::from(60_u8))])
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn targets() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        assert!(evaluate_cycle_userdata(&lua, r#"cycle("a", { targets = 1 })"#).is_err());
+        assert!(evaluate_cycle_userdata(
+            &lua,
+            r#"cycle("a", { targets = { v = { wurst = 1 } } })"#
+        )
+        .is_err());
+        assert!(evaluate_cycle_userdata(
+            &lua,
+            r#"cycle("a", { targets = { v = { type = "string" } } })"#
+        )
+        .is_err());
+        assert!(evaluate_cycle_userdata(
+            &lua,
+            r#"cycle("a", { targets = { v = { range = { 1, 0 } } } })"#
+        )
+        .is_err());
+        assert!(
+            evaluate_cycle_userdata(&lua, r#"cycle("a", { unknown_targets = "wurst" })"#).is_err()
+        );
+
+        let target_cycle = evaluate_cycle_userdata(
+            &lua,
+            r#"cycle("a:v=2 a:p a:cut=1.6 a:x=1", {
+              targets = {
+                v = { default = 1, range = { 0, 1 } },
+                p = { default = -0.5 },
+                cut = { type = "integer" }
+              },
+              unknown_targets = "warn"
+            }):map({ a = "c4" })"#,
+        )?;
+        assert!(target_cycle.target_schema.is_some());
+        let mut event_iter =
+            ScriptedCycleEventIter::with_mappings(target_cycle.cycle, target_cycle.mappings)
+                .with_target_schema(target_cycle.target_schema);
+        let mut volume_note = NoteEvent::from(Note::C4);
+        volume_note.volume = 1.0;
+        let mut panned_note = NoteEvent::from(Note::C4);
+        panned_note.panning = -0.5;
+        let mut cut_note = NoteEvent::from(Note::C4);
+        cut_note.extra = Some(EventData::from([(
+            "cut".to_string(),
+            EventDataValue::Integer(2),
+        )]));
+        assert_eq!(
+            event_iter
+                .run(PulseIterItem::default(), true)
+                .map(|events| events.into_iter().map(|e| e.event).collect::<Vec<_>>()),
+            Some(vec![
+                Event::NoteEvents(vec![Some(volume_note)]),
+                Event::NoteEvents(vec![Some(panned_note)]),
+                Event::NoteEvents(vec![Some(cut_note)]),
+                Event::NoteEvents(vec![new_note(Note::C4)])
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn instruments() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        assert!(evaluate_cycle_userdata(&lua, r#"cycle("a", { instruments = 1 })"#).is_err());
+        assert!(evaluate_cycle_userdata(&lua, r#"cycle("a", { instruments = {-1} })"#).is_err());
+        assert!(evaluate_cycle_userdata(&lua, r#"cycle("a", { wurst = {1} })"#).is_err());
+
+        let mapped_cycle = evaluate_cycle_userdata(
+            &lua,
+            r#"cycle("a b, a:9 a", { instruments = {1, 2} }):map({ a = "c4", b = "d4" })"#,
+        )?;
+        assert_eq!(
+            mapped_cycle.instruments,
+            vec![InstrumentId::from(1), InstrumentId::from(2)]
+        );
+        let mut event_iter =
+            ScriptedCycleEventIter::with_mappings(mapped_cycle.cycle, mapped_cycle.mappings)
+                .with_channel_instruments(&mapped_cycle.instruments);
+        assert_eq!(
+            event_iter
+                .run(PulseIterItem::default(), true)
+                .map(|events| events.into_iter().map(|e| e.event).collect::<Vec<_>>()),
+            Some(vec![
+                Event::NoteEvents(vec![
+                    new_note((Note::C4, InstrumentId::from(1))),
+                    new_note((Note::C4, InstrumentId::from(9)))
+                ]),
+                Event::NoteEvents(vec![
+                    new_note((Note::D4, InstrumentId::from(1))),
+                    new_note((Note::C4, InstrumentId::from(2)))
+                ])
+            ])
+        );
+        Ok(())
+    }
+
     #[test]
     fn mapping_functions() -> LuaResult<()> {
         let time_base = BeatTimeBase {
@@ -218,4 +609,69 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn mapping_function_events() -> LuaResult<()> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+
+        let (lua, timeout_hook) = new_test_engine_with_timebase(&time_base)?;
+
+        let mapped_cycle = evaluate_cycle_userdata(
+            &lua,
+            r#"
+                cycle("a b:1 c:v=0.5"):map(function(context, value)
+                    if value == "a" then
+                      assert(context.step_start == 0 and context.target == nil)
+                      return sequence("c4", "e4")
+                    elseif value == "b" then
+                      assert(math.abs(context.step_start - 1/3) < 0.001)
+                      assert(context.target == 1)
+                      return { parameter = 2, value = 0.5 }
+                    else
+                      assert(context.target == "v=0.5")
+                      return "g4"
+                    end
+                end)"#,
+        )?;
+        let mapping_callback =
+            LuaCallback::with_owned(&lua, mapped_cycle.mapping_function.unwrap().clone())?;
+        let mut event_iter = ScriptedCycleEventIter::with_mapping_callback(
+            mapped_cycle.cycle,
+            &timeout_hook,
+            mapping_callback,
+            &time_base,
+        )?;
+        let events = event_iter
+            .run(PulseIterItem::default(), true)
+            .unwrap_or_default();
+        assert_eq!(
+            events
+                .iter()
+                .map(|e| (e.start, e.event.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Fraction::from(0),
+                    Event::NoteEvents(vec![new_note(Note::C4)])
+                ),
+                (
+                    Fraction::new(1u64, 6u64),
+                    Event::NoteEvents(vec![new_note(Note::E4)])
+                ),
+                (
+                    Fraction::new(1u64, 3u64),
+                    Event::ParameterChangeEvent(new_parameter_change(ParameterId::from(2), 0.5))
+                ),
+                (
+                    Fraction::new(2u64, 3u64),
+                    Event::NoteEvents(vec![new_note(Note::G4)])
+                ),
+            ]
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,130 @@
+use mlua::prelude::*;
+
+use crate::prelude::*;
+
+use super::unwrap::bad_argument_error;
+
+// ---------------------------------------------------------------------------------------------
+
+impl LuaUserData for Groove {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("positions", |lua, this| -> LuaResult<LuaTable> {
+            lua.create_sequence_from(this.positions().iter().copied())
+        });
+        fields.add_field_method_get("length", |_lua, this| -> LuaResult<LuaNumber> {
+            Ok(this.length())
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+
+// Create a groove from a preset name or an array of step positions and a length.
+pub(crate) fn groove_from_values(
+    name_or_positions: &LuaValue,
+    length: Option<LuaNumber>,
+) -> LuaResult<Groove> {
+    if let Some(name) = name_or_positions.as_str() {
+        Groove::try_from(name).map_err(|err| {
+            bad_argument_error(
+                "groove",
+                "name",
+                1,
+                format!(
+                    "{}, valid presets are: {}",
+                    err,
+                    Groove::preset_names().join(", ")
+                )
+                .as_str(),
+            )
+        })
+    } else if let Some(table) = name_or_positions.as_table() {
+        let positions = table
+            .clone()
+            .sequence_values::<f64>()
+            .collect::<LuaResult<Vec<f64>>>()
+            .map_err(|err| {
+                bad_argument_error(
+                    "groove",
+                    "positions",
+                    1,
+                    &format!("invalid position values: {}", err),
+                )
+            })?;
+        let length = length.unwrap_or(positions.len() as LuaNumber);
+        Groove::try_from((positions.as_slice(), length))
+            .map_err(|err| bad_argument_error("groove", "positions", 1, &err))
+    } else {
+        Err(bad_argument_error(
+            "groove",
+            "name|positions",
+            1,
+            "expecting a preset name or a position array as first argument",
+        ))
+    }
+}
+
+// Unwrap a groove from a groove userdata or preset name.
+pub(crate) fn groove_from_value(value: &LuaValue) -> LuaResult<Groove> {
+    if let Some(userdata) = value.as_userdata() {
+        if let Ok(groove) = userdata.borrow::<Groove>() {
+            return Ok(groove.clone());
+        }
+    } else if value.is_string() {
+        return groove_from_values(value, None);
+    }
+    Err(LuaError::FromLuaConversionError {
+        from: value.type_name(),
+        to: "groove",
+        message: Some("groove must be a groove preset name or groove".to_string()),
+    })
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use crate::bindings::*;
+
+    fn new_test_engine() -> LuaResult<Lua> {
+        // create a new engine and register bindings
+        let (mut lua, mut timeout_hook) = new_engine()?;
+        register_bindings(
+            &mut lua,
+            &timeout_hook,
+            &BeatTimeBase {
+                beats_per_min: 120.0,
+                beats_per_bar: 4,
+                samples_per_sec: 44100,
+            },
+        )?;
+        timeout_hook.reset();
+        Ok(lua)
+    }
+
+    #[test]
+    fn groove() -> LuaResult<()> {
+        let lua = new_test_engine()?;
+
+        assert!(lua.load(r#"groove()"#).exec().is_err());
+        assert!(lua.load(r#"groove("wurst")"#).exec().is_err());
+        assert!(lua.load(r#"groove({0.5, 1})"#).exec().is_err());
+        assert!(lua.load(r#"groove({0, 1.5}, 1)"#).exec().is_err());
+
+        let length = lua
+            .load(r#"return groove("tresillo").length"#)
+            .eval::<LuaNumber>()?;
+        assert_eq!(length, 8.0);
+        let positions = lua
+            .load(r#"return groove({0, 1.5}, 3).positions"#)
+            .eval::<Vec<LuaNumber>>()?;
+        assert_eq!(positions, vec![0.0, 1.5]);
+
+        let groove = lua.load(r#"return groove({0, 1.5})"#).eval::<LuaValue>()?;
+        assert_eq!(
+            groove.as_userdata().unwrap().borrow::<Groove>()?.clone(),
+            Groove::try_from((&[0.0, 1.5] as &[f64], 2.0)).unwrap()
+        );
+        Ok(())
+    }
+}
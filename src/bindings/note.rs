@@ -201,7 +201,10 @@ impl LuaUserData for NoteUserData {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{bindings::*, event::new_note};
+    use crate::{
+        bindings::*,
+        event::{new_note, relative::RelativeNote, EventData, EventDataValue, NoteEvent},
+    };
 
     fn new_test_engine() -> LuaResult<(Lua, LuaTimeoutHook)> {
         let (mut lua, mut timeout_hook) = new_engine()?;
@@ -520,4 +523,133 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn note_strum() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        // chord strings
+        assert!(evaluate_note_userdata(&lua, r#"note("c4'maj s")"#).is_err());
+        assert!(evaluate_note_userdata(&lua, r#"note("c4'maj s2")"#).is_err());
+        assert!(evaluate_note_userdata(&lua, r#"note("c4'maj s0.1x")"#).is_err());
+        assert_eq!(
+            evaluate_note_userdata(&lua, r#"note("c4'maj s0.1")"#)?.notes,
+            vec![
+                new_note(("c4", None, 1.0, 0.0, 0.0)),
+                new_note(("e4", None, 1.0, 0.0, 0.1)),
+                new_note(("g4", None, 1.0, 0.0, 0.2)),
+            ]
+        );
+        assert_eq!(
+            evaluate_note_userdata(&lua, r#"note("c4'maj d0.25 s0.25d")"#)?.notes,
+            vec![
+                new_note(("c4", None, 1.0, 0.0, 0.75)),
+                new_note(("e4", None, 1.0, 0.0, 0.5)),
+                new_note(("g4", None, 1.0, 0.0, 0.25)),
+            ]
+        );
+        assert_eq!(
+            evaluate_note_userdata(&lua, r#"note("c4'maj s0.1c")"#)?.notes,
+            vec![
+                new_note(("c4", None, 1.0, 0.0, 0.1)),
+                new_note(("e4", None, 1.0, 0.0, 0.0)),
+                new_note(("g4", None, 1.0, 0.0, 0.1)),
+            ]
+        );
+
+        // chord tables
+        assert!(evaluate_note_userdata(&lua, r#"note({key = "c4'maj", strum = -1})"#).is_err());
+        assert!(evaluate_note_userdata(
+            &lua,
+            r#"note({key = "c4'maj", strum = 0.1, strum_direction = "sideways"})"#
+        )
+        .is_err());
+        assert_eq!(
+            evaluate_note_userdata(&lua, r#"note({key = "c4'maj", volume = 0.5})"#)?.notes,
+            vec![
+                new_note(("c4", None, 0.5)),
+                new_note(("e4", None, 0.5)),
+                new_note(("g4", None, 0.5)),
+            ]
+        );
+        assert_eq!(
+            evaluate_note_userdata(
+                &lua,
+                r#"note({key = "c4'maj", strum = 0.25, strum_direction = "down"})"#
+            )?
+            .notes,
+            vec![
+                new_note(("c4", None, 1.0, 0.0, 0.5)),
+                new_note(("e4", None, 1.0, 0.0, 0.25)),
+                new_note(("g4", None, 1.0, 0.0, 0.0)),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn note_relative() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        assert!(evaluate_note_userdata(&lua, r#"note("+3x")"#).is_err());
+        assert!(evaluate_note_userdata(&lua, r#"note("^")"#).is_err());
+        assert!(evaluate_note_userdata(&lua, r#"note("v3")"#).is_err());
+        assert_eq!(
+            evaluate_note_userdata(&lua, r#"note("+3 v0.5")"#)?.notes,
+            vec![Some(NoteEvent {
+                volume: 0.5,
+                ..RelativeNote::Semitones(3).to_note_event()
+            })]
+        );
+        assert_eq!(
+            evaluate_note_userdata(&lua, r#"note({key = "^-2", delay = 0.5})"#)?.notes,
+            vec![Some(NoteEvent {
+                delay: 0.5,
+                ..RelativeNote::Degrees(-2).to_note_event()
+            })]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn note_extra() -> LuaResult<()> {
+        let (lua, _) = new_test_engine()?;
+
+        assert!(evaluate_note_userdata(&lua, r#"note({key = "c4", extra = 1})"#).is_err());
+        assert!(evaluate_note_userdata(&lua, r#"note({key = "c4", extra = {1}})"#).is_err());
+        assert!(evaluate_note_userdata(&lua, r#"note({key = "c4", extra = {a = {}}})"#).is_err());
+
+        // extra data passes through transforms
+        let note_event = evaluate_note_userdata(
+            &lua,
+            r#"note({key = "c4", extra = {color = "red", track = 2, solo = true}}):transposed(12)"#,
+        )?;
+        let extra = EventData::from([
+            ("color".to_string(), EventDataValue::from("red")),
+            ("solo".to_string(), EventDataValue::from(true)),
+            ("track".to_string(), EventDataValue::from(2_i64)),
+        ]);
+        assert_eq!(
+            note_event.notes,
+            vec![Some(NoteEvent {
+                extra: Some(extra),
+                ..new_note("c5").unwrap()
+            })]
+        );
+
+        // and round-trips through lua
+        let value = lua
+            .load(
+                r#"
+                local n = note({key = "c4", extra = {color = "red", gain = 0.5}})
+                return n.notes[1].extra.color .. " " .. n.notes[1].extra.gain
+                "#,
+            )
+            .eval::<String>()?;
+        assert_eq!(value, "red 0.5");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,776 @@
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, ops::RangeInclusive, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::{
+    event::InstrumentId, memory::MemoryUsage, parameter::RhythmParameterValues,
+    shared::SharedValues, time::SampleTimeDisplay, BeatTimeBase, Rhythm, RhythmIter,
+    RhythmIterItem, RhythmParameter, SampleTime, Warning,
+};
+
+// ---------------------------------------------------------------------------------------------
+
+/// Max nesting level of tables which get scanned for numeric constants.
+const MAX_TABLE_DEPTH: usize = 4;
+
+// ---------------------------------------------------------------------------------------------
+
+/// A numeric constant of a Lua `rhythm{}` table, which got lifted into a named parameter.
+///
+/// Parameter ids are the dot separated paths of the constant in the rhythm table, e.g.
+/// `offset`, `pattern.2` or `emit.1.volume`. When a script creates multiple rhythm tables,
+/// e.g. for a `chain{}`, ids of all but the first table are prefixed with the table's number
+/// in order of creation, e.g. `2.pattern.2`. Ranges are inferred from the constant's name and
+/// default value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoParameter {
+    id: String,
+    integer: bool,
+    default_value: f64,
+    range: RangeInclusive<f64>,
+    value: f64,
+}
+
+impl AutoParameter {
+    /// The parameter's unique id, the path of the constant in the rhythm table.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// true when the constant is an integer value.
+    pub fn is_integer(&self) -> bool {
+        self.integer
+    }
+
+    /// The constant's value as specified in the script.
+    pub fn default_value(&self) -> f64 {
+        self.default_value
+    }
+
+    /// Inferred value range of the parameter.
+    pub fn range(&self) -> &RangeInclusive<f64> {
+        &self.range
+    }
+
+    /// The parameter's actual value, which got applied to the rhythm.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    fn infer_range(path: &[String], value: f64) -> RangeInclusive<f64> {
+        let name = path.last().map(String::as_str).unwrap_or_default();
+        let parent = if path.len() > 1 {
+            path[path.len() - 2].as_str()
+        } else {
+            ""
+        };
+        match name {
+            _ if parent == "humanize" => 0.0..=1.0,
+            "width" if parent == "panning" => 0.0..=1.0,
+            "delay" | "repeats" if parent == "echo" => 0.0..=16.0,
+            "transpose" if parent == "echo" => -12.0..=12.0,
+            "feedback" if parent == "echo" => 0.0..=1.0,
+            "transpose" if parent.is_empty() => -48.0..=48.0,
+            "degrees" if parent == "degree_shift" => -14.0..=14.0,
+            "amount" if parent == "seed_morph" => 0.0..=1.0,
+            "volume" | "delay" => 0.0..=1.0,
+            "panning" => -1.0..=1.0,
+            "key" => 0.0..=127.0,
+            "resolution" if value > 0.0 => value / 4.0..=value * 4.0,
+            _ if parent == "pattern" || parent == "gate" => 0.0..=1.0,
+            _ => {
+                let max = value.abs().max(1.0) * 2.0;
+                if value < 0.0 {
+                    -max..=max
+                } else {
+                    0.0..=max
+                }
+            }
+        }
+    }
+}
+
+impl From<&AutoParameter> for RhythmParameter {
+    fn from(parameter: &AutoParameter) -> Self {
+        RhythmParameter::new(
+            parameter.id.clone(),
+            parameter.range.clone(),
+            parameter.default_value,
+        )
+        .with_integer(parameter.integer)
+        .with_value(parameter.value)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+
+/// Auto parameter state of a Lua engine: collects lifted constants and applies value overrides.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AutoParameters {
+    values: HashMap<String, f64>,
+    parameters: Vec<AutoParameter>,
+    table_count: usize,
+    id_prefix: String,
+}
+
+impl AutoParameters {
+    pub fn new(values: &[(Cow<str>, f64)]) -> Self {
+        let values = values
+            .iter()
+            .map(|(id, value)| (id.to_string(), *value))
+            .collect();
+        let parameters = Vec::new();
+        let table_count = 0;
+        let id_prefix = String::new();
+        Self {
+            values,
+            parameters,
+            table_count,
+            id_prefix,
+        }
+    }
+
+    /// Lift all numeric constants of the given rhythm table into parameters, replacing
+    /// constants in the table with overridden values. Returns the newly lifted parameters.
+    pub fn lift(&mut self, table: &LuaTable) -> LuaResult<Vec<RhythmParameter>> {
+        // scope ids of all but the first table, so multiple rhythms don't share ids
+        self.table_count += 1;
+        self.id_prefix = if self.table_count > 1 {
+            format!("{}.", self.table_count)
+        } else {
+            String::new()
+        };
+        let mut path = Vec::new();
+        let first_new_parameter = self.parameters.len();
+        self.lift_table(table, &mut path)?;
+        Ok(self.parameters[first_new_parameter..]
+            .iter()
+            .map(RhythmParameter::from)
+            .collect())
+    }
+
+    /// Overridden value of the parameter with the given id, if any.
+    pub fn value_override(&self, id: &str) -> Option<f64> {
+        self.values.get(id).copied()
+    }
+
+    /// Consume the state and return all lifted parameters.
+    pub fn into_parameters(self) -> Vec<AutoParameter> {
+        self.parameters
+    }
+
+    fn lift_table(&mut self, table: &LuaTable, path: &mut Vec<String>) -> LuaResult<()> {
+        if path.len() >= MAX_TABLE_DEPTH {
+            return Ok(());
+        }
+        // sort keys to get a stable parameter order: sequence values first, then names
+        let mut entries = table
+            .clone()
+            .pairs::<LuaValue, LuaValue>()
+            .collect::<LuaResult<Vec<_>>>()?;
+        entries.retain(|(key, _)| key.is_integer() || key.is_string());
+        entries.sort_by(|(a, _), (b, _)| match (a.as_integer(), b.as_integer()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a
+                .as_str()
+                .unwrap_or_default()
+                .cmp(b.as_str().unwrap_or_default()),
+        });
+        for (key, value) in entries {
+            let name = match &key {
+                LuaValue::Integer(index) => index.to_string(),
+                _ => key.as_str().unwrap_or_default().to_string(),
+            };
+            if name == "instrument" {
+                // instrument ids are no continuous values
+                continue;
+            }
+            if name == "parameters" && path.is_empty() {
+                // explicitly declared parameters are no constants
+                continue;
+            }
+            path.push(name);
+            match value {
+                LuaValue::Integer(value) => {
+                    let value = self.lift_value(path, value as f64, true)?;
+                    table.raw_set(key, value.round() as i64)?;
+                }
+                LuaValue::Number(value) => {
+                    let value = self.lift_value(path, value, false)?;
+                    table.raw_set(key, value)?;
+                }
+                LuaValue::Table(table) => {
+                    self.lift_table(&table, path)?;
+                }
+                _ => (),
+            }
+            path.pop();
+        }
+        Ok(())
+    }
+
+    fn lift_value(&mut self, path: &[String], default_value: f64, integer: bool) -> LuaResult<f64> {
+        let id = self.id_prefix.clone() + &path.join(".");
+        if self.parameters.iter().any(|parameter| parameter.id == id) {
+            return Err(LuaError::runtime(format!(
+                "auto parameter id '{}' is ambiguous: it's used by multiple constants",
+                id
+            )));
+        }
+        let range = AutoParameter::infer_range(path, default_value);
+        let value = self.values.get(&id).map_or(default_value, |value| {
+            value.clamp(*range.start(), *range.end())
+        });
+        self.parameters.push(AutoParameter {
+            id,
+            integer,
+            default_value,
+            range,
+            value,
+        });
+        Ok(value)
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+
+/// Wraps a rhythm which got created with auto parameters, applying auto parameter value
+/// changes by re-evaluating the rhythm's script with the new values.
+///
+/// The re-evaluated rhythm gets seeked to the playback position of the replaced rhythm and
+/// gets all other parameter values and settings of the replaced rhythm applied.
+#[derive(Debug, Clone)]
+pub(crate) struct AutoParameterRhythm {
+    time_base: BeatTimeBase,
+    instrument: Option<InstrumentId>,
+    script: Rc<str>,
+    script_name: Rc<str>,
+    values: Vec<(String, f64)>,
+    rhythm: Rc<RefCell<dyn Rhythm>>,
+    external_context: Vec<(String, f64)>,
+    shared_values: Option<SharedValues>,
+    seed: Option<[u8; 32]>,
+    reversed: bool,
+    sample_position: SampleTime,
+}
+
+impl AutoParameterRhythm {
+    pub fn new(
+        time_base: BeatTimeBase,
+        instrument: Option<InstrumentId>,
+        script: &str,
+        script_name: &str,
+        parameters: &[AutoParameter],
+        rhythm: Rc<RefCell<dyn Rhythm>>,
+    ) -> Self {
+        let values = parameters
+            .iter()
+            .map(|parameter| (parameter.id.clone(), parameter.value))
+            .collect();
+        Self {
+            time_base,
+            instrument,
+            script: script.into(),
+            script_name: script_name.into(),
+            values,
+            rhythm,
+            external_context: Vec::new(),
+            shared_values: None,
+            seed: None,
+            reversed: false,
+            sample_position: 0,
+        }
+    }
+
+    /// Re-evaluate the script with the actual auto parameter values and replace the rhythm.
+    fn rebuild(&mut self) -> Result<(), String> {
+        let values = self
+            .values
+            .iter()
+            .map(|(id, value)| (Cow::Borrowed(id.as_str()), *value))
+            .collect::<Vec<_>>();
+        let (rhythm, _) = super::evaluate_rhythm_with_auto_parameters(
+            self.time_base,
+            self.instrument,
+            &self.script,
+            &self.script_name,
+            &values,
+        )
+        .map_err(|err| err.to_string())?;
+        {
+            let old_rhythm = self.rhythm.borrow();
+            let mut new_rhythm = rhythm.borrow_mut();
+            new_rhythm.set_sample_offset(old_rhythm.sample_offset());
+            if !self.external_context.is_empty() {
+                new_rhythm.set_external_context(&self.external_context_data());
+            }
+            if let Some(shared_values) = &self.shared_values {
+                new_rhythm.set_shared_values(shared_values);
+            }
+            if let Some(seed) = self.seed {
+                new_rhythm.set_seed(seed);
+            }
+            new_rhythm.set_reversed(self.reversed);
+            // keep values of all other parameters
+            for parameter in old_rhythm.parameters() {
+                if !self.values.iter().any(|(id, _)| id == parameter.id()) {
+                    let _ = new_rhythm.set_parameter_value(parameter.id(), parameter.value());
+                }
+            }
+            new_rhythm.seek_until_time(self.sample_position);
+        }
+        self.rhythm = rhythm;
+        Ok(())
+    }
+
+    fn external_context_data(&self) -> Vec<(Cow<str>, f64)> {
+        self.external_context
+            .iter()
+            .map(|(name, value)| (Cow::Borrowed(name.as_str()), *value))
+            .collect()
+    }
+}
+
+impl RhythmIter for AutoParameterRhythm {
+    fn sample_time_display(&self) -> Box<dyn SampleTimeDisplay> {
+        self.rhythm.borrow().sample_time_display()
+    }
+
+    fn sample_offset(&self) -> SampleTime {
+        self.rhythm.borrow().sample_offset()
+    }
+    fn set_sample_offset(&mut self, sample_offset: SampleTime) {
+        self.rhythm.borrow_mut().set_sample_offset(sample_offset);
+    }
+
+    fn run_until_time(&mut self, sample_time: SampleTime) -> Option<RhythmIterItem> {
+        let item = self.rhythm.borrow_mut().run_until_time(sample_time);
+        // memorize the position until which all events got emitted
+        self.sample_position = match &item {
+            Some(item) => item.time.saturating_add(1),
+            None => sample_time,
+        };
+        item
+    }
+
+    fn seek_until_time(&mut self, sample_time: SampleTime) {
+        self.rhythm.borrow_mut().seek_until_time(sample_time);
+        self.sample_position = sample_time;
+    }
+}
+
+impl Rhythm for AutoParameterRhythm {
+    fn pattern_step_length(&self) -> f64 {
+        self.rhythm.borrow().pattern_step_length()
+    }
+
+    fn pattern_length(&self) -> usize {
+        self.rhythm.borrow().pattern_length()
+    }
+
+    fn time_base(&self) -> &BeatTimeBase {
+        &self.time_base
+    }
+
+    fn set_time_base(&mut self, time_base: &BeatTimeBase) {
+        self.time_base = *time_base;
+        self.rhythm.borrow_mut().set_time_base(time_base);
+    }
+
+    fn set_instrument(&mut self, instrument: Option<InstrumentId>) {
+        self.instrument = instrument;
+        self.rhythm.borrow_mut().set_instrument(instrument);
+    }
+
+    fn set_external_context(&mut self, data: &[(Cow<str>, f64)]) {
+        self.external_context = data
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        self.rhythm.borrow_mut().set_external_context(data);
+    }
+
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        self.shared_values = Some(values.clone());
+        self.rhythm.borrow_mut().set_shared_values(values);
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rhythm.borrow_mut().set_seed(seed);
+    }
+
+    fn set_reversed(&mut self, reversed: bool) {
+        self.reversed = reversed;
+        self.rhythm.borrow_mut().set_reversed(reversed);
+    }
+
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        self.rhythm.borrow().parameters()
+    }
+
+    fn take_parameter_changes(&mut self) -> Vec<(String, f64)> {
+        self.rhythm.borrow_mut().take_parameter_changes()
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        self.rhythm.borrow_mut().take_warnings()
+    }
+
+    fn take_script_errors(&mut self) -> Vec<String> {
+        self.rhythm.borrow_mut().take_script_errors()
+    }
+
+    fn set_parameter_value(&mut self, id: &str, value: f64) -> Result<f64, String> {
+        let value = self.rhythm.borrow_mut().set_parameter_value(id, value)?;
+        if let Some(index) = self.values.iter().position(|(auto_id, _)| auto_id == id) {
+            if self.values[index].1 != value {
+                let old_value = std::mem::replace(&mut self.values[index].1, value);
+                if let Err(err) = self.rebuild() {
+                    self.values[index].1 = old_value;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        self.rhythm.borrow().memory_usage()
+    }
+
+    fn trim_memory(&mut self) {
+        self.rhythm.borrow_mut().trim_memory();
+    }
+
+    fn duplicate(&self) -> Result<Rc<RefCell<dyn Rhythm>>, String> {
+        Ok(Rc::new(RefCell::new(Self {
+            rhythm: self.rhythm.borrow().duplicate()?,
+            ..self.clone()
+        })))
+    }
+
+    fn reset(&mut self) {
+        self.rhythm.borrow_mut().reset();
+        self.sample_position = 0;
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+
+/// Parse explicitly declared parameters from a rhythm's `parameters` table. Entries either are
+/// plain default values, with inferred ranges, or tables with `default`, `min`, `max` and
+/// optional `name` and `integer` properties.
+pub(crate) fn parameters_from_table(table: &LuaTable) -> LuaResult<Vec<RhythmParameter>> {
+    let mut parameters = Vec::new();
+    for (id, value) in table.clone().pairs::<String, LuaValue>() {
+        let id = id.map_err(|_| {
+            LuaError::runtime("parameter ids in 'parameters' must be strings".to_string())
+        })?;
+        let parameter = match value {
+            LuaValue::Integer(value) => {
+                let value = value as f64;
+                let range = AutoParameter::infer_range(&[id.clone()], value);
+                RhythmParameter::new(id, range, value).with_integer(true)
+            }
+            LuaValue::Number(value) => {
+                let range = AutoParameter::infer_range(&[id.clone()], value);
+                RhythmParameter::new(id, range, value)
+            }
+            LuaValue::Table(properties) => {
+                let number = |key: &str| -> LuaResult<f64> {
+                    properties.get::<_, Option<f64>>(key)?.ok_or_else(|| {
+                        LuaError::runtime(format!(
+                            "parameter '{}' is missing a '{}' value",
+                            id, key
+                        ))
+                    })
+                };
+                let default_value = number("default")?;
+                let (min, max) = (number("min")?, number("max")?);
+                if min > max {
+                    return Err(LuaError::runtime(format!(
+                        "parameter '{}' has an invalid range: min must be <= max",
+                        id
+                    )));
+                }
+                let integer = properties.get::<_, Option<bool>>("integer")?;
+                let name = properties.get::<_, Option<String>>("name")?;
+                let mut parameter = RhythmParameter::new(id.clone(), min..=max, default_value)
+                    .with_integer(integer.unwrap_or(false));
+                if let Some(name) = name {
+                    parameter = parameter.with_name(name);
+                }
+                parameter
+            }
+            _ => {
+                return Err(LuaError::runtime(format!(
+                    "parameter '{}' must be a number or table, but is a '{}'",
+                    id,
+                    value.type_name()
+                )))
+            }
+        };
+        parameters.push(parameter);
+    }
+    parameters.sort_by(|a, b| a.id().cmp(b.id()));
+    Ok(parameters)
+}
+
+// ---------------------------------------------------------------------------------------------
+
+impl LuaUserData for RhythmParameterValues {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::Index, |lua, this, id: String| {
+            if let Some(value) = this.value(&id) {
+                value.into_lua(lua)
+            } else {
+                Ok(LuaValue::Nil)
+            }
+        });
+
+        methods.add_meta_method(
+            LuaMetaMethod::NewIndex,
+            |_lua, this, (id, value): (String, LuaValue)| {
+                let value = match value {
+                    LuaValue::Integer(value) => value as f64,
+                    LuaValue::Number(value) => value,
+                    _ => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "parameter '{}' must be set to a number, but is a '{}'",
+                            id,
+                            value.type_name()
+                        )))
+                    }
+                };
+                this.set_value(&id, value)
+                    .map(|_| ())
+                    .map_err(LuaError::RuntimeError)
+            },
+        );
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use crate::{bindings::*, event::Event, Note, RhythmParameter};
+
+    #[test]
+    fn auto_parameters() -> Result<(), Box<dyn std::error::Error>> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        let script = r#"
+            return rhythm {
+                unit = "1/8",
+                offset = 2,
+                pattern = { 1, 0, 0.5 },
+                emit = { { key = "c4", volume = 0.5, instrument = 1 } }
+            }
+        "#;
+        let (rhythm, parameters) = new_rhythm_from_string_with_auto_parameters(
+            time_base,
+            None,
+            script,
+            "[test]",
+            &[("offset".into(), 1.0), ("emit.1.volume".into(), 2.0)],
+        )?;
+        assert_eq!(
+            parameters
+                .iter()
+                .map(|p| (p.id(), p.default_value(), p.value(), p.range().clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("emit.1.volume", 0.5, 1.0, 0.0..=1.0),
+                ("offset", 2.0, 1.0, 0.0..=4.0),
+                ("pattern.1", 1.0, 1.0, 0.0..=1.0),
+                ("pattern.2", 0.0, 0.0, 0.0..=1.0),
+                ("pattern.3", 0.5, 0.5, 0.0..=1.0),
+            ]
+        );
+        assert!(parameters[1].is_integer());
+        // lifted parameters are attached to the rhythm
+        assert_eq!(
+            rhythm.borrow().parameters(),
+            parameters
+                .iter()
+                .map(RhythmParameter::from)
+                .collect::<Vec<_>>()
+        );
+        let event = rhythm.borrow_mut().run().unwrap();
+        assert_eq!(event.time, 11025);
+        match event.event {
+            Some(Event::NoteEvents(notes)) => {
+                assert_eq!(notes[0].as_ref().map(|n| n.volume), Some(1.0));
+            }
+            _ => panic!("expected a note event"),
+        }
+        // value changes re-evaluate the script and continue playing
+        let mut rhythm = rhythm.borrow_mut();
+        assert_eq!(rhythm.set_parameter_value("pattern.3", 1.0)?, 1.0);
+        assert_eq!(rhythm.set_parameter_value("emit.1.volume", 0.25)?, 0.25);
+        let event = std::iter::from_fn(|| rhythm.run())
+            .find(|event| event.event.is_some())
+            .unwrap();
+        assert_eq!(event.time, 11025 * 3);
+        match event.event {
+            Some(Event::NoteEvents(notes)) => {
+                assert_eq!(notes[0].as_ref().map(|n| n.volume), Some(0.25));
+            }
+            _ => panic!("expected a note event"),
+        }
+        assert!(rhythm
+            .parameters()
+            .iter()
+            .any(|p| p.id() == "emit.1.volume" && p.value() == 0.25));
+
+        // ambiguous ids
+        let script = r#"
+            return rhythm { emit = { [1] = 1, ["1"] = 2 } }
+        "#;
+        assert!(new_rhythm_from_string_with_auto_parameters(
+            time_base,
+            None,
+            script,
+            "[test]",
+            &[]
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn auto_parameters_in_chains() -> Result<(), Box<dyn std::error::Error>> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        // ids of multiple rhythm tables are scoped
+        let script = r#"
+            return chain {
+                rhythm { unit = "1/4", pattern = { 1, 0 }, emit = "c4" }, 1,
+                rhythm { unit = "1/4", pattern = { 1, 1 }, emit = "e4" }, 1
+            }
+        "#;
+        let (rhythm, parameters) = new_rhythm_from_string_with_auto_parameters(
+            time_base,
+            None,
+            script,
+            "[test]",
+            &[("2.pattern.1".into(), 0.0)],
+        )?;
+        assert_eq!(
+            parameters
+                .iter()
+                .map(|p| (p.id(), p.value()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("pattern.1", 1.0),
+                ("pattern.2", 0.0),
+                ("2.pattern.1", 0.0),
+                ("2.pattern.2", 1.0),
+            ]
+        );
+        let mut rhythm = rhythm.borrow_mut();
+        let events = std::iter::from_fn(|| rhythm.run())
+            .take(4)
+            .map(|event| event.event.is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![true, false, false, true]);
+        // value changes of scoped ids re-evaluate the script
+        assert_eq!(rhythm.set_parameter_value("2.pattern.1", 1.0)?, 1.0);
+        rhythm.reset();
+        let events = std::iter::from_fn(|| rhythm.run())
+            .take(4)
+            .map(|event| event.event.is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec![true, false, true, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn parameter_values() -> Result<(), Box<dyn std::error::Error>> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        let script = r#"
+            return rhythm {
+                parameters = {
+                    density = 0.5,
+                    section = { default = 1, min = 1, max = 4, integer = true, name = "Section" }
+                },
+                emit = function(context)
+                    context.parameters.section = context.parameters.section + 1.2
+                    return "c4"
+                end
+            }
+        "#;
+        let rhythm = new_rhythm_from_string(time_base, None, script, "[test]")?;
+        let parameters = rhythm.borrow().parameters();
+        assert_eq!(
+            parameters
+                .iter()
+                .map(|p| (p.id(), p.value(), p.range().clone()))
+                .collect::<Vec<_>>(),
+            vec![("density", 0.5, 0.0..=1.0), ("section", 1.0, 1.0..=4.0)]
+        );
+        assert_eq!(parameters[1].name(), "Section");
+        // changes get clamped, rounded and reported
+        rhythm.borrow_mut().run();
+        assert_eq!(
+            rhythm.borrow_mut().take_parameter_changes(),
+            vec![("section".to_string(), 2.0)]
+        );
+        rhythm.borrow_mut().run();
+        rhythm.borrow_mut().run();
+        rhythm.borrow_mut().run();
+        assert_eq!(
+            rhythm.borrow_mut().take_parameter_changes(),
+            vec![("section".to_string(), 4.0)]
+        );
+        assert!(rhythm.borrow_mut().take_parameter_changes().is_empty());
+
+        // set via context:set_parameter
+        let script = r#"
+            return rhythm {
+                parameters = { section = { default = 1, min = 1, max = 4, integer = true } },
+                emit = function(context)
+                    local applied = context:set_parameter("section", 10) == 4
+                    local missing = pcall(function() context:set_parameter("missing", 1) end)
+                    return (applied and not missing) and "c4" or "off"
+                end
+            }
+        "#;
+        let rhythm = new_rhythm_from_string(time_base, None, script, "[test]")?;
+        match rhythm.borrow_mut().run().and_then(|event| event.event) {
+            Some(Event::NoteEvents(notes)) => {
+                assert_eq!(notes[0].as_ref().map(|n| n.note), Some(Note::C4));
+            }
+            _ => panic!("expected a note event"),
+        }
+        assert_eq!(
+            rhythm.borrow_mut().take_parameter_changes(),
+            vec![("section".to_string(), 4.0)]
+        );
+
+        // invalid declarations and assignments
+        for script in [
+            r#"return rhythm { parameters = 1, emit = "c4" }"#,
+            r#"return rhythm { parameters = { a = "x" }, emit = "c4" }"#,
+            r#"return rhythm { parameters = { a = { min = 0, max = 1 } }, emit = "c4" }"#,
+            r#"return rhythm { parameters = { a = { default = 0, min = 1, max = 0 } }, emit = "c4" }"#,
+        ] {
+            assert!(new_rhythm_from_string(time_base, None, script, "[test]").is_err());
+        }
+        Ok(())
+    }
+}
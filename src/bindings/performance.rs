@@ -0,0 +1,257 @@
+use mlua::prelude::*;
+
+use crate::{phrase::RhythmIndex, prelude::*};
+
+use super::unwrap::{bad_argument_error, validate_table_properties};
+
+// ---------------------------------------------------------------------------------------------
+
+impl LuaUserData for PerformanceMacro {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_lua, this| -> LuaResult<String> {
+            Ok(this.name().to_string())
+        });
+        fields.add_field_method_get("value", |_lua, this| -> LuaResult<LuaNumber> {
+            Ok(this.value())
+        });
+        fields.add_field_method_get("target_values", |lua, this| -> LuaResult<LuaTable> {
+            lua.create_sequence_from(this.target_values())
+        });
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+
+// Create a performance macro from a `{ name, value?, targets }` table.
+pub(crate) fn performance_macro_from_table(table: &LuaTable) -> LuaResult<PerformanceMacro> {
+    validate_table_properties(table, &["name", "value", "targets"])?;
+    let name = table.get::<_, LuaValue>("name")?;
+    let name = name.as_str().ok_or_else(|| {
+        bad_argument_error(
+            "performance_macro",
+            "name",
+            1,
+            "expecting a string as macro 'name'",
+        )
+    })?;
+    let value = table.get::<_, Option<LuaNumber>>("value")?.unwrap_or(0.0);
+    let targets = match table.get::<_, LuaValue>("targets")? {
+        LuaValue::Table(targets) => targets
+            .sequence_values::<LuaTable>()
+            .map(|target| macro_target_from_table(&target?))
+            .collect::<LuaResult<Vec<_>>>()?,
+        value => {
+            return Err(bad_argument_error(
+                "performance_macro",
+                "targets",
+                1,
+                &format!(
+                    "expecting an array of target tables as macro 'targets', got '{}'",
+                    value.type_name()
+                ),
+            ))
+        }
+    };
+    Ok(PerformanceMacro::new(name, targets).with_value(value))
+}
+
+// Unwrap one or more performance macros from a macro userdata or an array of macros.
+pub(crate) fn performance_macros_from_value(value: &LuaValue) -> LuaResult<Vec<PerformanceMacro>> {
+    if let Some(userdata) = value.as_userdata() {
+        if let Ok(performance_macro) = userdata.borrow::<PerformanceMacro>() {
+            return Ok(vec![performance_macro.clone()]);
+        }
+    } else if let Some(table) = value.as_table() {
+        return table
+            .clone()
+            .sequence_values::<LuaValue>()
+            .map(|value| match value? {
+                LuaValue::Table(table) => performance_macro_from_table(&table),
+                value => performance_macros_from_value(&value)?
+                    .pop()
+                    .ok_or_else(|| LuaError::runtime("invalid performance macro")),
+            })
+            .collect();
+    }
+    Err(LuaError::FromLuaConversionError {
+        from: value.type_name(),
+        to: "performance_macro",
+        message: Some("expecting a performance macro or an array of macros".to_string()),
+    })
+}
+
+// Create a macro target from a `{ parameter|mute|delay|context, min?, max?, curve? }` table.
+fn macro_target_from_table(table: &LuaTable) -> LuaResult<MacroTarget> {
+    validate_table_properties(
+        table,
+        &[
+            "parameter",
+            "mute",
+            "delay",
+            "context",
+            "min",
+            "max",
+            "curve",
+        ],
+    )?;
+    let min = table.get::<_, Option<LuaNumber>>("min")?.unwrap_or(0.0);
+    let max = table.get::<_, Option<LuaNumber>>("max")?.unwrap_or(1.0);
+    let curve = match table.get::<_, Option<LuaString>>("curve")? {
+        None => ParameterRampCurve::Linear,
+        Some(curve) => match curve.to_str()? {
+            "linear" => ParameterRampCurve::Linear,
+            "exponential" => ParameterRampCurve::Exponential,
+            curve => {
+                return Err(bad_argument_error(
+                    "performance_macro",
+                    "curve",
+                    1,
+                    &format!(
+                        "invalid target curve '{}': expecting 'linear' or 'exponential'",
+                        curve
+                    ),
+                ))
+            }
+        },
+    };
+    // slot indices are 1-based in Lua
+    let slot_index = |key: &str| -> LuaResult<RhythmIndex> {
+        match table.get::<_, LuaInteger>(key)? {
+            index if index >= 1 => Ok(index as RhythmIndex - 1),
+            index => Err(bad_argument_error(
+                "performance_macro",
+                key,
+                1,
+                &format!("invalid slot index '{}': expecting an integer >= 1", index),
+            )),
+        }
+    };
+    if let Some(id) = table.get::<_, Option<String>>("parameter")? {
+        Ok(MacroTarget::parameter(id, min, max).with_curve(curve))
+    } else if table.contains_key("mute")? {
+        Ok(MacroTarget::mute(slot_index("mute")?, min, max))
+    } else if table.contains_key("delay")? {
+        Ok(MacroTarget::slot_delay(slot_index("delay")?, min, max))
+    } else if let Some(name) = table.get::<_, Option<String>>("context")? {
+        Ok(MacroTarget::context(name, min, max).with_curve(curve))
+    } else {
+        Err(bad_argument_error(
+            "performance_macro",
+            "targets",
+            1,
+            "expecting a 'parameter', 'mute', 'delay' or 'context' key in macro targets",
+        ))
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bindings::*;
+
+    fn new_test_engine() -> LuaResult<Lua> {
+        // create a new engine and register bindings
+        let (mut lua, mut timeout_hook) = new_engine()?;
+        register_bindings(
+            &mut lua,
+            &timeout_hook,
+            &BeatTimeBase {
+                beats_per_min: 120.0,
+                beats_per_bar: 4,
+                samples_per_sec: 44100,
+            },
+        )?;
+        timeout_hook.reset();
+        Ok(lua)
+    }
+
+    #[test]
+    fn performance_macro() -> LuaResult<()> {
+        let lua = new_test_engine()?;
+
+        // invalid definitions
+        assert!(lua
+            .load(r#"performance_macro { targets = {} }"#)
+            .exec()
+            .is_err());
+        assert!(lua
+            .load(r#"performance_macro { name = "a", targets = { { mute = 0 } } }"#)
+            .exec()
+            .is_err());
+        assert!(lua
+            .load(r#"performance_macro { name = "a", targets = { { unknown = 1 } } }"#)
+            .exec()
+            .is_err());
+        assert!(lua
+            .load(
+                r#"performance_macro { name = "a", targets = { { context = "x", curve = "y" } } }"#
+            )
+            .exec()
+            .is_err());
+
+        // valid definitions
+        lua.load(
+            r#"
+                test_macro = performance_macro {
+                    name = "morph",
+                    value = 0.5,
+                    targets = {
+                        { parameter = "0.0.volume", min = 0.5 },
+                        { mute = 2, max = 0.25 },
+                        { delay = 3, max = 0.1 },
+                        { context = "cutoff", min = 100, max = 10000, curve = "exponential" },
+                    }
+                }
+                "#,
+        )
+        .exec()?;
+        let performance_macro = lua.load(r#"return test_macro"#).eval::<LuaValue>()?;
+        let performance_macros = performance_macros_from_value(&performance_macro)?;
+        assert_eq!(
+            performance_macros,
+            vec![PerformanceMacro::new(
+                "morph",
+                vec![
+                    MacroTarget::parameter("0.0.volume", 0.5, 1.0),
+                    MacroTarget::mute(1, 0.0, 0.25),
+                    MacroTarget::slot_delay(2, 0.0, 0.1),
+                    MacroTarget::context("cutoff", 100.0, 10000.0)
+                        .with_curve(ParameterRampCurve::Exponential),
+                ]
+            )
+            .with_value(0.5)]
+        );
+        assert_eq!(
+            lua.load(r#"return test_macro.name"#).eval::<String>()?,
+            "morph"
+        );
+        assert_eq!(lua.load(r#"return test_macro.value"#).eval::<f64>()?, 0.5);
+        assert_eq!(
+            lua.load(r#"return test_macro.target_values"#)
+                .eval::<Vec<f64>>()?,
+            vec![0.75, 0.0, 0.05, 1000.0]
+        );
+
+        // arrays of plain tables
+        let performance_macros = lua
+            .load(
+                r#"
+                return {
+                    { name = "a", targets = { { mute = 1 } } },
+                    performance_macro { name = "b", targets = {} },
+                }
+                "#,
+            )
+            .eval::<LuaValue>()?;
+        assert_eq!(
+            performance_macros_from_value(&performance_macros)?
+                .iter()
+                .map(|performance_macro| performance_macro.name().to_string())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    ops::{Bound, RangeBounds, RangeInclusive},
+    rc::Rc,
+};
+
+use mlua::prelude::*;
+
+use crate::{
+    bindings::LuaAppData,
+    warning::{ValueRangePolicy, WarningCollector, WarningKind},
+    Warning,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+thread_local! {
+    // value ranges of the Lua engines which currently run on this thread: nested when an
+    // engine's callback gets invoked while another one is running.
+    static ACTIVE_VALUE_RANGES: RefCell<Vec<LuaValueRange>> = const { RefCell::new(Vec::new()) };
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Value range policy and clamp warnings of a single Lua engine. Cloned instances share the
+/// same state, so all callbacks of an engine report into the same warnings.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LuaValueRange {
+    inner: Rc<RefCell<LuaValueRangeInner>>,
+}
+
+#[derive(Debug, Default)]
+struct LuaValueRangeInner {
+    policy: ValueRangePolicy,
+    warnings: WarningCollector,
+}
+
+impl LuaValueRange {
+    /// Access the value range of the given engine.
+    pub fn of_engine(lua: &Lua) -> Self {
+        lua.app_data_ref::<LuaAppData>()
+            .map(|app_data| app_data.value_range.clone())
+            .unwrap_or_default()
+    }
+
+    /// The engine's policy.
+    pub fn policy(&self) -> ValueRangePolicy {
+        self.inner.borrow().policy
+    }
+
+    /// Set a new policy for the engine. Applies to all following value conversions.
+    pub fn set_policy(&self, policy: ValueRangePolicy) {
+        self.inner.borrow_mut().policy = policy;
+    }
+
+    /// Fetch and clear all pending clamp warnings of the engine.
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        self.inner.borrow_mut().warnings.take()
+    }
+
+    /// Apply the engine's policy to all value conversions, until the returned scope is dropped.
+    #[must_use]
+    pub fn enter(&self) -> LuaValueRangeScope {
+        ACTIVE_VALUE_RANGES.with(|ranges| ranges.borrow_mut().push(self.clone()));
+        LuaValueRangeScope { _private: () }
+    }
+
+    fn add_clamp_warning(&self, name: &str, value: impl Display, clamped: impl Display) {
+        self.inner.borrow_mut().warnings.add(
+            WarningKind::Clamped,
+            format!("{} value '{}' got clamped to '{}'", name, value, clamped),
+        );
+    }
+}
+
+/// Guard which makes a [`LuaValueRange`] the active one, see [`LuaValueRange::enter`].
+pub(crate) struct LuaValueRangeScope {
+    _private: (),
+}
+
+impl Drop for LuaValueRangeScope {
+    fn drop(&mut self) {
+        ACTIVE_VALUE_RANGES.with(|ranges| ranges.borrow_mut().pop());
+    }
+}
+
+/// The value range of the engine which currently runs. Without a running engine, values
+/// get converted strictly.
+fn active_value_range() -> Option<LuaValueRange> {
+    ACTIVE_VALUE_RANGES.with(|ranges| ranges.borrow().last().cloned())
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Apply the running engine's value range policy to the given float value. Returns the value,
+/// or the clamped value and reports a warning in clamp mode. Returns None when the value is
+/// out of range in strict mode, or is not a number, so the caller can raise its error.
+pub(crate) fn range_checked_float<Range>(name: &str, value: f32, range: &Range) -> Option<f32>
+where
+    Range: RangeBounds<f32>,
+{
+    if range.contains(&value) {
+        return Some(value);
+    }
+    let value_range = active_value_range()?;
+    if value.is_nan() || value_range.policy() == ValueRangePolicy::Strict {
+        return None;
+    }
+    let mut clamped = value;
+    match range.start_bound() {
+        Bound::Included(start) => clamped = clamped.max(*start),
+        Bound::Excluded(start) => clamped = clamped.max(*start + f32::EPSILON),
+        Bound::Unbounded => (),
+    }
+    match range.end_bound() {
+        Bound::Included(end) => clamped = clamped.min(*end),
+        Bound::Excluded(end) => clamped = clamped.min(*end - f32::EPSILON),
+        Bound::Unbounded => (),
+    }
+    value_range.add_clamp_warning(name, value, clamped);
+    Some(clamped)
+}
+
+/// Apply the running engine's value range policy to the given integer value, see
+/// [`range_checked_float`].
+pub(crate) fn range_checked_integer<T>(name: &str, value: T, range: &RangeInclusive<T>) -> Option<T>
+where
+    T: Ord + Copy + Display,
+{
+    if range.contains(&value) {
+        return Some(value);
+    }
+    let value_range = active_value_range()?;
+    if value_range.policy() == ValueRangePolicy::Strict {
+        return None;
+    }
+    let clamped = value.clamp(*range.start(), *range.end());
+    value_range.add_clamp_warning(name, value, clamped);
+    Some(clamped)
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        bindings::unwrap::{
+            delay_value_from_string, instrument_from_cycle_target, note_event_from_number,
+            volume_value_from_string,
+        },
+        event::{new_note, InstrumentId},
+        tidal::Target,
+        Note,
+    };
+
+    #[test]
+    fn policy() {
+        // strict is the default and used without a running engine
+        let value_range = LuaValueRange::default();
+        assert_eq!(value_range.policy(), ValueRangePolicy::Strict);
+        value_range.set_policy(ValueRangePolicy::Clamp);
+        assert!(volume_value_from_string("2").is_err());
+        {
+            let _scope = LuaValueRange::default().enter();
+            assert!(volume_value_from_string("2").is_err());
+            assert!(note_event_from_number(200).is_err());
+            assert!(instrument_from_cycle_target(&Target::Index(-1)).is_err());
+        }
+        {
+            let _scope = value_range.enter();
+            assert_eq!(volume_value_from_string("2").ok(), Some(1.0));
+            assert!(delay_value_from_string("1").is_ok_and(|delay| delay < 1.0));
+            assert_eq!(
+                note_event_from_number(200).ok(),
+                Some(new_note(Note::from(0x7f_u8)))
+            );
+            assert_eq!(
+                instrument_from_cycle_target(&Target::Index(-1)).ok(),
+                Some(Some(InstrumentId::from(0)))
+            );
+            // nested scopes of other engines apply their own policy
+            let _nested_scope = LuaValueRange::default().enter();
+            assert!(volume_value_from_string("2").is_err());
+        }
+        assert!(note_event_from_number(200).is_err());
+        let warnings = value_range.take_warnings();
+        assert_eq!(warnings.len(), 4);
+        assert_eq!(
+            warnings[0],
+            Warning::new(WarningKind::Clamped, "volume value '2' got clamped to '1'")
+        );
+        assert!(value_range.take_warnings().is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use mlua::prelude::*;
+
+use crate::{event::NoteEvent, Scale};
+
+use super::unwrap::{bad_argument_error, note_events_from_value};
+
+// ---------------------------------------------------------------------------------------------
+
+/// Weighted note pool Userdata in bindings
+#[derive(Clone, Debug)]
+pub struct PoolUserData {
+    pub pool: Vec<(Vec<Option<NoteEvent>>, f32)>,
+    pub avoid_repetition: bool,
+    pub scale: Option<Scale>,
+    pub rand_seed: Option<[u8; 32]>,
+}
+
+impl PoolUserData {
+    pub fn from(table: LuaTable, rand_seed: Option<[u8; 32]>) -> LuaResult<Self> {
+        // validate option keys: pool entries use integer keys
+        const POOL_OPTIONS: [&str; 2] = ["avoid_repetition", "scale"];
+        for (key, _) in table.clone().pairs::<LuaValue, LuaValue>().flatten() {
+            if let Some(key) = key.as_str() {
+                if !POOL_OPTIONS.contains(&key) {
+                    return Err(LuaError::RuntimeError(format!(
+                        "invalid/unknown table property: '{}'. valid properties are: '{}'",
+                        key,
+                        POOL_OPTIONS.join(", ")
+                    )));
+                }
+            }
+        }
+        // entries
+        let mut pool = Vec::new();
+        for (index, entry) in table.clone().sequence_values::<LuaValue>().enumerate() {
+            let entry = entry?;
+            let (note_value, weight) = match &entry {
+                LuaValue::Table(entry_table) if entry_table.raw_len() > 0 => {
+                    let note_value = entry_table.raw_get::<_, LuaValue>(1)?;
+                    let weight = match entry_table.raw_get::<_, LuaValue>(2)? {
+                        LuaValue::Nil => 1.0,
+                        LuaValue::Integer(weight) => weight as f32,
+                        LuaValue::Number(weight) => weight as f32,
+                        _ => {
+                            return Err(bad_argument_error(
+                                "pool",
+                                "weight",
+                                index + 1,
+                                "pool weights must be numbers",
+                            ))
+                        }
+                    };
+                    (note_value, weight)
+                }
+                _ => (entry.clone(), 1.0),
+            };
+            if !(weight >= 0.0 && weight.is_finite()) {
+                return Err(bad_argument_error(
+                    "pool",
+                    "weight",
+                    index + 1,
+                    "pool weights must be >= 0",
+                ));
+            }
+            pool.push((note_events_from_value(&note_value, Some(index))?, weight));
+        }
+        if pool.is_empty() {
+            return Err(bad_argument_error(
+                "pool",
+                "entries",
+                1,
+                "pool needs at least one {note, weight} entry",
+            ));
+        }
+        if pool.iter().all(|(_, weight)| *weight == 0.0) {
+            return Err(bad_argument_error(
+                "pool",
+                "weight",
+                1,
+                "pool needs at least one entry with a weight > 0",
+            ));
+        }
+        // options
+        let mut avoid_repetition = false;
+        if table.contains_key("avoid_repetition")? {
+            avoid_repetition = table.get::<_, bool>("avoid_repetition").map_err(|_| {
+                bad_argument_error(
+                    "pool",
+                    "avoid_repetition",
+                    1,
+                    "avoid_repetition must be a boolean",
+                )
+            })?;
+        }
+        let mut scale = None;
+        if table.contains_key("scale")? {
+            let value = table.get::<_, LuaValue>("scale")?;
+            if let Some(userdata) = value.as_userdata() {
+                if let Ok(value) = userdata.borrow::<Scale>() {
+                    scale = Some(value.clone());
+                }
+            }
+            if scale.is_none() {
+                return Err(bad_argument_error(
+                    "pool",
+                    "scale",
+                    1,
+                    "scale must be a scale object",
+                ));
+            }
+        }
+        Ok(PoolUserData {
+            pool,
+            avoid_repetition,
+            scale,
+            rand_seed,
+        })
+    }
+}
+
+impl LuaUserData for PoolUserData {
+    // PoolUserData is only passed through ATM
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{
+        bindings::*,
+        event::{new_note, Event},
+        rhythm::beat_time::BeatTimeRhythm,
+        Note,
+    };
+
+    fn new_test_engine() -> LuaResult<Lua> {
+        let (mut lua, mut timeout_hook) = new_engine()?;
+        register_bindings(
+            &mut lua,
+            &timeout_hook,
+            &BeatTimeBase {
+                beats_per_min: 120.0,
+                beats_per_bar: 4,
+                samples_per_sec: 44100,
+            },
+        )?;
+        timeout_hook.reset();
+        Ok(lua)
+    }
+
+    #[test]
+    fn pool() -> LuaResult<()> {
+        let lua = new_test_engine()?;
+
+        assert!(lua.load(r#"pool {}"#).eval::<LuaValue>().is_err());
+        assert!(lua
+            .load(r#"pool { {"c4", 0} }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"pool { {"c4", -1} }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"pool { {"c4", "x"} }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"pool { {"c4", 1}, scale = "c" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"pool { {"c4", 1}, wurst = true }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let pool = lua
+            .load(
+                r#"pool {
+                    {"c4", 3}, {"e4'maj", 1}, "g4",
+                    avoid_repetition = true, scale = scale("c", "major")
+                }"#,
+            )
+            .eval::<LuaValue>()?;
+        let pool = pool.as_userdata().unwrap().borrow::<PoolUserData>()?;
+        assert_eq!(
+            pool.pool,
+            vec![
+                (vec![new_note(Note::C4)], 3.0),
+                (
+                    vec![new_note(Note::E4), new_note(Note::Gs4), new_note(Note::B4)],
+                    1.0
+                ),
+                (vec![new_note(Note::G4)], 1.0),
+            ]
+        );
+        assert!(pool.avoid_repetition);
+        assert!(pool.scale.is_some());
+
+        let rhythm = lua
+            .load(r#"rhythm { emit = pool { {"c4", 1}, {"d4", 1}, avoid_repetition = true } }"#)
+            .eval::<LuaValue>()?;
+        let mut rhythm = rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        let notes = (0..8)
+            .filter_map(|_| rhythm.next())
+            .filter_map(|e| match e.event {
+                Some(Event::NoteEvents(notes)) => notes[0].clone(),
+                _ => None,
+            })
+            .map(|n: NoteEvent| n.note)
+            .collect::<Vec<_>>();
+        assert_eq!(notes.len(), 8);
+        assert!(notes.windows(2).all(|w| w[0] != w[1]));
+        Ok(())
+    }
+}
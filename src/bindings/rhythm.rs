@@ -4,18 +4,24 @@ use mlua::prelude::*;
 
 use crate::{
     event::InstrumentId,
-    rhythm::{beat_time::BeatTimeRhythm, second_time::SecondTimeRhythm, Rhythm},
+    rhythm::{
+        beat_time::BeatTimeRhythm, chain::ChainRhythm, second_time::SecondTimeRhythm, Rhythm,
+    },
 };
 
 // ---------------------------------------------------------------------------------------------
 
 mod beat_time;
+mod chain;
+mod registered;
 mod second_time;
 
+pub(crate) use registered::RegisteredRhythm;
+
 // ---------------------------------------------------------------------------------------------
 
-// unwrap a BeatTimeRhythm or SecondTimeRhythm from the given LuaValue,
-// which is expected to be a user data
+// unwrap a BeatTimeRhythm, SecondTimeRhythm, ChainRhythm or RegisteredRhythm from the given
+// LuaValue, which is expected to be a user data
 pub(crate) fn rhythm_from_userdata(
     value: &LuaValue,
     instrument: Option<InstrumentId>,
@@ -29,6 +35,13 @@ pub(crate) fn rhythm_from_userdata(
             Ok(Rc::new(RefCell::new(
                 second_time_rhythm.with_instrument(instrument),
             )))
+        } else if let Ok(mut chain_rhythm) = user_data.take::<ChainRhythm>() {
+            chain_rhythm.set_instrument(instrument);
+            Ok(Rc::new(RefCell::new(chain_rhythm)))
+        } else if let Ok(registered_rhythm) = user_data.take::<RegisteredRhythm>() {
+            let rhythm = registered_rhythm.into_rhythm();
+            rhythm.borrow_mut().set_instrument(instrument);
+            Ok(rhythm)
         } else {
             Err(LuaError::ToLuaConversionError {
                 from: "userdata",
@@ -54,11 +67,15 @@ pub(crate) fn rhythm_from_userdata(
 
 #[cfg(test)]
 mod test {
+    use super::rhythm_from_userdata;
     use crate::{
         bindings::*,
-        event::{Event, NoteEvent},
+        event::{Event, EventData, EventDataValue, NoteEvent, ParameterChangeEvent, ParameterId},
         note::Note,
-        rhythm::{beat_time::BeatTimeRhythm, second_time::SecondTimeRhythm, RhythmIterItem},
+        rhythm::{
+            beat_time::BeatTimeRhythm, second_time::SecondTimeRhythm, Rhythm, RhythmIter,
+            RhythmIterItem,
+        },
         time::BeatTimeStep,
         PulseIterItem,
     };
@@ -142,7 +159,8 @@ mod test {
                     note: Note::C6,
                     volume: 1.0,
                     panning: 0.0,
-                    delay: 0.0
+                    delay: 0.0,
+                    extra: None
                 })])),
                 duration: 11025
             })
@@ -238,7 +256,8 @@ mod test {
                     note: Note::C4,
                     volume: 1.0,
                     panning: 0.0,
-                    delay: 0.0
+                    delay: 0.0,
+                    extra: None
                 })])),
                 duration: 11025,
             })
@@ -251,6 +270,644 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn beat_time_groove() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { unit = "1/16", groove = "wurst" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { unit = "1/16", groove = 1 }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "1/16",
+                    groove = groove("tresillo"),
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        assert_eq!(
+            (0..4)
+                .map(|_| beat_time_rhythm.next().map(|e| (e.time, e.duration)))
+                .collect::<Vec<_>>(),
+            vec![
+                Some((0, 16537)),
+                Some((16537, 16537)),
+                Some((33075, 11025)),
+                Some((44100, 16537))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_humanize() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { unit = "1/16", humanize = { wurst = 0.5 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { unit = "1/16", humanize = { volume = 2.0 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "1/16",
+                    humanize = { volume = 0.25, timing = 0.5 },
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        for _ in 0..16 {
+            let event = beat_time_rhythm.next().and_then(|e| e.event);
+            if let Some(Event::NoteEvents(note_events)) = event {
+                let note_event = note_events[0].as_ref().unwrap();
+                assert!((0.75..=1.0).contains(&note_event.volume));
+                assert!((0.0..=0.5).contains(&note_event.delay));
+                assert_eq!(note_event.panning, 0.0);
+            } else {
+                panic!("expected note events");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_panning() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { panning = { strategy = "wurst" } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { panning = { strategy = "spread", width = 2 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { panning = "alternate" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let pannings = |script: &str| -> LuaResult<Vec<Vec<f32>>> {
+            let rhythm = lua.load(script).eval::<LuaValue>()?;
+            let mut rhythm = rhythm
+                .as_userdata()
+                .unwrap()
+                .borrow_mut::<BeatTimeRhythm>()?;
+            Ok((0..2)
+                .map(|_| match rhythm.next().and_then(|e| e.event) {
+                    Some(Event::NoteEvents(note_events)) => note_events
+                        .into_iter()
+                        .flatten()
+                        .map(|n| n.panning)
+                        .collect(),
+                    _ => panic!("expected note events"),
+                })
+                .collect())
+        };
+        assert_eq!(
+            pannings(
+                r#"rhythm { panning = { strategy = "alternate", width = 0.5 }, emit = "c4" }"#
+            )?,
+            vec![vec![-0.5], vec![0.5]]
+        );
+        assert_eq!(
+            pannings(r#"rhythm { panning = { strategy = "spread" }, emit = "c4'maj" }"#)?,
+            vec![vec![-1.0, 0.0, 1.0], vec![-1.0, 0.0, 1.0]]
+        );
+        // pannings add up with emitted pannings
+        assert_eq!(
+            pannings(
+                r#"rhythm {
+                  panning = { strategy = "alternate", width = 0.5 },
+                  emit = { key = "c4", panning = 0.5 }
+                }"#
+            )?,
+            vec![vec![0.0], vec![1.0]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_echo() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { unit = "1/16", echo = { wurst = 0.5 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { unit = "1/16", echo = { repeats = 1.5 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "1/16",
+                    pattern = { 1, 0, 0, 0 },
+                    echo = { delay = 2, feedback = 0.5, transpose = 12, repeats = 1 },
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        // echo controls are exposed as parameters
+        assert_eq!(
+            beat_time_rhythm
+                .parameters()
+                .iter()
+                .map(|p| (p.id().to_string(), p.value()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("echo.delay".to_string(), 2.0),
+                ("echo.feedback".to_string(), 0.5),
+                ("echo.transpose".to_string(), 12.0),
+                ("echo.repeats".to_string(), 1.0),
+            ]
+        );
+        let notes = (0..4)
+            .map(|_| {
+                beat_time_rhythm.next().and_then(|e| match e.event {
+                    Some(Event::NoteEvents(note_events)) => note_events
+                        .last()
+                        .cloned()
+                        .flatten()
+                        .map(|n| (n.note, n.volume)),
+                    _ => None,
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            notes,
+            vec![Some((Note::C4, 1.0)), None, Some((Note::C5, 0.5)), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_resolution_automation() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { resolution = { min = 0, max = 1 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { resolution = { min = 2, max = 1 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { resolution = { default = 1 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "beats",
+                    resolution = { default = 1, min = 0.25, max = 1 },
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        assert!(beat_time_rhythm
+            .parameters()
+            .iter()
+            .any(|parameter| parameter.id() == "rhythm.resolution"));
+        let mut times = Vec::new();
+        times.extend(beat_time_rhythm.by_ref().take(2).map(|event| event.time));
+        // halve the step length: applies with the next pulse
+        assert_eq!(
+            beat_time_rhythm.set_parameter_value("rhythm.resolution", 0.5),
+            Ok(0.5)
+        );
+        times.extend(beat_time_rhythm.by_ref().take(3).map(|event| event.time));
+        assert_eq!(times, vec![0, 22050, 44100, 55125, 66150]);
+
+        // offsets use the default resolution
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "beats",
+                    resolution = { default = 0.5, min = 0.25, max = 1 },
+                    offset = 2,
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        let mut times = Vec::new();
+        times.extend(beat_time_rhythm.by_ref().take(2).map(|event| event.time));
+        assert_eq!(
+            beat_time_rhythm.set_parameter_value("rhythm.resolution", 1.0),
+            Ok(1.0)
+        );
+        times.extend(beat_time_rhythm.by_ref().take(2).map(|event| event.time));
+        assert_eq!(times, vec![22050, 33075, 44100, 66150]);
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_transpose() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { transpose = 1.5, emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { degree_shift = { degrees = 2 }, emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { degree_shift = 2, emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    transpose = 12,
+                    degree_shift = { scale = scale("c", "major"), degrees = 2 },
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        let next_note = |rhythm: &mut BeatTimeRhythm| {
+            rhythm.next().and_then(|e| match e.event {
+                Some(Event::NoteEvents(note_events)) => {
+                    note_events.first().cloned().flatten().map(|n| n.note)
+                }
+                _ => None,
+            })
+        };
+        assert_eq!(next_note(&mut beat_time_rhythm), Some(Note::E5));
+        // transpositions are read from parameters with each pulse
+        assert_eq!(
+            beat_time_rhythm.set_parameter_value("transpose", 0.0),
+            Ok(0.0)
+        );
+        assert_eq!(
+            beat_time_rhythm.set_parameter_value("degree_shift.degrees", 4.0),
+            Ok(4.0)
+        );
+        assert_eq!(next_note(&mut beat_time_rhythm), Some(Note::G4));
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_seed() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { seed = "abc", emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let triggered_pulses = |script: &str| -> LuaResult<Vec<bool>> {
+            let rhythm = lua.load(script).eval::<LuaValue>()?;
+            let mut rhythm = rhythm
+                .as_userdata()
+                .unwrap()
+                .borrow_mut::<BeatTimeRhythm>()?;
+            Ok(rhythm
+                .by_ref()
+                .take(32)
+                .map(|event| event.event.is_some())
+                .collect())
+        };
+        let script = r#"rhythm { unit = "1/16", seed = 1, pattern = { 0.5 }, emit = "c4" }"#;
+        assert_eq!(triggered_pulses(script)?, triggered_pulses(script)?);
+        assert_ne!(
+            triggered_pulses(script)?,
+            triggered_pulses(&script.replace("seed = 1", "seed = 2"))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_seed_morph() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { seed_morph = 16, emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { seed_morph = { period = 0 }, emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { seed_morph = { period = 16, amount = 2 }, emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "1/16",
+                    seed = 1,
+                    seed_morph = { period = 8, amount = 0 },
+                    pattern = { 0.5 },
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()?;
+        let mut rhythm = rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        let triggered_pulses = |rhythm: &mut BeatTimeRhythm| -> Vec<bool> {
+            rhythm
+                .by_ref()
+                .take(8)
+                .map(|event| event.event.is_some())
+                .collect()
+        };
+        // locked seeds replay the first period
+        let first_period = triggered_pulses(&mut rhythm);
+        assert_eq!(triggered_pulses(&mut rhythm), first_period);
+        assert_eq!(triggered_pulses(&mut rhythm), first_period);
+        // amount is automatable
+        assert_eq!(
+            rhythm.set_parameter_value("seed_morph.amount", 1.0),
+            Ok(1.0)
+        );
+        rhythm.reset();
+        assert_eq!(triggered_pulses(&mut rhythm), first_period);
+
+        // periods are counted in steps of the actual resolution
+        let rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "1/16",
+                    resolution = { default = 1, min = 0.25, max = 1 },
+                    seed = 1,
+                    seed_morph = { period = 8, amount = 0 },
+                    pattern = { 0.5 },
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()?;
+        let mut rhythm = rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        let first_period = triggered_pulses(&mut rhythm);
+        assert_eq!(
+            rhythm.set_parameter_value("rhythm.resolution", 0.5),
+            Ok(0.5)
+        );
+        assert_eq!(triggered_pulses(&mut rhythm), first_period);
+        assert_eq!(triggered_pulses(&mut rhythm), first_period);
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_probability_curve() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { gate = { curve = {} , length = 4 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { gate = { curve = { {0, 2} } , length = 4 } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { gate = { curve = { {0, 1} } } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "beats",
+                    gate = { curve = { {0, 0}, {0.49, 0}, {0.5, 1} }, length = 4 },
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        assert_eq!(
+            (0..8)
+                .filter_map(|_| beat_time_rhythm.next())
+                .filter_map(|e| e.event.map(|_| e.time))
+                .collect::<Vec<_>>(),
+            vec![44100, 66150, 132300, 154350]
+        );
+
+        // sampling
+        assert!(lua
+            .load(r#"rhythm { gate = { sampling = "pink" } }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { gate = { curve = { {0, 1} }, length = 4, sampling = "golden" } }"#)
+            .eval::<LuaValue>()
+            .is_ok());
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "beats",
+                    pattern = { 0.5 },
+                    gate = { sampling = "shuffle" },
+                    emit = "c4"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        // shuffled bags trigger exactly half of the pulses in each bag of 8 pulses
+        let beat = 22050;
+        let mut bag_triggers = [0; 2];
+        for event in beat_time_rhythm.by_ref() {
+            if event.time >= 16 * beat {
+                break;
+            }
+            if event.event.is_some() {
+                bag_triggers[(event.time / (8 * beat)) as usize] += 1;
+            }
+        }
+        assert_eq!(bag_triggers, [4, 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_pulse_volume() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { gate = { threshold = 2 }, emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { pulse_volume = 1, emit = "c4" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let beat_time_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "beats",
+                    pattern = { 1, 0, 0.5, 0.25 },
+                    gate = { threshold = 0.25 },
+                    pulse_volume = true,
+                    emit = "c4 v0.5"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()
+            .unwrap();
+        let mut beat_time_rhythm = beat_time_rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        let volumes = beat_time_rhythm
+            .by_ref()
+            .take(4)
+            .map(|item| match item.event {
+                Some(Event::NoteEvents(note_events)) => {
+                    note_events[0].as_ref().map(|note_event| note_event.volume)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(volumes, vec![Some(0.5), None, Some(0.25), None]);
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_relative_notes() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        assert!(lua
+            .load(r#"rhythm { relative_notes = "c4", emit = "+3" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { relative_notes = { reference = "x" }, emit = "+3" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { relative_notes = { reference = "c4", scale = 1 }, emit = "+3" }"#)
+            .eval::<LuaValue>()
+            .is_err());
+
+        let notes = |value: LuaValue, count: usize| -> LuaResult<Vec<Note>> {
+            let mut rhythm = value
+                .as_userdata()
+                .unwrap()
+                .borrow_mut::<BeatTimeRhythm>()?;
+            Ok(rhythm
+                .by_ref()
+                .take(count)
+                .filter_map(|item| match item.event {
+                    Some(Event::NoteEvents(note_events)) => {
+                        note_events[0].as_ref().map(|note_event| note_event.note)
+                    }
+                    _ => None,
+                })
+                .collect())
+        };
+
+        // sequences, using a custom reference and scale
+        let sequence_rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "beats",
+                    relative_notes = { reference = "e4", scale = scale("c", "major") },
+                    emit = { "+3", "+-5 v0.5", "^2", { key = "^-3" } }
+                }
+            "#,
+            )
+            .eval::<LuaValue>()?;
+        assert_eq!(
+            notes(sequence_rhythm, 5)?,
+            vec![Note::G4, Note::D4, Note::F4, Note::C4, Note::Ds4]
+        );
+
+        // cycles, using the default reference and scale
+        let cycle_rhythm = lua
+            .load(r#"rhythm { emit = cycle("c4 +7 ^1 ^-2") }"#)
+            .eval::<LuaValue>()?;
+        assert_eq!(
+            notes(cycle_rhythm, 4)?,
+            vec![Note::C4, Note::G4, Note::A4, Note::F4]
+        );
+        Ok(())
+    }
+
     #[test]
     fn second_time() -> LuaResult<()> {
         let (lua, _) = new_test_engine(130.0, 8, 48000)?;
@@ -343,11 +1000,197 @@ mod test {
                     note: Note::C4,
                     volume: 1.0,
                     panning: 0.0,
-                    delay: 0.0
+                    delay: 0.0,
+                    extra: None
                 })],),),
                 duration: 48
             })
         );
         Ok(())
     }
+
+    #[test]
+    fn chain() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 1000)?;
+
+        // invalid chains
+        assert!(lua.load(r#"chain { 2 }"#).exec().is_err());
+        assert!(lua.load(r#"chain { "x", 2 }"#).exec().is_err());
+        assert!(lua
+            .load(r#"chain { rhythm { pattern = {1} }, -1 }"#)
+            .exec()
+            .is_err());
+
+        // valid chains: repeat counts default to 1
+        let chain_rhythm = lua
+            .load(
+                r#"
+                local a = rhythm { unit = "1/4", pattern = {1, 1}, emit = "c4" }
+                local b = rhythm { unit = "1/4", emit = "e4" }
+                return chain { a, 2, b }
+            "#,
+            )
+            .eval::<LuaValue>()?;
+        let chain_rhythm = rhythm_from_userdata(&chain_rhythm, None)?;
+        let mut chain_rhythm = chain_rhythm.borrow_mut();
+        assert_eq!(chain_rhythm.pattern_step_length(), 2500.0);
+        let mut events = Vec::new();
+        while let Some(item) = chain_rhythm.run_until_time(3000) {
+            if let Some(Event::NoteEvents(note_events)) = item.event {
+                let note = note_events[0].as_ref().map(|note_event| note_event.note);
+                events.push((item.time, note));
+            }
+        }
+        assert_eq!(
+            events,
+            vec![
+                (0, Some(Note::C4)),
+                (500, Some(Note::C4)),
+                (1000, Some(Note::C4)),
+                (1500, Some(Note::C4)),
+                (2000, Some(Note::E4)),
+                (2500, Some(Note::C4)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn registered_implementations() -> LuaResult<()> {
+        use crate::{
+            event::{new_note, new_note_event},
+            pattern::fixed::ToFixedPattern,
+            registry::{register_emitter, register_pattern, register_rhythm},
+        };
+        use std::{cell::RefCell, rc::Rc};
+        let (lua, _) = new_test_engine(120.0, 4, 1000)?;
+
+        // use names, which are not used in other tests, as registries are global
+        register_pattern("bindings-test.pulse", |_| Ok(Box::new([1, 0].to_pattern())))
+            .map_err(LuaError::runtime)?;
+        register_emitter("bindings-test.note", |_| {
+            Ok(Box::new(new_note_event(Note::C6)))
+        })
+        .map_err(LuaError::runtime)?;
+
+        assert!(lua
+            .load(r#"rhythm { pattern = "bindings-test.unknown" }"#)
+            .exec()
+            .is_err());
+        assert!(lua
+            .load(r#"rhythm { gate = "bindings-test.pulse" }"#)
+            .exec()
+            .is_err());
+
+        let rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "1/4",
+                    pattern = "bindings-test.pulse",
+                    emit = "bindings-test.note"
+                }
+            "#,
+            )
+            .eval::<LuaValue>()?;
+        let rhythm = rhythm_from_userdata(&rhythm, None)?;
+        let mut rhythm = rhythm.borrow_mut();
+        let mut events = Vec::new();
+        while let Some(item) = rhythm.run_until_time(2000) {
+            if let Some(Event::NoteEvents(note_events)) = item.event {
+                let note = note_events[0].as_ref().map(|note_event| note_event.note);
+                events.push((item.time, note));
+            }
+        }
+        assert_eq!(events, vec![(0, Some(Note::C6)), (1000, Some(Note::C6))]);
+        drop(rhythm);
+
+        // registered rhythms get created by name, also in chains
+        register_rhythm("bindings-test.rhythm", |time_base| {
+            Ok(Rc::new(RefCell::new(
+                time_base
+                    .every_nth_beat(1.0)
+                    .trigger(new_note_event(Note::D4)),
+            )))
+        })
+        .map_err(LuaError::runtime)?;
+        assert!(lua
+            .load(r#"rhythm("bindings-test.unknown")"#)
+            .exec()
+            .is_err());
+        let rhythm = lua
+            .load(r#"return rhythm("bindings-test.rhythm")"#)
+            .eval::<LuaValue>()?;
+        let rhythm = rhythm_from_userdata(&rhythm, Some(InstrumentId::from(1)))?;
+        let item = rhythm.borrow_mut().run_until_time(1).unwrap();
+        assert_eq!(
+            item.event,
+            Some(Event::NoteEvents(vec![new_note((
+                Note::D4,
+                InstrumentId::from(1)
+            ))]))
+        );
+        let chain_rhythm = lua
+            .load(
+                r#"
+                local registered = rhythm("bindings-test.rhythm")
+                return chain { registered, 2, rhythm { unit = "1/4", emit = "e4" } }
+            "#,
+            )
+            .eval::<LuaValue>()?;
+        let chain_rhythm = rhythm_from_userdata(&chain_rhythm, None)?;
+        let mut chain_rhythm = chain_rhythm.borrow_mut();
+        let mut events = Vec::new();
+        while let Some(item) = chain_rhythm.run_until_time(1600) {
+            if let Some(Event::NoteEvents(note_events)) = item.event {
+                let note = note_events[0].as_ref().map(|note_event| note_event.note);
+                events.push((item.time, note));
+            }
+        }
+        assert_eq!(
+            events,
+            vec![
+                (0, Some(Note::D4)),
+                (500, Some(Note::D4)),
+                (1000, Some(Note::E4)),
+                (1500, Some(Note::D4)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn beat_time_parameter_changes() -> LuaResult<()> {
+        let (lua, _) = new_test_engine(120.0, 4, 44100)?;
+
+        let rhythm = lua
+            .load(
+                r#"
+                rhythm {
+                    unit = "1/4",
+                    emit = function(context)
+                      return { parameter = 2, value = 0.5, extra = { color = "red" } }
+                    end
+                }
+            "#,
+            )
+            .eval::<LuaValue>()?;
+        let mut rhythm = rhythm
+            .as_userdata()
+            .unwrap()
+            .borrow_mut::<BeatTimeRhythm>()?;
+        let event = rhythm.next().and_then(|item| item.event);
+        assert_eq!(
+            event,
+            Some(Event::ParameterChangeEvent(ParameterChangeEvent {
+                parameter: Some(ParameterId::from(2)),
+                value: 0.5,
+                extra: Some(EventData::from([(
+                    "color".to_string(),
+                    EventDataValue::from("red")
+                )]))
+            }))
+        );
+        Ok(())
+    }
 }
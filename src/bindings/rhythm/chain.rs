@@ -0,0 +1,78 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+use super::{super::unwrap::bad_argument_error, RegisteredRhythm};
+
+use crate::prelude::*;
+
+// -------------------------------------------------------------------------------------------------
+
+impl LuaUserData for ChainRhythm {
+    // ChainRhythm is only passed through ATM
+}
+
+impl ChainRhythm {
+    // create a ChainRhythm from a `{ rhythm_a, repeats?, rhythm_b, repeats?, ... }` table
+    pub(crate) fn from_table(time_base: &BeatTimeBase, table: &LuaTable) -> LuaResult<Self> {
+        let mut rhythms = Vec::<(Rc<RefCell<dyn Rhythm>>, usize)>::new();
+        let mut expect_repeats = false;
+        for (index, value) in table.clone().sequence_values::<LuaValue>().enumerate() {
+            let value = value?;
+            if let Some(repeats) = value.as_integer() {
+                if !expect_repeats {
+                    return Err(bad_argument_error(
+                        "chain",
+                        "repeats",
+                        index + 1,
+                        "repeat counts must follow a rhythm",
+                    ));
+                }
+                if repeats < 0 {
+                    return Err(bad_argument_error(
+                        "chain",
+                        "repeats",
+                        index + 1,
+                        &format!("invalid repeat count '{}': must be >= 0", repeats),
+                    ));
+                }
+                rhythms.last_mut().expect("Expecting a chain entry").1 = repeats as usize;
+                expect_repeats = false;
+            } else {
+                // repeat counts default to 1 when omitted
+                rhythms.push((chain_entry_from_value(&value, index + 1)?, 1));
+                expect_repeats = true;
+            }
+        }
+        Ok(ChainRhythm::new(*time_base, rhythms))
+    }
+}
+
+// copy a rhythm userdata, so the same rhythm can be used multiple times in a chain
+fn chain_entry_from_value(value: &LuaValue, index: usize) -> LuaResult<Rc<RefCell<dyn Rhythm>>> {
+    if let Some(user_data) = value.as_userdata() {
+        let duplicate = if let Ok(rhythm) = user_data.borrow::<BeatTimeRhythm>() {
+            Some(rhythm.duplicate())
+        } else if let Ok(rhythm) = user_data.borrow::<SecondTimeRhythm>() {
+            Some(rhythm.duplicate())
+        } else if let Ok(rhythm) = user_data.borrow::<ChainRhythm>() {
+            Some(rhythm.duplicate())
+        } else if let Ok(rhythm) = user_data.borrow::<RegisteredRhythm>() {
+            Some(rhythm.rhythm().borrow().duplicate())
+        } else {
+            None
+        };
+        if let Some(duplicate) = duplicate {
+            return duplicate.map_err(LuaError::runtime);
+        }
+    }
+    Err(bad_argument_error(
+        "chain",
+        "rhythm",
+        index,
+        &format!(
+            "expecting a rhythm or an integer repeat count, got '{}'",
+            value.type_name()
+        ),
+    ))
+}
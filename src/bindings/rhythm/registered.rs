@@ -0,0 +1,40 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+
+use crate::{registry::new_registered_rhythm, BeatTimeBase, Rhythm};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Lua userdata of a rhythm, which got created from a registered rhythm implementation, see
+/// [`register_rhythm`](crate::registry::register_rhythm).
+pub(crate) struct RegisteredRhythm {
+    rhythm: Rc<RefCell<dyn Rhythm>>,
+}
+
+impl LuaUserData for RegisteredRhythm {
+    // RegisteredRhythm is only passed through ATM
+}
+
+impl RegisteredRhythm {
+    // create a new instance of the registered rhythm with the given name
+    pub(crate) fn from_name(
+        time_base: &BeatTimeBase,
+        name: &str,
+        rand_seed: Option<[u8; 32]>,
+    ) -> LuaResult<Self> {
+        let rhythm = new_registered_rhythm(name, time_base).map_err(LuaError::runtime)?;
+        if let Some(rand_seed) = rand_seed {
+            rhythm.borrow_mut().set_seed(rand_seed);
+        }
+        Ok(Self { rhythm })
+    }
+
+    pub(crate) fn rhythm(&self) -> &Rc<RefCell<dyn Rhythm>> {
+        &self.rhythm
+    }
+
+    pub(crate) fn into_rhythm(self) -> Rc<RefCell<dyn Rhythm>> {
+        self.rhythm
+    }
+}
@@ -1,9 +1,12 @@
 use mlua::prelude::*;
 
 use super::super::{
+    groove::groove_from_value,
     unwrap::{
-        bad_argument_error, event_iter_from_value, gate_from_value, pattern_from_value,
-        pattern_repeat_count_from_value,
+        bad_argument_error, echo_from_value, event_iter_from_value, gate_from_value,
+        humanizer_from_value, panner_from_value, pattern_from_value,
+        pattern_repeat_count_from_value, relative_notes_from_value, resolution_from_value,
+        seed_morph_from_value, transposer_from_values,
     },
     LuaTimeoutHook,
 };
@@ -27,17 +30,12 @@ impl SecondTimeRhythm {
     ) -> LuaResult<SecondTimeRhythm> {
         // resolution
         let mut resolution = 1.0;
+        let mut resolution_range = None;
         if table.contains_key("resolution")? {
-            resolution = table.get::<_, f64>("resolution")?;
-            if resolution <= 0.0 {
-                return Err(bad_argument_error(
-                    "rhythm",
-                    "resolution",
-                    1,
-                    "resolution must be > 0",
-                ));
-            }
+            let value = table.get::<_, LuaValue>("resolution")?;
+            (resolution, resolution_range) = resolution_from_value(&value)?;
         }
+        let resolution_value = resolution;
         // unit
         if table.contains_key("unit")? {
             let unit = table.get::<_, String>("unit")?;
@@ -50,6 +48,9 @@ impl SecondTimeRhythm {
         }
         // create a new SecondTimeRhythm with the given time base and step
         let mut rhythm = SecondTimeRhythm::new(*time_base, resolution, rand_seed);
+        if let Some(range) = resolution_range {
+            rhythm = rhythm.with_resolution_parameter(range, resolution_value);
+        }
         // offset
         if table.contains_key("offset")? {
             let offset = table.get::<_, f32>("offset")? as SecondTimeStep;
@@ -73,7 +74,7 @@ impl SecondTimeRhythm {
         // gate
         if table.contains_key("gate")? {
             let value = table.get::<_, LuaValue>("gate")?;
-            let gate = gate_from_value(lua, timeout_hook, &value, time_base)?;
+            let gate = gate_from_value(lua, timeout_hook, &value, time_base, rand_seed)?;
             rhythm = rhythm.with_gate_dyn(gate);
         }
         // repeat
@@ -82,6 +83,60 @@ impl SecondTimeRhythm {
             let repeat = pattern_repeat_count_from_value(&value)?;
             rhythm = rhythm.with_repeat(repeat);
         }
+        // groove
+        if table.contains_key("groove")? {
+            let value = table.get::<_, LuaValue>("groove")?;
+            let groove = groove_from_value(&value)?;
+            rhythm = rhythm.with_groove(groove);
+        }
+        // humanize
+        if table.contains_key("humanize")? {
+            let value = table.get::<_, LuaValue>("humanize")?;
+            let humanizer = humanizer_from_value(&value, rand_seed)?;
+            rhythm = rhythm.with_humanizer(humanizer);
+        }
+        // panning
+        if table.contains_key("panning")? {
+            let value = table.get::<_, LuaValue>("panning")?;
+            let panner = panner_from_value(&value, rand_seed)?;
+            rhythm = rhythm.with_panner(panner);
+        }
+        // echo
+        if table.contains_key("echo")? {
+            let value = table.get::<_, LuaValue>("echo")?;
+            let echo = echo_from_value(&value)?;
+            rhythm = rhythm.with_echo(echo);
+        }
+        // transpose
+        let transpose = table.get::<_, LuaValue>("transpose")?;
+        let degree_shift = table.get::<_, LuaValue>("degree_shift")?;
+        if let Some(transposer) = transposer_from_values(&transpose, &degree_shift)? {
+            rhythm = rhythm.with_transposer(transposer);
+        }
+        // relative notes
+        if table.contains_key("relative_notes")? {
+            let value = table.get::<_, LuaValue>("relative_notes")?;
+            let relative_notes = relative_notes_from_value(&value)?;
+            rhythm = rhythm.with_relative_notes(relative_notes);
+        }
+        // seed morph
+        if table.contains_key("seed_morph")? {
+            let value = table.get::<_, LuaValue>("seed_morph")?;
+            let seed_morph = seed_morph_from_value(&value, rand_seed)?;
+            rhythm = rhythm.with_seed_morph(seed_morph);
+        }
+        // pulse volume
+        if table.contains_key("pulse_volume")? {
+            let pulse_volume = table.get::<_, bool>("pulse_volume").map_err(|_| {
+                bad_argument_error(
+                    "rhythm",
+                    "pulse_volume",
+                    1,
+                    "pulse_volume must be a boolean",
+                )
+            })?;
+            rhythm = rhythm.with_pulse_volume(pulse_volume);
+        }
         // emit
         if table.contains_key("emit")? {
             let value: LuaValue<'_> = table.get::<_, LuaValue>("emit")?;
@@ -3,7 +3,8 @@ use mlua::prelude::*;
 use super::unwrap::{
     amplify_array_from_value, bad_argument_error, delay_array_from_value,
     instrument_array_from_value, note_events_from_value, panning_array_from_value,
-    sequence_from_value, transpose_steps_array_from_value, volume_array_from_value,
+    repeat_count_array_from_value, sequence_from_value, transpose_steps_array_from_value,
+    volume_array_from_value,
 };
 
 use crate::{
@@ -53,6 +54,62 @@ impl SequenceUserData {
     }
 }
 
+impl SequenceUserData {
+    // Set volumes of all notes in the sequence's steps, validating the given values.
+    fn set_volumes(&mut self, name: &str, volumes: Vec<f32>) -> LuaResult<()> {
+        for (notes, volume) in self.notes.iter_mut().zip(volumes) {
+            if !(0.0..=1.0).contains(&volume) {
+                return Err(bad_argument_error(
+                    name,
+                    "volume",
+                    1,
+                    "volume must be in range [0.0..=1.0]",
+                ));
+            }
+            for note in notes.iter_mut().flatten() {
+                note.volume = volume;
+            }
+        }
+        Ok(())
+    }
+
+    // Set pannings of all notes in the sequence's steps, validating the given values.
+    fn set_pannings(&mut self, name: &str, pannings: Vec<f32>) -> LuaResult<()> {
+        for (notes, panning) in self.notes.iter_mut().zip(pannings) {
+            if !(-1.0..=1.0).contains(&panning) {
+                return Err(bad_argument_error(
+                    name,
+                    "panning",
+                    1,
+                    "panning must be in range [-1.0..=1.0]",
+                ));
+            }
+            for note in notes.iter_mut().flatten() {
+                note.panning = panning;
+            }
+        }
+        Ok(())
+    }
+
+    // Set delays of all notes in the sequence's steps, validating the given values.
+    fn set_delays(&mut self, name: &str, delays: Vec<f32>) -> LuaResult<()> {
+        for (notes, delay) in self.notes.iter_mut().zip(delays) {
+            if !(0.0..=1.0).contains(&delay) {
+                return Err(bad_argument_error(
+                    name,
+                    "delay",
+                    1,
+                    "delay must be in range [-1.0..=1.0]",
+                ));
+            }
+            for note in notes.iter_mut().flatten() {
+                note.delay = delay;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl LuaUserData for SequenceUserData {
     fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
         fields.add_field_method_get("notes", |lua, this| -> LuaResult<LuaTable> {
@@ -124,60 +181,73 @@ impl LuaUserData for SequenceUserData {
 
         methods.add_method_mut("with_volume", |lua, this, value: LuaValue| {
             let volumes = volume_array_from_value(lua, value, this.notes.len())?;
-            for (notes, volume) in this.notes.iter_mut().zip(volumes) {
-                if !(0.0..=1.0).contains(&volume) {
-                    return Err(bad_argument_error(
-                        "with_volume",
-                        "volume",
-                        1,
-                        "volume must be in range [0.0..=1.0]",
-                    ));
-                }
-                for note in notes.iter_mut().flatten() {
-                    note.volume = volume;
-                }
-            }
+            this.set_volumes("with_volume", volumes)?;
             Ok(this.clone())
         });
 
         methods.add_method_mut("with_panning", |lua, this, value: LuaValue| {
             let pannings = panning_array_from_value(lua, value, this.notes.len())?;
-            for (notes, panning) in this.notes.iter_mut().zip(pannings) {
-                if !(-1.0..=1.0).contains(&panning) {
-                    return Err(bad_argument_error(
-                        "with_panning",
-                        "panning",
-                        1,
-                        "panning must be in range [-1.0..=1.0]",
-                    ));
-                }
-                for note in notes.iter_mut().flatten() {
-                    note.panning = panning;
-                }
-            }
+            this.set_pannings("with_panning", pannings)?;
             Ok(this.clone())
         });
 
         methods.add_method_mut("with_delay", |lua, this, value: LuaValue| {
             let delays = delay_array_from_value(lua, value, this.notes.len())?;
-            for (notes, delay) in this.notes.iter_mut().zip(delays) {
-                if !(0.0..=1.0).contains(&delay) {
-                    return Err(bad_argument_error(
-                        "with_delay",
-                        "delay",
-                        1,
-                        "delay must be in range [-1.0..=1.0]",
-                    ));
-                }
-                for note in notes.iter_mut().flatten() {
-                    note.delay = delay;
-                }
-            }
+            this.set_delays("with_delay", delays)?;
+            Ok(this.clone())
+        });
+
+        methods.add_method_mut("volumes", |lua, this, value: LuaValue| {
+            let volumes = volume_array_from_value(lua, value, this.notes.len())?;
+            let volumes = cycled_step_values("volumes", volumes, this.notes.len())?;
+            this.set_volumes("volumes", volumes)?;
+            Ok(this.clone())
+        });
+
+        methods.add_method_mut("pannings", |lua, this, value: LuaValue| {
+            let pannings = panning_array_from_value(lua, value, this.notes.len())?;
+            let pannings = cycled_step_values("pannings", pannings, this.notes.len())?;
+            this.set_pannings("pannings", pannings)?;
+            Ok(this.clone())
+        });
+
+        methods.add_method_mut("delays", |lua, this, value: LuaValue| {
+            let delays = delay_array_from_value(lua, value, this.notes.len())?;
+            let delays = cycled_step_values("delays", delays, this.notes.len())?;
+            this.set_delays("delays", delays)?;
+            Ok(this.clone())
+        });
+
+        methods.add_method_mut("repeat_steps", |lua, this, value: LuaValue| {
+            let counts = repeat_count_array_from_value(lua, value, this.notes.len())?;
+            let counts = cycled_step_values("repeat_steps", counts, this.notes.len())?;
+            this.notes = this
+                .notes
+                .iter()
+                .zip(counts)
+                .flat_map(|(notes, count)| std::iter::repeat(notes.clone()).take(count as usize))
+                .collect();
             Ok(this.clone())
         });
     }
 }
 
+// Repeat the given per step values, so they cover all steps of a sequence.
+fn cycled_step_values<T: Copy>(name: &str, values: Vec<T>, step_count: usize) -> LuaResult<Vec<T>> {
+    if values.is_empty() {
+        if step_count > 0 {
+            return Err(bad_argument_error(
+                name,
+                "values",
+                1,
+                "value array must not be empty",
+            ));
+        }
+        return Ok(values);
+    }
+    Ok(values.into_iter().cycle().take(step_count).collect())
+}
+
 // --------------------------------------------------------------------------------------------------
 
 #[cfg(test)]
@@ -361,6 +431,45 @@ mod test {
         )
         .is_ok());
 
+        // step modifiers
+        assert_eq!(
+            evaluate_sequence_userdata(
+                &lua,
+                r#"sequence("c", "d", "e"):volumes({1.0, 0.5}):pannings(-0.5):repeat_steps({2, 1})"#
+            )?
+            .notes,
+            vec![
+                vec![new_note(("c", None, 1.0, -0.5))],
+                vec![new_note(("c", None, 1.0, -0.5))],
+                vec![new_note(("d", None, 0.5, -0.5))],
+                vec![new_note(("e", None, 1.0, -0.5))],
+                vec![new_note(("e", None, 1.0, -0.5))],
+            ]
+        );
+        assert_eq!(
+            evaluate_sequence_userdata(&lua, r#"sequence("c", "d", "e"):delays({0.25})"#)?.notes,
+            vec![
+                vec![new_note(("c", None, 1.0, 0.0, 0.25))],
+                vec![new_note(("d", None, 1.0, 0.0, 0.25))],
+                vec![new_note(("e", None, 1.0, 0.0, 0.25))],
+            ]
+        );
+        assert!(evaluate_sequence_userdata(
+            &lua, //
+            r#"sequence("c", "d", "f"):volumes({})"#
+        )
+        .is_err());
+        assert!(evaluate_sequence_userdata(
+            &lua, //
+            r#"sequence("c", "d", "f"):repeat_steps(0)"#
+        )
+        .is_err());
+        assert!(evaluate_sequence_userdata(
+            &lua, //
+            r#"sequence("c", "d", "f"):repeat_steps(0x7fffffff)"#
+        )
+        .is_err());
+
         Ok(())
     }
 }
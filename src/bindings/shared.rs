@@ -0,0 +1,98 @@
+use mlua::prelude::*;
+
+use crate::SharedValues;
+
+use super::unwrap::event_data_value_from_value;
+
+// ---------------------------------------------------------------------------------------------
+
+impl LuaUserData for SharedValues {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::Index, |lua, this, key: String| {
+            if let Some(value) = this.get(&key) {
+                value.into_lua(lua)
+            } else {
+                Ok(LuaValue::Nil)
+            }
+        });
+
+        methods.add_meta_method(
+            LuaMetaMethod::NewIndex,
+            |_lua, this, (key, value): (String, LuaValue)| {
+                if value.is_nil() {
+                    this.remove(&key);
+                } else if let Some(value) = event_data_value_from_value(&value) {
+                    this.set(key, value);
+                } else {
+                    return Err(LuaError::RuntimeError(format!(
+                        "shared value '{}' must be a boolean, number or string, but is a '{}'",
+                        key,
+                        value.type_name()
+                    )));
+                }
+                Ok(())
+            },
+        );
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use crate::{bindings::*, event::Event, prelude::*};
+
+    #[test]
+    fn shared_values() -> Result<(), Box<dyn std::error::Error>> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let drums = new_rhythm_from_string(
+            time_base,
+            None,
+            r#"
+            return rhythm {
+                unit = "1/4",
+                pattern = function(context)
+                  context.shared.drums = (context.pulse_step % 2 == 1)
+                  return 1
+                end,
+                emit = "c4"
+            }
+            "#,
+            "[drums]",
+        )?;
+        let lead = new_rhythm_from_string(
+            time_base,
+            None,
+            r#"
+            return rhythm {
+                unit = "1/4",
+                gate = function(context)
+                  return context.shared.drums == true
+                end,
+                emit = "e4"
+            }
+            "#,
+            "[lead]",
+        )?;
+        let phrase = Phrase::new(
+            time_base,
+            vec![RhythmSlot::from(drums), RhythmSlot::from(lead)],
+            BeatTimeStep::Bar(1.0),
+        );
+        let mut sequence = Sequence::new(time_base, vec![phrase]);
+        let mut lead_times = Vec::new();
+        sequence.consume_events_until_time(2000, &mut |rhythm_index, time, event, _| {
+            if let (1, Some(Event::NoteEvents(_))) = (rhythm_index, event) {
+                lead_times.push(time);
+            }
+        });
+        assert_eq!(lead_times, vec![0, 1000]);
+        // hosts can access the values too
+        assert!(sequence.shared_values().get("drums").is_some());
+        Ok(())
+    }
+}
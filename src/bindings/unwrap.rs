@@ -1,15 +1,25 @@
 //! Various lua->rust conversion helpers
 
-use std::{ops::RangeBounds, sync::Arc};
+use std::{
+    ops::{RangeBounds, RangeInclusive},
+    sync::Arc,
+};
 
 use mlua::prelude::*;
 
 use crate::{
     bindings::{
-        callback::LuaCallback, cycle::CycleUserData, note::NoteUserData,
-        sequence::SequenceUserData, LuaTimeoutHook,
+        callback::LuaCallback,
+        cycle::CycleUserData,
+        note::NoteUserData,
+        policy::{range_checked_float, range_checked_integer, LuaValueRange},
+        pool::PoolUserData,
+        sequence::SequenceUserData,
+        LuaTimeoutHook,
     },
+    event::relative::RELATIVE_NOTE_KEY,
     prelude::*,
+    tidal::Target as CycleTarget,
 };
 
 // ---------------------------------------------------------------------------------------------
@@ -102,10 +112,41 @@ impl<'lua> IntoLua<'lua> for NoteEvent {
         table.set("volume", self.volume as f64)?;
         table.set("panning", self.panning as f64)?;
         table.set("delay", self.delay as f64)?;
+        if let Some(extra) = self.extra {
+            let extra_table = lua.create_table()?;
+            for (key, value) in extra {
+                extra_table.set(key, value)?;
+            }
+            table.set("extra", extra_table)?;
+        }
         Ok(LuaValue::Table(table))
     }
 }
 
+impl<'lua> IntoLua<'lua> for EventDataValue {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            EventDataValue::Boolean(value) => value.into_lua(lua),
+            EventDataValue::Integer(value) => value.into_lua(lua),
+            EventDataValue::Number(value) => value.into_lua(lua),
+            EventDataValue::String(value) => value.into_lua(lua),
+        }
+    }
+}
+
+// Convert a boolean, number or string lua value to an event data value.
+pub(crate) fn event_data_value_from_value(value: &LuaValue) -> Option<EventDataValue> {
+    match value {
+        LuaValue::Boolean(value) => Some(EventDataValue::Boolean(*value)),
+        LuaValue::Integer(value) => Some(EventDataValue::Integer(*value as i64)),
+        LuaValue::Number(value) => Some(EventDataValue::Number(*value)),
+        LuaValue::String(value) => {
+            Some(EventDataValue::String(value.to_string_lossy().to_string()))
+        }
+        _ => None,
+    }
+}
+
 // ---------------------------------------------------------------------------------------------
 
 // Check if a lua value is a sequence (an array alike table).
@@ -145,7 +186,7 @@ fn float_array_from_value<Range>(
 where
     Range: RangeBounds<f32> + std::fmt::Debug,
 {
-    let values;
+    let mut values;
     if let Some(value_table) = value.as_table() {
         values = value_table
             .clone()
@@ -155,8 +196,10 @@ where
         let value = f32::from_lua(value, lua)?;
         values = (0..array_len).map(|_| value).collect::<Vec<f32>>();
     }
-    for value in &values {
-        if !range.contains(value) {
+    for value in &mut values {
+        if let Some(checked_value) = range_checked_float(name, *value, &range) {
+            *value = checked_value;
+        } else {
             return Err(bad_argument_error(
                 None,
                 name,
@@ -168,17 +211,14 @@ where
     Ok(values)
 }
 
-fn integer_array_from_value<Range>(
+fn integer_array_from_value(
     lua: &Lua,
     value: LuaValue,
     array_len: usize,
     name: &str,
-    range: Range,
-) -> LuaResult<Vec<i32>>
-where
-    Range: RangeBounds<i32> + std::fmt::Debug,
-{
-    let values;
+    range: RangeInclusive<i32>,
+) -> LuaResult<Vec<i32>> {
+    let mut values;
     if let Some(value_table) = value.as_table() {
         values = value_table
             .clone()
@@ -188,8 +228,10 @@ where
         let value = i32::from_lua(value, lua)?;
         values = (0..array_len).map(|_| value).collect::<Vec<i32>>();
     }
-    for value in &values {
-        if !range.contains(value) {
+    for value in &mut values {
+        if let Some(checked_value) = range_checked_integer(name, *value, &range) {
+            *value = checked_value;
+        } else {
             return Err(bad_argument_error(
                 None,
                 name,
@@ -206,7 +248,7 @@ pub(crate) fn transpose_steps_array_from_value(
     step: LuaValue,
     array_len: usize,
 ) -> LuaResult<Vec<i32>> {
-    integer_array_from_value(lua, step, array_len, "transpose_step", i32::MIN..i32::MAX)
+    integer_array_from_value(lua, step, array_len, "transpose_step", i32::MIN..=i32::MAX)
 }
 
 pub(crate) fn instrument_array_from_value(
@@ -217,6 +259,17 @@ pub(crate) fn instrument_array_from_value(
     integer_array_from_value(lua, value, array_len, "instrument", 0..=i32::MAX)
 }
 
+/// Max number of times a single sequence step can be repeated.
+const MAX_REPEAT_COUNT: i32 = 256;
+
+pub(crate) fn repeat_count_array_from_value(
+    lua: &Lua,
+    value: LuaValue,
+    array_len: usize,
+) -> LuaResult<Vec<i32>> {
+    integer_array_from_value(lua, value, array_len, "repeat_count", 1..=MAX_REPEAT_COUNT)
+}
+
 pub(crate) fn amplify_array_from_value(
     lua: &Lua,
     value: LuaValue,
@@ -267,8 +320,8 @@ where
         .as_number()
         .or(value.as_integer().map(|i| i as LuaNumber))
     {
-        if range.contains(&(value as f32)) {
-            Ok(value as f32)
+        if let Some(value) = range_checked_float(name, value as f32, &range) {
+            Ok(value)
         } else {
             Err(LuaError::RuntimeError(format!(
                 "{} property must be in range [{:?}] but is '{}'",
@@ -289,7 +342,7 @@ pub(crate) fn instrument_value_from_table(table: &LuaTable) -> LuaResult<Option<
     if value.is_nil() {
         Ok(None)
     } else if let Some(value) = value.as_integer() {
-        if value >= 0 {
+        if let Some(value) = range_checked_integer("instrument", value, &(0..=LuaInteger::MAX)) {
             Ok(Some(InstrumentId::from(value as usize)))
         } else {
             Err(LuaError::RuntimeError(format!(
@@ -306,6 +359,36 @@ pub(crate) fn instrument_value_from_table(table: &LuaTable) -> LuaResult<Option<
     }
 }
 
+pub(crate) fn extra_value_from_table(table: &LuaTable) -> LuaResult<Option<EventData>> {
+    let value = table.get::<_, LuaValue>("extra")?;
+    if value.is_nil() {
+        return Ok(None);
+    }
+    let extra_table = value
+        .as_table()
+        .ok_or_else(|| LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "table",
+            message: Some("'extra' property must be a table".to_string()),
+        })?;
+    let mut extra = EventData::new();
+    for pair in extra_table.clone().pairs::<LuaValue, LuaValue>() {
+        let (key, value) = pair?;
+        let key = key.as_str().ok_or_else(|| {
+            LuaError::RuntimeError("'extra' property keys must be strings".to_string())
+        })?;
+        let value = event_data_value_from_value(&value).ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "'extra' property '{}' must be a boolean, number or string, but is a '{}'",
+                key,
+                value.type_name()
+            ))
+        })?;
+        extra.insert(key.to_string(), value);
+    }
+    Ok(Some(extra))
+}
+
 pub(crate) fn volume_value_from_table(table: &LuaTable) -> LuaResult<f32> {
     float_value_from_table(table, "volume", 0.0..=1.0, 1.0)
 }
@@ -318,6 +401,32 @@ pub(crate) fn delay_value_from_table(table: &LuaTable) -> LuaResult<f32> {
     float_value_from_table(table, "delay", 0.0..1.0, 0.0)
 }
 
+pub(crate) fn strum_value_from_table(table: &LuaTable) -> LuaResult<(f32, StrumDirection)> {
+    let amount = float_value_from_table(table, "strum", 0.0..1.0, 0.0)?;
+    let direction = match table.get::<_, LuaValue>("strum_direction")? {
+        LuaValue::Nil => StrumDirection::Up,
+        LuaValue::String(str) => match str.to_string_lossy().as_ref() {
+            "up" => StrumDirection::Up,
+            "down" => StrumDirection::Down,
+            "center" => StrumDirection::CenterOut,
+            other => {
+                return Err(LuaError::RuntimeError(format!(
+                    "'strum_direction' must be 'up', 'down' or 'center' but is '{}'",
+                    other
+                )))
+            }
+        },
+        value => {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "string",
+                message: Some("'strum_direction' property must be a string".to_string()),
+            })
+        }
+    };
+    Ok((amount, direction))
+}
+
 fn float_value_from_string<Range>(
     str: &str,
     name: &'static str,
@@ -342,7 +451,7 @@ where
                 message: Some(format!("{} property '{}' is not a number", name, str)),
             });
         }
-        if range.contains(&value) {
+        if let Some(value) = range_checked_float(name, value, &range) {
             Ok(value)
         } else {
             Err(LuaError::RuntimeError(format!(
@@ -357,13 +466,14 @@ pub(crate) fn instrument_value_from_string(str: &str) -> LuaResult<Option<Instru
     if str.is_empty() {
         Ok(None)
     } else if let Ok(value) = str.parse::<LuaInteger>() {
-        if value < 0 {
-            return Err(LuaError::RuntimeError(format!(
+        if let Some(value) = range_checked_integer("instrument", value, &(0..=LuaInteger::MAX)) {
+            Ok(Some(InstrumentId::from(value as usize)))
+        } else {
+            Err(LuaError::RuntimeError(format!(
                 "instrument property must be >= 0 but is '{}'",
                 value
-            )));
+            )))
         }
-        Ok(Some(InstrumentId::from(value as usize)))
     } else {
         Err(LuaError::FromLuaConversionError {
             from: "string",
@@ -385,6 +495,21 @@ pub(crate) fn delay_value_from_string(str: &str) -> LuaResult<f32> {
     float_value_from_string(str, "delay", 0.0..1.0, 0.0)
 }
 
+pub(crate) fn strum_value_from_string(str: &str) -> LuaResult<(f32, StrumDirection)> {
+    // "0.1" (up), "0.1u" (up), "0.1d" (down) or "0.1c" (center out)
+    let (amount_str, direction) = if let Some(amount_str) = str.strip_suffix('u') {
+        (amount_str, StrumDirection::Up)
+    } else if let Some(amount_str) = str.strip_suffix('d') {
+        (amount_str, StrumDirection::Down)
+    } else if let Some(amount_str) = str.strip_suffix('c') {
+        (amount_str, StrumDirection::CenterOut)
+    } else {
+        (str, StrumDirection::Up)
+    };
+    let amount = float_value_from_string(amount_str, "strum", 0.0..1.0, 0.0)?;
+    Ok((amount, direction))
+}
+
 // -------------------------------------------------------------------------------------------------
 
 pub(crate) fn is_empty_note_string(s: &str) -> bool {
@@ -426,8 +551,8 @@ pub(crate) fn note_degree_from_value(arg: &LuaValue, arg_index: usize) -> LuaRes
 }
 
 pub(crate) fn note_event_from_number(note_value: LuaInteger) -> LuaResult<Option<NoteEvent>> {
-    if (0..=0x7f).contains(&note_value) {
-        Ok(new_note(note_value.clamp(0, 0x7f) as u8))
+    if let Some(note_value) = range_checked_integer("note", note_value, &(0..=0x7f)) {
+        Ok(new_note(note_value as u8))
     } else {
         Err(LuaError::RuntimeError(format!(
             "note property must be in range [0..=0x7f] but is: '{}'",
@@ -442,8 +567,13 @@ pub(crate) fn note_event_from_string(str: &str) -> LuaResult<Option<NoteEvent>>
     if is_empty_note_string(note_part) {
         Ok(None)
     } else {
-        let note =
-            Note::try_from(note_part).map_err(|err| LuaError::RuntimeError(err.to_string()))?;
+        // relative notes get resolved by the rhythm at emit time
+        let relative_note = RelativeNote::try_from(note_part).ok();
+        let note = if relative_note.is_some() {
+            Note::C4
+        } else {
+            Note::try_from(note_part).map_err(|err| LuaError::RuntimeError(err.to_string()))?
+        };
         let mut instrument = None;
         let mut volume = 1.0;
         let mut panning = 0.0;
@@ -464,7 +594,11 @@ pub(crate) fn note_event_from_string(str: &str) -> LuaResult<Option<NoteEvent>>
                 );
             }
         }
-        Ok(new_note((note, instrument, volume, panning, delay)))
+        let note_event = new_note((note, instrument, volume, panning, delay));
+        Ok(note_event.map(|note_event| NoteEvent {
+            extra: relative_note.and_then(|relative_note| relative_note.to_note_event().extra),
+            ..note_event
+        }))
     }
 }
 
@@ -482,28 +616,59 @@ pub(crate) fn note_event_from_table_map(table: &LuaTable) -> LuaResult<Option<No
         let volume = volume_value_from_table(table)?;
         let panning = panning_value_from_table(table)?;
         let delay = delay_value_from_table(table)?;
-        // { key = 60, [volume = 1.0, panning = 0.0, delay = 0.0] }
-        if let Some(note_value) = key.as_i32() {
-            Ok(new_note((
-                Note::from(note_value as u8),
-                instrument,
-                volume,
-                panning,
-                delay,
-            )))
+        let mut extra = extra_value_from_table(table)?;
+        // { key = 60, [volume = 1.0, panning = 0.0, delay = 0.0, extra = {}] }
+        let note = if let Some(note_value) = key.as_i32() {
+            let note_value =
+                range_checked_integer("note", note_value, &(0..=0x7f)).ok_or_else(|| {
+                    LuaError::RuntimeError(format!(
+                        "'key' property must be in range [0..=0x7f] but is: '{}'",
+                        note_value
+                    ))
+                })?;
+            Note::from(note_value as u8)
         }
-        // { key = "C4", [instrument = 1, volume = 1.0, panning = 0.0, delay = 0.0] }
+        // { key = "C4", [instrument = 1, volume = 1.0, panning = 0.0, delay = 0.0, extra = {}] }
         else if let Some(note_str) = key.as_str() {
-            let note =
-                Note::try_from(note_str).map_err(|err| LuaError::RuntimeError(err.to_string()))?;
-            Ok(new_note((note, instrument, volume, panning, delay)))
+            if let Ok(relative_note) = RelativeNote::try_from(note_str) {
+                // { key = "+3", ... }: relative notes get resolved by the rhythm at emit time
+                extra.get_or_insert_with(EventData::new).insert(
+                    RELATIVE_NOTE_KEY.to_string(),
+                    EventDataValue::String(relative_note.to_string()),
+                );
+                Note::C4
+            } else {
+                Note::try_from(note_str).map_err(|err| LuaError::RuntimeError(err.to_string()))?
+            }
         } else {
-            Err(LuaError::FromLuaConversionError {
+            return Err(LuaError::FromLuaConversionError {
                 from: key.type_name(),
                 to: "note",
                 message: Some("invalid 'key' property in note table".to_string()),
-            })
+            });
+        };
+        let note_event = new_note((note, instrument, volume, panning, delay));
+        Ok(note_event.map(|note_event| NoteEvent {
+            extra,
+            ..note_event
+        }))
+    }
+}
+
+pub(crate) fn instrument_from_cycle_target(
+    target: &CycleTarget,
+) -> LuaResult<Option<InstrumentId>> {
+    if let CycleTarget::Index(index) = target {
+        if let Some(index) = range_checked_integer("instrument", *index, &(0..=i32::MAX)) {
+            Ok(Some(InstrumentId::from(index as usize)))
+        } else {
+            Err(LuaError::RuntimeError(format!(
+                "cycle target instrument must be >= 0 but is '{}'",
+                index
+            )))
         }
+    } else {
+        Ok(target.into())
     }
 }
 
@@ -572,6 +737,13 @@ pub(crate) fn note_events_from_value(
                     note_events.append(&mut note_events_from_value(arg, Some(arg_index))?);
                 }
                 Ok(note_events)
+            // { key = "C4'maj" } chord map
+            } else if table
+                .get::<_, LuaValue>("key")?
+                .as_str()
+                .is_some_and(|key| key.contains('\''))
+            {
+                chord_events_from_table_map(table)
             // { key = xxx } map
             } else {
                 Ok(vec![note_event_from_value(arg, arg_index)?])
@@ -590,6 +762,85 @@ pub(crate) fn note_events_from_value(
     }
 }
 
+pub(crate) fn parameter_change_event_from_table(
+    table: &LuaTable,
+) -> LuaResult<ParameterChangeEvent> {
+    // { parameter = 1, value = 0.5, [extra = {}] }
+    let parameter = match table.get::<_, LuaValue>("parameter")? {
+        LuaValue::Nil => None,
+        value => {
+            let parameter = value
+                .as_i32()
+                .ok_or_else(|| LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "parameter",
+                    message: Some(
+                        "invalid 'parameter' property in parameter change table".to_string(),
+                    ),
+                })?;
+            let parameter = range_checked_integer("parameter", parameter, &(0..=i32::MAX))
+                .ok_or_else(|| {
+                    LuaError::RuntimeError(format!(
+                        "'parameter' property must be >= 0 but is: '{}'",
+                        parameter
+                    ))
+                })?;
+            Some(ParameterId::from(parameter as usize))
+        }
+    };
+    let value = table.get::<_, LuaValue>("value")?;
+    let value = value.as_f32().ok_or_else(|| {
+        LuaError::RuntimeError(format!(
+            "'value' property in parameter change table must be a number but is: '{}'",
+            value.type_name()
+        ))
+    })?;
+    let extra = extra_value_from_table(table)?;
+    Ok(ParameterChangeEvent {
+        parameter,
+        value,
+        extra,
+    })
+}
+
+/// Convert the given value to a parameter change event, when it's a parameter change table.
+pub(crate) fn parameter_change_event_from_value(
+    value: &LuaValue,
+) -> LuaResult<Option<ParameterChangeEvent>> {
+    match value {
+        LuaValue::Table(table)
+            if table.contains_key("parameter")? || table.contains_key("value")? =>
+        {
+            Ok(Some(parameter_change_event_from_table(table)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Convert the result of a cycle map function: sequences emit their notes one after another,
+/// parameter change tables a parameter change, and all other values a single note stack.
+pub(crate) fn cycle_map_events_from_value(value: &LuaValue) -> LuaResult<Vec<Event>> {
+    match value {
+        LuaValue::UserData(userdata) if userdata.is::<SequenceUserData>() => {
+            let sequence = userdata.borrow::<SequenceUserData>()?;
+            Ok(sequence
+                .notes
+                .iter()
+                .cloned()
+                .map(Event::NoteEvents)
+                .collect())
+        }
+        _ => {
+            if let Some(parameter_change) = parameter_change_event_from_value(value)? {
+                Ok(vec![Event::ParameterChangeEvent(parameter_change)])
+            } else {
+                let note_events = note_events_from_value(value, None)?;
+                Ok(vec![Event::NoteEvents(note_events)])
+            }
+        }
+    }
+}
+
 // -------------------------------------------------------------------------------------------------
 
 pub(crate) fn chord_events_from_string(chord_string: &str) -> LuaResult<Vec<Option<NoteEvent>>> {
@@ -601,6 +852,7 @@ pub(crate) fn chord_events_from_string(chord_string: &str) -> LuaResult<Vec<Opti
     let mut volume = 1.0;
     let mut panning = 0.0;
     let mut delay = 0.0;
+    let mut strum = (0.0, StrumDirection::Up);
     for split in white_space_splits {
         if let Some(instrument_str) = split.strip_prefix('#') {
             instrument = instrument_value_from_string(instrument_str)?;
@@ -610,26 +862,64 @@ pub(crate) fn chord_events_from_string(chord_string: &str) -> LuaResult<Vec<Opti
             panning = panning_value_from_string(panning_str)?;
         } else if let Some(delay_str) = split.strip_prefix('d') {
             delay = delay_value_from_string(delay_str)?;
+        } else if let Some(strum_str) = split.strip_prefix('s') {
+            strum = strum_value_from_string(strum_str)?;
         } else {
             return Err(LuaError::RuntimeError(
-                    format!("invalid note string segment: '{}'. ", split) +
-                        "expecting only number values with '#' (instrument),'v' (volume), 'p' (panning) or 'd' (delay) prefixes here."),
+                    format!("invalid chord string segment: '{}'. ", split) +
+                        "expecting only number values with '#' (instrument),'v' (volume), 'p' (panning), 'd' (delay) or 's' (strum) prefixes here."),
                 );
         }
     }
-    Ok(chord
-        .intervals()
-        .iter()
-        .map(|i| {
-            new_note((
-                Note::from(chord.note() as u8 + i),
-                instrument,
-                volume,
-                panning,
-                delay,
-            ))
+    let mut note_events = chord
+        .notes()
+        .into_iter()
+        .map(|note| new_note((note, instrument, volume, panning, delay)))
+        .collect::<Vec<_>>();
+    strum_chord_events(&mut note_events, strum);
+    Ok(note_events)
+}
+
+pub(crate) fn chord_events_from_table_map(table: &LuaTable) -> LuaResult<Vec<Option<NoteEvent>>> {
+    // { key = "C4'maj", [instrument = 1, volume = 1.0, panning = 0.0, delay = 0.0,
+    //   strum = 0.0, strum_direction = "up", extra = {}] }
+    let key = table.get::<_, LuaValue>("key")?;
+    let key = key
+        .as_str()
+        .ok_or_else(|| LuaError::FromLuaConversionError {
+            from: key.type_name(),
+            to: "chord",
+            message: Some("invalid 'key' property in chord table".to_string()),
+        })?;
+    let chord = Chord::try_from(key).map_err(|err| LuaError::RuntimeError(err.to_string()))?;
+    let instrument = instrument_value_from_table(table)?;
+    let volume = volume_value_from_table(table)?;
+    let panning = panning_value_from_table(table)?;
+    let delay = delay_value_from_table(table)?;
+    let strum = strum_value_from_table(table)?;
+    let extra = extra_value_from_table(table)?;
+    let mut note_events = chord
+        .notes()
+        .into_iter()
+        .map(|note| {
+            new_note((note, instrument, volume, panning, delay)).map(|note_event| NoteEvent {
+                extra: extra.clone(),
+                ..note_event
+            })
         })
-        .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    strum_chord_events(&mut note_events, strum);
+    Ok(note_events)
+}
+
+fn strum_chord_events(note_events: &mut [Option<NoteEvent>], strum: (f32, StrumDirection)) {
+    let (amount, direction) = strum;
+    if amount > 0.0 {
+        VoiceSpread::new()
+            .with_min_voices(1)
+            .with_strum(amount, direction)
+            .apply(note_events);
+    }
 }
 
 pub(crate) fn chord_events_from_mode(
@@ -641,11 +931,11 @@ pub(crate) fn chord_events_from_mode(
         let chord = Chord::try_from((note_event.note, mode))
             .map_err(|err| LuaError::RuntimeError(err.to_string()))?;
         Ok(chord
-            .intervals()
-            .iter()
-            .map(|i| {
+            .notes()
+            .into_iter()
+            .map(|note| {
                 Some(NoteEvent {
-                    note: chord.note().transposed(*i as i32),
+                    note,
                     ..note_event.clone()
                 })
             })
@@ -668,11 +958,11 @@ pub(crate) fn chord_events_from_intervals(
         let chord = Chord::try_from((note_event.note, intervals.as_slice()))
             .map_err(|err| LuaError::RuntimeError(err.to_string()))?;
         Ok(chord
-            .intervals()
-            .iter()
-            .map(|i| {
+            .notes()
+            .into_iter()
+            .map(|note| {
                 Some(NoteEvent {
-                    note: chord.note().transposed(*i as i32),
+                    note,
                     ..note_event.clone()
                 })
             })
@@ -748,6 +1038,324 @@ pub(crate) fn pattern_repeat_count_from_value(value: &LuaValue) -> LuaResult<Opt
 
 // -------------------------------------------------------------------------------------------------
 
+pub(crate) fn humanizer_from_value(
+    value: &LuaValue,
+    rand_seed: Option<[u8; 32]>,
+) -> LuaResult<EventHumanizer> {
+    if let Some(table) = value.as_table() {
+        const HUMANIZE_PROPERTIES: [&str; 4] = ["volume", "panning", "timing", "correlation"];
+        validate_table_properties(table, &HUMANIZE_PROPERTIES)?;
+        let mut humanizer = EventHumanizer::new(rand_seed);
+        for property in HUMANIZE_PROPERTIES {
+            if let Some(amount) = table.get::<_, Option<f32>>(property)? {
+                if !(0.0..=1.0).contains(&amount) {
+                    return Err(LuaError::FromLuaConversionError {
+                        from: "number",
+                        to: "humanize",
+                        message: Some(format!(
+                            "invalid '{}' value: {}, must be in range [0 - 1]",
+                            property, amount
+                        )),
+                    });
+                }
+                humanizer = match property {
+                    "volume" => humanizer.with_volume_jitter(amount),
+                    "panning" => humanizer.with_panning_jitter(amount),
+                    "timing" => humanizer.with_timing_jitter(amount),
+                    _ => humanizer.with_correlation(amount),
+                };
+            }
+        }
+        Ok(humanizer)
+    } else {
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "humanize",
+            message: Some(
+                "must be a table with 'volume', 'panning', 'timing' or 'correlation' amounts"
+                    .to_string(),
+            ),
+        })
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+pub(crate) fn panner_from_value(
+    value: &LuaValue,
+    rand_seed: Option<[u8; 32]>,
+) -> LuaResult<EventPanner> {
+    if let Some(table) = value.as_table() {
+        const PANNING_PROPERTIES: [&str; 2] = ["strategy", "width"];
+        validate_table_properties(table, &PANNING_PROPERTIES)?;
+        let strategy = match table.get::<_, Option<String>>("strategy")?.as_deref() {
+            Some("alternate") => PanningStrategy::Alternate,
+            Some("spread") => PanningStrategy::Spread,
+            Some("random") => PanningStrategy::Random,
+            strategy => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: "string",
+                    to: "panning",
+                    message: Some(format!(
+                        "invalid 'strategy' value: {}, must be one of 'alternate', 'spread' or 'random'",
+                        strategy.unwrap_or("nil")
+                    )),
+                })
+            }
+        };
+        let mut panner = EventPanner::new(strategy, rand_seed);
+        if let Some(width) = table.get::<_, Option<f32>>("width")? {
+            if !(0.0..=1.0).contains(&width) {
+                return Err(LuaError::FromLuaConversionError {
+                    from: "number",
+                    to: "panning",
+                    message: Some(format!(
+                        "invalid 'width' value: {}, must be in range [0 - 1]",
+                        width
+                    )),
+                });
+            }
+            panner = panner.with_width(width);
+        }
+        Ok(panner)
+    } else {
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "panning",
+            message: Some("must be a table with a 'strategy' and optional 'width'".to_string()),
+        })
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+// Get a rhythm's seed morph from a `{period, amount}` table.
+pub(crate) fn seed_morph_from_value(
+    value: &LuaValue,
+    rand_seed: Option<[u8; 32]>,
+) -> LuaResult<SeedMorph> {
+    if let Some(table) = value.as_table() {
+        const SEED_MORPH_PROPERTIES: [&str; 2] = ["period", "amount"];
+        validate_table_properties(table, &SEED_MORPH_PROPERTIES)?;
+        let period = table.get::<_, f64>("period")?;
+        if !(period >= 1.0 && period.is_finite()) {
+            return Err(LuaError::FromLuaConversionError {
+                from: "number",
+                to: "seed_morph",
+                message: Some(format!("invalid 'period' value: {}, must be >= 1", period)),
+            });
+        }
+        let mut seed_morph = SeedMorph::new(period, rand_seed);
+        if let Some(amount) = table.get::<_, Option<f64>>("amount")? {
+            if !(0.0..=1.0).contains(&amount) {
+                return Err(LuaError::FromLuaConversionError {
+                    from: "number",
+                    to: "seed_morph",
+                    message: Some(format!(
+                        "invalid 'amount' value: {}, must be in range [0 - 1]",
+                        amount
+                    )),
+                });
+            }
+            seed_morph = seed_morph.with_amount(amount);
+        }
+        Ok(seed_morph)
+    } else {
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "seed_morph",
+            message: Some("must be a table with a 'period' and optional 'amount'".to_string()),
+        })
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+// Get a rhythm's resolution from a number or a `{default, min, max}` table, which makes the
+// resolution automatable. Returns the resolution and its automation range, if any.
+pub(crate) fn resolution_from_value(
+    value: &LuaValue,
+) -> LuaResult<(f64, Option<RangeInclusive<f64>>)> {
+    let invalid_resolution = |message: &str| bad_argument_error("rhythm", "resolution", 1, message);
+    if let Some(table) = value.as_table() {
+        validate_table_properties(table, &["default", "min", "max"])?;
+        let min = table
+            .get::<_, f64>("min")
+            .map_err(|_| invalid_resolution("missing or invalid resolution 'min' value"))?;
+        let max = table
+            .get::<_, f64>("max")
+            .map_err(|_| invalid_resolution("missing or invalid resolution 'max' value"))?;
+        if min <= 0.0 || min > max || !max.is_finite() {
+            return Err(invalid_resolution(
+                "resolution 'min' and 'max' must be > 0 and 'min' must be <= 'max'",
+            ));
+        }
+        let default = table
+            .get::<_, Option<f64>>("default")
+            .map_err(|_| invalid_resolution("invalid resolution 'default' value"))?
+            .unwrap_or(1.0)
+            .clamp(min, max);
+        Ok((default, Some(min..=max)))
+    } else {
+        let resolution = value
+            .as_f64()
+            .ok_or_else(|| invalid_resolution("resolution must be a number or table"))?;
+        if resolution <= 0.0 {
+            return Err(invalid_resolution("resolution must be > 0"));
+        }
+        Ok((resolution, None))
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+pub(crate) fn echo_from_value(value: &LuaValue) -> LuaResult<EventEcho> {
+    if let Some(table) = value.as_table() {
+        const ECHO_PROPERTIES: [&str; 4] = ["delay", "feedback", "transpose", "repeats"];
+        validate_table_properties(table, &ECHO_PROPERTIES)?;
+        let mut echo = EventEcho::new();
+        for property in ECHO_PROPERTIES {
+            if let Some(value) = table.get::<_, Option<f64>>(property)? {
+                let (range, integer) = match property {
+                    "delay" => (0.0..=16.0, false),
+                    "feedback" => (0.0..=1.0, false),
+                    "transpose" => (-12.0..=12.0, true),
+                    _ => (0.0..=16.0, true),
+                };
+                if !range.contains(&value) || (integer && value.fract() != 0.0) {
+                    return Err(LuaError::FromLuaConversionError {
+                        from: "number",
+                        to: "echo",
+                        message: Some(format!(
+                            "invalid '{}' value: {}, must be {} in range [{} - {}]",
+                            property,
+                            value,
+                            if integer { "an integer" } else { "a number" },
+                            range.start(),
+                            range.end()
+                        )),
+                    });
+                }
+                echo = match property {
+                    "delay" => echo.with_delay(value),
+                    "feedback" => echo.with_feedback(value as f32),
+                    "transpose" => echo.with_transpose(value as i32),
+                    _ => echo.with_repeats(value as usize),
+                };
+            }
+        }
+        Ok(echo)
+    } else {
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "echo",
+            message: Some(
+                "must be a table with 'delay', 'feedback', 'transpose' or 'repeats' values"
+                    .to_string(),
+            ),
+        })
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+pub(crate) fn transposer_from_values(
+    transpose: &LuaValue,
+    degree_shift: &LuaValue,
+) -> LuaResult<Option<EventTransposer>> {
+    if transpose.is_nil() && degree_shift.is_nil() {
+        return Ok(None);
+    }
+    let mut transposer = EventTransposer::new();
+    if !transpose.is_nil() {
+        let semitones = transpose
+            .as_number()
+            .or_else(|| transpose.as_integer().map(|i| i as f64))
+            .filter(|value| (-48.0..=48.0).contains(value) && value.fract() == 0.0)
+            .ok_or_else(|| LuaError::FromLuaConversionError {
+                from: transpose.type_name(),
+                to: "transpose",
+                message: Some("must be an integer in range [-48 - 48]".to_string()),
+            })?;
+        transposer = transposer.with_transpose(semitones as i32);
+    }
+    if let Some(table) = degree_shift.as_table() {
+        const DEGREE_SHIFT_PROPERTIES: [&str; 2] = ["scale", "degrees"];
+        validate_table_properties(table, &DEGREE_SHIFT_PROPERTIES)?;
+        let scale = table
+            .get::<_, LuaValue>("scale")?
+            .as_userdata()
+            .and_then(|userdata| userdata.borrow::<Scale>().ok().map(|scale| scale.clone()))
+            .ok_or_else(|| LuaError::FromLuaConversionError {
+                from: "table",
+                to: "degree_shift",
+                message: Some("'scale' must be a scale object".to_string()),
+            })?;
+        transposer = transposer.with_scale(scale);
+        if let Some(degrees) = table.get::<_, Option<f64>>("degrees")? {
+            if !(-14.0..=14.0).contains(&degrees) || degrees.fract() != 0.0 {
+                return Err(LuaError::FromLuaConversionError {
+                    from: "number",
+                    to: "degree_shift",
+                    message: Some(format!(
+                        "invalid 'degrees' value: {}, must be an integer in range [-14 - 14]",
+                        degrees
+                    )),
+                });
+            }
+            transposer = transposer.with_degree_shift(degrees as i32);
+        }
+    } else if !degree_shift.is_nil() {
+        return Err(LuaError::FromLuaConversionError {
+            from: degree_shift.type_name(),
+            to: "degree_shift",
+            message: Some(
+                "must be a table with a 'scale' and optional 'degrees' value".to_string(),
+            ),
+        });
+    }
+    Ok(Some(transposer))
+}
+
+pub(crate) fn relative_notes_from_value(value: &LuaValue) -> LuaResult<RelativeNoteResolver> {
+    // { reference = "c4", [scale = scale("c4", "minor")] }
+    let table = value
+        .as_table()
+        .ok_or_else(|| LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "relative_notes",
+            message: Some("must be a table with a 'reference' and optional 'scale'".to_string()),
+        })?;
+    const RELATIVE_NOTES_PROPERTIES: [&str; 2] = ["reference", "scale"];
+    validate_table_properties(table, &RELATIVE_NOTES_PROPERTIES)?;
+    let reference = note_event_from_value(&table.get::<_, LuaValue>("reference")?, None)?
+        .map(|note_event| note_event.note)
+        .filter(|note| note.is_note_on())
+        .ok_or_else(|| LuaError::FromLuaConversionError {
+            from: "table",
+            to: "relative_notes",
+            message: Some("'reference' must be a note value".to_string()),
+        })?;
+    let mut resolver = RelativeNoteResolver::new(reference);
+    match table.get::<_, LuaValue>("scale")? {
+        LuaValue::Nil => (),
+        value => {
+            let scale = value
+                .as_userdata()
+                .and_then(|userdata| userdata.borrow::<Scale>().ok().map(|scale| scale.clone()))
+                .ok_or_else(|| LuaError::FromLuaConversionError {
+                    from: "table",
+                    to: "relative_notes",
+                    message: Some("'scale' must be a scale object".to_string()),
+                })?;
+            resolver = resolver.with_scale(scale);
+        }
+    }
+    Ok(resolver)
+}
+
+// -------------------------------------------------------------------------------------------------
+
 pub fn gate_trigger_from_value(value: &LuaValue) -> LuaResult<bool> {
     match value {
         LuaValue::Nil => Ok(false),
@@ -800,10 +1408,16 @@ pub(crate) fn pattern_from_value(
                 .collect::<LuaResult<Vec<Pulse>>>()?;
             Ok(Box::new(pulses.to_pattern()))
         }
+        LuaValue::String(name) => {
+            new_registered_pattern(&name.to_string_lossy(), time_base).map_err(LuaError::runtime)
+        }
         _ => Err(LuaError::FromLuaConversionError {
             from: value.type_name(),
             to: "pattern",
-            message: Some("pattern must either be an array or a function".to_string()),
+            message: Some(
+                "pattern must either be an array, a function or a registered pattern name"
+                    .to_string(),
+            ),
         }),
     }
 }
@@ -815,6 +1429,7 @@ pub(crate) fn gate_from_value(
     timeout_hook: &LuaTimeoutHook,
     value: &LuaValue,
     time_base: &BeatTimeBase,
+    rand_seed: Option<[u8; 32]>,
 ) -> LuaResult<Box<dyn Gate>> {
     match value {
         LuaValue::Function(func) => {
@@ -822,14 +1437,105 @@ pub(crate) fn gate_from_value(
             let gate = ScriptedGate::new(timeout_hook, callback, time_base)?;
             Ok(Box::new(gate))
         }
+        LuaValue::Table(table) => {
+            if table.contains_key("curve")? || table.contains_key("length")? {
+                let gate = probability_curve_gate_from_table(table, rand_seed)?;
+                Ok(Box::new(gate))
+            } else if table.contains_key("threshold")? {
+                let gate = threshold_gate_from_table(table)?;
+                Ok(Box::new(gate))
+            } else {
+                let gate = probability_gate_from_table(table, rand_seed)?;
+                Ok(Box::new(gate))
+            }
+        }
+        LuaValue::String(name) => {
+            let mut gate = new_registered_gate(&name.to_string_lossy(), time_base)
+                .map_err(LuaError::runtime)?;
+            if let Some(rand_seed) = rand_seed {
+                gate.set_seed(rand_seed);
+            }
+            Ok(gate)
+        }
         _ => Err(LuaError::FromLuaConversionError {
             from: value.type_name(),
             to: "gate",
-            message: Some("gate must either be nil or a function".to_string()),
+            message: Some(
+                "gate must either be nil, a function, a table or a registered gate name"
+                    .to_string(),
+            ),
         }),
     }
 }
 
+pub(crate) fn probability_curve_gate_from_table(
+    table: &LuaTable,
+    rand_seed: Option<[u8; 32]>,
+) -> LuaResult<ProbabilityCurveGate> {
+    validate_table_properties(table, &["curve", "length", "sampling"])?;
+    let sampling = gate_sampling_from_table(table)?;
+    let length = table.get::<_, f64>("length").map_err(|_| {
+        bad_argument_error("gate", "length", 1, "missing or invalid curve length value")
+    })?;
+    let curve = table.get::<_, LuaTable>("curve").map_err(|_| {
+        bad_argument_error("gate", "curve", 1, "missing or invalid curve points table")
+    })?;
+    let mut points = Vec::new();
+    for point in curve.sequence_values::<LuaTable>() {
+        let point = point.map_err(|_| {
+            bad_argument_error(
+                "gate",
+                "curve",
+                1,
+                "curve points must be tables of {position, probability} numbers",
+            )
+        })?;
+        let position = point.get::<_, f64>(1)?;
+        let probability = point.get::<_, f32>(2)?;
+        points.push((position, probability));
+    }
+    ProbabilityCurveGate::new(&points, length, rand_seed)
+        .map(|gate| gate.with_sampling(sampling))
+        .map_err(|err| bad_argument_error("gate", "curve", 1, &err))
+}
+
+pub(crate) fn probability_gate_from_table(
+    table: &LuaTable,
+    rand_seed: Option<[u8; 32]>,
+) -> LuaResult<ProbabilityGate> {
+    validate_table_properties(table, &["sampling"])?;
+    let sampling = gate_sampling_from_table(table)?;
+    Ok(ProbabilityGate::new(rand_seed).with_sampling(sampling))
+}
+
+pub(crate) fn threshold_gate_from_table(table: &LuaTable) -> LuaResult<ThresholdGate> {
+    validate_table_properties(table, &["threshold"])?;
+    let threshold = table
+        .get::<_, f32>("threshold")
+        .map_err(|_| bad_argument_error("gate", "threshold", 1, "threshold must be a number"))?;
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(bad_argument_error(
+            "gate",
+            "threshold",
+            1,
+            "threshold must be in range [0 - 1]",
+        ));
+    }
+    Ok(ThresholdGate::new(threshold))
+}
+
+fn gate_sampling_from_table(table: &LuaTable) -> LuaResult<GateSampling> {
+    if table.contains_key("sampling")? {
+        let sampling = table
+            .get::<_, String>("sampling")
+            .map_err(|_| bad_argument_error("gate", "sampling", 1, "sampling must be a string"))?;
+        GateSampling::from_name(&sampling)
+            .map_err(|err| bad_argument_error("gate", "sampling", 1, &err))
+    } else {
+        Ok(GateSampling::default())
+    }
+}
+
 // -------------------------------------------------------------------------------------------------
 
 pub(crate) fn event_iter_from_value(
@@ -856,13 +1562,28 @@ pub(crate) fn event_iter_from_value(
                         timeout_hook,
                         mapping_callback,
                         time_base,
-                    )?;
+                    )?
+                    .with_channel_instruments(&userdata.instruments)
+                    .with_fractional_notes(userdata.fractional_notes)
+                    .with_target_schema(userdata.target_schema.clone());
                     Ok(Box::new(event_iter))
                 } else {
                     let mappings = userdata.mappings.clone();
-                    let event_iter = ScriptedCycleEventIter::with_mappings(cycle, mappings);
+                    let event_iter = ScriptedCycleEventIter::with_mappings(cycle, mappings)
+                        .with_value_range(LuaValueRange::of_engine(lua))
+                        .with_channel_instruments(&userdata.instruments)
+                        .with_fractional_notes(userdata.fractional_notes)
+                        .with_target_schema(userdata.target_schema.clone());
                     Ok(Box::new(event_iter))
                 }
+            } else if userdata.is::<PoolUserData>() {
+                let userdata = userdata.borrow::<PoolUserData>()?;
+                let event_iter =
+                    RandomPoolEventIter::new(userdata.pool.clone(), userdata.rand_seed)
+                        .map_err(LuaError::runtime)?
+                        .with_avoid_repetition(userdata.avoid_repetition)
+                        .with_scale(userdata.scale.clone());
+                Ok(Box::new(event_iter))
             } else {
                 Err(LuaError::FromLuaConversionError {
                     from: "userdata",
@@ -886,12 +1607,20 @@ pub(crate) fn event_iter_from_value(
                 let iter = note_event_sequence.to_event_sequence();
                 Ok(Box::new(iter))
             }
+            // convert table to a single parameter change event
+            else if let Some(parameter_change) = parameter_change_event_from_value(value)? {
+                let event_iter = parameter_change.to_event();
+                Ok(Box::new(event_iter))
+            }
             // convert table to a single note event
             else {
                 let event_iter = note_event_from_value(value, None)?.to_event();
                 Ok(Box::new(event_iter))
             }
         }
+        LuaValue::String(name) if is_registered(RegistryKind::Emitter, &name.to_string_lossy()) => {
+            new_registered_emitter(&name.to_string_lossy(), time_base).map_err(LuaError::runtime)
+        }
         _ => {
             // try converting a note number or note/chord string to an event iter
             let event_iter = note_events_from_value(value, None)?.to_event();
@@ -0,0 +1,351 @@
+use std::collections::BTreeSet;
+
+use mlua::prelude::*;
+
+use super::{new_engine, register_bindings, rhythm::rhythm_from_userdata};
+
+use crate::{BeatTimeBase, RhythmParameter};
+
+// ---------------------------------------------------------------------------------------------
+
+/// Name of validated script chunks, as shown in error messages.
+const SCRIPT_NAME: &str = "script";
+
+/// Time base which is used to evaluate validated scripts.
+const VALIDATION_TIME_BASE: BeatTimeBase = BeatTimeBase {
+    beats_per_min: 120.0,
+    beats_per_bar: 4,
+    samples_per_sec: 44100,
+};
+
+/// Context fields which are set by afseq itself, so scripts don't need them from the host.
+const BUILTIN_CONTEXT_FIELDS: [&str; 17] = [
+    "beats_per_min",
+    "beats_per_bar",
+    "samples_per_sec",
+    "shared",
+    "parameters",
+    "pulse_value",
+    "pulse_time",
+    "pulse_step",
+    "pulse_time_step",
+    "step",
+    "channel",
+    "step_length",
+    "step_start",
+    "target",
+    "trigger_note",
+    "trigger_volume",
+    "trigger_offset",
+];
+
+// ---------------------------------------------------------------------------------------------
+
+/// Kind of a [`ScriptIssue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptIssueKind {
+    /// The script failed to compile.
+    SyntaxError,
+    /// A value of an unexpected type got passed to a function or returned by the script.
+    TypeError,
+    /// The script failed to evaluate.
+    RuntimeError,
+    /// The script evaluated, but produced warnings.
+    Warning,
+}
+
+/// A single issue of a [`ValidationReport`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptIssue {
+    /// The issue's kind.
+    pub kind: ScriptIssueKind,
+    /// Line number in the script, starting with 1, if known.
+    pub line: Option<usize>,
+    /// Human readable description of the issue.
+    pub message: String,
+}
+
+// ---------------------------------------------------------------------------------------------
+
+/// Result of a script validation, see [`validate_script`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    issues: Vec<ScriptIssue>,
+    parameters: Vec<RhythmParameter>,
+    required_context_fields: Vec<String>,
+}
+
+impl ValidationReport {
+    /// true when the script has no errors. Warnings don't invalidate scripts.
+    pub fn is_valid(&self) -> bool {
+        self.issues
+            .iter()
+            .all(|issue| issue.kind == ScriptIssueKind::Warning)
+    }
+
+    /// All errors and warnings of the script.
+    pub fn issues(&self) -> &[ScriptIssue] {
+        &self.issues
+    }
+
+    /// Parameters which are declared by the script's rhythm. Empty when the script failed
+    /// to evaluate.
+    pub fn parameters(&self) -> &[RhythmParameter] {
+        &self.parameters
+    }
+
+    /// Names of all `context` fields which are read by the script, but are not provided by
+    /// afseq itself, so hosts need to pass them as external context values. Sorted by name.
+    pub fn required_context_fields(&self) -> &[String] {
+        &self.required_context_fields
+    }
+}
+
+// ---------------------------------------------------------------------------------------------
+
+/// Dry-run a Lua rhythm script, e.g. to lint scripts in editors as the user types, without
+/// requiring a time base or playing the resulting rhythm.
+///
+/// The script gets compiled and evaluated in a new, temporary engine with a default time
+/// base. Syntax errors, evaluation errors and type errors of `rhythm` options are reported
+/// as issues, along with the declared parameters of the returned rhythm.
+///
+/// The returned rhythm gets built, but never runs, so errors in its callbacks, which only
+/// happen while playing, are not reported. Context fields, which the script reads, get
+/// detected by scanning the script's source for `context.field`, `context["field"]` and
+/// `context['field']` accesses, so they are also found in callbacks which did not run.
+/// Context arguments which got renamed, e.g. `function(ctx) ... end`, or got assigned to other
+/// variables, and fields which are accessed with computed keys are not detected.
+pub fn validate_script(script: &str) -> ValidationReport {
+    let mut report = ValidationReport {
+        required_context_fields: required_context_fields(script),
+        ..ValidationReport::default()
+    };
+    let result = new_engine().and_then(|(mut lua, mut timeout_hook)| {
+        register_bindings(&mut lua, &timeout_hook, &VALIDATION_TIME_BASE)?;
+        timeout_hook.reset();
+        // compile
+        let function = match lua.load(script).set_name(SCRIPT_NAME).into_function() {
+            Ok(function) => function,
+            Err(err) => {
+                report
+                    .issues
+                    .push(new_issue(ScriptIssueKind::SyntaxError, &err));
+                return Ok(());
+            }
+        };
+        // evaluate
+        let value = match function.call::<_, LuaValue>(()) {
+            Ok(value) => value,
+            Err(err) => {
+                report.issues.push(new_issue(error_kind(&err), &err));
+                return Ok(());
+            }
+        };
+        match rhythm_from_userdata(&value, None) {
+            Ok(rhythm) => {
+                let mut rhythm = rhythm.borrow_mut();
+                report.parameters = rhythm.parameters();
+                for warning in rhythm.take_warnings() {
+                    report.issues.push(ScriptIssue {
+                        kind: ScriptIssueKind::Warning,
+                        line: None,
+                        message: warning.to_string(),
+                    });
+                }
+            }
+            Err(err) => {
+                report
+                    .issues
+                    .push(new_issue(ScriptIssueKind::TypeError, &err));
+            }
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        report
+            .issues
+            .push(new_issue(ScriptIssueKind::RuntimeError, &err));
+    }
+    report
+}
+
+// ---------------------------------------------------------------------------------------------
+
+// Create a new issue from the given error and its root cause.
+fn new_issue(kind: ScriptIssueKind, err: &LuaError) -> ScriptIssue {
+    // tracebacks of callback errors contain the line of the failed call
+    let line = error_line(&err.to_string());
+    let message = root_cause(err).to_string();
+    ScriptIssue {
+        kind,
+        line,
+        message,
+    }
+}
+
+// Categorize the given evaluation error.
+fn error_kind(err: &LuaError) -> ScriptIssueKind {
+    match root_cause(err) {
+        LuaError::SyntaxError { .. } => ScriptIssueKind::SyntaxError,
+        LuaError::BadArgument { .. }
+        | LuaError::FromLuaConversionError { .. }
+        | LuaError::ToLuaConversionError { .. } => ScriptIssueKind::TypeError,
+        _ => ScriptIssueKind::RuntimeError,
+    }
+}
+
+// Unwrap the error which was raised in nested callback errors.
+fn root_cause(err: &LuaError) -> &LuaError {
+    match err {
+        LuaError::CallbackError { cause, .. } => root_cause(cause),
+        err => err,
+    }
+}
+
+// Parse the line number of the first "name:line:" location in the given error message.
+fn error_line(message: &str) -> Option<usize> {
+    let pattern = format!("{}\"]:", SCRIPT_NAME);
+    let (_, location) = message
+        .split_once(&pattern)
+        .or_else(|| message.split_once(&format!("{}:", SCRIPT_NAME)))?;
+    let digits = location
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>();
+    digits.parse().ok()
+}
+
+// Scan the given script source for `context.field`, `context["field"]` and `context['field']`
+// accesses of non builtin context fields.
+fn required_context_fields(script: &str) -> Vec<String> {
+    const CONTEXT: &str = "context";
+    let mut fields = BTreeSet::new();
+    for line in script.lines() {
+        // skip comments
+        let code = line.split_once("--").map_or(line, |(code, _)| code);
+        let mut start = 0;
+        while let Some(offset) = code[start..].find(CONTEXT) {
+            let position = start + offset;
+            start = position + CONTEXT.len();
+            if code[..position]
+                .chars()
+                .next_back()
+                .is_some_and(is_identifier_char)
+            {
+                continue;
+            }
+            if let Some(field) = context_field(&code[start..]) {
+                if !BUILTIN_CONTEXT_FIELDS.contains(&field.as_str()) {
+                    fields.insert(field);
+                }
+            }
+        }
+    }
+    fields.into_iter().collect()
+}
+
+// Parse the field name of a `.field`, `["field"]` or `['field']` access at the start of the
+// given code, which follows a `context` identifier.
+fn context_field(code: &str) -> Option<String> {
+    let code = code.trim_start();
+    let field = if let Some(code) = code.strip_prefix('.') {
+        code.trim_start()
+            .chars()
+            .take_while(|c| is_identifier_char(*c))
+            .collect::<String>()
+    } else {
+        let code = code.strip_prefix('[')?.trim_start();
+        let quote = code.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let (field, rest) = code[1..].split_once(quote)?;
+        if !rest.trim_start().starts_with(']') || !field.chars().all(is_identifier_char) {
+            return None;
+        }
+        field.to_string()
+    };
+    if field.is_empty() || field.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(field)
+}
+
+// true for characters which can be part of Lua identifiers.
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        // valid scripts
+        let report = validate_script(
+            r#"
+            -- context.commented is ignored
+            return rhythm {
+                unit = "1/16",
+                parameters = { density = { default = 0.5, min = 0, max = 1 } },
+                pattern = function(context)
+                    return context.parameters.density > (context.cutoff or 0)
+                end,
+                emit = function(context)
+                    return context.trigger_note or context["root_note"] or context['scale']
+                        or context.contextual or "c4"
+                end
+            }
+            "#,
+        );
+        assert!(report.is_valid());
+        assert!(report.issues().is_empty());
+        assert_eq!(
+            report
+                .parameters()
+                .iter()
+                .map(|parameter| parameter.id().to_string())
+                .collect::<Vec<_>>(),
+            vec!["density"]
+        );
+        assert_eq!(
+            report.required_context_fields(),
+            &["contextual", "cutoff", "root_note", "scale"]
+        );
+        // renamed contexts and computed keys are not detected
+        let report = validate_script(
+            r#"
+            local key = "velocity"
+            return rhythm {
+                unit = "1/16",
+                emit = function(ctx)
+                    return ctx.root_note or context[key] or context.contexts or "c4"
+                end
+            }
+            "#,
+        );
+        assert!(report.is_valid());
+        assert_eq!(report.required_context_fields(), &["contexts"]);
+
+        // syntax errors
+        let report = validate_script("local x = \n return rhythm {");
+        assert!(!report.is_valid());
+        assert_eq!(report.issues().len(), 1);
+        assert_eq!(report.issues()[0].kind, ScriptIssueKind::SyntaxError);
+        assert_eq!(report.issues()[0].line, Some(2));
+
+        // type errors
+        let report = validate_script("return rhythm { unit = \"1/4\", pattern = true }");
+        assert!(!report.is_valid());
+        assert_eq!(report.issues()[0].kind, ScriptIssueKind::TypeError);
+        let report = validate_script("return 1");
+        assert_eq!(report.issues()[0].kind, ScriptIssueKind::TypeError);
+
+        // runtime errors
+        let report = validate_script("error('failed')");
+        assert!(!report.is_valid());
+        assert_eq!(report.issues()[0].kind, ScriptIssueKind::RuntimeError);
+        assert_eq!(report.issues()[0].line, Some(1));
+    }
+}
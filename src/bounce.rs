@@ -0,0 +1,297 @@
+//! Offline bounce of a `Sequence`'s rhythm slots into separate stems, or of a single `Rhythm`
+//! with multiple random seeds.
+
+use crate::{phrase::RhythmIndex, rhythm::seed_from_number, Event, Rhythm, SampleTime, Sequence};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single event in a [`Stem`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct StemEvent {
+    pub time: SampleTime,
+    pub event: Event,
+    pub duration: SampleTime,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// All events of a single rhythm slot, as bounced by a [`StemBounce`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stem {
+    name: String,
+    rhythm_index: RhythmIndex,
+    events: Vec<StemEvent>,
+}
+
+impl Stem {
+    /// The stem's name: the rhythm slot's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Index of the rhythm slot in the sequence's phrases.
+    pub fn rhythm_index(&self) -> RhythmIndex {
+        self.rhythm_index
+    }
+
+    /// All bounced events of the stem, sorted by time.
+    pub fn events(&self) -> &[StemEvent] {
+        &self.events
+    }
+
+    /// File name for the stem with the given file extension, derived from the slot's index and
+    /// name, e.g. `02-snare.wav`. Characters which are not allowed in file names are replaced.
+    pub fn file_name(&self, extension: &str) -> String {
+        let name = self
+            .name
+            .trim()
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        format!("{:02}-{}.{}", self.rhythm_index + 1, name, extension)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Runs a [`Sequence`] offline and splits the emitted events of all or a soloed set of its
+/// rhythm slots into separate [`Stem`]S in one pass, e.g. to render them into separate audio
+/// files for mixing in a DAW.
+///
+/// With the `player` feature, stems can be rendered into audio files via a
+/// `player::stems::StemRenderer`.
+#[derive(Clone, Debug, Default)]
+pub struct StemBounce {
+    slot_names: Vec<String>,
+    solo: Option<Vec<RhythmIndex>>,
+}
+
+impl StemBounce {
+    /// Create a new bounce which bounces all rhythm slots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new bounce which uses the given names for the sequence's rhythm slots.
+    /// Unnamed slots are named `slot_N`.
+    #[must_use]
+    pub fn with_slot_names<S: Into<String>>(self, slot_names: Vec<S>) -> Self {
+        let slot_names = slot_names.into_iter().map(Into::into).collect();
+        Self { slot_names, ..self }
+    }
+
+    /// Return a new bounce which only bounces the given soloed rhythm slots.
+    #[must_use]
+    pub fn with_solo(self, rhythm_indices: Vec<RhythmIndex>) -> Self {
+        let solo = Some(rhythm_indices);
+        Self { solo, ..self }
+    }
+
+    /// Reset the given sequence and bounce its rhythm slots from the start until the given
+    /// sample time is reached. Returns one stem for each bounced rhythm slot.
+    pub fn run(&self, sequence: &mut Sequence, length: SampleTime) -> Vec<Stem> {
+        let mut stems = (0..sequence.phrase_rhythm_slot_count())
+            .filter(|rhythm_index| {
+                self.solo
+                    .as_ref()
+                    .map_or(true, |solo| solo.contains(rhythm_index))
+            })
+            .map(|rhythm_index| Stem {
+                name: self
+                    .slot_names
+                    .get(rhythm_index)
+                    .cloned()
+                    .unwrap_or_else(|| format!("slot_{}", rhythm_index + 1)),
+                rhythm_index,
+                events: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        sequence.reset();
+        sequence.consume_events_until_time(
+            length,
+            &mut |rhythm_index, time, event: Option<Event>, duration| {
+                if let Some(event) = event {
+                    if let Some(stem) = stems
+                        .iter_mut()
+                        .find(|stem| stem.rhythm_index == rhythm_index)
+                    {
+                        stem.events.push(StemEvent {
+                            time,
+                            event,
+                            duration,
+                        });
+                    }
+                }
+            },
+        );
+        stems
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// All events of a rhythm, bounced with a single seed by a [`SeedBounce`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeedTake {
+    seed: u32,
+    events: Vec<StemEvent>,
+}
+
+impl SeedTake {
+    /// The seed the take got bounced with.
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// All bounced events of the take, sorted by time.
+    pub fn events(&self) -> &[StemEvent] {
+        &self.events
+    }
+}
+
+/// Runs a generative [`Rhythm`] offline with multiple random seeds and collects the emitted
+/// events of each seed in separate [`SeedTake`]S, so the takes can be auditioned side-by-side
+/// to pick a seed. The picked seed then can be set persistently via [`Rhythm::set_seed`] and
+/// [`seed_from_number`], or via the `seed` option of Lua rhythms.
+///
+/// Random values in scripted callbacks are not affected by the seeds.
+#[derive(Clone, Debug, Default)]
+pub struct SeedBounce {
+    seeds: Vec<u32>,
+}
+
+impl SeedBounce {
+    /// Create a new bounce which bounces the given seeds.
+    pub fn new(seeds: Vec<u32>) -> Self {
+        Self { seeds }
+    }
+
+    /// The seeds which get bounced.
+    pub fn seeds(&self) -> &[u32] {
+        &self.seeds
+    }
+
+    /// Bounce duplicates of the given rhythm with all seeds from the start until the given sample
+    /// time is reached. Returns one take for each seed. The passed rhythm is not modified.
+    /// Fails when the rhythm can't be duplicated, see [`Rhythm::duplicate`].
+    pub fn run(&self, rhythm: &dyn Rhythm, length: SampleTime) -> Result<Vec<SeedTake>, String> {
+        self.seeds
+            .iter()
+            .map(|seed| {
+                let rhythm = rhythm.duplicate()?;
+                let mut rhythm = rhythm.borrow_mut();
+                rhythm.set_seed(seed_from_number(*seed as f64));
+                rhythm.reset();
+                let mut events = Vec::new();
+                while let Some(item) = rhythm.run_until_time(length) {
+                    if let Some(event) = item.event {
+                        events.push(StemEvent {
+                            time: item.time,
+                            event,
+                            duration: item.duration,
+                        });
+                    }
+                }
+                Ok(SeedTake {
+                    seed: *seed,
+                    events,
+                })
+            })
+            .collect()
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn bounce() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let phrase = Phrase::new(
+            time_base,
+            vec![
+                time_base
+                    .every_nth_beat(1.0)
+                    .trigger(new_note_event(Note::C4)),
+                time_base
+                    .every_nth_beat(2.0)
+                    .trigger(new_note_event(Note::D4)),
+                time_base
+                    .every_nth_beat(4.0)
+                    .trigger(new_note_event(Note::E4)),
+            ],
+            BeatTimeStep::Bar(1.0),
+        );
+        let mut sequence = Sequence::new(time_base, vec![phrase]);
+
+        let stems = StemBounce::new()
+            .with_slot_names(vec!["kick", "snare / clap"])
+            .with_solo(vec![1, 2])
+            .run(&mut sequence, 2000);
+        assert_eq!(
+            stems
+                .iter()
+                .map(|stem| (stem.file_name("wav"), stem.events().len()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("02-snare___clap.wav".to_string(), 2),
+                ("03-slot_3.wav".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            stems[0].events()[1],
+            StemEvent {
+                time: 1000,
+                event: Event::NoteEvents(vec![new_note(Note::D4)]),
+                duration: 1000
+            }
+        );
+
+        // bouncing again starts from scratch
+        let stems = StemBounce::new().run(&mut sequence, 2000);
+        assert_eq!(
+            stems
+                .iter()
+                .map(|stem| stem.events().len())
+                .collect::<Vec<_>>(),
+            vec![4, 2, 1]
+        );
+    }
+
+    #[test]
+    fn seed_bounce() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let rhythm = time_base
+            .every_nth_sixteenth(1.0)
+            .with_pattern(vec![0.5].to_pattern())
+            .trigger(new_note_event(Note::C4));
+
+        let takes = SeedBounce::new(vec![1, 2, 1]).run(&rhythm, 8000)?;
+        assert_eq!(
+            takes.iter().map(SeedTake::seed).collect::<Vec<_>>(),
+            vec![1, 2, 1]
+        );
+        assert!(!takes[0].events().is_empty());
+        assert_eq!(takes[0].events(), takes[2].events());
+        assert_ne!(takes[0].events(), takes[1].events());
+        Ok(())
+    }
+}
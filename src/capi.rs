@@ -0,0 +1,727 @@
+//! C API to embed afseq into non-Rust hosts.
+//!
+//! An [`AfseqEngine`] plays rhythms from Lua scripts in parallel, each script in its own rhythm
+//! slot. Hosts run the engine in blocks of sample frames and poll the generated events. All
+//! functions are single threaded: an engine must only be accessed from one thread at a time.
+//!
+//! Enable the `capi` feature to build the API. The C header `afseq.h` gets generated via
+//! cbindgen into cargo's `OUT_DIR` while building. To build a shared library, use e.g.
+//! `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! Panics in the engine are caught and never unwind into the host: functions then return
+//! their error values, e.g. null, -1 or false.
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::VecDeque,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    rc::Rc,
+};
+
+use crate::{
+    bindings::new_rhythm_from_string, phrase::RhythmSlot, time::BeatTimeStep, BeatTimeBase, Event,
+    Phrase, Rhythm, SampleTime, Sequence,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Kind of an [`AfseqEvent`].
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AfseqEventKind {
+    /// A note-on event.
+    NoteOn = 0,
+    /// A note-off event.
+    NoteOff = 1,
+    /// A parameter change event.
+    ParameterChange = 2,
+}
+
+/// A single event, as polled via [`afseq_engine_poll_event`]. Note events with multiple
+/// voices are split up into one event per voice.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AfseqEvent {
+    /// Event kind.
+    pub kind: AfseqEventKind,
+    /// Rhythm slot index of the script which emitted the event.
+    pub slot: u32,
+    /// Absolute event time in sample frames.
+    pub time: u64,
+    /// Event duration in sample frames.
+    pub duration: u64,
+    /// Voice index of note events.
+    pub voice: u32,
+    /// MIDI note number of note events.
+    pub note: u8,
+    /// Instrument id of note events or -1 when no instrument is set.
+    pub instrument: i64,
+    /// Volume of note events.
+    pub volume: f32,
+    /// Panning of note events.
+    pub panning: f32,
+    /// Delay of note events, relative to the event's duration.
+    pub delay: f32,
+    /// Parameter id of parameter change events or -1 when no parameter is set.
+    pub parameter: i64,
+    /// Value of parameter change events.
+    pub value: f32,
+}
+
+impl AfseqEvent {
+    fn new(kind: AfseqEventKind, slot: usize, time: SampleTime, duration: SampleTime) -> Self {
+        Self {
+            kind,
+            slot: slot as u32,
+            time,
+            duration,
+            voice: 0,
+            note: 0,
+            instrument: -1,
+            volume: 0.0,
+            panning: 0.0,
+            delay: 0.0,
+            parameter: -1,
+            value: 0.0,
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Opaque engine handle, created via [`afseq_engine_new`].
+pub struct AfseqEngine {
+    time_base: BeatTimeBase,
+    rhythms: Vec<Rc<RefCell<dyn Rhythm>>>,
+    sequence: Sequence,
+    playing: bool,
+    events: VecDeque<AfseqEvent>,
+    last_error: Option<CString>,
+}
+
+impl AfseqEngine {
+    fn new(time_base: BeatTimeBase) -> Self {
+        let rhythms = Vec::new();
+        let sequence = Sequence::new(time_base, vec![]);
+        let playing = false;
+        let events = VecDeque::new();
+        let last_error = None;
+        Self {
+            time_base,
+            rhythms,
+            sequence,
+            playing,
+            events,
+            last_error,
+        }
+    }
+
+    fn set_error(&mut self, error: &str) {
+        self.last_error = CString::new(error.replace('\0', " ")).ok();
+    }
+
+    fn new_rhythm(&self, script: &str, name: &str) -> Result<Rc<RefCell<dyn Rhythm>>, String> {
+        let rhythm = new_rhythm_from_string(self.time_base, None, script, name)
+            .map_err(|err| err.to_string())?;
+        // apply external values before the rhythm gets seeked to the playback position
+        let external_context = self.sequence.external_context().to_context_data();
+        rhythm.borrow_mut().set_external_context(&external_context);
+        Ok(rhythm)
+    }
+
+    fn load_script(&mut self, script: &str, name: &str) -> Result<usize, String> {
+        let rhythm = self.new_rhythm(script, name)?;
+        self.rhythms.push(rhythm);
+        self.rebuild_sequence();
+        Ok(self.rhythms.len() - 1)
+    }
+
+    fn replace_script(&mut self, slot: usize, script: &str, name: &str) -> Result<(), String> {
+        if slot >= self.rhythms.len() {
+            return Err(format!("invalid rhythm slot index: {slot}"));
+        }
+        self.rhythms[slot] = self.new_rhythm(script, name)?;
+        self.rebuild_sequence();
+        Ok(())
+    }
+
+    fn rebuild_sequence(&mut self) {
+        // recreate the sequence with all rhythms and move it to the old sequence's position.
+        // rhythms are free running, so rhythms which were already playing keep their state and
+        // pending events, while new rhythms get seeked to the playback position.
+        let sample_position = self.sequence.sample_position();
+        let external_context = self.sequence.external_context().clone();
+        let slots = self
+            .rhythms
+            .iter()
+            .map(|rhythm| RhythmSlot::FreeRunning(Rc::clone(rhythm)))
+            .collect::<Vec<_>>();
+        let mut phrase = Phrase::new(self.time_base, slots, BeatTimeStep::Bar(1.0));
+        let empty_phrase = Phrase::new(self.time_base, Vec::<RhythmSlot>::new(), phrase.length());
+        let previous_phrase = self.sequence.phrases().first().unwrap_or(&empty_phrase);
+        phrase.reset_with_offset(sample_position, previous_phrase);
+        self.sequence = Sequence::new(self.time_base, vec![phrase]);
+        self.sequence.restore_external_context(&external_context);
+        self.sequence.skip_events_until_time(sample_position);
+    }
+
+    fn set_parameter(&mut self, slot: usize, id: &str, value: f64) -> Result<f64, String> {
+        // parameter ids of sequences are "phrase_index.rhythm_index.parameter_id"
+        self.sequence
+            .set_parameter_value(&format!("0.{slot}.{id}"), value)
+    }
+
+    fn set_external_value(&mut self, name: &str, value: f64) {
+        self.sequence
+            .set_external_context(&[(Cow::Borrowed(name), value)]);
+    }
+
+    fn run(&mut self, frames: u64) {
+        if !self.playing || self.rhythms.is_empty() {
+            return;
+        }
+        let run_until_time = self.sequence.sample_position() + frames;
+        let events = &mut self.events;
+        self.sequence.consume_events_until_time(
+            run_until_time,
+            &mut |slot, time, event, duration| match event {
+                Some(Event::NoteEvents(note_events)) => {
+                    for (voice, note_event) in note_events.iter().enumerate() {
+                        if let Some(note_event) = note_event {
+                            let kind = if note_event.note.is_note_on() {
+                                AfseqEventKind::NoteOn
+                            } else if note_event.note.is_note_off() {
+                                AfseqEventKind::NoteOff
+                            } else {
+                                continue;
+                            };
+                            let mut event = AfseqEvent::new(kind, slot, time, duration);
+                            event.voice = voice as u32;
+                            event.note = u8::from(note_event.note);
+                            event.instrument = note_event
+                                .instrument
+                                .map_or(-1, |id| usize::from(id) as i64);
+                            event.volume = note_event.volume;
+                            event.panning = note_event.panning;
+                            event.delay = note_event.delay;
+                            events.push_back(event);
+                        }
+                    }
+                }
+                Some(Event::ParameterChangeEvent(change)) => {
+                    let kind = AfseqEventKind::ParameterChange;
+                    let mut event = AfseqEvent::new(kind, slot, time, duration);
+                    event.parameter = change.parameter.map_or(-1, |id| usize::from(id) as i64);
+                    event.value = change.value;
+                    events.push_back(event);
+                }
+                None => (),
+            },
+        );
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Run the given function and catch all panics, so they don't unwind into the host. Returns
+/// the given error value when the function panicked.
+fn catch_panic<T, F: FnOnce() -> T>(error_value: T, func: F) -> T {
+    panic::catch_unwind(AssertUnwindSafe(func)).unwrap_or_else(|_| {
+        log::error!("Caught a panic in an afseq C API call");
+        error_value
+    })
+}
+
+/// Convert a C string into a str, when it's not null and valid utf8.
+unsafe fn str_from_c<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok()
+    }
+}
+
+/// Create a new engine with the given time base. Returns null when the time base is invalid.
+/// The engine must be destroyed via [`afseq_engine_free`].
+#[no_mangle]
+pub extern "C" fn afseq_engine_new(
+    beats_per_min: f32,
+    beats_per_bar: u32,
+    samples_per_sec: u32,
+) -> *mut AfseqEngine {
+    catch_panic(ptr::null_mut(), || {
+        if !(beats_per_min > 0.0 && beats_per_min.is_finite()) || beats_per_bar == 0 {
+            return ptr::null_mut();
+        }
+        if samples_per_sec == 0 {
+            return ptr::null_mut();
+        }
+        let time_base = BeatTimeBase {
+            beats_per_min,
+            beats_per_bar,
+            samples_per_sec,
+        };
+        Box::into_raw(Box::new(AfseqEngine::new(time_base)))
+    })
+}
+
+/// Destroy an engine which got created via [`afseq_engine_new`].
+///
+/// # Safety
+/// `engine` must be null or a valid engine pointer, which must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_free(engine: *mut AfseqEngine) {
+    catch_panic((), || {
+        if !engine.is_null() {
+            drop(Box::from_raw(engine));
+        }
+    });
+}
+
+/// Get the last error message of the engine, or null when there was no error. The returned
+/// string is valid until the next call which changes the engine.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_last_error(engine: *const AfseqEngine) -> *const c_char {
+    catch_panic(ptr::null(), || {
+        if let Some(engine) = engine.as_ref() {
+            engine
+                .last_error
+                .as_ref()
+                .map_or(ptr::null(), |error| error.as_ptr())
+        } else {
+            ptr::null()
+        }
+    })
+}
+
+/// Load a Lua script which returns a rhythm, and add it as new rhythm slot to the engine.
+/// `name` is used in error messages. The new rhythm starts in sync with the playback
+/// position, all other slots continue playing. Returns the new slot's index or -1 on errors:
+/// see [`afseq_engine_last_error`].
+///
+/// # Safety
+/// `engine` must be a valid engine pointer, `script` and `name` valid, null terminated
+/// UTF-8 strings.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_load_script(
+    engine: *mut AfseqEngine,
+    script: *const c_char,
+    name: *const c_char,
+) -> i32 {
+    catch_panic(-1, || {
+        if let Some(engine) = engine.as_mut() {
+            let (script, name) = match (str_from_c(script), str_from_c(name)) {
+                (Some(script), Some(name)) => (script, name),
+                _ => {
+                    engine.set_error("script and name must be valid UTF-8 strings");
+                    return -1;
+                }
+            };
+            match engine.load_script(script, name) {
+                Ok(slot) => {
+                    engine.last_error = None;
+                    slot as i32
+                }
+                Err(err) => {
+                    engine.set_error(&err);
+                    -1
+                }
+            }
+        } else {
+            -1
+        }
+    })
+}
+
+/// Replace the rhythm in the given slot with a rhythm from a new Lua script. Only the replaced
+/// slot gets reset: the new rhythm starts in sync with the playback position, all other slots
+/// continue playing. Returns false on errors: see [`afseq_engine_last_error`].
+///
+/// # Safety
+/// `engine` must be a valid engine pointer, `script` and `name` valid, null terminated
+/// UTF-8 strings.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_replace_script(
+    engine: *mut AfseqEngine,
+    slot: u32,
+    script: *const c_char,
+    name: *const c_char,
+) -> bool {
+    catch_panic(false, || {
+        if let Some(engine) = engine.as_mut() {
+            let (script, name) = match (str_from_c(script), str_from_c(name)) {
+                (Some(script), Some(name)) => (script, name),
+                _ => {
+                    engine.set_error("script and name must be valid UTF-8 strings");
+                    return false;
+                }
+            };
+            match engine.replace_script(slot as usize, script, name) {
+                Ok(()) => {
+                    engine.last_error = None;
+                    true
+                }
+                Err(err) => {
+                    engine.set_error(&err);
+                    false
+                }
+            }
+        } else {
+            false
+        }
+    })
+}
+
+/// Remove all loaded scripts from the engine and rewind the playback position. External
+/// values are kept.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_clear_scripts(engine: *mut AfseqEngine) {
+    catch_panic((), || {
+        if let Some(engine) = engine.as_mut() {
+            engine.rhythms.clear();
+            engine.events.clear();
+            let external_context = engine.sequence.external_context().clone();
+            engine.sequence = Sequence::new(engine.time_base, vec![]);
+            engine.sequence.restore_external_context(&external_context);
+        }
+    });
+}
+
+/// Set the value of a parameter, which the rhythm in the given slot declares in its
+/// `parameter` table. Values get clamped to the parameter's range. Returns false when the
+/// slot or parameter does not exist: see [`afseq_engine_last_error`].
+///
+/// # Safety
+/// `engine` must be a valid engine pointer, `id` a valid, null terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_set_parameter(
+    engine: *mut AfseqEngine,
+    slot: u32,
+    id: *const c_char,
+    value: f64,
+) -> bool {
+    catch_panic(false, || {
+        if let Some(engine) = engine.as_mut() {
+            let Some(id) = str_from_c(id) else {
+                engine.set_error("parameter id must be a valid UTF-8 string");
+                return false;
+            };
+            match engine.set_parameter(slot as usize, id, value) {
+                Ok(_) => {
+                    engine.last_error = None;
+                    true
+                }
+                Err(err) => {
+                    engine.set_error(&err);
+                    false
+                }
+            }
+        } else {
+            false
+        }
+    })
+}
+
+/// Set an external context value, which gets passed to all scripts as
+/// `context.external.NAME`. Values are memorized and also applied to scripts which get
+/// loaded later on. Returns false when the name is not a valid string.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer, `name` a valid, null terminated UTF-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_set_external_value(
+    engine: *mut AfseqEngine,
+    name: *const c_char,
+    value: f64,
+) -> bool {
+    catch_panic(false, || {
+        if let (Some(engine), Some(name)) = (engine.as_mut(), str_from_c(name)) {
+            engine.set_external_value(name, value);
+            true
+        } else {
+            false
+        }
+    })
+}
+
+/// Change the engine's tempo. Returns false when the tempo is invalid.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_set_tempo(
+    engine: *mut AfseqEngine,
+    beats_per_min: f32,
+) -> bool {
+    catch_panic(false, || {
+        if let Some(engine) = engine.as_mut() {
+            if !(beats_per_min > 0.0 && beats_per_min.is_finite()) {
+                return false;
+            }
+            engine.time_base.beats_per_min = beats_per_min;
+            let time_base = engine.time_base;
+            engine.sequence.set_time_base(&time_base);
+            true
+        } else {
+            false
+        }
+    })
+}
+
+/// Start or continue playback from the current playback position.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_start(engine: *mut AfseqEngine) {
+    catch_panic((), || {
+        if let Some(engine) = engine.as_mut() {
+            engine.playing = true;
+        }
+    });
+}
+
+/// Pause playback. The playback position is kept, already generated events can still be
+/// polled.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_stop(engine: *mut AfseqEngine) {
+    catch_panic((), || {
+        if let Some(engine) = engine.as_mut() {
+            engine.playing = false;
+        }
+    });
+}
+
+/// Returns true when the engine is playing.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_is_playing(engine: *const AfseqEngine) -> bool {
+    catch_panic(false, || {
+        engine.as_ref().is_some_and(|engine| engine.playing)
+    })
+}
+
+/// Rewind playback to the start and drop all pending events.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_rewind(engine: *mut AfseqEngine) {
+    catch_panic((), || {
+        if let Some(engine) = engine.as_mut() {
+            engine.events.clear();
+            engine.sequence.reset();
+        }
+    });
+}
+
+/// Current playback position in sample frames.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_position(engine: *const AfseqEngine) -> u64 {
+    catch_panic(0, || {
+        engine
+            .as_ref()
+            .map_or(0, |engine| engine.sequence.sample_position())
+    })
+}
+
+/// Run a playing engine for the given number of sample frames and queue all generated
+/// events. Returns the number of pending events, which can be fetched via
+/// [`afseq_engine_poll_event`].
+///
+/// # Safety
+/// `engine` must be a valid engine pointer.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_run(engine: *mut AfseqEngine, frames: u64) -> u32 {
+    catch_panic(0, || {
+        if let Some(engine) = engine.as_mut() {
+            engine.run(frames);
+            engine.events.len() as u32
+        } else {
+            0
+        }
+    })
+}
+
+/// Fetch the next pending event. Returns false when there are no more pending events.
+///
+/// # Safety
+/// `engine` must be a valid engine pointer and `event` a valid pointer to an event struct.
+#[no_mangle]
+pub unsafe extern "C" fn afseq_engine_poll_event(
+    engine: *mut AfseqEngine,
+    event: *mut AfseqEvent,
+) -> bool {
+    catch_panic(false, || {
+        if let (Some(engine), Some(event)) = (engine.as_mut(), event.as_mut()) {
+            if let Some(next_event) = engine.events.pop_front() {
+                *event = next_event;
+                return true;
+            }
+        }
+        false
+    })
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn engine() {
+        unsafe {
+            assert!(afseq_engine_new(0.0, 4, 44100).is_null());
+            let engine = afseq_engine_new(120.0, 4, 1000);
+            assert!(!engine.is_null());
+
+            let script = CString::new(
+                r#"
+                return rhythm {
+                  unit = "1/4",
+                  parameters = { density = { default = 1, min = 0, max = 1 } },
+                  pattern = function(context)
+                    return context.parameters.density >= 1 and
+                      (context.external.enabled or 1) >= 1
+                  end,
+                  emit = "c4"
+                }
+                "#,
+            )
+            .unwrap();
+            let name = CString::new("[test]").unwrap();
+            let invalid_script = CString::new("return 1").unwrap();
+            assert_eq!(
+                afseq_engine_load_script(engine, invalid_script.as_ptr(), name.as_ptr()),
+                -1
+            );
+            assert!(!afseq_engine_last_error(engine).is_null());
+            assert_eq!(
+                afseq_engine_load_script(engine, script.as_ptr(), name.as_ptr()),
+                0
+            );
+            assert!(afseq_engine_last_error(engine).is_null());
+
+            // stopped engines don't run
+            assert_eq!(afseq_engine_run(engine, 1000), 0);
+            afseq_engine_start(engine);
+            assert!(afseq_engine_is_playing(engine));
+            assert_eq!(afseq_engine_run(engine, 1000), 2);
+            assert_eq!(afseq_engine_position(engine), 1000);
+
+            let mut event = AfseqEvent::new(AfseqEventKind::NoteOff, 0, 0, 0);
+            assert!(afseq_engine_poll_event(engine, &mut event));
+            assert_eq!(event.kind, AfseqEventKind::NoteOn);
+            assert_eq!((event.slot, event.time, event.note), (0, 0, 48));
+            assert!(afseq_engine_poll_event(engine, &mut event));
+            assert_eq!(event.time, 500);
+            assert!(!afseq_engine_poll_event(engine, &mut event));
+
+            // parameters
+            let density = CString::new("density").unwrap();
+            let missing = CString::new("missing").unwrap();
+            assert!(!afseq_engine_set_parameter(
+                engine,
+                0,
+                missing.as_ptr(),
+                0.0
+            ));
+            assert!(!afseq_engine_last_error(engine).is_null());
+            assert!(!afseq_engine_set_parameter(
+                engine,
+                1,
+                density.as_ptr(),
+                0.0
+            ));
+            assert!(afseq_engine_set_parameter(engine, 0, density.as_ptr(), 0.0));
+            assert_eq!(afseq_engine_run(engine, 1000), 0);
+            assert!(afseq_engine_set_parameter(engine, 0, density.as_ptr(), 1.0));
+            assert_eq!(afseq_engine_run(engine, 1000), 2);
+            while afseq_engine_poll_event(engine, &mut event) {}
+
+            // external values
+            let enabled = CString::new("enabled").unwrap();
+            assert!(afseq_engine_set_external_value(
+                engine,
+                enabled.as_ptr(),
+                0.0
+            ));
+            assert_eq!(afseq_engine_run(engine, 1000), 0);
+            assert_eq!(afseq_engine_position(engine), 4000);
+            assert!(afseq_engine_set_external_value(
+                engine,
+                enabled.as_ptr(),
+                1.0
+            ));
+
+            // loading and replacing scripts only resets the new slot
+            let offbeat_script = CString::new(
+                r#"
+                return rhythm {
+                  unit = "1/4",
+                  offset = 1,
+                  pattern = { 1, 0 },
+                  emit = "d4"
+                }
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                afseq_engine_load_script(engine, offbeat_script.as_ptr(), name.as_ptr()),
+                1
+            );
+            assert_eq!(afseq_engine_run(engine, 1000), 3);
+            let mut slot_events = Vec::new();
+            while afseq_engine_poll_event(engine, &mut event) {
+                slot_events.push((event.slot, event.time));
+            }
+            assert_eq!(slot_events, vec![(0, 4000), (0, 4500), (1, 4500)]);
+            assert!(!afseq_engine_replace_script(
+                engine,
+                2,
+                script.as_ptr(),
+                name.as_ptr()
+            ));
+            assert!(afseq_engine_replace_script(
+                engine,
+                1,
+                offbeat_script.as_ptr(),
+                name.as_ptr()
+            ));
+            assert_eq!(afseq_engine_run(engine, 1000), 3);
+            slot_events.clear();
+            while afseq_engine_poll_event(engine, &mut event) {
+                slot_events.push((event.slot, event.time));
+            }
+            assert_eq!(slot_events, vec![(0, 5000), (0, 5500), (1, 5500)]);
+
+            // transport
+            afseq_engine_rewind(engine);
+            assert_eq!(afseq_engine_position(engine), 0);
+            assert!(afseq_engine_set_tempo(engine, 60.0));
+            assert!(!afseq_engine_set_tempo(engine, -1.0));
+            afseq_engine_stop(engine);
+            assert!(!afseq_engine_is_playing(engine));
+
+            afseq_engine_free(engine);
+        }
+    }
+}
@@ -3,7 +3,7 @@
 use lazy_static::lazy_static;
 use std::collections::HashMap;
 
-use crate::note::Note;
+use crate::note::{Note, NoteSpelling};
 
 // --------------------------------------------------------------------------------------------------
 
@@ -198,31 +198,309 @@ pub fn chord_intervals(p: &str) -> Vec<u8> {
 
 // --------------------------------------------------------------------------------------------------
 
-/// Note vector, created from a root note and intervals.
+/// Base triad quality of an extended chord symbol.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChordQuality {
+    Major,
+    Dominant,
+    Minor,
+    Diminished,
+    Augmented,
+}
+
+/// Parse an extended chord symbol such as "maj7#11", "m9", "7b9b13", "7sus4" or "add9" into
+/// a base triad and extension intervals.
+fn parse_chord_symbol(symbol: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    fn strip_number(s: &str) -> Option<(u8, &str)> {
+        let len = s.chars().take_while(|c| c.is_ascii_digit()).count();
+        s[..len]
+            .parse::<u8>()
+            .ok()
+            .map(|number| (number, &s[len..]))
+    }
+    fn degree_interval(degree: u8) -> Option<u8> {
+        match degree {
+            2 => Some(2),
+            4 => Some(5),
+            5 => Some(7),
+            6 => Some(9),
+            9 => Some(14),
+            11 => Some(17),
+            13 => Some(21),
+            _ => None,
+        }
+    }
+    let invalid_symbol = || format!("invalid chord symbol '{}'", symbol);
+    if symbol.is_empty() {
+        return Err(invalid_symbol());
+    }
+    // quality
+    let mut rest = symbol;
+    let mut quality = ChordQuality::Dominant;
+    for (prefix, prefix_quality) in [
+        ("major", ChordQuality::Major),
+        ("maj", ChordQuality::Major),
+        ("minor", ChordQuality::Minor),
+        ("min", ChordQuality::Minor),
+        ("dim", ChordQuality::Diminished),
+        ("aug", ChordQuality::Augmented),
+        ("M", ChordQuality::Major),
+        ("Δ", ChordQuality::Major),
+        ("m", ChordQuality::Minor),
+        ("-", ChordQuality::Minor),
+        ("o", ChordQuality::Diminished),
+        ("+", ChordQuality::Augmented),
+    ] {
+        if let Some(stripped) = rest.strip_prefix(prefix) {
+            quality = prefix_quality;
+            rest = stripped;
+            break;
+        }
+    }
+    let mut third = if matches!(quality, ChordQuality::Minor | ChordQuality::Diminished) {
+        3
+    } else {
+        4
+    };
+    let mut fifth = match quality {
+        ChordQuality::Diminished => 6,
+        ChordQuality::Augmented => 8,
+        _ => 7,
+    };
+    // degree
+    let mut extensions = Vec::new();
+    if let Some((degree, stripped)) = strip_number(rest) {
+        let seventh = match quality {
+            ChordQuality::Major => 11,
+            ChordQuality::Diminished => 9,
+            _ => 10,
+        };
+        match degree {
+            6 => extensions.push(9),
+            7 => extensions.push(seventh),
+            9 => extensions.extend([seventh, 14]),
+            11 => extensions.extend([seventh, 14, 17]),
+            13 if quality == ChordQuality::Major => extensions.extend([seventh, 14, 21]),
+            13 => extensions.extend([seventh, 14, 17, 21]),
+            _ => return Err(invalid_symbol()),
+        }
+        rest = stripped;
+    }
+    // suspensions
+    if let Some(stripped) = rest.strip_prefix("sus") {
+        if let Some((degree, stripped)) = strip_number(stripped) {
+            third = match degree {
+                2 => 2,
+                4 => 5,
+                _ => return Err(invalid_symbol()),
+            };
+            rest = stripped;
+        } else {
+            third = 5;
+            rest = stripped;
+        }
+    }
+    // alterations and additions
+    while !rest.is_empty() {
+        if let Some(stripped) = rest.strip_prefix(['(', ')', ',', ' ']) {
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("add") {
+            let (degree, stripped) = strip_number(stripped).ok_or_else(invalid_symbol)?;
+            let interval = degree_interval(degree).ok_or_else(invalid_symbol)?;
+            if !extensions.contains(&interval) {
+                extensions.push(interval);
+            }
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix(['b', '#']) {
+            let flat = rest.starts_with('b');
+            let (degree, stripped) = strip_number(stripped).ok_or_else(invalid_symbol)?;
+            match degree {
+                5 => fifth = if flat { 6 } else { 8 },
+                9 | 11 | 13 => {
+                    let natural = degree_interval(degree).ok_or_else(invalid_symbol)?;
+                    let altered = if flat { natural - 1 } else { natural + 1 };
+                    extensions.retain(|interval| *interval != natural);
+                    extensions.push(altered);
+                }
+                _ => return Err(invalid_symbol()),
+            }
+            rest = stripped;
+        } else {
+            return Err(invalid_symbol());
+        }
+    }
+    extensions.sort_unstable();
+    extensions.dedup();
+    Ok((vec![0, third, fifth], extensions))
+}
+
+/// Resolve a chord mode or chord symbol with an optional slash bass note, e.g. "maj7/g".
+fn parse_chord_mode(note: Note, mode: &str) -> Result<Chord, String> {
+    let (mode, bass) = match mode.split_once('/') {
+        Some((mode, bass)) => (mode, Some(bass)),
+        None => (mode, None),
+    };
+    let chord = if let Some(intervals) = CHORD_TABLE.get(mode) {
+        Chord::new(note, intervals.clone())
+    } else {
+        let (triad, extensions) = parse_chord_symbol(mode).map_err(|err| {
+            format!(
+                "{}, valid modes are: {} or extended chord symbols such as 'maj7#11' or 'm9/g'",
+                err,
+                chord_names()
+            )
+        })?;
+        Chord::from_parts(note, triad, extensions)
+    };
+    if let Some(bass) = bass {
+        let bass_note = Note::try_from(bass)?;
+        if !bass_note.is_note_on() {
+            return Err(format!("invalid chord bass note '{}'", bass));
+        }
+        if bass.chars().any(|c| c.is_ascii_digit()) {
+            Ok(chord.with_bass(bass_note))
+        } else {
+            // place bass notes without an explicit octave below the chord's root note
+            let mut offset = (note.key() as i32 + 12 - bass_note.key() as i32) % 12;
+            if offset == 0 {
+                offset = 12;
+            }
+            Ok(chord.with_bass(note.transposed(-offset)))
+        }
+    } else {
+        Ok(chord)
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+/// Note vector, created from a root note and intervals, with an optional bass note.
+///
+/// Intervals are split into the chord's base triad and its extensions, so emitters can voice
+/// chords differently, e.g. by dropping extensions or spreading them over octaves.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Chord {
     note: Note,
     intervals: Vec<u8>,
+    triad: Vec<u8>,
+    extensions: Vec<u8>,
+    bass: Option<Note>,
+    spelling: Option<NoteSpelling>,
 }
 
 impl Chord {
     /// Create a new chord from the given base note and interval
     pub fn new<N: Into<Note>>(note: N, intervals: Vec<u8>) -> Self {
+        // the lowest intervals below a sixth form the base triad, in whatever order the
+        // intervals are listed
+        let mut sorted_intervals = intervals.clone();
+        sorted_intervals.sort_unstable();
+        let triad = sorted_intervals
+            .into_iter()
+            .take(3)
+            .take_while(|interval| *interval < 9)
+            .collect::<Vec<_>>();
+        let mut extensions = intervals.clone();
+        for interval in &triad {
+            if let Some(index) = extensions.iter().position(|other| other == interval) {
+                extensions.remove(index);
+            }
+        }
         Self {
             note: note.into(),
             intervals,
+            triad,
+            extensions,
+            bass: None,
+            spelling: None,
         }
     }
 
+    /// Create a new chord from the given base note, triad and extension intervals.
+    pub fn from_parts<N: Into<Note>>(note: N, triad: Vec<u8>, extensions: Vec<u8>) -> Self {
+        let mut intervals = triad.clone();
+        intervals.extend(&extensions);
+        Self {
+            note: note.into(),
+            intervals,
+            triad,
+            extensions,
+            bass: None,
+            spelling: None,
+        }
+    }
+
+    /// Return a new chord with the given slash bass note.
+    #[must_use]
+    pub fn with_bass<N: Into<Option<Note>>>(self, bass: N) -> Self {
+        let bass = bass.into();
+        Self { bass, ..self }
+    }
+
+    /// Return a new chord which spells its note names with the given accidentals.
+    #[must_use]
+    pub fn with_spelling<S: Into<Option<NoteSpelling>>>(self, spelling: S) -> Self {
+        let spelling = spelling.into();
+        Self { spelling, ..self }
+    }
+
     /// Root note.
     pub fn note(&self) -> Note {
         self.note
     }
 
+    /// Optional slash bass note.
+    pub fn bass(&self) -> Option<Note> {
+        self.bass
+    }
+
     /// Note intervals / steps.
     pub fn intervals(&self) -> &Vec<u8> {
         &self.intervals
     }
+
+    /// Note intervals of the chord's base triad.
+    pub fn triad(&self) -> &[u8] {
+        &self.triad
+    }
+
+    /// Note intervals of the chord's extensions, e.g. sevenths, ninths or added tones.
+    pub fn extensions(&self) -> &[u8] {
+        &self.extensions
+    }
+
+    /// All chord notes: the bass note, if any, followed by the root note's intervals.
+    pub fn notes(&self) -> Vec<Note> {
+        self.bass
+            .into_iter()
+            .chain(
+                self.intervals
+                    .iter()
+                    .map(|interval| self.note.transposed(*interval as i32)),
+            )
+            .collect()
+    }
+
+    /// Accidental spelling of the chord's note names. When not explicitly set, this is the
+    /// spelling of the chord's major key, or the relative major key for minor chords.
+    pub fn spelling(&self) -> NoteSpelling {
+        self.spelling.unwrap_or_else(|| {
+            if self.triad().contains(&3) {
+                NoteSpelling::from_key(self.note.key() + 3)
+            } else {
+                NoteSpelling::from_key(self.note.key())
+            }
+        })
+    }
+
+    /// Names of all chord notes, including the bass note, spelled with the chord's spelling.
+    pub fn note_names(&self) -> Vec<String> {
+        let spelling = self.spelling();
+        self.notes()
+            .into_iter()
+            .map(|note| note.name(spelling))
+            .collect()
+    }
 }
 
 impl TryFrom<&str> for Chord {
@@ -230,6 +508,8 @@ impl TryFrom<&str> for Chord {
 
     /// Try converting the given string to a chord string in the form:
     /// $note'$chord where $note is a root key or note string and $chord is a key of `CHORD_TABLE`
+    /// or an extended chord symbol such as "maj7#11", optionally followed by a slash bass note,
+    /// e.g. "c4'maj7/g".
     fn try_from(s: &str) -> Result<Self, String> {
         let mut splits = s.split('\'');
         if let Some(note_part) = splits.next() {
@@ -240,11 +520,8 @@ impl TryFrom<&str> for Chord {
                     );
                 }
                 let note = Note::try_from(note_part)?;
-                let intervals = CHORD_TABLE.get(chord_part).ok_or(format!(
-                    "invalid mode, valid modes are: {}",
-                    chord_names()
-                ))?;
-                return Ok(Self::new(note, intervals.clone()));
+                let spelling = NoteSpelling::from_note_str(note_part);
+                return Ok(parse_chord_mode(note, chord_part)?.with_spelling(spelling));
             }
         }
         Err("invalid chord string: \
@@ -261,11 +538,7 @@ where
 
     /// Try converting the given string to a note and mode tuple.
     fn try_from((note, mode): (N, &str)) -> Result<Self, String> {
-        let intervals = CHORD_TABLE.get(mode).ok_or(format!(
-            "Invalid chord mode, valid chords are: {}",
-            chord_names()
-        ))?;
-        Ok(Self::new(note, intervals.clone()))
+        parse_chord_mode(note.into(), mode)
     }
 }
 
@@ -313,7 +586,7 @@ where
 
 #[cfg(test)]
 mod test {
-    use crate::{Chord, Note};
+    use crate::{note::NoteSpelling, Chord, Note};
 
     #[test]
     fn chord() -> Result<(), String> {
@@ -361,4 +634,64 @@ mod test {
         );
         Ok(())
     }
+
+    #[test]
+    fn chord_symbols() -> Result<(), String> {
+        assert!(Chord::try_from("c4'maj8").is_err());
+        assert!(Chord::try_from("c4'7b7").is_err());
+        assert!(Chord::try_from("c4'maj7/x").is_err());
+        let chord = Chord::try_from("c4'maj7#11")?;
+        assert_eq!(chord.triad(), &[0, 4, 7]);
+        assert_eq!(chord.extensions(), &[11, 18]);
+        assert_eq!(chord.bass(), None);
+        let chord = Chord::try_from("c4'7b9b13")?;
+        assert_eq!(chord.intervals(), &vec![0, 4, 7, 10, 13, 20]);
+        let chord = Chord::try_from("c4'm9")?;
+        assert_eq!(chord.triad(), &[0, 3, 7]);
+        assert_eq!(chord.extensions(), &[10, 14]);
+        let chord = Chord::try_from("c4'7sus4add13")?;
+        assert_eq!(chord.intervals(), &vec![0, 5, 7, 10, 21]);
+        let chord = Chord::try_from("c4'm7(b5)")?;
+        assert_eq!(chord.intervals(), &vec![0, 3, 6, 10]);
+        // triads don't depend on the order of the intervals
+        let chord = Chord::try_from("c4'm69")?;
+        assert_eq!(chord.intervals(), &vec![0, 3, 9, 7, 14]);
+        assert_eq!(chord.triad(), &[0, 3, 7]);
+        assert_eq!(chord.extensions(), &[9, 14]);
+        let chord = Chord::new(Note::C4, vec![7, 0, 12, 4]);
+        assert_eq!(chord.triad(), &[0, 4, 7]);
+        assert_eq!(chord.extensions(), &[12]);
+        Ok(())
+    }
+
+    #[test]
+    fn chord_slash() -> Result<(), String> {
+        let chord = Chord::try_from("c4'maj7/g")?;
+        assert_eq!(chord.bass(), Some(Note::G3));
+        assert_eq!(
+            chord.notes(),
+            vec![Note::G3, Note::C4, Note::E4, Note::G4, Note::B4]
+        );
+        let chord = Chord::try_from("c4'm/c2")?;
+        assert_eq!(chord.bass(), Some(Note::C2));
+        assert_eq!(chord.triad(), &[0, 3, 7]);
+        assert!(chord.extensions().is_empty());
+        let chord = Chord::try_from((Note::D4, "m7/c"))?;
+        assert_eq!(chord.bass(), Some(Note::C4));
+        Ok(())
+    }
+
+    #[test]
+    fn chord_spelling() -> Result<(), String> {
+        let chord = Chord::try_from("gb4'maj")?;
+        assert_eq!(chord.spelling(), NoteSpelling::Flats);
+        assert_eq!(chord.note_names(), vec!["Gb4", "Bb4", "Db5"]);
+        let chord = Chord::try_from("f#4'maj")?;
+        assert_eq!(chord.note_names(), vec!["F#4", "A#4", "C#5"]);
+        let chord = Chord::try_from("g4'min")?;
+        assert_eq!(chord.note_names(), vec!["G4", "Bb4", "D5"]);
+        let chord = Chord::try_from("d4'maj")?;
+        assert_eq!(chord.note_names(), vec!["D4", "F#4", "A4"]);
+        Ok(())
+    }
 }
@@ -0,0 +1,296 @@
+//! Similarity scores and diffs of pulse patterns and bounced event lists.
+
+use std::fmt::Display;
+
+use crate::{bounce::StemEvent, Event, Note, Pulse, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single difference between two hit lists in a [`HitDiff`].
+///
+/// Positions are steps for pulse patterns and sample times for event lists. Notes are only set
+/// for event lists: hits only match other hits with the same note.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HitChange {
+    /// A hit which only exists in the new hit list.
+    Added { position: f64, note: Option<Note> },
+    /// A hit which only exists in the original hit list.
+    Removed { position: f64, note: Option<Note> },
+    /// A hit which got shifted in time.
+    Moved {
+        from: f64,
+        to: f64,
+        note: Option<Note>,
+    },
+}
+
+impl HitChange {
+    /// The change's position in the original, or for added hits in the new hit list.
+    pub fn position(&self) -> f64 {
+        match *self {
+            Self::Added { position, .. } | Self::Removed { position, .. } => position,
+            Self::Moved { from, .. } => from,
+        }
+    }
+}
+
+impl Display for HitChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hit_name = |note: &Option<Note>| note.map_or("hit".to_string(), |n| n.to_string());
+        match self {
+            Self::Added { position, note } => write!(f, "added {} at {}", hit_name(note), position),
+            Self::Removed { position, note } => {
+                write!(f, "removed {} at {}", hit_name(note), position)
+            }
+            Self::Moved { from, to, note } => {
+                write!(f, "moved {} from {} to {}", hit_name(note), from, to)
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Differences between two pulse patterns or event lists, as created by [`diff_pulses`] and
+/// [`diff_events`], e.g. to limit how far mutated variations may stray from the original.
+///
+/// Displays as human-readable list of changes, one change per line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HitDiff {
+    changes: Vec<HitChange>,
+    unchanged: usize,
+    similarity: f64,
+}
+
+impl HitDiff {
+    /// Added, removed and moved hits, sorted by position.
+    pub fn changes(&self) -> &[HitChange] {
+        &self.changes
+    }
+
+    /// Number of hits which exist at the same position in both hit lists.
+    pub fn unchanged(&self) -> usize {
+        self.unchanged
+    }
+
+    /// Similarity score in range \[0 - 1\], where 1 means identical and 0 means nothing in
+    /// common. Unchanged hits score 1, moved hits score 1 to 0.5 depending on how far they moved,
+    /// added and removed hits score 0. The score is the average score of all hits.
+    pub fn similarity(&self) -> f64 {
+        self.similarity
+    }
+
+    /// Returns true when both hit lists are identical.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Display for HitDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, change) in self.changes.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Compare hits of two pulse patterns. Pulses with values > 0 are hits, positioned in steps:
+/// hits in sub divisions have fractional positions. Hits which shifted by up to `max_move`
+/// steps count as moved, others as removed and added.
+pub fn diff_pulses(original: &[Pulse], new: &[Pulse], max_move: f64) -> HitDiff {
+    diff_hits(&pulse_hits(original), &pulse_hits(new), max_move)
+}
+
+/// Compare note-on hits of two bounced event lists, e.g. [`Stem`](crate::bounce::Stem)
+/// events. Notes of chords are separate hits. Notes which shifted by up to `max_move` samples
+/// count as moved, others as removed and added.
+pub fn diff_events(original: &[StemEvent], new: &[StemEvent], max_move: SampleTime) -> HitDiff {
+    diff_hits(&event_hits(original), &event_hits(new), max_move as f64)
+}
+
+// -------------------------------------------------------------------------------------------------
+
+type Hit = (f64, Option<Note>);
+
+fn pulse_hits(pulses: &[Pulse]) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    for (step, pulse) in pulses.iter().enumerate() {
+        let mut position = step as f64;
+        for item in pulse.flattened() {
+            if item.value > 0.0 {
+                hits.push((position, None));
+            }
+            position += item.step_time;
+        }
+    }
+    hits
+}
+
+fn event_hits(events: &[StemEvent]) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    for stem_event in events {
+        if let Event::NoteEvents(notes) = &stem_event.event {
+            for note_event in notes.iter().flatten() {
+                if note_event.note.is_note_on() {
+                    hits.push((stem_event.time as f64, Some(note_event.note)));
+                }
+            }
+        }
+    }
+    hits
+}
+
+fn diff_hits(original: &[Hit], new: &[Hit], max_move: f64) -> HitDiff {
+    const EPSILON: f64 = 1e-9;
+    let mut original_matched = vec![false; original.len()];
+    let mut new_matched = vec![false; new.len()];
+    // match unchanged hits first, so moves can't steal them
+    let mut unchanged = 0;
+    for (index, (position, note)) in original.iter().enumerate() {
+        if let Some(new_index) = (0..new.len()).find(|new_index| {
+            let (new_position, new_note) = new[*new_index];
+            !new_matched[*new_index]
+                && new_note == *note
+                && (new_position - position).abs() < EPSILON
+        }) {
+            original_matched[index] = true;
+            new_matched[new_index] = true;
+            unchanged += 1;
+        }
+    }
+    // then match each remaining hit with the closest remaining hit in range
+    let mut changes = Vec::new();
+    let mut score = unchanged as f64;
+    for (index, (position, note)) in original.iter().enumerate() {
+        if original_matched[index] {
+            continue;
+        }
+        let closest = (0..new.len())
+            .filter(|new_index| !new_matched[*new_index] && new[*new_index].1 == *note)
+            .map(|new_index| (new_index, (new[new_index].0 - position).abs()))
+            .filter(|(_, distance)| *distance <= max_move)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((new_index, distance)) = closest {
+            new_matched[new_index] = true;
+            score += 1.0 - 0.5 * distance / max_move;
+            changes.push(HitChange::Moved {
+                from: *position,
+                to: new[new_index].0,
+                note: *note,
+            });
+        } else {
+            changes.push(HitChange::Removed {
+                position: *position,
+                note: *note,
+            });
+        }
+    }
+    for (index, (position, note)) in new.iter().enumerate() {
+        if !new_matched[index] {
+            changes.push(HitChange::Added {
+                position: *position,
+                note: *note,
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.position().total_cmp(&b.position()));
+    let hit_count = unchanged + changes.len();
+    let similarity = if hit_count == 0 {
+        1.0
+    } else {
+        score / hit_count as f64
+    };
+    HitDiff {
+        changes,
+        unchanged,
+        similarity,
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::new_note;
+
+    #[test]
+    fn pulses() {
+        let original = [
+            Pulse::from(1),
+            Pulse::from(0),
+            Pulse::from(1),
+            Pulse::from(1),
+        ];
+        assert!(diff_pulses(&original, &original, 1.0).is_empty());
+        assert_eq!(diff_pulses(&original, &original, 1.0).similarity(), 1.0);
+        assert_eq!(diff_pulses(&[], &[], 1.0).similarity(), 1.0);
+
+        let new = [
+            Pulse::from(1),
+            Pulse::from(vec![0, 1]),
+            Pulse::from(0),
+            Pulse::from(0),
+        ];
+        let diff = diff_pulses(&original, &new, 1.0);
+        assert_eq!(diff.unchanged(), 1);
+        assert_eq!(
+            diff.changes(),
+            &[
+                HitChange::Moved {
+                    from: 2.0,
+                    to: 1.5,
+                    note: None
+                },
+                HitChange::Removed {
+                    position: 3.0,
+                    note: None
+                }
+            ]
+        );
+        assert_eq!(diff.similarity(), (1.0 + 0.75 + 0.0) / 3.0);
+        assert_eq!(
+            diff.to_string(),
+            "moved hit from 2 to 1.5\nremoved hit at 3"
+        );
+
+        // without moves
+        let diff = diff_pulses(&original, &new, 0.0);
+        assert_eq!(diff.unchanged(), 1);
+        assert_eq!(diff.changes().len(), 3);
+        assert_eq!(diff.similarity(), 0.25);
+    }
+
+    #[test]
+    fn events() {
+        let note_event = |time, notes: &[Note]| StemEvent {
+            time,
+            event: Event::NoteEvents(notes.iter().map(|note| Some(new_note(*note))).collect()),
+            duration: 100,
+        };
+        let original = [
+            note_event(0, &[Note::C4, Note::E4]),
+            note_event(100, &[Note::OFF]),
+            note_event(200, &[Note::G4]),
+        ];
+        let new = [
+            note_event(0, &[Note::C4]),
+            note_event(210, &[Note::G4]),
+            note_event(300, &[Note::A4]),
+        ];
+        let diff = diff_events(&original, &new, 20);
+        assert_eq!(diff.unchanged(), 1);
+        assert_eq!(
+            diff.to_string(),
+            "removed E4 at 0\nmoved G4 from 200 to 210\nadded A4 at 300"
+        );
+        assert!(diff.similarity() > 0.0 && diff.similarity() < 1.0);
+        assert_eq!(diff_events(&original, &[], 20).similarity(), 0.0);
+    }
+}
@@ -2,38 +2,141 @@
 
 use std::{
     borrow::Cow,
+    collections::{BTreeMap, HashMap},
     fmt::Debug,
     fmt::Display,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        RwLock,
+    },
 };
 
-use crate::{BeatTimeBase, Note, PulseIterItem};
+use crate::{
+    memory::MemoryUsage, parameter::RhythmParameterValues, shared::SharedValues, BeatTimeBase,
+    Note, PulseIterItem, Tuning, Warning,
+};
 use fixed::{FixedEventIter, ToFixedEventIter, ToFixedEventIterSequence};
 
 use derive_more::{Deref, Display, From, Into};
 use fraction::{ConstOne, ConstZero, Fraction};
+use lazy_static::lazy_static;
 
 // -------------------------------------------------------------------------------------------------
 
+pub mod bassline;
+pub mod cloud;
 pub mod cycle;
+pub mod echo;
 pub mod empty;
 pub mod fixed;
+pub mod humanizer;
+pub mod layering;
 pub mod mutated;
+pub mod panner;
+pub mod piano_roll;
+pub mod pool;
+pub mod quantizer;
+pub mod relative;
 #[cfg(feature = "scripting")]
 pub mod scripted;
 #[cfg(feature = "scripting")]
 pub mod scripted_cycle;
+pub mod target;
+pub mod transform;
+pub mod transposer;
+pub mod voicing;
 
 // -------------------------------------------------------------------------------------------------
 
 /// Id to refer to a specific instrument/patch/sample in a [`NoteEvent`].
-#[derive(Copy, Clone, Debug, Display, Deref, From, Into, PartialEq, Eq, Hash)]
+///
+/// Ids can optionally get a display name via [`InstrumentId::register_name`], which then shows
+/// up in the id's `Display` impl, e.g. in logs and error messages.
+#[derive(Copy, Clone, Debug, Deref, From, Into, PartialEq, Eq, Hash)]
 pub struct InstrumentId(usize);
 
 /// Id to refer to a specific parameter in a [`ParameterChangeEvent`].
-#[derive(Copy, Clone, Debug, Display, Deref, From, Into, PartialEq, Eq, Hash)]
+///
+/// Ids can optionally get a display name via [`ParameterId::register_name`], which then shows
+/// up in the id's `Display` impl, e.g. in logs and error messages.
+#[derive(Copy, Clone, Debug, Deref, From, Into, PartialEq, Eq, Hash)]
 pub struct ParameterId(usize);
 
+lazy_static! {
+    static ref INSTRUMENT_NAMES: RwLock<HashMap<usize, String>> = RwLock::new(HashMap::new());
+    static ref PARAMETER_NAMES: RwLock<HashMap<usize, String>> = RwLock::new(HashMap::new());
+}
+
+macro_rules! impl_id_name_registry {
+    ($id_type:ident, $registry:ident, $kind:literal) => {
+        impl $id_type {
+            /// Register or replace the global display name of the id.
+            pub fn register_name<S: Into<String>>(self, name: S) {
+                $registry
+                    .write()
+                    .expect("failed to access id name registry")
+                    .insert(self.0, name.into());
+            }
+
+            /// Remove the global display name of the id, if any.
+            pub fn unregister_name(self) {
+                $registry
+                    .write()
+                    .expect("failed to access id name registry")
+                    .remove(&self.0);
+            }
+
+            /// Registered display name of the id, if any.
+            pub fn name(&self) -> Option<String> {
+                $registry
+                    .read()
+                    .expect("failed to access id name registry")
+                    .get(&self.0)
+                    .cloned()
+            }
+
+            /// Find a registered id by its display name.
+            pub fn from_name(name: &str) -> Option<Self> {
+                $registry
+                    .read()
+                    .expect("failed to access id name registry")
+                    .iter()
+                    .find(|(_, registered)| registered.as_str() == name)
+                    .map(|(id, _)| Self(*id))
+            }
+
+            /// Try creating an id which got registered with a display name before.
+            ///
+            /// Returns error when the id has no registered name.
+            pub fn try_from_registered(id: usize) -> Result<Self, String> {
+                if $registry
+                    .read()
+                    .expect("failed to access id name registry")
+                    .contains_key(&id)
+                {
+                    Ok(Self(id))
+                } else {
+                    Err(format!("{} id '{}' is not registered", $kind, id))
+                }
+            }
+        }
+
+        impl Display for $id_type {
+            /// Shows the numeric id, followed by the id's registered name, if any.
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                Display::fmt(&self.0, f)?;
+                if let Some(name) = self.name() {
+                    write!(f, " ({})", name)?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_id_name_registry!(InstrumentId, INSTRUMENT_NAMES, "instrument");
+impl_id_name_registry!(ParameterId, PARAMETER_NAMES, "parameter");
+
 // -------------------------------------------------------------------------------------------------
 
 /// Generate a new unique instrument id.
@@ -44,6 +147,27 @@ pub fn unique_instrument_id() -> InstrumentId {
 
 // -------------------------------------------------------------------------------------------------
 
+/// Value of a single entry in [`EventData`].
+#[derive(Clone, PartialEq, Debug, Display, From)]
+pub enum EventDataValue {
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+}
+
+impl From<&str> for EventDataValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+/// Custom user data of a [`NoteEvent`] or [`ParameterChangeEvent`], e.g. routing hints or UI
+/// colors for hosts. Event transforms pass the data through as it is.
+pub type EventData = BTreeMap<String, EventDataValue>;
+
+// -------------------------------------------------------------------------------------------------
+
 /// Single note event in a [`Event`].
 #[derive(Clone, PartialEq, Debug)]
 pub struct NoteEvent {
@@ -52,6 +176,7 @@ pub struct NoteEvent {
     pub volume: f32,  // [0 - INF]
     pub panning: f32, // [-1 - 1]
     pub delay: f32,   // [0 - 1]
+    pub extra: Option<EventData>,
 }
 
 impl NoteEvent {
@@ -76,6 +201,17 @@ impl NoteEvent {
             )
         }
     }
+
+    /// The note's pitch as fractional MIDI note number in the given tuning.
+    /// None for note-offs and empty notes.
+    pub fn pitch_in(&self, tuning: &Tuning) -> Option<f64> {
+        self.note.is_note_on().then(|| tuning.pitch(self.note))
+    }
+
+    /// The note's frequency in Hz in the given tuning. None for note-offs and empty notes.
+    pub fn frequency_in(&self, tuning: &Tuning) -> Option<f64> {
+        self.note.is_note_on().then(|| tuning.frequency(self.note))
+    }
 }
 
 impl<N: TryInto<Note>> From<N> for NoteEvent
@@ -91,6 +227,7 @@ where
             volume: 1.0,
             panning: 0.0,
             delay: 0.0,
+            extra: None,
         }
     }
 }
@@ -109,6 +246,7 @@ where
             volume: 1.0,
             panning: 0.0,
             delay: 0.0,
+            extra: None,
         }
     }
 }
@@ -128,6 +266,7 @@ where
             volume,
             panning: 0.0,
             delay: 0.0,
+            extra: None,
         }
     }
 }
@@ -148,6 +287,7 @@ where
             volume,
             panning,
             delay: 0.0,
+            extra: None,
         }
     }
 }
@@ -169,6 +309,7 @@ where
             volume,
             panning,
             delay,
+            extra: None,
         }
     }
 }
@@ -262,6 +403,7 @@ pub fn new_polyphonic_note_sequence_event<E: Into<NoteEvent>>(
 pub struct ParameterChangeEvent {
     pub parameter: Option<ParameterId>,
     pub value: f32,
+    pub extra: Option<EventData>,
 }
 
 impl ParameterChangeEvent {
@@ -295,7 +437,11 @@ pub fn new_parameter_change<Parameter: Into<Option<ParameterId>>>(
     value: f32,
 ) -> ParameterChangeEvent {
     let parameter: Option<ParameterId> = parameter.into();
-    ParameterChangeEvent { parameter, value }
+    ParameterChangeEvent {
+        parameter,
+        value,
+        extra: None,
+    }
 }
 
 /// Shortcut for creating a new [`ParameterChangeEvent`] [`EventIter`].
@@ -387,6 +533,24 @@ pub trait EventIter: Debug {
     /// Set optional, application specific external context data for the event iter.
     fn set_external_context(&mut self, data: &[(Cow<str>, f64)]);
 
+    /// Set the shared values of the sequence the event iter plays in. Only used by scripted
+    /// event iters: the default implementation ignores the values.
+    fn set_shared_values(&mut self, _values: &SharedValues) {}
+
+    /// Set the parameter values of the rhythm the event iter plays in. Only used by scripted
+    /// event iters and event iters which read parameters: the default implementation ignores
+    /// the values.
+    fn set_parameter_values(&mut self, _values: &RhythmParameterValues) {}
+
+    /// Set a new seed for the event iter's random number generator. The seed is also used in
+    /// all following resets. The default implementation ignores the seed, which is fine for
+    /// event iters which don't use random numbers.
+    fn set_seed(&mut self, _seed: [u8; 32]) {}
+
+    /// Play the emitted events backwards within each cycle. Only supported by cycle event
+    /// iters: the default implementation ignores it.
+    fn set_reversed(&mut self, _reversed: bool) {}
+
     /// Move iterator with the given pulse value forward.
     /// `pulse` contains the current value and timing information for the current step in the pattern.
     /// `emit_event` indicates whether the iterator should trigger the next event in the sequence as
@@ -395,11 +559,40 @@ pub trait EventIter: Debug {
     /// Returns an optional stack of event iter items, which should be emitted for the given pulse.
     fn run(&mut self, pulse: PulseIterItem, emit_event: bool) -> Option<Vec<EventIterItem>>;
 
+    /// Fetch and clear all non-fatal issues, which got detected while running the event iter.
+    /// The default implementation reports no warnings.
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        Vec::new()
+    }
+
+    /// Fetch and clear all errors of scripted callbacks, which failed while running the event iter.
+    /// The default implementation reports no errors.
+    fn take_script_errors(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Estimated memory usage of the event iter, e.g. its parsed cycle or the heap of a
+    /// scripted emitter's Lua engine. The default implementation reports no memory usage.
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::default()
+    }
+
+    /// Release unused memory, e.g. the garbage of a scripted emitter's Lua engine. The default
+    /// implementation does nothing.
+    fn trim_memory(&mut self) {}
+
     /// Create a new cloned instance of this event iter. This actualy is a clone(), wrapped into
     /// a `Box<dyn EventIter>`, but called 'duplicate' to avoid conflicts with possible
     /// Clone impls.
     fn duplicate(&self) -> Box<dyn EventIter>;
 
+    /// Create a new instance of this event iter for rhythm duplicates, which doesn't share any
+    /// state with this event iter, unlike `duplicate`. Fails when the state can't be copied,
+    /// e.g. for scripted coroutines. The default implementation returns a `duplicate`.
+    fn try_duplicate(&self) -> Result<Box<dyn EventIter>, String> {
+        Ok(self.duplicate())
+    }
+
     /// Reset/rewind the iterator to its initial state.
     fn reset(&mut self);
 }
@@ -419,3 +612,35 @@ impl Iterator for dyn EventIter {
         self.run(pulse, emit_event)
     }
 }
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn id_names() {
+        // use ids, which are not used in other tests, as registries are global
+        let kick = InstrumentId::from(10_001);
+        assert!(kick.name().is_none());
+        assert!(InstrumentId::try_from_registered(10_001).is_err());
+        assert_eq!(format!("#{:02}", kick), "#10001");
+        kick.register_name("kick");
+        assert_eq!(kick.name().as_deref(), Some("kick"));
+        assert_eq!(InstrumentId::from_name("kick"), Some(kick));
+        assert_eq!(InstrumentId::try_from_registered(10_001), Ok(kick));
+        assert_eq!(kick.to_string(), "10001 (kick)");
+        kick.unregister_name();
+        assert_eq!(kick.to_string(), "10001");
+
+        let cutoff = ParameterId::from(10_001);
+        cutoff.register_name("cutoff");
+        assert!(InstrumentId::from_name("cutoff").is_none());
+        assert_eq!(
+            new_parameter_change(cutoff, 0.5).to_string(),
+            "10001 (cutoff) 0.500"
+        );
+        cutoff.unregister_name();
+    }
+}
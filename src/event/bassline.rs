@@ -0,0 +1,292 @@
+use std::borrow::Cow;
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    event::{fixed::FixedEventIter, Event, EventIter, EventIterItem, NoteEvent},
+    rhythm::new_random_seed,
+    BeatTimeBase, Chord, Note, PulseIterItem, Scale,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Generates a bassline from a chord progression: plays roots on the first step of each chord,
+/// randomly picks roots, fifths and octaves on the following steps and approaches the next
+/// chord's root with a passing tone on the last step of a chord.
+///
+/// Each chord lasts `steps_per_chord` emitted pulses. The `density` sets the probability of
+/// playing a note on steps after the chord's first step, the `syncopation` moves notes from
+/// even to odd (off-beat) steps. Chords advance with each pulse, also with gated pulses. Steps without notes let the previous note sustain.
+///
+/// Passing tones are a semitone above or below the next root, or the nearest scale note when a
+/// [`Scale`] is set. When seeded, the emitter generates the same bassline after each reset.
+#[derive(Debug, Clone)]
+pub struct BasslineEventIter {
+    chords: Vec<Chord>,
+    steps_per_chord: usize,
+    octave: u8,
+    density: f32,
+    syncopation: f32,
+    scale: Option<Scale>,
+    step: usize,
+    note_event_state: Vec<Option<NoteEvent>>,
+    rand_gen: Xoshiro256PlusPlus,
+    seed: Option<[u8; 32]>,
+}
+
+impl BasslineEventIter {
+    /// Try creating a new bassline emitter from the given chord progression, where each chord
+    /// plays for `steps_per_chord` pulses.
+    ///
+    /// Returns error when the progression is empty or the step count is 0.
+    pub fn new(
+        chords: Vec<Chord>,
+        steps_per_chord: usize,
+        seed: Option<[u8; 32]>,
+    ) -> Result<Self, String> {
+        if chords.is_empty() {
+            return Err("bassline chord progression must not be empty".to_string());
+        }
+        if steps_per_chord == 0 {
+            return Err("bassline steps per chord must be > 0".to_string());
+        }
+        let octave = 2;
+        let density = 0.5;
+        let syncopation = 0.0;
+        let scale = None;
+        let step = 0;
+        let note_event_state = Vec::new();
+        let rand_seed = seed.unwrap_or_else(new_random_seed);
+        let rand_gen = Xoshiro256PlusPlus::from_seed(rand_seed);
+        Ok(Self {
+            chords,
+            steps_per_chord,
+            octave,
+            density,
+            syncopation,
+            scale,
+            step,
+            note_event_state,
+            rand_gen,
+            seed,
+        })
+    }
+
+    /// Return a new bassline emitter which plays chord roots in the given octave.
+    /// The default octave is 2.
+    #[must_use]
+    pub fn with_octave(self, octave: u8) -> Self {
+        let octave = octave.min(9);
+        Self { octave, ..self }
+    }
+
+    /// Return a new bassline emitter with the given note density in range \[0 - 1\].
+    #[must_use]
+    pub fn with_density(self, density: f32) -> Self {
+        let density = density.clamp(0.0, 1.0);
+        Self { density, ..self }
+    }
+
+    /// Return a new bassline emitter with the given syncopation amount in range \[0 - 1\].
+    #[must_use]
+    pub fn with_syncopation(self, syncopation: f32) -> Self {
+        let syncopation = syncopation.clamp(0.0, 1.0);
+        Self {
+            syncopation,
+            ..self
+        }
+    }
+
+    /// Return a new bassline emitter which picks passing tones from the given scale.
+    #[must_use]
+    pub fn with_scale<S: Into<Option<Scale>>>(self, scale: S) -> Self {
+        let scale = scale.into();
+        Self { scale, ..self }
+    }
+
+    /// Read-only access to the chord progression.
+    pub fn chords(&self) -> &Vec<Chord> {
+        &self.chords
+    }
+
+    fn root_note(&self, chord: &Chord) -> Note {
+        let key = chord.bass().unwrap_or(chord.note()).key();
+        Note::from((self.octave * 12 + key).min(0x7f))
+    }
+
+    fn fifth_note(&self, chord: &Chord) -> Note {
+        // use the chord's own fifth, e.g. for diminished chords
+        let interval = chord
+            .triad()
+            .iter()
+            .find(|interval| (6..=8).contains(*interval))
+            .copied()
+            .unwrap_or(7);
+        self.root_note(chord).transposed(interval as i32)
+    }
+
+    fn passing_note(&mut self, next_root: Note) -> Note {
+        let direction = if self.rand_gen.gen_bool(0.5) { 1 } else { -1 };
+        if let Some(scale) = &self.scale {
+            // nearest scale note in the given direction
+            let degrees = scale.degrees();
+            for distance in 1..12 {
+                let note = next_root.transposed(direction * distance);
+                let step = (note.key() + 12 - scale.key()) % 12;
+                if degrees[step as usize] != 0 {
+                    return note;
+                }
+            }
+        }
+        next_root.transposed(direction)
+    }
+
+    fn should_play(&mut self, chord_step: usize) -> bool {
+        if chord_step == 0 {
+            return true;
+        }
+        let probability = if chord_step % 2 == 1 {
+            (self.density * (1.0 + self.syncopation)).min(1.0)
+        } else {
+            self.density * (1.0 - self.syncopation)
+        };
+        probability > 0.0 && self.rand_gen.gen_range(0.0..1.0) < probability
+    }
+
+    fn next_note(&mut self) -> Option<Note> {
+        let chord_index = (self.step / self.steps_per_chord) % self.chords.len();
+        let chord_step = self.step % self.steps_per_chord;
+        self.step += 1;
+        if !self.should_play(chord_step) {
+            return None;
+        }
+        let chord = &self.chords[chord_index];
+        let root = self.root_note(chord);
+        if chord_step == 0 {
+            return Some(root);
+        }
+        let next_chord = &self.chords[(chord_index + 1) % self.chords.len()];
+        let next_root = self.root_note(next_chord);
+        if chord_step == self.steps_per_chord - 1 && next_root != root {
+            return Some(self.passing_note(next_root));
+        }
+        let fifth = self.fifth_note(chord);
+        let note = match self.rand_gen.gen_range(0.0..1.0) {
+            value if value < 0.6 => root,
+            value if value < 0.9 => fifth,
+            _ => root.transposed(12),
+        };
+        Some(note)
+    }
+}
+
+impl EventIter for BasslineEventIter {
+    fn set_time_base(&mut self, _time_base: &BeatTimeBase) {
+        // nothing to do
+    }
+
+    fn set_external_context(&mut self, _data: &[(Cow<str>, f64)]) {
+        // nothing to do
+    }
+
+    fn run(&mut self, _pulse: PulseIterItem, emit_event: bool) -> Option<Vec<EventIterItem>> {
+        // advance with each pulse, so chord changes stay in sync with the rhythm
+        let note = self.next_note();
+        if !emit_event {
+            return None;
+        }
+        let note = note?;
+        let mut event = Event::NoteEvents(vec![Some(NoteEvent::from(note))]);
+        FixedEventIter::normalize_event(&mut event, &mut self.note_event_state);
+        Some(vec![EventIterItem::new(event)])
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+    }
+
+    fn duplicate(&self) -> Box<dyn EventIter> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self) {
+        // reset progression state
+        self.step = 0;
+        self.note_event_state.clear();
+        // reset random number generator to its initial state when the emitter is seeded
+        if let Some(seed) = self.seed {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        }
+        // else create a new random number generator from a random seed
+        else {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(new_random_seed());
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn played_notes(event_iter: &mut BasslineEventIter, count: usize) -> Vec<Option<Note>> {
+        (0..count)
+            .map(|_| {
+                event_iter
+                    .run(PulseIterItem::default(), true)
+                    .and_then(|items| match &items[0].event {
+                        Event::NoteEvents(notes) => notes[0].as_ref().map(|n| n.note),
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bassline() -> Result<(), String> {
+        assert!(BasslineEventIter::new(vec![], 4, None).is_err());
+        assert!(BasslineEventIter::new(vec![Chord::try_from("c'maj")?], 0, None).is_err());
+
+        let seed = Some([1; 32]);
+        let chords = vec![Chord::try_from("c'maj")?, Chord::try_from("g'min")?];
+        let mut event_iter = BasslineEventIter::new(chords.clone(), 4, seed)?.with_density(1.0);
+        let notes = played_notes(&mut event_iter, 8);
+        // roots on chord starts
+        assert_eq!(notes[0], Some(Note::C2));
+        assert_eq!(notes[4], Some(Note::G2));
+        // passing tones to the next root
+        assert!(matches!(notes[3], Some(Note::Fs2 | Note::Gs2)));
+        assert!(matches!(notes[7], Some(Note::B1 | Note::Cs2)));
+        // roots, fifths or octaves inbetween
+        assert!(notes[1..3]
+            .iter()
+            .all(|n| matches!(n, Some(Note::C2 | Note::G2 | Note::C3))));
+
+        // seeded emitters repeat after reset
+        event_iter.reset();
+        assert_eq!(played_notes(&mut event_iter, 8), notes);
+
+        // scale aware passing tones
+        let scale = Scale::try_from((Note::C4, "major"))?;
+        let mut event_iter = BasslineEventIter::new(chords.clone(), 4, seed)?
+            .with_density(1.0)
+            .with_octave(3)
+            .with_scale(scale);
+        let notes = played_notes(&mut event_iter, 8);
+        assert_eq!(notes[0], Some(Note::C3));
+        assert!(matches!(notes[3], Some(Note::F3 | Note::A3)));
+        assert!(matches!(notes[7], Some(Note::B2 | Note::D3)));
+
+        // syncopated basslines only play on chord starts and off-beats
+        let mut event_iter = BasslineEventIter::new(chords, 4, seed)?
+            .with_density(1.0)
+            .with_syncopation(1.0);
+        let notes = played_notes(&mut event_iter, 8);
+        assert!(notes[2].is_none() && notes[6].is_none());
+        assert!(notes[1].is_some() && notes[3].is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+use std::borrow::Cow;
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    event::{Event, EventIter, EventIterItem, InstrumentId, NoteEvent},
+    parameter::{RhythmParameter, RhythmParameterValues},
+    rhythm::new_random_seed,
+    BeatTimeBase, Note, PulseIterItem, Scale,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Parameter id of the cloud's note probability per pulse.
+pub const CLOUD_DENSITY_PARAMETER: &str = "cloud.density";
+/// Parameter id of the cloud's center note.
+pub const CLOUD_REGISTER_PARAMETER: &str = "cloud.register";
+/// Parameter id of the cloud's note range around the center note in semitones.
+pub const CLOUD_SPREAD_PARAMETER: &str = "cloud.spread";
+/// Parameter id of the cloud's average note volume.
+pub const CLOUD_VELOCITY_PARAMETER: &str = "cloud.velocity";
+
+/// Max note range around the center note in semitones.
+const MAX_SPREAD: u8 = 48;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Generates a texture of sparse, random notes from a [`Scale`]: a probability cloud, e.g.
+/// as ambient building block.
+///
+/// On each pulse, a note gets played with the cloud's `density` probability. Notes are picked
+/// randomly from all scale notes within `spread` semitones around the `register` center note
+/// and play with the cloud's `velocity`, randomly varied by the velocity variation. Notes are
+/// distributed over multiple voices, so they overlap and ring out until their voice gets
+/// reused.
+///
+/// Density, register, spread and velocity are exposed as [`RhythmParameter`]S, so their
+/// contours can be shaped over time, e.g. with parameter ramps of a
+/// [`Sequence`](crate::Sequence). To do so, use the same [`RhythmParameterValues`] for the
+/// cloud and its rhythm: see [`with_parameter_values`](Self::with_parameter_values).
+///
+/// Clouds don't depend on musical time: run them in a
+/// [`SecondTimeRhythm`](crate::prelude::SecondTimeRhythm) to make them tempo-independent.
+/// When seeded, the cloud generates the same notes after each reset.
+#[derive(Debug, Clone)]
+pub struct CloudEventIter {
+    scale: Scale,
+    density: f32,
+    register: u8,
+    spread: u8,
+    velocity: f32,
+    velocity_variation: f32,
+    voices: usize,
+    voice: usize,
+    parameter_values: Option<RhythmParameterValues>,
+    rand_gen: Xoshiro256PlusPlus,
+    seed: Option<[u8; 32]>,
+}
+
+impl CloudEventIter {
+    /// Create a new cloud which picks notes from the given scale. By default, clouds play
+    /// with a density of 0.25 within an octave around the scale's root note on 4 voices.
+    pub fn new(scale: Scale, seed: Option<[u8; 32]>) -> Self {
+        let density = 0.25;
+        let register = u8::from(scale.notes()[0]).saturating_add(6).min(0x7f);
+        let spread = 6;
+        let velocity = 0.7;
+        let velocity_variation = 0.2;
+        let voices = 4;
+        let voice = 0;
+        let parameter_values = None;
+        let rand_seed = seed.unwrap_or_else(new_random_seed);
+        let rand_gen = Xoshiro256PlusPlus::from_seed(rand_seed);
+        Self {
+            scale,
+            density,
+            register,
+            spread,
+            velocity,
+            velocity_variation,
+            voices,
+            voice,
+            parameter_values,
+            rand_gen,
+            seed,
+        }
+    }
+
+    /// Return a new cloud with the given note probability per pulse in range \[0 - 1\].
+    #[must_use]
+    pub fn with_density(self, density: f32) -> Self {
+        let density = density.clamp(0.0, 1.0);
+        Self { density, ..self }
+    }
+
+    /// Return a new cloud which picks notes around the given center note.
+    #[must_use]
+    pub fn with_register(self, register: Note) -> Self {
+        let register = u8::from(register).min(0x7f);
+        Self { register, ..self }
+    }
+
+    /// Return a new cloud which picks notes within the given semitones around its center note.
+    #[must_use]
+    pub fn with_spread(self, spread: u8) -> Self {
+        let spread = spread.min(MAX_SPREAD);
+        Self { spread, ..self }
+    }
+
+    /// Return a new cloud with the given average note volume and random volume variation,
+    /// both in range \[0 - 1\].
+    #[must_use]
+    pub fn with_velocity(self, velocity: f32, variation: f32) -> Self {
+        let velocity = velocity.clamp(0.0, 1.0);
+        let velocity_variation = variation.clamp(0.0, 1.0);
+        Self {
+            velocity,
+            velocity_variation,
+            ..self
+        }
+    }
+
+    /// Return a new cloud which distributes notes over the given number of voices.
+    #[must_use]
+    pub fn with_voices(self, voices: usize) -> Self {
+        let voices = voices.max(1);
+        Self { voices, ..self }
+    }
+
+    /// Return a new cloud which reads its parameter values from the given store. The cloud's
+    /// parameters get added to the store, using the cloud's current settings as default
+    /// values. Pass the same store to the `with_parameter_values` function of the cloud's
+    /// rhythm, so the parameters can be automated.
+    #[must_use]
+    pub fn with_parameter_values(self, parameter_values: RhythmParameterValues) -> Self {
+        parameter_values.add_parameters(self.parameters());
+        let parameter_values = Some(parameter_values);
+        Self {
+            parameter_values,
+            ..self
+        }
+    }
+
+    /// Read-only access to the cloud's scale.
+    pub fn scale(&self) -> &Scale {
+        &self.scale
+    }
+
+    /// Describe the cloud's controls as parameters, using the cloud's current settings as
+    /// default values.
+    pub fn parameters(&self) -> Vec<RhythmParameter> {
+        vec![
+            RhythmParameter::new(CLOUD_DENSITY_PARAMETER, 0.0..=1.0, self.density as f64)
+                .with_name("Density"),
+            RhythmParameter::new(CLOUD_REGISTER_PARAMETER, 0.0..=127.0, self.register as f64)
+                .with_name("Register")
+                .with_integer(true),
+            RhythmParameter::new(
+                CLOUD_SPREAD_PARAMETER,
+                0.0..=MAX_SPREAD as f64,
+                self.spread as f64,
+            )
+            .with_name("Spread")
+            .with_integer(true),
+            RhythmParameter::new(CLOUD_VELOCITY_PARAMETER, 0.0..=1.0, self.velocity as f64)
+                .with_name("Velocity"),
+        ]
+    }
+
+    fn apply_parameter_values(&mut self) {
+        let Some(values) = &self.parameter_values else {
+            return;
+        };
+        if let Some(density) = values.value(CLOUD_DENSITY_PARAMETER) {
+            self.density = (density as f32).clamp(0.0, 1.0);
+        }
+        if let Some(register) = values.value(CLOUD_REGISTER_PARAMETER) {
+            self.register = register.round().clamp(0.0, 127.0) as u8;
+        }
+        if let Some(spread) = values.value(CLOUD_SPREAD_PARAMETER) {
+            self.spread = spread.round().clamp(0.0, MAX_SPREAD as f64) as u8;
+        }
+        if let Some(velocity) = values.value(CLOUD_VELOCITY_PARAMETER) {
+            self.velocity = (velocity as f32).clamp(0.0, 1.0);
+        }
+    }
+
+    fn next_note_event(&mut self) -> Option<NoteEvent> {
+        if self.density <= 0.0 || self.rand_gen.gen_range(0.0..1.0) >= self.density {
+            return None;
+        }
+        // pick a random scale note within the register
+        let low = self.register.saturating_sub(self.spread);
+        let high = self.register.saturating_add(self.spread).min(0x7f);
+        let degrees = self.scale.degrees();
+        let notes = (low..=high)
+            .filter(|note| degrees[((note + 12 - self.scale.key()) % 12) as usize] != 0)
+            .collect::<Vec<_>>();
+        if notes.is_empty() {
+            return None;
+        }
+        let note = Note::from(notes[self.rand_gen.gen_range(0..notes.len())]);
+        let variation = if self.velocity_variation > 0.0 {
+            self.rand_gen
+                .gen_range(-self.velocity_variation..=self.velocity_variation)
+        } else {
+            0.0
+        };
+        let volume = (self.velocity * (1.0 + variation)).clamp(0.0, 1.0);
+        Some(NoteEvent::from((note, None::<InstrumentId>, volume)))
+    }
+}
+
+impl EventIter for CloudEventIter {
+    fn set_time_base(&mut self, _time_base: &BeatTimeBase) {
+        // nothing to do
+    }
+
+    fn set_external_context(&mut self, _data: &[(Cow<str>, f64)]) {
+        // nothing to do
+    }
+
+    fn run(&mut self, _pulse: PulseIterItem, emit_event: bool) -> Option<Vec<EventIterItem>> {
+        self.apply_parameter_values();
+        // advance with each pulse, so seeded clouds don't depend on the gate
+        let note_event = self.next_note_event();
+        if !emit_event {
+            return None;
+        }
+        let note_event = note_event?;
+        let mut note_events = vec![None; self.voices];
+        note_events[self.voice % self.voices] = Some(note_event);
+        self.voice = (self.voice + 1) % self.voices;
+        Some(vec![EventIterItem::new(Event::NoteEvents(note_events))])
+    }
+
+    fn set_parameter_values(&mut self, values: &RhythmParameterValues) {
+        // only follow parameters when the cloud got automatable
+        if self.parameter_values.is_some() {
+            self.parameter_values = Some(values.clone());
+        }
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+    }
+
+    fn duplicate(&self) -> Box<dyn EventIter> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self) {
+        // reset voice state
+        self.voice = 0;
+        // reset random number generator to its initial state when the cloud is seeded
+        if let Some(seed) = self.seed {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        }
+        // else create a new random number generator from a random seed
+        else {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(new_random_seed());
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{RhythmIter, SecondTimeBase};
+
+    #[test]
+    fn cloud() -> Result<(), String> {
+        let scale = Scale::try_from((Note::C4, "major"))?;
+        let seed = Some([1; 32]);
+        let played_notes = |cloud: &mut CloudEventIter, count: usize| {
+            (0..count)
+                .filter_map(|_| cloud.run(PulseIterItem::default(), true))
+                .map(|items| match &items[0].event {
+                    Event::NoteEvents(notes) => notes.iter().flatten().next().cloned(),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // notes are scale notes within the register
+        let mut cloud = CloudEventIter::new(scale.clone(), seed)
+            .with_density(1.0)
+            .with_register(Note::C5)
+            .with_spread(12)
+            .with_velocity(0.5, 0.0);
+        let notes = played_notes(&mut cloud, 32);
+        assert_eq!(notes.len(), 32);
+        assert!(notes.iter().flatten().all(|note_event| {
+            (Note::C4..=Note::C6).contains(&note_event.note)
+                && scale
+                    .notes()
+                    .iter()
+                    .any(|n| n.key() == note_event.note.key())
+                && note_event.volume == 0.5
+        }));
+        // seeded clouds repeat after reset
+        cloud.reset();
+        assert_eq!(played_notes(&mut cloud, 32), notes);
+
+        // density
+        let mut cloud = CloudEventIter::new(scale.clone(), seed).with_density(0.0);
+        assert!(played_notes(&mut cloud, 32).is_empty());
+
+        // parameters
+        let values = RhythmParameterValues::default();
+        let mut cloud = CloudEventIter::new(scale, seed)
+            .with_density(0.0)
+            .with_parameter_values(values.clone());
+        assert_eq!(values.parameters().len(), 4);
+        values.set_value(CLOUD_DENSITY_PARAMETER, 1.0)?;
+        values.set_value(CLOUD_SPREAD_PARAMETER, 0.0)?;
+        values.set_value(CLOUD_REGISTER_PARAMETER, 67.0)?;
+        let notes = played_notes(&mut cloud, 4);
+        assert_eq!(notes.len(), 4);
+        assert!(notes.iter().flatten().all(|n| n.note == Note::G5));
+
+        // tempo independent second time rhythms
+        let time_base = SecondTimeBase {
+            samples_per_sec: 1000,
+        };
+        let mut rhythm = time_base.every_nth_seconds(0.5).trigger(
+            CloudEventIter::new(Scale::try_from((Note::C4, "major"))?, seed).with_density(1.0),
+        );
+        let times = (0..4)
+            .filter_map(|_| rhythm.run_until_time(2000))
+            .map(|item| item.time)
+            .collect::<Vec<_>>();
+        assert_eq!(times, vec![0, 500, 1000, 1500]);
+        Ok(())
+    }
+}
@@ -1,11 +1,17 @@
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::HashMap, mem::size_of};
 
 use fraction::Fraction;
 
 use crate::{
-    event::{new_note, Event, EventIter, EventIterItem, InstrumentId, NoteEvent},
+    event::{
+        new_note, new_parameter_change, relative::RelativeNote, target::TargetSchema,
+        voicing::VoiceSpread, Event, EventData, EventDataValue, EventIter, EventIterItem,
+        InstrumentId, NoteEvent, ParameterId,
+    },
+    memory::MemoryUsage,
     tidal::{Cycle, Event as CycleEvent, Target as CycleTarget, Value as CycleValue},
-    BeatTimeBase, Chord, Note, PulseIterItem,
+    warning::{ValueRangePolicy, WarningCollector, WarningKind},
+    BeatTimeBase, Chord, Note, PulseIterItem, Warning,
 };
 
 // -------------------------------------------------------------------------------------------------
@@ -36,11 +42,17 @@ impl TryFrom<&CycleValue> for Vec<Option<NoteEvent>> {
             CycleValue::Pitch(p) => Ok(vec![new_note(Note::from(p.midi_note()))]),
             CycleValue::Chord(p, m) => {
                 let chord = Chord::try_from((p.midi_note(), m.as_ref()))?;
-                Ok(chord
-                    .intervals()
-                    .iter()
-                    .map(|i| new_note(chord.note().transposed(*i as i32)))
-                    .collect())
+                Ok(chord.notes().into_iter().map(new_note).collect())
+            }
+            CycleValue::Glide(p, t) => {
+                // pass glide target as extra note data
+                let mut note_event = NoteEvent::from(Note::from(p.midi_note()));
+                let extra = EventData::from([(
+                    "glide".to_string(),
+                    EventDataValue::Integer(t.midi_note() as i64),
+                )]);
+                note_event.extra = Some(extra);
+                Ok(vec![Some(note_event)])
             }
             CycleValue::Name(s) => {
                 if s.eq_ignore_ascii_case("off") {
@@ -55,6 +67,178 @@ impl TryFrom<&CycleValue> for Vec<Option<NoteEvent>> {
 
 // -------------------------------------------------------------------------------------------------
 
+/// Defines how pitch glides in cycles, e.g. `c4~g4`, get emitted by a [`CycleEventIter`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CycleGlideMode {
+    /// Emit the glide's start note only, with the glide's target note number set as `glide`
+    /// value in the note's extra data, so hosts can apply the pitch curve on their own.
+    #[default]
+    NoteData,
+    /// Emit the glide's start note along with `steps` parameter changes, which ramp the given
+    /// parameter from 0 to the glide's pitch offset in semitones across the glide's span.
+    /// Parameters are not reset for notes without glides.
+    ParameterRamp {
+        parameter: ParameterId,
+        steps: usize,
+    },
+}
+
+impl CycleGlideMode {
+    /// Create pitch ramp parameter change items for a glide with the given pitch offset and
+    /// time span. Returns an empty list when glides are emitted as note data.
+    fn ramp_items(&self, semitones: f32, start: Fraction, length: Fraction) -> Vec<EventIterItem> {
+        match *self {
+            CycleGlideMode::NoteData => vec![],
+            CycleGlideMode::ParameterRamp { parameter, steps } => {
+                let steps = steps.max(1);
+                let step_length = length / Fraction::from(steps as u64);
+                (0..steps)
+                    .map(|step| {
+                        let value = if steps > 1 {
+                            semitones * step as f32 / (steps - 1) as f32
+                        } else {
+                            semitones
+                        };
+                        let event =
+                            Event::ParameterChangeEvent(new_parameter_change(parameter, value));
+                        let start = start + step_length * Fraction::from(step as u64);
+                        EventIterItem::new_with_fraction(event, start, step_length)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Resolve the instrument for the given cycle channel from a list of channel instruments.
+/// Channel indices which exceed the instrument list wrap around.
+pub(crate) fn channel_instrument(
+    instruments: &[InstrumentId],
+    channel_index: usize,
+) -> Option<InstrumentId> {
+    if instruments.is_empty() {
+        None
+    } else {
+        Some(instruments[channel_index % instruments.len()])
+    }
+}
+
+/// Note event extra data key, which assigns a note to a choke group, as set by `chokeN` cycle
+/// targets such as `oh:choke1`. See `ChokeGroups` in the player.
+pub const CHOKE_GROUP_KEY: &str = "choke";
+
+/// Note event extra data key, which holds a note's pitch offset in cents, as set by fractional
+/// note values in cycles with enabled fractional notes, e.g. `60.5`.
+pub const DETUNE_KEY: &str = "detune";
+
+/// Report unmapped identifiers of the given unmapped cycle value and apply the given policy to
+/// out of range note values. Returns error when a note value is out of range in strict mode.
+pub(crate) fn check_cycle_value(
+    policy: ValueRangePolicy,
+    warnings: &mut WarningCollector,
+    value: &CycleValue,
+) -> Result<(), String> {
+    match value {
+        CycleValue::Name(name) if !name.eq_ignore_ascii_case("off") => warnings.add(
+            WarningKind::Unmapped,
+            format!("unmapped cycle identifier '{}' plays nothing", name),
+        ),
+        CycleValue::Float(f) => warnings.add(
+            WarningKind::Unmapped,
+            format!("unmapped cycle value '{}' plays nothing", f),
+        ),
+        CycleValue::Integer(i) if !(0..=0x7f).contains(i) => match policy {
+            ValueRangePolicy::Strict => {
+                return Err(format!("cycle note value {} must be in range [0..=127]", i));
+            }
+            ValueRangePolicy::Clamp => warnings.add(
+                WarningKind::Clamped,
+                format!(
+                    "cycle note value {} got clamped to {}",
+                    i,
+                    (*i).clamp(0, 0x7f)
+                ),
+            ),
+        },
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Convert a fractional MIDI note value to a note event, passing the fractional part as
+/// [`DETUNE_KEY`] cents. Out of range values get clamped and reported as warnings, or are
+/// errors in strict mode. Returns None for non finite values.
+pub(crate) fn fractional_note_event(
+    policy: ValueRangePolicy,
+    warnings: &mut WarningCollector,
+    value: f64,
+) -> Result<Option<NoteEvent>, String> {
+    if !value.is_finite() {
+        return Ok(None);
+    }
+    let pitch = value.clamp(0.0, 127.0);
+    if pitch != value {
+        if policy == ValueRangePolicy::Strict {
+            return Err(format!(
+                "cycle note value {} must be in range [0..=127]",
+                value
+            ));
+        }
+        warnings.add(
+            WarningKind::Clamped,
+            format!("cycle note value {} got clamped to {}", value, pitch),
+        );
+    }
+    let note = pitch.floor();
+    let mut note_event = NoteEvent::from(Note::from(note as u8));
+    let detune = (pitch - note) * 100.0;
+    if detune != 0.0 {
+        note_event.extra = Some(EventData::from([(
+            DETUNE_KEY.to_string(),
+            EventDataValue::Number(detune),
+        )]));
+    }
+    Ok(Some(note_event))
+}
+
+/// Assign note events to the choke group of the given cycle target, if it's a `chokeN` target.
+pub(crate) fn apply_choke_group_target(
+    target: &CycleTarget,
+    note_events: &mut [Option<NoteEvent>],
+) {
+    if let CycleTarget::Name(name) = target {
+        if let Some(group) = name
+            .strip_prefix("choke")
+            .and_then(|group| group.parse::<u32>().ok())
+        {
+            for note_event in note_events.iter_mut().flatten() {
+                note_event.extra.get_or_insert_with(EventData::new).insert(
+                    CHOKE_GROUP_KEY.to_string(),
+                    EventDataValue::Integer(group as i64),
+                );
+            }
+        }
+    }
+}
+
+/// Estimated memory usage of a parsed cycle and its note mappings in bytes.
+pub(crate) fn cycle_memory_usage(
+    cycle: &Cycle,
+    mappings: &HashMap<String, Vec<Option<NoteEvent>>>,
+) -> usize {
+    cycle.memory_usage()
+        + mappings
+            .iter()
+            .map(|(key, note_events)| {
+                key.capacity() + note_events.capacity() * size_of::<Option<NoteEvent>>()
+            })
+            .sum::<usize>()
+}
+
+// -------------------------------------------------------------------------------------------------
+
 /// Helper struct to convert time tagged events from Cycle into a `Vec<EventIterItem>`
 pub(crate) struct CycleNoteEvents {
     // collected events for a given time span per channels
@@ -148,20 +332,45 @@ impl CycleNoteEvents {
 /// Emits a vector of [`EventIterItem`] from a Tidal [`Cycle`].
 ///
 /// Channels from cycle are merged down into note events on different voices.
-/// Values in cycles can be mapped to notes with an optional mapping table.
+/// Values in cycles can be mapped to notes with an optional mapping table. Channels can
+/// optionally be assigned to instruments, which then apply to all notes without a target.
 ///
 /// See also [`ScriptedCycleEventIter`](`super::scripted_cycle::ScriptedCycleEventIter`)
 #[derive(Clone, Debug)]
 pub struct CycleEventIter {
     cycle: Cycle,
     mappings: HashMap<String, Vec<Option<NoteEvent>>>,
+    channel_instruments: Vec<InstrumentId>,
+    voice_spread: Option<VoiceSpread>,
+    glide_mode: CycleGlideMode,
+    fractional_notes: bool,
+    target_schema: Option<TargetSchema>,
+    value_range_policy: ValueRangePolicy,
+    warnings: WarningCollector,
 }
 
 impl CycleEventIter {
     /// Create a new cycle event iter from the given precompiled cycle.
     pub(crate) fn new(cycle: Cycle) -> Self {
         let mappings = HashMap::new();
-        Self { cycle, mappings }
+        let channel_instruments = Vec::new();
+        let voice_spread = None;
+        let glide_mode = CycleGlideMode::default();
+        let fractional_notes = false;
+        let target_schema = None;
+        let value_range_policy = ValueRangePolicy::Clamp;
+        let warnings = WarningCollector::new();
+        Self {
+            cycle,
+            mappings,
+            channel_instruments,
+            voice_spread,
+            glide_mode,
+            fractional_notes,
+            target_schema,
+            value_range_policy,
+            warnings,
+        }
     }
 
     /// Try creating a new cycle event iter from the given mini notation string.
@@ -191,15 +400,98 @@ impl CycleEventIter {
         Self { mappings, ..self }
     }
 
+    /// Return a new cycle which assigns the given instruments to the cycle's stack channels.
+    /// Channels which exceed the given instrument list wrap around. Note events with explicit
+    /// targets or instruments are not affected.
+    #[must_use]
+    pub fn with_channel_instruments(self, instruments: &[InstrumentId]) -> Self {
+        let channel_instruments = instruments.to_vec();
+        Self {
+            channel_instruments,
+            ..self
+        }
+    }
+
+    /// Return a new cycle which spreads big chords in the cycle's note events with the given
+    /// voice spread.
+    #[must_use]
+    pub fn with_voice_spread<S: Into<Option<VoiceSpread>>>(self, spread: S) -> Self {
+        let voice_spread = spread.into();
+        Self {
+            voice_spread,
+            ..self
+        }
+    }
+
+    /// Return a new cycle which emits pitch glides with the given glide mode.
+    #[must_use]
+    pub fn with_glide_mode(self, glide_mode: CycleGlideMode) -> Self {
+        Self { glide_mode, ..self }
+    }
+
+    /// Return a new cycle which interprets float values as fractional MIDI notes, e.g. for
+    /// microtonal targets: `60.5` plays note 60, detuned by 50 cents via [`DETUNE_KEY`].
+    /// By default, float values are unmapped and play nothing.
+    #[must_use]
+    pub fn with_fractional_notes(self, fractional_notes: bool) -> Self {
+        Self {
+            fractional_notes,
+            ..self
+        }
+    }
+
+    /// Return a new cycle which validates named targets such as `v=0.5` with the given
+    /// schema and applies their values to the cycle's note events. Without a schema, named
+    /// targets other than choke groups are ignored.
+    #[must_use]
+    pub fn with_target_schema<S: Into<Option<TargetSchema>>>(self, schema: S) -> Self {
+        let target_schema = schema.into();
+        Self {
+            target_schema,
+            ..self
+        }
+    }
+
+    /// Return a new cycle which applies the given policy to out of range note values. By
+    /// default, out of range values get clamped. With a strict policy, events with out of
+    /// range values get dropped. Both get reported as warnings.
+    #[must_use]
+    pub fn with_value_range_policy(self, value_range_policy: ValueRangePolicy) -> Self {
+        Self {
+            value_range_policy,
+            ..self
+        }
+    }
+
     /// Generate a note event from a single cycle event, applying mappings if necessary
-    fn note_events(&mut self, event: CycleEvent) -> Result<Vec<Option<NoteEvent>>, String> {
+    fn note_events(
+        &mut self,
+        channel_index: usize,
+        event: CycleEvent,
+    ) -> Result<Vec<Option<NoteEvent>>, String> {
         let mut note_events = {
             if let Some(note_events) = self.mappings.get(event.string()) {
                 // apply custom note mappings
                 note_events.clone()
+            } else if let Ok(relative_note) = RelativeNote::try_from(event.string()) {
+                // relative notes get resolved by the rhythm at emit time
+                vec![Some(relative_note.to_note_event())]
             } else {
-                // try converting the cycle value to a single note
-                event.value().try_into()?
+                match event.value() {
+                    CycleValue::Float(value) if self.fractional_notes => {
+                        // interpret floats as fractional notes
+                        vec![fractional_note_event(
+                            self.value_range_policy,
+                            &mut self.warnings,
+                            *value,
+                        )?]
+                    }
+                    value => {
+                        // try converting the cycle value to a single note
+                        check_cycle_value(self.value_range_policy, &mut self.warnings, value)?;
+                        value.try_into()?
+                    }
+                }
             }
         };
         // inject target instrument, if present
@@ -210,6 +502,22 @@ impl CycleEventIter {
                 }
             }
         }
+        // inject choke group, if present
+        apply_choke_group_target(event.target(), &mut note_events);
+        // apply named target values, if present
+        if let Some(schema) = &self.target_schema {
+            schema.apply(&mut self.warnings, event.target(), &mut note_events)?;
+        }
+        // inject channel instrument, if present
+        if let Some(instrument) = channel_instrument(&self.channel_instruments, channel_index) {
+            for note_event in note_events.iter_mut().flatten() {
+                note_event.instrument = note_event.instrument.or(Some(instrument));
+            }
+        }
+        // spread big chords, if enabled
+        if let Some(voice_spread) = &self.voice_spread {
+            voice_spread.apply(&mut note_events);
+        }
         Ok(note_events)
     }
 
@@ -227,26 +535,56 @@ impl CycleEventIter {
             }
         };
         let mut timed_note_events = CycleNoteEvents::new();
+        let mut ramp_items = Vec::new();
         // convert possibly mapped cycle channel items to a list of note events
         for (channel_index, channel_events) in events.into_iter().enumerate() {
             for event in channel_events.into_iter() {
                 let start = event.span().start();
                 let length = event.span().length();
-                match self.note_events(event) {
-                    Ok(note_events) => {
+                let glide = match event.value() {
+                    CycleValue::Glide(p, t) => Some(t.midi_note() as f32 - p.midi_note() as f32),
+                    _ => None,
+                };
+                match self.note_events(channel_index, event) {
+                    Ok(mut note_events) => {
+                        if let Some(semitones) = glide {
+                            let mut items = self.glide_mode.ramp_items(semitones, start, length);
+                            if !items.is_empty() {
+                                // glides get emitted as parameter ramps only
+                                for note_event in note_events.iter_mut().flatten() {
+                                    let is_empty = note_event.extra.as_mut().is_some_and(|extra| {
+                                        extra.remove("glide");
+                                        extra.is_empty()
+                                    });
+                                    if is_empty {
+                                        note_event.extra = None;
+                                    }
+                                }
+                                ramp_items.append(&mut items);
+                            }
+                        }
                         if !note_events.is_empty() {
                             timed_note_events.add(channel_index, start, length, note_events);
                         }
                     }
                     Err(err) => {
-                        //  NB: only expected error here is a chord parser error
-                        panic!("Cycle runtime error: {err}");
+                        // skip events with invalid values, e.g. out of range values in strict mode
+                        self.warnings.add(
+                            WarningKind::Dropped,
+                            format!("{}: cycle event got dropped", err),
+                        );
                     }
                 }
             }
         }
         // convert timed note events into EventIterItems
-        timed_note_events.into_event_iter_items()
+        let mut event_iter_items = timed_note_events.into_event_iter_items();
+        if !ramp_items.is_empty() {
+            // merge in glide parameter ramps, keeping notes in front of ramps
+            event_iter_items.append(&mut ramp_items);
+            event_iter_items.sort_by(|a, b| a.start.cmp(&b.start));
+        }
+        event_iter_items
     }
 }
 
@@ -267,6 +605,25 @@ impl EventIter for CycleEventIter {
         }
     }
 
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.cycle.set_seed(seed);
+    }
+
+    fn set_reversed(&mut self, reversed: bool) {
+        self.cycle.set_reversed(reversed);
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        self.warnings.take()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            cycles: cycle_memory_usage(&self.cycle, &self.mappings),
+            ..MemoryUsage::default()
+        }
+    }
+
     fn duplicate(&self) -> Box<dyn EventIter> {
         Box::new(self.clone())
     }
@@ -285,3 +642,195 @@ pub fn new_cycle_event(input: &str) -> Result<CycleEventIter, String> {
 pub fn new_cycle_event_with_seed(input: &str, seed: [u8; 32]) -> Result<CycleEventIter, String> {
     CycleEventIter::from_mini_with_seed(input, seed)
 }
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glides() -> Result<(), String> {
+        let pulse = PulseIterItem::default();
+        // note data
+        let mut event_iter = CycleEventIter::from_mini("c4~g4 e4")?;
+        let items = event_iter.run(pulse, true).unwrap();
+        assert_eq!(items.len(), 2);
+        let mut glide_note = NoteEvent::from(Note::C4);
+        glide_note.extra = Some(EventData::from([(
+            "glide".to_string(),
+            EventDataValue::Integer(Note::G4 as i64),
+        )]));
+        assert_eq!(items[0].event, Event::NoteEvents(vec![Some(glide_note)]));
+
+        // parameter ramps
+        let parameter = ParameterId::from(1);
+        let mut event_iter =
+            CycleEventIter::from_mini("c4~g4 e4")?.with_glide_mode(CycleGlideMode::ParameterRamp {
+                parameter,
+                steps: 3,
+            });
+        let items = event_iter.run(pulse, true).unwrap();
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (item.start, item.event.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Fraction::from(0),
+                    Event::NoteEvents(vec![new_note(Note::C4)])
+                ),
+                (
+                    Fraction::from(0),
+                    Event::ParameterChangeEvent(new_parameter_change(parameter, 0.0))
+                ),
+                (
+                    Fraction::new(1u64, 6u64),
+                    Event::ParameterChangeEvent(new_parameter_change(parameter, 3.5))
+                ),
+                (
+                    Fraction::new(1u64, 3u64),
+                    Event::ParameterChangeEvent(new_parameter_change(parameter, 7.0))
+                ),
+                (
+                    Fraction::new(1u64, 2u64),
+                    Event::NoteEvents(vec![new_note(Note::E4)])
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn choke_targets() -> Result<(), String> {
+        let pulse = PulseIterItem::default();
+        let mut event_iter = CycleEventIter::from_mini("c4:choke1 e4:2 g4:wurst")?;
+        let items = event_iter.run(pulse, true).unwrap();
+        let mut choked_note = NoteEvent::from(Note::C4);
+        choked_note.extra = Some(EventData::from([(
+            CHOKE_GROUP_KEY.to_string(),
+            EventDataValue::Integer(1),
+        )]));
+        assert_eq!(items[0].event, Event::NoteEvents(vec![Some(choked_note)]));
+        let mut instrument_note = NoteEvent::from(Note::E4);
+        instrument_note.instrument = Some(InstrumentId::from(2));
+        assert_eq!(
+            items[1].event,
+            Event::NoteEvents(vec![Some(instrument_note)])
+        );
+        assert_eq!(items[2].event, Event::NoteEvents(vec![new_note(Note::G4)]));
+        Ok(())
+    }
+
+    #[test]
+    fn warnings() -> Result<(), String> {
+        let mut event_iter = CycleEventIter::from_mini("bd c4 200 [bd sn]")?
+            .with_mappings(&[("sn", vec![new_note(Note::C4)])]);
+        event_iter.run(PulseIterItem::default(), true);
+        let warnings = event_iter.take_warnings();
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::new(
+                    WarningKind::Unmapped,
+                    "unmapped cycle identifier 'bd' plays nothing"
+                ),
+                Warning::new(
+                    WarningKind::Clamped,
+                    "cycle note value 200 got clamped to 127"
+                ),
+            ]
+        );
+        // distinct warnings are only reported once
+        event_iter.run(PulseIterItem::default(), true);
+        assert!(event_iter.take_warnings().is_empty());
+
+        // out of range values get dropped in strict mode
+        let mut event_iter =
+            CycleEventIter::from_mini("c4 200")?.with_value_range_policy(ValueRangePolicy::Strict);
+        assert_eq!(
+            event_iter
+                .run(PulseIterItem::default(), true)
+                .map(|items| items.into_iter().map(|item| item.event).collect::<Vec<_>>()),
+            Some(vec![Event::NoteEvents(vec![new_note(Note::C4)])])
+        );
+        assert_eq!(
+            event_iter.take_warnings(),
+            vec![Warning::new(
+                WarningKind::Dropped,
+                "cycle note value 200 must be in range [0..=127]: cycle event got dropped"
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fractional_notes() -> Result<(), String> {
+        let detuned_note = |note: u8, cents: f64| {
+            let mut note_event = NoteEvent::from(Note::from(note));
+            note_event.extra = Some(EventData::from([(
+                DETUNE_KEY.to_string(),
+                EventDataValue::Number(cents),
+            )]));
+            Some(note_event)
+        };
+        // floats are unmapped by default
+        let mut event_iter = CycleEventIter::from_mini("60.5")?;
+        let items = event_iter.run(PulseIterItem::default(), true).unwrap();
+        assert_eq!(items[0].event, Event::NoteEvents(vec![None]));
+
+        let mut event_iter =
+            CycleEventIter::from_mini("60.5 61.75 62.0 200.5")?.with_fractional_notes(true);
+        let items = event_iter.run(PulseIterItem::default(), true).unwrap();
+        assert_eq!(
+            items.into_iter().map(|item| item.event).collect::<Vec<_>>(),
+            vec![
+                Event::NoteEvents(vec![detuned_note(60, 50.0)]),
+                Event::NoteEvents(vec![detuned_note(61, 75.0)]),
+                Event::NoteEvents(vec![new_note(Note::from(62_u8))]),
+                Event::NoteEvents(vec![new_note(Note::from(127_u8))]),
+            ]
+        );
+        assert_eq!(
+            event_iter.take_warnings(),
+            vec![Warning::new(
+                WarningKind::Clamped,
+                "cycle note value 200.5 got clamped to 127"
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn relative_notes() -> Result<(), String> {
+        let mut event_iter = CycleEventIter::from_mini("+3 ^-2 -5 v3")?;
+        let items = event_iter.run(PulseIterItem::default(), true).unwrap();
+        assert_eq!(
+            items.into_iter().map(|item| item.event).collect::<Vec<_>>(),
+            vec![
+                Event::NoteEvents(vec![Some(RelativeNote::Semitones(3).to_note_event())]),
+                Event::NoteEvents(vec![Some(RelativeNote::Degrees(-2).to_note_event())]),
+                // negative integers still clamp to note 0 and names are still unmapped names
+                Event::NoteEvents(vec![new_note(Note::from(0_u8))]),
+                Event::NoteEvents(vec![None]),
+            ]
+        );
+        assert_eq!(
+            event_iter.take_warnings(),
+            vec![
+                Warning::new(WarningKind::Clamped, "cycle note value -5 got clamped to 0"),
+                Warning::new(
+                    WarningKind::Unmapped,
+                    "unmapped cycle identifier 'v3' plays nothing"
+                ),
+            ]
+        );
+        // names which look like relative notes can be mapped
+        let mut event_iter =
+            CycleEventIter::from_mini("v3")?.with_mappings(&[("v3", vec![new_note(Note::C4)])]);
+        let items = event_iter.run(PulseIterItem::default(), true).unwrap();
+        assert_eq!(items[0].event, Event::NoteEvents(vec![new_note(Note::C4)]));
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+use std::mem::size_of;
+
+use fraction::{Fraction, ToPrimitive};
+use rand::RngCore;
+
+use crate::{
+    event::{
+        transform::{EventTransform, EventTransformPulse},
+        Event, EventIterItem, NoteEvent,
+    },
+    parameter::{RhythmParameter, RhythmParameterValues},
+    PulseIterItem,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Parameter id of the echo delay time in rhythm steps.
+pub const ECHO_DELAY_PARAMETER: &str = "echo.delay";
+/// Parameter id of the echo's volume feedback amount.
+pub const ECHO_FEEDBACK_PARAMETER: &str = "echo.feedback";
+/// Parameter id of the echo's pitch shift in semitones per repeat.
+pub const ECHO_TRANSPOSE_PARAMETER: &str = "echo.transpose";
+/// Parameter id of the maximum number of echo repeats.
+pub const ECHO_REPEATS_PARAMETER: &str = "echo.repeats";
+
+/// Max allowed echo delay in rhythm steps.
+const MAX_DELAY: f64 = 16.0;
+/// Max allowed number of echo repeats.
+const MAX_REPEATS: usize = 16;
+/// Echo repeats with volumes below this value are no longer emitted.
+const MIN_VOLUME: f32 = 0.001;
+
+// -------------------------------------------------------------------------------------------------
+
+/// A not yet emitted echo of a note.
+#[derive(Debug, Clone)]
+struct PendingEcho {
+    time: f64,
+    length: f64,
+    voice: usize,
+    note_event: NoteEvent,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Re-emits emitted notes as echoes after a delay, with decaying volumes and an optional pitch
+/// shift per repeat. This is an event effect: echoes are new note events, not audio.
+///
+/// The delay is specified in steps of the rhythm, so echoes stay in sync with the rhythm's tempo.
+/// Each repeat's volume is the previous repeat's volume multiplied by the `feedback` amount.
+/// Echoes play on their own voices, so they don't cut the original notes.
+///
+/// All controls are exposed as [`RhythmParameter`]S, so they can be automated: see
+/// [`parameters`](Self::parameters).
+#[derive(Debug, Clone)]
+pub struct EventEcho {
+    delay: f64,
+    feedback: f32,
+    transpose: i32,
+    repeats: usize,
+    position: f64,
+    pending: Vec<PendingEcho>,
+}
+
+impl Default for EventEcho {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventEcho {
+    /// Create a new echo with a delay of one step, 0.5 feedback and 3 repeats.
+    pub fn new() -> Self {
+        Self {
+            delay: 1.0,
+            feedback: 0.5,
+            transpose: 0,
+            repeats: 3,
+            position: 0.0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Return a new echo with the given delay in rhythm steps. A delay of 0 disables the echo.
+    #[must_use]
+    pub fn with_delay(self, delay: f64) -> Self {
+        let delay = Self::valid_delay(delay);
+        Self { delay, ..self }
+    }
+
+    /// Return a new echo with the given volume feedback in range \[0 - 1\].
+    #[must_use]
+    pub fn with_feedback(self, feedback: f32) -> Self {
+        let feedback = Self::valid_feedback(feedback);
+        Self { feedback, ..self }
+    }
+
+    /// Return a new echo which transposes each repeat by the given amount of semitones.
+    #[must_use]
+    pub fn with_transpose(self, transpose: i32) -> Self {
+        let transpose = transpose.clamp(-12, 12);
+        Self { transpose, ..self }
+    }
+
+    /// Return a new echo which emits up to the given number of repeats.
+    #[must_use]
+    pub fn with_repeats(self, repeats: usize) -> Self {
+        let repeats = repeats.min(MAX_REPEATS);
+        Self { repeats, ..self }
+    }
+
+    /// Delay in rhythm steps.
+    pub fn delay(&self) -> f64 {
+        self.delay
+    }
+
+    /// Volume feedback amount.
+    pub fn feedback(&self) -> f32 {
+        self.feedback
+    }
+
+    /// Pitch shift in semitones per repeat.
+    pub fn transpose(&self) -> i32 {
+        self.transpose
+    }
+
+    /// Maximum number of repeats.
+    pub fn repeats(&self) -> usize {
+        self.repeats
+    }
+
+    /// Describe the echo's controls as parameters, using the echo's current settings as
+    /// default values.
+    pub fn parameters(&self) -> Vec<RhythmParameter> {
+        vec![
+            RhythmParameter::new(ECHO_DELAY_PARAMETER, 0.0..=MAX_DELAY, self.delay)
+                .with_name("Echo Delay"),
+            RhythmParameter::new(ECHO_FEEDBACK_PARAMETER, 0.0..=1.0, self.feedback as f64)
+                .with_name("Echo Feedback"),
+            RhythmParameter::new(
+                ECHO_TRANSPOSE_PARAMETER,
+                -12.0..=12.0,
+                self.transpose as f64,
+            )
+            .with_name("Echo Transpose")
+            .with_integer(true),
+            RhythmParameter::new(
+                ECHO_REPEATS_PARAMETER,
+                0.0..=MAX_REPEATS as f64,
+                self.repeats as f64,
+            )
+            .with_name("Echo Repeats")
+            .with_integer(true),
+        ]
+    }
+
+    /// Apply the actual values of the echo's parameters from the given store, if present.
+    pub fn apply_parameter_values(&mut self, values: &RhythmParameterValues) {
+        if let Some(delay) = values.value(ECHO_DELAY_PARAMETER) {
+            self.delay = Self::valid_delay(delay);
+        }
+        if let Some(feedback) = values.value(ECHO_FEEDBACK_PARAMETER) {
+            self.feedback = Self::valid_feedback(feedback as f32);
+        }
+        if let Some(transpose) = values.value(ECHO_TRANSPOSE_PARAMETER) {
+            self.transpose = (transpose.round() as i32).clamp(-12, 12);
+        }
+        if let Some(repeats) = values.value(ECHO_REPEATS_PARAMETER) {
+            self.repeats = (repeats.round().max(0.0) as usize).min(MAX_REPEATS);
+        }
+    }
+
+    /// Schedule echoes of the given newly emitted pulse events and return the events together
+    /// with all echoes which are due within the pulse, sorted by start time.
+    pub fn process(
+        &mut self,
+        pulse: &PulseIterItem,
+        items: Option<Vec<EventIterItem>>,
+    ) -> Option<Vec<EventIterItem>> {
+        let step_time = if pulse.step_time > 0.0 {
+            pulse.step_time
+        } else {
+            1.0
+        };
+        // schedule echoes of new notes
+        if let Some(items) = &items {
+            for item in items {
+                self.schedule(item, step_time);
+            }
+        }
+        // emit due echoes
+        let pulse_end = self.position + step_time;
+        let mut due = Vec::new();
+        self.pending.retain(|echo| {
+            if echo.time < pulse_end {
+                due.push(echo.clone());
+                false
+            } else {
+                true
+            }
+        });
+        self.position = pulse_end;
+        if due.is_empty() {
+            return items;
+        }
+        let mut items = items.unwrap_or_default();
+        for echo in due {
+            let start = ((echo.time - (pulse_end - step_time)) / step_time).clamp(0.0, 1.0);
+            let length = (echo.length / step_time).clamp(0.0, 1.0 - start);
+            let mut note_events = vec![None; echo.voice];
+            note_events.push(Some(echo.note_event));
+            items.push(EventIterItem::new_with_fraction(
+                Event::NoteEvents(note_events),
+                Fraction::from(start),
+                Fraction::from(length),
+            ));
+        }
+        items.sort_by(|a, b| a.start.cmp(&b.start));
+        Some(items)
+    }
+
+    /// Clear all pending echoes.
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+        self.pending.clear();
+    }
+
+    /// Estimated memory usage of the pending echoes in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.pending.capacity() * size_of::<PendingEcho>()
+    }
+
+    /// Release unused capacity of the pending echoes.
+    pub fn trim_memory(&mut self) {
+        self.pending.shrink_to_fit();
+    }
+
+    fn schedule(&mut self, item: &EventIterItem, step_time: f64) {
+        if self.delay <= 0.0 || self.repeats == 0 || self.feedback <= 0.0 {
+            return;
+        }
+        if let Event::NoteEvents(note_events) = &item.event {
+            let voice_count = note_events.len();
+            let start = item.start.to_f64().unwrap_or(0.0) * step_time;
+            let length = item.length.to_f64().unwrap_or(1.0) * step_time;
+            for (voice, note_event) in note_events.iter().enumerate() {
+                if let Some(note_event) = note_event.as_ref().filter(|n| n.note.is_note_on()) {
+                    let time = self.position + start + note_event.delay as f64 * length;
+                    let mut volume = note_event.volume;
+                    for repeat in 1..=self.repeats {
+                        volume *= self.feedback;
+                        if volume < MIN_VOLUME {
+                            break;
+                        }
+                        let note = note_event.note.transposed(self.transpose * repeat as i32);
+                        self.pending.push(PendingEcho {
+                            time: time + self.delay * repeat as f64,
+                            length: length.min(self.delay),
+                            voice: voice + voice_count * repeat,
+                            note_event: NoteEvent {
+                                note,
+                                volume,
+                                delay: 0.0,
+                                ..note_event.clone()
+                            },
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn valid_delay(delay: f64) -> f64 {
+        if delay.is_finite() {
+            delay.clamp(0.0, MAX_DELAY)
+        } else {
+            0.0
+        }
+    }
+
+    fn valid_feedback(feedback: f32) -> f32 {
+        if feedback.is_finite() {
+            feedback.clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl EventTransform for EventEcho {
+    fn apply(&mut self, _event: &mut Event, _rand_gen: &mut dyn RngCore) {
+        // echoes only get scheduled for pulses
+    }
+
+    fn process(
+        &mut self,
+        pulse: &EventTransformPulse,
+        items: Option<Vec<EventIterItem>>,
+        _rand_gen: &mut dyn RngCore,
+    ) -> Option<Vec<EventIterItem>> {
+        EventEcho::process(self, &pulse.pulse, items)
+    }
+
+    fn applies_without_pulse(&self) -> bool {
+        false
+    }
+
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        EventEcho::parameters(self)
+    }
+
+    fn apply_parameter_values(&mut self, values: &RhythmParameterValues) {
+        EventEcho::apply_parameter_values(self, values);
+    }
+
+    fn reset(&mut self) {
+        EventEcho::reset(self);
+    }
+
+    fn memory_usage(&self) -> usize {
+        EventEcho::memory_usage(self)
+    }
+
+    fn trim_memory(&mut self) {
+        EventEcho::trim_memory(self);
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(self.clone())
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    fn run(echo: &mut EventEcho, note: Option<Note>) -> Vec<(Fraction, Vec<Option<(Note, f32)>>)> {
+        let items =
+            note.map(|note| vec![EventIterItem::new(Event::NoteEvents(vec![new_note(note)]))]);
+        let pulse = PulseIterItem {
+            value: 1.0,
+            step_time: 1.0,
+        };
+        echo.process(&pulse, items)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| match item.event {
+                Event::NoteEvents(notes) => (
+                    item.start,
+                    notes
+                        .into_iter()
+                        .map(|n| n.map(|n| (n.note, n.volume)))
+                        .collect(),
+                ),
+                _ => panic!("expected note events"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn echo() {
+        let mut echo = EventEcho::new()
+            .with_delay(1.5)
+            .with_feedback(0.5)
+            .with_transpose(12)
+            .with_repeats(2);
+        assert_eq!(
+            run(&mut echo, Some(Note::C4)),
+            vec![(Fraction::from(0), vec![Some((Note::C4, 1.0))])]
+        );
+        assert_eq!(
+            run(&mut echo, None),
+            vec![(Fraction::from(0.5), vec![None, Some((Note::C5, 0.5))])]
+        );
+        assert_eq!(
+            run(&mut echo, Some(Note::D4)),
+            vec![(Fraction::from(0), vec![Some((Note::D4, 1.0))])]
+        );
+        assert_eq!(
+            run(&mut echo, None),
+            vec![
+                (Fraction::from(0), vec![None, None, Some((Note::C6, 0.25))]),
+                (Fraction::from(0.5), vec![None, Some((Note::D5, 0.5))])
+            ]
+        );
+        // parameters
+        let values = RhythmParameterValues::new(echo.parameters());
+        values.set_value(ECHO_REPEATS_PARAMETER, 0.0).unwrap();
+        echo.apply_parameter_values(&values);
+        assert_eq!(echo.repeats(), 0);
+        assert_eq!(echo.delay(), 1.5);
+        // pending echoes still play, but no new ones get scheduled
+        assert_eq!(
+            run(&mut echo, Some(Note::E4)),
+            vec![(Fraction::from(0), vec![Some((Note::E4, 1.0))])]
+        );
+        assert_eq!(
+            run(&mut echo, None),
+            vec![(Fraction::from(0), vec![None, None, Some((Note::D6, 0.25))])]
+        );
+        echo.reset();
+        assert!(run(&mut echo, None).is_empty());
+    }
+}
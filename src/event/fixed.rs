@@ -1,28 +1,120 @@
 use std::borrow::Cow;
 
+use fraction::{ConstZero, Fraction};
+
 use crate::{
-    event::{new_note, Event, EventIter, EventIterItem, NoteEvent, ParameterChangeEvent},
+    event::{
+        new_note, voicing::VoiceSpread, Event, EventIter, EventIterItem, NoteEvent,
+        ParameterChangeEvent,
+    },
+    memory::{event_memory_usage, MemoryUsage},
     BeatTimeBase, Note, PulseIterItem,
 };
 
 // -------------------------------------------------------------------------------------------------
 
+/// A single step in a fixed sequence of note events.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FixedSequenceStep {
+    /// Trigger the given note events.
+    Notes(Vec<Option<NoteEvent>>),
+    /// Stop all playing notes.
+    Rest,
+    /// Continue playing the previous step's notes, extending their duration.
+    Hold,
+}
+
+impl From<NoteEvent> for FixedSequenceStep {
+    fn from(note_event: NoteEvent) -> Self {
+        Self::Notes(vec![Some(note_event)])
+    }
+}
+
+impl From<Option<NoteEvent>> for FixedSequenceStep {
+    fn from(note_event: Option<NoteEvent>) -> Self {
+        Self::Notes(vec![note_event])
+    }
+}
+
+impl From<Vec<Option<NoteEvent>>> for FixedSequenceStep {
+    fn from(note_events: Vec<Option<NoteEvent>>) -> Self {
+        Self::Notes(note_events)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
 /// Continuously emits a single, fixed [`EventIterItem`].
 #[derive(Clone, Debug)]
 pub struct FixedEventIter {
     events: Vec<Event>,
+    event_lengths: Vec<usize>,
     event_index: usize,
+    event_step: usize,
 }
 
 impl FixedEventIter {
     pub fn new(events: Vec<Event>) -> Self {
+        let event_lengths = vec![1; events.len()];
+        Self::with_lengths(events, event_lengths)
+    }
+
+    /// Create a new event iter from a sequence of note, rest and hold steps. Holds extend the
+    /// duration of the previous step's event. Leading holds extend the last event's duration.
+    pub fn from_steps(steps: Vec<FixedSequenceStep>) -> Self {
+        let mut events = Vec::with_capacity(steps.len());
+        let mut event_lengths = Vec::with_capacity(steps.len());
+        let mut leading_holds = 0;
+        for step in steps {
+            match step {
+                FixedSequenceStep::Notes(note_events) => {
+                    events.push(Event::NoteEvents(note_events));
+                    event_lengths.push(1);
+                }
+                FixedSequenceStep::Rest => {
+                    events.push(Event::NoteEvents(vec![new_note(Note::OFF)]));
+                    event_lengths.push(1);
+                }
+                FixedSequenceStep::Hold => {
+                    if let Some(length) = event_lengths.last_mut() {
+                        *length += 1;
+                    } else {
+                        leading_holds += 1;
+                    }
+                }
+            }
+        }
+        if let Some(length) = event_lengths.last_mut() {
+            *length += leading_holds;
+        }
+        Self::with_lengths(events, event_lengths)
+    }
+
+    fn with_lengths(events: Vec<Event>, event_lengths: Vec<usize>) -> Self {
+        debug_assert_eq!(events.len(), event_lengths.len());
         let mut events = events;
         Self::normalize_events(&mut events);
         let event_index = 0;
+        let event_step = 0;
         Self {
             events,
+            event_lengths,
             event_index,
+            event_step,
+        }
+    }
+
+    /// Return a new event iter which spreads big chords in all note events with the given
+    /// voice spread.
+    #[must_use]
+    pub fn with_voice_spread(self, spread: &VoiceSpread) -> Self {
+        let mut events = self.events;
+        for event in &mut events {
+            if let Event::NoteEvents(note_events) = event {
+                spread.apply(note_events);
+            }
         }
+        Self { events, ..self }
     }
 
     /// Access to the event that we're triggering
@@ -30,6 +122,11 @@ impl FixedEventIter {
         &self.events
     }
 
+    /// Access to the events' lengths in pulse steps.
+    pub fn event_lengths(&self) -> &Vec<usize> {
+        &self.event_lengths
+    }
+
     /// Add note-offs for all notes in the given event list
     pub(crate) fn normalize_events(events: &mut Vec<Event>) {
         let mut note_event_state = Vec::<Option<NoteEvent>>::new();
@@ -102,12 +199,36 @@ impl EventIter for FixedEventIter {
         if !emit_event || self.events.is_empty() {
             return None;
         }
-        let event = self.events[self.event_index].clone();
-        self.event_index += 1;
-        if self.event_index >= self.events.len() {
-            self.event_index = 0;
+        let event_length = self.event_lengths[self.event_index];
+        let event_item = if self.event_step == 0 {
+            let event = self.events[self.event_index].clone();
+            if event_length > 1 {
+                let start = Fraction::ZERO;
+                let length = Fraction::from(event_length as u64);
+                Some(vec![EventIterItem::new_with_fraction(event, start, length)])
+            } else {
+                Some(vec![EventIterItem::new(event)])
+            }
+        } else {
+            // holding the previous event
+            None
+        };
+        self.event_step += 1;
+        if self.event_step >= event_length {
+            self.event_step = 0;
+            self.event_index += 1;
+            if self.event_index >= self.events.len() {
+                self.event_index = 0;
+            }
+        }
+        event_item
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            events: self.events.iter().map(event_memory_usage).sum(),
+            ..MemoryUsage::default()
         }
-        Some(vec![EventIterItem::new(event)])
     }
 
     fn duplicate(&self) -> Box<dyn EventIter> {
@@ -117,6 +238,7 @@ impl EventIter for FixedEventIter {
     fn reset(&mut self) {
         // reset step counter
         self.event_index = 0;
+        self.event_step = 0;
     }
 }
 
@@ -195,6 +317,14 @@ impl ToFixedEventIterSequence for Vec<Vec<Option<NoteEvent>>> {
     }
 }
 
+impl ToFixedEventIterSequence for Vec<FixedSequenceStep> {
+    /// Wrap a vector of [`FixedSequenceStep`] to a new [`FixedEventIter`]
+    /// resulting into a sequence of note events with rests and holds.
+    fn to_event_sequence(self) -> FixedEventIter {
+        FixedEventIter::from_steps(self)
+    }
+}
+
 impl ToFixedEventIterSequence for Vec<ParameterChangeEvent> {
     /// Wrap a [`ParameterChangeEvent`] into a new [`FixedEventIter`]
     fn to_event_sequence(self) -> FixedEventIter {
@@ -205,3 +335,24 @@ impl ToFixedEventIterSequence for Vec<ParameterChangeEvent> {
         FixedEventIter::new(sequence)
     }
 }
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn emitter_macro() {
+        let emitter = crate::emitter!("c4 [e4,g4] _ ~ off");
+        let expected = FixedEventIter::from_steps(vec![
+            FixedSequenceStep::Notes(vec![new_note(Note::C4)]),
+            FixedSequenceStep::Notes(vec![new_note(Note::E4), new_note(Note::G4)]),
+            FixedSequenceStep::Hold,
+            FixedSequenceStep::Rest,
+            FixedSequenceStep::Notes(vec![new_note(Note::OFF)]),
+        ]);
+        assert_eq!(emitter.events(), expected.events());
+    }
+}
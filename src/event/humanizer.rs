@@ -0,0 +1,231 @@
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    event::{transform::EventTransform, Event, NoteEvent},
+    rhythm::new_random_seed,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Randomly varies emitted note volumes, pannings and timings, to make rhythms sound less
+/// mechanical.
+///
+/// Jitter amounts are applied per field: volume and panning jitter randomly shift values in
+/// range \[-amount, amount\], timing jitter delays notes by up to the given amount of the
+/// event's duration. The correlation controls whether all notes of a chord get the same
+/// jitter (1.0, tight chords) or independent jitter (0.0, loose chords).
+///
+/// When seeded, the humanizer generates the same variations after each reset.
+#[derive(Debug, Clone)]
+pub struct EventHumanizer {
+    volume_jitter: f32,
+    panning_jitter: f32,
+    timing_jitter: f32,
+    correlation: f32,
+    rand_gen: Xoshiro256PlusPlus,
+    seed: Option<[u8; 32]>,
+}
+
+impl Default for EventHumanizer {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl EventHumanizer {
+    /// Create a new humanizer which does not vary anything, using the given optional seed
+    /// for the random number generator.
+    pub fn new(seed: Option<[u8; 32]>) -> Self {
+        let rand_seed = seed.unwrap_or_else(new_random_seed);
+        let rand_gen = Xoshiro256PlusPlus::from_seed(rand_seed);
+        Self {
+            volume_jitter: 0.0,
+            panning_jitter: 0.0,
+            timing_jitter: 0.0,
+            correlation: 0.0,
+            rand_gen,
+            seed,
+        }
+    }
+
+    /// Return a new humanizer which randomly shifts note volumes by up to the given amount.
+    #[must_use]
+    pub fn with_volume_jitter(self, amount: f32) -> Self {
+        let volume_jitter = Self::valid_amount(amount);
+        Self {
+            volume_jitter,
+            ..self
+        }
+    }
+
+    /// Return a new humanizer which randomly shifts note pannings by up to the given amount.
+    #[must_use]
+    pub fn with_panning_jitter(self, amount: f32) -> Self {
+        let panning_jitter = Self::valid_amount(amount);
+        Self {
+            panning_jitter,
+            ..self
+        }
+    }
+
+    /// Return a new humanizer which randomly delays notes by up to the given amount of
+    /// the event's duration in range \[0 - 1\].
+    #[must_use]
+    pub fn with_timing_jitter(self, amount: f32) -> Self {
+        let timing_jitter = Self::valid_amount(amount).min(1.0);
+        Self {
+            timing_jitter,
+            ..self
+        }
+    }
+
+    /// Return a new humanizer which correlates the jitter of notes in a chord by the given
+    /// amount in range \[0 - 1\].
+    #[must_use]
+    pub fn with_correlation(self, correlation: f32) -> Self {
+        let correlation = Self::valid_amount(correlation).min(1.0);
+        Self {
+            correlation,
+            ..self
+        }
+    }
+
+    /// Set a new seed for the random number generator, which also gets used in following resets.
+    pub fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+    }
+
+    /// Reset the random number generator to its initial seed, if any.
+    pub fn reset(&mut self) {
+        if let Some(seed) = self.seed {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        }
+    }
+
+    /// Humanize the given event in place. Parameter change events are not modified.
+    pub fn apply(&mut self, event: &mut Event) {
+        if let Event::NoteEvents(note_events) = event {
+            // shared, per chord random values
+            let shared = self.random_values();
+            for note_event in note_events.iter_mut().flatten() {
+                let own = self.random_values();
+                let [volume, panning, timing] = [0, 1, 2]
+                    .map(|i| self.correlation * shared[i] + (1.0 - self.correlation) * own[i]);
+                self.apply_note_event(note_event, volume, panning, timing);
+            }
+        }
+    }
+
+    fn apply_note_event(&self, note_event: &mut NoteEvent, volume: f32, panning: f32, timing: f32) {
+        if !note_event.note.is_note_on() {
+            return;
+        }
+        if self.volume_jitter > 0.0 {
+            note_event.volume = (note_event.volume + volume * self.volume_jitter).clamp(0.0, 1.0);
+        }
+        if self.panning_jitter > 0.0 {
+            note_event.panning =
+                (note_event.panning + panning * self.panning_jitter).clamp(-1.0, 1.0);
+        }
+        if self.timing_jitter > 0.0 {
+            // timing values are in range [-1, 1]: map them to [0, 1] as delays can't be negative
+            let delay = (timing + 1.0) / 2.0 * self.timing_jitter;
+            note_event.delay = (note_event.delay + delay).clamp(0.0, 1.0);
+        }
+    }
+
+    fn random_values(&mut self) -> [f32; 3] {
+        [0; 3].map(|_| self.rand_gen.gen_range(-1.0..=1.0))
+    }
+
+    fn valid_amount(amount: f32) -> f32 {
+        if amount.is_finite() {
+            amount.max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl EventTransform for EventHumanizer {
+    fn apply(&mut self, event: &mut Event, _rand_gen: &mut dyn RngCore) {
+        EventHumanizer::apply(self, event);
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        EventHumanizer::set_seed(self, seed);
+    }
+
+    fn reset(&mut self) {
+        EventHumanizer::reset(self);
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(self.clone())
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        event::{new_note, InstrumentId},
+        Note,
+    };
+
+    fn chord() -> Event {
+        Event::NoteEvents(vec![
+            new_note((Note::C4, None::<InstrumentId>, 0.5)),
+            new_note((Note::E4, None::<InstrumentId>, 0.5)),
+            new_note((Note::G4, None::<InstrumentId>, 0.5)),
+            new_note(Note::OFF),
+        ])
+    }
+
+    fn notes(event: &Event) -> Vec<NoteEvent> {
+        match event {
+            Event::NoteEvents(notes) => notes.iter().flatten().cloned().collect(),
+            _ => panic!("expected note events"),
+        }
+    }
+
+    #[test]
+    fn humanize() {
+        let seed = Some([1; 32]);
+        let mut humanizer = EventHumanizer::new(seed)
+            .with_volume_jitter(0.25)
+            .with_panning_jitter(0.5)
+            .with_timing_jitter(0.2);
+
+        // loose chords
+        let mut event = chord();
+        humanizer.apply(&mut event);
+        let loose = notes(&event);
+        assert!(loose[..3].iter().all(|n| (0.25..=0.75).contains(&n.volume)
+            && (-0.5..=0.5).contains(&n.panning)
+            && (0.0..=0.2).contains(&n.delay)));
+        assert!(loose[0].volume != loose[1].volume);
+        assert_eq!(loose[3], new_note(Note::OFF).unwrap());
+
+        // seeded humanizers repeat after reset
+        humanizer.reset();
+        let mut event = chord();
+        humanizer.apply(&mut event);
+        assert_eq!(notes(&event), loose);
+
+        // tight chords
+        let mut humanizer = humanizer.with_correlation(1.0);
+        let mut event = chord();
+        humanizer.apply(&mut event);
+        let tight = notes(&event);
+        assert!(tight[..3].windows(2).all(|w| w[0].volume == w[1].volume
+            && w[0].panning == w[1].panning
+            && w[0].delay == w[1].delay));
+    }
+}
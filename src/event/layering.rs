@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+
+use crate::{
+    event::{Event, InstrumentId, NoteEvent},
+    phrase::RhythmIndex,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Max allowed transposition of a layer in semitones.
+const MAX_TRANSPOSE: i32 = 48;
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single target instrument of an [`InstrumentLayers`] table, with an optional volume factor
+/// and transposition, which get applied to all layered notes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstrumentLayer {
+    instrument: InstrumentId,
+    volume: f32,
+    transpose: i32,
+}
+
+impl InstrumentLayer {
+    /// Create a new layer which plays notes as they are on the given instrument.
+    pub fn new(instrument: InstrumentId) -> Self {
+        Self {
+            instrument,
+            volume: 1.0,
+            transpose: 0,
+        }
+    }
+
+    /// Return a new layer which scales note volumes with the given factor.
+    #[must_use]
+    pub fn with_volume(self, volume: f32) -> Self {
+        let volume = volume.max(0.0);
+        Self { volume, ..self }
+    }
+
+    /// Return a new layer which shifts notes by the given amount of semitones.
+    #[must_use]
+    pub fn with_transpose(self, transpose: i32) -> Self {
+        let transpose = transpose.clamp(-MAX_TRANSPOSE, MAX_TRANSPOSE);
+        Self { transpose, ..self }
+    }
+
+    /// The layer's target instrument.
+    pub fn instrument(&self) -> InstrumentId {
+        self.instrument
+    }
+
+    /// The layer's volume factor.
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// The layer's pitch shift in semitones.
+    pub fn transpose(&self) -> i32 {
+        self.transpose
+    }
+
+    fn apply(&self, note_event: &NoteEvent) -> NoteEvent {
+        let mut note_event = note_event.clone();
+        note_event.instrument = Some(self.instrument);
+        if note_event.note.is_note_on() {
+            note_event.note = note_event.note.transposed(self.transpose);
+            note_event.volume *= self.volume;
+        }
+        note_event
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Fans out notes of an instrument to multiple [`InstrumentLayer`]S, e.g. to layer a kick with
+/// a sub bass or to double a lead, without changing the rhythms which play the notes.
+///
+/// Layers replace the original note: add a layer with the original instrument to keep it.
+/// Notes of instruments without layers pass through unchanged.
+///
+/// The first layer of a note plays on the note's original voice. All other layers get appended
+/// as new voices after the original voices of the event, so voices of notes without layers
+/// don't move. Note-offs and following notes on the original voice get passed to the appended
+/// voices too, so note-offs stop all layered notes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InstrumentLayers {
+    layers: HashMap<InstrumentId, Vec<InstrumentLayer>>,
+}
+
+impl InstrumentLayers {
+    /// Create a new, empty layer table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new table which plays notes of the given instrument on the given layers.
+    #[must_use]
+    pub fn with_layers(self, instrument: InstrumentId, layers: Vec<InstrumentLayer>) -> Self {
+        let mut table = self;
+        table.set_layers(instrument, layers);
+        table
+    }
+
+    /// Set new layers for the given instrument. Empty layers remove the instrument's layers.
+    pub fn set_layers(&mut self, instrument: InstrumentId, layers: Vec<InstrumentLayer>) {
+        if layers.is_empty() {
+            self.layers.remove(&instrument);
+        } else {
+            self.layers.insert(instrument, layers);
+        }
+    }
+
+    /// Remove all layers of the given instrument.
+    pub fn remove_layers(&mut self, instrument: InstrumentId) {
+        self.layers.remove(&instrument);
+    }
+
+    /// Layers of the given instrument, if any.
+    pub fn layers(&self, instrument: InstrumentId) -> Option<&[InstrumentLayer]> {
+        self.layers.get(&instrument).map(Vec::as_slice)
+    }
+
+    /// Returns true when the table does not layer any instrument.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Largest number of layers of a single instrument.
+    pub fn max_layer_count(&self) -> usize {
+        self.layers.values().map(Vec::len).max().unwrap_or(1)
+    }
+
+    /// Expand notes of layered instruments in the given event of the given rhythm slot.
+    /// Appended voices get memorized in `voices`, so note-offs on the original voices, which
+    /// got emitted in later events, can be passed to them.
+    pub(crate) fn apply_to_event(
+        &self,
+        voices: &mut LayeredVoices,
+        rhythm_index: RhythmIndex,
+        event: &mut Event,
+    ) {
+        let Event::NoteEvents(note_events) = event else {
+            return;
+        };
+        if self.is_empty() && voices.is_empty() {
+            return;
+        }
+        // append new voices after all voices the rhythm emitted so far
+        let voice_count = voices.voice_counts.entry(rhythm_index).or_default();
+        *voice_count = (*voice_count).max(note_events.len());
+        let first_appended_voice = *voice_count;
+        let mut appended_note_events = Vec::new();
+        for (voice_index, note_event) in note_events.iter_mut().enumerate() {
+            let Some(note_event) = note_event else {
+                continue;
+            };
+            let key = (rhythm_index, voice_index);
+            let layers = note_event.instrument.and_then(|id| self.layers.get(&id));
+            if note_event.note.is_note_off() {
+                // stop all appended voices of the original voice
+                let appended_voices = voices.appended.remove(&key).unwrap_or_default();
+                for (appended_voice, instrument) in appended_voices {
+                    let mut note_off = note_event.clone();
+                    note_off.instrument = note_event.instrument.map(|_| instrument);
+                    appended_note_events.push((appended_voice, note_off));
+                }
+                if let Some(layers) = layers {
+                    *note_event = layers[0].apply(note_event);
+                }
+            } else if let Some(layers) = layers {
+                // play further layers on the voices which got appended for this voice before
+                let mut appended_voices = voices.appended.remove(&key).unwrap_or_default();
+                for (index, layer) in layers.iter().enumerate().skip(1) {
+                    let appended_voice = match appended_voices.get_mut(index - 1) {
+                        Some((appended_voice, instrument)) => {
+                            *instrument = layer.instrument;
+                            *appended_voice
+                        }
+                        None => {
+                            let appended_voice = voices.free_voice(
+                                rhythm_index,
+                                first_appended_voice,
+                                &appended_voices,
+                            );
+                            appended_voices.push((appended_voice, layer.instrument));
+                            appended_voice
+                        }
+                    };
+                    appended_note_events.push((appended_voice, layer.apply(note_event)));
+                }
+                voices.appended.insert(key, appended_voices);
+                *note_event = layers[0].apply(note_event);
+            }
+        }
+        for (voice_index, note_event) in appended_note_events {
+            if note_events.len() <= voice_index {
+                note_events.resize(voice_index + 1, None);
+            }
+            note_events[voice_index] = Some(note_event);
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Voices which got appended to the events of rhythm slots by [`InstrumentLayers`], along with
+/// the instruments which play on them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LayeredVoices {
+    appended: HashMap<(RhythmIndex, usize), Vec<(usize, InstrumentId)>>,
+    voice_counts: HashMap<RhythmIndex, usize>,
+}
+
+impl LayeredVoices {
+    /// Returns true when no voices got appended.
+    pub fn is_empty(&self) -> bool {
+        self.appended.is_empty()
+    }
+
+    /// Forget all appended voices, e.g. when the sequence got reset.
+    pub fn clear(&mut self) {
+        self.appended.clear();
+        self.voice_counts.clear();
+    }
+
+    // First unused appended voice of the given rhythm slot, starting with the given voice.
+    fn free_voice(
+        &self,
+        rhythm_index: RhythmIndex,
+        first_voice: usize,
+        reserved_voices: &[(usize, InstrumentId)],
+    ) -> usize {
+        let is_used = |voice: usize| {
+            reserved_voices
+                .iter()
+                .any(|(reserved, _)| *reserved == voice)
+                || self.appended.iter().any(|((index, _), appended)| {
+                    *index == rhythm_index && appended.iter().any(|(used, _)| *used == voice)
+                })
+        };
+        let mut voice = first_voice;
+        while is_used(voice) {
+            voice += 1;
+        }
+        voice
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    #[test]
+    fn layers() {
+        let kick = InstrumentId::from(1);
+        let sub = InstrumentId::from(2);
+        let lead = InstrumentId::from(3);
+        let layers = InstrumentLayers::new().with_layers(
+            kick,
+            vec![
+                InstrumentLayer::new(kick),
+                InstrumentLayer::new(sub)
+                    .with_volume(0.5)
+                    .with_transpose(-12),
+            ],
+        );
+        assert_eq!(layers.max_layer_count(), 2);
+
+        // layers get appended after the original voices
+        let mut voices = LayeredVoices::default();
+        let mut event = Event::NoteEvents(vec![
+            new_note((Note::C4, kick, 0.8)),
+            new_note((Note::E4, lead, 1.0)),
+            None,
+            new_note(Note::OFF),
+        ]);
+        layers.apply_to_event(&mut voices, 0, &mut event);
+        assert_eq!(
+            event,
+            Event::NoteEvents(vec![
+                new_note((Note::C4, kick, 0.8)),
+                new_note((Note::E4, lead, 1.0)),
+                None,
+                new_note(Note::OFF),
+                new_note((Note::C3, sub, 0.4)),
+            ])
+        );
+
+        // note-offs on the original voice stop all layers
+        let mut event = Event::NoteEvents(vec![new_note((Note::OFF, kick))]);
+        layers.apply_to_event(&mut voices, 0, &mut event);
+        assert_eq!(
+            event,
+            Event::NoteEvents(vec![
+                new_note((Note::OFF, kick)),
+                None,
+                None,
+                None,
+                new_note((Note::OFF, sub))
+            ])
+        );
+        assert!(voices.is_empty());
+
+        // also note-offs without instruments, and with changed layer tables
+        let mut event = Event::NoteEvents(vec![None, new_note((Note::C4, kick))]);
+        layers.apply_to_event(&mut voices, 1, &mut event);
+        assert_eq!(
+            event,
+            Event::NoteEvents(vec![
+                None,
+                new_note((Note::C4, kick)),
+                new_note((Note::C3, sub, 0.5)),
+            ])
+        );
+        let mut event = Event::NoteEvents(vec![None, new_note(Note::OFF)]);
+        InstrumentLayers::new().apply_to_event(&mut voices, 1, &mut event);
+        assert_eq!(
+            event,
+            Event::NoteEvents(vec![None, new_note(Note::OFF), new_note(Note::OFF)])
+        );
+
+        // empty tables don't change anything
+        let mut event = Event::NoteEvents(vec![new_note((Note::C4, kick))]);
+        InstrumentLayers::new().apply_to_event(&mut voices, 0, &mut event);
+        assert_eq!(event, Event::NoteEvents(vec![new_note((Note::C4, kick))]));
+    }
+}
@@ -0,0 +1,211 @@
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    event::{transform::EventTransform, Event},
+    rhythm::new_random_seed,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// How an [`EventPanner`] distributes emitted notes across the stereo field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanningStrategy {
+    /// Alternate notes between left and right, starting on the left.
+    Alternate,
+    /// Spread notes of chords evenly from left to right, lowest note left. Single notes
+    /// stay centered.
+    Spread,
+    /// Randomly pan notes within the panner's width.
+    Random,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Automatically pans emitted notes with a [`PanningStrategy`], e.g. to widen arpeggios or
+/// chords without setting pannings in patterns or emitters.
+///
+/// The width in range \[0 - 1\] sets how far notes get panned. Pannings get added to the
+/// note's own panning, so the panner composes with pannings from emitters and other event
+/// transforms. Note-offs and parameter changes are not modified.
+///
+/// When seeded, random pannings are the same after each reset.
+#[derive(Debug, Clone)]
+pub struct EventPanner {
+    strategy: PanningStrategy,
+    width: f32,
+    note_count: usize,
+    rand_gen: Xoshiro256PlusPlus,
+    seed: Option<[u8; 32]>,
+}
+
+impl EventPanner {
+    /// Create a new panner with the given strategy and full width, using the given optional
+    /// seed for random pannings.
+    pub fn new(strategy: PanningStrategy, seed: Option<[u8; 32]>) -> Self {
+        let rand_seed = seed.unwrap_or_else(new_random_seed);
+        let rand_gen = Xoshiro256PlusPlus::from_seed(rand_seed);
+        Self {
+            strategy,
+            width: 1.0,
+            note_count: 0,
+            rand_gen,
+            seed,
+        }
+    }
+
+    /// Return a new panner which pans notes by up to the given width in range \[0 - 1\].
+    #[must_use]
+    pub fn with_width(self, width: f32) -> Self {
+        let width = if width.is_finite() {
+            width.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        Self { width, ..self }
+    }
+
+    /// The panner's strategy.
+    pub fn strategy(&self) -> PanningStrategy {
+        self.strategy
+    }
+
+    /// The panner's width.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Set a new seed for the random number generator, which also gets used in following resets.
+    pub fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+    }
+
+    /// Restart alternating pannings and reset the random number generator to its initial
+    /// seed, if any.
+    pub fn reset(&mut self) {
+        self.note_count = 0;
+        if let Some(seed) = self.seed {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        }
+    }
+
+    /// Pan the given event in place.
+    pub fn apply(&mut self, event: &mut Event) {
+        if let Event::NoteEvents(note_events) = event {
+            // collect note-on voices, sorted by pitch for spreads
+            let mut voices = note_events
+                .iter()
+                .enumerate()
+                .filter_map(|(index, note_event)| {
+                    note_event
+                        .as_ref()
+                        .filter(|n| n.note.is_note_on())
+                        .map(|n| (index, n.note))
+                })
+                .collect::<Vec<_>>();
+            if self.strategy == PanningStrategy::Spread {
+                voices.sort_by_key(|(_, note)| *note);
+            }
+            let voice_count = voices.len();
+            for (voice, (index, _)) in voices.into_iter().enumerate() {
+                let panning = match self.strategy {
+                    PanningStrategy::Alternate => {
+                        let left = self.note_count % 2 == 0;
+                        self.note_count += 1;
+                        if left {
+                            -1.0
+                        } else {
+                            1.0
+                        }
+                    }
+                    PanningStrategy::Spread => {
+                        if voice_count > 1 {
+                            voice as f32 / (voice_count - 1) as f32 * 2.0 - 1.0
+                        } else {
+                            0.0
+                        }
+                    }
+                    PanningStrategy::Random => self.rand_gen.gen_range(-1.0..=1.0),
+                };
+                if let Some(note_event) = &mut note_events[index] {
+                    note_event.panning =
+                        (note_event.panning + panning * self.width).clamp(-1.0, 1.0);
+                }
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl EventTransform for EventPanner {
+    fn apply(&mut self, event: &mut Event, _rand_gen: &mut dyn RngCore) {
+        EventPanner::apply(self, event);
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        EventPanner::set_seed(self, seed);
+    }
+
+    fn reset(&mut self) {
+        EventPanner::reset(self);
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(self.clone())
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    fn pannings(panner: &mut EventPanner, notes: &[Note]) -> Vec<f32> {
+        let mut event = Event::NoteEvents(notes.iter().map(|n| new_note(*n)).collect());
+        panner.apply(&mut event);
+        match event {
+            Event::NoteEvents(notes) => notes.into_iter().map(|n| n.unwrap().panning).collect(),
+            _ => panic!("expected note events"),
+        }
+    }
+
+    #[test]
+    fn alternate() {
+        let mut panner = EventPanner::new(PanningStrategy::Alternate, None).with_width(0.5);
+        assert_eq!(pannings(&mut panner, &[Note::C4]), vec![-0.5]);
+        assert_eq!(
+            pannings(&mut panner, &[Note::C4, Note::E4]),
+            vec![0.5, -0.5]
+        );
+        assert_eq!(pannings(&mut panner, &[Note::OFF]), vec![0.0]);
+        assert_eq!(pannings(&mut panner, &[Note::C4]), vec![0.5]);
+        panner.reset();
+        assert_eq!(pannings(&mut panner, &[Note::C4]), vec![-0.5]);
+    }
+
+    #[test]
+    fn spread() {
+        let mut panner = EventPanner::new(PanningStrategy::Spread, None);
+        assert_eq!(pannings(&mut panner, &[Note::C4]), vec![0.0]);
+        assert_eq!(
+            pannings(&mut panner, &[Note::G4, Note::C4, Note::E4]),
+            vec![1.0, -1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn random() {
+        let mut panner = EventPanner::new(PanningStrategy::Random, Some([1; 32])).with_width(0.5);
+        let first = pannings(&mut panner, &[Note::C4, Note::E4, Note::G4]);
+        assert!(first.iter().all(|p| (-0.5..=0.5).contains(p)));
+        panner.reset();
+        assert_eq!(
+            pannings(&mut panner, &[Note::C4, Note::E4, Note::G4]),
+            first
+        );
+    }
+}
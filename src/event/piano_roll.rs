@@ -0,0 +1,367 @@
+use std::borrow::Cow;
+
+use fraction::{Fraction, ToPrimitive};
+
+use crate::{
+    event::{Event, EventIter, EventIterItem, NoteEvent},
+    memory::{event_memory_usage, MemoryUsage},
+    BeatTimeBase, Note, PulseIterItem,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Max distance of note start and end times in steps, which are treated as equal.
+const TIME_EPSILON: f64 = 1e-6;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Emits notes from a piano roll: a list of notes with absolute start times and durations, as
+/// used by GUI editors or imported from MIDI files.
+///
+/// Note start times and durations are specified in steps of the rhythm, so in beats when the
+/// rhythm steps by beats. Each pulse advances the piano roll by one step, so it should be used
+/// with a rhythm which emits one pulse per step, e.g. the default pattern.
+///
+/// Notes with the same start time get grouped into chords. Overlapping notes get assigned to
+/// separate voices, so they don't cut each other. Each note gets stopped with a note-off at the
+/// end of its duration, unless another note starts in the same voice at that time. Emitted
+/// events have the duration of their longest note.
+///
+/// The piano roll loops after [`length`](Self::length) steps. Notes which play past the end of
+/// the loop get cut at the loop end.
+#[derive(Clone, Debug)]
+pub struct PianoRollEventIter {
+    length: usize,
+    steps: Vec<Vec<EventIterItem>>,
+    step: usize,
+}
+
+impl PianoRollEventIter {
+    /// Create a new piano roll from `(start, duration, note, velocity)` tuples. The loop length
+    /// is the end of the last note, rounded up to full steps.
+    ///
+    /// Notes with negative start times, empty durations or note values which are not note-ons
+    /// get ignored.
+    pub fn new(notes: &[(f64, f64, Note, f32)]) -> Self {
+        let notes = Self::valid_notes(notes);
+        let end = notes
+            .iter()
+            .map(|(start, duration, _, _)| start + duration)
+            .fold(0.0, f64::max);
+        let length = ((end - TIME_EPSILON).ceil() as usize).max(1);
+        Self::with_notes_and_length(notes, length)
+    }
+
+    /// Return a new piano roll which loops after the given number of steps. Notes which start
+    /// at or after the loop end are dropped.
+    #[must_use]
+    pub fn with_length(self, length: usize) -> Self {
+        let notes = self.notes();
+        Self::with_notes_and_length(notes, length.max(1))
+    }
+
+    /// Loop length in steps.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The piano roll's notes as `(start, duration, note, velocity)` tuples, sorted by start
+    /// time and voice. Durations of notes which got cut at the loop end are shortened.
+    pub fn notes(&self) -> Vec<(f64, f64, Note, f32)> {
+        fn stop_note(
+            notes: &mut Vec<(f64, f64, Note, f32)>,
+            playing: &mut Option<(f64, NoteEvent)>,
+            end: f64,
+        ) {
+            if let Some((start, note_event)) = playing.take() {
+                notes.push((start, end - start, note_event.note, note_event.volume));
+            }
+        }
+        let mut notes = Vec::new();
+        let mut playing = Vec::<Option<(f64, NoteEvent)>>::new();
+        for (step, items) in self.steps.iter().enumerate() {
+            for item in items {
+                let time = step as f64 + item.start.to_f64().unwrap_or(0.0);
+                if let Event::NoteEvents(note_events) = &item.event {
+                    if playing.len() < note_events.len() {
+                        playing.resize(note_events.len(), None);
+                    }
+                    for (voice, note_event) in note_events.iter().enumerate() {
+                        if let Some(note_event) = note_event {
+                            stop_note(&mut notes, &mut playing[voice], time);
+                            if note_event.note.is_note_on() {
+                                playing[voice] = Some((time, note_event.clone()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for voice in &mut playing {
+            stop_note(&mut notes, voice, self.length as f64);
+        }
+        notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        notes
+    }
+
+    fn valid_notes(notes: &[(f64, f64, Note, f32)]) -> Vec<(f64, f64, Note, f32)> {
+        notes
+            .iter()
+            .filter(|(start, duration, note, _)| {
+                start.is_finite()
+                    && *start >= 0.0
+                    && duration.is_finite()
+                    && *duration > TIME_EPSILON
+                    && note.is_note_on()
+            })
+            .copied()
+            .collect()
+    }
+
+    fn with_notes_and_length(notes: Vec<(f64, f64, Note, f32)>, length: usize) -> Self {
+        // drop notes after the loop end and cut notes at the loop end
+        let loop_end = length as f64;
+        let mut notes = notes
+            .into_iter()
+            .filter(|(start, _, _, _)| *start < loop_end - TIME_EPSILON)
+            .map(|(start, duration, note, velocity)| {
+                (start, duration.min(loop_end - start), note, velocity)
+            })
+            .collect::<Vec<_>>();
+        // sort by start time, then by pitch, so chord voices get allocated from low to high
+        notes.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.2.cmp(&b.2)));
+        // allocate voices: reuse the first voice which finished playing
+        let mut voice_ends = Vec::<f64>::new();
+        let mut changes = Vec::<(f64, usize, NoteEvent, f64)>::new();
+        for (start, duration, note, velocity) in notes {
+            let voice = match voice_ends
+                .iter()
+                .position(|end| *end <= start + TIME_EPSILON)
+            {
+                Some(voice) => voice,
+                None => {
+                    voice_ends.push(0.0);
+                    voice_ends.len() - 1
+                }
+            };
+            voice_ends[voice] = start + duration;
+            let note_event = NoteEvent {
+                volume: velocity.max(0.0),
+                ..NoteEvent::from(note)
+            };
+            changes.push((start, voice, note_event, duration));
+            changes.push((start + duration, voice, NoteEvent::from(Note::OFF), 0.0));
+        }
+        // group changes at the same time into single events: note-ons replace note-offs
+        // and the event's length is the duration of its longest note
+        changes.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let mut steps = vec![Vec::new(); length];
+        let mut index = 0;
+        while index < changes.len() {
+            let time = changes[index].0;
+            let mut note_events = Vec::<Option<NoteEvent>>::new();
+            let mut event_length = 0.0_f64;
+            while index < changes.len() && changes[index].0 - time <= TIME_EPSILON {
+                let (_, voice, note_event, duration) = changes[index].clone();
+                if note_events.len() <= voice {
+                    note_events.resize(voice + 1, None);
+                }
+                let has_note_on = note_events[voice]
+                    .as_ref()
+                    .is_some_and(|n| n.note.is_note_on());
+                if !has_note_on {
+                    note_events[voice] = Some(note_event);
+                    event_length = event_length.max(duration);
+                }
+                index += 1;
+            }
+            // note-offs at the loop end get emitted at the start of the next loop
+            let time = if time >= loop_end - TIME_EPSILON {
+                0.0
+            } else {
+                time
+            };
+            let step = (time.floor() as usize).min(length - 1);
+            let start = time - step as f64;
+            steps[step].push(EventIterItem::new_with_fraction(
+                Event::NoteEvents(note_events),
+                Fraction::from(start),
+                Fraction::from(event_length),
+            ));
+        }
+        // merge wrapped note-offs with the first step's events
+        let steps = steps.into_iter().map(Self::merge_items).collect();
+        Self {
+            length,
+            steps,
+            step: 0,
+        }
+    }
+
+    fn merge_items(items: Vec<EventIterItem>) -> Vec<EventIterItem> {
+        let mut merged = Vec::<EventIterItem>::with_capacity(items.len());
+        for item in items {
+            if let Some(previous) = merged.iter_mut().find(|i| i.start == item.start) {
+                if let (Event::NoteEvents(previous_notes), Event::NoteEvents(notes)) =
+                    (&mut previous.event, item.event)
+                {
+                    if previous_notes.len() < notes.len() {
+                        previous_notes.resize(notes.len(), None);
+                    }
+                    for (voice, note_event) in notes.into_iter().enumerate() {
+                        if note_event.is_some() && previous_notes[voice].is_none() {
+                            previous_notes[voice] = note_event;
+                        }
+                    }
+                }
+            } else {
+                merged.push(item);
+            }
+        }
+        merged.sort_by(|a, b| a.start.cmp(&b.start));
+        merged
+    }
+}
+
+impl EventIter for PianoRollEventIter {
+    fn set_time_base(&mut self, _time_base: &BeatTimeBase) {
+        // nothing to do
+    }
+
+    fn set_external_context(&mut self, _data: &[(Cow<str>, f64)]) {
+        // nothing to do
+    }
+
+    fn run(&mut self, _pulse: PulseIterItem, emit_event: bool) -> Option<Vec<EventIterItem>> {
+        let step = self.step;
+        self.step = (self.step + 1) % self.length;
+        if !emit_event || self.steps[step].is_empty() {
+            return None;
+        }
+        Some(self.steps[step].clone())
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            events: self
+                .steps
+                .iter()
+                .flatten()
+                .map(|item| event_memory_usage(&item.event))
+                .sum(),
+            ..MemoryUsage::default()
+        }
+    }
+
+    fn duplicate(&self) -> Box<dyn EventIter> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self) {
+        self.step = 0;
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use fraction::{ConstOne, ConstZero};
+
+    use super::*;
+    use crate::event::new_note;
+
+    fn run_steps(piano_roll: &mut PianoRollEventIter, count: usize) -> Vec<Vec<EventIterItem>> {
+        (0..count)
+            .map(|_| {
+                piano_roll
+                    .run(PulseIterItem::default(), true)
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chords_and_overlaps() {
+        let mut piano_roll = PianoRollEventIter::new(&[
+            (0.0, 1.0, Note::E4, 1.0),
+            (0.0, 1.0, Note::C4, 0.5),
+            (0.5, 1.0, Note::G4, 1.0),
+            (1.0, 0.5, Note::D4, 1.0),
+        ]);
+        assert_eq!(piano_roll.length(), 2);
+        let steps = run_steps(&mut piano_roll, 2);
+        // chord, sorted by pitch
+        assert_eq!(
+            steps[0][0],
+            EventIterItem::new_with_fraction(
+                Event::NoteEvents(vec![
+                    Some(NoteEvent {
+                        volume: 0.5,
+                        ..NoteEvent::from(Note::C4)
+                    }),
+                    new_note(Note::E4)
+                ]),
+                Fraction::ZERO,
+                Fraction::ONE
+            )
+        );
+        // overlapping note gets a new voice
+        assert_eq!(
+            steps[0][1].event,
+            Event::NoteEvents(vec![None, None, new_note(Note::G4)])
+        );
+        assert_eq!(steps[0][1].start, Fraction::new(1u64, 2u64));
+        // next note reuses the first free voice, the other chord note gets stopped
+        assert_eq!(
+            steps[1][0].event,
+            Event::NoteEvents(vec![new_note(Note::D4), new_note(Note::OFF)])
+        );
+        assert_eq!(
+            steps[1][1].event,
+            Event::NoteEvents(vec![new_note(Note::OFF), None, new_note(Note::OFF)])
+        );
+        // loops
+        assert_eq!(run_steps(&mut piano_roll, 2), steps);
+        piano_roll.reset();
+        assert_eq!(run_steps(&mut piano_roll, 2), steps);
+    }
+
+    #[test]
+    fn length() {
+        let notes = [(0.0, 1.0, Note::C4, 1.0), (3.5, 2.0, Note::D4, 1.0)];
+        let piano_roll = PianoRollEventIter::new(&notes);
+        assert_eq!(piano_roll.length(), 6);
+        assert_eq!(piano_roll.notes(), notes.to_vec());
+
+        // cut notes at the loop end
+        let mut piano_roll = piano_roll.with_length(4);
+        assert_eq!(
+            piano_roll.notes(),
+            vec![(0.0, 1.0, Note::C4, 1.0), (3.5, 0.5, Note::D4, 1.0)]
+        );
+        let steps = run_steps(&mut piano_roll, 4);
+        assert_eq!(
+            steps[0][0].event,
+            Event::NoteEvents(vec![new_note(Note::C4)])
+        );
+        assert_eq!(
+            steps[3][0].event,
+            Event::NoteEvents(vec![new_note(Note::D4)])
+        );
+        assert_eq!(steps[3][0].start, Fraction::new(1u64, 2u64));
+
+        // drop notes after the loop end
+        let piano_roll = piano_roll.with_length(2);
+        assert_eq!(piano_roll.notes(), vec![(0.0, 1.0, Note::C4, 1.0)]);
+
+        // ignore invalid notes
+        let piano_roll = PianoRollEventIter::new(&[
+            (-1.0, 1.0, Note::C4, 1.0),
+            (0.0, 0.0, Note::C4, 1.0),
+            (0.0, 1.0, Note::OFF, 1.0),
+        ]);
+        assert_eq!(piano_roll.length(), 1);
+        assert!(piano_roll.notes().is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use std::{borrow::Cow, mem::size_of};
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    event::{fixed::FixedEventIter, Event, EventIter, EventIterItem, NoteEvent},
+    memory::MemoryUsage,
+    rhythm::new_random_seed,
+    BeatTimeBase, PulseIterItem, Scale,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Randomly picks note events from a weighted pool of note events for each emitted pulse.
+///
+/// Optionally avoids picking the same pool entry twice in a row, and fits all picked notes
+/// into a [`Scale`]. When seeded, the emitter generates the same sequence of notes after each
+/// reset.
+#[derive(Debug, Clone)]
+pub struct RandomPoolEventIter {
+    pool: Vec<(Vec<Option<NoteEvent>>, f32)>,
+    avoid_repetition: bool,
+    scale: Option<Scale>,
+    last_index: Option<usize>,
+    note_event_state: Vec<Option<NoteEvent>>,
+    rand_gen: Xoshiro256PlusPlus,
+    seed: Option<[u8; 32]>,
+}
+
+impl RandomPoolEventIter {
+    /// Try creating a new pool emitter from the given (note events, weight) entries.
+    ///
+    /// Returns error when the pool is empty or weights are invalid.
+    pub fn new(
+        pool: Vec<(Vec<Option<NoteEvent>>, f32)>,
+        seed: Option<[u8; 32]>,
+    ) -> Result<Self, String> {
+        if pool.is_empty() {
+            return Err("note pool must not be empty".to_string());
+        }
+        for (_, weight) in &pool {
+            if !(*weight >= 0.0 && weight.is_finite()) {
+                return Err(format!(
+                    "note pool weights must be >= 0 but are '{}'",
+                    weight
+                ));
+            }
+        }
+        if pool.iter().all(|(_, weight)| *weight == 0.0) {
+            return Err("note pool needs at least one entry with a weight > 0".to_string());
+        }
+        let avoid_repetition = false;
+        let scale = None;
+        let last_index = None;
+        let note_event_state = Vec::new();
+        let rand_seed = seed.unwrap_or_else(new_random_seed);
+        let rand_gen = Xoshiro256PlusPlus::from_seed(rand_seed);
+        Ok(Self {
+            pool,
+            avoid_repetition,
+            scale,
+            last_index,
+            note_event_state,
+            rand_gen,
+            seed,
+        })
+    }
+
+    /// Return a new pool emitter which avoids picking the same entry twice in a row.
+    #[must_use]
+    pub fn with_avoid_repetition(self, avoid_repetition: bool) -> Self {
+        Self {
+            avoid_repetition,
+            ..self
+        }
+    }
+
+    /// Return a new pool emitter which fits all picked notes into the given scale.
+    #[must_use]
+    pub fn with_scale<S: Into<Option<Scale>>>(self, scale: S) -> Self {
+        let scale = scale.into();
+        Self { scale, ..self }
+    }
+
+    /// Read-only access to the pool entries.
+    pub fn pool(&self) -> &Vec<(Vec<Option<NoteEvent>>, f32)> {
+        &self.pool
+    }
+
+    fn pick_index(&mut self) -> usize {
+        let excluded = if self.avoid_repetition {
+            self.last_index
+        } else {
+            None
+        };
+        let weight = |index: usize, weight: f32| {
+            if Some(index) == excluded {
+                0.0
+            } else {
+                weight
+            }
+        };
+        let total_weight = self
+            .pool
+            .iter()
+            .enumerate()
+            .map(|(index, (_, w))| weight(index, *w))
+            .sum::<f32>();
+        if total_weight <= 0.0 {
+            // only a single usable entry: repetitions can't be avoided
+            return self.last_index.unwrap_or(0);
+        }
+        let mut target = self.rand_gen.gen_range(0.0..total_weight);
+        let mut picked_index = 0;
+        for (index, (_, w)) in self.pool.iter().enumerate() {
+            let w = weight(index, *w);
+            if w > 0.0 {
+                picked_index = index;
+                if target < w {
+                    break;
+                }
+                target -= w;
+            }
+        }
+        picked_index
+    }
+}
+
+impl EventIter for RandomPoolEventIter {
+    fn set_time_base(&mut self, _time_base: &BeatTimeBase) {
+        // nothing to do
+    }
+
+    fn set_external_context(&mut self, _data: &[(Cow<str>, f64)]) {
+        // nothing to do
+    }
+
+    fn run(&mut self, _pulse: PulseIterItem, emit_event: bool) -> Option<Vec<EventIterItem>> {
+        if !emit_event {
+            return None;
+        }
+        let index = self.pick_index();
+        self.last_index = Some(index);
+        let mut note_events = self.pool[index].0.clone();
+        if let Some(scale) = &self.scale {
+            for note_event in note_events.iter_mut().flatten() {
+                if note_event.note.is_note_on() {
+                    note_event.note = scale.transpose(note_event.note, 0);
+                }
+            }
+        }
+        let mut event = Event::NoteEvents(note_events);
+        FixedEventIter::normalize_event(&mut event, &mut self.note_event_state);
+        Some(vec![EventIterItem::new(event)])
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let pool = self
+            .pool
+            .iter()
+            .map(|(note_events, _)| note_events.capacity() * size_of::<Option<NoteEvent>>())
+            .sum::<usize>();
+        MemoryUsage {
+            events: pool + self.pool.capacity() * size_of::<(Vec<Option<NoteEvent>>, f32)>(),
+            ..MemoryUsage::default()
+        }
+    }
+
+    fn duplicate(&self) -> Box<dyn EventIter> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self) {
+        // reset picking state
+        self.last_index = None;
+        self.note_event_state.clear();
+        // reset random number generator to its initial state when the emitter is seeded
+        if let Some(seed) = self.seed {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        }
+        // else create a new random number generator from a random seed
+        else {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(new_random_seed());
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    fn picked_notes(event_iter: &mut RandomPoolEventIter, count: usize) -> Vec<Note> {
+        (0..count)
+            .filter_map(|_| event_iter.run(PulseIterItem::default(), true))
+            .filter_map(|items| match &items[0].event {
+                Event::NoteEvents(notes) => notes[0].as_ref().map(|n| n.note),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pool() -> Result<(), String> {
+        assert!(RandomPoolEventIter::new(vec![], None).is_err());
+        assert!(RandomPoolEventIter::new(vec![(vec![new_note(Note::C4)], 0.0)], None).is_err());
+        assert!(RandomPoolEventIter::new(vec![(vec![new_note(Note::C4)], -1.0)], None).is_err());
+
+        let seed = Some([1; 32]);
+        let pool = vec![
+            (vec![new_note(Note::C4)], 3.0),
+            (vec![new_note(Note::E4)], 1.0),
+            (vec![new_note(Note::G4)], 0.0),
+        ];
+        let mut event_iter = RandomPoolEventIter::new(pool.clone(), seed)?;
+        let notes = picked_notes(&mut event_iter, 32);
+        assert!(notes.iter().all(|n| *n == Note::C4 || *n == Note::E4));
+
+        // seeded emitters repeat after reset
+        event_iter.reset();
+        assert_eq!(picked_notes(&mut event_iter, 32), notes);
+
+        // avoid repetitions
+        let mut event_iter =
+            RandomPoolEventIter::new(pool.clone(), seed)?.with_avoid_repetition(true);
+        let notes = picked_notes(&mut event_iter, 32);
+        assert!(notes.windows(2).all(|w| w[0] != w[1]));
+
+        // scale
+        let scale = Scale::try_from((Note::C4, "minor"))?;
+        let mut event_iter = RandomPoolEventIter::new(pool, seed)?.with_scale(scale);
+        let notes = picked_notes(&mut event_iter, 32);
+        assert!(notes.iter().all(|n| *n == Note::C4 || *n == Note::Ds4));
+        Ok(())
+    }
+}
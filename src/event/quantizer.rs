@@ -0,0 +1,189 @@
+use rand::RngCore;
+
+use crate::{
+    event::{transform::EventTransform, Event, NoteEvent},
+    Scale,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Quantizes emitted event values to a fixed step grid, to emulate the value granularity of
+/// hardware sequencers.
+///
+/// Volume, panning and delay values are rounded to the given step sizes. Note pitches can
+/// optionally be fit into a [`Scale`]. Parameter change values are rounded with the parameter
+/// step size.
+#[derive(Debug, Clone, Default)]
+pub struct EventQuantizer {
+    volume_step: Option<f32>,
+    panning_step: Option<f32>,
+    delay_step: Option<f32>,
+    parameter_step: Option<f32>,
+    scale: Option<Scale>,
+}
+
+impl EventQuantizer {
+    /// Create a new quantizer which does not quantize anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new quantizer which rounds note volumes to the given step size.
+    #[must_use]
+    pub fn with_volume_step<S: Into<Option<f32>>>(self, step: S) -> Self {
+        let volume_step = Self::valid_step(step.into());
+        Self {
+            volume_step,
+            ..self
+        }
+    }
+
+    /// Return a new quantizer which rounds note pannings to the given step size.
+    #[must_use]
+    pub fn with_panning_step<S: Into<Option<f32>>>(self, step: S) -> Self {
+        let panning_step = Self::valid_step(step.into());
+        Self {
+            panning_step,
+            ..self
+        }
+    }
+
+    /// Return a new quantizer which rounds note delays to the given step size.
+    #[must_use]
+    pub fn with_delay_step<S: Into<Option<f32>>>(self, step: S) -> Self {
+        let delay_step = Self::valid_step(step.into());
+        Self { delay_step, ..self }
+    }
+
+    /// Return a new quantizer which rounds parameter change values to the given step size.
+    #[must_use]
+    pub fn with_parameter_step<S: Into<Option<f32>>>(self, step: S) -> Self {
+        let parameter_step = Self::valid_step(step.into());
+        Self {
+            parameter_step,
+            ..self
+        }
+    }
+
+    /// Return a new quantizer which fits all note pitches into the given scale.
+    #[must_use]
+    pub fn with_scale<S: Into<Option<Scale>>>(self, scale: S) -> Self {
+        let scale = scale.into();
+        Self { scale, ..self }
+    }
+
+    /// Quantize the given event in place.
+    pub fn apply(&self, event: &mut Event) {
+        match event {
+            Event::NoteEvents(note_events) => {
+                for note_event in note_events.iter_mut().flatten() {
+                    self.apply_note_event(note_event);
+                }
+            }
+            Event::ParameterChangeEvent(change) => {
+                if let Some(step) = self.parameter_step {
+                    change.value = Self::round(change.value, step);
+                }
+            }
+        }
+    }
+
+    fn apply_note_event(&self, note_event: &mut NoteEvent) {
+        if let Some(scale) = &self.scale {
+            if note_event.note.is_note_on() {
+                note_event.note = scale.transpose(note_event.note, 0);
+            }
+        }
+        if let Some(step) = self.volume_step {
+            note_event.volume = Self::round(note_event.volume, step).max(0.0);
+        }
+        if let Some(step) = self.panning_step {
+            note_event.panning = Self::round(note_event.panning, step).clamp(-1.0, 1.0);
+        }
+        if let Some(step) = self.delay_step {
+            note_event.delay = Self::round(note_event.delay, step).clamp(0.0, 1.0);
+        }
+    }
+
+    fn valid_step(step: Option<f32>) -> Option<f32> {
+        step.filter(|step| *step > 0.0 && step.is_finite())
+    }
+
+    fn round(value: f32, step: f32) -> f32 {
+        (value / step).round() * step
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl EventTransform for EventQuantizer {
+    fn apply(&mut self, event: &mut Event, _rand_gen: &mut dyn RngCore) {
+        EventQuantizer::apply(self, event);
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(self.clone())
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        event::{new_note, new_parameter_change, InstrumentId, ParameterId},
+        Note,
+    };
+
+    #[test]
+    fn quantize() {
+        let quantizer = EventQuantizer::new()
+            .with_volume_step(0.25)
+            .with_panning_step(0.5)
+            .with_delay_step(0.1)
+            .with_parameter_step(0.2);
+
+        let mut event = Event::NoteEvents(vec![
+            new_note((Note::C4, None::<InstrumentId>, 0.3, -0.3, 0.47)),
+            None,
+        ]);
+        quantizer.apply(&mut event);
+        assert_eq!(
+            event,
+            Event::NoteEvents(vec![
+                new_note((Note::C4, None::<InstrumentId>, 0.25, -0.5, 0.5)),
+                None
+            ])
+        );
+
+        let mut event =
+            Event::ParameterChangeEvent(new_parameter_change(ParameterId::from(1), 0.75));
+        quantizer.apply(&mut event);
+        assert_eq!(
+            event,
+            Event::ParameterChangeEvent(new_parameter_change(ParameterId::from(1), 0.8))
+        );
+    }
+
+    #[test]
+    fn quantize_scale() {
+        let scale = Scale::try_from((Note::C4, "major")).unwrap();
+        let quantizer = EventQuantizer::new().with_scale(scale);
+
+        let mut event = Event::NoteEvents(vec![
+            new_note(Note::Cs4),
+            new_note(Note::E4),
+            new_note(Note::OFF),
+        ]);
+        quantizer.apply(&mut event);
+        assert_eq!(
+            event,
+            Event::NoteEvents(vec![
+                new_note(Note::C4),
+                new_note(Note::E4),
+                new_note(Note::OFF)
+            ])
+        );
+    }
+}
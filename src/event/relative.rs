@@ -0,0 +1,276 @@
+//! Notes which are specified relative to the previously emitted note.
+
+use std::fmt::Display;
+
+use rand::RngCore;
+
+use crate::{
+    event::{
+        transform::EventTransform, transposer::EventTransposer, Event, EventData, EventDataValue,
+        NoteEvent,
+    },
+    Note, Scale,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Note event extra data key, which marks a note event as relative note. The key's value is
+/// the relative note's string representation, e.g. `+3`. Relative notes get resolved and the
+/// key gets removed by a [`RelativeNoteResolver`] at emit time.
+pub const RELATIVE_NOTE_KEY: &str = "relative";
+
+// -------------------------------------------------------------------------------------------------
+
+/// A note interval from the previously emitted note, e.g. to write transposable riffs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelativeNote {
+    /// Offset in semitones, written as `+3` or `+-5`.
+    Semitones(i32),
+    /// Offset in scale degrees, written as `^2` or `^-3`.
+    Degrees(i32),
+}
+
+impl RelativeNote {
+    /// Create a placeholder note event for the relative note, which gets resolved by a
+    /// [`RelativeNoteResolver`] when it gets emitted.
+    pub fn to_note_event(self) -> NoteEvent {
+        NoteEvent {
+            extra: Some(EventData::from([(
+                RELATIVE_NOTE_KEY.to_string(),
+                EventDataValue::String(self.to_string()),
+            )])),
+            ..NoteEvent::from(Note::C4)
+        }
+    }
+
+    /// Get the relative note from the given note event's extra data, if it's a relative note.
+    pub fn from_note_event(note_event: &NoteEvent) -> Option<Self> {
+        match note_event.extra.as_ref()?.get(RELATIVE_NOTE_KEY)? {
+            EventDataValue::String(str) => Self::try_from(str.as_str()).ok(),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&str> for RelativeNote {
+    type Error = String;
+
+    fn try_from(str: &str) -> Result<Self, Self::Error> {
+        // relative notes always start with an explicit '+' or '^' prefix, so they can't be
+        // confused with negative note numbers or names
+        let mut chars = str.chars();
+        let prefix = chars.next();
+        let (sign, amount) = match chars.as_str().strip_prefix('-') {
+            Some(amount) => (-1, amount),
+            None => (1, chars.as_str()),
+        };
+        let amount = amount
+            .parse::<u8>()
+            .ok()
+            .filter(|value| *value <= 0x7F && !amount.starts_with('+'))
+            .map(|value| sign * value as i32);
+        match (prefix, amount) {
+            (Some('+'), Some(amount)) => Ok(Self::Semitones(amount)),
+            (Some('^'), Some(amount)) => Ok(Self::Degrees(amount)),
+            _ => Err(format!(
+                "invalid relative note '{}': expecting '+N' or '+-N' semitones, or '^N' or '^-N' \
+                scale degrees with N in range [0 - 127]",
+                str
+            )),
+        }
+    }
+}
+
+impl Display for RelativeNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Semitones(amount) => write!(f, "+{}", amount),
+            Self::Degrees(amount) => write!(f, "^{}", amount),
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Resolves [`RelativeNote`]S in emitted note events at emit time, using the previously
+/// emitted note-on as reference. Before the first note-on and after resets, notes are relative
+/// to the resolver's reference note.
+///
+/// Notes in polyphonic events get resolved in order, so relative notes in a chord stack up
+/// intervals. Scale degrees move along the resolver's scale, which by default is the major
+/// scale of the reference note.
+#[derive(Debug, Clone)]
+pub struct RelativeNoteResolver {
+    reference: Note,
+    scale: Scale,
+    previous: Note,
+}
+
+impl RelativeNoteResolver {
+    /// Create a new resolver with the given reference note. Notes which are not note-ons
+    /// fall back to C4.
+    pub fn new(reference: Note) -> Self {
+        let reference = if reference.is_note_on() {
+            reference
+        } else {
+            Note::C4
+        };
+        let scale = Scale::try_from((reference, "major")).expect("Failed to create major scale");
+        let previous = reference;
+        Self {
+            reference,
+            scale,
+            previous,
+        }
+    }
+
+    /// Return a new resolver which moves scale degrees along the given scale.
+    #[must_use]
+    pub fn with_scale(self, scale: Scale) -> Self {
+        Self { scale, ..self }
+    }
+
+    /// The note which relative notes refer to before the first emitted note.
+    pub fn reference(&self) -> Note {
+        self.reference
+    }
+
+    /// The scale of relative scale degrees.
+    pub fn scale(&self) -> &Scale {
+        &self.scale
+    }
+
+    /// Resolve relative notes in the given event in place and memorize its last note-on.
+    /// Parameter change events are not modified.
+    pub fn apply(&mut self, event: &mut Event) {
+        if let Event::NoteEvents(note_events) = event {
+            self.resolve(note_events);
+        }
+    }
+
+    /// Resolve relative notes in the given note events in place and memorize the last note-on.
+    pub fn resolve(&mut self, note_events: &mut [Option<NoteEvent>]) {
+        for note_event in note_events.iter_mut().flatten() {
+            if let Some(relative_note) = RelativeNote::from_note_event(note_event) {
+                note_event.note = match relative_note {
+                    RelativeNote::Semitones(amount) => self.previous.transposed(amount),
+                    RelativeNote::Degrees(amount) => {
+                        EventTransposer::shift_degrees(&self.scale, self.previous, amount)
+                    }
+                };
+                let is_empty = note_event.extra.as_mut().is_some_and(|extra| {
+                    extra.remove(RELATIVE_NOTE_KEY);
+                    extra.is_empty()
+                });
+                if is_empty {
+                    note_event.extra = None;
+                }
+            }
+            if note_event.note.is_note_on() {
+                self.previous = note_event.note;
+            }
+        }
+    }
+
+    /// Reset the previously emitted note to the reference note.
+    pub fn reset(&mut self) {
+        self.previous = self.reference;
+    }
+}
+
+impl Default for RelativeNoteResolver {
+    fn default() -> Self {
+        Self::new(Note::C4)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl EventTransform for RelativeNoteResolver {
+    fn apply(&mut self, event: &mut Event, _rand_gen: &mut dyn RngCore) {
+        RelativeNoteResolver::apply(self, event);
+    }
+
+    fn applies_without_pulse(&self) -> bool {
+        // injected events should not change the previously emitted note
+        false
+    }
+
+    fn reset(&mut self) {
+        RelativeNoteResolver::reset(self);
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(self.clone())
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::new_note;
+
+    #[test]
+    fn parse() {
+        assert_eq!(RelativeNote::try_from("+3"), Ok(RelativeNote::Semitones(3)));
+        assert_eq!(
+            RelativeNote::try_from("+-5"),
+            Ok(RelativeNote::Semitones(-5))
+        );
+        assert_eq!(RelativeNote::try_from("^2"), Ok(RelativeNote::Degrees(2)));
+        assert_eq!(RelativeNote::try_from("^-3"), Ok(RelativeNote::Degrees(-3)));
+        for invalid in [
+            "", "+", "3", "++3", "+-+3", "+--3", "^x", "^-", "+128", "c4", "-5", "v3",
+        ] {
+            assert!(RelativeNote::try_from(invalid).is_err(), "{}", invalid);
+        }
+        for relative in ["+0", "+12", "+-7", "^1", "^-2"] {
+            assert_eq!(
+                RelativeNote::try_from(relative).unwrap().to_string(),
+                relative
+            );
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        let mut resolver = RelativeNoteResolver::new(Note::C4);
+        let mut notes = vec![
+            Some(RelativeNote::Semitones(3).to_note_event()),
+            new_note(Note::OFF),
+            Some(RelativeNote::Semitones(-5).to_note_event()),
+            new_note(Note::C5),
+            Some(RelativeNote::Degrees(2).to_note_event()),
+            Some(RelativeNote::Degrees(-3).to_note_event()),
+        ];
+        resolver.resolve(&mut notes);
+        assert_eq!(
+            notes,
+            vec![
+                new_note(Note::Ds4),
+                new_note(Note::OFF),
+                new_note(Note::As3),
+                new_note(Note::C5),
+                new_note(Note::E5),
+                new_note(Note::B4),
+            ]
+        );
+        // reset
+        resolver.reset();
+        let mut notes = vec![Some(RelativeNote::Degrees(1).to_note_event())];
+        resolver.resolve(&mut notes);
+        assert_eq!(notes, vec![new_note(Note::D4)]);
+
+        // scale
+        let scale = Scale::try_from((Note::C4, "minor")).unwrap();
+        let mut resolver = RelativeNoteResolver::new(Note::C4).with_scale(scale);
+        let mut notes = vec![Some(RelativeNote::Degrees(2).to_note_event())];
+        resolver.resolve(&mut notes);
+        assert_eq!(notes, vec![new_note(Note::Ds4)]);
+
+        // invalid references
+        assert_eq!(RelativeNoteResolver::new(Note::OFF).reference(), Note::C4);
+    }
+}
@@ -1,11 +1,16 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, mem::size_of};
 
 use mlua::prelude::*;
 
 use crate::{
-    bindings::{note_events_from_value, LuaCallback, LuaTimeoutHook},
-    event::{fixed::FixedEventIter, NoteEvent},
-    BeatTimeBase, Event, EventIter, EventIterItem, PulseIterItem,
+    bindings::{
+        note_events_from_value, parameter_change_event_from_value, LuaCallback, LuaTimeoutHook,
+    },
+    event::{fixed::FixedEventIter, voicing::VoiceSpread, NoteEvent},
+    memory::MemoryUsage,
+    parameter::RhythmParameterValues,
+    shared::SharedValues,
+    BeatTimeBase, Event, EventIter, EventIterItem, PulseIterItem, Warning,
 };
 
 // -------------------------------------------------------------------------------------------------
@@ -16,6 +21,7 @@ pub struct ScriptedEventIter {
     timeout_hook: LuaTimeoutHook,
     callback: LuaCallback,
     note_event_state: Vec<Option<NoteEvent>>,
+    voice_spread: Option<VoiceSpread>,
     pulse_step: usize,
     pulse_time_step: f64,
     step: usize,
@@ -33,6 +39,7 @@ impl ScriptedEventIter {
         // initialize emitter context for the function
         let mut callback = callback;
         let note_event_state = Vec::new();
+        let voice_spread = None;
         let pulse = PulseIterItem::default();
         let pulse_step = 0;
         let pulse_time_step = 0.0;
@@ -42,12 +49,24 @@ impl ScriptedEventIter {
             timeout_hook,
             callback,
             note_event_state,
+            voice_spread,
             pulse_step,
             pulse_time_step,
             step,
         })
     }
 
+    /// Return a new event iter which spreads big chords in the script's note events with the
+    /// given voice spread.
+    #[must_use]
+    pub fn with_voice_spread<S: Into<Option<VoiceSpread>>>(self, spread: S) -> Self {
+        let voice_spread = spread.into();
+        Self {
+            voice_spread,
+            ..self
+        }
+    }
+
     fn next_event(&mut self, pulse: PulseIterItem) -> LuaResult<Option<Vec<EventIterItem>>> {
         // reset timeout
         self.timeout_hook.reset();
@@ -56,10 +75,21 @@ impl ScriptedEventIter {
         self.callback
             .set_context_pulse_step(self.pulse_step, self.pulse_time_step)?;
         self.callback.set_context_step(self.step)?;
-        // invoke callback and evaluate the result
-        let events = note_events_from_value(&self.callback.call()?, None)?;
+        // invoke callback and evaluate the result with the engine's value range policy
+        let _scope = self.callback.value_range().enter();
+        let result = self.callback.call()?;
+        let mut event = match parameter_change_event_from_value(&result)? {
+            Some(parameter_change) => Event::ParameterChangeEvent(parameter_change),
+            None => {
+                let mut events = note_events_from_value(&result, None)?;
+                // spread big chords, if enabled
+                if let Some(voice_spread) = &self.voice_spread {
+                    voice_spread.apply(&mut events);
+                }
+                Event::NoteEvents(events)
+            }
+        };
         // normalize event
-        let mut event = Event::NoteEvents(events);
         FixedEventIter::normalize_event(&mut event, &mut self.note_event_state);
         // return as EventIterItem
         Ok(Some(vec![EventIterItem::new(event)]))
@@ -72,6 +102,7 @@ impl Clone for ScriptedEventIter {
             timeout_hook: self.timeout_hook.clone(),
             callback: self.callback.clone(),
             note_event_state: self.note_event_state.clone(),
+            voice_spread: self.voice_spread.clone(),
             pulse_step: self.pulse_step,
             pulse_time_step: self.pulse_time_step,
             step: self.step,
@@ -96,6 +127,18 @@ impl EventIter for ScriptedEventIter {
         }
     }
 
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        if let Err(err) = self.callback.set_context_shared_values(values) {
+            self.callback.handle_error(&err);
+        }
+    }
+
+    fn set_parameter_values(&mut self, values: &RhythmParameterValues) {
+        if let Err(err) = self.callback.set_context_parameter_values(values) {
+            self.callback.handle_error(&err);
+        }
+    }
+
     fn run(&mut self, pulse: PulseIterItem, emit_event: bool) -> Option<Vec<EventIterItem>> {
         // generate a new event and move or only update pulse counters
         if emit_event {
@@ -117,10 +160,39 @@ impl EventIter for ScriptedEventIter {
         }
     }
 
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        self.callback.value_range().take_warnings()
+    }
+
+    fn take_script_errors(&mut self) -> Vec<String> {
+        self.callback.script_errors().take()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            lua_heap: self.callback.used_memory(),
+            events: self.note_event_state.capacity() * size_of::<Option<NoteEvent>>(),
+            ..MemoryUsage::default()
+        }
+    }
+
+    fn trim_memory(&mut self) {
+        self.callback.collect_garbage();
+        self.note_event_state.shrink_to_fit();
+    }
+
     fn duplicate(&self) -> Box<dyn EventIter> {
         Box::new(self.clone())
     }
 
+    fn try_duplicate(&self) -> Result<Box<dyn EventIter>, String> {
+        let callback = self.callback.duplicate().map_err(|err| err.to_string())?;
+        Ok(Box::new(Self {
+            callback,
+            ..self.clone()
+        }))
+    }
+
     fn reset(&mut self) {
         // reset timeout
         self.timeout_hook.reset();
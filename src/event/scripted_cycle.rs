@@ -1,12 +1,28 @@
 use std::{borrow::Cow, collections::HashMap};
 
-use fraction::ToPrimitive;
+use fraction::{Fraction, ToPrimitive};
 use mlua::prelude::*;
 
 use crate::{
-    bindings::{add_lua_callback_error, note_events_from_value, LuaCallback, LuaTimeoutHook},
-    event::{cycle::CycleNoteEvents, EventIter, EventIterItem, NoteEvent},
-    BeatTimeBase, PulseIterItem,
+    bindings::{
+        cycle_map_events_from_value, instrument_from_cycle_target, LuaCallback, LuaScriptErrors,
+        LuaTimeoutHook, LuaValueRange,
+    },
+    event::{
+        cycle::{
+            apply_choke_group_target, channel_instrument, check_cycle_value, cycle_memory_usage,
+            fractional_note_event, CycleNoteEvents,
+        },
+        relative::RelativeNote,
+        target::TargetSchema,
+        voicing::VoiceSpread,
+        Event, EventIter, EventIterItem, InstrumentId, NoteEvent,
+    },
+    memory::MemoryUsage,
+    parameter::RhythmParameterValues,
+    shared::SharedValues,
+    warning::WarningCollector,
+    BeatTimeBase, PulseIterItem, Warning,
 };
 
 use crate::tidal::{Cycle, Event as CycleEvent, Value as CycleValue};
@@ -17,7 +33,8 @@ use crate::tidal::{Cycle, Event as CycleEvent, Value as CycleValue};
 ///
 /// Channels from cycle are merged down into note events on different voices.
 /// Values in cycles can be mapped to notes with an optional mapping table or
-/// callbacks from from scripts.
+/// callbacks from from scripts. Channels can optionally be assigned to instruments, which
+/// then apply to all notes without a target.
 ///
 /// See also [`CycleEventIter`](`super::cycle::CycleEventIter`)
 #[derive(Clone, Debug)]
@@ -27,6 +44,13 @@ pub struct ScriptedCycleEventIter {
     mapping_callback: Option<LuaCallback>,
     timeout_hook: Option<LuaTimeoutHook>,
     channel_steps: Vec<usize>,
+    channel_instruments: Vec<InstrumentId>,
+    voice_spread: Option<VoiceSpread>,
+    fractional_notes: bool,
+    target_schema: Option<TargetSchema>,
+    value_range: LuaValueRange,
+    script_errors: LuaScriptErrors,
+    warnings: WarningCollector,
 }
 
 impl ScriptedCycleEventIter {
@@ -36,12 +60,26 @@ impl ScriptedCycleEventIter {
         let mapping_callback = None;
         let timeout_hook = None;
         let channel_steps = vec![];
+        let channel_instruments = vec![];
+        let voice_spread = None;
+        let fractional_notes = false;
+        let target_schema = None;
+        let value_range = LuaValueRange::default();
+        let script_errors = LuaScriptErrors::default();
+        let warnings = WarningCollector::new();
         Self {
             cycle,
             mappings,
             mapping_callback,
             timeout_hook,
             channel_steps,
+            channel_instruments,
+            voice_spread,
+            fractional_notes,
+            target_schema,
+            value_range,
+            script_errors,
+            warnings,
         }
     }
 
@@ -63,24 +101,94 @@ impl ScriptedCycleEventIter {
         let step_length = 0.0;
         mapping_callback.set_cycle_context(time_base, channel, step, step_length)?;
         let channel_steps = vec![];
+        let channel_instruments = vec![];
+        let voice_spread = None;
+        let fractional_notes = false;
+        let target_schema = None;
+        let value_range = mapping_callback.value_range().clone();
+        let script_errors = mapping_callback.script_errors().clone();
+        let warnings = WarningCollector::new();
         Ok(Self {
             cycle,
             mappings,
             mapping_callback: Some(mapping_callback),
             timeout_hook: Some(timeout_hook),
             channel_steps,
+            channel_instruments,
+            voice_spread,
+            fractional_notes,
+            target_schema,
+            value_range,
+            script_errors,
+            warnings,
         })
     }
 
-    /// Generate a note event stack from a single cycle event, applying mappings if necessary
-    fn note_events(
+    /// Return a new cycle which assigns the given instruments to the cycle's stack channels.
+    /// Channels which exceed the given instrument list wrap around. Note events with explicit
+    /// targets or instruments are not affected.
+    #[must_use]
+    pub fn with_channel_instruments(self, instruments: &[InstrumentId]) -> Self {
+        let channel_instruments = instruments.to_vec();
+        Self {
+            channel_instruments,
+            ..self
+        }
+    }
+
+    /// Return a new cycle which spreads big chords in the cycle's note events with the given
+    /// voice spread.
+    #[must_use]
+    pub fn with_voice_spread<S: Into<Option<VoiceSpread>>>(self, spread: S) -> Self {
+        let voice_spread = spread.into();
+        Self {
+            voice_spread,
+            ..self
+        }
+    }
+
+    /// Return a new cycle which interprets float values as fractional MIDI notes, see
+    /// [`CycleEventIter::with_fractional_notes`](`super::cycle::CycleEventIter::with_fractional_notes`).
+    #[must_use]
+    pub fn with_fractional_notes(self, fractional_notes: bool) -> Self {
+        Self {
+            fractional_notes,
+            ..self
+        }
+    }
+
+    /// Return a new cycle which validates and applies named targets with the given schema, see
+    /// [`CycleEventIter::with_target_schema`](`super::cycle::CycleEventIter::with_target_schema`).
+    #[must_use]
+    pub fn with_target_schema<S: Into<Option<TargetSchema>>>(self, schema: S) -> Self {
+        let target_schema = schema.into();
+        Self {
+            target_schema,
+            ..self
+        }
+    }
+
+    /// Return a new cycle which applies the value range policy of the given Lua engine to
+    /// out of range values and reports clamped values as warnings.
+    #[must_use]
+    pub(crate) fn with_value_range(self, value_range: LuaValueRange) -> Self {
+        Self {
+            value_range,
+            ..self
+        }
+    }
+
+    /// Generate events from a single cycle event, applying mappings if necessary. Mapping
+    /// functions may return multiple events, which then get spread across the cycle event.
+    fn events(
         &mut self,
         channel_index: usize,
         _event_index: usize,
+        event_start: f64,
         event_length: f64,
         event: CycleEvent,
-    ) -> LuaResult<Vec<Option<NoteEvent>>> {
-        let mut note_events = {
+    ) -> LuaResult<Vec<Event>> {
+        let mut events = {
             if let Some(mapping_callback) = self.mapping_callback.as_mut() {
                 // increase step counter
                 if self.channel_steps.len() <= channel_index {
@@ -94,36 +202,98 @@ impl ScriptedCycleEventIter {
                     channel_step,
                     event_length,
                 )?;
+                mapping_callback.set_context_cycle_event(event_start, event.target())?;
                 // call mapping function
                 let result = mapping_callback.call_with_arg(event.string())?;
-                note_events_from_value(&result, None)?
+                cycle_map_events_from_value(&result)?
             } else if let Some(note_events) = self.mappings.get(event.string()) {
                 // apply custom note mapping
-                note_events.clone()
+                vec![Event::NoteEvents(note_events.clone())]
+            } else if let Ok(relative_note) = RelativeNote::try_from(event.string()) {
+                // relative notes get resolved by the rhythm at emit time
+                vec![Event::NoteEvents(vec![Some(relative_note.to_note_event())])]
             } else {
-                // try converting the cycle value to a single note
-                event.value().try_into().map_err(LuaError::RuntimeError)?
+                match event.value() {
+                    CycleValue::Float(value) if self.fractional_notes => {
+                        // interpret floats as fractional notes
+                        vec![Event::NoteEvents(vec![fractional_note_event(
+                            self.value_range.policy(),
+                            &mut self.warnings,
+                            *value,
+                        )
+                        .map_err(LuaError::RuntimeError)?])]
+                    }
+                    value => {
+                        // try converting the cycle value to a single note, applying the
+                        // engine's policy to out of range values
+                        let note_events: Vec<Option<NoteEvent>> =
+                            value.try_into().map_err(LuaError::RuntimeError)?;
+                        // verify that all identifiers are mapped
+                        if note_events.iter().all(|f| f.is_none())
+                            && !matches!(value, CycleValue::Rest | CycleValue::Hold)
+                        {
+                            return Err(LuaError::runtime(format!(
+                                "invalid/unknown identifier in cycle: '{}'. please check for typos or add a custom mapping for it.",
+                                event.string()
+                            )));
+                        }
+                        check_cycle_value(self.value_range.policy(), &mut self.warnings, value)
+                            .map_err(LuaError::RuntimeError)?;
+                        vec![Event::NoteEvents(note_events)]
+                    }
+                }
             }
         };
-        // verify that all identifiers are mapped
-        if (note_events.is_empty() || note_events.iter().all(|f| f.is_none()))
-            && self.mapping_callback.is_none()
-            && !matches!(event.value(), CycleValue::Rest | CycleValue::Hold)
-        {
-            return Err(LuaError::runtime(format!(
-                "invalid/unknown identifier in cycle: '{}'. please check for typos or add a custom mapping for it.",
-                event.string()
-            )));
+        for event_item in &mut events {
+            if let Event::NoteEvents(note_events) = event_item {
+                self.apply_targets(channel_index, &event, note_events)?;
+            }
         }
+        Ok(events)
+    }
+
+    /// Apply the cycle event's targets and the channel's instrument, spreading big chords.
+    fn apply_targets(
+        &mut self,
+        channel_index: usize,
+        event: &CycleEvent,
+        note_events: &mut [Option<NoteEvent>],
+    ) -> LuaResult<()> {
         // inject target instrument, if present
-        if let Some(instrument) = event.target().into() {
-            for mut note_event in &mut note_events {
-                if let Some(note_event) = &mut note_event {
-                    note_event.instrument = Some(instrument);
-                }
+        if let Some(instrument) = instrument_from_cycle_target(event.target())? {
+            for note_event in note_events.iter_mut().flatten() {
+                note_event.instrument = Some(instrument);
             }
         }
-        Ok(note_events)
+        // inject choke group, if present
+        apply_choke_group_target(event.target(), note_events);
+        // apply named target values, if present
+        if let Some(schema) = &self.target_schema {
+            schema
+                .apply(&mut self.warnings, event.target(), note_events)
+                .map_err(LuaError::RuntimeError)?;
+        }
+        // inject channel instrument, if present
+        if let Some(instrument) = channel_instrument(&self.channel_instruments, channel_index) {
+            for note_event in note_events.iter_mut().flatten() {
+                note_event.instrument = note_event.instrument.or(Some(instrument));
+            }
+        }
+        // spread big chords, if enabled
+        if let Some(voice_spread) = &self.voice_spread {
+            voice_spread.apply(note_events);
+        }
+        Ok(())
+    }
+
+    /// Report a cycle or mapping error via the mapping callback, if any, else add it to the
+    /// cycle's own errors under the given name.
+    fn handle_error(&self, name: &str, err: &LuaError) {
+        if let Some(callback) = &self.mapping_callback {
+            callback.handle_error(err)
+        } else {
+            self.script_errors.add(name, err)
+        }
     }
 
     /// Generate next batch of events from the next cycle run.
@@ -134,7 +304,7 @@ impl ScriptedCycleEventIter {
             match self.cycle.generate() {
                 Ok(events) => events,
                 Err(err) => {
-                    add_lua_callback_error("cycle", &LuaError::RuntimeError(err));
+                    self.handle_error("cycle", &LuaError::RuntimeError(err));
                     // skip processing events
                     return vec![];
                 }
@@ -144,31 +314,57 @@ impl ScriptedCycleEventIter {
         if let Some(timeout_hook) = &mut self.timeout_hook {
             timeout_hook.reset();
         }
+        // apply the engine's value range policy to all converted values
+        let _scope = self.value_range.enter();
         // convert possibly mapped cycle channel items to a list of note events
         let mut timed_note_events = CycleNoteEvents::new();
+        let mut parameter_change_events = Vec::new();
         for (channel_index, channel_events) in events.into_iter().enumerate() {
             for (event_index, event) in channel_events.into_iter().enumerate() {
                 let start = event.span().start();
                 let length = event.span().length();
+                let event_start = start.to_f64().unwrap_or_default();
                 let event_length = length.to_f64().unwrap_or_default();
-                match self.note_events(channel_index, event_index, event_length, event) {
-                    Err(err) => {
-                        if let Some(callback) = &self.mapping_callback {
-                            callback.handle_error(&err)
-                        } else {
-                            add_lua_callback_error("map", &err)
+                let events =
+                    match self.events(channel_index, event_index, event_start, event_length, event)
+                    {
+                        Err(err) => {
+                            self.handle_error("map", &err);
+                            continue;
                         }
-                    }
-                    Ok(note_events) => {
-                        if !note_events.is_empty() {
-                            timed_note_events.add(channel_index, start, length, note_events);
+                        Ok(events) => events,
+                    };
+                // spread multiple events evenly across the cycle event
+                let sub_length = length / Fraction::from(events.len().max(1) as u64);
+                for (index, event) in events.into_iter().enumerate() {
+                    let sub_start = start + sub_length * Fraction::from(index as u64);
+                    match event {
+                        Event::NoteEvents(note_events) => {
+                            if !note_events.is_empty() {
+                                timed_note_events.add(
+                                    channel_index,
+                                    sub_start,
+                                    sub_length,
+                                    note_events,
+                                );
+                            }
+                        }
+                        Event::ParameterChangeEvent(_) => {
+                            parameter_change_events.push(EventIterItem::new_with_fraction(
+                                event, sub_start, sub_length,
+                            ));
                         }
                     }
                 }
             }
         }
         // convert timed note events into EventIterItems
-        timed_note_events.into_event_iter_items()
+        let mut event_iter_items = timed_note_events.into_event_iter_items();
+        if !parameter_change_events.is_empty() {
+            event_iter_items.append(&mut parameter_change_events);
+            event_iter_items.sort_by(|a, b| a.start.cmp(&b.start));
+        }
+        event_iter_items
     }
 }
 
@@ -195,6 +391,22 @@ impl EventIter for ScriptedCycleEventIter {
         }
     }
 
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        if let Some(callback) = &mut self.mapping_callback {
+            if let Err(err) = callback.set_context_shared_values(values) {
+                callback.handle_error(&err);
+            }
+        }
+    }
+
+    fn set_parameter_values(&mut self, values: &RhythmParameterValues) {
+        if let Some(callback) = &mut self.mapping_callback {
+            if let Err(err) = callback.set_context_parameter_values(values) {
+                callback.handle_error(&err);
+            }
+        }
+    }
+
     fn run(&mut self, _pulse: PulseIterItem, emit_event: bool) -> Option<Vec<EventIterItem>> {
         if emit_event {
             Some(self.generate_events())
@@ -203,10 +415,56 @@ impl EventIter for ScriptedCycleEventIter {
         }
     }
 
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.cycle.set_seed(seed);
+    }
+
+    fn set_reversed(&mut self, reversed: bool) {
+        self.cycle.set_reversed(reversed);
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        let mut warnings = self.warnings.take();
+        warnings.append(&mut self.value_range.take_warnings());
+        warnings
+    }
+
+    fn take_script_errors(&mut self) -> Vec<String> {
+        self.script_errors.take()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            lua_heap: self
+                .mapping_callback
+                .as_ref()
+                .map_or(0, LuaCallback::used_memory),
+            cycles: cycle_memory_usage(&self.cycle, &self.mappings),
+            ..MemoryUsage::default()
+        }
+    }
+
+    fn trim_memory(&mut self) {
+        if let Some(callback) = &self.mapping_callback {
+            callback.collect_garbage();
+        }
+    }
+
     fn duplicate(&self) -> Box<dyn EventIter> {
         Box::new(self.clone())
     }
 
+    fn try_duplicate(&self) -> Result<Box<dyn EventIter>, String> {
+        let mapping_callback = match &self.mapping_callback {
+            Some(callback) => Some(callback.duplicate().map_err(|err| err.to_string())?),
+            None => None,
+        };
+        Ok(Box::new(Self {
+            mapping_callback,
+            ..self.clone()
+        }))
+    }
+
     fn reset(&mut self) {
         // reset cycle
         self.cycle.reset();
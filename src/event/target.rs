@@ -0,0 +1,305 @@
+//! Schemas for named cycle targets, which validate target values and apply them to notes.
+
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use crate::{
+    event::{EventData, EventDataValue, NoteEvent},
+    tidal::Target as CycleTarget,
+    warning::{WarningCollector, WarningKind},
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Value type of a named cycle target.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TargetKind {
+    /// Floating point values.
+    Number,
+    /// Integer values: fractional values get rounded.
+    Integer,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Declares value type, default value and value range of a named cycle target in a
+/// [`TargetSchema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetDefinition {
+    kind: TargetKind,
+    default: Option<f64>,
+    range: Option<RangeInclusive<f64>>,
+}
+
+impl TargetDefinition {
+    /// Create a new target definition of the given kind without default value and range.
+    pub fn new(kind: TargetKind) -> Self {
+        Self {
+            kind,
+            default: None,
+            range: None,
+        }
+    }
+
+    /// Return a new definition with the given default value, which applies when the target
+    /// is used without a value, e.g. `bd:v` instead of `bd:v=0.5`.
+    #[must_use]
+    pub fn with_default(self, default: f64) -> Self {
+        let default = Some(default);
+        Self { default, ..self }
+    }
+
+    /// Return a new definition with the given value range. Out of range values get clamped.
+    #[must_use]
+    pub fn with_range(self, range: RangeInclusive<f64>) -> Self {
+        let range = Some(range);
+        Self { range, ..self }
+    }
+
+    /// The target's value type.
+    pub fn kind(&self) -> TargetKind {
+        self.kind
+    }
+
+    /// The target's default value, if any.
+    pub fn default(&self) -> Option<f64> {
+        self.default
+    }
+
+    /// The target's value range, if any.
+    pub fn range(&self) -> Option<&RangeInclusive<f64>> {
+        self.range.as_ref()
+    }
+
+    /// Validate and normalize the given value of the target with the given name.
+    fn normalize(&self, name: &str, value: f64, warnings: &mut WarningCollector) -> f64 {
+        let mut normalized = value;
+        if self.kind == TargetKind::Integer {
+            normalized = normalized.round();
+            if normalized != value {
+                warnings.add(
+                    WarningKind::Rounded,
+                    format!(
+                        "cycle target '{}' value {} got rounded to {}",
+                        name, value, normalized
+                    ),
+                );
+            }
+        }
+        if let Some(range) = &self.range {
+            let rounded = normalized;
+            normalized = normalized.clamp(*range.start(), *range.end());
+            if normalized != rounded {
+                warnings.add(
+                    WarningKind::Clamped,
+                    format!(
+                        "cycle target '{}' value {} got clamped to {}",
+                        name, value, normalized
+                    ),
+                );
+            }
+        }
+        normalized
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Handling of named cycle targets which are not declared in a [`TargetSchema`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum UnknownTargetAction {
+    /// Silently ignore unknown targets. This is the default.
+    #[default]
+    Ignore,
+    /// Ignore unknown targets and report them as warnings.
+    Warn,
+    /// Unknown targets are errors.
+    Error,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Declares named targets of a cycle, so target values such as `bd:v=0.5` or `bd:cut=2` get
+/// validated, normalized and applied to the cycle's note events.
+///
+/// Targets named `v`, `p` and `d` set the note's volume, panning and delay. All other targets
+/// get passed as note event extra data with the target's name as key.
+///
+/// `chokeN` targets are always handled as choke groups and are not affected by schemas.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TargetSchema {
+    targets: HashMap<String, TargetDefinition>,
+    unknown_targets: UnknownTargetAction,
+}
+
+impl TargetSchema {
+    /// Create a new, empty schema, which ignores unknown targets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new schema with the given target declared.
+    #[must_use]
+    pub fn with_target<S: Into<String>>(self, name: S, definition: TargetDefinition) -> Self {
+        let mut targets = self.targets;
+        targets.insert(name.into(), definition);
+        Self { targets, ..self }
+    }
+
+    /// Return a new schema which handles undeclared targets with the given action.
+    #[must_use]
+    pub fn with_unknown_targets(self, unknown_targets: UnknownTargetAction) -> Self {
+        Self {
+            unknown_targets,
+            ..self
+        }
+    }
+
+    /// Definition of the target with the given name, if it's declared.
+    pub fn target(&self, name: &str) -> Option<&TargetDefinition> {
+        self.targets.get(name)
+    }
+
+    /// Handling of undeclared targets.
+    pub fn unknown_targets(&self) -> UnknownTargetAction {
+        self.unknown_targets
+    }
+
+    /// Validate the given cycle target and apply its value to the given note events.
+    ///
+    /// Returns error when the target is unknown and unknown targets are errors, or when a
+    /// declared target has neither a value nor a default value.
+    pub(crate) fn apply(
+        &self,
+        warnings: &mut WarningCollector,
+        target: &CycleTarget,
+        note_events: &mut [Option<NoteEvent>],
+    ) -> Result<(), String> {
+        if let CycleTarget::Name(target) = target {
+            let (name, value) = match target.split_once('=') {
+                Some((name, value)) => (name, value.parse::<f64>().ok()),
+                None => (target.as_ref(), None),
+            };
+            if value.is_none()
+                && name
+                    .strip_prefix("choke")
+                    .is_some_and(|group| group.parse::<u32>().is_ok())
+            {
+                // choke groups are handled separately
+                return Ok(());
+            }
+            if let Some(definition) = self.targets.get(name) {
+                let value = value.or(definition.default).ok_or_else(|| {
+                    format!("cycle target '{}' needs a value, e.g. '{}=1'", name, name)
+                })?;
+                let value = definition.normalize(name, value, warnings);
+                for note_event in note_events.iter_mut().flatten() {
+                    match name {
+                        "v" => note_event.volume = value as f32,
+                        "p" => note_event.panning = value as f32,
+                        "d" => note_event.delay = value as f32,
+                        _ => {
+                            let value = match definition.kind {
+                                TargetKind::Number => EventDataValue::Number(value),
+                                TargetKind::Integer => EventDataValue::Integer(value as i64),
+                            };
+                            note_event
+                                .extra
+                                .get_or_insert_with(EventData::new)
+                                .insert(name.to_string(), value);
+                        }
+                    }
+                }
+            } else {
+                match self.unknown_targets {
+                    UnknownTargetAction::Ignore => (),
+                    UnknownTargetAction::Warn => warnings.add(
+                        WarningKind::Unmapped,
+                        format!("unknown cycle target '{}' got ignored", name),
+                    ),
+                    UnknownTargetAction::Error => {
+                        return Err(format!("unknown cycle target '{}'", name));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    #[test]
+    fn schema() {
+        let schema = TargetSchema::new()
+            .with_target(
+                "v",
+                TargetDefinition::new(TargetKind::Number)
+                    .with_default(1.0)
+                    .with_range(0.0..=1.0),
+            )
+            .with_target(
+                "p",
+                TargetDefinition::new(TargetKind::Number).with_range(-1.0..=1.0),
+            )
+            .with_target("cut", TargetDefinition::new(TargetKind::Integer))
+            .with_unknown_targets(UnknownTargetAction::Warn);
+        let mut warnings = WarningCollector::new();
+        let target = |name: &str| CycleTarget::Name(Rc::from(name));
+        let apply = |warnings: &mut WarningCollector, name: &str| {
+            let mut note_events = vec![new_note(Note::C4)];
+            schema
+                .apply(warnings, &target(name), &mut note_events)
+                .map(|_| note_events.remove(0).unwrap())
+        };
+
+        assert_eq!(apply(&mut warnings, "v=0.5").unwrap().volume, 0.5);
+        assert_eq!(apply(&mut warnings, "v=2").unwrap().volume, 1.0);
+        assert_eq!(apply(&mut warnings, "p=-0.5").unwrap().panning, -0.5);
+        assert!(apply(&mut warnings, "p").is_err());
+        assert_eq!(
+            apply(&mut warnings, "cut=2.4").unwrap().extra,
+            Some(EventData::from([(
+                "cut".to_string(),
+                EventDataValue::Integer(2)
+            )]))
+        );
+        assert_eq!(
+            apply(&mut warnings, "wurst=1").unwrap(),
+            NoteEvent::from(Note::C4)
+        );
+        assert_eq!(
+            apply(&mut warnings, "choke1").unwrap(),
+            NoteEvent::from(Note::C4)
+        );
+        assert_eq!(
+            warnings.take(),
+            vec![
+                crate::Warning::new(
+                    WarningKind::Clamped,
+                    "cycle target 'v' value 2 got clamped to 1"
+                ),
+                crate::Warning::new(
+                    WarningKind::Rounded,
+                    "cycle target 'cut' value 2.4 got rounded to 2"
+                ),
+                crate::Warning::new(
+                    WarningKind::Unmapped,
+                    "unknown cycle target 'wurst' got ignored"
+                ),
+            ]
+        );
+
+        let schema = schema.with_unknown_targets(UnknownTargetAction::Error);
+        let mut note_events = vec![new_note(Note::C4)];
+        assert!(schema
+            .apply(&mut warnings, &target("wurst=1"), &mut note_events)
+            .is_err());
+    }
+}
@@ -0,0 +1,715 @@
+use std::fmt::Debug;
+
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    event::{Event, EventIterItem},
+    parameter::{RhythmParameter, RhythmParameterValues},
+    rhythm::{derived_seed, new_random_seed, seed_morph::SeedMorph},
+    Groove, PulseIterItem,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// The pulse of a rhythm which triggered the events of an [`EventTransform::process`] call.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EventTransformPulse {
+    /// The pattern's pulse.
+    pub pulse: PulseIterItem,
+    /// Start position of the pulse in the rhythm's steps, without groove and resolution changes.
+    pub position: f64,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single per-event transformation in an [`EventTransformStack`], such as [`VelocityJitter`],
+/// [`TimingJitter`] or [`NoteDropout`], or the built-in processing of a rhythm, like its
+/// [`EventHumanizer`](super::humanizer::EventHumanizer) or [`EventEcho`](super::echo::EventEcho).
+///
+/// Transforms get the stack's random number generator passed, so all random transforms of a
+/// rhythm share the rhythm's seed and produce reproducible output. Transforms with their own
+/// random number generator get seeded via [`set_seed`](Self::set_seed) instead.
+pub trait EventTransform: Debug {
+    /// Transform the given event in place.
+    fn apply(&mut self, event: &mut Event, rand_gen: &mut dyn RngCore);
+
+    /// Transform all events which got emitted for a single pulse of the rhythm. Transforms which
+    /// depend on the pulse, or which add events, such as echoes, override this. The default
+    /// implementation applies [`apply`](Self::apply) to all events.
+    fn process(
+        &mut self,
+        _pulse: &EventTransformPulse,
+        items: Option<Vec<EventIterItem>>,
+        rand_gen: &mut dyn RngCore,
+    ) -> Option<Vec<EventIterItem>> {
+        let mut items = items;
+        for item in items.iter_mut().flatten() {
+            self.apply(&mut item.event, rand_gen);
+        }
+        items
+    }
+
+    /// Returns false when the transform only applies to events of pulses, so it should not get
+    /// applied to injected events, see [`Rhythm::transform_event`](crate::Rhythm::transform_event).
+    /// The default implementation returns true.
+    fn applies_without_pulse(&self) -> bool {
+        true
+    }
+
+    /// Describe the transform's controls as parameters, which get added to the rhythm's
+    /// parameters. The default implementation has no parameters.
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        Vec::new()
+    }
+
+    /// Apply the actual values of the transform's parameters from the given store. The default
+    /// implementation ignores them.
+    fn apply_parameter_values(&mut self, _values: &RhythmParameterValues) {}
+
+    /// Set a new seed for the transform's own random number generator, if it has one. The
+    /// default implementation ignores it.
+    fn set_seed(&mut self, _seed: [u8; 32]) {}
+
+    /// Reset the transform's state, e.g. pending events. The default implementation does nothing.
+    fn reset(&mut self) {}
+
+    /// Estimated memory usage of pending events in bytes. The default implementation has none.
+    fn memory_usage(&self) -> usize {
+        0
+    }
+
+    /// Release unused memory of pending events. The default implementation does nothing.
+    fn trim_memory(&mut self) {}
+
+    /// Create a new cloned instance of this transform.
+    fn duplicate(&self) -> Box<dyn EventTransform>;
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Randomly shifts the volume of note-ons by up to the given amount.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VelocityJitter {
+    amount: f32,
+}
+
+impl VelocityJitter {
+    /// Create a new jitter which shifts volumes in range \[-amount, amount\].
+    pub fn new(amount: f32) -> Self {
+        let amount = valid_amount(amount);
+        Self { amount }
+    }
+}
+
+impl EventTransform for VelocityJitter {
+    fn apply(&mut self, event: &mut Event, rand_gen: &mut dyn RngCore) {
+        if let Event::NoteEvents(note_events) = event {
+            for note_event in note_events.iter_mut().flatten() {
+                if note_event.note.is_note_on() && self.amount > 0.0 {
+                    let jitter = rand_gen.gen_range(-1.0_f32..=1.0) * self.amount;
+                    note_event.volume = (note_event.volume + jitter).clamp(0.0, 1.0);
+                }
+            }
+        }
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(*self)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Randomly delays note-ons by up to the given amount of the event's duration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimingJitter {
+    amount: f32,
+}
+
+impl TimingJitter {
+    /// Create a new jitter which delays notes by up to the given amount in range \[0 - 1\].
+    pub fn new(amount: f32) -> Self {
+        let amount = valid_amount(amount).min(1.0);
+        Self { amount }
+    }
+}
+
+impl EventTransform for TimingJitter {
+    fn apply(&mut self, event: &mut Event, rand_gen: &mut dyn RngCore) {
+        if let Event::NoteEvents(note_events) = event {
+            for note_event in note_events.iter_mut().flatten() {
+                if note_event.note.is_note_on() && self.amount > 0.0 {
+                    let delay = rand_gen.gen_range(0.0_f32..=1.0) * self.amount;
+                    note_event.delay = (note_event.delay + delay).clamp(0.0, 1.0);
+                }
+            }
+        }
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(*self)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Randomly drops note-ons with the given probability. Note-offs are never dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteDropout {
+    probability: f32,
+}
+
+impl NoteDropout {
+    /// Create a new dropout which drops notes with the given probability in range \[0 - 1\].
+    pub fn new(probability: f32) -> Self {
+        let probability = valid_amount(probability).min(1.0);
+        Self { probability }
+    }
+}
+
+impl EventTransform for NoteDropout {
+    fn apply(&mut self, event: &mut Event, rand_gen: &mut dyn RngCore) {
+        if let Event::NoteEvents(note_events) = event {
+            for note_event in note_events.iter_mut() {
+                if note_event
+                    .as_ref()
+                    .is_some_and(|note_event| note_event.note.is_note_on())
+                    && rand_gen.gen::<f32>() < self.probability
+                {
+                    *note_event = None;
+                }
+            }
+        }
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(*self)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Scales the volume of note-ons with the value of the pulse that triggered them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PulseVolume;
+
+impl EventTransform for PulseVolume {
+    fn apply(&mut self, _event: &mut Event, _rand_gen: &mut dyn RngCore) {}
+
+    fn process(
+        &mut self,
+        pulse: &EventTransformPulse,
+        items: Option<Vec<EventIterItem>>,
+        _rand_gen: &mut dyn RngCore,
+    ) -> Option<Vec<EventIterItem>> {
+        let mut items = items;
+        let volume = pulse.pulse.value.max(0.0);
+        for item in items.iter_mut().flatten() {
+            scale_note_on_volumes(&mut item.event, volume);
+        }
+        items
+    }
+
+    fn applies_without_pulse(&self) -> bool {
+        false
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(*self)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Accents the volume of note-ons with the velocities of a [`Groove`] at the pulse's position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrooveVelocity {
+    groove: Groove,
+}
+
+impl GrooveVelocity {
+    /// Create a new transform which applies the given groove's velocities.
+    pub fn new(groove: Groove) -> Self {
+        Self { groove }
+    }
+}
+
+impl EventTransform for GrooveVelocity {
+    fn apply(&mut self, _event: &mut Event, _rand_gen: &mut dyn RngCore) {}
+
+    fn process(
+        &mut self,
+        pulse: &EventTransformPulse,
+        items: Option<Vec<EventIterItem>>,
+        _rand_gen: &mut dyn RngCore,
+    ) -> Option<Vec<EventIterItem>> {
+        let mut items = items;
+        let velocity = self.groove.velocity(pulse.position);
+        for item in items.iter_mut().flatten() {
+            scale_note_on_volumes(&mut item.event, velocity);
+        }
+        items
+    }
+
+    fn applies_without_pulse(&self) -> bool {
+        false
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(self.clone())
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Built-in transforms of a [`GenericRhythm`](crate::rhythm::generic::GenericRhythm) in an
+/// [`EventTransformStack`], in the order they get applied, before all pushed transforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum BuiltinTransform {
+    RelativeNotes,
+    PulseVolume,
+    GrooveVelocity,
+    Transposer,
+    Echo,
+    Humanizer,
+    Panner,
+    Quantizer,
+}
+
+impl BuiltinTransform {
+    /// Index of the built-in's sub-seed of the stack's seed. Indices are fixed per built-in, so
+    /// setting or removing other built-ins or pushed transforms doesn't change its output.
+    fn seed_index(self) -> u64 {
+        match self {
+            Self::RelativeNotes => 2,
+            Self::PulseVolume => 3,
+            Self::GrooveVelocity => 4,
+            Self::Transposer => 5,
+            Self::Echo => 6,
+            Self::Humanizer => 7,
+            Self::Panner => 8,
+            Self::Quantizer => 9,
+        }
+    }
+}
+
+/// Index of the sub-seed of the stack's seed for its seed morph.
+const SEED_MORPH_SEED_INDEX: u64 = 1;
+/// Index of the sub-seed of the stack's seed from which the seeds of pushed transforms derive.
+const PUSHED_TRANSFORMS_SEED_INDEX: u64 = 0;
+
+// -------------------------------------------------------------------------------------------------
+
+/// A chain of [`EventTransform`]S, which get applied in the order they got pushed to all
+/// emitted events of a rhythm. Built-in transforms of rhythms, such as their humanizer or
+/// echo, are part of the stack too: they get applied in a fixed order before all pushed ones.
+///
+/// All transforms share the stack's random number generator. When seeded, the stack
+/// generates the same output after each reset. An optional [`SeedMorph`] periodically reseeds
+/// the stack, see [`seed_for_step`](Self::seed_for_step).
+#[derive(Debug)]
+pub struct EventTransformStack {
+    builtins: Vec<(BuiltinTransform, Box<dyn EventTransform>)>,
+    transforms: Vec<Box<dyn EventTransform>>,
+    seed_morph: Option<SeedMorph>,
+    rand_gen: Xoshiro256PlusPlus,
+    seed: Option<[u8; 32]>,
+}
+
+impl Default for EventTransformStack {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Clone for EventTransformStack {
+    fn clone(&self) -> Self {
+        Self {
+            builtins: self
+                .builtins
+                .iter()
+                .map(|(builtin, transform)| (*builtin, transform.duplicate()))
+                .collect(),
+            transforms: self
+                .transforms
+                .iter()
+                .map(|transform| transform.duplicate())
+                .collect(),
+            seed_morph: self.seed_morph.clone(),
+            rand_gen: self.rand_gen.clone(),
+            seed: self.seed,
+        }
+    }
+}
+
+impl EventTransformStack {
+    /// Create a new, empty stack using the given optional seed for the random number generator.
+    pub fn new(seed: Option<[u8; 32]>) -> Self {
+        let rand_seed = seed.unwrap_or_else(new_random_seed);
+        let rand_gen = Xoshiro256PlusPlus::from_seed(rand_seed);
+        Self {
+            builtins: Vec::new(),
+            transforms: Vec::new(),
+            seed_morph: None,
+            rand_gen,
+            seed,
+        }
+    }
+
+    /// Return a new stack with the given transform pushed on top of the stack.
+    #[must_use]
+    pub fn with_transform<T: EventTransform + 'static>(self, transform: T) -> Self {
+        let mut stack = self;
+        stack.push(transform);
+        stack
+    }
+
+    /// Push a new transform on top of the stack: it gets applied after all existing ones.
+    pub fn push<T: EventTransform + 'static>(&mut self, transform: T) {
+        self.transforms.push(Box::new(transform));
+    }
+
+    /// Remove and return the topmost transform, if any.
+    pub fn pop(&mut self) -> Option<Box<dyn EventTransform>> {
+        self.transforms.pop()
+    }
+
+    /// Remove all pushed transforms. Built-in transforms are kept.
+    pub fn clear(&mut self) {
+        self.transforms.clear();
+    }
+
+    /// Number of pushed transforms in the stack.
+    pub fn len(&self) -> usize {
+        self.transforms.len()
+    }
+
+    /// Returns true when the stack has no pushed transforms.
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Set or remove the given built-in transform.
+    pub(crate) fn set_builtin(
+        &mut self,
+        builtin: BuiltinTransform,
+        transform: Option<Box<dyn EventTransform>>,
+    ) {
+        self.builtins.retain(|(other, _)| *other != builtin);
+        if let Some(transform) = transform {
+            let index = self.builtins.partition_point(|(other, _)| *other < builtin);
+            self.builtins.insert(index, (builtin, transform));
+        }
+    }
+
+    /// Set or remove the stack's seed morph, which periodically reseeds the stack.
+    pub(crate) fn set_seed_morph(&mut self, seed_morph: Option<SeedMorph>) {
+        self.seed_morph = seed_morph;
+    }
+
+    /// Parameters of all transforms and the seed morph.
+    pub fn parameters(&self) -> Vec<RhythmParameter> {
+        self.seed_morph
+            .iter()
+            .flat_map(|seed_morph| seed_morph.parameters())
+            .chain(self.iter().flat_map(|transform| transform.parameters()))
+            .collect()
+    }
+
+    /// Apply the actual values of all transforms' and the seed morph's parameters from the
+    /// given store.
+    pub fn apply_parameter_values(&mut self, values: &RhythmParameterValues) {
+        if let Some(seed_morph) = &mut self.seed_morph {
+            seed_morph.apply_parameter_values(values);
+        }
+        for transform in self.iter_mut() {
+            transform.apply_parameter_values(values);
+        }
+    }
+
+    /// Returns a new seed for the rhythm's random number generators, when the stack's seed morph
+    /// enters a new period at the given played step. All transforms and the stack's random
+    /// number generator then get reseeded with it. Without a seed morph, returns None.
+    pub fn seed_for_step(&mut self, step: f64) -> Option<[u8; 32]> {
+        let seed = self.seed_morph.as_mut()?.seed_for_step(step)?;
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        self.set_transform_seeds(seed);
+        Some(seed)
+    }
+
+    /// Set a new seed for the random number generator, the seed morph and all transforms,
+    /// which also gets used in following resets.
+    pub fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        if let Some(seed_morph) = &mut self.seed_morph {
+            seed_morph.set_seed(derived_seed(seed, SEED_MORPH_SEED_INDEX));
+        }
+        self.set_transform_seeds(seed);
+    }
+
+    /// Reset all transforms, the seed morph and the random number generator to its initial
+    /// seed, if any.
+    pub fn reset(&mut self) {
+        if let Some(seed) = self.seed {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        }
+        if let Some(seed_morph) = &mut self.seed_morph {
+            seed_morph.reset();
+        }
+        for transform in self.iter_mut() {
+            transform.reset();
+        }
+    }
+
+    /// Estimated memory usage of all transforms' pending events in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.iter().map(|transform| transform.memory_usage()).sum()
+    }
+
+    /// Release unused memory of all transforms.
+    pub fn trim_memory(&mut self) {
+        for transform in self.iter_mut() {
+            transform.trim_memory();
+        }
+    }
+
+    /// Apply all transforms, which apply without pulses, to the given event in place.
+    pub fn apply(&mut self, event: &mut Event) {
+        for transform in Self::transforms_mut(&mut self.builtins, &mut self.transforms) {
+            if transform.applies_without_pulse() {
+                transform.apply(event, &mut self.rand_gen);
+            }
+        }
+    }
+
+    /// Apply all transforms to the given events of a single pulse and return them.
+    pub fn process(
+        &mut self,
+        pulse: &EventTransformPulse,
+        items: Option<Vec<EventIterItem>>,
+    ) -> Option<Vec<EventIterItem>> {
+        let mut items = items;
+        for transform in Self::transforms_mut(&mut self.builtins, &mut self.transforms) {
+            items = transform.process(pulse, items, &mut self.rand_gen);
+        }
+        items
+    }
+
+    /// Seed built-ins by their kind and pushed transforms by their position in the stack.
+    fn set_transform_seeds(&mut self, seed: [u8; 32]) {
+        for (builtin, transform) in &mut self.builtins {
+            transform.set_seed(derived_seed(seed, builtin.seed_index()));
+        }
+        let pushed_seed = derived_seed(seed, PUSHED_TRANSFORMS_SEED_INDEX);
+        for (index, transform) in self.transforms.iter_mut().enumerate() {
+            transform.set_seed(derived_seed(pushed_seed, index as u64));
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &dyn EventTransform> {
+        self.builtins
+            .iter()
+            .map(|(_, transform)| Box::as_ref(transform))
+            .chain(self.transforms.iter().map(Box::as_ref))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn EventTransform>> {
+        Self::transforms_mut(&mut self.builtins, &mut self.transforms)
+    }
+
+    fn transforms_mut<'a>(
+        builtins: &'a mut [(BuiltinTransform, Box<dyn EventTransform>)],
+        transforms: &'a mut [Box<dyn EventTransform>],
+    ) -> impl Iterator<Item = &'a mut Box<dyn EventTransform>> {
+        builtins
+            .iter_mut()
+            .map(|(_, transform)| transform)
+            .chain(transforms.iter_mut())
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn scale_note_on_volumes(event: &mut Event, factor: f32) {
+    if let Event::NoteEvents(note_events) = event {
+        for note_event in note_events.iter_mut().flatten() {
+            if note_event.note.is_note_on() {
+                note_event.volume *= factor;
+            }
+        }
+    }
+}
+
+fn valid_amount(amount: f32) -> f32 {
+    if amount.is_finite() {
+        amount.max(0.0)
+    } else {
+        0.0
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        event::{
+            humanizer::EventHumanizer, new_note, transposer::EventTransposer, InstrumentId,
+            NoteEvent,
+        },
+        rhythm::seed_morph::SEED_MORPH_AMOUNT_PARAMETER,
+        Note,
+    };
+
+    fn chord() -> Event {
+        Event::NoteEvents(vec![
+            new_note((Note::C4, None::<InstrumentId>, 0.5)),
+            new_note((Note::E4, None::<InstrumentId>, 0.5)),
+            new_note((Note::G4, None::<InstrumentId>, 0.5)),
+            new_note(Note::OFF),
+        ])
+    }
+
+    fn notes(event: &Event) -> Vec<Option<NoteEvent>> {
+        match event {
+            Event::NoteEvents(notes) => notes.clone(),
+            _ => panic!("expected note events"),
+        }
+    }
+
+    #[test]
+    fn transforms() {
+        let seed = Some([2; 32]);
+        let mut stack = EventTransformStack::new(seed)
+            .with_transform(VelocityJitter::new(0.25))
+            .with_transform(TimingJitter::new(0.5));
+        assert_eq!(stack.len(), 2);
+
+        let mut event = chord();
+        stack.apply(&mut event);
+        let jittered = notes(&event);
+        assert!(jittered[..3]
+            .iter()
+            .flatten()
+            .all(|n| (0.25..=0.75).contains(&n.volume) && (0.0..=0.5).contains(&n.delay)));
+        let volumes = jittered[..3]
+            .iter()
+            .flatten()
+            .map(|n| n.volume)
+            .collect::<Vec<_>>();
+        assert!(volumes[0] != volumes[1]);
+        assert_eq!(jittered[3], new_note(Note::OFF));
+
+        // seeded stacks repeat after reset and in clones
+        stack.reset();
+        let mut clone = stack.clone();
+        let mut event = chord();
+        stack.apply(&mut event);
+        assert_eq!(notes(&event), jittered);
+        let mut event = chord();
+        clone.apply(&mut event);
+        assert_eq!(notes(&event), jittered);
+
+        // dropouts
+        stack.clear();
+        stack.push(NoteDropout::new(1.0));
+        let mut event = chord();
+        stack.apply(&mut event);
+        assert_eq!(notes(&event), vec![None, None, None, new_note(Note::OFF)]);
+        assert!(stack.pop().is_some());
+        assert!(stack.is_empty());
+        let mut event = chord();
+        stack.apply(&mut event);
+        assert_eq!(event, chord());
+    }
+
+    #[test]
+    fn builtins() {
+        let mut stack = EventTransformStack::new(None).with_transform(NoteDropout::new(0.0));
+        stack.set_builtin(
+            BuiltinTransform::Transposer,
+            Some(Box::new(EventTransposer::new().with_transpose(12))),
+        );
+        stack.set_builtin(BuiltinTransform::PulseVolume, Some(Box::new(PulseVolume)));
+        assert_eq!(stack.len(), 1);
+        assert!(!stack.parameters().is_empty());
+
+        // pulse related transforms only apply to pulses
+        let pulse = EventTransformPulse {
+            pulse: PulseIterItem {
+                value: 0.5,
+                step_time: 1.0,
+            },
+            position: 0.0,
+        };
+        let items = stack.process(&pulse, Some(vec![EventIterItem::new(chord())]));
+        let processed = notes(&items.unwrap()[0].event);
+        assert_eq!(
+            processed[0],
+            new_note((Note::C5, None::<InstrumentId>, 0.25))
+        );
+        assert_eq!(processed[3], new_note(Note::OFF));
+        let mut event = chord();
+        stack.apply(&mut event);
+        assert_eq!(
+            notes(&event)[0],
+            new_note((Note::C5, None::<InstrumentId>, 0.5))
+        );
+
+        // built-ins are kept when clearing the stack, and can be removed
+        stack.clear();
+        stack.set_builtin(BuiltinTransform::Transposer, None);
+        assert!(stack.parameters().is_empty());
+        let items = stack.process(&pulse, Some(vec![EventIterItem::new(chord())]));
+        let processed = notes(&items.unwrap()[0].event);
+        assert_eq!(
+            processed[0],
+            new_note((Note::C4, None::<InstrumentId>, 0.25))
+        );
+        let mut event = chord();
+        stack.apply(&mut event);
+        assert_eq!(event, chord());
+    }
+
+    #[test]
+    fn seeds() {
+        let humanizer = || {
+            EventHumanizer::new(None)
+                .with_volume_jitter(0.5)
+                .with_timing_jitter(0.5)
+        };
+        let mut stack = EventTransformStack::new(None);
+        stack.set_builtin(BuiltinTransform::Humanizer, Some(Box::new(humanizer())));
+        stack.set_seed([3; 32]);
+        let mut event = chord();
+        stack.apply(&mut event);
+
+        // built-ins keep their seeds when other transforms get added
+        let mut other_stack = EventTransformStack::new(None).with_transform(NoteDropout::new(0.0));
+        other_stack.set_builtin(
+            BuiltinTransform::Transposer,
+            Some(Box::new(EventTransposer::new())),
+        );
+        other_stack.set_builtin(BuiltinTransform::Humanizer, Some(Box::new(humanizer())));
+        other_stack.set_seed([3; 32]);
+        let mut other_event = chord();
+        other_stack.apply(&mut other_event);
+        assert_eq!(notes(&event), notes(&other_event));
+        assert_ne!(notes(&event), notes(&chord()));
+
+        // seed morphs reseed the stack and expose their parameters
+        stack.set_seed_morph(Some(SeedMorph::new(4.0, Some([1; 32]))));
+        assert!(stack
+            .parameters()
+            .iter()
+            .any(|parameter| parameter.id() == SEED_MORPH_AMOUNT_PARAMETER));
+        assert!(stack.seed_for_step(0.0).is_some());
+        assert!(stack.seed_for_step(1.0).is_none());
+        assert!(stack.seed_for_step(4.0).is_some());
+        stack.set_seed_morph(None);
+        assert!(stack.seed_for_step(8.0).is_none());
+    }
+}
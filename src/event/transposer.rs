@@ -0,0 +1,236 @@
+use rand::RngCore;
+
+use crate::{
+    event::{transform::EventTransform, Event, NoteEvent},
+    parameter::{RhythmParameter, RhythmParameterValues},
+    Note, Scale,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Parameter id of the transposer's pitch shift in semitones.
+pub const TRANSPOSE_PARAMETER: &str = "transpose";
+/// Parameter id of the transposer's pitch shift in scale degrees.
+pub const DEGREE_SHIFT_PARAMETER: &str = "degree_shift.degrees";
+
+/// Max allowed transposition in semitones.
+const MAX_TRANSPOSE: i32 = 48;
+/// Max allowed shift in scale degrees.
+const MAX_DEGREE_SHIFT: i32 = 14;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Shifts the pitch of emitted notes by semitones and/or by degrees of a [`Scale`], e.g. to
+/// move a whole rhythm up a fifth for a few bars without touching its patterns or emitters.
+///
+/// Degree shifts need a scale as musical context: notes are fit into the scale and then moved
+/// along the scale's notes, so the result stays in key. Without a scale, degree shifts are
+/// ignored. Semitone transpositions get applied after degree shifts.
+///
+/// All controls are exposed as [`RhythmParameter`]S, so they can be automated: see
+/// [`parameters`](Self::parameters).
+#[derive(Debug, Clone, Default)]
+pub struct EventTransposer {
+    transpose: i32,
+    degree_shift: i32,
+    scale: Option<Scale>,
+}
+
+impl EventTransposer {
+    /// Create a new transposer which does not transpose anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new transposer which shifts notes by the given amount of semitones.
+    #[must_use]
+    pub fn with_transpose(self, transpose: i32) -> Self {
+        let transpose = transpose.clamp(-MAX_TRANSPOSE, MAX_TRANSPOSE);
+        Self { transpose, ..self }
+    }
+
+    /// Return a new transposer which shifts notes by the given amount of scale degrees.
+    #[must_use]
+    pub fn with_degree_shift(self, degree_shift: i32) -> Self {
+        let degree_shift = degree_shift.clamp(-MAX_DEGREE_SHIFT, MAX_DEGREE_SHIFT);
+        Self {
+            degree_shift,
+            ..self
+        }
+    }
+
+    /// Return a new transposer which shifts scale degrees within the given scale.
+    #[must_use]
+    pub fn with_scale<S: Into<Option<Scale>>>(self, scale: S) -> Self {
+        let scale = scale.into();
+        Self { scale, ..self }
+    }
+
+    /// Pitch shift in semitones.
+    pub fn transpose(&self) -> i32 {
+        self.transpose
+    }
+
+    /// Pitch shift in scale degrees.
+    pub fn degree_shift(&self) -> i32 {
+        self.degree_shift
+    }
+
+    /// Scale of degree shifts, if any.
+    pub fn scale(&self) -> Option<&Scale> {
+        self.scale.as_ref()
+    }
+
+    /// Describe the transposer's controls as parameters, using the transposer's current
+    /// settings as default values. The degree shift parameter is only present with a scale.
+    pub fn parameters(&self) -> Vec<RhythmParameter> {
+        let mut parameters = vec![RhythmParameter::new(
+            TRANSPOSE_PARAMETER,
+            -MAX_TRANSPOSE as f64..=MAX_TRANSPOSE as f64,
+            self.transpose as f64,
+        )
+        .with_name("Transpose")
+        .with_integer(true)];
+        if self.scale.is_some() {
+            parameters.push(
+                RhythmParameter::new(
+                    DEGREE_SHIFT_PARAMETER,
+                    -MAX_DEGREE_SHIFT as f64..=MAX_DEGREE_SHIFT as f64,
+                    self.degree_shift as f64,
+                )
+                .with_name("Degree Shift")
+                .with_integer(true),
+            );
+        }
+        parameters
+    }
+
+    /// Apply the actual values of the transposer's parameters from the given store, if present.
+    pub fn apply_parameter_values(&mut self, values: &RhythmParameterValues) {
+        if let Some(transpose) = values.value(TRANSPOSE_PARAMETER) {
+            self.transpose = (transpose.round() as i32).clamp(-MAX_TRANSPOSE, MAX_TRANSPOSE);
+        }
+        if let Some(degree_shift) = values.value(DEGREE_SHIFT_PARAMETER) {
+            self.degree_shift =
+                (degree_shift.round() as i32).clamp(-MAX_DEGREE_SHIFT, MAX_DEGREE_SHIFT);
+        }
+    }
+
+    /// Transpose the given event in place. Parameter change events are not modified.
+    pub fn apply(&self, event: &mut Event) {
+        if self.transpose == 0 && (self.degree_shift == 0 || self.scale.is_none()) {
+            return;
+        }
+        if let Event::NoteEvents(note_events) = event {
+            for note_event in note_events.iter_mut().flatten() {
+                self.apply_note_event(note_event);
+            }
+        }
+    }
+
+    fn apply_note_event(&self, note_event: &mut NoteEvent) {
+        if !note_event.note.is_note_on() {
+            return;
+        }
+        if let Some(scale) = &self.scale {
+            if self.degree_shift != 0 {
+                note_event.note = Self::shift_degrees(scale, note_event.note, self.degree_shift);
+            }
+        }
+        if self.transpose != 0 {
+            note_event.note = note_event.note.transposed(self.transpose);
+        }
+    }
+
+    pub(crate) fn shift_degrees(scale: &Scale, note: Note, degrees: i32) -> Note {
+        let steps = scale.steps();
+        if steps.is_empty() {
+            return note;
+        }
+        // fit the note into the scale, then move along the scale's steps
+        let offset = scale.transpose(note, 0) as i32 - scale.key() as i32;
+        let octave = offset.div_euclid(12);
+        let step = offset.rem_euclid(12) as usize;
+        let step_index = steps.iter().position(|s| *s == step).unwrap_or(0) as i32;
+        let shifted_index = step_index + degrees;
+        let step_count = steps.len() as i32;
+        let shifted_octave = octave + shifted_index.div_euclid(step_count);
+        let shifted_step = steps[shifted_index.rem_euclid(step_count) as usize] as i32;
+        let shifted_note = scale.key() as i32 + shifted_octave * 12 + shifted_step;
+        Note::from(shifted_note.clamp(0, 0x7F) as u8)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl EventTransform for EventTransposer {
+    fn apply(&mut self, event: &mut Event, _rand_gen: &mut dyn RngCore) {
+        EventTransposer::apply(self, event);
+    }
+
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        EventTransposer::parameters(self)
+    }
+
+    fn apply_parameter_values(&mut self, values: &RhythmParameterValues) {
+        EventTransposer::apply_parameter_values(self, values);
+    }
+
+    fn duplicate(&self) -> Box<dyn EventTransform> {
+        Box::new(self.clone())
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::new_note;
+
+    fn transposed(transposer: &EventTransposer, note: Note) -> Note {
+        let mut event = Event::NoteEvents(vec![new_note(note)]);
+        transposer.apply(&mut event);
+        match event {
+            Event::NoteEvents(notes) => notes[0].as_ref().unwrap().note,
+            _ => panic!("expected note events"),
+        }
+    }
+
+    #[test]
+    fn transpose() {
+        let transposer = EventTransposer::new().with_transpose(7);
+        assert_eq!(transposed(&transposer, Note::C4), Note::G4);
+        assert_eq!(transposed(&transposer, Note::OFF), Note::OFF);
+        // degree shifts without scale are ignored
+        let transposer = EventTransposer::new().with_degree_shift(2);
+        assert_eq!(transposed(&transposer, Note::C4), Note::C4);
+        assert_eq!(transposer.parameters().len(), 1);
+    }
+
+    #[test]
+    fn degree_shift() {
+        let scale = Scale::try_from((Note::C4, "major")).unwrap();
+        let transposer = EventTransposer::new()
+            .with_scale(scale)
+            .with_degree_shift(2);
+        assert_eq!(transposed(&transposer, Note::C4), Note::E4);
+        assert_eq!(transposed(&transposer, Note::A4), Note::C5);
+        // out of scale notes get fit into the scale first
+        assert_eq!(transposed(&transposer, Note::Cs4), Note::E4);
+        let transposer = transposer.with_degree_shift(-1);
+        assert_eq!(transposed(&transposer, Note::C4), Note::B3);
+        // semitones apply after degrees
+        let transposer = transposer.with_transpose(12);
+        assert_eq!(transposed(&transposer, Note::C4), Note::B4);
+
+        // parameters
+        let values = RhythmParameterValues::new(transposer.parameters());
+        values.set_value(DEGREE_SHIFT_PARAMETER, 4.0).unwrap();
+        values.set_value(TRANSPOSE_PARAMETER, 0.0).unwrap();
+        let mut transposer = transposer;
+        transposer.apply_parameter_values(&values);
+        assert_eq!(transposer.degree_shift(), 4);
+        assert_eq!(transposed(&transposer, Note::C4), Note::G4);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::event::NoteEvent;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Order in which notes of a chord get strummed by a [`VoiceSpread`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StrumDirection {
+    /// Strum from the lowest to the highest note.
+    Up,
+    /// Strum from the highest to the lowest note.
+    Down,
+    /// Strum from the middle notes outwards: notes with the same distance to the middle of
+    /// the chord get played together.
+    CenterOut,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Spreads notes of big chords in emitted note events, to avoid phase pileups of many
+/// simultaneously triggered notes.
+///
+/// Notes can be strummed, by delaying each note in the chord by a fixed amount, and can be
+/// distributed across octaves, by moving every second note of the chord one octave up.
+/// Note events with less than `min_voices` note-ons are left untouched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceSpread {
+    min_voices: usize,
+    strum_time: f32,
+    strum_direction: StrumDirection,
+    octave_spread: bool,
+}
+
+impl Default for VoiceSpread {
+    fn default() -> Self {
+        Self {
+            min_voices: 3,
+            strum_time: 0.0,
+            strum_direction: StrumDirection::Up,
+            octave_spread: false,
+        }
+    }
+}
+
+impl VoiceSpread {
+    /// Create a new voice spread which does not spread anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new voice spread which only applies to note events with at least the given
+    /// number of note-ons.
+    #[must_use]
+    pub fn with_min_voices(self, min_voices: usize) -> Self {
+        let min_voices = min_voices.max(1);
+        Self { min_voices, ..self }
+    }
+
+    /// Return a new voice spread which strums notes with the given delay per note in the given
+    /// direction. Delays are relative to the event's length, just like note delays.
+    #[must_use]
+    pub fn with_strum(self, strum_time: f32, strum_direction: StrumDirection) -> Self {
+        let strum_time = strum_time.clamp(0.0, 1.0);
+        Self {
+            strum_time,
+            strum_direction,
+            ..self
+        }
+    }
+
+    /// Return a new voice spread which distributes every second note one octave up.
+    #[must_use]
+    pub fn with_octave_spread(self, octave_spread: bool) -> Self {
+        Self {
+            octave_spread,
+            ..self
+        }
+    }
+
+    /// Spread the given note events in place.
+    pub fn apply(&self, note_events: &mut [Option<NoteEvent>]) {
+        // collect note-ons, sorted by pitch
+        let mut voices = note_events
+            .iter()
+            .enumerate()
+            .filter_map(|(index, note_event)| {
+                note_event
+                    .as_ref()
+                    .filter(|note_event| note_event.note.is_note_on())
+                    .map(|note_event| (index, note_event.note))
+            })
+            .collect::<Vec<_>>();
+        if voices.len() < self.min_voices {
+            return;
+        }
+        voices.sort_by_key(|(_, note)| *note as u8);
+        // spread over octaves
+        if self.octave_spread {
+            for (index, _) in voices.iter().skip(1).step_by(2) {
+                if let Some(note_event) = &mut note_events[*index] {
+                    note_event.note = note_event.note.transposed(12);
+                }
+            }
+            voices.sort_by_key(|(index, _)| {
+                note_events[*index]
+                    .as_ref()
+                    .map_or(0, |note_event| note_event.note as u8)
+            });
+        }
+        // strum
+        if self.strum_time > 0.0 {
+            let voice_count = voices.len();
+            for (position, (index, _)) in voices.iter().enumerate() {
+                let rank = match self.strum_direction {
+                    StrumDirection::Up => position,
+                    StrumDirection::Down => voice_count - 1 - position,
+                    StrumDirection::CenterOut => (2 * position).abs_diff(voice_count - 1) / 2,
+                };
+                if let Some(note_event) = &mut note_events[*index] {
+                    note_event.delay =
+                        (note_event.delay + rank as f32 * self.strum_time).clamp(0.0, 1.0);
+                }
+            }
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    #[test]
+    fn strum() {
+        let spread = VoiceSpread::new().with_strum(0.1, StrumDirection::Down);
+
+        let mut note_events = vec![new_note(Note::C4), new_note(Note::E4)];
+        spread.apply(&mut note_events);
+        assert_eq!(note_events, vec![new_note(Note::C4), new_note(Note::E4)]);
+
+        let mut note_events = vec![
+            new_note(Note::E4),
+            None,
+            new_note(Note::C4),
+            new_note(Note::G4),
+        ];
+        spread.apply(&mut note_events);
+        let delays = note_events
+            .iter()
+            .map(|n| n.as_ref().map(|n| n.delay))
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![Some(0.1), None, Some(0.2), Some(0.0)]);
+
+        let spread = VoiceSpread::new().with_strum(0.1, StrumDirection::CenterOut);
+        let mut note_events = vec![
+            new_note(Note::C4),
+            new_note(Note::E4),
+            new_note(Note::G4),
+            new_note(Note::B4),
+            new_note(Note::D5),
+        ];
+        spread.apply(&mut note_events);
+        let delays = note_events
+            .iter()
+            .map(|n| n.as_ref().map(|n| n.delay))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            vec![Some(0.2), Some(0.1), Some(0.0), Some(0.1), Some(0.2)]
+        );
+    }
+
+    #[test]
+    fn octave_spread() {
+        let spread = VoiceSpread::new()
+            .with_min_voices(4)
+            .with_octave_spread(true)
+            .with_strum(0.25, StrumDirection::Up);
+
+        let mut note_events = vec![
+            new_note(Note::C4),
+            new_note(Note::E4),
+            new_note(Note::G4),
+            new_note(Note::B4),
+        ];
+        spread.apply(&mut note_events);
+        let notes = note_events
+            .iter()
+            .map(|n| n.as_ref().map(|n| (n.note, n.delay)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            notes,
+            vec![
+                Some((Note::C4, 0.0)),
+                Some((Note::E5, 0.5)),
+                Some((Note::G4, 0.25)),
+                Some((Note::B5, 0.75))
+            ]
+        );
+    }
+}
@@ -2,13 +2,19 @@
 
 use std::{borrow::Cow, fmt::Debug};
 
-use crate::{BeatTimeBase, PulseIterItem};
+use crate::{
+    memory::MemoryUsage, parameter::RhythmParameterValues, shared::SharedValues, BeatTimeBase,
+    PulseIterItem, Warning,
+};
 
 // -------------------------------------------------------------------------------------------------
 
+pub mod curve;
 pub mod probability;
+pub mod sampling;
 #[cfg(feature = "scripting")]
 pub mod scripted;
+pub mod threshold;
 
 // -------------------------------------------------------------------------------------------------
 
@@ -21,14 +27,56 @@ pub trait Gate: Debug {
     /// Set optional, application specific external context data for the pattern.
     fn set_external_context(&mut self, data: &[(Cow<str>, f64)]);
 
+    /// Set the shared values of the sequence the gate plays in. Only used by scripted
+    /// gates: the default implementation ignores the values.
+    fn set_shared_values(&mut self, _values: &SharedValues) {}
+
+    /// Set the parameter values of the rhythm the gate plays in. Only used by scripted
+    /// gates: the default implementation ignores the values.
+    fn set_parameter_values(&mut self, _values: &RhythmParameterValues) {}
+
+    /// Set a new seed for the gate's random number generator. The seed is also used in all
+    /// following resets. The default implementation ignores the seed, which is fine for
+    /// gates which don't use random numbers.
+    fn set_seed(&mut self, _seed: [u8; 32]) {}
+
     /// Returns true if the event should be triggered, else false.
     fn run(&mut self, pulse: &PulseIterItem) -> bool;
 
+    /// Fetch and clear all non-fatal issues, which got detected while running the gate.
+    /// The default implementation reports no warnings.
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        Vec::new()
+    }
+
+    /// Fetch and clear all errors of scripted callbacks, which failed while running the gate.
+    /// The default implementation reports no errors.
+    fn take_script_errors(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Estimated memory usage of the gate. Only scripted gates report their Lua heap size:
+    /// the default implementation reports no memory usage.
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::default()
+    }
+
+    /// Release unused memory, e.g. the garbage of a scripted gate's Lua engine. The default
+    /// implementation does nothing.
+    fn trim_memory(&mut self) {}
+
     /// Create a new cloned instance of this gate. This actualy is a clone(), wrapped into
     /// a `Box<dyn Gate>`, but called 'duplicate' to avoid conflicts with possible
     /// Clone impls.
     fn duplicate(&self) -> Box<dyn Gate>;
 
+    /// Create a new instance of this gate for rhythm duplicates, which doesn't share any state
+    /// with this gate, unlike `duplicate`. Fails when the state can't be copied, e.g. for
+    /// scripted coroutines. The default implementation returns a `duplicate`.
+    fn try_duplicate(&self) -> Result<Box<dyn Gate>, String> {
+        Ok(self.duplicate())
+    }
+
     /// Resets the gate's internal state.
     fn reset(&mut self);
 }
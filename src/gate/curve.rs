@@ -0,0 +1,205 @@
+use std::borrow::Cow;
+
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use super::sampling::{GateSampler, GateSampling};
+use crate::{rhythm::new_random_seed, BeatTimeBase, Gate, PulseIterItem};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Probability gate implementation, which scales pulse values with a probability curve over a
+/// fixed number of pattern steps, e.g. to increase the chance of extra hits in the last bar of
+/// a four bar phrase.
+///
+/// The curve is defined by (position, probability) points, where positions are relative to the
+/// curve's length in range \[0 - 1\]. Probabilities in between points are linearly interpolated.
+/// The curve repeats after `length` steps. See [`GateSampling`] for ways to spread random
+/// triggers evenly.
+#[derive(Debug, Clone)]
+pub struct ProbabilityCurveGate {
+    points: Vec<(f64, f32)>,
+    length: f64,
+    position: f64,
+    sampler: GateSampler,
+    rand_gen: Xoshiro256PlusPlus,
+    seed: Option<[u8; 32]>,
+}
+
+impl ProbabilityCurveGate {
+    /// Try creating a new gate from the given curve points and curve length in steps.
+    ///
+    /// Returns error when the given points or length are invalid.
+    pub fn new(points: &[(f64, f32)], length: f64, seed: Option<[u8; 32]>) -> Result<Self, String> {
+        if points.is_empty() {
+            return Err("probability curve points must not be empty".to_string());
+        }
+        if !(length > 0.0 && length.is_finite()) {
+            return Err(format!(
+                "probability curve length must be > 0 but is '{}'",
+                length
+            ));
+        }
+        for (position, probability) in points {
+            if !(0.0..=1.0).contains(position) {
+                return Err(format!(
+                    "probability curve positions must be in range [0 - 1] but are '{}'",
+                    position
+                ));
+            }
+            if !(0.0..=1.0).contains(probability) {
+                return Err(format!(
+                    "probability curve values must be in range [0 - 1] but are '{}'",
+                    probability
+                ));
+            }
+        }
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let position = 0.0;
+        let rand_seed = seed.unwrap_or_else(new_random_seed);
+        let rand_gen = Xoshiro256PlusPlus::from_seed(rand_seed);
+        let sampler = GateSampler::new(GateSampling::default());
+        Ok(Self {
+            points,
+            length,
+            position,
+            sampler,
+            rand_gen,
+            seed,
+        })
+    }
+
+    /// Curve's probability at the given relative position in range \[0 - 1\].
+    pub fn probability_at(&self, position: f64) -> f32 {
+        let next = self.points.partition_point(|(p, _)| *p <= position);
+        if next == 0 {
+            self.points[0].1
+        } else if next >= self.points.len() {
+            self.points[self.points.len() - 1].1
+        } else {
+            let (start_position, start_value) = self.points[next - 1];
+            let (end_position, end_value) = self.points[next];
+            let fraction = ((position - start_position) / (end_position - start_position)) as f32;
+            start_value + (end_value - start_value) * fraction
+        }
+    }
+
+    /// Return a new gate which draws random values with the given sampling mode.
+    #[must_use]
+    pub fn with_sampling(self, sampling: GateSampling) -> Self {
+        let mut sampler = self.sampler;
+        sampler.set_sampling(sampling);
+        Self { sampler, ..self }
+    }
+
+    /// The gate's random value sampling mode.
+    pub fn sampling(&self) -> GateSampling {
+        self.sampler.sampling()
+    }
+
+    /// Switch the gate's random value sampling mode at runtime.
+    pub fn set_sampling(&mut self, sampling: GateSampling) {
+        self.sampler.set_sampling(sampling);
+    }
+}
+
+impl Gate for ProbabilityCurveGate {
+    fn set_time_base(&mut self, _time_base: &BeatTimeBase) {
+        // nothing to do
+    }
+
+    fn set_external_context(&mut self, _data: &[(Cow<str>, f64)]) {
+        // nothing to do
+    }
+
+    fn run(&mut self, pulse: &PulseIterItem) -> bool {
+        let position = (self.position % self.length) / self.length;
+        self.position += pulse.step_time;
+        let probability = pulse.value.clamp(0.0, 1.0) * self.probability_at(position);
+        probability >= 1.0
+            || (probability > 0.0 && probability > self.sampler.next(&mut self.rand_gen))
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        self.sampler.reset();
+    }
+
+    fn duplicate(&self) -> Box<dyn Gate> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self) {
+        // rewind curve position
+        self.position = 0.0;
+        // restart sampling sequences
+        self.sampler.reset();
+        // reset random number generator to its initial state when the gate is seeded
+        if let Some(seed) = self.seed {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        }
+        // else create a new random number generator from a random seed
+        else {
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(new_random_seed());
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pulse(value: f32) -> PulseIterItem {
+        PulseIterItem {
+            value,
+            ..PulseIterItem::default()
+        }
+    }
+
+    #[test]
+    fn curve() {
+        assert!(ProbabilityCurveGate::new(&[], 4.0, None).is_err());
+        assert!(ProbabilityCurveGate::new(&[(0.0, 1.0)], 0.0, None).is_err());
+        assert!(ProbabilityCurveGate::new(&[(1.5, 1.0)], 4.0, None).is_err());
+        assert!(ProbabilityCurveGate::new(&[(0.0, 2.0)], 4.0, None).is_err());
+
+        let gate = ProbabilityCurveGate::new(&[(0.5, 1.0), (0.25, 0.5)], 4.0, None).unwrap();
+        // values before the first and after the last point are held
+        assert_eq!(gate.probability_at(0.0), 0.5);
+        assert_eq!(gate.probability_at(1.0), 1.0);
+        // values at and in between points
+        assert_eq!(gate.probability_at(0.25), 0.5);
+        assert_eq!(gate.probability_at(0.375), 0.75);
+        assert_eq!(gate.probability_at(0.5), 1.0);
+    }
+
+    #[test]
+    fn run() {
+        let points = [(0.0, 0.0), (0.5, 0.0), (0.75, 1.0), (1.0, 1.0)];
+        let mut gate = ProbabilityCurveGate::new(&points, 4.0, None).unwrap();
+        // probabilities of 0 never and of 1 always trigger, and the curve repeats
+        let triggers = (0..8).map(|_| gate.run(&pulse(1.0))).collect::<Vec<_>>();
+        assert_eq!(
+            triggers,
+            [false, false, false, true, false, false, false, true]
+        );
+        // pulse values scale the curve's probabilities
+        gate.reset();
+        let triggers = (0..4).map(|_| gate.run(&pulse(0.0))).collect::<Vec<_>>();
+        assert_eq!(triggers, [false, false, false, false]);
+
+        // seeded gates repeat after reset
+        let mut gate = ProbabilityCurveGate::new(&[(0.0, 0.5)], 4.0, Some([1; 32])).unwrap();
+        let triggers = (0..16).map(|_| gate.run(&pulse(1.0))).collect::<Vec<_>>();
+        assert!(triggers.contains(&true) && triggers.contains(&false));
+        gate.reset();
+        assert_eq!(
+            (0..16).map(|_| gate.run(&pulse(1.0))).collect::<Vec<_>>(),
+            triggers
+        );
+    }
+}
@@ -1,31 +1,144 @@
 use std::borrow::Cow;
 
-use rand::{thread_rng, Rng, SeedableRng};
+use rand::SeedableRng;
 use rand_xoshiro::Xoshiro256PlusPlus;
 
-use crate::{BeatTimeBase, Gate, PulseIterItem};
+use super::sampling::{GateSampler, GateSampling};
+use crate::{rhythm::new_random_seed, BeatTimeBase, Gate, PulseIterItem};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Probability weights for pulses at different metric positions within a bar, as used by the
+/// [`ProbabilityGate`] to thin out patterns musically: downbeats are more likely to trigger
+/// than beats, beats more likely than off-beats and off-beats more likely than other
+/// subdivisions.
+///
+/// Metric positions are derived from the time base's `beats_per_bar` and the number of pattern
+/// steps per beat, e.g. 4 for rhythms with a 1/16 unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricEmphasis {
+    beats_per_bar: u32,
+    steps_per_beat: f64,
+    weights: [f32; 4],
+}
+
+impl MetricEmphasis {
+    /// Try creating a new emphasis with default weights for the given time base and
+    /// number of pattern steps per beat.
+    ///
+    /// Returns error when the steps per beat are invalid.
+    pub fn new(time_base: &BeatTimeBase, steps_per_beat: f64) -> Result<Self, String> {
+        if !(steps_per_beat > 0.0 && steps_per_beat.is_finite()) {
+            return Err(format!(
+                "metric emphasis steps per beat must be > 0 but are '{}'",
+                steps_per_beat
+            ));
+        }
+        let beats_per_bar = time_base.beats_per_bar.max(1);
+        let weights = [1.0, 0.8, 0.6, 0.4];
+        Ok(Self {
+            beats_per_bar,
+            steps_per_beat,
+            weights,
+        })
+    }
+
+    /// Return a new emphasis with the given emphasis curve: probability weights for downbeats,
+    /// beats, off-beats and all other subdivisions in range \[0 - 1\].
+    #[must_use]
+    pub fn with_weights(self, downbeat: f32, beat: f32, offbeat: f32, subdivision: f32) -> Self {
+        let weights = [downbeat, beat, offbeat, subdivision].map(|w| w.clamp(0.0, 1.0));
+        Self { weights, ..self }
+    }
+
+    /// Probability weight of a pulse at the given position in pattern steps.
+    pub fn weight_at(&self, step_position: f64) -> f32 {
+        const EPSILON: f64 = 1e-6;
+        let is_on_grid = |value: f64| {
+            let fraction = value.fract();
+            fraction < EPSILON || fraction > 1.0 - EPSILON
+        };
+        let beat_position = step_position / self.steps_per_beat;
+        let bar_position = beat_position / self.beats_per_bar as f64;
+        if is_on_grid(bar_position) {
+            self.weights[0]
+        } else if is_on_grid(beat_position) {
+            self.weights[1]
+        } else if is_on_grid(beat_position * 2.0) {
+            self.weights[2]
+        } else {
+            self.weights[3]
+        }
+    }
+
+    fn set_time_base(&mut self, time_base: &BeatTimeBase) {
+        self.beats_per_bar = time_base.beats_per_bar.max(1);
+    }
+}
 
 // -------------------------------------------------------------------------------------------------
 
 /// Probability gate implementation. Returns false for 0 pulse values and true for values of 1.
 /// Values inbetween 0 and 1 do *maybe* trigger, using the pulse value as probability.
+///
+/// With a [`MetricEmphasis`], pulse values get scaled by the weight of the pulse's metric
+/// position in the bar first. See [`GateSampling`] for ways to spread random triggers evenly.
 #[derive(Debug, Clone)]
 pub struct ProbabilityGate {
+    emphasis: Option<MetricEmphasis>,
+    position: f64,
+    sampler: GateSampler,
     rand_gen: Xoshiro256PlusPlus,
     seed: Option<[u8; 32]>,
 }
 
 impl ProbabilityGate {
     pub fn new(seed: Option<[u8; 32]>) -> Self {
-        let rand_seed = seed.unwrap_or_else(|| thread_rng().gen());
+        let emphasis = None;
+        let position = 0.0;
+        let rand_seed = seed.unwrap_or_else(new_random_seed);
         let rand_gen = Xoshiro256PlusPlus::from_seed(rand_seed);
-        Self { rand_gen, seed }
+        let sampler = GateSampler::new(GateSampling::default());
+        Self {
+            emphasis,
+            position,
+            sampler,
+            rand_gen,
+            seed,
+        }
+    }
+
+    /// Return a new gate which weights pulse probabilities by their metric position.
+    #[must_use]
+    pub fn with_metric_emphasis<E: Into<Option<MetricEmphasis>>>(self, emphasis: E) -> Self {
+        let emphasis = emphasis.into();
+        Self { emphasis, ..self }
+    }
+
+    /// Return a new gate which draws random values with the given sampling mode.
+    #[must_use]
+    pub fn with_sampling(self, sampling: GateSampling) -> Self {
+        let mut sampler = self.sampler;
+        sampler.set_sampling(sampling);
+        Self { sampler, ..self }
+    }
+
+    /// The gate's random value sampling mode.
+    pub fn sampling(&self) -> GateSampling {
+        self.sampler.sampling()
+    }
+
+    /// Switch the gate's random value sampling mode at runtime.
+    pub fn set_sampling(&mut self, sampling: GateSampling) {
+        self.sampler.set_sampling(sampling);
     }
 }
 
 impl Gate for ProbabilityGate {
-    fn set_time_base(&mut self, _time_base: &BeatTimeBase) {
-        // nothing to do
+    fn set_time_base(&mut self, time_base: &BeatTimeBase) {
+        if let Some(emphasis) = &mut self.emphasis {
+            emphasis.set_time_base(time_base);
+        }
     }
 
     fn set_external_context(&mut self, _data: &[(Cow<str>, f64)]) {
@@ -33,7 +146,19 @@ impl Gate for ProbabilityGate {
     }
 
     fn run(&mut self, pulse: &PulseIterItem) -> bool {
-        pulse.value >= 1.0 || (pulse.value > 0.0 && pulse.value > self.rand_gen.gen_range(0.0..1.0))
+        let mut probability = pulse.value;
+        if let Some(emphasis) = &self.emphasis {
+            probability = probability.clamp(0.0, 1.0) * emphasis.weight_at(self.position);
+            self.position += pulse.step_time;
+        }
+        probability >= 1.0
+            || (probability > 0.0 && probability > self.sampler.next(&mut self.rand_gen))
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = Some(seed);
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        self.sampler.reset();
     }
 
     fn duplicate(&self) -> Box<dyn Gate> {
@@ -41,13 +166,86 @@ impl Gate for ProbabilityGate {
     }
 
     fn reset(&mut self) {
+        // rewind metric position
+        self.position = 0.0;
+        // restart sampling sequences
+        self.sampler.reset();
         // reset random number generator to its initial state when the gate is seeded
         if let Some(seed) = self.seed {
             self.rand_gen = Xoshiro256PlusPlus::from_seed(seed);
         }
         // else create a new random number generator from a random seed
         else {
-            self.rand_gen = Xoshiro256PlusPlus::from_seed(thread_rng().gen());
+            self.rand_gen = Xoshiro256PlusPlus::from_seed(new_random_seed());
         }
     }
 }
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn metric_emphasis() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 3,
+            samples_per_sec: 44100,
+        };
+        assert!(MetricEmphasis::new(&time_base, 0.0).is_err());
+
+        let emphasis = MetricEmphasis::new(&time_base, 4.0)?.with_weights(1.0, 0.5, 0.25, 0.0);
+        let weights = (0..13)
+            .map(|step| emphasis.weight_at(step as f64))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            weights,
+            vec![1.0, 0.0, 0.25, 0.0, 0.5, 0.0, 0.25, 0.0, 0.5, 0.0, 0.25, 0.0, 1.0]
+        );
+
+        // thin out a constant pulse train: always trigger downbeats, never subdivisions
+        let mut gate = ProbabilityGate::new(Some([2; 32])).with_metric_emphasis(emphasis);
+        let pulse = PulseIterItem {
+            value: 1.0,
+            step_time: 1.0,
+        };
+        let run_bars =
+            |gate: &mut ProbabilityGate| (0..120).map(|_| gate.run(&pulse)).collect::<Vec<_>>();
+        let triggers = run_bars(&mut gate);
+        assert!(triggers.iter().step_by(12).all(|t| *t));
+        assert!(triggers.iter().skip(1).step_by(2).all(|t| !*t));
+        let beats = triggers.iter().skip(4).step_by(4).filter(|t| **t).count();
+        let offbeats = triggers.iter().skip(2).step_by(4).filter(|t| **t).count();
+        assert!(beats > offbeats);
+
+        // seeded gates repeat after reset
+        gate.reset();
+        assert_eq!(run_bars(&mut gate), triggers);
+        Ok(())
+    }
+
+    #[test]
+    fn even_sampling() {
+        let pulse = PulseIterItem {
+            value: 0.5,
+            step_time: 1.0,
+        };
+        let mut gate =
+            ProbabilityGate::new(Some([3; 32])).with_sampling(GateSampling::ShuffledBag(4));
+        let triggers = (0..16).map(|_| gate.run(&pulse)).collect::<Vec<_>>();
+        assert!(triggers
+            .chunks(4)
+            .all(|chunk| chunk.iter().filter(|t| **t).count() == 2));
+        // seeded gates repeat after reset
+        gate.reset();
+        assert_eq!(
+            (0..16).map(|_| gate.run(&pulse)).collect::<Vec<_>>(),
+            triggers
+        );
+        // sampling can be switched at runtime
+        gate.set_sampling(GateSampling::WhiteNoise);
+        assert_eq!(gate.sampling(), GateSampling::WhiteNoise);
+    }
+}
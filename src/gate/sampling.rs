@@ -0,0 +1,149 @@
+use rand::{seq::SliceRandom, Rng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Default number of values in a [`GateSampling::ShuffledBag`].
+pub const DEFAULT_BAG_SIZE: usize = 8;
+
+// -------------------------------------------------------------------------------------------------
+
+/// How probability gates draw the random values which they compare pulse probabilities with.
+///
+/// White noise is truly random, so a "50%" probability may trigger several pulses in a row and
+/// then skip several pulses. The other modes spread triggers evenly over time, while still
+/// being random.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum GateSampling {
+    /// Independent, uniformly distributed random values.
+    #[default]
+    WhiteNoise,
+    /// A low-discrepancy golden ratio sequence with a random start value: consecutive values
+    /// are spread over the entire value range.
+    GoldenRatio,
+    /// Evenly spaced values, which get shuffled and drawn one by one until the bag is empty.
+    /// A constant probability then triggers exactly `probability * size` pulses per bag.
+    ShuffledBag(usize),
+}
+
+impl GateSampling {
+    /// Try converting a sampling mode name to a sampling mode: "white", "golden" or "shuffle".
+    /// Shuffled bags use the [`DEFAULT_BAG_SIZE`].
+    ///
+    /// Returns error when the name is invalid.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "white" => Ok(Self::WhiteNoise),
+            "golden" => Ok(Self::GoldenRatio),
+            "shuffle" => Ok(Self::ShuffledBag(DEFAULT_BAG_SIZE)),
+            _ => Err(format!(
+                "invalid gate sampling mode '{}': expected 'white', 'golden' or 'shuffle'",
+                name
+            )),
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Draws random values in range \[0 - 1) for probability gates with a [`GateSampling`] mode.
+#[derive(Debug, Clone)]
+pub(crate) struct GateSampler {
+    sampling: GateSampling,
+    golden_position: Option<f64>,
+    bag: Vec<f32>,
+}
+
+impl GateSampler {
+    pub fn new(sampling: GateSampling) -> Self {
+        let golden_position = None;
+        let bag = Vec::new();
+        Self {
+            sampling,
+            golden_position,
+            bag,
+        }
+    }
+
+    pub fn sampling(&self) -> GateSampling {
+        self.sampling
+    }
+
+    /// Switch to the given sampling mode. This restarts the sampling sequence.
+    pub fn set_sampling(&mut self, sampling: GateSampling) {
+        self.sampling = sampling;
+        self.reset();
+    }
+
+    /// Draw the next value, using the given random number generator.
+    pub fn next(&mut self, rand_gen: &mut Xoshiro256PlusPlus) -> f32 {
+        const GOLDEN_RATIO_CONJUGATE: f64 = 0.618_033_988_749_895;
+        match self.sampling {
+            GateSampling::WhiteNoise => rand_gen.gen_range(0.0..1.0),
+            GateSampling::GoldenRatio => {
+                let position = self.golden_position.map_or_else(
+                    || rand_gen.gen_range(0.0..1.0),
+                    |position| (position + GOLDEN_RATIO_CONJUGATE).fract(),
+                );
+                self.golden_position = Some(position);
+                position as f32
+            }
+            GateSampling::ShuffledBag(size) => {
+                if self.bag.is_empty() {
+                    let size = size.max(1);
+                    self.bag = (0..size)
+                        .map(|index| (index as f32 + 0.5) / size as f32)
+                        .collect();
+                    self.bag.shuffle(rand_gen);
+                }
+                self.bag.pop().unwrap_or_default()
+            }
+        }
+    }
+
+    /// Restart the sampling sequence.
+    pub fn reset(&mut self) {
+        self.golden_position = None;
+        self.bag.clear();
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn sampling() {
+        let mut rand_gen = Xoshiro256PlusPlus::from_seed([1; 32]);
+        let count_triggers = |sampler: &mut GateSampler, rand_gen: &mut Xoshiro256PlusPlus| {
+            (0..8).filter(|_| 0.5 > sampler.next(rand_gen)).count()
+        };
+        // bags trigger exactly half of the pulses in each bag
+        let mut sampler = GateSampler::new(GateSampling::ShuffledBag(8));
+        for _ in 0..4 {
+            assert_eq!(count_triggers(&mut sampler, &mut rand_gen), 4);
+        }
+        // golden ratio sequences never skip or trigger more than 3 pulses in a row
+        sampler.set_sampling(GateSampling::GoldenRatio);
+        let triggers = (0..64)
+            .map(|_| 0.5 > sampler.next(&mut rand_gen))
+            .collect::<Vec<_>>();
+        assert!(triggers
+            .windows(4)
+            .all(|window| window.iter().any(|t| *t) && window.iter().any(|t| !*t)));
+        assert!((0..64).all(|_| (0.0..1.0).contains(&sampler.next(&mut rand_gen))));
+
+        assert_eq!(
+            GateSampling::from_name("golden"),
+            Ok(GateSampling::GoldenRatio)
+        );
+        assert_eq!(
+            GateSampling::from_name("shuffle"),
+            Ok(GateSampling::ShuffledBag(DEFAULT_BAG_SIZE))
+        );
+        assert!(GateSampling::from_name("pink").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use std::borrow::Cow;
+
+use crate::{BeatTimeBase, Gate, PulseIterItem};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Threshold gate implementation: triggers all pulses with values above the gate's threshold.
+///
+/// Unlike the [`ProbabilityGate`](super::probability::ProbabilityGate), pulse values are never
+/// used as probabilities, so fractional pulse values can be passed on to emitters as velocity.
+/// See [`with_pulse_volume`](crate::rhythm::generic::GenericRhythm::with_pulse_volume).
+#[derive(Debug, Clone)]
+pub struct ThresholdGate {
+    threshold: f32,
+}
+
+impl ThresholdGate {
+    /// Create a new gate which triggers pulses with values above the given threshold in range
+    /// \[0 - 1\]. A threshold of 0 triggers all non zero pulses.
+    pub fn new(threshold: f32) -> Self {
+        let threshold = if threshold.is_finite() {
+            threshold.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        Self { threshold }
+    }
+
+    /// The gate's threshold.
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+}
+
+impl Default for ThresholdGate {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+impl Gate for ThresholdGate {
+    fn set_time_base(&mut self, _time_base: &BeatTimeBase) {
+        // nothing to do
+    }
+
+    fn set_external_context(&mut self, _data: &[(Cow<str>, f64)]) {
+        // nothing to do
+    }
+
+    fn run(&mut self, pulse: &PulseIterItem) -> bool {
+        pulse.value > self.threshold
+    }
+
+    fn duplicate(&self) -> Box<dyn Gate> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&mut self) {
+        // nothing to do
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn threshold() {
+        let pulses = [0.0, 0.1, 0.5, 1.0].map(|value| PulseIterItem {
+            value,
+            ..PulseIterItem::default()
+        });
+        let mut gate = ThresholdGate::default();
+        assert_eq!(
+            pulses.map(|pulse| gate.run(&pulse)),
+            [false, true, true, true]
+        );
+        let mut gate = ThresholdGate::new(0.5);
+        assert_eq!(
+            pulses.map(|pulse| gate.run(&pulse)),
+            [false, false, false, true]
+        );
+        assert_eq!(ThresholdGate::new(2.0).threshold(), 1.0);
+    }
+}
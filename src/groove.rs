@@ -0,0 +1,391 @@
+//! Microrhythm templates, which remap the time positions of pulses in a `Rhythm`.
+
+// -------------------------------------------------------------------------------------------------
+
+/// Max timing deviation of extracted groove positions from the grid in steps.
+const MAX_EXTRACTED_DEVIATION: f64 = 0.45;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Name and step positions of a known groove preset.
+struct GroovePreset {
+    name: &'static str,
+    positions: &'static [f64],
+    length: f64,
+}
+
+const GROOVE_PRESETS: [GroovePreset; 10] = [
+    GroovePreset {
+        name: "straight",
+        positions: &[0.0],
+        length: 1.0,
+    },
+    // 2:1 swing over two steps
+    GroovePreset {
+        name: "swing",
+        positions: &[0.0, 4.0 / 3.0],
+        length: 2.0,
+    },
+    // 3:2 swing over two steps
+    GroovePreset {
+        name: "quintuplet swing",
+        positions: &[0.0, 6.0 / 5.0],
+        length: 2.0,
+    },
+    // 4:3 swing over two steps
+    GroovePreset {
+        name: "septuplet swing",
+        positions: &[0.0, 8.0 / 7.0],
+        length: 2.0,
+    },
+    // 3 pulses spread as 3+3+2 over eight steps
+    GroovePreset {
+        name: "tresillo",
+        positions: &[0.0, 3.0, 6.0],
+        length: 8.0,
+    },
+    // 5 pulses spread as 2+1+2+1+2 over eight steps
+    GroovePreset {
+        name: "cinquillo",
+        positions: &[0.0, 2.0, 3.0, 5.0, 6.0],
+        length: 8.0,
+    },
+    // 3 pulses over two steps
+    GroovePreset {
+        name: "triplet",
+        positions: &[0.0, 2.0 / 3.0, 4.0 / 3.0],
+        length: 2.0,
+    },
+    // 5 pulses over four steps
+    GroovePreset {
+        name: "quintuplet",
+        positions: &[0.0, 4.0 / 5.0, 8.0 / 5.0, 12.0 / 5.0, 16.0 / 5.0],
+        length: 4.0,
+    },
+    // 7 pulses over four steps
+    GroovePreset {
+        name: "septuplet",
+        positions: &[
+            0.0,
+            4.0 / 7.0,
+            8.0 / 7.0,
+            12.0 / 7.0,
+            16.0 / 7.0,
+            20.0 / 7.0,
+            24.0 / 7.0,
+        ],
+        length: 4.0,
+    },
+    // 9 pulses over eight steps
+    GroovePreset {
+        name: "nonuplet",
+        positions: &[
+            0.0,
+            8.0 / 9.0,
+            16.0 / 9.0,
+            24.0 / 9.0,
+            32.0 / 9.0,
+            40.0 / 9.0,
+            48.0 / 9.0,
+            56.0 / 9.0,
+            64.0 / 9.0,
+        ],
+        length: 8.0,
+    },
+];
+
+// -------------------------------------------------------------------------------------------------
+
+/// A microrhythm template which remaps the time positions of pulses in a rhythm.
+///
+/// Each group of `positions.len()` pulses in the rhythm gets moved to the given step positions
+/// within `length` steps. Times in between pulses, e.g. pulses in subdivisions, are linearly
+/// interpolated. Optional velocities accent or soften the note volumes of each pulse in a group.
+///
+/// Grooves can be extracted from recorded events too, see [`Groove::extract`], so the feel of a
+/// played take can be transferred to other rhythms.
+///
+/// ### Example
+///
+/// ```rust
+/// use afseq::Groove;
+/// // 7 pulses over 4 steps: a septuplet over a beat in a 1/16 rhythm
+/// let groove = Groove::try_from("septuplet").unwrap();
+/// assert_eq!(groove.map(7.0), 4.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Groove {
+    positions: Vec<f64>,
+    length: f64,
+    velocities: Vec<f32>,
+}
+
+impl Default for Groove {
+    fn default() -> Self {
+        Self {
+            positions: vec![0.0],
+            length: 1.0,
+            velocities: Vec::new(),
+        }
+    }
+}
+
+impl TryFrom<&str> for Groove {
+    type Error = String;
+
+    /// Try creating a groove from a known preset name.
+    fn try_from(name: &str) -> Result<Self, String> {
+        if let Some(preset) = GROOVE_PRESETS
+            .iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(name))
+        {
+            Ok(Self {
+                positions: preset.positions.to_vec(),
+                length: preset.length,
+                velocities: Vec::new(),
+            })
+        } else {
+            Err(format!("unknown groove preset '{}'", name))
+        }
+    }
+}
+
+impl TryFrom<(&[f64], f64)> for Groove {
+    type Error = String;
+
+    /// Try creating a groove from custom, ascending step positions within the given length.
+    fn try_from((positions, length): (&[f64], f64)) -> Result<Self, String> {
+        if positions.is_empty() {
+            return Err("groove positions must not be empty".to_string());
+        }
+        if !(length > 0.0 && length.is_finite()) {
+            return Err(format!("groove length must be > 0 but is '{}'", length));
+        }
+        if positions[0] != 0.0 {
+            return Err("groove positions must start with 0".to_string());
+        }
+        if positions.windows(2).any(|w| w[1] <= w[0]) || positions[positions.len() - 1] >= length {
+            return Err(
+                "groove positions must be ascending and smaller than the groove length".to_string(),
+            );
+        }
+        Ok(Self {
+            positions: positions.to_vec(),
+            length,
+            velocities: Vec::new(),
+        })
+    }
+}
+
+impl Groove {
+    /// Derive a groove template from played notes, e.g. note-ons of a recorded take, with one
+    /// groove position per grid step within a group of `length` steps. Note times are given
+    /// in grid steps, relative to the start of the take, along with the note volumes.
+    ///
+    /// Each note is assigned to its nearest grid step. The groove positions are the average
+    /// timing deviations of all notes at each step within the group, and the velocities are
+    /// the average volumes at each step, relative to the average volume of all notes. Steps
+    /// without notes stay straight. Deviations of the group's first step get subtracted from
+    /// all steps, so the groove starts on the grid.
+    ///
+    /// Returns error when the length is 0 or there are no valid notes.
+    pub fn extract(notes: &[(f64, f32)], length: usize) -> Result<Self, String> {
+        if length == 0 {
+            return Err("groove length must be > 0".to_string());
+        }
+        let mut deviations = vec![0.0; length];
+        let mut volumes = vec![0.0; length];
+        let mut counts = vec![0_usize; length];
+        for (time, volume) in notes {
+            if !time.is_finite() || *time < 0.0 {
+                continue;
+            }
+            let step = time.round();
+            let position = step as usize % length;
+            deviations[position] += time - step;
+            volumes[position] += volume.max(0.0) as f64;
+            counts[position] += 1;
+        }
+        let note_count = counts.iter().sum::<usize>();
+        if note_count == 0 {
+            return Err("no notes to extract a groove from".to_string());
+        }
+        let deviation = |position: usize| {
+            if counts[position] > 0 {
+                (deviations[position] / counts[position] as f64)
+                    .clamp(-MAX_EXTRACTED_DEVIATION, MAX_EXTRACTED_DEVIATION)
+            } else {
+                0.0
+            }
+        };
+        let start = deviation(0);
+        let positions = (0..length)
+            .map(|position| position as f64 + deviation(position) - start)
+            .collect();
+        let average_volume = volumes.iter().sum::<f64>() / note_count as f64;
+        let velocities = (0..length)
+            .map(|position| {
+                if counts[position] > 0 && average_volume > 0.0 {
+                    (volumes[position] / counts[position] as f64 / average_volume) as f32
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        Ok(Self {
+            positions,
+            length: length as f64,
+            velocities,
+        })
+    }
+
+    /// Return a new groove which scales note volumes of the pulses in a group with the given
+    /// velocities. Missing velocities default to 1.0, surplus velocities are ignored. Pass an
+    /// empty vector to disable velocities.
+    #[must_use]
+    pub fn with_velocities(self, velocities: Vec<f32>) -> Self {
+        let mut velocities = velocities;
+        if !velocities.is_empty() {
+            velocities.resize(self.positions.len(), 1.0);
+            for velocity in &mut velocities {
+                *velocity = velocity.max(0.0);
+            }
+        }
+        Self { velocities, ..self }
+    }
+
+    /// Known groove preset names.
+    pub fn preset_names() -> Vec<&'static str> {
+        GROOVE_PRESETS.iter().map(|preset| preset.name).collect()
+    }
+
+    /// Step positions of the pulses in a single groove group.
+    pub fn positions(&self) -> &[f64] {
+        &self.positions
+    }
+
+    /// Length of a single groove group in steps.
+    pub fn length(&self) -> f64 {
+        self.length
+    }
+
+    /// Volume factors of the pulses in a single groove group. Empty when the groove does not
+    /// change volumes.
+    pub fn velocities(&self) -> &[f32] {
+        &self.velocities
+    }
+
+    /// Volume factor of the pulse at the given time in pulse steps.
+    pub fn velocity(&self, time: f64) -> f32 {
+        if self.velocities.is_empty() {
+            return 1.0;
+        }
+        let count = self.velocities.len() as i64;
+        let index = ((time + 1.0e-9).floor() as i64).rem_euclid(count);
+        self.velocities[index as usize]
+    }
+
+    /// Map the given time in pulse steps to a new time in steps.
+    pub fn map(&self, time: f64) -> f64 {
+        let count = self.positions.len() as f64;
+        let group = (time / count).floor();
+        let time_in_group = time - group * count;
+        let index = (time_in_group.floor() as usize).min(self.positions.len() - 1);
+        let fraction = time_in_group - index as f64;
+        let start = self.positions[index];
+        let end = self
+            .positions
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.length);
+        group * self.length + start + (end - start) * fraction
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn presets() {
+        for name in Groove::preset_names() {
+            let groove = Groove::try_from(name).unwrap();
+            assert_eq!(
+                Groove::try_from((groove.positions(), groove.length())),
+                Ok(groove)
+            );
+        }
+        assert!(Groove::try_from("wurst").is_err());
+        assert_eq!(Groove::try_from("Tresillo"), Groove::try_from("tresillo"));
+    }
+
+    #[test]
+    fn custom() {
+        let groove = |positions: &[f64], length: f64| Groove::try_from((positions, length));
+        assert!(groove(&[], 1.0).is_err());
+        assert!(groove(&[0.0], 0.0).is_err());
+        assert!(groove(&[0.5], 1.0).is_err());
+        assert!(groove(&[0.0, 0.0], 1.0).is_err());
+        assert!(groove(&[0.0, 2.0], 2.0).is_err());
+        assert!(groove(&[0.0, 1.5], 2.0).is_ok());
+    }
+
+    #[test]
+    fn map() {
+        let straight = Groove::default();
+        assert_eq!(straight.map(0.0), 0.0);
+        assert_eq!(straight.map(2.5), 2.5);
+
+        let tresillo = Groove::try_from("tresillo").unwrap();
+        assert_eq!(
+            (0..7).map(|t| tresillo.map(t as f64)).collect::<Vec<_>>(),
+            vec![0.0, 3.0, 6.0, 8.0, 11.0, 14.0, 16.0]
+        );
+        assert_eq!(tresillo.map(0.5), 1.5);
+        assert_eq!(tresillo.map(2.5), 7.0);
+
+        let swing = Groove::try_from((&[0.0, 1.5] as &[f64], 2.0)).unwrap();
+        assert_eq!(
+            (0..4).map(|t| swing.map(t as f64)).collect::<Vec<_>>(),
+            vec![0.0, 1.5, 2.0, 3.5]
+        );
+    }
+
+    #[test]
+    fn velocities() {
+        let groove = Groove::try_from("swing").unwrap();
+        assert_eq!(groove.velocity(1.0), 1.0);
+        let groove = groove.with_velocities(vec![1.5]);
+        assert_eq!(groove.velocities(), &[1.5, 1.0]);
+        assert_eq!(
+            (0..4)
+                .map(|t| groove.velocity(t as f64))
+                .collect::<Vec<_>>(),
+            vec![1.5, 1.0, 1.5, 1.0]
+        );
+        assert!(groove.with_velocities(vec![]).velocities().is_empty());
+    }
+
+    #[test]
+    fn extract() {
+        assert!(Groove::extract(&[(0.0, 1.0)], 0).is_err());
+        assert!(Groove::extract(&[], 2).is_err());
+
+        // swung and accented eighths
+        let groove =
+            Groove::extract(&[(0.0, 0.75), (1.25, 0.25), (2.0, 0.75), (3.25, 0.25)], 2).unwrap();
+        assert_eq!(groove.positions(), &[0.0, 1.25]);
+        assert_eq!(groove.length(), 2.0);
+        assert_eq!(groove.velocities(), &[1.5, 0.5]);
+        // steps without notes stay straight
+        let groove = Groove::extract(&[(0.0, 1.0), (2.25, 1.0)], 4).unwrap();
+        assert_eq!(groove.positions(), &[0.0, 1.0, 2.25, 3.0]);
+        assert_eq!(groove.velocities(), &[1.0, 1.0, 1.0, 1.0]);
+        // grooves start on the grid
+        let groove = Groove::extract(&[(0.25, 1.0), (1.25, 1.0)], 2).unwrap();
+        assert_eq!(groove.positions(), &[0.0, 1.0]);
+        assert!(Groove::try_from((groove.positions(), groove.length())).is_ok());
+    }
+}
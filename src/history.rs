@@ -0,0 +1,147 @@
+//! Ring buffers of recently emitted events, which hosts can inspect without consuming them.
+
+use std::collections::VecDeque;
+
+use crate::{event::Event, memory::event_memory_usage, phrase::RhythmIndex, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single recorded event of an [`EventHistory`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventHistoryItem {
+    /// Sample time at which the event got emitted.
+    pub time: SampleTime,
+    /// The emitted event.
+    pub event: Event,
+    /// Duration of the event in samples.
+    pub duration: SampleTime,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Memorizes the most recently emitted events of each rhythm slot in a ring buffer, e.g. for
+/// "repeat last bar" features or visuals which analyze the output of a sequence.
+///
+/// Only emitted events are recorded. Empty slot events, which continue previous events, are
+/// skipped. When a slot's buffer is full, its oldest events get dropped.
+#[derive(Clone, Debug)]
+pub struct EventHistory {
+    capacity: usize,
+    slots: Vec<VecDeque<EventHistoryItem>>,
+}
+
+impl EventHistory {
+    /// Create a new history which memorizes up to `capacity` events per rhythm slot.
+    pub fn new(capacity: usize) -> Self {
+        let slots = Vec::new();
+        Self { capacity, slots }
+    }
+
+    /// Maximum number of memorized events per rhythm slot.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of rhythm slots which emitted events so far.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Memorized events of the given rhythm slot, oldest first.
+    pub fn events(&self, rhythm_index: RhythmIndex) -> impl Iterator<Item = &EventHistoryItem> {
+        self.slots.get(rhythm_index).into_iter().flatten()
+    }
+
+    /// The most recently memorized event of the given rhythm slot, if any.
+    pub fn last_event(&self, rhythm_index: RhythmIndex) -> Option<&EventHistoryItem> {
+        self.slots.get(rhythm_index)?.back()
+    }
+
+    /// Memorized events of the given rhythm slot which got emitted at or after the given
+    /// sample time, oldest first. E.g. to fetch the events of the last bar.
+    pub fn events_since(
+        &self,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+    ) -> impl Iterator<Item = &EventHistoryItem> {
+        self.events(rhythm_index)
+            .skip_while(move |item| item.time < sample_time)
+    }
+
+    /// Forget all memorized events.
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            slot.clear();
+        }
+    }
+
+    /// Estimated memory usage of all memorized events in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|item| event_memory_usage(&item.event))
+            .sum()
+    }
+
+    /// Memorize an emitted event of the given rhythm slot.
+    pub(crate) fn push(
+        &mut self,
+        rhythm_index: RhythmIndex,
+        time: SampleTime,
+        event: &Option<Event>,
+        duration: SampleTime,
+    ) {
+        let Some(event) = event else {
+            return;
+        };
+        if self.capacity == 0 {
+            return;
+        }
+        if self.slots.len() <= rhythm_index {
+            self.slots.resize_with(rhythm_index + 1, VecDeque::new);
+        }
+        let slot = &mut self.slots[rhythm_index];
+        if slot.len() >= self.capacity {
+            slot.pop_front();
+        }
+        slot.push_back(EventHistoryItem {
+            time,
+            event: event.clone(),
+            duration,
+        });
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    #[test]
+    fn history() {
+        let mut history = EventHistory::new(2);
+        let event = Some(Event::NoteEvents(vec![new_note(Note::C4)]));
+        history.push(1, 0, &event, 10);
+        history.push(1, 10, &None, 10);
+        history.push(1, 20, &event, 10);
+        history.push(1, 30, &event, 10);
+        assert_eq!(history.slot_count(), 2);
+        assert_eq!(history.events(0).count(), 0);
+        assert_eq!(
+            history.events(1).map(|item| item.time).collect::<Vec<_>>(),
+            vec![20, 30]
+        );
+        assert_eq!(history.last_event(1).map(|item| item.time), Some(30));
+        assert_eq!(history.events_since(1, 25).count(), 1);
+        assert_eq!(history.events(5).count(), 0);
+        history.clear();
+        assert_eq!(history.last_event(1), None);
+
+        let mut history = EventHistory::new(0);
+        history.push(0, 0, &event, 10);
+        assert_eq!(history.events(0).count(), 0);
+    }
+}
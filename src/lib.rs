@@ -39,7 +39,7 @@ pub mod time;
 pub use time::{BeatTimeBase, SampleTime, SecondTimeBase, TimeBase};
 
 pub mod note;
-pub use note::Note;
+pub use note::{Note, NoteSpelling, OctaveNumbering, Tuning};
 
 pub mod chord;
 pub use chord::Chord;
@@ -47,6 +47,9 @@ pub use chord::Chord;
 pub mod scale;
 pub use scale::Scale;
 
+pub mod groove;
+pub use groove::Groove;
+
 pub mod event;
 pub use event::{Event, EventIter, EventIterItem};
 
@@ -59,21 +62,73 @@ pub use pulse::{Pulse, PulseIter, PulseIterItem};
 pub mod pattern;
 pub use pattern::Pattern;
 
+#[cfg(feature = "macros")]
+pub use afseq_macros::{emitter, pattern};
+// allow compiling the macros' `::afseq` paths in our own tests
+#[cfg(all(test, feature = "macros"))]
+extern crate self as afseq;
+
 pub mod gate;
 pub use gate::Gate;
 
 pub mod rhythm;
 pub use rhythm::{Rhythm, RhythmIter, RhythmIterItem};
 
+pub mod parameter;
+pub use parameter::{RhythmParameter, RhythmParameterValues};
+
 pub mod phrase;
 pub use phrase::Phrase;
 
+pub mod scene;
+pub use scene::Scene;
+
+pub mod shared;
+pub use shared::SharedValues;
+
+pub mod warning;
+pub use warning::Warning;
+
+pub mod memory;
+pub use memory::MemoryUsage;
+
 pub mod sequence;
-pub use sequence::Sequence;
+pub use sequence::{CuePoint, ExternalContextValues, Sequence};
+
+pub mod bounce;
+
+pub mod history;
+
+pub mod performance;
+
+pub mod diff;
+
+pub mod stats;
+
+pub mod piano_roll;
+
+pub mod polyrhythm;
+
+pub mod midi;
+
+pub mod tempo;
+
+pub mod notification;
+
+pub mod registry;
+
+#[cfg(feature = "profiling")]
+pub mod profiling;
 
 #[cfg(feature = "scripting")]
 pub mod bindings;
 
+#[cfg(feature = "capi")]
+pub mod capi;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 #[cfg(feature = "player")]
 pub mod player;
 
@@ -0,0 +1,130 @@
+//! Memory usage estimates of rhythms and sequences, e.g. to monitor long-running sessions.
+
+use std::{
+    fmt::Display,
+    mem::size_of,
+    ops::{Add, AddAssign},
+};
+
+use crate::{event::NoteEvent, Event};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Estimated memory usage of a rhythm, phrase or sequence component in bytes, as reported by
+/// [`Rhythm::memory_usage`](crate::Rhythm::memory_usage).
+///
+/// Estimates cover the allocations which may grow while playing, not the size of all static
+/// rhythm data. Use [`Rhythm::trim_memory`](crate::Rhythm::trim_memory) to release unused
+/// memory, e.g. between songs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Heap size of the Lua engines of scripted patterns, gates and emitters.
+    pub lua_heap: usize,
+    /// Parsed and cached mini-notation cycles.
+    pub cycles: usize,
+    /// Pending, buffered and pooled events.
+    pub events: usize,
+}
+
+impl MemoryUsage {
+    /// Create a new, empty memory usage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total estimated memory usage in bytes.
+    pub fn total(&self) -> usize {
+        self.lua_heap + self.cycles + self.events
+    }
+
+    /// Combine with the memory usage of a component which runs in the same Lua engine, e.g.
+    /// the pattern and emitter of a single rhythm: Lua heaps are not summed up then, but the
+    /// larger heap size is used.
+    #[must_use]
+    pub fn merge_shared(self, other: Self) -> Self {
+        Self {
+            lua_heap: self.lua_heap.max(other.lua_heap),
+            cycles: self.cycles + other.cycles,
+            events: self.events + other.events,
+        }
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            lua_heap: self.lua_heap + other.lua_heap,
+            cycles: self.cycles + other.cycles,
+            events: self.events + other.events,
+        }
+    }
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Display for MemoryUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "lua heap: {} bytes, cycles: {} bytes, events: {} bytes",
+            self.lua_heap, self.cycles, self.events
+        )
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Estimated size of the given event in bytes, including its note event allocations.
+pub(crate) fn event_memory_usage(event: &Event) -> usize {
+    match event {
+        Event::NoteEvents(note_events) => {
+            size_of::<Event>() + note_events.capacity() * size_of::<Option<NoteEvent>>()
+        }
+        Event::ParameterChangeEvent(_) => size_of::<Event>(),
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn memory_usage() {
+        let a = MemoryUsage {
+            lua_heap: 100,
+            cycles: 10,
+            events: 1,
+        };
+        let b = MemoryUsage {
+            lua_heap: 50,
+            cycles: 20,
+            events: 2,
+        };
+        assert_eq!(
+            a + b,
+            MemoryUsage {
+                lua_heap: 150,
+                cycles: 30,
+                events: 3
+            }
+        );
+        assert_eq!(
+            a.merge_shared(b),
+            MemoryUsage {
+                lua_heap: 100,
+                cycles: 30,
+                events: 3
+            }
+        );
+        assert_eq!(a.total(), 111);
+        assert_eq!(MemoryUsage::new().total(), 0);
+    }
+}
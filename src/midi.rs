@@ -0,0 +1,690 @@
+//! Import and export note patterns from and to Standard MIDI files.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    event::{
+        fixed::{FixedSequenceStep, ToFixedEventIterSequence},
+        new_note, InstrumentId, NoteEvent,
+    },
+    pattern::fixed::ToFixedPattern,
+    rhythm::beat_time::BeatTimeRhythm,
+    time::BeatTimeStep,
+    BeatTimeBase, Note, NoteSpelling, Scale,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single note in a MIDI track, with start time and length in MIDI ticks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiNote {
+    pub start: u64,
+    pub length: u64,
+    pub channel: u8,
+    pub note: Note,
+    pub velocity: u8,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// The key signature of a MIDI track, so other applications can spell note names as in the
+/// track's musical context, e.g. F# vs Gb. Gets written as key signature meta event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MidiKeySignature {
+    /// Number of sharps (positive) or flats (negative) in range \[-7 - 7\].
+    pub accidentals: i8,
+    /// True for minor keys, false for major keys.
+    pub minor: bool,
+}
+
+impl MidiKeySignature {
+    /// Key signature of the given scale, using the scale's accidental spelling, see
+    /// [`Scale::spelling`]. Scales with a minor third are minor keys, all others major keys.
+    pub fn from_scale(scale: &Scale) -> Self {
+        let steps = scale.steps();
+        let minor = steps.contains(&3) && !steps.contains(&4);
+        // minor keys use the key signature of their relative major key
+        let major_key = if minor {
+            (scale.key() + 3) % 12
+        } else {
+            scale.key()
+        };
+        let accidentals = match (major_key, scale.spelling()) {
+            (1, NoteSpelling::Sharps) => 7,
+            (1, NoteSpelling::Flats) => -5,
+            (6, NoteSpelling::Sharps) => 6,
+            (6, NoteSpelling::Flats) => -6,
+            (key, _) => [0, 7, 2, -3, 4, -1, 6, 1, -4, 3, -2, 5][key as usize],
+        };
+        Self { accidentals, minor }
+    }
+
+    /// Accidental spelling of the key signature.
+    pub fn spelling(&self) -> NoteSpelling {
+        if self.accidentals < 0 {
+            NoteSpelling::Flats
+        } else {
+            NoteSpelling::Sharps
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single track of a MIDI file with all its notes, sorted by start time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiTrack {
+    name: Option<String>,
+    notes: Vec<MidiNote>,
+    length: u64,
+    key_signature: Option<MidiKeySignature>,
+}
+
+impl MidiTrack {
+    /// Create a new track from the given notes, e.g. to export them into a MIDI file.
+    /// The track's length is the end time of the track's last note.
+    pub fn new(name: Option<String>, notes: Vec<MidiNote>) -> Self {
+        let mut notes = notes;
+        notes.sort_by_key(|note| (note.start, note.note as u8));
+        let length = notes
+            .iter()
+            .map(|note| note.start + note.length)
+            .max()
+            .unwrap_or(0);
+        let key_signature = None;
+        Self {
+            name,
+            notes,
+            length,
+            key_signature,
+        }
+    }
+
+    /// Return a new track with the given key signature. When None, the track has no key
+    /// signature.
+    #[must_use]
+    pub fn with_key_signature<K: Into<Option<MidiKeySignature>>>(self, key_signature: K) -> Self {
+        let key_signature = key_signature.into();
+        Self {
+            key_signature,
+            ..self
+        }
+    }
+
+    /// The track's name, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// All notes in the track, sorted by start time.
+    pub fn notes(&self) -> &Vec<MidiNote> {
+        &self.notes
+    }
+
+    /// Length of the track in ticks: the end time of the track's last event.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+
+    /// The track's key signature, if any.
+    pub fn key_signature(&self) -> Option<MidiKeySignature> {
+        self.key_signature
+    }
+
+    /// Convert the track to a beat time rhythm with a fixed pattern and emitter, quantizing
+    /// notes to the given grid. Velocities and note lengths are preserved.
+    ///
+    /// Notes which start at the same grid step form chords, which play as long as the chord's
+    /// longest note, or until the next note starts. The rhythm's pattern length gets rounded
+    /// up to full bars.
+    ///
+    /// Returns error when the grid step is invalid.
+    pub fn to_rhythm(
+        &self,
+        ticks_per_beat: u16,
+        time_base: BeatTimeBase,
+        grid: BeatTimeStep,
+    ) -> Result<BeatTimeRhythm, String> {
+        let beats_per_step = grid.to_samples(&time_base) / time_base.samples_per_beat();
+        if !(beats_per_step > 0.0 && beats_per_step.is_finite()) {
+            return Err(format!("invalid MIDI import grid step: '{:?}'", grid));
+        }
+        let ticks_per_step = ticks_per_beat as f64 * beats_per_step;
+        let quantize = |ticks: u64| (ticks as f64 / ticks_per_step).round() as usize;
+        // group notes by quantized start step
+        let mut chords = HashMap::<usize, (Vec<Option<NoteEvent>>, usize)>::new();
+        for note in &self.notes {
+            let start_step = quantize(note.start);
+            let length_steps = quantize(note.length).max(1);
+            let (note_events, length) = chords.entry(start_step).or_default();
+            note_events.push(new_note((
+                note.note,
+                None::<InstrumentId>,
+                note.velocity as f32 / 127.0,
+            )));
+            *length = (*length).max(length_steps);
+        }
+        // round up length to full bars
+        let steps_per_bar = (time_base.beats_per_bar as f64 / beats_per_step).round() as usize;
+        let mut step_count = quantize(self.length);
+        for (start, (_, length)) in &chords {
+            step_count = step_count.max(start + length);
+        }
+        if steps_per_bar > 0 {
+            step_count = step_count.div_ceil(steps_per_bar).max(1) * steps_per_bar;
+        }
+        step_count = step_count.max(1);
+        // create steps
+        let mut steps = Vec::with_capacity(step_count);
+        let mut playing_until = None;
+        for step in 0..step_count {
+            if let Some((note_events, length)) = chords.remove(&step) {
+                steps.push(FixedSequenceStep::Notes(note_events));
+                playing_until = Some(step + length);
+            } else if step == 0 || playing_until == Some(step) {
+                // stop playing notes, or start with silence
+                steps.push(FixedSequenceStep::Rest);
+            } else {
+                // continue playing notes or silence
+                steps.push(FixedSequenceStep::Hold);
+            }
+        }
+        let pattern = vec![1_u32; step_count].to_pattern();
+        Ok(BeatTimeRhythm::new(time_base, grid, None)
+            .with_pattern(pattern)
+            .trigger(steps.to_event_sequence()))
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A parsed Standard MIDI file (format 0 or 1), containing the notes of all tracks.
+///
+/// Tempo and time signature changes are ignored: notes are imported in beats, so they can be
+/// played with any time base.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiFile {
+    ticks_per_beat: u16,
+    tracks: Vec<MidiTrack>,
+}
+
+impl MidiFile {
+    /// Create a new MIDI file from the given tracks, e.g. to export them via [`Self::to_bytes`].
+    pub fn new(ticks_per_beat: u16, tracks: Vec<MidiTrack>) -> Self {
+        let ticks_per_beat = ticks_per_beat.clamp(1, 0x7FFF);
+        Self {
+            ticks_per_beat,
+            tracks,
+        }
+    }
+
+    /// Return a new MIDI file which applies the given key signature to all tracks, see
+    /// [`MidiTrack::with_key_signature`].
+    #[must_use]
+    pub fn with_key_signature<K: Into<Option<MidiKeySignature>>>(self, key_signature: K) -> Self {
+        let key_signature = key_signature.into();
+        let tracks = self
+            .tracks
+            .into_iter()
+            .map(|track| track.with_key_signature(key_signature))
+            .collect();
+        Self { tracks, ..self }
+    }
+
+    /// Try reading a MIDI file from the given file path.
+    ///
+    /// Returns error when the file can't be read or is not a valid MIDI file.
+    pub fn from_file(file_path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(file_path)
+            .map_err(|err| format!("failed to read MIDI file '{}': {}", file_path, err))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Try parsing a MIDI file from the given raw file content.
+    ///
+    /// Returns error when the content is not a valid MIDI file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = MidiReader::new(bytes);
+        // header
+        let (chunk_type, header) = reader.read_chunk()?;
+        if chunk_type != *b"MThd" || header.len() < 6 {
+            return Err("invalid MIDI file: missing header chunk".to_string());
+        }
+        let division = u16::from_be_bytes([header[4], header[5]]);
+        if division & 0x8000 != 0 {
+            return Err(
+                "unsupported MIDI file: SMPTE time divisions are not supported".to_string(),
+            );
+        }
+        let ticks_per_beat = division.max(1);
+        // tracks
+        let mut tracks = Vec::new();
+        while !reader.is_empty() {
+            let (chunk_type, data) = reader.read_chunk()?;
+            if chunk_type == *b"MTrk" {
+                tracks.push(Self::parse_track(data)?);
+            }
+            // else skip unknown chunks
+        }
+        Ok(Self {
+            ticks_per_beat,
+            tracks,
+        })
+    }
+
+    /// Try writing the MIDI file to the given file path.
+    ///
+    /// Returns error when the file can't be written.
+    pub fn write_to_file(&self, file_path: &str) -> Result<(), String> {
+        std::fs::write(file_path, self.to_bytes())
+            .map_err(|err| format!("failed to write MIDI file '{}': {}", file_path, err))
+    }
+
+    /// Serialize the MIDI file into a format 1 Standard MIDI file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend(6_u32.to_be_bytes());
+        bytes.extend(1_u16.to_be_bytes());
+        bytes.extend((self.tracks.len().min(u16::MAX as usize) as u16).to_be_bytes());
+        bytes.extend(self.ticks_per_beat.to_be_bytes());
+        for track in self.tracks.iter().take(u16::MAX as usize) {
+            let data = Self::write_track(track);
+            bytes.extend(b"MTrk");
+            bytes.extend((data.len() as u32).to_be_bytes());
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    /// Number of MIDI ticks per quarter note beat.
+    pub fn ticks_per_beat(&self) -> u16 {
+        self.ticks_per_beat
+    }
+
+    /// All tracks of the file.
+    pub fn tracks(&self) -> &Vec<MidiTrack> {
+        &self.tracks
+    }
+
+    /// Convert all tracks which contain notes to beat time rhythms, quantizing notes to the
+    /// given grid. See [`MidiTrack::to_rhythm`].
+    ///
+    /// Returns error when the grid step is invalid.
+    pub fn to_rhythms(
+        &self,
+        time_base: BeatTimeBase,
+        grid: BeatTimeStep,
+    ) -> Result<Vec<BeatTimeRhythm>, String> {
+        self.tracks
+            .iter()
+            .filter(|track| !track.notes.is_empty())
+            .map(|track| track.to_rhythm(self.ticks_per_beat, time_base, grid))
+            .collect()
+    }
+
+    fn write_track(track: &MidiTrack) -> Vec<u8> {
+        let mut data = Vec::new();
+        if let Some(name) = &track.name {
+            data.extend([0x00, 0xFF, 0x03]);
+            write_var_len(&mut data, name.len() as u32);
+            data.extend(name.as_bytes());
+        }
+        if let Some(key_signature) = &track.key_signature {
+            let accidentals = key_signature.accidentals.clamp(-7, 7) as u8;
+            data.extend([0x00, 0xFF, 0x59, 0x02]);
+            data.extend([accidentals, key_signature.minor as u8]);
+        }
+        // collect note ons and offs, with note offs first at equal times
+        let mut events = Vec::with_capacity(track.notes.len() * 2);
+        for note in &track.notes {
+            let channel = note.channel & 0x0F;
+            let key = note.note as u8 & 0x7F;
+            let velocity = note.velocity.clamp(1, 0x7F);
+            events.push((note.start, 1, [0x90 | channel, key, velocity]));
+            events.push((note.start + note.length, 0, [0x80 | channel, key, 0]));
+        }
+        events.sort_by_key(|(time, order, _)| (*time, *order));
+        let mut time = 0;
+        for (event_time, _, message) in events {
+            write_var_len(&mut data, (event_time - time) as u32);
+            data.extend(message);
+            time = event_time;
+        }
+        // end of track
+        write_var_len(&mut data, track.length.saturating_sub(time) as u32);
+        data.extend([0xFF, 0x2F, 0x00]);
+        data
+    }
+
+    fn parse_track(data: &[u8]) -> Result<MidiTrack, String> {
+        let mut reader = MidiReader::new(data);
+        let mut name = None;
+        let mut key_signature = None;
+        let mut notes = Vec::new();
+        let mut playing_notes = HashMap::<(u8, u8), VecDeque<(u64, u8)>>::new();
+        let mut time = 0u64;
+        let mut running_status = None;
+        while !reader.is_empty() {
+            time += reader.read_var_len()? as u64;
+            let mut status = reader.read_u8()?;
+            let first_data_byte = if status < 0x80 {
+                // running status: the status byte is the first data byte
+                let data_byte = status;
+                status = running_status.ok_or("invalid MIDI track: missing status byte")?;
+                Some(data_byte)
+            } else {
+                None
+            };
+            match status {
+                0x80..=0xEF => {
+                    running_status = Some(status);
+                    let data_len = if matches!(status & 0xF0, 0xC0 | 0xD0) {
+                        1
+                    } else {
+                        2
+                    };
+                    let data1 = match first_data_byte {
+                        Some(data_byte) => data_byte,
+                        None => reader.read_u8()?,
+                    };
+                    let data2 = if data_len == 2 { reader.read_u8()? } else { 0 };
+                    let channel = status & 0x0F;
+                    match status & 0xF0 {
+                        0x90 if data2 > 0 => {
+                            playing_notes
+                                .entry((channel, data1))
+                                .or_default()
+                                .push_back((time, data2));
+                        }
+                        0x80 | 0x90 => {
+                            let note_on = playing_notes
+                                .get_mut(&(channel, data1))
+                                .and_then(VecDeque::pop_front);
+                            if let Some((start, velocity)) = note_on {
+                                notes.push(MidiNote {
+                                    start,
+                                    length: time - start,
+                                    channel,
+                                    note: Note::from(data1 & 0x7F),
+                                    velocity,
+                                });
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                0xF0 | 0xF7 => {
+                    // sysex
+                    running_status = None;
+                    let len = reader.read_var_len()?;
+                    reader.read_bytes(len)?;
+                }
+                0xFF => {
+                    // meta event
+                    running_status = None;
+                    let meta_type = reader.read_u8()?;
+                    let len = reader.read_var_len()?;
+                    let meta_data = reader.read_bytes(len)?;
+                    match meta_type {
+                        0x03 => name = Some(String::from_utf8_lossy(meta_data).to_string()),
+                        0x59 if meta_data.len() == 2 => {
+                            key_signature = Some(MidiKeySignature {
+                                accidentals: (meta_data[0] as i8).clamp(-7, 7),
+                                minor: meta_data[1] == 1,
+                            });
+                        }
+                        0x2F => break,
+                        _ => (),
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid MIDI track: unexpected status byte {status}"
+                    ))
+                }
+            }
+        }
+        // end hanging notes at the end of the track
+        for ((channel, key), note_ons) in playing_notes {
+            for (start, velocity) in note_ons {
+                notes.push(MidiNote {
+                    start,
+                    length: time - start,
+                    channel,
+                    note: Note::from(key & 0x7F),
+                    velocity,
+                });
+            }
+        }
+        notes.sort_by_key(|note| (note.start, note.note as u8));
+        let length = time;
+        Ok(MidiTrack {
+            name,
+            notes,
+            length,
+            key_signature,
+        })
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Write a MIDI variable length value.
+fn write_var_len(data: &mut Vec<u8>, value: u32) {
+    let value = value.min(0x0FFF_FFFF);
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push(((rest & 0x7F) as u8) | 0x80);
+        rest >>= 7;
+    }
+    data.extend(bytes.iter().rev());
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Reads big endian values and chunks from raw MIDI file content.
+struct MidiReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> MidiReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.position + len > self.bytes.len() {
+            return Err("invalid MIDI file: unexpected end of data".to_string());
+        }
+        let bytes = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_var_len(&mut self) -> Result<usize, String> {
+        let mut value = 0usize;
+        for _ in 0..4 {
+            let byte = self.read_u8()?;
+            value = (value << 7) | (byte & 0x7F) as usize;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("invalid MIDI file: variable length value is too long".to_string())
+    }
+
+    fn read_chunk(&mut self) -> Result<([u8; 4], &'a [u8]), String> {
+        let chunk_type = self.read_bytes(4)?;
+        let len = self.read_bytes(4)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let data = self.read_bytes(len)?;
+        Ok((
+            [chunk_type[0], chunk_type[1], chunk_type[2], chunk_type[3]],
+            data,
+        ))
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Event;
+
+    fn midi_file_bytes(tracks: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend([0, 0, 0, 6, 0, 1, 0, tracks.len() as u8, 0x01, 0xE0]);
+        for track in tracks {
+            bytes.extend(b"MTrk");
+            bytes.extend((track.len() as u32).to_be_bytes());
+            bytes.extend(*track);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse() -> Result<(), String> {
+        assert!(MidiFile::from_bytes(&[]).is_err());
+        assert!(MidiFile::from_bytes(b"MThd\0\0\0\x06\0\0").is_err());
+
+        let track: &[u8] = &[
+            0x00, 0xFF, 0x03, 0x04, b'l', b'e', b'a', b'd', // track name
+            0x00, 0x90, 48, 100, // C4 on
+            0x83, 0x60, 0x80, 48, 0, // C4 off after 480 ticks
+            0x83, 0x60, 0x90, 52, 64, // E4 on
+            0x81, 0x70, 52, 0, // E4 off after 240 ticks (running status)
+            0x00, 0xFF, 0x2F, 0x00, // end of track
+        ];
+        let midi_file = MidiFile::from_bytes(&midi_file_bytes(&[track]))?;
+        assert_eq!(midi_file.ticks_per_beat(), 480);
+        assert_eq!(midi_file.tracks().len(), 1);
+        let track = &midi_file.tracks()[0];
+        assert_eq!(track.name(), Some("lead"));
+        assert_eq!(track.length(), 1200);
+        assert_eq!(
+            track.notes(),
+            &vec![
+                MidiNote {
+                    start: 0,
+                    length: 480,
+                    channel: 0,
+                    note: Note::C4,
+                    velocity: 100
+                },
+                MidiNote {
+                    start: 960,
+                    length: 240,
+                    channel: 0,
+                    note: Note::E4,
+                    velocity: 64
+                }
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write() -> Result<(), String> {
+        let notes = vec![
+            MidiNote {
+                start: 480,
+                length: 240,
+                channel: 1,
+                note: Note::E4,
+                velocity: 64,
+            },
+            MidiNote {
+                start: 0,
+                length: 480,
+                channel: 0,
+                note: Note::C4,
+                velocity: 100,
+            },
+        ];
+        let track = MidiTrack::new(Some("lead".to_string()), notes);
+        assert_eq!(track.length(), 720);
+        assert_eq!(track.notes()[0].note, Note::C4);
+        let midi_file = MidiFile::new(480, vec![track]);
+        assert_eq!(MidiFile::from_bytes(&midi_file.to_bytes())?, midi_file);
+
+        // key signatures
+        let key_signature = MidiKeySignature {
+            accidentals: -3,
+            minor: true,
+        };
+        let midi_file = midi_file.with_key_signature(key_signature);
+        assert_eq!(midi_file.tracks()[0].key_signature(), Some(key_signature));
+        assert_eq!(MidiFile::from_bytes(&midi_file.to_bytes())?, midi_file);
+        Ok(())
+    }
+
+    #[test]
+    fn key_signature() -> Result<(), String> {
+        let key_signature = |note: Note, mode: &str| -> Result<(i8, bool), String> {
+            let key_signature = MidiKeySignature::from_scale(&Scale::try_from((note, mode))?);
+            Ok((key_signature.accidentals, key_signature.minor))
+        };
+        assert_eq!(key_signature(Note::C4, "major")?, (0, false));
+        assert_eq!(key_signature(Note::E4, "major")?, (4, false));
+        assert_eq!(key_signature(Note::Fs4, "major")?, (6, false));
+        assert_eq!(key_signature(Note::As4, "major")?, (-2, false));
+        assert_eq!(key_signature(Note::A4, "minor")?, (0, true));
+        assert_eq!(key_signature(Note::D4, "minor")?, (-1, true));
+        assert_eq!(key_signature(Note::Cs4, "minor")?, (4, true));
+        assert_eq!(
+            MidiKeySignature::from_scale(&Scale::try_from((Note::F4, "major"))?).spelling(),
+            NoteSpelling::Flats
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn to_rhythm() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        let track: &[u8] = &[
+            0x00, 0x90, 48, 100, // C4 on
+            0x83, 0x50, 0x80, 48, 0, // C4 off after 464 ticks
+            0x83, 0x70, 0x90, 52, 127, // E4 on after 496 ticks
+            0x81, 0x70, 52, 0, // E4 off after 240 ticks (running status)
+            0x00, 0xFF, 0x2F, 0x00, // end of track
+        ];
+        let midi_file = MidiFile::from_bytes(&midi_file_bytes(&[track]))?;
+        let mut rhythms = midi_file.to_rhythms(time_base, BeatTimeStep::Eighth(1.0))?;
+        assert_eq!(rhythms.len(), 1);
+        let events = rhythms[0]
+            .by_ref()
+            .take(8)
+            .filter_map(|item| match item.event {
+                Some(Event::NoteEvents(notes)) => notes[0]
+                    .as_ref()
+                    .map(|n| (item.time, n.note, n.volume, item.duration)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (0, Note::C4, 100.0 / 127.0, 22050),
+                (22050, Note::OFF, 1.0, 22050),
+                (44100, Note::E4, 1.0, 11025),
+                (55125, Note::OFF, 1.0, 33075),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -4,8 +4,11 @@ use std::{
     fmt::Display,
     mem,
     ops::{Add, Sub},
+    sync::atomic::{AtomicU8, Ordering},
 };
 
+use crate::Scale;
+
 // -------------------------------------------------------------------------------------------------
 
 /// A note representable in a 7 bit unsigned int. The subscript 'S' to a note means sharp.
@@ -18,6 +21,9 @@ use std::{
 /// For From<&str> conversions, the following notation is supported:
 /// `C4` (plain), `C#1` (sharps), `Db1` (flats),
 /// `D_2` (using _ as separator), `G 5` (using space as separator)
+///
+/// Octave numbers in note strings and names follow the engine-wide [`OctaveNumbering`], see
+/// [`Note::set_octave_numbering`]. Enum names always use the default numbering: C4 = 48.
 #[repr(u8)]
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
 #[allow(non_camel_case_types)]
@@ -170,23 +176,155 @@ impl Note {
         *self as u8 % 12
     }
 
-    /// Get note's octave value.
+    /// Get note's octave value in the default octave numbering, where note 0 is in octave 0.
     pub fn octave(&self) -> u8 {
         *self as u8 / 12
     }
 
+    /// Get note's octave value in the given octave numbering, e.g. -1 for C-1 when middle C
+    /// is C4.
+    pub fn octave_in(&self, numbering: OctaveNumbering) -> i32 {
+        self.octave() as i32 + numbering.octave_offset()
+    }
+
+    /// The engine-wide octave numbering, which is used to parse and name notes.
+    pub fn octave_numbering() -> OctaveNumbering {
+        OctaveNumbering::from_index(OCTAVE_NUMBERING.load(Ordering::Relaxed))
+    }
+
+    /// Set the engine-wide octave numbering, which is used to parse and name notes.
+    ///
+    /// Should be set once before creating any scripts or cycles: already parsed notes keep
+    /// their pitch.
+    pub fn set_octave_numbering(numbering: OctaveNumbering) {
+        OCTAVE_NUMBERING.store(numbering as u8, Ordering::Relaxed);
+    }
+
     /// return a new transposed note with the given offset.
     #[must_use]
     pub fn transposed(&self, offset: i32) -> Self {
         Note::from((*self as i32 + offset).clamp(0, 0x7f) as u8)
     }
+
+    /// Note name with octave, using the given accidental spelling, e.g. "Gb4".
+    pub fn name(&self, spelling: NoteSpelling) -> String {
+        self.name_with(spelling, Self::octave_numbering())
+    }
+
+    /// Note name with octave, using the given accidental spelling and octave numbering.
+    pub fn name_with(&self, spelling: NoteSpelling, numbering: OctaveNumbering) -> String {
+        static SHARP_NOTE_NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+        ];
+        static FLAT_NOTE_NAMES: [&str; 12] = [
+            "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+        ];
+        let names = match spelling {
+            NoteSpelling::Sharps => &SHARP_NOTE_NAMES,
+            NoteSpelling::Flats => &FLAT_NOTE_NAMES,
+        };
+        format!(
+            "{}{}",
+            names[self.key() as usize],
+            self.octave_in(numbering)
+        )
+    }
+
+    /// Note name with octave, spelled as in the key signature of the given scale.
+    pub fn name_in(&self, scale: &Scale) -> String {
+        self.name(scale.spelling())
+    }
 }
 
-impl TryFrom<&str> for Note {
-    type Error = String;
+// -------------------------------------------------------------------------------------------------
 
-    /// Try converting the given string to a Note value
-    fn try_from(s: &str) -> Result<Self, String> {
+/// Accidental spelling of note names of black keys, e.g. `F#` vs `Gb`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum NoteSpelling {
+    /// Spell black keys as sharps: C#, D#, F#, G#, A#.
+    #[default]
+    Sharps,
+    /// Spell black keys as flats: Db, Eb, Gb, Ab, Bb.
+    Flats,
+}
+
+impl NoteSpelling {
+    /// Spelling of the major key signature with the given root key (0 = C, 1 = C# ...).
+    /// F#/Gb major is spelled with sharps.
+    pub fn from_key(key: u8) -> Self {
+        match key % 12 {
+            1 | 3 | 5 | 8 | 10 => Self::Flats,
+            _ => Self::Sharps,
+        }
+    }
+
+    /// Explicit accidental spelling of the given note string, e.g. "Gb4" or "f#", if any.
+    pub fn from_note_str(s: &str) -> Option<Self> {
+        match s.trim().chars().nth(1) {
+            Some('#' | 's' | 'S' | '♯') => Some(Self::Sharps),
+            Some('b' | 'B' | '♭') => Some(Self::Flats),
+            _ => None,
+        }
+    }
+
+    /// Letter index of the given root key in this spelling: 0 = C, 1 = D ... 6 = B.
+    pub(crate) fn letter_index(&self, key: u8) -> u8 {
+        static SHARP_LETTERS: [u8; 12] = [0, 0, 1, 1, 2, 3, 3, 4, 4, 5, 5, 6];
+        static FLAT_LETTERS: [u8; 12] = [0, 1, 1, 2, 2, 3, 4, 4, 5, 5, 6, 6];
+        match self {
+            Self::Sharps => SHARP_LETTERS[(key % 12) as usize],
+            Self::Flats => FLAT_LETTERS[(key % 12) as usize],
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// The engine-wide octave numbering setting.
+static OCTAVE_NUMBERING: AtomicU8 = AtomicU8::new(OctaveNumbering::MiddleC5 as u8);
+
+/// Octave numbering convention of note names, named after the name of middle C (MIDI note 60).
+///
+/// Notes without an octave, e.g. "c" or "f#", are always placed in the default octave 4 of
+/// [`OctaveNumbering::MiddleC5`], so they keep their pitch in all numberings.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum OctaveNumbering {
+    /// Middle C is C5, so MIDI note 0 is C0. afseq's default, as used in Renoise.
+    #[default]
+    MiddleC5,
+    /// Middle C is C4, so MIDI note 0 is C-1: the scientific pitch notation.
+    MiddleC4,
+    /// Middle C is C3, so MIDI note 0 is C-2, as used in e.g. Ableton Live or Cubase.
+    MiddleC3,
+}
+
+impl OctaveNumbering {
+    /// Offset of octave numbers in this numbering, relative to the default numbering.
+    pub fn octave_offset(&self) -> i32 {
+        match self {
+            Self::MiddleC5 => 0,
+            Self::MiddleC4 => -1,
+            Self::MiddleC3 => -2,
+        }
+    }
+
+    fn from_index(index: u8) -> Self {
+        match index {
+            1 => Self::MiddleC4,
+            2 => Self::MiddleC3,
+            _ => Self::MiddleC5,
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+impl Note {
+    /// Try converting the given string to a Note value, using the given octave numbering.
+    ///
+    /// In numberings with negative octaves, a '-' in front of octave digits is a minus sign
+    /// and not a separator, e.g. "C-1" is the lowest C when middle C is C4.
+    pub fn try_from_with(s: &str, numbering: OctaveNumbering) -> Result<Self, String> {
         fn is_note_off(s: &str) -> bool {
             s.to_lowercase() == "off"
         }
@@ -222,6 +360,9 @@ impl TryFrom<&str> for Note {
             }
             false
         }
+        fn is_digit_symbol(s: &str, index: usize) -> bool {
+            s.chars().nth(index).is_some_and(|c| c.is_ascii_digit())
+        }
         fn octave_value_at(s: &str, index: usize) -> Result<i32, String> {
             let str = &s[index..];
             str.parse::<i32>()
@@ -262,28 +403,42 @@ impl TryFrom<&str> for Note {
         }
 
         // Note-On
+        const DEFAULT_OCTAVE: i32 = 4;
+        let offset = numbering.octave_offset();
         let note = note_value_at(s, 0)? as i32;
-        let octave = if is_sharp_symbol(s, 1) || is_flat_symbol(s, 1) || is_empty_symbol(s, 1) {
+        let octave = if offset < 0 && s.chars().nth(1) == Some('-') && is_digit_symbol(s, 2) {
+            octave_value_at(s, 1)? - offset
+        } else if is_sharp_symbol(s, 1) || is_flat_symbol(s, 1) || is_empty_symbol(s, 1) {
             if s.len() > 2 && !is_white_space_symbol(s, 2) {
-                octave_value_at(s, 2)?
+                octave_value_at(s, 2)? - offset
             } else {
-                4
+                DEFAULT_OCTAVE
             }
         } else if s.len() > 1 && !is_white_space_symbol(s, 1) {
-            octave_value_at(s, 1)?
+            octave_value_at(s, 1)? - offset
         } else {
-            4
+            DEFAULT_OCTAVE
         };
         if !(0..=10).contains(&octave) {
             return Err(format!(
                 "invalid note str '{}' - octave '{}' is out of range.",
-                s, octave
+                s,
+                octave + offset
             ));
         }
         Ok(Self::from((octave * 12 + note) as u8))
     }
 }
 
+impl TryFrom<&str> for Note {
+    type Error = String;
+
+    /// Try converting the given string to a Note value, using the engine-wide octave numbering.
+    fn try_from(s: &str) -> Result<Self, String> {
+        Self::try_from_with(s, Self::octave_numbering())
+    }
+}
+
 impl From<u8> for Note {
     fn from(n: u8) -> Note {
         unsafe { mem::transmute(n & 0x7f) }
@@ -324,13 +479,191 @@ impl Sub<u8> for Note {
 
 impl Display for Note {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        static NOTE_NAMES: [&str; 12] = [
-            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
-        ];
+        write!(f, "{}", self.name(NoteSpelling::Sharps))
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Frequency of MIDI note 69 (A5 in afseq's default octave numbering) in standard tuning.
+const CONCERT_PITCH: f64 = 440.0;
+
+/// A microtonal tuning, which maps notes to fractional MIDI note numbers or frequencies, e.g.
+/// to play notes in an N-EDO tuning or a tuning from a Scala (.scl) file.
+///
+/// Tunings define the pitches of their scale degrees in cents relative to a root note. The
+/// last degree is the period of the scale, usually an octave, which repeats the scale. Notes
+/// map linearly to degrees: each semitone step of a note plays the next degree of the tuning,
+/// starting with the root note, which plays the root frequency.
+///
+/// The default tuning is 12-EDO, where all notes play their standard MIDI pitches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    description: String,
+    cents: Vec<f64>,
+    root_note: Note,
+    root_frequency: f64,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::equal_temperament(12).expect("12-EDO should be a valid tuning")
+    }
+}
+
+impl Tuning {
+    /// Create a new tuning from the given degree pitches in cents, relative to the root note.
+    /// The last, largest pitch is the period of the tuning.
+    pub fn from_cents(description: &str, cents: Vec<f64>) -> Result<Self, String> {
+        if cents.is_empty() {
+            return Err("tuning needs at least one pitch".to_string());
+        }
+        if let Some(pitch) = cents.iter().find(|pitch| !pitch.is_finite()) {
+            return Err(format!("invalid tuning pitch '{}'", pitch));
+        }
+        let period = cents[cents.len() - 1];
+        if period <= 0.0 {
+            return Err(format!(
+                "tuning period must be larger than 0 cents, but is '{}'",
+                period
+            ));
+        }
+        Ok(Self {
+            description: description.to_string(),
+            cents,
+            root_note: Note::C5,
+            root_frequency: CONCERT_PITCH * 2.0_f64.powf((Note::C5 as u8 as f64 - 69.0) / 12.0),
+        })
+    }
+
+    /// Create a new equal temperament tuning, which divides the octave into the given
+    /// number of equal steps, e.g. 19 for 19-EDO.
+    pub fn equal_temperament(divisions: usize) -> Result<Self, String> {
+        if divisions == 0 {
+            return Err("equal temperament divisions must be larger than 0".to_string());
+        }
+        let step = 1200.0 / divisions as f64;
+        let cents = (1..=divisions).map(|degree| degree as f64 * step).collect();
+        Self::from_cents(&format!("{}-EDO", divisions), cents)
+    }
+
+    /// Create a new tuning from the contents of a Scala (.scl) file.
+    ///
+    /// Pitches with a period are cents values, all others are ratios such as `3/2` or `2`.
+    /// Text after the pitch values and lines starting with `!` are ignored.
+    pub fn from_scala(content: &str) -> Result<Self, String> {
+        let mut lines = content.lines().filter(|line| !line.starts_with('!'));
+        let description = lines
+            .next()
+            .ok_or("missing description line in scala file")?
+            .trim();
+        let count_line = lines
+            .next()
+            .ok_or("missing note count line in scala file")?
+            .trim();
+        let count = count_line
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid note count '{}' in scala file", count_line))?;
+        let cents = lines
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .take(count)
+            .map(Self::parse_scala_pitch)
+            .collect::<Result<Vec<_>, _>>()?;
+        if cents.len() != count {
+            return Err(format!(
+                "expected {} pitches in scala file, got {}",
+                count,
+                cents.len()
+            ));
+        }
+        Self::from_cents(description, cents)
+    }
+
+    /// Return a new tuning which plays the given root frequency at the given root note.
+    /// By default, the root note is middle C with its 12-EDO frequency.
+    #[must_use]
+    pub fn with_root(self, root_note: Note, root_frequency: f64) -> Self {
+        let root_frequency = root_frequency.max(f64::MIN_POSITIVE);
+        Self {
+            root_note,
+            root_frequency,
+            ..self
+        }
+    }
+
+    /// The tuning's description, e.g. the description line of a Scala file.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Pitches of all scale degrees in cents, excluding the root and including the period.
+    pub fn cents(&self) -> &[f64] {
+        &self.cents
+    }
+
+    /// Number of degrees in the tuning's period.
+    pub fn degree_count(&self) -> usize {
+        self.cents.len()
+    }
+
+    /// Size of the tuning's period in cents, usually 1200 (an octave).
+    pub fn period(&self) -> f64 {
+        self.cents[self.cents.len() - 1]
+    }
 
-        let octave = (*self as u8 / 12) as i32;
-        let note = (*self as u8 % 12) as usize;
-        write!(f, "{}{}", NOTE_NAMES[note], octave)
+    /// The note which plays the root frequency.
+    pub fn root_note(&self) -> Note {
+        self.root_note
+    }
+
+    /// Frequency of the root note in Hz.
+    pub fn root_frequency(&self) -> f64 {
+        self.root_frequency
+    }
+
+    /// Pitch of the given note as fractional MIDI note number, e.g. 60.5 for a quarter tone
+    /// above middle C.
+    pub fn pitch(&self, note: Note) -> f64 {
+        69.0 + 12.0 * (self.frequency(note) / CONCERT_PITCH).log2()
+    }
+
+    /// Frequency of the given note in Hz.
+    pub fn frequency(&self, note: Note) -> f64 {
+        self.root_frequency * 2.0_f64.powf(self.note_cents(note) / 1200.0)
+    }
+
+    /// Pitch of the given note in cents, relative to the root note.
+    fn note_cents(&self, note: Note) -> f64 {
+        let steps = note as u8 as i32 - self.root_note as u8 as i32;
+        let degree_count = self.cents.len() as i32;
+        let periods = steps.div_euclid(degree_count);
+        let degree = steps.rem_euclid(degree_count) as usize;
+        let degree_cents = if degree == 0 {
+            0.0
+        } else {
+            self.cents[degree - 1]
+        };
+        periods as f64 * self.period() + degree_cents
+    }
+
+    /// Parse a single cents or ratio pitch value of a Scala file into cents.
+    fn parse_scala_pitch(line: &str) -> Result<f64, String> {
+        let value = line.split_whitespace().next().unwrap_or_default();
+        let invalid_pitch = || format!("invalid pitch '{}' in scala file", value);
+        if value.contains('.') {
+            return value.parse::<f64>().map_err(|_| invalid_pitch());
+        }
+        let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+        let numerator = numerator.parse::<u64>().map_err(|_| invalid_pitch())?;
+        let denominator = denominator.parse::<u64>().map_err(|_| invalid_pitch())?;
+        if numerator == 0 || denominator == 0 {
+            return Err(invalid_pitch());
+        }
+        Ok(1200.0 * (numerator as f64 / denominator as f64).log2())
     }
 }
 
@@ -338,7 +671,8 @@ impl Display for Note {
 
 #[cfg(test)]
 mod test {
-    use super::Note;
+    use super::{Note, NoteSpelling, OctaveNumbering, Tuning};
+    use crate::Scale;
 
     #[test]
     fn note_number_conversion() {
@@ -357,6 +691,31 @@ mod test {
         assert_eq!(Note::Fs10.to_string(), "F#10");
     }
 
+    #[test]
+    fn note_spelling() -> Result<(), String> {
+        assert_eq!(Note::Fs4.name(NoteSpelling::Sharps), "F#4");
+        assert_eq!(Note::Fs4.name(NoteSpelling::Flats), "Gb4");
+        assert_eq!(Note::E4.name(NoteSpelling::Flats), "E4");
+        assert_eq!(
+            NoteSpelling::from_note_str("Gb4"),
+            Some(NoteSpelling::Flats)
+        );
+        assert_eq!(
+            NoteSpelling::from_note_str("c#"),
+            Some(NoteSpelling::Sharps)
+        );
+        assert_eq!(NoteSpelling::from_note_str("c4"), None);
+        assert_eq!(NoteSpelling::from_note_str("c♮4"), None);
+
+        let d_minor = Scale::try_from((Note::D4, "minor"))?;
+        assert_eq!(Note::As4.name_in(&d_minor), "Bb4");
+        let e_major = Scale::try_from((Note::E4, "major"))?;
+        assert_eq!(Note::Gs4.name_in(&e_major), "G#4");
+        let c_minor = Scale::try_from((Note::C4, "minor"))?;
+        assert_eq!(Note::Ds4.name_in(&c_minor), "Eb4");
+        Ok(())
+    }
+
     #[test]
     fn note_deserialization() -> Result<(), String> {
         assert!(Note::try_from("").is_err());
@@ -376,4 +735,80 @@ mod test {
         assert_eq!(Note::try_from("bb2")?, Note::As2);
         Ok(())
     }
+
+    #[test]
+    fn octave_numbering() -> Result<(), String> {
+        use OctaveNumbering::*;
+        assert_eq!(Note::octave_numbering(), MiddleC5);
+
+        assert_eq!(Note::try_from_with("C5", MiddleC5)?, Note::C5);
+        assert_eq!(Note::try_from_with("C4", MiddleC4)?, Note::C5);
+        assert_eq!(Note::try_from_with("C3", MiddleC3)?, Note::C5);
+        assert_eq!(Note::try_from_with("C-1", MiddleC4)?, Note::C0);
+        assert_eq!(Note::try_from_with("C#-2", MiddleC3)?, Note::Cs0);
+        assert_eq!(Note::try_from_with("D -1", MiddleC3)?, Note::D1);
+        assert_eq!(Note::try_from_with("G8", MiddleC3)?, Note::G10);
+        // '-' stays a separator without negative octaves
+        assert_eq!(Note::try_from_with("E-1", MiddleC5)?, Note::E1);
+        // notes without octaves keep their pitch
+        assert_eq!(Note::try_from_with("c", MiddleC3)?, Note::C4);
+        assert_eq!(Note::try_from_with("f#", MiddleC4)?, Note::Fs4);
+        // out of range octaves
+        assert!(Note::try_from_with("C-2", MiddleC4).is_err());
+        assert!(Note::try_from_with("C9", MiddleC3).is_err());
+
+        assert_eq!(Note::C5.name_with(NoteSpelling::Sharps, MiddleC4), "C4");
+        assert_eq!(Note::Cs0.name_with(NoteSpelling::Flats, MiddleC3), "Db-2");
+        assert_eq!(Note::G10.name_with(NoteSpelling::Sharps, MiddleC3), "G8");
+        assert_eq!(Note::C0.octave_in(MiddleC4), -1);
+        Ok(())
+    }
+
+    #[test]
+    fn tunings() -> Result<(), String> {
+        fn assert_approx_eq(a: f64, b: f64) {
+            assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+        }
+
+        // 12-EDO plays standard pitches
+        let tuning = Tuning::default();
+        assert_eq!(tuning.degree_count(), 12);
+        assert_approx_eq(tuning.pitch(Note::C5), 60.0);
+        assert_approx_eq(tuning.pitch(Note::Ds2), Note::Ds2 as u8 as f64);
+        assert_approx_eq(tuning.frequency(Note::A5), 440.0);
+
+        // N-EDO
+        let tuning = Tuning::equal_temperament(24)?;
+        assert_eq!(tuning.description(), "24-EDO");
+        assert_approx_eq(tuning.pitch(Note::Cs5), 60.5);
+        assert_approx_eq(tuning.pitch(Note::B4), 59.5);
+        assert_approx_eq(tuning.pitch(Note::C7), 72.0);
+        assert!(Tuning::equal_temperament(0).is_err());
+
+        // scala files
+        let tuning = Tuning::from_scala(
+            "! just.scl\n\
+            !\n\
+            Just major triad\n \
+            3\n\
+            !\n\
+            5/4 major third\n\
+            701.955\n\
+            2\n",
+        )?
+        .with_root(Note::A5, 440.0);
+        assert_eq!(tuning.description(), "Just major triad");
+        assert_eq!(tuning.degree_count(), 3);
+        assert_approx_eq(tuning.period(), 1200.0);
+        assert_approx_eq(tuning.frequency(Note::A5), 440.0);
+        assert_approx_eq(tuning.frequency(Note::As5), 550.0);
+        assert_approx_eq(tuning.pitch(Note::B5), 69.0 + 7.01955);
+        assert_approx_eq(tuning.frequency(Note::C6), 880.0);
+        assert_approx_eq(tuning.frequency(Note::Gs5), 440.0 / 2.0 * 3.0 / 2.0);
+        assert!(Tuning::from_scala("missing count").is_err());
+        assert!(Tuning::from_scala("missing pitches\n2\n3/2\n").is_err());
+        assert!(Tuning::from_scala("bad ratio\n1\n3/0\n").is_err());
+        assert!(Tuning::from_scala("no period\n1\n-100.0\n").is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,297 @@
+//! Typed engine notifications, which hosts can subscribe to instead of polling.
+
+use std::{cell::RefCell, fmt::Debug, rc::Rc, sync::mpsc::Sender};
+
+use crate::{phrase::RhythmIndex, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Kind of a [`Notification`], e.g. to subscribe to some notifications only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotificationKind {
+    /// See [`Notification::PhraseChanged`].
+    PhraseChanged,
+    /// See [`Notification::PhraseLooped`].
+    PhraseLooped,
+    /// See [`Notification::PatternChanged`].
+    PatternChanged,
+    /// See [`Notification::CuePointPassed`].
+    CuePointPassed,
+    /// See [`Notification::ParameterChanged`].
+    ParameterChanged,
+    /// See [`Notification::TempoChanged`].
+    TempoChanged,
+    /// See [`Notification::ScriptError`].
+    ScriptError,
+}
+
+impl NotificationKind {
+    /// All notification kinds, e.g. to subscribe to all notifications.
+    pub const ALL: [NotificationKind; 7] = [
+        Self::PhraseChanged,
+        Self::PhraseLooped,
+        Self::PatternChanged,
+        Self::CuePointPassed,
+        Self::ParameterChanged,
+        Self::TempoChanged,
+        Self::ScriptError,
+    ];
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A notification, as published by a [`NotificationBus`].
+///
+/// Sample times use the time frame of the sequence's `consume_events_until_time` function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    /// The sequence switched to a different phrase.
+    PhraseChanged {
+        phrase_index: usize,
+        sample_time: SampleTime,
+    },
+    /// The sequence's phrase ended and starts over again, e.g. in single phrase sequences.
+    PhraseLooped {
+        phrase_index: usize,
+        sample_time: SampleTime,
+    },
+    /// A rhythm slot plays a different rhythm or stopped playing, because the sequence switched
+    /// to a phrase with a different rhythm in the slot. Published after the phrase change.
+    PatternChanged {
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+    },
+    /// Playback passed a cue point.
+    CuePointPassed {
+        name: String,
+        sample_time: SampleTime,
+    },
+    /// A parameter value got changed from the outside, e.g. by a host, a parameter ramp or a
+    /// performance macro. Contains the applied, clamped value.
+    ParameterChanged { id: String, value: f64 },
+    /// The sequence's tempo changed.
+    TempoChanged { beats_per_min: f32 },
+    /// A scripted callback of one of the sequence's rhythms failed to evaluate. Published at the
+    /// end of the sequence's `consume_events_until_time` call in which the callback failed.
+    ScriptError { message: String },
+}
+
+impl Notification {
+    /// The notification's kind.
+    pub fn kind(&self) -> NotificationKind {
+        match self {
+            Self::PhraseChanged { .. } => NotificationKind::PhraseChanged,
+            Self::PhraseLooped { .. } => NotificationKind::PhraseLooped,
+            Self::PatternChanged { .. } => NotificationKind::PatternChanged,
+            Self::CuePointPassed { .. } => NotificationKind::CuePointPassed,
+            Self::ParameterChanged { .. } => NotificationKind::ParameterChanged,
+            Self::TempoChanged { .. } => NotificationKind::TempoChanged,
+            Self::ScriptError { .. } => NotificationKind::ScriptError,
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Id of a subscription in a [`NotificationBus`], as returned by its subscribe functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(usize);
+
+#[derive(Clone)]
+enum Subscriber {
+    Callback(Rc<dyn Fn(&Notification)>),
+    Channel(Sender<Notification>),
+}
+
+#[derive(Clone)]
+struct Subscription {
+    id: SubscriptionId,
+    kinds: Vec<NotificationKind>,
+    subscriber: Subscriber,
+}
+
+#[derive(Default)]
+struct NotificationBusInner {
+    subscriptions: Vec<Subscription>,
+    next_id: usize,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Publishes typed [`Notification`]S of a [`Sequence`](crate::Sequence) to subscribed
+/// callbacks and channels, e.g. to update a host's UI when the sequence switches phrases or
+/// when scripts fail.
+///
+/// Subscribers only receive notifications of the kinds they subscribed to. Notifications
+/// are published synchronously while the sequence consumes events, so callbacks should
+/// return quickly. Use channels to pass notifications to other threads. Channel subscriptions
+/// get removed automatically when their receiver got dropped.
+///
+/// Clones of the bus share their subscriptions.
+#[derive(Clone, Default)]
+pub struct NotificationBus {
+    inner: Rc<RefCell<NotificationBusInner>>,
+}
+
+impl NotificationBus {
+    /// Create a new bus without subscriptions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call the given callback for all published notifications of the given kinds.
+    pub fn subscribe<F: Fn(&Notification) + 'static>(
+        &self,
+        kinds: &[NotificationKind],
+        callback: F,
+    ) -> SubscriptionId {
+        self.add_subscription(kinds, Subscriber::Callback(Rc::new(callback)))
+    }
+
+    /// Send all published notifications of the given kinds to the given channel.
+    pub fn subscribe_channel(
+        &self,
+        kinds: &[NotificationKind],
+        sender: Sender<Notification>,
+    ) -> SubscriptionId {
+        self.add_subscription(kinds, Subscriber::Channel(sender))
+    }
+
+    /// Remove a subscription. Removing already removed subscriptions does nothing.
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        self.inner
+            .borrow_mut()
+            .subscriptions
+            .retain(|subscription| subscription.id != id);
+    }
+
+    /// Number of active subscriptions.
+    pub fn subscription_count(&self) -> usize {
+        self.inner.borrow().subscriptions.len()
+    }
+
+    /// Returns true when someone subscribed to notifications of the given kind.
+    pub fn has_subscribers(&self, kind: NotificationKind) -> bool {
+        self.inner
+            .borrow()
+            .subscriptions
+            .iter()
+            .any(|subscription| subscription.kinds.contains(&kind))
+    }
+
+    /// Publish the given notification to all subscribers of the notification's kind.
+    pub fn publish(&self, notification: Notification) {
+        // collect subscribers first: callbacks may (un)subscribe
+        let kind = notification.kind();
+        let subscriptions = self
+            .inner
+            .borrow()
+            .subscriptions
+            .iter()
+            .filter(|subscription| subscription.kinds.contains(&kind))
+            .cloned()
+            .collect::<Vec<_>>();
+        for subscription in subscriptions {
+            match subscription.subscriber {
+                Subscriber::Callback(callback) => callback(&notification),
+                Subscriber::Channel(sender) => {
+                    if sender.send(notification.clone()).is_err() {
+                        self.unsubscribe(subscription.id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Publish the given errors of scripted callbacks, as fetched from the sequence's rhythms.
+    pub(crate) fn publish_script_errors(&self, errors: Vec<String>) {
+        if self.has_subscribers(NotificationKind::ScriptError) {
+            for message in errors {
+                self.publish(Notification::ScriptError { message });
+            }
+        }
+    }
+
+    fn add_subscription(
+        &self,
+        kinds: &[NotificationKind],
+        subscriber: Subscriber,
+    ) -> SubscriptionId {
+        let mut inner = self.inner.borrow_mut();
+        let id = SubscriptionId(inner.next_id);
+        inner.next_id += 1;
+        inner.subscriptions.push(Subscription {
+            id,
+            kinds: kinds.to_vec(),
+            subscriber,
+        });
+        id
+    }
+}
+
+impl Debug for NotificationBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationBus")
+            .field("subscriptions", &self.subscription_count())
+            .finish()
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, sync::mpsc::channel};
+
+    use super::*;
+
+    #[test]
+    fn subscriptions() {
+        let bus = NotificationBus::new();
+        let tempo_changes = Rc::new(Cell::new(0));
+        let id = bus.subscribe(&[NotificationKind::TempoChanged], {
+            let tempo_changes = Rc::clone(&tempo_changes);
+            move |notification| {
+                assert_eq!(notification.kind(), NotificationKind::TempoChanged);
+                tempo_changes.set(tempo_changes.get() + 1);
+            }
+        });
+        let (sender, receiver) = channel();
+        bus.subscribe_channel(&NotificationKind::ALL, sender);
+        assert_eq!(bus.subscription_count(), 2);
+
+        bus.publish(Notification::TempoChanged {
+            beats_per_min: 100.0,
+        });
+        bus.publish(Notification::ScriptError {
+            message: "failed".to_string(),
+        });
+        assert_eq!(tempo_changes.get(), 1);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                Notification::TempoChanged {
+                    beats_per_min: 100.0
+                },
+                Notification::ScriptError {
+                    message: "failed".to_string()
+                }
+            ]
+        );
+
+        // clones share subscriptions
+        bus.unsubscribe(id);
+        bus.clone().publish(Notification::TempoChanged {
+            beats_per_min: 120.0,
+        });
+        assert_eq!(tempo_changes.get(), 1);
+        assert_eq!(receiver.try_iter().count(), 1);
+
+        // dropped receivers unsubscribe
+        drop(receiver);
+        bus.publish(Notification::TempoChanged {
+            beats_per_min: 130.0,
+        });
+        assert_eq!(bus.subscription_count(), 0);
+    }
+}
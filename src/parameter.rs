@@ -0,0 +1,232 @@
+//! Describes user controllable parameters of a `Rhythm`.
+
+use std::{cell::RefCell, ops::RangeInclusive, rc::Rc};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Describes a single numeric parameter of a [`Rhythm`](`crate::Rhythm`), so that hosts can
+/// automatically create controls for it.
+///
+/// Parameter ids are unique within a rhythm. Names are human readable labels, which default
+/// to the parameter's id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RhythmParameter {
+    id: String,
+    name: String,
+    integer: bool,
+    default_value: f64,
+    range: RangeInclusive<f64>,
+    value: f64,
+}
+
+impl RhythmParameter {
+    /// Create a new parameter with the given id, value range and default value.
+    /// The default value is clamped to the given range.
+    pub fn new<S: Into<String>>(id: S, range: RangeInclusive<f64>, default_value: f64) -> Self {
+        let id = id.into();
+        let name = id.clone();
+        let integer = false;
+        let default_value = default_value.clamp(*range.start(), *range.end());
+        let value = default_value;
+        Self {
+            id,
+            name,
+            integer,
+            default_value,
+            range,
+            value,
+        }
+    }
+
+    /// Return a new parameter with the given display name.
+    #[must_use]
+    pub fn with_name<S: Into<String>>(self, name: S) -> Self {
+        let name = name.into();
+        Self { name, ..self }
+    }
+
+    /// Return a new parameter which only accepts integer values.
+    #[must_use]
+    pub fn with_integer(self, integer: bool) -> Self {
+        Self { integer, ..self }
+    }
+
+    /// Return a new parameter with the given actual value, clamped to the parameter's range.
+    #[must_use]
+    pub fn with_value(self, value: f64) -> Self {
+        let value = value.clamp(*self.range.start(), *self.range.end());
+        Self { value, ..self }
+    }
+
+    /// The parameter's unique id.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The parameter's display name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// true when the parameter is an integer value.
+    pub fn is_integer(&self) -> bool {
+        self.integer
+    }
+
+    /// The parameter's default value.
+    pub fn default_value(&self) -> f64 {
+        self.default_value
+    }
+
+    /// Value range of the parameter.
+    pub fn range(&self) -> &RangeInclusive<f64> {
+        &self.range
+    }
+
+    /// The parameter's actual value, which got applied to the rhythm.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+struct RhythmParameterValuesInner {
+    parameters: Vec<RhythmParameter>,
+    changes: Vec<(String, f64)>,
+}
+
+/// Actual values of a rhythm's [`RhythmParameter`]S, which are shared by the rhythm and its
+/// scripted callbacks, so scripts can change parameter values on their own, e.g. to advance
+/// a "section" parameter after a few bars.
+///
+/// Value changes are recorded, so hosts can update their parameter controls: see
+/// [`Sequence::take_parameter_changes`](`crate::Sequence::take_parameter_changes`).
+/// Clones of the store share their values.
+#[derive(Clone, Debug, Default)]
+pub struct RhythmParameterValues {
+    inner: Rc<RefCell<RhythmParameterValuesInner>>,
+}
+
+impl RhythmParameterValues {
+    /// Create a new store with the given parameters and their actual values.
+    pub fn new(parameters: Vec<RhythmParameter>) -> Self {
+        let changes = Vec::new();
+        let inner = Rc::new(RefCell::new(RhythmParameterValuesInner {
+            parameters,
+            changes,
+        }));
+        Self { inner }
+    }
+
+    /// Create a deep copy of the store, which no longer shares its values with this store.
+    pub fn duplicate(&self) -> Self {
+        let inner = self.inner.borrow();
+        let inner = Rc::new(RefCell::new(RhythmParameterValuesInner {
+            parameters: inner.parameters.clone(),
+            changes: inner.changes.clone(),
+        }));
+        Self { inner }
+    }
+
+    /// Add the given parameters to the store, unless parameters with the same ids already exist.
+    pub fn add_parameters(&self, parameters: Vec<RhythmParameter>) {
+        let mut inner = self.inner.borrow_mut();
+        for parameter in parameters {
+            if !inner.parameters.iter().any(|p| p.id() == parameter.id()) {
+                inner.parameters.push(parameter);
+            }
+        }
+    }
+
+    /// Snapshot of all parameters with their actual values.
+    pub fn parameters(&self) -> Vec<RhythmParameter> {
+        self.inner.borrow().parameters.clone()
+    }
+
+    /// Actual value of the parameter with the given id, if it exists.
+    pub fn value(&self, id: &str) -> Option<f64> {
+        self.inner
+            .borrow()
+            .parameters
+            .iter()
+            .find(|parameter| parameter.id() == id)
+            .map(RhythmParameter::value)
+    }
+
+    /// Set a new value for the parameter with the given id. The value gets clamped to the
+    /// parameter's range and rounded for integer parameters. Returns the applied value.
+    ///
+    /// Returns error when the parameter does not exist.
+    pub fn set_value(&self, id: &str, value: f64) -> Result<f64, String> {
+        self.update_value(id, value, true)
+    }
+
+    /// Set a new value like [`Self::set_value`], but without recording the change, e.g. to
+    /// apply values which got changed by the host.
+    pub fn apply_value(&self, id: &str, value: f64) -> Result<f64, String> {
+        self.update_value(id, value, false)
+    }
+
+    /// Fetch and clear all value changes since the last call, as (id, value) pairs.
+    pub fn take_changes(&self) -> Vec<(String, f64)> {
+        std::mem::take(&mut self.inner.borrow_mut().changes)
+    }
+
+    fn update_value(&self, id: &str, value: f64, record_change: bool) -> Result<f64, String> {
+        let mut inner = self.inner.borrow_mut();
+        let parameter = inner
+            .parameters
+            .iter_mut()
+            .find(|parameter| parameter.id() == id)
+            .ok_or_else(|| format!("parameter '{}' does not exist", id))?;
+        let value = if parameter.is_integer() {
+            value.round()
+        } else {
+            value
+        };
+        let old_value = parameter.value();
+        *parameter = parameter.clone().with_value(value);
+        let value = parameter.value();
+        if record_change && value != old_value {
+            inner.changes.retain(|(changed_id, _)| changed_id != id);
+            inner.changes.push((id.to_string(), value));
+        }
+        Ok(value)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parameter_values() {
+        let values = RhythmParameterValues::new(vec![
+            RhythmParameter::new("section", 1.0..=8.0, 1.0).with_integer(true),
+            RhythmParameter::new("density", 0.0..=1.0, 0.5),
+        ]);
+        let shared = values.clone();
+        assert_eq!(shared.set_value("section", 2.4), Ok(2.0));
+        assert_eq!(shared.set_value("density", 2.0), Ok(1.0));
+        assert!(shared.set_value("wurst", 1.0).is_err());
+        assert_eq!(values.value("section"), Some(2.0));
+        assert_eq!(values.parameters()[1].value(), 1.0);
+        shared.set_value("section", 3.0).unwrap();
+        assert_eq!(
+            values.take_changes(),
+            vec![("density".to_string(), 1.0), ("section".to_string(), 3.0)]
+        );
+        assert!(values.take_changes().is_empty());
+        // unchanged values are not recorded
+        shared.set_value("section", 3.0).unwrap();
+        assert!(values.take_changes().is_empty());
+        // applied values are not recorded
+        assert_eq!(shared.apply_value("section", 4.0), Ok(4.0));
+        assert_eq!(values.value("section"), Some(4.0));
+        assert!(values.take_changes().is_empty());
+    }
+}
@@ -2,7 +2,10 @@
 
 use std::{borrow::Cow, fmt::Debug};
 
-use crate::{BeatTimeBase, PulseIterItem};
+use crate::{
+    memory::MemoryUsage, parameter::RhythmParameterValues, shared::SharedValues, BeatTimeBase,
+    PulseIterItem, Warning,
+};
 
 pub mod empty;
 pub mod euclidean;
@@ -33,15 +36,56 @@ pub trait Pattern: Debug {
     /// Set optional, application specific external context data for the pattern.
     fn set_external_context(&mut self, data: &[(Cow<str>, f64)]);
 
+    /// Set the shared values of the sequence the pattern plays in. Only used by scripted
+    /// patterns: the default implementation ignores the values.
+    fn set_shared_values(&mut self, _values: &SharedValues) {}
+
+    /// Set the parameter values of the rhythm the pattern plays in. Only used by scripted
+    /// patterns: the default implementation ignores the values.
+    fn set_parameter_values(&mut self, _values: &RhythmParameterValues) {}
+
+    /// Play the pattern's pulses backwards within each pattern cycle. Only supported by fixed
+    /// patterns: the default implementation ignores it.
+    fn set_reversed(&mut self, _reversed: bool) {}
+
     /// Set how many times the pattern should be repeated. If 0, the pattern will be run once.
     /// When None, which is the default, the pattern will be repeated indefinitely.
     fn set_repeat_count(&mut self, count: Option<usize>);
 
+    /// Fetch and clear all non-fatal issues, which got detected while running the pattern.
+    /// The default implementation reports no warnings.
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        Vec::new()
+    }
+
+    /// Fetch and clear all errors of scripted callbacks, which failed while running the pattern.
+    /// The default implementation reports no errors.
+    fn take_script_errors(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Estimated memory usage of the pattern. Only scripted patterns report their Lua heap
+    /// size: the default implementation reports no memory usage.
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::default()
+    }
+
+    /// Release unused memory, e.g. the garbage of a scripted pattern's Lua engine. The default
+    /// implementation does nothing.
+    fn trim_memory(&mut self) {}
+
     /// Create a new cloned instance of this event iter. This actualy is a clone(), wrapped into
     /// a `Box<dyn EventIter>`, but called 'duplicate' to avoid conflicts with possible
     /// Clone impls.
     fn duplicate(&self) -> Box<dyn Pattern>;
 
+    /// Create a new instance of this pattern for rhythm duplicates, which doesn't share any
+    /// state with this pattern, unlike `duplicate`. Fails when the state can't be copied,
+    /// e.g. for scripted coroutines. The default implementation returns a `duplicate`.
+    fn try_duplicate(&self) -> Result<Box<dyn Pattern>, String> {
+        Ok(self.duplicate())
+    }
+
     /// Reset the pattern genertor, so it emits the same values as if it was freshly initialized.
     /// This does to reset the pattern itself, but onlt the pattern playback position.
     fn reset(&mut self);
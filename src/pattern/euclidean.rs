@@ -53,11 +53,46 @@ pub fn euclidean(steps: u32, pulses: u32, offset: i32) -> Vec<bool> {
     }
 }
 
+/// Generates a Euclidean rhythm pattern with accents: the pattern's on steps get accented by a
+/// second Euclidean distribution of `accent_steps` in `accent_pulses`, which repeats over the
+/// on steps. E.g. accents (2, 3) over (5, 8) accent all but the third on step.
+///
+/// Returns per step velocities: 1 for accented steps, the given velocity in range \[0 - 1\]
+/// for unaccented on steps and 0 for off steps.
+pub fn euclidean_accents(
+    steps: u32,
+    pulses: u32,
+    offset: i32,
+    accent_steps: u32,
+    accent_pulses: u32,
+    velocity: f32,
+) -> Vec<f32> {
+    let velocity = if velocity.is_finite() {
+        velocity.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let accents = euclidean(accent_steps, accent_pulses, 0);
+    let mut accents = accents.iter().cycle();
+    euclidean(steps, pulses, offset)
+        .into_iter()
+        .map(|on| {
+            if !on {
+                0.0
+            } else if accents.next().is_some_and(|accent| *accent) {
+                1.0
+            } else {
+                velocity
+            }
+        })
+        .collect()
+}
+
 // --------------------------------------------------------------------------------------------------
 
 #[cfg(test)]
 mod test {
-    use super::euclidean;
+    use super::*;
 
     #[test]
     fn patterns() {
@@ -113,4 +148,23 @@ mod test {
         assert_eq!(euclidean(3, 8, 5), euclidean(3, 8, 5 + 8));
         assert_eq!(euclidean(3, 8, -3), euclidean(3, 8, -3 - 8));
     }
+
+    #[test]
+    fn accents() {
+        assert_eq!(
+            euclidean_accents(5, 8, 0, 2, 3, 0.5),
+            [1.0, 0.0, 1.0, 0.5, 0.0, 1.0, 1.0, 0.0]
+        );
+        // accents follow rotated patterns
+        assert_eq!(
+            euclidean_accents(3, 8, 3, 1, 2, 0.25),
+            [1.0, 0.0, 0.0, 0.25, 0.0, 1.0, 0.0, 0.0]
+        );
+        // no or all accents
+        assert_eq!(euclidean_accents(3, 4, 0, 0, 2, 0.5), [0.5, 0.5, 0.5, 0.0]);
+        assert_eq!(euclidean_accents(3, 4, 0, 0, 0, 0.5), [0.5, 0.5, 0.5, 0.0]);
+        assert_eq!(euclidean_accents(3, 4, 0, 2, 2, 0.5), [1.0, 1.0, 1.0, 0.0]);
+        // invalid velocities
+        assert_eq!(euclidean_accents(1, 2, 0, 0, 1, 2.0), [1.0, 0.0]);
+    }
 }
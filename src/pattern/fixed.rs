@@ -13,6 +13,7 @@ pub struct FixedPattern {
     pulse_iter: Option<PulseIter>,
     repeat_count_option: Option<usize>,
     repeat_count: usize,
+    reversed: bool,
 }
 
 impl Default for FixedPattern {
@@ -36,12 +37,14 @@ impl FixedPattern {
         let pulse_iter = pulses.first().map(|pulse| pulse.clone().into_iter());
         let repeat_count_option = None;
         let repeat_count = 0;
+        let reversed = false;
         FixedPattern {
             pulses,
             pulse_index,
             pulse_iter,
             repeat_count_option,
             repeat_count,
+            reversed,
         }
     }
 
@@ -49,6 +52,30 @@ impl FixedPattern {
     pub fn from_euclidean(steps: u32, pulses: u32, offset: i32) -> Self {
         Self::from_pulses(euclidean(steps, pulses, offset))
     }
+
+    /// Return a new pattern which plays its pulses backwards.
+    #[must_use]
+    pub fn with_reversed(self, reversed: bool) -> Self {
+        let mut pattern = Self { reversed, ..self };
+        pattern.reset();
+        pattern
+    }
+
+    /// Returns true when the pattern plays its pulses backwards.
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+
+    /// Create a new pulse iter for the pulse at the given step in the pattern.
+    fn new_pulse_iter(&self, pulse_index: usize) -> PulseIter {
+        if self.reversed {
+            self.pulses[self.pulses.len() - 1 - pulse_index]
+                .reversed()
+                .into_iter()
+        } else {
+            self.pulses[pulse_index].clone().into_iter()
+        }
+    }
 }
 
 impl Pattern for FixedPattern {
@@ -87,7 +114,7 @@ impl Pattern for FixedPattern {
             }
         }
         // reset pulse iter and fetch first pulse from it
-        let mut pulse_iter = self.pulses[self.pulse_index].clone().into_iter();
+        let mut pulse_iter = self.new_pulse_iter(self.pulse_index);
         let pulse = pulse_iter.next().unwrap_or_default();
         self.pulse_iter = Some(pulse_iter);
         Some(pulse)
@@ -101,6 +128,11 @@ impl Pattern for FixedPattern {
         // nothing to do
     }
 
+    fn set_reversed(&mut self, reversed: bool) {
+        // steps keep their position in the pattern, so this applies with the next step
+        self.reversed = reversed;
+    }
+
     fn set_repeat_count(&mut self, count: Option<usize>) {
         self.repeat_count_option = count;
     }
@@ -115,7 +147,7 @@ impl Pattern for FixedPattern {
         if self.pulses.is_empty() {
             self.pulse_iter = None;
         } else {
-            self.pulse_iter = Some(self.pulses[self.pulse_index].clone().into_iter());
+            self.pulse_iter = Some(self.new_pulse_iter(self.pulse_index));
         }
     }
 }
@@ -250,4 +282,52 @@ mod test {
         );
         assert_eq!(pattern.run(), None);
     }
+
+    #[test]
+    fn reversed() {
+        let values = |pattern: &mut FixedPattern, count: usize| {
+            (0..count)
+                .map(|_| pattern.run().map(|pulse| pulse.value))
+                .collect::<Vec<_>>()
+        };
+        let mut pattern = [
+            Pulse::from(1.0),
+            Pulse::from(0.0),
+            Pulse::from(vec![0.25, 0.5]),
+        ]
+        .to_pattern()
+        .with_reversed(true);
+        assert!(pattern.is_reversed());
+        assert_eq!(
+            values(&mut pattern, 8),
+            [0.5, 0.25, 0.0, 1.0, 0.5, 0.25, 0.0, 1.0].map(Some)
+        );
+        // toggling keeps the position in the pattern and applies with the next step
+        pattern.run();
+        pattern.set_reversed(false);
+        assert_eq!(
+            values(&mut pattern, 5),
+            [0.25, 0.0, 0.25, 0.5, 1.0].map(Some)
+        );
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn pattern_macro() {
+        let values = |mut pattern: FixedPattern| {
+            (0..7)
+                .map(|_| pattern.run().map(|pulse| (pulse.value, pulse.step_time)))
+                .collect::<Vec<_>>()
+        };
+        let expected = [
+            Pulse::from(1.0),
+            Pulse::from(0.0),
+            Pulse::from(0.5),
+            Pulse::from(vec![1.0, 1.0]),
+        ]
+        .to_pattern();
+        assert_eq!(values(crate::pattern!("x ~ 0.5 x*2")), values(expected));
+        let expected = [1.0, 0.0, 0.0, 1.0, 0.0, 1.0].to_pattern();
+        assert_eq!(values(crate::pattern!("x.-x | ~X")), values(expected));
+    }
 }
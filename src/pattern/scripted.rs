@@ -4,7 +4,10 @@ use mlua::prelude::*;
 
 use crate::{
     bindings::{pattern_pulse_from_value, LuaCallback, LuaTimeoutHook},
-    BeatTimeBase, Pattern, Pulse, PulseIter, PulseIterItem,
+    memory::MemoryUsage,
+    parameter::RhythmParameterValues,
+    shared::SharedValues,
+    BeatTimeBase, Pattern, Pulse, PulseIter, PulseIterItem, Warning,
 };
 
 // -------------------------------------------------------------------------------------------------
@@ -152,14 +155,53 @@ impl Pattern for ScriptedPattern {
         }
     }
 
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        if let Err(err) = self.callback.set_context_shared_values(values) {
+            self.callback.handle_error(&err);
+        }
+    }
+
+    fn set_parameter_values(&mut self, values: &RhythmParameterValues) {
+        if let Err(err) = self.callback.set_context_parameter_values(values) {
+            self.callback.handle_error(&err);
+        }
+    }
+
     fn set_repeat_count(&mut self, count: Option<usize>) {
         self.repeat_count_option = count;
     }
 
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        self.callback.value_range().take_warnings()
+    }
+
+    fn take_script_errors(&mut self) -> Vec<String> {
+        self.callback.script_errors().take()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            lua_heap: self.callback.used_memory(),
+            ..MemoryUsage::default()
+        }
+    }
+
+    fn trim_memory(&mut self) {
+        self.callback.collect_garbage();
+    }
+
     fn duplicate(&self) -> Box<dyn Pattern> {
         Box::new(self.clone())
     }
 
+    fn try_duplicate(&self) -> Result<Box<dyn Pattern>, String> {
+        let callback = self.callback.duplicate().map_err(|err| err.to_string())?;
+        Ok(Box::new(Self {
+            callback,
+            ..self.clone()
+        }))
+    }
+
     fn reset(&mut self) {
         // reset timeout
         self.timeout_hook.reset();
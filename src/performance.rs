@@ -0,0 +1,265 @@
+//! Live performance macros, which bind a single control to multiple sequence targets.
+
+use crate::{event::Event, phrase::RhythmIndex, sequence::ParameterRampCurve};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single target of a [`PerformanceMacro`], which follows the macro's control value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MacroTarget {
+    /// Set a rhythm parameter, using the ids of
+    /// [`Sequence::parameters`](crate::Sequence::parameters), to a value in range
+    /// \[min - max\], interpolated with the given curve.
+    Parameter {
+        id: String,
+        min: f64,
+        max: f64,
+        curve: ParameterRampCurve,
+    },
+    /// Mute new notes of a rhythm slot in all phrases and layers while the control value is
+    /// in range \[min - max\]. Already playing notes are not stopped, so their tails ring out.
+    Mute {
+        rhythm_index: RhythmIndex,
+        min: f64,
+        max: f64,
+    },
+    /// Delay a rhythm slot in all phrases and layers by a value in range \[min - max\] in
+    /// seconds, see [`Sequence::set_slot_delay`](crate::Sequence::set_slot_delay).
+    SlotDelay {
+        rhythm_index: RhythmIndex,
+        min: f64,
+        max: f64,
+    },
+    /// Set an external context value to a value in range \[min - max\], interpolated with the
+    /// given curve, e.g. to drive transform amounts in scripts.
+    Context {
+        name: String,
+        min: f64,
+        max: f64,
+        curve: ParameterRampCurve,
+    },
+}
+
+impl MacroTarget {
+    /// Create a new linear parameter target.
+    pub fn parameter<S: Into<String>>(id: S, min: f64, max: f64) -> Self {
+        let id = id.into();
+        let curve = ParameterRampCurve::Linear;
+        Self::Parameter {
+            id,
+            min,
+            max,
+            curve,
+        }
+    }
+
+    /// Create a new mute target.
+    pub fn mute(rhythm_index: RhythmIndex, min: f64, max: f64) -> Self {
+        Self::Mute {
+            rhythm_index,
+            min,
+            max,
+        }
+    }
+
+    /// Create a new slot delay target.
+    pub fn slot_delay(rhythm_index: RhythmIndex, min: f64, max: f64) -> Self {
+        Self::SlotDelay {
+            rhythm_index,
+            min,
+            max,
+        }
+    }
+
+    /// Create a new linear external context target.
+    pub fn context<S: Into<String>>(name: S, min: f64, max: f64) -> Self {
+        let name = name.into();
+        let curve = ParameterRampCurve::Linear;
+        Self::Context {
+            name,
+            min,
+            max,
+            curve,
+        }
+    }
+
+    /// Return a new parameter or context target which interpolates values with the given
+    /// curve. Other targets are returned unchanged.
+    #[must_use]
+    pub fn with_curve(self, curve: ParameterRampCurve) -> Self {
+        match self {
+            Self::Parameter { id, min, max, .. } => Self::Parameter {
+                id,
+                min,
+                max,
+                curve,
+            },
+            Self::Context { name, min, max, .. } => Self::Context {
+                name,
+                min,
+                max,
+                curve,
+            },
+            target => target,
+        }
+    }
+
+    /// The target's value for the given control value in range \[0 - 1\]. Mute targets
+    /// return 1 when muted and 0 when not.
+    pub fn value_at(&self, control_value: f64) -> f64 {
+        let control_value = control_value.clamp(0.0, 1.0);
+        let interpolate = |min: f64, max: f64, curve: ParameterRampCurve| match curve {
+            ParameterRampCurve::Exponential if min != 0.0 && (max / min) > 0.0 => {
+                min * (max / min).powf(control_value)
+            }
+            _ => min + (max - min) * control_value,
+        };
+        match *self {
+            Self::Parameter {
+                min, max, curve, ..
+            }
+            | Self::Context {
+                min, max, curve, ..
+            } => interpolate(min, max, curve),
+            Self::SlotDelay { min, max, .. } => interpolate(min, max, ParameterRampCurve::Linear),
+            Self::Mute { min, max, .. } => {
+                if (min..=max).contains(&control_value) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A named control with a value in range \[0 - 1\], which drives multiple [`MacroTarget`]S,
+/// so a single knob can morph an entire [`Sequence`](crate::Sequence).
+///
+/// Macros get added to a sequence via
+/// [`Sequence::add_macro`](crate::Sequence::add_macro) and get controlled via
+/// [`Sequence::set_macro_value`](crate::Sequence::set_macro_value).
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerformanceMacro {
+    name: String,
+    targets: Vec<MacroTarget>,
+    value: f64,
+}
+
+impl PerformanceMacro {
+    /// Create a new macro with the given name and targets and a value of 0.
+    pub fn new<S: Into<String>>(name: S, targets: Vec<MacroTarget>) -> Self {
+        let name = name.into();
+        let value = 0.0;
+        Self {
+            name,
+            targets,
+            value,
+        }
+    }
+
+    /// Return a new macro with the given initial value in range \[0 - 1\].
+    #[must_use]
+    pub fn with_value(self, value: f64) -> Self {
+        let mut performance_macro = self;
+        performance_macro.set_value(value);
+        performance_macro
+    }
+
+    /// The macro's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Read-only access to the macro's targets.
+    pub fn targets(&self) -> &[MacroTarget] {
+        &self.targets
+    }
+
+    /// The macro's current control value in range \[0 - 1\].
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Current values of all targets, see [`MacroTarget::value_at`].
+    pub fn target_values(&self) -> Vec<f64> {
+        self.targets
+            .iter()
+            .map(|target| target.value_at(self.value))
+            .collect()
+    }
+
+    /// Returns true when the macro currently mutes the given rhythm slot.
+    pub fn mutes_slot(&self, rhythm_index: RhythmIndex) -> bool {
+        self.targets.iter().any(|target| {
+            matches!(target, MacroTarget::Mute { rhythm_index: index, .. }
+                if *index == rhythm_index && target.value_at(self.value) > 0.0)
+        })
+    }
+
+    pub(crate) fn set_value(&mut self, value: f64) {
+        self.value = if value.is_finite() {
+            value.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Remove all note-ons from the given event, so playing notes still get stopped.
+pub(crate) fn muted_event(event: Option<Event>) -> Option<Event> {
+    match event {
+        Some(Event::NoteEvents(note_events)) => {
+            let note_events = note_events
+                .into_iter()
+                .map(|note_event| note_event.filter(|n| n.note.is_note_off()))
+                .collect::<Vec<_>>();
+            if note_events.iter().any(Option::is_some) {
+                Some(Event::NoteEvents(note_events))
+            } else {
+                None
+            }
+        }
+        event => event,
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn targets() {
+        let performance_macro = PerformanceMacro::new(
+            "morph",
+            vec![
+                MacroTarget::parameter("0.0.density", 0.0, 10.0),
+                MacroTarget::context("cutoff", 100.0, 10000.0)
+                    .with_curve(ParameterRampCurve::Exponential),
+                MacroTarget::mute(1, 0.0, 0.25),
+                MacroTarget::slot_delay(2, 0.0, 0.5),
+            ],
+        );
+        assert_eq!(performance_macro.value(), 0.0);
+        assert_eq!(
+            performance_macro.target_values(),
+            vec![0.0, 100.0, 1.0, 0.0]
+        );
+        assert!(performance_macro.mutes_slot(1));
+        assert!(!performance_macro.mutes_slot(2));
+
+        let performance_macro = performance_macro.with_value(0.5);
+        assert_eq!(
+            performance_macro.target_values(),
+            vec![5.0, 1000.0, 0.0, 0.25]
+        );
+        assert!(!performance_macro.mutes_slot(1));
+        assert_eq!(performance_macro.with_value(2.0).value(), 1.0);
+    }
+}
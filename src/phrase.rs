@@ -4,11 +4,18 @@ use std::{borrow::Cow, cell::RefCell, cmp::Ordering, fmt::Debug, rc::Rc};
 
 use crate::{
     event::{Event, InstrumentId},
+    memory::{event_memory_usage, MemoryUsage},
+    parameter::RhythmParameter,
     prelude::BeatTimeStep,
-    time::SampleTimeDisplay,
-    BeatTimeBase, Rhythm, RhythmIter, RhythmIterItem, SampleTime,
+    rhythm::derived_seed,
+    shared::SharedValues,
+    time::{SampleTimeDisplay, TimeBase},
+    BeatTimeBase, Rhythm, RhythmIter, RhythmIterItem, SampleTime, Warning,
 };
 
+#[cfg(feature = "profiling")]
+use crate::profiling::{ProfileScope, RhythmProfile};
+
 // -------------------------------------------------------------------------------------------------
 
 /// A single slot in a [`Phrase`] vector.
@@ -22,6 +29,22 @@ pub enum RhythmSlot {
     /// Play a shared rhytm in this slot. NB: This is a shared reference, in order to
     /// resolve 'Continue' modes in a [Sequence](`crate::Sequence`).
     Rhythm(Rc<RefCell<dyn Rhythm>>),
+    /// Play a shared rhythm which free-runs: unlike `Rhythm` slots, the rhythm isn't reset
+    /// at phrase boundaries in a [Sequence][`crate::Sequence`] or on scene switches, but
+    /// keeps running on the sequence's timeline, starting at the sequence's start. When the
+    /// slot's phrase wasn't playing for a while, the rhythm skips all events it missed.
+    /// Free-running rhythms only get reset when the entire phrase or sequence is reset.
+    ///
+    /// This is mostly useful for [`SecondTimeRhythm`][`crate::SecondTimeRhythm`]S, e.g.
+    /// ambient textures, which play over a rhythmic, beat-time based bed.
+    FreeRunning(Rc<RefCell<dyn Rhythm>>),
+}
+
+impl RhythmSlot {
+    /// Create a new free-running slot from the given rhythm.
+    pub fn free_running<R: Rhythm + 'static>(rhythm: R) -> Self {
+        Self::FreeRunning(Rc::new(RefCell::new(rhythm)))
+    }
 }
 
 /// Convert an unboxed [`Rhythm`] to a [`RhythmSlot`]
@@ -43,6 +66,64 @@ impl From<Rc<RefCell<dyn Rhythm>>> for RhythmSlot {
 
 // -------------------------------------------------------------------------------------------------
 
+/// How a [`SlotDependency`] suppresses events of its target slot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlotDependencyMode {
+    /// Drop the target slot's events.
+    Drop,
+    /// Delay the target slot's events until the dependency's window passed.
+    Delay,
+    /// Scale the volume of the target slot's notes with the given factor.
+    Duck(f32),
+}
+
+/// Sidechain-style dependency between two slots in a [`Phrase`]: suppresses note-on events of
+/// the `target` slot, which get emitted at the same time or within the given `window` after a
+/// note-on event of the `source` slot, e.g. to let a bass skip notes when the kick plays.
+///
+/// Note-offs and parameter changes of the target slot are never suppressed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlotDependency {
+    pub source: RhythmIndex,
+    pub target: RhythmIndex,
+    /// Window length in seconds.
+    pub window: f64,
+    pub mode: SlotDependencyMode,
+}
+
+impl SlotDependency {
+    pub fn new(
+        source: RhythmIndex,
+        target: RhythmIndex,
+        window: f64,
+        mode: SlotDependencyMode,
+    ) -> Self {
+        Self {
+            source,
+            target,
+            window,
+            mode,
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// How a paused rhythm slot in a [`Phrase`] resumes playback, see [`Phrase::resume_slot`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlotResumeMode {
+    /// Continue right away where the slot got paused.
+    Immediately,
+    /// Continue where the slot got paused, but delay playback until the pause's length is a
+    /// multiple of the given quantum, so the slot stays in phase with e.g. the bar.
+    Quantized(BeatTimeStep),
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Shared rhythm reference, as used in [`RhythmSlot`].
+pub(crate) type RhythmRef = Rc<RefCell<dyn Rhythm>>;
+
 /// Rhythm index in `PhraseIterItem`.
 pub type RhythmIndex = usize;
 /// Event as emitted by the Phrase, tagged with an additional rhythm index.
@@ -59,13 +140,24 @@ pub type PhraseIterItem = (RhythmIndex, RhythmIterItem);
 ///
 /// The `run_until_time` function is also used by [Sequence][`crate::Sequence`] to play a phrase
 /// with a player engine.
+///
+/// Single rhythm slots can be paused and resumed while the other slots keep playing, e.g. for
+/// breakdowns. Paused slots freeze their local time: each slot has its own clock offset, which
+/// grows by the length of each pause.
 #[derive(Clone, Debug)]
 pub struct Phrase {
     time_base: BeatTimeBase,
     length: BeatTimeStep,
     rhythm_slots: Vec<RhythmSlot>,
     next_events: Vec<Option<PhraseIterItem>>,
+    dependencies: Vec<SlotDependency>,
+    slot_delays: Vec<f64>,
+    slot_clock_offsets: Vec<SampleTime>,
+    slot_pause_times: Vec<Option<SampleTime>>,
+    last_note_on_times: Vec<Option<SampleTime>>,
     sample_offset: SampleTime,
+    #[cfg(feature = "profiling")]
+    slot_profiles: Vec<RhythmProfile>,
 }
 
 impl Phrase {
@@ -78,7 +170,14 @@ impl Phrase {
         length: BeatTimeStep,
     ) -> Self {
         let next_events = vec![None; rhythm_slots.len()];
+        let dependencies = Vec::new();
+        let slot_delays = vec![0.0; rhythm_slots.len()];
+        let slot_clock_offsets = vec![0; rhythm_slots.len()];
+        let slot_pause_times = vec![None; rhythm_slots.len()];
+        let last_note_on_times = vec![None; rhythm_slots.len()];
         let sample_offset = 0;
+        #[cfg(feature = "profiling")]
+        let slot_profiles = vec![RhythmProfile::new(); rhythm_slots.len()];
         Self {
             time_base,
             length,
@@ -87,7 +186,36 @@ impl Phrase {
                 .map(|rhythm| -> RhythmSlot { rhythm.into() })
                 .collect::<Vec<_>>(),
             next_events,
+            dependencies,
+            slot_delays,
+            slot_clock_offsets,
+            slot_pause_times,
+            last_note_on_times,
             sample_offset,
+            #[cfg(feature = "profiling")]
+            slot_profiles,
+        }
+    }
+
+    /// Return a new phrase which shifts all events of its rhythm slots by the given delays in
+    /// seconds, e.g. to compensate slow sample attacks or latencies of external gear. Negative
+    /// delays play events earlier. Missing delays are 0.
+    #[must_use]
+    pub fn with_slot_delays(self, delays: Vec<f64>) -> Self {
+        let mut phrase = self;
+        for (rhythm_index, delay) in delays.into_iter().enumerate() {
+            phrase.set_slot_delay(rhythm_index, delay);
+        }
+        phrase
+    }
+
+    /// Return a new phrase which applies the given sidechain-style dependencies between its
+    /// rhythm slots. Dependencies are applied in the given order.
+    #[must_use]
+    pub fn with_dependencies(self, dependencies: Vec<SlotDependency>) -> Self {
+        Self {
+            dependencies,
+            ..self
         }
     }
 
@@ -102,6 +230,162 @@ impl Phrase {
         &self.rhythm_slots
     }
 
+    /// Time spent in the rhythm slots' `run_until_time` calls so far, when the `profiling`
+    /// feature is enabled. Profiles are not cleared on resets, but via
+    /// [`clear_slot_profiles`](Self::clear_slot_profiles).
+    #[cfg(feature = "profiling")]
+    pub fn slot_profiles(&self) -> &[RhythmProfile] {
+        &self.slot_profiles
+    }
+
+    /// Clear all measured slot profiles.
+    #[cfg(feature = "profiling")]
+    pub fn clear_slot_profiles(&mut self) {
+        self.slot_profiles.fill(RhythmProfile::new());
+    }
+
+    /// Read-only access to our slot dependencies.
+    pub fn dependencies(&self) -> &[SlotDependency] {
+        &self.dependencies
+    }
+
+    /// Read-only access to the delays of our rhythm slots in seconds.
+    pub fn slot_delays(&self) -> &[f64] {
+        &self.slot_delays
+    }
+
+    /// Change the delay of the given rhythm slot in seconds at runtime. Already scheduled
+    /// events of the slot get moved by the delay's difference.
+    pub fn set_slot_delay(&mut self, rhythm_index: RhythmIndex, delay: f64) {
+        if rhythm_index >= self.slot_delays.len() {
+            return;
+        }
+        let delay = if delay.is_finite() { delay } else { 0.0 };
+        let old_delay = self.delay_in_samples(rhythm_index);
+        self.slot_delays[rhythm_index] = delay;
+        let new_delay = self.delay_in_samples(rhythm_index);
+        if let Some((_, event)) = &mut self.next_events[rhythm_index] {
+            event.time = event
+                .time
+                .saturating_add_signed(-old_delay)
+                .saturating_add_signed(new_delay);
+        }
+    }
+
+    /// Play the given rhythm slot's pattern and cycle events backwards at runtime, see
+    /// [`Rhythm::set_reversed`]. Already scheduled events of the slot are not affected.
+    pub fn set_slot_reversed(&mut self, rhythm_index: RhythmIndex, reversed: bool) {
+        if let Some(RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm)) =
+            self.rhythm_slots.get(rhythm_index)
+        {
+            rhythm.borrow_mut().set_reversed(reversed);
+        }
+    }
+
+    /// Returns true when the given rhythm slot currently is paused.
+    pub fn is_slot_paused(&self, rhythm_index: RhythmIndex) -> bool {
+        self.slot_pause_times
+            .get(rhythm_index)
+            .is_some_and(Option::is_some)
+    }
+
+    /// Accumulated length of all pauses of the given rhythm slot in samples: the amount the
+    /// slot's local time lags behind the phrase's time.
+    pub fn slot_clock_offset(&self, rhythm_index: RhythmIndex) -> SampleTime {
+        self.slot_clock_offsets
+            .get(rhythm_index)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Pause the given rhythm slot at the given sample time, while all other slots keep playing.
+    /// The sample time uses the time frame of `consume_events_until_time`. Pausing already
+    /// paused slots does nothing.
+    pub fn pause_slot(&mut self, rhythm_index: RhythmIndex, sample_time: SampleTime) {
+        if let Some(pause_time) = self.slot_pause_times.get_mut(rhythm_index) {
+            if pause_time.is_none() {
+                *pause_time = Some(sample_time);
+            }
+        }
+    }
+
+    /// Resume a paused rhythm slot at the given sample time with the given resume mode.
+    /// Resuming slots which are not paused does nothing.
+    pub fn resume_slot(
+        &mut self,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+        mode: SlotResumeMode,
+    ) {
+        if let Some(pause_time) = self
+            .slot_pause_times
+            .get_mut(rhythm_index)
+            .and_then(Option::take)
+        {
+            let mut pause_length = sample_time.saturating_sub(pause_time);
+            if let SlotResumeMode::Quantized(quantum) = mode {
+                let quantum = quantum.to_samples(&self.time_base);
+                if quantum > 0.0 {
+                    pause_length =
+                        ((pause_length as f64 / quantum).ceil() * quantum).round() as SampleTime;
+                }
+            }
+            // shift the slot's clock and already scheduled events by the pause's length
+            self.slot_clock_offsets[rhythm_index] += pause_length;
+            if let Some((_, event)) = &mut self.next_events[rhythm_index] {
+                event.time += pause_length;
+            }
+        }
+    }
+
+    /// Estimated memory usage of the phrase's already fetched, but not yet emitted events.
+    pub(crate) fn pending_events_memory_usage(&self) -> usize {
+        self.next_events
+            .iter()
+            .flatten()
+            .filter_map(|(_, item)| item.event.as_ref())
+            .map(event_memory_usage)
+            .sum()
+    }
+
+    /// Create a deep copy of the phrase, which duplicates all rhythms in its slots. Rhythms
+    /// which got duplicated already, e.g. in other phrases of a sequence, are looked up in and
+    /// added to `duplicates`, so shared rhythms stay shared in the copies. Fails when a rhythm
+    /// can't be duplicated, see [`Rhythm::duplicate`].
+    pub(crate) fn duplicate_with(
+        &self,
+        duplicates: &mut Vec<(RhythmRef, RhythmRef)>,
+    ) -> Result<Self, String> {
+        let mut duplicate_rhythm = |rhythm: &RhythmRef| -> Result<RhythmRef, String> {
+            if let Some((_, duplicate)) = duplicates
+                .iter()
+                .find(|(original, _)| Rc::ptr_eq(original, rhythm))
+            {
+                Ok(Rc::clone(duplicate))
+            } else {
+                let duplicate = rhythm.borrow().duplicate()?;
+                duplicates.push((Rc::clone(rhythm), Rc::clone(&duplicate)));
+                Ok(duplicate)
+            }
+        };
+        let rhythm_slots = self
+            .rhythm_slots
+            .iter()
+            .map(|rhythm_slot| match rhythm_slot {
+                RhythmSlot::Rhythm(rhythm) => Ok(RhythmSlot::Rhythm(duplicate_rhythm(rhythm)?)),
+                RhythmSlot::FreeRunning(rhythm) => {
+                    Ok(RhythmSlot::FreeRunning(duplicate_rhythm(rhythm)?))
+                }
+                RhythmSlot::Stop => Ok(RhythmSlot::Stop),
+                RhythmSlot::Continue => Ok(RhythmSlot::Continue),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            rhythm_slots,
+            ..self.clone()
+        })
+    }
+
     /// Run rhythms until a given sample time is reached, calling the given `consumer`
     /// visitor function for all emitted events.
     pub fn consume_events_until_time<F>(&mut self, sample_time: SampleTime, consumer: &mut F)
@@ -118,11 +402,21 @@ impl Phrase {
     /// Seek rhythms until a given sample time is reached, ignoring all events until that time.
     pub fn skip_events_until_time(&mut self, sample_time: SampleTime) {
         // skip next events in all rhythms
-        for (rhythm_slot, next_event) in self
+        for (((rhythm_slot, next_event), delay), (clock_offset, pause_time)) in self
             .rhythm_slots
             .iter_mut()
             .zip(self.next_events.iter_mut())
+            .zip(self.slot_delays.iter())
+            .zip(
+                self.slot_clock_offsets
+                    .iter()
+                    .zip(self.slot_pause_times.iter()),
+            )
         {
+            // paused slots don't advance
+            if pause_time.is_some() {
+                continue;
+            }
             // skip cached, next due events
             if let Some((rhythm_index, event)) = next_event.take() {
                 if event.time >= sample_time {
@@ -132,8 +426,11 @@ impl Phrase {
             }
             // when there's no cached event, seek the rhythm
             if next_event.is_none() {
-                if let RhythmSlot::Rhythm(rhythm) = rhythm_slot {
-                    rhythm.borrow_mut().seek_until_time(sample_time);
+                if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                    let time_shift = Self::slot_time_shift(*delay, *clock_offset, &self.time_base);
+                    rhythm
+                        .borrow_mut()
+                        .seek_until_time(sample_time.saturating_add_signed(-time_shift));
                 }
             }
         }
@@ -141,9 +438,11 @@ impl Phrase {
 
     /// reset playback status and shift events to the given sample position.
     /// Further take over rhythms from the passed previously playing phrase for `RhythmSlot::Continue` slots.   
+    /// `RhythmSlot::FreeRunning` slots are not reset, but seeked to the given sample position.
     pub fn reset_with_offset(&mut self, sample_offset: SampleTime, previous_phrase: &Phrase) {
         // reset rhythm iters, unless they are in continue mode. in contine mode, copy the slot
         // from the previously playing phrase and adjust sample offsets to fit.
+        self.last_note_on_times.fill(None);
         for rhythm_index in 0..self.rhythm_slots.len() {
             match &mut self.rhythm_slots[rhythm_index] {
                 RhythmSlot::Rhythm(rhythm) => {
@@ -153,14 +452,58 @@ impl Phrase {
                         rhythm.set_sample_offset(sample_offset);
                     }
                     self.next_events[rhythm_index] = None;
+                    // restarted rhythms start in sync with the phrase, paused ones stay paused
+                    self.slot_clock_offsets[rhythm_index] = 0;
+                    if self.slot_pause_times[rhythm_index].is_some() {
+                        self.slot_pause_times[rhythm_index] = Some(sample_offset);
+                    }
+                }
+                RhythmSlot::FreeRunning(rhythm) => {
+                    // take over pending events when the same rhythm played in the previous
+                    // phrase, else skip all events the rhythm missed while it wasn't playing
+                    let is_same_rhythm = match previous_phrase.rhythm_slots.get(rhythm_index) {
+                        Some(RhythmSlot::FreeRunning(previous_rhythm)) => {
+                            Rc::ptr_eq(previous_rhythm, rhythm)
+                        }
+                        _ => false,
+                    };
+                    if is_same_rhythm {
+                        self.next_events[rhythm_index]
+                            .clone_from(&previous_phrase.next_events[rhythm_index]);
+                    } else {
+                        let next_event = &mut self.next_events[rhythm_index];
+                        if next_event
+                            .as_ref()
+                            .is_some_and(|(_, event)| event.time < sample_offset)
+                        {
+                            *next_event = None;
+                        }
+                        if next_event.is_none() {
+                            let clock_offset = self.slot_clock_offsets[rhythm_index];
+                            rhythm
+                                .borrow_mut()
+                                .seek_until_time(sample_offset.saturating_sub(clock_offset));
+                        }
+                    }
                 }
                 RhythmSlot::Stop => {
                     self.next_events[rhythm_index] = None;
+                    self.slot_clock_offsets[rhythm_index] = 0;
                 }
                 RhythmSlot::Continue => {
-                    // take over pending events
+                    // take over pending events and the slot's clock
                     self.next_events[rhythm_index]
                         .clone_from(&previous_phrase.next_events[rhythm_index]);
+                    self.slot_clock_offsets[rhythm_index] = previous_phrase
+                        .slot_clock_offsets
+                        .get(rhythm_index)
+                        .copied()
+                        .unwrap_or_default();
+                    self.slot_pause_times[rhythm_index] = previous_phrase
+                        .slot_pause_times
+                        .get(rhythm_index)
+                        .copied()
+                        .flatten();
                     // take over rhythm
                     self.rhythm_slots[rhythm_index]
                         .clone_from(&previous_phrase.rhythm_slots[rhythm_index]);
@@ -170,19 +513,50 @@ impl Phrase {
     }
 
     fn next_event_until_time(&mut self, sample_time: SampleTime) -> Option<PhraseIterItem> {
+        // fetch events until one passes all slot dependencies
+        while let Some((rhythm_index, event)) = self.next_due_event_until_time(sample_time) {
+            if let Some(event) = self.apply_dependencies(rhythm_index, event) {
+                if Self::is_note_on_event(&event.event) {
+                    self.last_note_on_times[rhythm_index] = Some(event.time);
+                }
+                return Some((rhythm_index, event.with_offset(self.sample_offset)));
+            }
+        }
+        None
+    }
+
+    fn next_due_event_until_time(&mut self, sample_time: SampleTime) -> Option<PhraseIterItem> {
         // fetch next events in all rhythms
-        for (rhythm_index, (rhythm_slot, next_event)) in self
+        for (rhythm_index, (((rhythm_slot, next_event), delay), (clock_offset, pause_time))) in self
             .rhythm_slots
             .iter_mut()
             .zip(self.next_events.iter_mut())
+            .zip(self.slot_delays.iter())
+            .zip(
+                self.slot_clock_offsets
+                    .iter()
+                    .zip(self.slot_pause_times.iter()),
+            )
             .enumerate()
         {
-            if !next_event.is_some() {
+            if !next_event.is_some() && pause_time.is_none() {
                 match rhythm_slot {
                     // NB: Continue mode is resolved by the Sequence - if not, it should behave like Stop
                     RhythmSlot::Stop | RhythmSlot::Continue => *next_event = None,
-                    RhythmSlot::Rhythm(rhythm) => {
-                        if let Some(event) = rhythm.borrow_mut().run_until_time(sample_time) {
+                    RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) => {
+                        // run delayed or paused rhythms behind and negatively delayed ones ahead
+                        let time_shift =
+                            Self::slot_time_shift(*delay, *clock_offset, &self.time_base);
+                        let rhythm_time = sample_time.saturating_add_signed(-time_shift);
+                        #[cfg(feature = "profiling")]
+                        let profile_scope = ProfileScope::start();
+                        let event = rhythm.borrow_mut().run_until_time(rhythm_time);
+                        #[cfg(feature = "profiling")]
+                        if let Some(profile) = self.slot_profiles.get_mut(rhythm_index) {
+                            profile_scope.stop(profile);
+                        }
+                        if let Some(mut event) = event {
+                            event.time = event.time.saturating_add_signed(time_shift);
                             *next_event = Some((rhythm_index, event));
                         } else {
                             *next_event = None;
@@ -191,26 +565,31 @@ impl Phrase {
                 }
             }
         }
-        // select the next from all pre-fetched events with the smallest sample time
-        let next_due = self.next_events.iter_mut().reduce(|min, next| {
-            if let Some((_, min_event)) = min {
-                if let Some((_, next_event)) = next {
-                    match min_event.time.cmp(&next_event.time) {
-                        Ordering::Less | Ordering::Equal => min,
-                        Ordering::Greater => next,
+        // select the next from all pre-fetched events of unpaused slots with the smallest time
+        let next_due = self
+            .next_events
+            .iter_mut()
+            .zip(self.slot_pause_times.iter())
+            .filter_map(|(next_event, pause_time)| pause_time.is_none().then_some(next_event))
+            .reduce(|min, next| {
+                if let Some((_, min_event)) = min {
+                    if let Some((_, next_event)) = next {
+                        match min_event.time.cmp(&next_event.time) {
+                            Ordering::Less | Ordering::Equal => min,
+                            Ordering::Greater => next,
+                        }
+                    } else {
+                        min
                     }
                 } else {
-                    min
+                    next
                 }
-            } else {
-                next
-            }
-        });
+            });
         if let Some(next_due) = next_due {
             if let Some((rhythm_index, event)) = next_due.clone() {
                 if event.time < sample_time {
                     *next_due = None; // consume
-                    Some((rhythm_index, event.with_offset(self.sample_offset)))
+                    Some((rhythm_index, event))
                 } else {
                     None // not yet due
                 }
@@ -221,6 +600,83 @@ impl Phrase {
             None
         }
     }
+
+    /// Apply slot dependencies to the given due event. Returns None when the event got dropped
+    /// or delayed: delayed events are put back into the slot's next event.
+    fn apply_dependencies(
+        &mut self,
+        rhythm_index: RhythmIndex,
+        mut event: RhythmIterItem,
+    ) -> Option<RhythmIterItem> {
+        if !Self::is_note_on_event(&event.event) {
+            return Some(event);
+        }
+        for dependency in &self.dependencies {
+            if dependency.target != rhythm_index || dependency.source == rhythm_index {
+                continue;
+            }
+            // source notes which play at the same time, else the source's last played note
+            let source_time = match self.next_events.get(dependency.source) {
+                Some(Some((_, source_event)))
+                    if source_event.time == event.time
+                        && Self::is_note_on_event(&source_event.event) =>
+                {
+                    Some(source_event.time)
+                }
+                _ => self
+                    .last_note_on_times
+                    .get(dependency.source)
+                    .copied()
+                    .flatten(),
+            };
+            if let Some(source_time) = source_time {
+                let window = self.time_base.seconds_to_samples(dependency.window).max(1);
+                if (source_time..source_time + window).contains(&event.time) {
+                    match dependency.mode {
+                        SlotDependencyMode::Drop => return None,
+                        SlotDependencyMode::Delay => {
+                            event.time = source_time + window;
+                            self.next_events[rhythm_index] = Some((rhythm_index, event));
+                            return None;
+                        }
+                        SlotDependencyMode::Duck(volume) => {
+                            if let Some(Event::NoteEvents(note_events)) = &mut event.event {
+                                for note_event in note_events.iter_mut().flatten() {
+                                    if note_event.note.is_note_on() {
+                                        note_event.volume *= volume.max(0.0);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Some(event)
+    }
+
+    fn delay_in_samples(&self, rhythm_index: RhythmIndex) -> i64 {
+        Self::delay_to_samples(self.slot_delays[rhythm_index], &self.time_base)
+    }
+
+    fn delay_to_samples(delay: f64, time_base: &BeatTimeBase) -> i64 {
+        (delay * time_base.samples_per_sec as f64).round() as i64
+    }
+
+    fn slot_time_shift(delay: f64, clock_offset: SampleTime, time_base: &BeatTimeBase) -> i64 {
+        Self::delay_to_samples(delay, time_base) + clock_offset as i64
+    }
+
+    fn is_note_on_event(event: &Option<Event>) -> bool {
+        if let Some(Event::NoteEvents(note_events)) = event {
+            note_events
+                .iter()
+                .flatten()
+                .any(|note_event| note_event.note.is_note_on())
+        } else {
+            false
+        }
+    }
 }
 
 /// Custom iterator impl for phrases:
@@ -272,7 +728,7 @@ impl Rhythm for Phrase {
     fn set_time_base(&mut self, time_base: &BeatTimeBase) {
         self.time_base.clone_from(time_base);
         for rhythm_slot in &mut self.rhythm_slots {
-            if let RhythmSlot::Rhythm(rhythm) = rhythm_slot {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
                 rhythm.borrow_mut().set_time_base(time_base);
             }
         }
@@ -280,7 +736,7 @@ impl Rhythm for Phrase {
 
     fn set_instrument(&mut self, instrument: Option<InstrumentId>) {
         for rhythm_slot in &mut self.rhythm_slots {
-            if let RhythmSlot::Rhythm(rhythm) = rhythm_slot {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
                 rhythm.borrow_mut().set_instrument(instrument);
             }
         }
@@ -288,14 +744,122 @@ impl Rhythm for Phrase {
 
     fn set_external_context(&mut self, data: &[(Cow<str>, f64)]) {
         for rhythm_slot in &mut self.rhythm_slots {
-            if let RhythmSlot::Rhythm(rhythm) = rhythm_slot {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
                 rhythm.borrow_mut().set_external_context(data);
             }
         }
     }
 
-    fn duplicate(&self) -> Rc<RefCell<dyn Rhythm>> {
-        Rc::new(RefCell::new(self.clone()))
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        for rhythm_slot in &mut self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                rhythm.borrow_mut().set_shared_values(values);
+            }
+        }
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        // free running rhythms are not restarted with the phrase, so they are not reseeded
+        for (rhythm_index, rhythm_slot) in self.rhythm_slots.iter_mut().enumerate() {
+            if let RhythmSlot::Rhythm(rhythm) = rhythm_slot {
+                rhythm
+                    .borrow_mut()
+                    .set_seed(derived_seed(seed, rhythm_index as u64));
+            }
+        }
+    }
+
+    fn set_reversed(&mut self, reversed: bool) {
+        for rhythm_slot in &mut self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                rhythm.borrow_mut().set_reversed(reversed);
+            }
+        }
+    }
+
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        let mut parameters = Vec::new();
+        for rhythm_slot in &self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                parameters.append(&mut rhythm.borrow().parameters());
+            }
+        }
+        parameters
+    }
+
+    fn take_parameter_changes(&mut self) -> Vec<(String, f64)> {
+        let mut changes = Vec::new();
+        for rhythm_slot in &mut self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                changes.append(&mut rhythm.borrow_mut().take_parameter_changes());
+            }
+        }
+        changes
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        for rhythm_slot in &mut self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                warnings.append(&mut rhythm.borrow_mut().take_warnings());
+            }
+        }
+        warnings
+    }
+
+    fn take_script_errors(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for rhythm_slot in &mut self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                errors.append(&mut rhythm.borrow_mut().take_script_errors());
+            }
+        }
+        errors
+    }
+
+    fn set_parameter_value(&mut self, id: &str, value: f64) -> Result<f64, String> {
+        // apply to all rhythms which have a parameter with the given id
+        let mut result = Err(format!("parameter '{}' does not exist", id));
+        for rhythm_slot in &mut self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                if let Ok(value) = rhythm.borrow_mut().set_parameter_value(id, value) {
+                    result = Ok(value);
+                }
+            }
+        }
+        result
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        // rhythms may play in multiple slots: count them only once
+        let mut usage = MemoryUsage::default();
+        let mut visited_rhythms: Vec<&RhythmRef> = Vec::new();
+        for rhythm_slot in &self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                if visited_rhythms
+                    .iter()
+                    .any(|other| Rc::ptr_eq(other, rhythm))
+                {
+                    continue;
+                }
+                visited_rhythms.push(rhythm);
+                usage += rhythm.borrow().memory_usage();
+            }
+        }
+        usage.events += self.pending_events_memory_usage();
+        usage
+    }
+
+    fn trim_memory(&mut self) {
+        for rhythm_slot in &mut self.rhythm_slots {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                rhythm.borrow_mut().trim_memory();
+            }
+        }
+    }
+
+    fn duplicate(&self) -> Result<Rc<RefCell<dyn Rhythm>>, String> {
+        Ok(Rc::new(RefCell::new(self.duplicate_with(&mut Vec::new())?)))
     }
 
     fn reset(&mut self) {
@@ -303,11 +867,166 @@ impl Rhythm for Phrase {
         self.sample_offset = 0;
         // reset iterator state
         self.next_events.fill(None);
+        self.last_note_on_times.fill(None);
+        self.slot_clock_offsets.fill(0);
+        self.slot_pause_times.fill(None);
         // reset all rhythms in our slots as well
         for rhythm_slot in &mut self.rhythm_slots {
-            if let RhythmSlot::Rhythm(rhythm) = rhythm_slot {
+            if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
                 rhythm.borrow_mut().reset();
             }
         }
     }
 }
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn slot_delays() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let mut phrase = Phrase::new(
+            time_base,
+            vec![
+                time_base
+                    .every_nth_beat(1.0)
+                    .trigger(new_note_event(Note::C3)),
+                time_base
+                    .every_nth_beat(1.0)
+                    .trigger(new_note_event(Note::C4)),
+            ],
+            BeatTimeStep::Bar(1.0),
+        )
+        .with_slot_delays(vec![0.01, -0.02]);
+        assert_eq!(phrase.slot_delays(), &[0.01, -0.02]);
+        let mut events = Vec::new();
+        phrase.consume_events_until_time(1000, &mut |rhythm_index, time, _, _| {
+            events.push((rhythm_index, time));
+        });
+        assert_eq!(events, vec![(1, 0), (0, 10), (1, 480), (0, 510), (1, 980)]);
+        // change delays at runtime
+        events.clear();
+        phrase.set_slot_delay(1, 0.0);
+        phrase.consume_events_until_time(2000, &mut |rhythm_index, time, _, _| {
+            events.push((rhythm_index, time));
+        });
+        assert_eq!(events, vec![(0, 1010), (1, 1500), (0, 1510)]);
+    }
+
+    #[test]
+    fn slot_dependencies() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let run = |mode: SlotDependencyMode| {
+            let mut phrase = Phrase::new(
+                time_base,
+                vec![
+                    time_base
+                        .every_nth_beat(0.5)
+                        .trigger(new_note_event(Note::C3)),
+                    time_base
+                        .every_nth_beat(1.0)
+                        .trigger(new_note_event(Note::C4)),
+                ],
+                BeatTimeStep::Bar(1.0),
+            )
+            .with_dependencies(vec![SlotDependency::new(1, 0, 0.1, mode)]);
+            let mut bass_notes = Vec::new();
+            phrase.consume_events_until_time(2000, &mut |rhythm_index, time, event, _| {
+                if let (0, Some(Event::NoteEvents(note_events))) = (rhythm_index, event) {
+                    bass_notes.push((time, note_events[0].as_ref().unwrap().volume));
+                }
+            });
+            bass_notes
+        };
+        assert_eq!(
+            run(SlotDependencyMode::Drop),
+            vec![(250, 1.0), (750, 1.0), (1250, 1.0), (1750, 1.0)]
+        );
+        assert_eq!(
+            run(SlotDependencyMode::Delay),
+            vec![
+                (100, 1.0),
+                (250, 1.0),
+                (600, 1.0),
+                (750, 1.0),
+                (1100, 1.0),
+                (1250, 1.0),
+                (1600, 1.0),
+                (1750, 1.0)
+            ]
+        );
+        assert_eq!(
+            run(SlotDependencyMode::Duck(0.5)),
+            vec![
+                (0, 0.5),
+                (250, 1.0),
+                (500, 0.5),
+                (750, 1.0),
+                (1000, 0.5),
+                (1250, 1.0),
+                (1500, 0.5),
+                (1750, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn paused_slots() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let run = |mode: SlotResumeMode| {
+            let mut phrase = Phrase::new(
+                time_base,
+                vec![
+                    time_base
+                        .every_nth_beat(1.0)
+                        .trigger(new_note_event(Note::C3)),
+                    time_base
+                        .every_nth_beat(1.0)
+                        .trigger(new_note_event(Note::C4)),
+                ],
+                BeatTimeStep::Bar(4.0),
+            );
+            let mut events = Vec::new();
+            let mut consume = |phrase: &mut Phrase, sample_time| {
+                phrase.consume_events_until_time(sample_time, &mut |rhythm_index, time, _, _| {
+                    if rhythm_index == 0 {
+                        events.push(time);
+                    }
+                });
+            };
+            consume(&mut phrase, 1000);
+            phrase.pause_slot(0, 1000);
+            assert!(phrase.is_slot_paused(0));
+            assert!(!phrase.is_slot_paused(1));
+            consume(&mut phrase, 1750);
+            phrase.resume_slot(0, 1750, mode);
+            assert!(!phrase.is_slot_paused(0));
+            consume(&mut phrase, 4000);
+            (events, phrase.slot_clock_offset(0))
+        };
+        assert_eq!(
+            run(SlotResumeMode::Immediately),
+            (vec![0, 500, 1750, 2250, 2750, 3250, 3750], 750)
+        );
+        assert_eq!(
+            run(SlotResumeMode::Quantized(BeatTimeStep::Bar(1.0))),
+            (vec![0, 500, 3000, 3500], 2000)
+        );
+    }
+}
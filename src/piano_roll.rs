@@ -0,0 +1,192 @@
+//! Piano roll visualization data of upcoming events in a `Sequence`.
+
+use std::collections::HashMap;
+
+use crate::{phrase::RhythmIndex, Event, SampleTime, Sequence};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single note in a [`PianoRoll`]. Times are in beats, relative to the sequence's start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PianoRollNote {
+    /// Index of the rhythm slot which emitted the note.
+    pub rhythm_index: RhythmIndex,
+    /// MIDI note number.
+    pub pitch: u8,
+    /// Start time in beats.
+    pub start: f64,
+    /// Duration in beats.
+    pub duration: f64,
+    /// Note volume in range \[0 - 1\].
+    pub velocity: f32,
+    /// Instrument of the note, if any.
+    pub instrument: Option<usize>,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Compact piano roll representation of the events a [`Sequence`] emits in a given time window,
+/// e.g. to show upcoming events in editors, without duplicating the scheduling logic there.
+///
+/// Note durations are the emitted event durations, shortened by note-offs or new notes which
+/// got emitted on the same voice in the same rhythm slot.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PianoRoll {
+    notes: Vec<PianoRollNote>,
+}
+
+impl PianoRoll {
+    /// Create a piano roll from all notes which the given sequence emits in the given sample time
+    /// range \[start_time, end_time). The sequence itself is not modified: events are generated
+    /// on a duplicate of the sequence, starting from the sequence's current position. Fails when
+    /// the sequence can't be duplicated, see [`Sequence::duplicate`].
+    pub fn from_sequence(
+        sequence: &Sequence,
+        start_time: SampleTime,
+        end_time: SampleTime,
+    ) -> Result<Self, String> {
+        let samples_per_beat = sequence.time_base().samples_per_beat();
+        let to_beats = |samples: SampleTime| samples as f64 / samples_per_beat;
+        let mut notes = Vec::<PianoRollNote>::new();
+        if end_time <= start_time {
+            return Ok(Self { notes });
+        }
+        // run a duplicate, so the original sequence is not affected
+        let mut sequence = sequence.duplicate()?;
+        if start_time < sequence.sample_position() {
+            sequence.reset();
+        }
+        let sample_position = sequence.sample_position();
+        sequence.skip_events_until_time(start_time.max(sample_position));
+        // index of the last note in each rhythm's voice
+        let mut playing_notes = HashMap::<(RhythmIndex, usize), usize>::new();
+        sequence.consume_events_until_time(
+            end_time,
+            &mut |rhythm_index, time, event: Option<Event>, duration| {
+                if let Some(Event::NoteEvents(note_events)) = event {
+                    for (voice_index, note_event) in note_events.iter().enumerate() {
+                        if let Some(note_event) = note_event {
+                            // shorten previously playing notes
+                            if let Some(note_index) =
+                                playing_notes.remove(&(rhythm_index, voice_index))
+                            {
+                                let note = &mut notes[note_index];
+                                note.duration = note.duration.min(to_beats(time) - note.start);
+                            }
+                            if note_event.note.is_note_on() {
+                                playing_notes.insert((rhythm_index, voice_index), notes.len());
+                                notes.push(PianoRollNote {
+                                    rhythm_index,
+                                    pitch: u8::from(note_event.note),
+                                    start: to_beats(time),
+                                    duration: to_beats(duration),
+                                    velocity: note_event.volume,
+                                    instrument: note_event.instrument.map(usize::from),
+                                });
+                            }
+                        }
+                    }
+                }
+            },
+        );
+        Ok(Self { notes })
+    }
+
+    /// All notes in the piano roll, sorted by start time.
+    pub fn notes(&self) -> &[PianoRollNote] {
+        &self.notes
+    }
+
+    /// Serialize the piano roll into a compact JSON array of note objects with the properties
+    /// `slot`, `pitch`, `start`, `duration`, `velocity` and `instrument`.
+    pub fn to_json(&self) -> String {
+        let notes = self
+            .notes
+            .iter()
+            .map(|note| {
+                let instrument = note
+                    .instrument
+                    .map_or("null".to_string(), |instrument| instrument.to_string());
+                format!(
+                    r#"{{"slot":{},"pitch":{},"start":{},"duration":{},"velocity":{},"instrument":{}}}"#,
+                    note.rhythm_index,
+                    note.pitch,
+                    note.start,
+                    note.duration,
+                    note.velocity,
+                    instrument
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", notes.join(","))
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn piano_roll() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let phrase = Phrase::new(
+            time_base,
+            vec![
+                time_base
+                    .every_nth_beat(1.0)
+                    .trigger(new_note_event_sequence(vec![
+                        new_note(("c4", InstrumentId::from(1), 0.5)),
+                        new_note("off"),
+                    ]))
+                    .into(),
+                RhythmSlot::Stop,
+            ],
+            BeatTimeStep::Bar(1.0),
+        );
+        let mut sequence = Sequence::new(time_base, vec![phrase]);
+        sequence.skip_events_until_time(1000);
+
+        let piano_roll = PianoRoll::from_sequence(&sequence, 1000, 2500)?;
+        assert_eq!(
+            piano_roll.notes(),
+            &[
+                PianoRollNote {
+                    rhythm_index: 0,
+                    pitch: 48,
+                    start: 2.0,
+                    duration: 1.0,
+                    velocity: 0.5,
+                    instrument: Some(1),
+                },
+                PianoRollNote {
+                    rhythm_index: 0,
+                    pitch: 48,
+                    start: 4.0,
+                    duration: 1.0,
+                    velocity: 0.5,
+                    instrument: Some(1),
+                }
+            ]
+        );
+        assert_eq!(
+            piano_roll.to_json(),
+            r#"[{"slot":0,"pitch":48,"start":2,"duration":1,"velocity":0.5,"instrument":1},{"slot":0,"pitch":48,"start":4,"duration":1,"velocity":0.5,"instrument":1}]"#
+        );
+        // original sequence is not affected
+        assert_eq!(sequence.sample_position(), 1000);
+        assert_eq!(PianoRoll::from_sequence(&sequence, 1000, 2500)?, piano_roll);
+        // windows in the past rewind the sequence's duplicate
+        assert_eq!(
+            PianoRoll::from_sequence(&sequence, 0, 500)?.notes().len(),
+            1
+        );
+        Ok(())
+    }
+}
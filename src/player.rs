@@ -1,7 +1,7 @@
 //! Example player implementation, which plays back a `Sequence` via the `afplay` crate.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{Arc, RwLock},
     time::Duration,
 };
@@ -9,17 +9,50 @@ use std::{
 use crossbeam_channel::Sender;
 
 use afplay::{
-    source::file::preloaded::PreloadedFileSource, utils::speed_from_note, AudioFilePlaybackId,
-    AudioFilePlaybackStatusContext, AudioFilePlaybackStatusEvent, AudioFilePlayer, AudioOutput,
-    DefaultAudioOutput, Error, FilePlaybackOptions,
+    source::{file::preloaded::PreloadedFileSource, AudioSource, AudioSourceTime},
+    utils::speed_from_note,
+    AudioFilePlaybackId, AudioFilePlaybackStatusContext, AudioFilePlaybackStatusEvent,
+    AudioFilePlayer, AudioOutput, DefaultAudioOutput, Error, FilePlaybackOptions,
 };
 
 use crate::{
-    event::{unique_instrument_id, InstrumentId},
+    event::{unique_instrument_id, InstrumentId, NoteEvent, ParameterId},
+    phrase::RhythmIndex,
     time::{SampleTimeDisplay, TimeBase},
-    Event, Note, SampleTime, Sequence,
+    BeatTimeBase, Event, Note, SampleTime, Sequence,
 };
 
+pub mod capture;
+use capture::CaptureBuffer;
+
+pub mod choke;
+use choke::ChokeGroups;
+
+pub mod clock;
+use clock::{MidiClock, MidiClockMessage};
+
+pub mod effects;
+use effects::PerformanceEffects;
+
+pub mod gain;
+use gain::AutoGain;
+
+pub mod note_trigger;
+
+pub mod record;
+use record::{OutputRecorder, OutputRecording};
+
+pub mod region;
+use region::{SampleRegion, SampleRegionParameter};
+
+pub mod stems;
+
+pub mod sync;
+use sync::{ClockSource, ClockSync};
+
+pub mod trigger;
+use trigger::{TriggerOutput, TriggerRenderer};
+
 // -------------------------------------------------------------------------------------------------
 
 /// Preload time of the player's `run_until` function. Should be big enough to ensure that events
@@ -125,10 +158,49 @@ impl SamplePlaybackContext {
 
 // -------------------------------------------------------------------------------------------------
 
+/// Amount of time the player should advance in [`SamplePlayer::advance_by`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HostAdvance {
+    /// Advance by the given number of samples.
+    Samples(SampleTime),
+    /// Advance by the given number of beats, using the sequence's tempo.
+    Beats(f64),
+}
+
+/// Transport state of an external clock (e.g. a plugin host), which drives the player in
+/// [`SamplePlayer::advance_by`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HostTransport {
+    /// The host's playback position in samples, relative to the start of the sequence.
+    pub sample_position: SampleTime,
+    /// The host's tempo for the current block. When None, the sequence's tempo is used.
+    pub beats_per_min: Option<f32>,
+}
+
+// -------------------------------------------------------------------------------------------------
+
 /// An simple example player implementation, which plays back a `Sequence` via the `afplay` crate
 /// using the default audio output device using plain samples loaded from a file as instruments.
 ///
 /// Works on an existing sample pool, which can be used outside of the player as well.
+///
+/// The player either runs on its own clock via `run` and `run_until`, or gets driven by an
+/// external clock via `advance_by`, or by a pluggable [`ClockSource`] with drift correction
+/// via `advance_with_clock`.
+///
+/// Live performance effects, such as stutters or fills, can be applied to the sequence's rhythm
+/// slots via [`SamplePlayer::performance_effects_mut`].
+///
+/// When driven via `advance_by`, the player optionally renders trigger pulses for beats, bars
+/// or a rhythm slot's notes into a dedicated channel of the host's output buffers, in order to
+/// sync external analog gear. See [`SamplePlayer::set_trigger_output`].
+///
+/// Volumes of new notes can be attenuated automatically in dense sections, based on the number
+/// of simultaneously playing voices. See [`SamplePlayer::set_auto_gain`].
+///
+/// MIDI clock and transport messages for external gear can be generated via
+/// [`SamplePlayer::set_midi_clock`]. The player has no MIDI output on its own, so the messages
+/// need to be fetched via [`SamplePlayer::drain_midi_clock_messages`] and sent by the caller.
 pub struct SamplePlayer {
     player: AudioFilePlayer,
     sample_pool: Arc<RwLock<SamplePool>>,
@@ -139,6 +211,19 @@ pub struct SamplePlayer {
     playback_sample_time: SampleTime,
     emitted_sample_time: SampleTime,
     emitted_beats: u32,
+    host_sample_offset: SampleTime,
+    performance_effects: PerformanceEffects,
+    sample_regions: HashMap<InstrumentId, SampleRegion>,
+    region_parameters: HashMap<ParameterId, (InstrumentId, SampleRegionParameter)>,
+    rendered_regions: HashMap<(InstrumentId, [u64; 5]), InstrumentId>,
+    choke_groups: ChokeGroups,
+    choked_notes: HashMap<u32, Vec<AudioFilePlaybackId>>,
+    auto_gain: Option<AutoGain>,
+    trigger_renderer: Option<TriggerRenderer>,
+    midi_clock: Option<MidiClock>,
+    midi_clock_messages: Vec<(SampleTime, MidiClockMessage)>,
+    capture_buffer: Option<CaptureBuffer>,
+    output_recording: Option<OutputRecording>,
 }
 
 impl SamplePlayer {
@@ -161,6 +246,19 @@ impl SamplePlayer {
         let playback_sample_time = player.output_sample_frame_position();
         let emitted_sample_time = 0;
         let emitted_beats = 0;
+        let host_sample_offset = 0;
+        let performance_effects = PerformanceEffects::new();
+        let sample_regions = HashMap::new();
+        let region_parameters = HashMap::new();
+        let rendered_regions = HashMap::new();
+        let choke_groups = ChokeGroups::new();
+        let choked_notes = HashMap::new();
+        let auto_gain = None;
+        let trigger_renderer = None;
+        let midi_clock = None;
+        let midi_clock_messages = Vec::new();
+        let capture_buffer = None;
+        let output_recording = None;
         Ok(Self {
             player,
             sample_pool,
@@ -171,13 +269,90 @@ impl SamplePlayer {
             playback_sample_time,
             emitted_sample_time,
             emitted_beats,
+            host_sample_offset,
+            performance_effects,
+            sample_regions,
+            region_parameters,
+            rendered_regions,
+            choke_groups,
+            choked_notes,
+            auto_gain,
+            trigger_renderer,
+            midi_clock,
+            midi_clock_messages,
+            capture_buffer,
+            output_recording,
         })
     }
 
+    /// Playback region of the given instrument's sample, if any.
+    pub fn sample_region(&self, instrument: InstrumentId) -> Option<SampleRegion> {
+        self.sample_regions.get(&instrument).copied()
+    }
+
+    /// Set or remove the playback region for all new notes of the given instrument. Single
+    /// notes can override the region via note event extra data, see [`SampleRegion`].
+    ///
+    /// Reversed or looped regions get rendered once into temporary sample files when they are
+    /// played for the first time.
+    pub fn set_sample_region(&mut self, instrument: InstrumentId, region: Option<SampleRegion>) {
+        if let Some(region) = region {
+            self.sample_regions.insert(instrument, region);
+        } else {
+            self.sample_regions.remove(&instrument);
+        }
+    }
+
+    /// Instrument and region property which gets changed by parameter change events with the
+    /// given parameter id, if any.
+    pub fn region_parameter(
+        &self,
+        parameter: ParameterId,
+    ) -> Option<(InstrumentId, SampleRegionParameter)> {
+        self.region_parameters.get(&parameter).copied()
+    }
+
+    /// Set or remove a mapping of parameter change events with the given parameter id to a
+    /// property of the given instrument's sample region, so regions can be sequenced.
+    pub fn set_region_parameter(
+        &mut self,
+        parameter: ParameterId,
+        target: Option<(InstrumentId, SampleRegionParameter)>,
+    ) {
+        if let Some(target) = target {
+            self.region_parameters.insert(parameter, target);
+        } else {
+            self.region_parameters.remove(&parameter);
+        }
+    }
+
+    /// Choke groups of instruments: new notes in a group stop all playing notes of the group.
+    pub fn choke_groups(&self) -> &ChokeGroups {
+        &self.choke_groups
+    }
+
+    pub fn choke_groups_mut(&mut self) -> &mut ChokeGroups {
+        &mut self.choke_groups
+    }
+
+    /// Automatic gain staging of new notes, if any.
+    pub fn auto_gain(&self) -> Option<&AutoGain> {
+        self.auto_gain.as_ref()
+    }
+
+    /// Set or remove automatic gain staging. When set, the volume of new notes gets
+    /// attenuated based on the number of voices which play simultaneously in all rhythm slots.
+    /// Voices count as playing until they get stopped by a note-off or a new note on the same
+    /// voice channel.
+    pub fn set_auto_gain(&mut self, auto_gain: Option<AutoGain>) {
+        self.auto_gain = auto_gain;
+    }
+
     /// Access to our file player.
     pub fn file_player(&self) -> &AudioFilePlayer {
         &self.player
     }
+
     pub fn file_player_mut(&mut self) -> &mut AudioFilePlayer {
         &mut self.player
     }
@@ -186,6 +361,7 @@ impl SamplePlayer {
     pub fn show_events(&self) -> bool {
         self.show_events
     }
+
     /// by default false: set to true to dump events to stdout while playing them.
     pub fn set_show_events(&mut self, show: bool) {
         self.show_events = show;
@@ -195,6 +371,7 @@ impl SamplePlayer {
     pub fn playback_pos_emit_rate(&self) -> Duration {
         self.playback_pos_emit_rate
     }
+
     pub fn set_playback_pos_emit_rate(&mut self, emit_rate: Duration) {
         self.playback_pos_emit_rate = emit_rate;
     }
@@ -203,11 +380,165 @@ impl SamplePlayer {
     pub fn new_note_action(&self) -> NewNoteAction {
         self.new_note_action
     }
+
     // set a new new note action behaviour.
     pub fn set_new_note_action(&mut self, action: NewNoteAction) {
         self.new_note_action = action;
     }
 
+    /// Access to the performance effects, which get applied to all played events.
+    pub fn performance_effects(&self) -> &PerformanceEffects {
+        &self.performance_effects
+    }
+
+    pub fn performance_effects_mut(&mut self) -> &mut PerformanceEffects {
+        &mut self.performance_effects
+    }
+
+    /// Trigger pulse output configuration, if any.
+    pub fn trigger_output(&self) -> Option<&TriggerOutput> {
+        self.trigger_renderer.as_ref().map(TriggerRenderer::output)
+    }
+
+    /// Set or remove trigger pulse output. Pulses are rendered via
+    /// [`SamplePlayer::render_trigger_output`].
+    pub fn set_trigger_output(&mut self, output: Option<TriggerOutput>) {
+        self.trigger_renderer = output.map(TriggerRenderer::new);
+    }
+
+    /// Render trigger pulses of the last block which got emitted via `advance_by` into the
+    /// trigger output's channel of the given interleaved host output buffer. `sample_position`
+    /// is the transport's sample position, which got passed to `advance_by` for this block.
+    ///
+    /// Does nothing when no trigger output is set or the output channel does not exist.
+    pub fn render_trigger_output(
+        &mut self,
+        sequence: &Sequence,
+        buffer: &mut [f32],
+        channel_count: usize,
+        sample_position: SampleTime,
+    ) {
+        if let Some(renderer) = &mut self.trigger_renderer {
+            let samples_per_sec = sequence.time_base().samples_per_sec;
+            renderer.render(buffer, channel_count, sample_position, samples_per_sec);
+        }
+    }
+
+    /// MIDI clock generator, if any.
+    pub fn midi_clock(&self) -> Option<&MidiClock> {
+        self.midi_clock.as_ref()
+    }
+
+    /// Set or remove the MIDI clock generator. The clock starts with the next emitted block.
+    pub fn set_midi_clock(&mut self, clock: Option<MidiClock>) {
+        self.midi_clock = clock;
+        self.midi_clock_messages.clear();
+    }
+
+    /// Event capture buffer, if any.
+    pub fn capture_buffer(&self) -> Option<&CaptureBuffer> {
+        self.capture_buffer.as_ref()
+    }
+
+    /// Mut access to the event capture buffer, if any, e.g. to clear it.
+    pub fn capture_buffer_mut(&mut self) -> Option<&mut CaptureBuffer> {
+        self.capture_buffer.as_mut()
+    }
+
+    /// Set or remove the event capture buffer, which records all played events, so they can
+    /// be exported afterwards.
+    pub fn set_capture_buffer(&mut self, buffer: Option<CaptureBuffer>) {
+        self.capture_buffer = buffer;
+    }
+
+    /// Output recording, if a recording is running.
+    pub fn output_recording(&self) -> Option<&OutputRecording> {
+        self.output_recording.as_ref()
+    }
+
+    /// Start recording the host's output into a new WAV file at the given path. Recording
+    /// starts at the next bar of the given sequence. An already running recording gets finished.
+    ///
+    /// Output buffers need to be passed to [`SamplePlayer::record_output`], e.g. from the
+    /// host's audio thread. Use [`SamplePlayer::start_playback_recording`] to record the
+    /// player's own output instead.
+    pub fn start_output_recording<P: AsRef<std::path::Path>>(
+        &mut self,
+        sequence: &Sequence,
+        file_path: P,
+        channel_count: usize,
+    ) -> std::io::Result<()> {
+        self.stop_output_recording()?;
+        let recording = OutputRecording::create(file_path, sequence.time_base(), channel_count)?;
+        self.output_recording = Some(recording);
+        Ok(())
+    }
+
+    /// Start recording the player's own output, as played via `run` or `advance_by`, into a
+    /// new WAV file at the given path. Recording starts at the next bar of the given sequence.
+    /// An already running recording gets finished.
+    ///
+    /// Played samples get rendered on the recording's writer thread, see [`OutputRecording`].
+    pub fn start_playback_recording<P: AsRef<std::path::Path>>(
+        &mut self,
+        sequence: &Sequence,
+        file_path: P,
+        channel_count: usize,
+    ) -> std::io::Result<()> {
+        self.stop_output_recording()?;
+        let recording = OutputRecording::create_playback(
+            file_path,
+            sequence.time_base(),
+            channel_count,
+            Arc::clone(&self.sample_pool),
+        )?;
+        self.output_recording = Some(recording);
+        Ok(())
+    }
+
+    /// Finish a running output recording, if any. Waits until all pending output got written.
+    pub fn stop_output_recording(&mut self) -> std::io::Result<()> {
+        if let Some(recording) = self.output_recording.take() {
+            log::debug!(target: "Player",
+                "Finished output recording of {:.2} beats", recording.recorded_beats()
+            );
+            recording.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Record the given interleaved host output buffer, e.g. the final master output, when an
+    /// output recording is running. `sample_position` is the transport's sample position,
+    /// which got passed to `advance_by` for this block.
+    ///
+    /// Buffers get written on the recording's writer thread, so this can be called in
+    /// real-time audio threads. Buffers are ignored in playback recordings. When writing
+    /// failed, the recording gets stopped and the error is returned.
+    pub fn record_output(
+        &mut self,
+        buffer: &[f32],
+        sample_position: SampleTime,
+    ) -> std::io::Result<()> {
+        if let Some(recording) = &mut self.output_recording {
+            if recording.records_playback() {
+                return Ok(());
+            }
+            if let Err(err) = recording.write(buffer, sample_position) {
+                if let Some(recording) = self.output_recording.take() {
+                    recording.finish()?;
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetch all MIDI clock and transport messages which got generated so far. Message times
+    /// are sequence sample times, as passed to `advance_by` or used in `run`.
+    pub fn drain_midi_clock_messages(&mut self) -> Vec<(SampleTime, MidiClockMessage)> {
+        std::mem::take(&mut self.midi_clock_messages)
+    }
+
     /// Run/play the given sequence until it stops.
     pub fn run(
         &mut self,
@@ -238,6 +569,7 @@ impl SamplePlayer {
                 .resize(sequence.phrase_rhythm_slot_count(), HashMap::new());
             // seek new phase to our previously played time
             sequence.skip_events_until_time(self.emitted_sample_time);
+            self.performance_effects.reset(self.emitted_sample_time);
             log::debug!(target: "Player",
                 "Seek sequence to time {:.2}",
                 time_base.samples_to_seconds(self.emitted_sample_time)
@@ -281,19 +613,104 @@ impl SamplePlayer {
         }
     }
 
+    /// Advance the given sequence by the given amount of time, using an externally supplied
+    /// transport position and tempo, e.g. from a plugin host. Unlike `run`, the player does not
+    /// advance time on its own here, but only emits events for the given block of time.
+    ///
+    /// When the transport's position does not match the end of the previously emitted block,
+    /// e.g. when the host jumped backwards or looped, playback is stopped and the sequence is
+    /// seeked to the new position.
+    pub fn advance_by(
+        &mut self,
+        sequence: &mut Sequence,
+        transport: &HostTransport,
+        amount: HostAdvance,
+    ) {
+        // apply tempo changes
+        if let Some(beats_per_min) = transport.beats_per_min {
+            if beats_per_min != sequence.time_base().beats_per_min {
+                let time_base = BeatTimeBase {
+                    beats_per_min,
+                    ..*sequence.time_base()
+                };
+                sequence.set_time_base(&time_base);
+                log::debug!(target: "Player", "Changed tempo to {:.2} BPM", beats_per_min);
+            }
+        }
+        // handle transport jumps
+        let sample_position = transport.sample_position;
+        if sample_position != self.emitted_sample_time {
+            self.reset_playback_position(sequence);
+            sequence.reset_to_start();
+            sequence.skip_events_until_time(sample_position);
+            self.performance_effects.reset(sample_position);
+            self.emitted_sample_time = sample_position;
+            self.host_sample_offset = sample_position;
+            log::debug!(target: "Player",
+                "Seek sequence to host time {:.2}",
+                sequence.time_base().samples_to_seconds(sample_position)
+            );
+        }
+        // emit events for the given block
+        let samples_to_emit = match amount {
+            HostAdvance::Samples(samples) => samples,
+            HostAdvance::Beats(beats) => {
+                (beats * sequence.time_base().samples_per_beat()) as SampleTime
+            }
+        };
+        if samples_to_emit > 0 {
+            self.run_until_time(
+                sequence,
+                self.playback_sample_time,
+                self.emitted_sample_time + samples_to_emit,
+            );
+            self.emitted_sample_time += samples_to_emit;
+        }
+    }
+
+    /// Advance the given sequence by a block of the given size, following the given clock
+    /// sync's clock source. Drifts between the clock and the player's transport get corrected
+    /// gently, see [`ClockSync`]. Does nothing while the clock source is stopped.
+    ///
+    /// The clock sync's status can be used to show the sync state in hosts.
+    pub fn advance_with_clock<S: ClockSource>(
+        &mut self,
+        sequence: &mut Sequence,
+        clock_sync: &mut ClockSync<S>,
+        block_samples: SampleTime,
+    ) {
+        let audio_frame_position = self.player.output_sample_frame_position();
+        if let Some((transport, amount)) = clock_sync.process(audio_frame_position, block_samples) {
+            self.advance_by(sequence, &transport, amount);
+        }
+    }
+
     fn reset_playback_position(&mut self, sequence: &Sequence) {
         // rebuild playing notes vec
         self.playing_notes.clear();
         self.playing_notes
             .resize(sequence.phrase_rhythm_slot_count(), HashMap::new());
+        self.choked_notes.clear();
         // stop whatever is playing in case we're restarting
         self.player
             .stop_all_sources()
             .expect("failed to stop all playing samples");
+        if let Some(recording) = &self.output_recording {
+            recording.stop_all_samples(self.player.output_sample_frame_position());
+        }
+        // stop the MIDI clock: it restarts at the new position with the next block
+        if let Some(clock) = &mut self.midi_clock {
+            clock.stop(self.emitted_sample_time);
+        }
         // fetch player's actual position and use it as start offset
         self.playback_sample_time = self.player.output_sample_frame_position();
         self.emitted_sample_time = 0;
         self.emitted_beats = 0;
+        self.host_sample_offset = 0;
+        self.performance_effects.reset(0);
+        if let Some(renderer) = &mut self.trigger_renderer {
+            renderer.reset();
+        }
     }
 
     fn run_until_time(
@@ -303,86 +720,317 @@ impl SamplePlayer {
         sample_time: SampleTime,
     ) {
         let time_base = *sequence.time_base();
+        // collect emitted events and apply performance effects
+        let mut events = Vec::new();
         sequence.consume_events_until_time(
             sample_time,
             &mut |rhythm_index, sample_time, event: Option<Event>, event_duration| {
-                // print
-                if self.show_events {
-                    const SHOW_INSTRUMENTS_AND_PARAMETERS: bool = true;
-                    println!(
-                        "{}: {}",
-                        time_base.display(sample_time),
-                        match &event {
-                            Some(event) => event.to_string(SHOW_INSTRUMENTS_AND_PARAMETERS),
-                            None => "---".to_string(),
+                events.push((rhythm_index, sample_time, event, event_duration));
+            },
+        );
+        let events = self
+            .performance_effects
+            .process(&time_base, events, sample_time);
+        // collect trigger pulses
+        if let Some(renderer) = &mut self.trigger_renderer {
+            renderer.add_triggers(&time_base, &events, self.emitted_sample_time, sample_time);
+        }
+        // capture played events
+        if let Some(buffer) = &mut self.capture_buffer {
+            buffer.add_events(&time_base, &events, self.emitted_sample_time, sample_time);
+        }
+        // generate MIDI clock
+        if let Some(clock) = &mut self.midi_clock {
+            let messages = clock.process(&time_base, self.emitted_sample_time, sample_time);
+            self.midi_clock_messages.extend(messages);
+        }
+        // play
+        for (rhythm_index, sample_time, event, event_duration) in events {
+            self.play_event(
+                &time_base,
+                start_offset,
+                rhythm_index,
+                sample_time,
+                event,
+                event_duration,
+            );
+        }
+        // render played samples into playback recordings
+        if let Some(recording) = &self.output_recording {
+            let played_time = start_offset + sample_time.saturating_sub(self.host_sample_offset);
+            recording.advance(played_time, start_offset);
+        }
+    }
+
+    /// Number of voices in all rhythm slots, which play after the given notes got played in
+    /// the given rhythm slot.
+    fn voice_count_after_notes(
+        &self,
+        rhythm_index: RhythmIndex,
+        notes: &[Option<NoteEvent>],
+    ) -> usize {
+        let mut voice_count = 0;
+        for (index, playing_notes) in self.playing_notes.iter().enumerate() {
+            if index == rhythm_index {
+                let mut voices = playing_notes.keys().copied().collect::<HashSet<_>>();
+                for (voice_index, note_event) in notes.iter().enumerate() {
+                    if let Some(note_event) = note_event {
+                        if note_event.note.is_note_on() && note_event.instrument.is_some() {
+                            voices.insert(voice_index);
+                        } else if note_event.note.is_note_off() {
+                            voices.remove(&voice_index);
                         }
-                    );
+                    }
                 }
-                // play
-                let playing_notes_in_rhythm = &mut self.playing_notes[rhythm_index];
-                if let Some(Event::NoteEvents(notes)) = event {
-                    for (voice_index, note_event) in notes.iter().enumerate() {
-                        if let Some(note_event) = note_event {
-                            // stop playing samples on this voice channel
-                            if let Some((playback_id, _)) =
-                                playing_notes_in_rhythm.get(&voice_index)
-                            {
-                                if self.new_note_action == NewNoteAction::Stop
-                                    || note_event.note.is_note_off()
-                                {
-                                    if let Err(_err) = self.player.stop_source_at_sample_time(
-                                        *playback_id,
-                                        start_offset + sample_time,
-                                    ) {
-                                        // this is expected when the sample played to end
+                voice_count += voices.len();
+            } else {
+                voice_count += playing_notes.len();
+            }
+        }
+        voice_count
+    }
+
+    fn play_event(
+        &mut self,
+        time_base: &BeatTimeBase,
+        start_offset: SampleTime,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+        event: Option<Event>,
+        event_duration: SampleTime,
+    ) {
+        // print
+        if self.show_events {
+            const SHOW_INSTRUMENTS_AND_PARAMETERS: bool = true;
+            println!(
+                "{}: {}",
+                time_base.display(sample_time),
+                match &event {
+                    Some(event) => event.to_string(SHOW_INSTRUMENTS_AND_PARAMETERS),
+                    None => "---".to_string(),
+                }
+            );
+        }
+        // apply auto gain
+        let gain = match (&self.auto_gain, &event) {
+            (Some(auto_gain), Some(Event::NoteEvents(notes))) => {
+                auto_gain.gain(self.voice_count_after_notes(rhythm_index, notes))
+            }
+            _ => 1.0,
+        };
+        // apply region parameter changes
+        if let Some(Event::ParameterChangeEvent(change)) = &event {
+            let target = change
+                .parameter
+                .and_then(|parameter| self.region_parameter(parameter));
+            if let Some((instrument, region_parameter)) = target {
+                let region = self
+                    .sample_region(instrument)
+                    .unwrap_or_default()
+                    .with_parameter(region_parameter, change.value as f64);
+                self.set_sample_region(instrument, Some(region));
+            }
+        }
+        // play
+        let sample_time = sample_time - self.host_sample_offset;
+        let playing_notes_in_rhythm = &mut self.playing_notes[rhythm_index];
+        if let Some(Event::NoteEvents(notes)) = event {
+            for (voice_index, note_event) in notes.iter().enumerate() {
+                if let Some(note_event) = note_event {
+                    // stop playing samples on this voice channel
+                    if let Some((playback_id, _)) = playing_notes_in_rhythm.get(&voice_index) {
+                        if self.new_note_action == NewNoteAction::Stop
+                            || note_event.note.is_note_off()
+                        {
+                            if let Err(_err) = self.player.stop_source_at_sample_time(
+                                *playback_id,
+                                start_offset + sample_time,
+                            ) {
+                                // this is expected when the sample played to end
+                            }
+                            if let Some(recording) = &self.output_recording {
+                                recording.stop_sample(*playback_id, start_offset + sample_time);
+                            }
+                            playing_notes_in_rhythm.remove(&voice_index);
+                        }
+                    }
+                    // start a new sample - when this is a note off, we already stopped it above
+                    if note_event.note.is_note_on() {
+                        if let Some(instrument) = note_event.instrument {
+                            let speed = speed_from_note(note_event.note as u8);
+                            let playback_options = FilePlaybackOptions::default()
+                                .speed(speed)
+                                .playback_pos_emit_rate(self.playback_pos_emit_rate);
+                            let playback_sample_rate = self.player.output_sample_rate();
+                            // resolve sample regions, rendering reversed or looped ones
+                            let mut region = self
+                                .sample_regions
+                                .get(&instrument)
+                                .copied()
+                                .unwrap_or_default()
+                                .with_overrides(note_event.extra.as_ref());
+                            let mut sample_instrument = instrument;
+                            if region.needs_rendering() {
+                                match Self::rendered_region_sample(
+                                    &self.sample_pool,
+                                    &mut self.rendered_regions,
+                                    instrument,
+                                    &region,
+                                    playback_sample_rate,
+                                ) {
+                                    Ok(rendered_instrument) => {
+                                        sample_instrument = rendered_instrument;
+                                        region = SampleRegion::default();
+                                    }
+                                    Err(err) => {
+                                        log::warn!(target: "Player", "Failed to render sample region: {}", err);
                                     }
-                                    playing_notes_in_rhythm.remove(&voice_index);
                                 }
                             }
-                            // start a new sample - when this is a note off, we already stopped it above
-                            if note_event.note.is_note_on() {
-                                if let Some(instrument) = note_event.instrument {
-                                    let playback_options = FilePlaybackOptions::default()
-                                        .speed(speed_from_note(note_event.note as u8))
-                                        .playback_pos_emit_rate(self.playback_pos_emit_rate);
-                                    let playback_sample_rate = self.player.output_sample_rate();
-                                    let sample_pool = self
-                                        .sample_pool
-                                        .read()
-                                        .expect("Failed to access sample pool");
-                                    if let Ok(mut sample) = sample_pool.get_sample(
-                                        instrument,
-                                        playback_options,
-                                        playback_sample_rate,
+                            let sample_pool = self
+                                .sample_pool
+                                .read()
+                                .expect("Failed to access sample pool");
+                            if let Ok(mut sample) = sample_pool.get_sample(
+                                sample_instrument,
+                                playback_options,
+                                playback_sample_rate,
+                            ) {
+                                sample.set_volume(note_event.volume * gain);
+                                let context = Arc::new(SamplePlaybackContext {
+                                    rhythm_index: Some(rhythm_index),
+                                    voice_index: Some(voice_index),
+                                });
+                                let sample_delay =
+                                    (note_event.delay * event_duration as f32) as SampleTime;
+                                let playback_start_time = start_offset + sample_time + sample_delay;
+                                // stop playing notes in the note's choke group
+                                let choke_group = self
+                                    .choke_groups
+                                    .note_group(instrument, note_event.extra.as_ref());
+                                if let Some(group) = choke_group {
+                                    for playback_id in
+                                        self.choked_notes.remove(&group).unwrap_or_default()
+                                    {
+                                        if let Err(_err) = self.player.stop_source_at_sample_time(
+                                            playback_id,
+                                            playback_start_time,
+                                        ) {
+                                            // this is expected when the sample played to end
+                                        }
+                                        if let Some(recording) = &self.output_recording {
+                                            recording.stop_sample(playback_id, playback_start_time);
+                                        }
+                                    }
+                                }
+                                let playback_id = self
+                                    .player
+                                    .play_file_source_with_context(
+                                        sample,
+                                        Some(playback_start_time),
+                                        Some(context),
+                                    )
+                                    .expect("Failed to play file source");
+                                if let Some(recording) = &self.output_recording {
+                                    recording.play_sample(
+                                        playback_id,
+                                        sample_instrument,
+                                        note_event.note as u8,
+                                        note_event.volume * gain,
+                                        playback_start_time,
+                                    );
+                                }
+                                // apply sample regions
+                                if region.start() > 0.0 {
+                                    let position = Duration::from_secs_f64(region.start());
+                                    if let Err(err) = self.player.seek_source(playback_id, position)
+                                    {
+                                        log::warn!(target: "Player", "Failed to seek sample: {}", err);
+                                    }
+                                }
+                                if let Some(duration) = region.playback_duration(speed) {
+                                    let region_length =
+                                        (duration * playback_sample_rate as f64) as SampleTime;
+                                    if let Err(_err) = self.player.stop_source_at_sample_time(
+                                        playback_id,
+                                        playback_start_time + region_length,
                                     ) {
-                                        sample.set_volume(note_event.volume);
-                                        let context = Arc::new(SamplePlaybackContext {
-                                            rhythm_index: Some(rhythm_index),
-                                            voice_index: Some(voice_index),
-                                        });
-                                        let sample_delay = (note_event.delay
-                                            * event_duration as f32)
-                                            as SampleTime;
-                                        let playback_id = self
-                                            .player
-                                            .play_file_source_with_context(
-                                                sample,
-                                                Some(start_offset + sample_time + sample_delay),
-                                                Some(context),
-                                            )
-                                            .expect("Failed to play file source");
-                                        playing_notes_in_rhythm
-                                            .insert(voice_index, (playback_id, note_event.note));
+                                        // this is expected when the sample played to end
                                     }
-                                    else {
-                                        log::error!(target: "Player", "Failed to get sample with id {}", instrument);
+                                    if let Some(recording) = &self.output_recording {
+                                        recording.stop_sample(
+                                            playback_id,
+                                            playback_start_time + region_length,
+                                        );
                                     }
                                 }
+                                if let Some(group) = choke_group {
+                                    self.choked_notes
+                                        .entry(group)
+                                        .or_default()
+                                        .push(playback_id);
+                                }
+                                playing_notes_in_rhythm
+                                    .insert(voice_index, (playback_id, note_event.note));
+                            } else {
+                                log::error!(target: "Player", "Failed to get sample with id {}", instrument);
                             }
                         }
                     }
                 }
-            },
-        );
+            }
+        }
+    }
+
+    /// Render the given reversed or looped region of the given instrument's sample into a new,
+    /// temporary sample in the sample pool, or fetch an already rendered one.
+    fn rendered_region_sample(
+        sample_pool: &RwLock<SamplePool>,
+        rendered_regions: &mut HashMap<(InstrumentId, [u64; 5]), InstrumentId>,
+        instrument: InstrumentId,
+        region: &SampleRegion,
+        sample_rate: u32,
+    ) -> Result<InstrumentId, Box<dyn std::error::Error>> {
+        let key = (instrument, region.cache_key());
+        if let Some(rendered_instrument) = rendered_regions.get(&key) {
+            return Ok(*rendered_instrument);
+        }
+        let sample_pool = sample_pool.read().expect("Failed to access sample pool");
+        // read the entire sample
+        let mut sample =
+            sample_pool.get_sample(instrument, FilePlaybackOptions::default(), sample_rate)?;
+        let channel_count = sample.channel_count().max(1);
+        let mut buffer = Vec::new();
+        let mut block = vec![0.0; 1024 * channel_count];
+        let mut position = 0;
+        while !sample.is_exhausted() {
+            let time = AudioSourceTime {
+                pos_in_frames: position,
+            };
+            let written = sample.write(&mut block, &time);
+            if written == 0 {
+                break;
+            }
+            buffer.extend_from_slice(&block[..written]);
+            position += (written / channel_count) as u64;
+        }
+        // render the region into a temporary file and load it
+        let rendered = region.render(&buffer, channel_count, sample_rate);
+        let file_path = std::env::temp_dir().join(format!(
+            "afseq-region-{}-{}.wav",
+            std::process::id(),
+            rendered_regions.len()
+        ));
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: sample_rate,
+        };
+        let mut recorder = OutputRecorder::create(&file_path, &time_base, channel_count)?;
+        recorder.write(&rendered, 0)?;
+        recorder.finish()?;
+        let rendered_instrument = sample_pool.load_sample(&file_path.to_string_lossy())?;
+        rendered_regions.insert(key, rendered_instrument);
+        Ok(rendered_instrument)
     }
 }
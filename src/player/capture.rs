@@ -0,0 +1,503 @@
+//! Retrospective capture of all events which got played by the `SamplePlayer`.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    midi::{MidiFile, MidiNote, MidiTrack},
+    phrase::{RhythmIndex, RhythmSlot},
+    player::effects::SequenceEvent,
+    time::{BeatTimeStep, TimeBase, TimingQuantize},
+    BeatTimeBase, Event, Groove, Phrase, SampleTime,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single event in a [`CaptureBuffer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedEvent {
+    pub rhythm_index: RhythmIndex,
+    pub time: SampleTime,
+    pub event: Event,
+    pub duration: SampleTime,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Rolling buffer of all events which got played within the last `max_length` seconds, so
+/// improvised takes can be recovered after the fact and exported as MIDI file or phrase.
+///
+/// Captured event times are continuous: when the playback position jumps back, e.g. when the
+/// host loops, following events are appended after the already captured events at the next
+/// position with the same bar phase, so captured events stay on the bar grid.
+///
+/// Tempo changes are captured too, so exported beat positions follow the played tempo.
+///
+/// Captured events optionally get partially quantized, see [`TimingQuantize`].
+#[derive(Clone, Debug)]
+pub struct CaptureBuffer {
+    max_length: f64,
+    quantize: Option<TimingQuantize>,
+    events: VecDeque<CapturedEvent>,
+    tempo_changes: Vec<TempoChange>,
+    time_offset: SampleTime,
+    end_time: SampleTime,
+}
+
+impl CaptureBuffer {
+    /// Create a new, empty buffer which keeps events of the last `max_length` seconds.
+    pub fn new(max_length: f64) -> Self {
+        let max_length = if max_length.is_finite() {
+            max_length.max(0.0)
+        } else {
+            0.0
+        };
+        let quantize = None;
+        let events = VecDeque::new();
+        let tempo_changes = Vec::new();
+        let time_offset = 0;
+        let end_time = 0;
+        Self {
+            max_length,
+            quantize,
+            events,
+            tempo_changes,
+            time_offset,
+            end_time,
+        }
+    }
+
+    /// Return a new buffer which quantizes the times of captured events with the given
+    /// quantize setting.
+    #[must_use]
+    pub fn with_quantize<Q: Into<Option<TimingQuantize>>>(self, quantize: Q) -> Self {
+        let quantize = quantize.into();
+        Self { quantize, ..self }
+    }
+
+    /// Maximum length of the buffer in seconds.
+    pub fn max_length(&self) -> f64 {
+        self.max_length
+    }
+
+    /// Quantize setting of the buffer, if any.
+    pub fn quantize(&self) -> Option<&TimingQuantize> {
+        self.quantize.as_ref()
+    }
+
+    /// All captured events, sorted by time.
+    pub fn events(&self) -> &VecDeque<CapturedEvent> {
+        &self.events
+    }
+
+    /// Remove all captured events.
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.tempo_changes.clear();
+        self.time_offset = 0;
+        self.end_time = 0;
+    }
+
+    /// Capture events of a single played block of time and drop events which are older than
+    /// the buffer's max length.
+    pub fn add_events(
+        &mut self,
+        time_base: &BeatTimeBase,
+        events: &[SequenceEvent],
+        start_time: SampleTime,
+        end_time: SampleTime,
+    ) {
+        // memorize tempo changes
+        let samples_per_beat = time_base.samples_per_beat();
+        if self
+            .tempo_changes
+            .last()
+            .map_or(true, |change| change.samples_per_beat != samples_per_beat)
+        {
+            let time = self.end_time;
+            let beats = self.beats_at(time_base, time as f64);
+            self.tempo_changes.push(TempoChange {
+                time,
+                beats,
+                samples_per_beat,
+            });
+        }
+        // keep captured times continuous and in bar phase on playback jumps
+        if start_time + self.time_offset < self.end_time {
+            let beats_per_bar = time_base.beats_per_bar.max(1) as f64;
+            let bar_phase = (start_time as f64 / samples_per_beat).rem_euclid(beats_per_bar);
+            let end_beats = self.beats_at(time_base, self.end_time as f64);
+            let mut start_beats = (end_beats / beats_per_bar).floor() * beats_per_bar + bar_phase;
+            if start_beats < end_beats - BEAT_EPSILON {
+                start_beats += beats_per_bar;
+            }
+            let capture_start_time = self.time_at(time_base, start_beats).round() as SampleTime;
+            self.time_offset = capture_start_time.max(self.end_time) - start_time;
+        }
+        for (rhythm_index, time, event, duration) in events {
+            if let Some(event) = event {
+                let time = match &self.quantize {
+                    Some(quantize) => quantize.apply(time_base, *time),
+                    None => *time,
+                } + self.time_offset;
+                // quantized events may move before already captured ones
+                let index = self
+                    .events
+                    .partition_point(|captured| captured.time <= time);
+                self.events.insert(
+                    index,
+                    CapturedEvent {
+                        rhythm_index: *rhythm_index,
+                        time,
+                        event: event.clone(),
+                        duration: *duration,
+                    },
+                );
+            }
+        }
+        self.end_time = self.end_time.max(end_time + self.time_offset);
+        // remove old events
+        let max_length = time_base.seconds_to_samples(self.max_length);
+        let start_time = self.end_time.saturating_sub(max_length);
+        while self
+            .events
+            .front()
+            .is_some_and(|event| event.time < start_time)
+        {
+            self.events.pop_front();
+        }
+        // remove tempo changes which no longer apply to any captured event
+        let obsolete_changes = self
+            .tempo_changes
+            .windows(2)
+            .take_while(|changes| changes[1].time <= start_time)
+            .count();
+        self.tempo_changes.drain(..obsolete_changes);
+    }
+
+    /// Convert captured note events to a MIDI file with one track per rhythm slot, starting
+    /// at the bar of the first captured event. Parameter change events are not exported.
+    pub fn to_midi_file(&self, time_base: &BeatTimeBase, ticks_per_beat: u16) -> MidiFile {
+        let start_beats = self.start_beats(time_base);
+        let to_ticks = |time: SampleTime| {
+            let beats = (self.beats_at(time_base, time as f64) - start_beats).max(0.0);
+            (beats * ticks_per_beat as f64).round() as u64
+        };
+        // collect notes of all rhythm slots and voices
+        let track_count = self
+            .events
+            .iter()
+            .map(|event| event.rhythm_index + 1)
+            .max()
+            .unwrap_or(0);
+        let mut notes = vec![Vec::new(); track_count];
+        let mut playing_notes = HashMap::<(RhythmIndex, usize), (MidiNote, SampleTime)>::new();
+        for captured in &self.events {
+            if let Event::NoteEvents(note_events) = &captured.event {
+                for (voice_index, note_event) in note_events.iter().enumerate() {
+                    if let Some(note_event) = note_event {
+                        let key = (captured.rhythm_index, voice_index);
+                        let time = captured.time
+                            + (note_event.delay * captured.duration as f32) as SampleTime;
+                        // stop playing note on this voice
+                        if note_event.note.is_note_on() || note_event.note.is_note_off() {
+                            if let Some((mut note, _)) = playing_notes.remove(&key) {
+                                note.length = to_ticks(time).saturating_sub(note.start);
+                                notes[captured.rhythm_index].push(note);
+                            }
+                        }
+                        // start a new one
+                        if note_event.note.is_note_on() {
+                            let velocity = (note_event.volume.clamp(0.0, 1.0) * 127.0).round();
+                            let note = MidiNote {
+                                start: to_ticks(time),
+                                length: 0,
+                                channel: 0,
+                                note: note_event.note,
+                                velocity: velocity as u8,
+                            };
+                            playing_notes.insert(key, (note, time + captured.duration));
+                        }
+                    }
+                }
+            }
+        }
+        // end hanging notes with their event's duration
+        for ((rhythm_index, _), (mut note, end_time)) in playing_notes {
+            note.length = to_ticks(end_time).saturating_sub(note.start);
+            notes[rhythm_index].push(note);
+        }
+        let tracks = notes
+            .into_iter()
+            .enumerate()
+            .map(|(rhythm_index, notes)| {
+                MidiTrack::new(Some(format!("Rhythm {}", rhythm_index + 1)), notes)
+            })
+            .collect();
+        MidiFile::new(ticks_per_beat, tracks)
+    }
+
+    /// Convert captured note events to a phrase with one rhythm slot per captured rhythm slot,
+    /// quantizing notes to the given grid. See [`MidiTrack::to_rhythm`].
+    ///
+    /// Returns error when the grid step is invalid.
+    pub fn to_phrase(
+        &self,
+        time_base: &BeatTimeBase,
+        grid: BeatTimeStep,
+    ) -> Result<Phrase, String> {
+        const TICKS_PER_BEAT: u16 = 960;
+        let midi_file = self.to_midi_file(time_base, TICKS_PER_BEAT);
+        let mut rhythm_slots = Vec::new();
+        for track in midi_file.tracks() {
+            if track.notes().is_empty() {
+                rhythm_slots.push(RhythmSlot::Stop);
+            } else {
+                let rhythm = track.to_rhythm(TICKS_PER_BEAT, *time_base, grid)?;
+                rhythm_slots.push(RhythmSlot::from(rhythm));
+            }
+        }
+        // use captured length, rounded up to full bars
+        let beats = self.beats_at(time_base, self.end_time as f64) - self.start_beats(time_base);
+        let bars = (beats / time_base.beats_per_bar as f64).ceil().max(1.0);
+        Ok(Phrase::new(
+            *time_base,
+            rhythm_slots,
+            BeatTimeStep::Bar(bars as f32),
+        ))
+    }
+
+    /// Extract a groove template from the timing and volumes of all captured note-ons, with
+    /// one groove position per grid step within a group of `length` grid steps, starting at
+    /// the bar of the first captured event. See [`Groove::extract`].
+    ///
+    /// Returns error when the grid step or length is invalid or no notes got captured.
+    pub fn to_groove(
+        &self,
+        time_base: &BeatTimeBase,
+        grid: BeatTimeStep,
+        length: usize,
+    ) -> Result<Groove, String> {
+        let step = grid.to_samples(time_base) / time_base.samples_per_beat();
+        if !(step > 0.0 && step.is_finite()) {
+            return Err(format!("invalid groove grid step: '{:?}'", grid));
+        }
+        let start_beats = self.start_beats(time_base);
+        let mut notes = Vec::new();
+        for captured in &self.events {
+            if let Event::NoteEvents(note_events) = &captured.event {
+                for note_event in note_events.iter().flatten() {
+                    if note_event.note.is_note_on() {
+                        let time = captured.time as f64
+                            + (note_event.delay * captured.duration as f32) as f64;
+                        let beats = self.beats_at(time_base, time) - start_beats;
+                        notes.push((beats / step, note_event.volume));
+                    }
+                }
+            }
+        }
+        Groove::extract(&notes, length)
+    }
+
+    /// Beat position of the bar of the first captured event.
+    fn start_beats(&self, time_base: &BeatTimeBase) -> f64 {
+        if let Some(event) = self.events.front() {
+            let beats_per_bar = time_base.beats_per_bar.max(1) as f64;
+            let beats = self.beats_at(time_base, event.time as f64);
+            return ((beats + BEAT_EPSILON) / beats_per_bar).floor() * beats_per_bar;
+        }
+        0.0
+    }
+
+    /// Convert a captured sample time to a beat position, applying all captured tempo changes.
+    fn beats_at(&self, time_base: &BeatTimeBase, time: f64) -> f64 {
+        let index = self
+            .tempo_changes
+            .partition_point(|change| change.time as f64 <= time);
+        match self.tempo_changes.get(index.saturating_sub(1)) {
+            Some(change) => change.beats + (time - change.time as f64) / change.samples_per_beat,
+            None => time / time_base.samples_per_beat(),
+        }
+    }
+
+    /// Convert a beat position to a captured sample time, applying all captured tempo changes.
+    fn time_at(&self, time_base: &BeatTimeBase, beats: f64) -> f64 {
+        let index = self
+            .tempo_changes
+            .partition_point(|change| change.beats <= beats);
+        match self.tempo_changes.get(index.saturating_sub(1)) {
+            Some(change) => change.time as f64 + (beats - change.beats) * change.samples_per_beat,
+            None => beats * time_base.samples_per_beat(),
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Tolerance for rounding errors when comparing beat positions.
+const BEAT_EPSILON: f64 = 1.0e-6;
+
+/// Tempo of captured events, starting at the given captured sample time and beat position.
+#[derive(Clone, Debug)]
+struct TempoChange {
+    time: SampleTime,
+    beats: f64,
+    samples_per_beat: f64,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        event::{new_note, InstrumentId},
+        Note,
+    };
+
+    #[test]
+    fn capture() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let note = |note: Note| Some(Event::NoteEvents(vec![new_note(note)]));
+        let mut buffer = CaptureBuffer::new(4.0);
+        buffer.add_events(
+            &time_base,
+            &[
+                (0, 2000, note(Note::C4), 500),
+                (1, 2500, note(Note::E4), 250),
+            ],
+            2000,
+            3000,
+        );
+        buffer.add_events(&time_base, &[(0, 3000, note(Note::OFF), 500)], 3000, 4000);
+        assert_eq!(buffer.events().len(), 3);
+        // playback jumps get appended
+        buffer.add_events(&time_base, &[(0, 0, note(Note::G4), 500)], 0, 1000);
+        assert_eq!(buffer.events().back().map(|event| event.time), Some(4000));
+        // old events get dropped
+        buffer.add_events(&time_base, &[], 1000, 3000);
+        assert_eq!(buffer.events().len(), 2);
+        // playback jumps keep the bar phase
+        buffer.add_events(&time_base, &[(0, 500, note(Note::A4), 500)], 500, 1000);
+        assert_eq!(buffer.events().back().map(|event| event.time), Some(8500));
+
+        // export
+        let mut buffer = CaptureBuffer::new(60.0);
+        buffer.add_events(
+            &time_base,
+            &[
+                (0, 2000, note(Note::C4), 500),
+                (1, 2500, note(Note::E4), 250),
+            ],
+            2000,
+            3000,
+        );
+        buffer.add_events(&time_base, &[(0, 3000, note(Note::OFF), 500)], 3000, 4000);
+        let midi_file = buffer.to_midi_file(&time_base, 480);
+        assert_eq!(midi_file.tracks().len(), 2);
+        assert_eq!(
+            midi_file.tracks()[0].notes(),
+            &vec![MidiNote {
+                start: 0,
+                length: 960,
+                channel: 0,
+                note: Note::C4,
+                velocity: 127
+            }]
+        );
+        assert_eq!(
+            midi_file.tracks()[1].notes(),
+            &vec![MidiNote {
+                start: 480,
+                length: 240,
+                channel: 0,
+                note: Note::E4,
+                velocity: 127
+            }]
+        );
+        let phrase = buffer.to_phrase(&time_base, BeatTimeStep::Sixteenth(1.0))?;
+        assert_eq!(phrase.rhythm_slots().len(), 2);
+        assert_eq!(phrase.length(), BeatTimeStep::Bar(1.0));
+
+        // tempo changes
+        let mut buffer = CaptureBuffer::new(60.0);
+        buffer.add_events(&time_base, &[(0, 0, note(Note::C4), 500)], 0, 2000);
+        let slow_time_base = BeatTimeBase {
+            beats_per_min: 60.0,
+            ..time_base
+        };
+        buffer.add_events(
+            &slow_time_base,
+            &[
+                (0, 2000, note(Note::E4), 1000),
+                (0, 3000, note(Note::G4), 1000),
+            ],
+            2000,
+            4000,
+        );
+        let midi_file = buffer.to_midi_file(&slow_time_base, 480);
+        assert_eq!(
+            midi_file.tracks()[0]
+                .notes()
+                .iter()
+                .map(|note| note.start)
+                .collect::<Vec<_>>(),
+            vec![0, 1920, 2400]
+        );
+        let phrase = buffer.to_phrase(&slow_time_base, BeatTimeStep::Beats(1.0))?;
+        assert_eq!(phrase.length(), BeatTimeStep::Bar(2.0));
+
+        // partial quantize
+        let mut buffer = CaptureBuffer::new(60.0)
+            .with_quantize(TimingQuantize::new(BeatTimeStep::Beats(1.0)).with_strength(0.5));
+        buffer.add_events(
+            &time_base,
+            &[
+                (0, 2100, note(Note::C4), 500),
+                (1, 2040, note(Note::E4), 250),
+            ],
+            2000,
+            3000,
+        );
+        assert_eq!(
+            buffer
+                .events()
+                .iter()
+                .map(|event| event.time)
+                .collect::<Vec<_>>(),
+            vec![2020, 2050]
+        );
+
+        // groove extraction
+        let mut buffer = CaptureBuffer::new(60.0);
+        let volume_note = |volume: f32| {
+            Some(Event::NoteEvents(vec![new_note((
+                Note::C4,
+                None::<InstrumentId>,
+                volume,
+            ))]))
+        };
+        buffer.add_events(
+            &time_base,
+            &[
+                (0, 2000, volume_note(1.0), 250),
+                (0, 2300, volume_note(0.5), 250),
+                (0, 2500, volume_note(1.0), 250),
+                (0, 2800, volume_note(0.5), 250),
+            ],
+            2000,
+            3000,
+        );
+        let groove = buffer.to_groove(&time_base, BeatTimeStep::Eighth(1.0), 2)?;
+        assert_eq!(groove.positions().len(), 2);
+        assert!((groove.positions()[1] - 1.2).abs() < 1.0e-9);
+        assert!(groove.velocities()[0] > 1.0 && groove.velocities()[1] < 1.0);
+        assert!(buffer
+            .to_groove(&time_base, BeatTimeStep::Eighth(0.0), 2)
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+//! Choke groups of instruments, as used by the `SamplePlayer`.
+
+use std::collections::HashMap;
+
+use crate::event::{cycle::CHOKE_GROUP_KEY, EventData, EventDataValue, InstrumentId};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Assigns instruments to choke groups: a new note in a group stops all currently playing notes
+/// of the group, e.g. to let closed hi-hats cut open hi-hats.
+///
+/// Groups can be set per instrument in the [`SamplePlayer`](super::SamplePlayer) and get
+/// overridden by single notes via the note event's `choke` extra data, which cycles set with
+/// `chokeN` targets, e.g. `oh:choke1`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ChokeGroups {
+    groups: HashMap<InstrumentId, u32>,
+}
+
+impl ChokeGroups {
+    /// Create new, empty choke groups.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Choke group of the given instrument, if any.
+    pub fn group(&self, instrument: InstrumentId) -> Option<u32> {
+        self.groups.get(&instrument).copied()
+    }
+
+    /// Assign the given instrument to a choke group or remove it from its group (when passing
+    /// `None`).
+    pub fn set_group(&mut self, instrument: InstrumentId, group: Option<u32>) {
+        if let Some(group) = group {
+            self.groups.insert(instrument, group);
+        } else {
+            self.groups.remove(&instrument);
+        }
+    }
+
+    /// Remove all instruments from their choke groups.
+    pub fn clear(&mut self) {
+        self.groups.clear();
+    }
+
+    /// Resolve the choke group of a note with the given instrument and note event extra data.
+    /// Extra data overrides the instrument's group.
+    pub fn note_group(&self, instrument: InstrumentId, extra: Option<&EventData>) -> Option<u32> {
+        match extra.and_then(|extra| extra.get(CHOKE_GROUP_KEY)) {
+            Some(EventDataValue::Integer(group)) => u32::try_from(*group).ok(),
+            Some(EventDataValue::Number(group)) if *group >= 0.0 => Some(*group as u32),
+            _ => self.group(instrument),
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn choke_groups() {
+        let closed_hihat = InstrumentId::from(1);
+        let open_hihat = InstrumentId::from(2);
+        let mut groups = ChokeGroups::new();
+        groups.set_group(closed_hihat, Some(1));
+        groups.set_group(open_hihat, Some(1));
+        assert_eq!(groups.group(open_hihat), Some(1));
+        assert_eq!(groups.note_group(closed_hihat, None), Some(1));
+        groups.set_group(open_hihat, None);
+        assert_eq!(groups.note_group(open_hihat, None), None);
+
+        let extra = EventData::from([(CHOKE_GROUP_KEY.to_string(), EventDataValue::Integer(3))]);
+        assert_eq!(groups.note_group(open_hihat, Some(&extra)), Some(3));
+        assert_eq!(groups.note_group(closed_hihat, Some(&extra)), Some(3));
+        groups.clear();
+        assert_eq!(groups.group(closed_hihat), None);
+    }
+}
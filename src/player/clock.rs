@@ -0,0 +1,239 @@
+//! Generates MIDI clock and transport messages from a player's transport.
+
+use crate::{BeatTimeBase, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// MIDI clock pulses per quarter note.
+pub const MIDI_CLOCKS_PER_BEAT: u32 = 24;
+
+/// MIDI clock pulses per sixteenth note, the song position pointer's unit.
+pub(crate) const MIDI_CLOCKS_PER_SIXTEENTH: u64 = 6;
+
+// -------------------------------------------------------------------------------------------------
+
+/// MIDI system real-time and song position messages, as emitted by a [`MidiClock`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MidiClockMessage {
+    /// Timing clock pulse, sent 24 times per quarter note.
+    Clock,
+    /// Start playback from the song's beginning.
+    Start,
+    /// Continue playback from the last song position pointer.
+    Continue,
+    /// Stop playback.
+    Stop,
+    /// Song position pointer in sixteenth notes.
+    SongPosition(u16),
+}
+
+impl MidiClockMessage {
+    /// Raw MIDI bytes of the message.
+    pub fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Clock => vec![0xF8],
+            Self::Start => vec![0xFA],
+            Self::Continue => vec![0xFB],
+            Self::Stop => vec![0xFC],
+            Self::SongPosition(position) => {
+                let position = position.min(0x3FFF);
+                vec![0xF2, (position & 0x7F) as u8, (position >> 7) as u8]
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Generates sample time stamped MIDI clock and transport messages, so external gear such as
+/// drum machines can slave to a sequence.
+///
+/// Clock pulses are derived from the sequence's time base. Tempo changes are smoothed over
+/// a few clock pulses, so jittering tempo values, e.g. from a host transport, don't result
+/// in jittering clock intervals. Set a smoothing factor of 1.0 to disable smoothing.
+#[derive(Debug, Clone)]
+pub struct MidiClock {
+    smoothing: f64,
+    running: bool,
+    clock_count: u64,
+    next_clock_time: f64,
+    samples_per_clock: Option<f64>,
+    pending_messages: Vec<(SampleTime, MidiClockMessage)>,
+}
+
+impl Default for MidiClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MidiClock {
+    /// Default tempo smoothing factor.
+    pub const DEFAULT_SMOOTHING: f64 = 0.25;
+
+    /// Create a new, stopped MIDI clock.
+    pub fn new() -> Self {
+        Self {
+            smoothing: Self::DEFAULT_SMOOTHING,
+            running: false,
+            clock_count: 0,
+            next_clock_time: 0.0,
+            samples_per_clock: None,
+            pending_messages: Vec::new(),
+        }
+    }
+
+    /// Return a new clock with the given tempo smoothing factor in range (0, 1]: the amount
+    /// a clock pulse's interval moves towards the actual tempo's interval per pulse.
+    #[must_use]
+    pub fn with_smoothing(self, smoothing: f64) -> Self {
+        let smoothing = smoothing.clamp(0.001, 1.0);
+        Self { smoothing, ..self }
+    }
+
+    /// Tempo smoothing factor.
+    pub fn smoothing(&self) -> f64 {
+        self.smoothing
+    }
+
+    /// true when the clock got started and is sending clock pulses.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Start the clock at the given sequence sample time. Sends a start message when starting
+    /// at the song's beginning, else a song position pointer and a continue message. Clock
+    /// pulses start at the next sixteenth note.
+    pub fn start(&mut self, time_base: &BeatTimeBase, sample_time: SampleTime) {
+        if self.running {
+            self.stop(sample_time);
+        }
+        let samples_per_clock = time_base.samples_per_beat() / MIDI_CLOCKS_PER_BEAT as f64;
+        let samples_per_sixteenth = samples_per_clock * MIDI_CLOCKS_PER_SIXTEENTH as f64;
+        let song_position = (sample_time as f64 / samples_per_sixteenth).ceil() as u64;
+        if song_position == 0 {
+            self.pending_messages
+                .push((sample_time, MidiClockMessage::Start));
+        } else {
+            let position = song_position.min(0x3FFF) as u16;
+            self.pending_messages
+                .push((sample_time, MidiClockMessage::SongPosition(position)));
+            self.pending_messages
+                .push((sample_time, MidiClockMessage::Continue));
+        }
+        self.running = true;
+        self.clock_count = song_position * MIDI_CLOCKS_PER_SIXTEENTH;
+        self.next_clock_time = song_position as f64 * samples_per_sixteenth;
+        self.samples_per_clock = Some(samples_per_clock);
+    }
+
+    /// Stop the clock at the given sequence sample time.
+    pub fn stop(&mut self, sample_time: SampleTime) {
+        if self.running {
+            self.pending_messages
+                .push((sample_time, MidiClockMessage::Stop));
+            self.running = false;
+        }
+    }
+
+    /// Number of clock pulses since the song's beginning.
+    pub fn clock_count(&self) -> u64 {
+        self.clock_count
+    }
+
+    /// Generate all messages in range \[start_time, end_time). Pending transport messages come
+    /// first, followed by the clock pulses in this range. Starts the clock at `start_time` when
+    /// it's not yet running.
+    pub fn process(
+        &mut self,
+        time_base: &BeatTimeBase,
+        start_time: SampleTime,
+        end_time: SampleTime,
+    ) -> Vec<(SampleTime, MidiClockMessage)> {
+        if !self.running {
+            self.start(time_base, start_time);
+        }
+        let mut messages = std::mem::take(&mut self.pending_messages);
+        let target_samples_per_clock = time_base.samples_per_beat() / MIDI_CLOCKS_PER_BEAT as f64;
+        let mut samples_per_clock = self.samples_per_clock.unwrap_or(target_samples_per_clock);
+        while (self.next_clock_time.round() as SampleTime) < end_time {
+            let clock_time = self.next_clock_time.round() as SampleTime;
+            if clock_time >= start_time {
+                messages.push((clock_time, MidiClockMessage::Clock));
+            }
+            self.clock_count += 1;
+            samples_per_clock += (target_samples_per_clock - samples_per_clock) * self.smoothing;
+            self.next_clock_time += samples_per_clock;
+        }
+        self.samples_per_clock = Some(samples_per_clock);
+        messages
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clock() {
+        let mut time_base = BeatTimeBase {
+            beats_per_min: 125.0,
+            beats_per_bar: 4,
+            samples_per_sec: 48000,
+        };
+        // 960 samples per clock
+        let mut clock = MidiClock::new();
+        let messages = clock.process(&time_base, 0, 2000);
+        assert_eq!(
+            messages,
+            vec![
+                (0, MidiClockMessage::Start),
+                (0, MidiClockMessage::Clock),
+                (960, MidiClockMessage::Clock),
+                (1920, MidiClockMessage::Clock),
+            ]
+        );
+        let messages = clock.process(&time_base, 2000, 23040);
+        assert_eq!(messages.len(), 21);
+        assert_eq!(clock.clock_count(), 24);
+
+        // tempo changes are smoothed
+        time_base.beats_per_min = 250.0;
+        let messages = clock.process(&time_base, 23040, 25000);
+        assert_eq!(
+            messages,
+            vec![
+                (23040, MidiClockMessage::Clock),
+                (23880, MidiClockMessage::Clock),
+                (24630, MidiClockMessage::Clock),
+            ]
+        );
+
+        // seek
+        clock.stop(25000);
+        time_base.beats_per_min = 125.0;
+        clock.start(&time_base, 11000);
+        let messages = clock.process(&time_base, 11000, 12000);
+        assert_eq!(
+            messages,
+            vec![
+                (25000, MidiClockMessage::Stop),
+                (11000, MidiClockMessage::SongPosition(2)),
+                (11000, MidiClockMessage::Continue),
+                (11520, MidiClockMessage::Clock),
+            ]
+        );
+        assert_eq!(clock.clock_count(), 13);
+    }
+
+    #[test]
+    fn message_bytes() {
+        assert_eq!(MidiClockMessage::Clock.to_bytes(), vec![0xF8]);
+        assert_eq!(
+            MidiClockMessage::SongPosition(300).to_bytes(),
+            vec![0xF2, 0x2C, 0x02]
+        );
+    }
+}
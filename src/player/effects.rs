@@ -0,0 +1,434 @@
+//! Live performance effects, which get applied to emitted sequence events in the player.
+
+use std::collections::VecDeque;
+
+use crate::{event::Event, phrase::RhythmIndex, time::BeatTimeStep, BeatTimeBase, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Max length of the event history which is kept for stutter effects.
+const HISTORY_BARS: f64 = 4.0;
+
+// -------------------------------------------------------------------------------------------------
+
+/// An event as emitted by a sequence: rhythm slot index, sample time, event and event duration.
+pub type SequenceEvent = (RhythmIndex, SampleTime, Option<Event>, SampleTime);
+
+// -------------------------------------------------------------------------------------------------
+
+/// Performance effect, which can be applied live to a single rhythm slot of a sequence.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PerformanceEffect {
+    /// Repeat the last emitted slice of events with the given length, until the effect stops.
+    Stutter(BeatTimeStep),
+    /// Double the density of events, by repeating each event in the middle of its step.
+    Fill,
+    /// Mute all new notes. Already playing notes are not stopped, so their tails ring out.
+    Mute,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Applies [`PerformanceEffect`]S to events of a sequence's rhythm slots.
+///
+/// Effect starts and stops are quantized to the next quantum, which by default is the next beat.
+/// To be able to stutter, the last few bars of the emitted events are buffered.
+#[derive(Debug, Clone)]
+pub struct PerformanceEffects {
+    quantum: BeatTimeStep,
+    slots: Vec<EffectSlot>,
+    sample_position: SampleTime,
+}
+
+impl Default for PerformanceEffects {
+    fn default() -> Self {
+        Self {
+            quantum: BeatTimeStep::Beats(1.0),
+            slots: Vec::new(),
+            sample_position: 0,
+        }
+    }
+}
+
+impl PerformanceEffects {
+    /// Create a new effect processor without any active effects.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new effect processor which quantizes effect starts and stops to the given step.
+    #[must_use]
+    pub fn with_quantum(self, quantum: BeatTimeStep) -> Self {
+        Self { quantum, ..self }
+    }
+
+    /// Step to which effect starts and stops get quantized.
+    pub fn quantum(&self) -> BeatTimeStep {
+        self.quantum
+    }
+    /// Set a new step in musical time, e.g. beats or bars, to which effect starts and stops get
+    /// quantized. Applies to all following effect changes. Steps must be greater than zero:
+    /// zero or negative steps apply effect changes immediately, without quantization.
+    pub fn set_quantum(&mut self, quantum: BeatTimeStep) {
+        self.quantum = quantum;
+    }
+
+    /// Currently active effect in the given rhythm slot.
+    pub fn effect(&self, rhythm_index: RhythmIndex) -> Option<PerformanceEffect> {
+        self.slots
+            .get(rhythm_index)
+            .and_then(|slot| slot.effect.as_ref().map(|effect| effect.effect))
+    }
+
+    /// Effect change which will be applied at the next quantum in the given rhythm slot, if any.
+    /// `Some(None)` stops the active effect.
+    pub fn pending_effect(&self, rhythm_index: RhythmIndex) -> Option<Option<PerformanceEffect>> {
+        self.slots
+            .get(rhythm_index)
+            .and_then(|slot| slot.pending_effect)
+    }
+
+    /// Start the given effect or stop the active effect (when passing `None`) in the given
+    /// rhythm slot at the next quantum.
+    pub fn set_effect(&mut self, rhythm_index: RhythmIndex, effect: Option<PerformanceEffect>) {
+        if self.slots.len() <= rhythm_index {
+            self.slots
+                .resize_with(rhythm_index + 1, EffectSlot::default);
+        }
+        self.slots[rhythm_index].pending_effect = Some(effect);
+    }
+
+    /// Stop all active effects at the next quantum.
+    pub fn stop_all(&mut self) {
+        for slot in &mut self.slots {
+            if slot.effect.is_some() || slot.pending_effect.is_some() {
+                slot.pending_effect = Some(None);
+            }
+        }
+    }
+
+    /// Clear buffered events and restart active effects at the given sample time, e.g. after
+    /// the sequence got seeked.
+    pub fn reset(&mut self, sample_position: SampleTime) {
+        for slot in &mut self.slots {
+            slot.history.clear();
+            if let Some(active) = slot.effect.take() {
+                if slot.pending_effect.is_none() {
+                    slot.pending_effect = Some(Some(active.effect));
+                }
+            }
+        }
+        self.sample_position = sample_position;
+    }
+
+    /// Apply effects to the given events, which got emitted by a sequence until the given sample
+    /// time, and return the resulting events, sorted by time.
+    pub fn process(
+        &mut self,
+        time_base: &BeatTimeBase,
+        events: Vec<SequenceEvent>,
+        run_until_time: SampleTime,
+    ) -> Vec<SequenceEvent> {
+        let slot_count = events
+            .iter()
+            .map(|(index, ..)| index + 1)
+            .max()
+            .unwrap_or(0);
+        if self.slots.len() < slot_count {
+            self.slots.resize_with(slot_count, EffectSlot::default);
+        }
+        let switch_time = self.next_switch_time(time_base);
+        let history_length = (time_base.samples_per_bar() * HISTORY_BARS) as SampleTime;
+        let mut slot_events = vec![Vec::new(); self.slots.len()];
+        for (rhythm_index, time, event, duration) in events {
+            slot_events[rhythm_index].push((time, event, duration));
+        }
+        let mut output = Vec::new();
+        for (rhythm_index, (slot, events)) in self.slots.iter_mut().zip(slot_events).enumerate() {
+            let mut context = SlotContext {
+                rhythm_index,
+                time_base,
+                history_length,
+                output: &mut output,
+            };
+            slot.process(&mut context, events, switch_time, run_until_time);
+        }
+        output.sort_by_key(|(_, time, ..)| *time);
+        self.sample_position = run_until_time;
+        output
+    }
+
+    fn next_switch_time(&self, time_base: &BeatTimeBase) -> SampleTime {
+        let quantum = self.quantum.to_samples(time_base);
+        if quantum <= 0.0 {
+            return self.sample_position;
+        }
+        let switch_time = ((self.sample_position as f64 / quantum).ceil() * quantum) as SampleTime;
+        switch_time.max(self.sample_position)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single event in an effect slot: sample time, event and event duration.
+type SlotEvent = (SampleTime, Option<Event>, SampleTime);
+
+/// Shared state while processing effect slots.
+struct SlotContext<'a> {
+    rhythm_index: RhythmIndex,
+    time_base: &'a BeatTimeBase,
+    history_length: SampleTime,
+    output: &'a mut Vec<SequenceEvent>,
+}
+
+/// An active effect in an effect slot.
+#[derive(Debug, Clone)]
+struct ActiveEffect {
+    effect: PerformanceEffect,
+    start_time: SampleTime,
+    // stutter state
+    slice: Vec<SlotEvent>,
+    slice_length: SampleTime,
+    emitted_until: SampleTime,
+}
+
+/// Effect state of a single rhythm slot.
+#[derive(Debug, Clone, Default)]
+struct EffectSlot {
+    effect: Option<ActiveEffect>,
+    pending_effect: Option<Option<PerformanceEffect>>,
+    history: VecDeque<SlotEvent>,
+}
+
+impl EffectSlot {
+    fn process(
+        &mut self,
+        context: &mut SlotContext,
+        events: Vec<SlotEvent>,
+        switch_time: SampleTime,
+        run_until_time: SampleTime,
+    ) {
+        let mut switch_pending = self.pending_effect.is_some() && switch_time < run_until_time;
+        for (time, event, duration) in events {
+            if switch_pending && time >= switch_time {
+                self.advance(context, switch_time);
+                self.activate(context, switch_time);
+                switch_pending = false;
+            }
+            self.advance(context, time);
+            self.apply(context, time, event, duration);
+        }
+        if switch_pending {
+            self.advance(context, switch_time);
+            self.activate(context, switch_time);
+        }
+        self.advance(context, run_until_time);
+        // forget events which can no longer be stuttered
+        let history_start = run_until_time - run_until_time.min(context.history_length);
+        while self
+            .history
+            .front()
+            .is_some_and(|(time, ..)| *time < history_start)
+        {
+            self.history.pop_front();
+        }
+    }
+
+    fn activate(&mut self, context: &SlotContext, sample_time: SampleTime) {
+        let effect = self.pending_effect.take().flatten();
+        self.effect = effect.map(|effect| {
+            let mut slice = Vec::new();
+            let mut slice_length = 0;
+            if let PerformanceEffect::Stutter(step) = effect {
+                slice_length = (step.to_samples(context.time_base) as SampleTime)
+                    .clamp(1, context.history_length.max(1));
+                let slice_start = sample_time - sample_time.min(slice_length);
+                slice = self
+                    .history
+                    .iter()
+                    .filter(|(time, ..)| *time >= slice_start && *time < sample_time)
+                    .map(|(time, event, duration)| (time - slice_start, event.clone(), *duration))
+                    .collect();
+            }
+            ActiveEffect {
+                effect,
+                start_time: sample_time,
+                slice,
+                slice_length,
+                emitted_until: sample_time,
+            }
+        });
+    }
+
+    fn advance(&mut self, context: &mut SlotContext, sample_time: SampleTime) {
+        // emit stuttered events
+        if let Some(active) = &mut self.effect {
+            if active.slice_length == 0 || active.emitted_until >= sample_time {
+                return;
+            }
+            let mut slice_start = active.start_time
+                + (active.emitted_until - active.start_time) / active.slice_length
+                    * active.slice_length;
+            while slice_start < sample_time {
+                for (offset, event, duration) in &active.slice {
+                    let time = slice_start + offset;
+                    if time >= active.emitted_until && time < sample_time {
+                        context
+                            .output
+                            .push((context.rhythm_index, time, event.clone(), *duration));
+                        self.history.push_back((time, event.clone(), *duration));
+                    }
+                }
+                slice_start += active.slice_length;
+            }
+            active.emitted_until = sample_time;
+        }
+    }
+
+    fn apply(
+        &mut self,
+        context: &mut SlotContext,
+        sample_time: SampleTime,
+        event: Option<Event>,
+        duration: SampleTime,
+    ) {
+        let mut emit = |time: SampleTime, event: Option<Event>, duration: SampleTime| {
+            self.history.push_back((time, event.clone(), duration));
+            context
+                .output
+                .push((context.rhythm_index, time, event, duration));
+        };
+        match self.effect.as_ref().map(|active| active.effect) {
+            None => emit(sample_time, event, duration),
+            Some(PerformanceEffect::Fill) => {
+                if event.is_some() && duration >= 2 {
+                    let half_duration = duration / 2;
+                    emit(sample_time, event.clone(), half_duration);
+                    emit(sample_time + half_duration, event, duration - half_duration);
+                } else {
+                    emit(sample_time, event, duration);
+                }
+            }
+            Some(PerformanceEffect::Mute) => {
+                let event = match event {
+                    Some(Event::NoteEvents(note_events)) => {
+                        // only pass note-offs, so playing notes get stopped as usual
+                        let note_events = note_events
+                            .into_iter()
+                            .map(|note_event| note_event.filter(|n| n.note.is_note_off()))
+                            .collect::<Vec<_>>();
+                        if note_events.iter().any(Option::is_some) {
+                            Some(Event::NoteEvents(note_events))
+                        } else {
+                            None
+                        }
+                    }
+                    event => event,
+                };
+                emit(sample_time, event, duration);
+            }
+            Some(PerformanceEffect::Stutter(_)) => {
+                // input events are replaced by the stuttered slice
+            }
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    fn time_base() -> BeatTimeBase {
+        BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        }
+    }
+
+    // one note event on each beat in [start, end) in the first rhythm slot
+    fn beat_events(start: SampleTime, end: SampleTime, note: Note) -> Vec<SequenceEvent> {
+        (start..end)
+            .step_by(22050)
+            .map(|time| {
+                let event = Event::NoteEvents(vec![new_note(note)]);
+                (0, time, Some(event), 22050)
+            })
+            .collect()
+    }
+
+    fn event_times(events: &[SequenceEvent]) -> Vec<(SampleTime, Option<Note>)> {
+        events
+            .iter()
+            .map(|(_, time, event, _)| {
+                let note = match event {
+                    Some(Event::NoteEvents(notes)) => notes[0].as_ref().map(|n| n.note),
+                    _ => None,
+                };
+                (*time, note)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fill_and_mute() {
+        let time_base = time_base();
+        let mut effects = PerformanceEffects::new();
+        effects.set_effect(0, Some(PerformanceEffect::Fill));
+        assert_eq!(effects.effect(0), None);
+        assert_eq!(
+            effects.pending_effect(0),
+            Some(Some(PerformanceEffect::Fill))
+        );
+        let events = effects.process(&time_base, beat_events(0, 44100, Note::C4), 44100);
+        assert_eq!(effects.effect(0), Some(PerformanceEffect::Fill));
+        assert_eq!(
+            event_times(&events),
+            vec![
+                (0, Some(Note::C4)),
+                (11025, Some(Note::C4)),
+                (22050, Some(Note::C4)),
+                (33075, Some(Note::C4)),
+            ]
+        );
+
+        // switches are quantized to the next beat
+        effects.set_effect(0, Some(PerformanceEffect::Mute));
+        let events = effects.process(&time_base, beat_events(44100, 88200, Note::C4), 88200);
+        assert_eq!(event_times(&events), vec![(44100, None), (66150, None)]);
+
+        effects.set_effect(0, None);
+        let events = effects.process(&time_base, beat_events(88200, 110250, Note::D4), 110250);
+        assert_eq!(effects.effect(0), None);
+        assert_eq!(event_times(&events), vec![(88200, Some(Note::D4))]);
+    }
+
+    #[test]
+    fn stutter() {
+        let time_base = time_base();
+        let mut effects = PerformanceEffects::new();
+        let mut events = beat_events(0, 88200, Note::C4);
+        if let Some((_, _, Some(Event::NoteEvents(notes)), _)) = events.last_mut() {
+            notes[0] = new_note(Note::G4);
+        }
+        // stutter the last beat at beat 4
+        let output = effects.process(&time_base, events, 88200);
+        assert_eq!(output.len(), 4);
+        effects.set_effect(
+            0,
+            Some(PerformanceEffect::Stutter(BeatTimeStep::Beats(1.0))),
+        );
+        let output = effects.process(&time_base, beat_events(88200, 154350, Note::C4), 154350);
+        assert_eq!(
+            event_times(&output),
+            vec![
+                (88200, Some(Note::G4)),
+                (110250, Some(Note::G4)),
+                (132300, Some(Note::G4)),
+            ]
+        );
+    }
+}
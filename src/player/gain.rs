@@ -0,0 +1,129 @@
+//! Automatic gain staging of simultaneously playing voices, as used by the `SamplePlayer`.
+
+// -------------------------------------------------------------------------------------------------
+
+/// Curve of an [`AutoGain`], which maps the ratio of playing voices to the auto gain's
+/// voice headroom to a gain factor.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AutoGainCurve {
+    /// Keep the summed power of uncorrelated voices constant: `1 / sqrt(ratio)`.
+    EqualPower,
+    /// Keep the summed amplitude of voices constant: `1 / ratio`.
+    Linear,
+    /// Custom attenuation curve: `1 / ratio^exponent`.
+    Power(f32),
+}
+
+impl AutoGainCurve {
+    fn exponent(&self) -> f32 {
+        match self {
+            Self::EqualPower => 0.5,
+            Self::Linear => 1.0,
+            Self::Power(exponent) => exponent.max(0.0),
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Attenuates the volume of new notes as the number of simultaneously playing voices grows,
+/// so dense sections with many stacked samples don't clip.
+///
+/// Voices up to the headroom voice count play unattenuated. Above that, the gain follows the
+/// given [`AutoGainCurve`], but never falls below the minimum gain.
+///
+/// Set via [`SamplePlayer::set_auto_gain`](super::SamplePlayer::set_auto_gain).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AutoGain {
+    curve: AutoGainCurve,
+    headroom_voices: usize,
+    min_gain: f32,
+}
+
+impl Default for AutoGain {
+    fn default() -> Self {
+        Self {
+            curve: AutoGainCurve::EqualPower,
+            headroom_voices: 4,
+            min_gain: 0.1,
+        }
+    }
+}
+
+impl AutoGain {
+    /// Create a new auto gain with the given curve, a headroom of 4 voices and a minimum gain
+    /// of 0.1 (-20 dB).
+    pub fn new(curve: AutoGainCurve) -> Self {
+        Self {
+            curve,
+            ..Self::default()
+        }
+    }
+
+    /// Return a new auto gain which plays up to the given number of voices unattenuated.
+    #[must_use]
+    pub fn with_headroom_voices(self, headroom_voices: usize) -> Self {
+        let headroom_voices = headroom_voices.max(1);
+        Self {
+            headroom_voices,
+            ..self
+        }
+    }
+
+    /// Return a new auto gain which never attenuates voices below the given gain.
+    #[must_use]
+    pub fn with_min_gain(self, min_gain: f32) -> Self {
+        let min_gain = min_gain.clamp(0.0, 1.0);
+        Self { min_gain, ..self }
+    }
+
+    /// The auto gain's attenuation curve.
+    pub fn curve(&self) -> AutoGainCurve {
+        self.curve
+    }
+
+    /// Number of voices which play unattenuated.
+    pub fn headroom_voices(&self) -> usize {
+        self.headroom_voices
+    }
+
+    /// Lowest gain factor the auto gain applies.
+    pub fn min_gain(&self) -> f32 {
+        self.min_gain
+    }
+
+    /// Gain factor for new notes when the given number of voices, including the new notes,
+    /// are playing.
+    pub fn gain(&self, voice_count: usize) -> f32 {
+        if voice_count <= self.headroom_voices {
+            return 1.0;
+        }
+        let ratio = voice_count as f32 / self.headroom_voices as f32;
+        ratio.powf(-self.curve.exponent()).max(self.min_gain)
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gain() {
+        let auto_gain = AutoGain::new(AutoGainCurve::Linear).with_headroom_voices(2);
+        assert_eq!(auto_gain.gain(0), 1.0);
+        assert_eq!(auto_gain.gain(2), 1.0);
+        assert_eq!(auto_gain.gain(4), 0.5);
+        assert_eq!(auto_gain.gain(100), 0.1);
+
+        let auto_gain = AutoGain::new(AutoGainCurve::EqualPower)
+            .with_headroom_voices(1)
+            .with_min_gain(0.0);
+        assert_eq!(auto_gain.gain(4), 0.5);
+        assert_eq!(auto_gain.gain(16), 0.25);
+
+        let auto_gain = AutoGain::new(AutoGainCurve::Power(0.0));
+        assert_eq!(auto_gain.gain(100), 1.0);
+    }
+}
@@ -0,0 +1,409 @@
+//! Live note triggering of rhythms with sustain and latch modes, e.g. to start patterns via
+//! incoming MIDI notes.
+
+use std::{borrow::Cow, ops::RangeInclusive};
+
+use crate::Note;
+
+// -------------------------------------------------------------------------------------------------
+
+/// How note-offs and the sustain pedal stop patterns in a [`NoteTrigger`].
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum NoteTriggerMode {
+    /// Patterns play while their note is held, or after the note got released while the sustain
+    /// pedal is down.
+    #[default]
+    Gate,
+    /// Note-ons toggle patterns on and off. Note-offs and the sustain pedal are ignored.
+    Latch,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Key and velocity split of a [`NoteTrigger`]: note-ons within the rule's note and volume
+/// ranges trigger the rule's pattern.
+///
+/// Patterns are indices into an application specific list of patterns, e.g. rhythm slots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteTriggerRule {
+    pattern: usize,
+    notes: RangeInclusive<Note>,
+    volumes: RangeInclusive<f32>,
+}
+
+impl NoteTriggerRule {
+    /// Create a new rule, which triggers the given pattern with all notes and volumes.
+    pub fn new(pattern: usize) -> Self {
+        let notes = Note::C0..=Note::G10;
+        let volumes = 0.0..=f32::MAX;
+        Self {
+            pattern,
+            notes,
+            volumes,
+        }
+    }
+
+    /// Return a new rule which only applies to notes in the given note range (key split).
+    #[must_use]
+    pub fn with_notes(self, notes: RangeInclusive<Note>) -> Self {
+        Self { notes, ..self }
+    }
+
+    /// Return a new rule which only applies to notes in the given volume range (velocity layer).
+    #[must_use]
+    pub fn with_volumes(self, volumes: RangeInclusive<f32>) -> Self {
+        Self { volumes, ..self }
+    }
+
+    /// The pattern which the rule triggers.
+    pub fn pattern(&self) -> usize {
+        self.pattern
+    }
+
+    /// The note range of the rule.
+    pub fn notes(&self) -> &RangeInclusive<Note> {
+        &self.notes
+    }
+
+    /// The volume range of the rule.
+    pub fn volumes(&self) -> &RangeInclusive<f32> {
+        &self.volumes
+    }
+
+    /// Returns true when the rule applies to the given note and volume.
+    pub fn matches(&self, note: Note, volume: f32) -> bool {
+        self.notes.contains(&note) && self.volumes.contains(&volume)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Start or stop request of a [`NoteTrigger`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NoteTriggerAction {
+    /// Start a new instance of the given pattern for the given note with the given note volume.
+    Start {
+        pattern: usize,
+        note: Note,
+        volume: f32,
+    },
+    /// Stop the instance of the given pattern which got started by the given note.
+    Stop { pattern: usize, note: Note },
+}
+
+/// A pattern instance which got started by a [`NoteTrigger`] and still is playing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TriggeredPattern {
+    pub pattern: usize,
+    pub note: Note,
+    pub volume: f32,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Tracks incoming live notes and decides when patterns, which get triggered by notes, should
+/// start and stop playing.
+///
+/// Hosts feed note-ons, note-offs and sustain pedal changes into the trigger and start or stop a
+/// pattern instance for each returned [`NoteTriggerAction`], passing the [`trigger_context`]
+/// of the note to the instance's rhythm as external context. MIDI note-ons with velocity 0 should
+/// be passed as note-offs.
+///
+/// Without rules, all notes trigger pattern 0. With rules, notes trigger the patterns of all
+/// matching rules, so one keyboard can drive several patterns via key splits and velocity layers.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NoteTrigger {
+    mode: NoteTriggerMode,
+    rules: Vec<NoteTriggerRule>,
+    sustain: bool,
+    held_notes: Vec<Note>,
+    playing_patterns: Vec<TriggeredPattern>,
+}
+
+impl NoteTrigger {
+    /// Create a new note trigger in gate mode, without rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new note trigger with the given mode.
+    #[must_use]
+    pub fn with_mode(self, mode: NoteTriggerMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    /// Return a new note trigger with the given key and velocity split rules.
+    #[must_use]
+    pub fn with_rules(self, rules: Vec<NoteTriggerRule>) -> Self {
+        Self { rules, ..self }
+    }
+
+    /// The trigger's mode.
+    pub fn mode(&self) -> NoteTriggerMode {
+        self.mode
+    }
+
+    /// Change the trigger's mode. Switching to gate mode stops all patterns which no longer
+    /// would be playing in gate mode.
+    pub fn set_mode(&mut self, mode: NoteTriggerMode) -> Vec<NoteTriggerAction> {
+        self.mode = mode;
+        self.stop_released_notes()
+    }
+
+    /// The trigger's key and velocity split rules.
+    pub fn rules(&self) -> &[NoteTriggerRule] {
+        &self.rules
+    }
+
+    /// Change the trigger's key and velocity split rules. Already playing patterns keep playing
+    /// until their notes stop them: new rules only apply to new note-ons.
+    pub fn set_rules(&mut self, rules: Vec<NoteTriggerRule>) {
+        self.rules = rules;
+    }
+
+    /// Is the sustain pedal currently down?
+    pub fn sustain(&self) -> bool {
+        self.sustain
+    }
+
+    /// Press or release the sustain pedal. In gate mode, releasing the pedal stops all patterns
+    /// of notes which no longer are held.
+    pub fn set_sustain(&mut self, sustain: bool) -> Vec<NoteTriggerAction> {
+        self.sustain = sustain;
+        self.stop_released_notes()
+    }
+
+    /// All currently playing patterns, in the order they got started.
+    pub fn playing_patterns(&self) -> &[TriggeredPattern] {
+        &self.playing_patterns
+    }
+
+    /// Handle a note-on. Retriggers the note's patterns in gate mode, when they still are
+    /// playing, and toggles the note's patterns on or off in latch mode.
+    pub fn note_on(&mut self, note: Note, volume: f32) -> Vec<NoteTriggerAction> {
+        if !self.held_notes.contains(&note) {
+            self.held_notes.push(note);
+        }
+        let mut actions = Vec::new();
+        let was_playing = self.stop_note(note, &mut actions);
+        if !was_playing || self.mode == NoteTriggerMode::Gate {
+            let patterns = if self.rules.is_empty() {
+                vec![0]
+            } else {
+                self.rules
+                    .iter()
+                    .filter(|rule| rule.matches(note, volume))
+                    .map(NoteTriggerRule::pattern)
+                    .collect()
+            };
+            for pattern in patterns {
+                self.playing_patterns.push(TriggeredPattern {
+                    pattern,
+                    note,
+                    volume,
+                });
+                actions.push(NoteTriggerAction::Start {
+                    pattern,
+                    note,
+                    volume,
+                });
+            }
+        }
+        actions
+    }
+
+    /// Handle a note-off. Stops the note's patterns in gate mode, unless the sustain pedal is
+    /// down.
+    pub fn note_off(&mut self, note: Note) -> Vec<NoteTriggerAction> {
+        self.held_notes.retain(|held_note| *held_note != note);
+        self.stop_released_notes()
+    }
+
+    /// Stop all playing patterns and forget all held notes, e.g. when the host stops playback.
+    /// The sustain pedal state is kept.
+    pub fn stop_all(&mut self) -> Vec<NoteTriggerAction> {
+        self.held_notes.clear();
+        self.playing_patterns
+            .drain(..)
+            .map(|playing| NoteTriggerAction::Stop {
+                pattern: playing.pattern,
+                note: playing.note,
+            })
+            .collect()
+    }
+
+    fn stop_note(&mut self, note: Note, actions: &mut Vec<NoteTriggerAction>) -> bool {
+        let playing_count = self.playing_patterns.len();
+        self.playing_patterns.retain(|playing| {
+            if playing.note == note {
+                actions.push(NoteTriggerAction::Stop {
+                    pattern: playing.pattern,
+                    note,
+                });
+                false
+            } else {
+                true
+            }
+        });
+        self.playing_patterns.len() != playing_count
+    }
+
+    fn stop_released_notes(&mut self) -> Vec<NoteTriggerAction> {
+        let mut actions = Vec::new();
+        if self.mode == NoteTriggerMode::Gate && !self.sustain {
+            let held_notes = &self.held_notes;
+            self.playing_patterns.retain(|playing| {
+                let held = held_notes.contains(&playing.note);
+                if !held {
+                    actions.push(NoteTriggerAction::Stop {
+                        pattern: playing.pattern,
+                        note: playing.note,
+                    });
+                }
+                held
+            });
+        }
+        actions
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// External context values for rhythms which got started by the given note and note volume:
+/// sets the `trigger_note` and `trigger_volume` values of Lua rhythm contexts.
+pub fn trigger_context(note: Note, volume: f32) -> Vec<(Cow<'static, str>, f64)> {
+    vec![
+        (Cow::Borrowed("trigger_note"), u8::from(note) as f64),
+        (Cow::Borrowed("trigger_volume"), volume as f64),
+    ]
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn start(pattern: usize, note: Note, volume: f32) -> NoteTriggerAction {
+        NoteTriggerAction::Start {
+            pattern,
+            note,
+            volume,
+        }
+    }
+
+    fn stop(pattern: usize, note: Note) -> NoteTriggerAction {
+        NoteTriggerAction::Stop { pattern, note }
+    }
+
+    fn playing_notes(trigger: &NoteTrigger) -> Vec<(Note, f32)> {
+        trigger
+            .playing_patterns()
+            .iter()
+            .map(|playing| (playing.note, playing.volume))
+            .collect()
+    }
+
+    #[test]
+    fn gate_and_sustain() {
+        let mut trigger = NoteTrigger::new();
+        assert_eq!(
+            trigger.note_on(Note::C4, 1.0),
+            vec![start(0, Note::C4, 1.0)]
+        );
+        assert_eq!(trigger.note_off(Note::C4), vec![stop(0, Note::C4)]);
+
+        // sustained notes keep playing until the pedal gets released
+        assert!(trigger.set_sustain(true).is_empty());
+        trigger.note_on(Note::C4, 1.0);
+        trigger.note_on(Note::E4, 0.5);
+        assert!(trigger.note_off(Note::C4).is_empty());
+        assert!(trigger.note_off(Note::E4).is_empty());
+        assert_eq!(
+            playing_notes(&trigger),
+            vec![(Note::C4, 1.0), (Note::E4, 0.5)]
+        );
+        // sustained notes get retriggered
+        assert_eq!(
+            trigger.note_on(Note::C4, 0.25),
+            vec![stop(0, Note::C4), start(0, Note::C4, 0.25)]
+        );
+        // held notes survive releasing the pedal
+        assert_eq!(trigger.set_sustain(false), vec![stop(0, Note::E4)]);
+        assert_eq!(playing_notes(&trigger), vec![(Note::C4, 0.25)]);
+        assert_eq!(trigger.stop_all(), vec![stop(0, Note::C4)]);
+    }
+
+    #[test]
+    fn latch() {
+        let mut trigger = NoteTrigger::new().with_mode(NoteTriggerMode::Latch);
+        trigger.note_on(Note::C4, 1.0);
+        assert!(trigger.note_off(Note::C4).is_empty());
+        assert!(trigger.set_sustain(false).is_empty());
+        assert_eq!(playing_notes(&trigger), vec![(Note::C4, 1.0)]);
+        assert_eq!(trigger.note_on(Note::C4, 1.0), vec![stop(0, Note::C4)]);
+        assert!(trigger.playing_patterns().is_empty());
+
+        // switching to gate mode stops released notes
+        trigger.note_on(Note::C4, 1.0);
+        trigger.note_off(Note::C4);
+        trigger.note_on(Note::E4, 1.0);
+        assert_eq!(
+            trigger.set_mode(NoteTriggerMode::Gate),
+            vec![stop(0, Note::C4)]
+        );
+        assert_eq!(playing_notes(&trigger), vec![(Note::E4, 1.0)]);
+
+        assert_eq!(
+            trigger_context(Note::C4, 0.5),
+            vec![
+                (Cow::Borrowed("trigger_note"), 48.0),
+                (Cow::Borrowed("trigger_volume"), 0.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn splits() {
+        // bass pattern below C4, and two velocity layers above
+        let mut trigger = NoteTrigger::new().with_rules(vec![
+            NoteTriggerRule::new(0).with_notes(Note::C0..=Note::B3),
+            NoteTriggerRule::new(1)
+                .with_notes(Note::C4..=Note::G10)
+                .with_volumes(0.0..=0.5),
+            NoteTriggerRule::new(2)
+                .with_notes(Note::C4..=Note::G10)
+                .with_volumes(0.5..=1.0),
+        ]);
+        assert_eq!(
+            trigger.note_on(Note::C3, 1.0),
+            vec![start(0, Note::C3, 1.0)]
+        );
+        assert_eq!(
+            trigger.note_on(Note::C4, 0.2),
+            vec![start(1, Note::C4, 0.2)]
+        );
+        assert_eq!(
+            trigger.note_on(Note::E4, 0.8),
+            vec![start(2, Note::E4, 0.8)]
+        );
+        // overlapping layers trigger both patterns
+        assert_eq!(
+            trigger.note_on(Note::G4, 0.5),
+            vec![start(1, Note::G4, 0.5), start(2, Note::G4, 0.5)]
+        );
+        assert_eq!(
+            trigger.note_off(Note::G4),
+            vec![stop(1, Note::G4), stop(2, Note::G4)]
+        );
+
+        // new rules only apply to new notes
+        trigger.set_rules(vec![NoteTriggerRule::new(3)]);
+        assert_eq!(
+            trigger.note_on(Note::G4, 0.5),
+            vec![start(3, Note::G4, 0.5)]
+        );
+        assert_eq!(trigger.note_off(Note::C4), vec![stop(1, Note::C4)]);
+        assert_eq!(trigger.rules().len(), 1);
+    }
+}
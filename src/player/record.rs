@@ -0,0 +1,776 @@
+//! Beat synced recording of the player's output into WAV files.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError, TrySendError};
+
+use afplay::{
+    source::{file::preloaded::PreloadedFileSource, AudioSource, AudioSourceTime},
+    utils::speed_from_note,
+    AudioFilePlaybackId, FilePlaybackOptions,
+};
+
+use crate::{event::InstrumentId, player::SamplePool, BeatTimeBase, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Size of the RIFF header, fmt and acid chunks, which precede the data chunk's samples.
+const WAV_HEADER_SIZE: u64 = 12 + (8 + 16) + (8 + 24) + 8;
+
+/// Number of sample frames in a single queued or rendered recording block.
+const RECORDING_BLOCK_FRAMES: usize = 2048;
+
+/// Number of blocks which can be queued for a recording's writer thread.
+const RECORDING_QUEUE_BLOCKS: usize = 64;
+
+/// Time the writer thread sleeps when its queue is empty. The writer polls instead of waiting,
+/// so real-time threads which push blocks never need to wake it up.
+const RECORDING_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+// -------------------------------------------------------------------------------------------------
+
+/// Records interleaved output buffers into a 32-bit float WAV file.
+///
+/// Recording starts sample accurately at the first bar boundary of the sequence's time base,
+/// which is at or after the first recorded buffer's start position. Samples before the
+/// boundary are skipped, so recordings only contain full bars and line up with the grid when
+/// imported into a DAW.
+///
+/// The time base's tempo and bar length are embedded as ACID chunk, which most DAWs and
+/// samplers read, so recordings can be auto-stretched to the project's tempo.
+///
+/// Recordings need to be finished via [`finish`](Self::finish), else the WAV header's sizes
+/// are not updated.
+#[derive(Debug)]
+pub struct OutputRecorder<W: Write + Seek = BufWriter<File>> {
+    writer: W,
+    time_base: BeatTimeBase,
+    channel_count: usize,
+    start_time: Option<SampleTime>,
+    recorded_frames: u64,
+}
+
+impl OutputRecorder {
+    /// Create a new recorder which writes into a new WAV file at the given path.
+    ///
+    /// Returns error when the file can not be created.
+    pub fn create<P: AsRef<Path>>(
+        file_path: P,
+        time_base: &BeatTimeBase,
+        channel_count: usize,
+    ) -> io::Result<Self> {
+        let writer = BufWriter::new(File::create(file_path)?);
+        Self::new(writer, time_base, channel_count)
+    }
+}
+
+impl<W: Write + Seek> OutputRecorder<W> {
+    /// Create a new recorder which writes into the given writer.
+    ///
+    /// Returns error when the channel count is 0 or when writing the WAV header fails.
+    pub fn new(writer: W, time_base: &BeatTimeBase, channel_count: usize) -> io::Result<Self> {
+        if channel_count == 0 || channel_count > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid recording channel count: '{}'", channel_count),
+            ));
+        }
+        let mut recorder = Self {
+            writer,
+            time_base: *time_base,
+            channel_count,
+            start_time: None,
+            recorded_frames: 0,
+        };
+        recorder.write_header()?;
+        Ok(recorder)
+    }
+
+    /// Number of interleaved channels in the recording.
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Sample time of the bar boundary at which the recording started, if it started.
+    pub fn start_time(&self) -> Option<SampleTime> {
+        self.start_time
+    }
+
+    /// Number of recorded sample frames.
+    pub fn recorded_frames(&self) -> u64 {
+        self.recorded_frames
+    }
+
+    /// Length of the recording in beats, using the recorder's time base.
+    pub fn recorded_beats(&self) -> f64 {
+        self.recorded_frames as f64 / self.time_base.samples_per_beat()
+    }
+
+    /// Record an interleaved buffer, which starts at the given sample position.
+    ///
+    /// The first call arms the recorder at the next bar boundary. When the sample position
+    /// jumps, e.g. when the host loops, the buffer gets appended to the recording.
+    pub fn write(&mut self, buffer: &[f32], sample_position: SampleTime) -> io::Result<()> {
+        let start_time = match self.start_time {
+            Some(start_time) => start_time,
+            None => {
+                let start_time = self.next_bar_boundary(sample_position);
+                self.start_time = Some(start_time);
+                start_time
+            }
+        };
+        // skip frames before the recording start
+        let skip_frames = if self.recorded_frames == 0 {
+            start_time.saturating_sub(sample_position) as usize
+        } else {
+            0
+        };
+        for frame in buffer.chunks_exact(self.channel_count).skip(skip_frames) {
+            for sample in frame {
+                self.writer.write_all(&sample.to_le_bytes())?;
+            }
+            self.recorded_frames += 1;
+        }
+        Ok(())
+    }
+
+    /// Finish the recording by updating the WAV header's sizes and the recorded length.
+    /// Returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn next_bar_boundary(&self, sample_position: SampleTime) -> SampleTime {
+        let samples_per_bar = self.time_base.samples_per_bar();
+        if samples_per_bar <= 0.0 {
+            return sample_position;
+        }
+        // avoid float rounding issues at bar boundaries
+        let bar = ((sample_position as f64 - 0.5) / samples_per_bar)
+            .ceil()
+            .max(0.0);
+        (bar * samples_per_bar).round() as SampleTime
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let channel_count = self.channel_count as u16;
+        let sample_rate = self.time_base.samples_per_sec;
+        let block_align = channel_count as u32 * 4;
+        let data_size = self.recorded_frames * block_align as u64;
+        let riff_size = WAV_HEADER_SIZE - 8 + data_size;
+        if riff_size > u32::MAX as u64 {
+            return Err(io::Error::other(
+                "recording exceeds the maximum WAV file size",
+            ));
+        }
+        let w = &mut self.writer;
+        w.seek(SeekFrom::Start(0))?;
+        // RIFF header
+        w.write_all(b"RIFF")?;
+        w.write_all(&(riff_size as u32).to_le_bytes())?;
+        w.write_all(b"WAVE")?;
+        // format: IEEE float
+        w.write_all(b"fmt ")?;
+        w.write_all(&16_u32.to_le_bytes())?;
+        w.write_all(&3_u16.to_le_bytes())?;
+        w.write_all(&channel_count.to_le_bytes())?;
+        w.write_all(&sample_rate.to_le_bytes())?;
+        w.write_all(&(sample_rate * block_align).to_le_bytes())?;
+        w.write_all(&(block_align as u16).to_le_bytes())?;
+        w.write_all(&32_u16.to_le_bytes())?;
+        // tempo and meter: ACID chunk with stretch flag set
+        let beats = self.recorded_beats().round() as u32;
+        w.write_all(b"acid")?;
+        w.write_all(&24_u32.to_le_bytes())?;
+        w.write_all(&0x04_u32.to_le_bytes())?;
+        w.write_all(&60_u16.to_le_bytes())?;
+        w.write_all(&0x8000_u16.to_le_bytes())?;
+        w.write_all(&0.0_f32.to_le_bytes())?;
+        w.write_all(&beats.to_le_bytes())?;
+        w.write_all(&4_u16.to_le_bytes())?;
+        w.write_all(&(self.time_base.beats_per_bar as u16).to_le_bytes())?;
+        w.write_all(&self.time_base.beats_per_min.to_le_bytes())?;
+        // samples
+        w.write_all(b"data")?;
+        w.write_all(&(data_size as u32).to_le_bytes())?;
+        Ok(())
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A running [`OutputRecorder`] recording, which writes on a separate writer thread.
+///
+/// Host output buffers, see [`write`](Self::write), get copied into preallocated blocks, which
+/// are passed to the writer thread via lock-free queues, so recording never blocks, allocates
+/// or accesses files in real-time audio threads. When the writer can't keep up, blocks get
+/// dropped and are counted in [`dropped_frames`](Self::dropped_frames).
+///
+/// Playback recordings instead record the player's own output: the player passes all samples
+/// it plays and stops, and the writer thread renders them with the player's samples. Like
+/// in the [`StemRenderer`](super::stems::StemRenderer), sample regions and performance effects
+/// are not applied.
+///
+/// Recordings need to be finished via [`finish`](Self::finish), which waits until all queued
+/// blocks got written.
+#[derive(Debug)]
+pub struct OutputRecording {
+    time_base: BeatTimeBase,
+    channel_count: usize,
+    records_playback: bool,
+    sender: Sender<RecordingMessage>,
+    free_blocks: Receiver<Vec<f32>>,
+    spare_block: Option<Vec<f32>>,
+    recorded_frames: Arc<AtomicU64>,
+    dropped_frames: u64,
+    writer_thread: JoinHandle<io::Result<()>>,
+}
+
+impl OutputRecording {
+    /// Start a new recording of host output buffers into a new WAV file at the given path.
+    ///
+    /// Returns error when the file can not be created.
+    pub fn create<P: AsRef<Path>>(
+        file_path: P,
+        time_base: &BeatTimeBase,
+        channel_count: usize,
+    ) -> io::Result<Self> {
+        let recorder = OutputRecorder::create(file_path, time_base, channel_count)?;
+        Ok(Self::spawn(recorder, None))
+    }
+
+    /// Start a new recording of the player's own playback into a new WAV file at the given
+    /// path, rendering played samples from the given sample pool.
+    ///
+    /// Returns error when the file can not be created.
+    pub(crate) fn create_playback<P: AsRef<Path>>(
+        file_path: P,
+        time_base: &BeatTimeBase,
+        channel_count: usize,
+        sample_pool: Arc<RwLock<SamplePool>>,
+    ) -> io::Result<Self> {
+        let recorder = OutputRecorder::create(file_path, time_base, channel_count)?;
+        let mixer = PlaybackMixer::new(sample_pool, time_base, recorder.channel_count());
+        Ok(Self::spawn(recorder, Some(mixer)))
+    }
+
+    fn spawn(recorder: OutputRecorder, mixer: Option<PlaybackMixer>) -> Self {
+        let time_base = recorder.time_base;
+        let channel_count = recorder.channel_count;
+        let records_playback = mixer.is_some();
+        let (sender, receiver) = crossbeam_channel::bounded(RECORDING_QUEUE_BLOCKS);
+        let (free_sender, free_blocks) = crossbeam_channel::bounded(RECORDING_QUEUE_BLOCKS);
+        if !records_playback {
+            for _ in 0..RECORDING_QUEUE_BLOCKS {
+                let block = Vec::with_capacity(RECORDING_BLOCK_FRAMES * channel_count);
+                free_sender
+                    .try_send(block)
+                    .expect("Failed to allocate recording blocks");
+            }
+        }
+        let recorded_frames = Arc::new(AtomicU64::new(0));
+        let writer_thread = std::thread::Builder::new()
+            .name("afseq-recording".to_string())
+            .spawn({
+                let recorded_frames = Arc::clone(&recorded_frames);
+                move || run_writer(recorder, mixer, receiver, free_sender, recorded_frames)
+            })
+            .expect("Failed to spawn recording thread");
+        Self {
+            time_base,
+            channel_count,
+            records_playback,
+            sender,
+            free_blocks,
+            spare_block: None,
+            recorded_frames,
+            dropped_frames: 0,
+            writer_thread,
+        }
+    }
+
+    /// Number of interleaved channels in the recording.
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Returns true when the recording records the player's own playback instead of host
+    /// output buffers.
+    pub fn records_playback(&self) -> bool {
+        self.records_playback
+    }
+
+    /// Number of sample frames which got written so far.
+    pub fn recorded_frames(&self) -> u64 {
+        self.recorded_frames.load(Ordering::Relaxed)
+    }
+
+    /// Length of the written recording in beats, using the recording's time base.
+    pub fn recorded_beats(&self) -> f64 {
+        self.recorded_frames() as f64 / self.time_base.samples_per_beat()
+    }
+
+    /// Number of host output sample frames which got dropped, because the writer thread could
+    /// not keep up.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+
+    /// Queue an interleaved host output buffer, which starts at the given sample position, see
+    /// [`OutputRecorder::write`]. Does not block or allocate, so this can be called in
+    /// real-time audio threads.
+    ///
+    /// Returns error when the writer thread stopped, e.g. because writing the file failed.
+    /// Use [`finish`](Self::finish) to fetch the writer's error.
+    pub fn write(&mut self, buffer: &[f32], sample_position: SampleTime) -> io::Result<()> {
+        let block_samples = RECORDING_BLOCK_FRAMES * self.channel_count;
+        let mut position = sample_position;
+        for chunk in buffer.chunks(block_samples) {
+            let frames = (chunk.len() / self.channel_count) as u64;
+            let block = self
+                .spare_block
+                .take()
+                .or_else(|| self.free_blocks.try_recv().ok());
+            let Some(mut block) = block else {
+                self.dropped_frames += frames;
+                position += frames;
+                continue;
+            };
+            block.clear();
+            block.extend_from_slice(chunk);
+            match self
+                .sender
+                .try_send(RecordingMessage::Buffer(block, position))
+            {
+                Ok(()) => (),
+                Err(TrySendError::Full(message)) => {
+                    // keep the block, so it doesn't get deallocated here
+                    if let RecordingMessage::Buffer(block, _) = message {
+                        self.spare_block = Some(block);
+                    }
+                    self.dropped_frames += frames;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    return Err(io::Error::other("recording writer thread stopped"));
+                }
+            }
+            position += frames;
+        }
+        Ok(())
+    }
+
+    /// Record a sample of a playback recording, which starts playing at the given output time.
+    /// Does nothing in host output recordings, as do all other playback functions.
+    pub(crate) fn play_sample(
+        &self,
+        playback_id: AudioFilePlaybackId,
+        instrument: InstrumentId,
+        note: u8,
+        volume: f32,
+        time: SampleTime,
+    ) {
+        self.send(RecordingMessage::Play {
+            playback_id,
+            instrument,
+            note,
+            volume,
+            time,
+        });
+    }
+
+    /// Stop a recorded sample of a playback recording at the given output time.
+    pub(crate) fn stop_sample(&self, playback_id: AudioFilePlaybackId, time: SampleTime) {
+        self.send(RecordingMessage::Stop { playback_id, time });
+    }
+
+    /// Stop all recorded samples of a playback recording at the given output time.
+    pub(crate) fn stop_all_samples(&self, time: SampleTime) {
+        self.send(RecordingMessage::StopAll(time));
+    }
+
+    /// Render a playback recording until the given output time, after all samples until this
+    /// time got played. `start_offset` is the output time of the sequence's start, so the
+    /// recording starts at the sequence's bar boundaries.
+    pub(crate) fn advance(&self, time: SampleTime, start_offset: SampleTime) {
+        self.send(RecordingMessage::Advance { time, start_offset });
+    }
+
+    fn send(&self, message: RecordingMessage) {
+        if !self.records_playback {
+            return;
+        }
+        // playback messages are sent from the player's thread, which may wait for the writer
+        if self.sender.send(message).is_err() {
+            log::warn!(target: "Player", "Recording writer thread stopped");
+        }
+    }
+
+    /// Finish the recording: waits until all queued blocks got written and updates the WAV
+    /// header. Returns the writer thread's error, if writing failed.
+    pub fn finish(self) -> io::Result<()> {
+        let Self {
+            sender,
+            writer_thread,
+            dropped_frames,
+            ..
+        } = self;
+        drop(sender);
+        if dropped_frames > 0 {
+            log::warn!(target: "Player",
+                "Dropped {} frames in output recording: the writer could not keep up", dropped_frames
+            );
+        }
+        writer_thread
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("recording writer thread panicked")))
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Messages of an [`OutputRecording`] to its writer thread.
+#[derive(Debug)]
+enum RecordingMessage {
+    /// An interleaved host output buffer and its sample position.
+    Buffer(Vec<f32>, SampleTime),
+    /// A sample got played in the player.
+    Play {
+        playback_id: AudioFilePlaybackId,
+        instrument: InstrumentId,
+        note: u8,
+        volume: f32,
+        time: SampleTime,
+    },
+    /// A sample got stopped in the player.
+    Stop {
+        playback_id: AudioFilePlaybackId,
+        time: SampleTime,
+    },
+    /// All samples got stopped in the player.
+    StopAll(SampleTime),
+    /// The player played all samples until the given time.
+    Advance {
+        time: SampleTime,
+        start_offset: SampleTime,
+    },
+}
+
+/// Writes queued recording messages until the recording gets finished or writing fails.
+fn run_writer(
+    mut recorder: OutputRecorder,
+    mut mixer: Option<PlaybackMixer>,
+    receiver: Receiver<RecordingMessage>,
+    free_blocks: Sender<Vec<f32>>,
+    recorded_frames: Arc<AtomicU64>,
+) -> io::Result<()> {
+    loop {
+        let message = match receiver.try_recv() {
+            Ok(message) => message,
+            Err(TryRecvError::Empty) => {
+                std::thread::sleep(RECORDING_POLL_INTERVAL);
+                continue;
+            }
+            Err(TryRecvError::Disconnected) => break,
+        };
+        match (message, &mut mixer) {
+            (RecordingMessage::Buffer(block, sample_position), _) => {
+                let result = recorder.write(&block, sample_position);
+                let _ = free_blocks.try_send(block);
+                result?;
+            }
+            (
+                RecordingMessage::Play {
+                    playback_id,
+                    instrument,
+                    note,
+                    volume,
+                    time,
+                },
+                Some(mixer),
+            ) => mixer.play(playback_id, instrument, note, volume, time),
+            (RecordingMessage::Stop { playback_id, time }, Some(mixer)) => {
+                mixer.stop(playback_id, time);
+            }
+            (RecordingMessage::StopAll(time), Some(mixer)) => mixer.stop_all(time),
+            (RecordingMessage::Advance { time, start_offset }, Some(mixer)) => {
+                mixer.render_until(time, start_offset, &mut recorder)?;
+            }
+            (_, None) => (),
+        }
+        recorded_frames.store(recorder.recorded_frames(), Ordering::Relaxed);
+    }
+    recorder.finish()?;
+    Ok(())
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A sample which plays in a [`PlaybackMixer`].
+struct MixerVoice {
+    playback_id: AudioFilePlaybackId,
+    sample: PreloadedFileSource,
+    sample_position: u64,
+    start_time: SampleTime,
+    stop_time: Option<SampleTime>,
+}
+
+/// Renders the samples of a playback recording on the writer thread.
+struct PlaybackMixer {
+    sample_pool: Arc<RwLock<SamplePool>>,
+    sample_rate: u32,
+    channel_count: usize,
+    voices: Vec<MixerVoice>,
+    position: Option<SampleTime>,
+    buffer: Vec<f32>,
+    sample_buffer: Vec<f32>,
+}
+
+impl PlaybackMixer {
+    fn new(
+        sample_pool: Arc<RwLock<SamplePool>>,
+        time_base: &BeatTimeBase,
+        channel_count: usize,
+    ) -> Self {
+        Self {
+            sample_pool,
+            sample_rate: time_base.samples_per_sec,
+            channel_count,
+            voices: Vec::new(),
+            position: None,
+            buffer: Vec::new(),
+            sample_buffer: Vec::new(),
+        }
+    }
+
+    fn play(
+        &mut self,
+        playback_id: AudioFilePlaybackId,
+        instrument: InstrumentId,
+        note: u8,
+        volume: f32,
+        time: SampleTime,
+    ) {
+        let playback_options = FilePlaybackOptions::default().speed(speed_from_note(note));
+        let sample_pool = self
+            .sample_pool
+            .read()
+            .expect("Failed to access sample pool");
+        match sample_pool.get_sample(instrument, playback_options, self.sample_rate) {
+            Ok(mut sample) => {
+                sample.set_volume(volume);
+                // samples which got played too late start right away
+                let start_time = time.max(self.position.unwrap_or(0));
+                self.voices.push(MixerVoice {
+                    playback_id,
+                    sample,
+                    sample_position: 0,
+                    start_time,
+                    stop_time: None,
+                });
+            }
+            Err(err) => {
+                log::warn!(target: "Player", "Failed to record sample {}: {}", instrument, err);
+            }
+        }
+    }
+
+    fn stop(&mut self, playback_id: AudioFilePlaybackId, time: SampleTime) {
+        for voice in &mut self.voices {
+            if voice.playback_id == playback_id {
+                voice.stop_time = Some(voice.stop_time.map_or(time, |stop| stop.min(time)));
+            }
+        }
+    }
+
+    fn stop_all(&mut self, time: SampleTime) {
+        for voice in &mut self.voices {
+            voice.stop_time = Some(voice.stop_time.map_or(time, |stop| stop.min(time)));
+        }
+    }
+
+    fn render_until(
+        &mut self,
+        time: SampleTime,
+        start_offset: SampleTime,
+        recorder: &mut OutputRecorder,
+    ) -> io::Result<()> {
+        let mut position = *self.position.get_or_insert(time.min(start_offset));
+        while position < time {
+            let block_end = time.min(position + RECORDING_BLOCK_FRAMES as SampleTime);
+            let frames = (block_end - position) as usize;
+            self.buffer.clear();
+            self.buffer.resize(frames * self.channel_count, 0.0);
+            for voice in &mut self.voices {
+                Self::render_voice(
+                    voice,
+                    &mut self.buffer,
+                    &mut self.sample_buffer,
+                    self.channel_count,
+                    position,
+                    block_end,
+                );
+            }
+            self.voices.retain(|voice| {
+                !voice.sample.is_exhausted()
+                    && voice.stop_time.map_or(true, |stop| stop > block_end)
+            });
+            recorder.write(&self.buffer, position.saturating_sub(start_offset))?;
+            position = block_end;
+        }
+        self.position = Some(position);
+        Ok(())
+    }
+
+    fn render_voice(
+        voice: &mut MixerVoice,
+        buffer: &mut [f32],
+        sample_buffer: &mut Vec<f32>,
+        channel_count: usize,
+        block_start: SampleTime,
+        block_end: SampleTime,
+    ) {
+        let start_time = voice.start_time.max(block_start);
+        let stop_time = voice.stop_time.unwrap_or(block_end).min(block_end);
+        if start_time >= stop_time || voice.sample.is_exhausted() {
+            return;
+        }
+        let sample_channel_count = voice.sample.channel_count().max(1);
+        let frames = (stop_time - start_time) as usize;
+        sample_buffer.resize(frames * sample_channel_count, 0.0);
+        let source_time = AudioSourceTime {
+            pos_in_frames: voice.sample_position,
+        };
+        let written = voice.sample.write(sample_buffer, &source_time);
+        let written_frames = written / sample_channel_count;
+        // mix into the block, wrapping sample channels around output channels
+        let frame_offset = (start_time - block_start) as usize;
+        for (frame_index, frame) in sample_buffer[..written_frames * sample_channel_count]
+            .chunks_exact(sample_channel_count)
+            .enumerate()
+        {
+            let output_frame = (frame_offset + frame_index) * channel_count;
+            for channel in 0..channel_count {
+                buffer[output_frame + channel] += frame[channel % sample_channel_count];
+            }
+        }
+        voice.sample_position += written_frames as u64;
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn record() -> io::Result<()> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 8,
+        };
+        // a bar has 16 frames: start recording at frame 16
+        let mut recorder = OutputRecorder::new(Cursor::new(Vec::new()), &time_base, 2)?;
+        assert!(OutputRecorder::new(Cursor::new(Vec::new()), &time_base, 0).is_err());
+        let buffer = (0..24)
+            .flat_map(|frame| [frame as f32, -frame as f32])
+            .collect::<Vec<_>>();
+        recorder.write(&buffer[..20], 6)?;
+        assert_eq!(recorder.start_time(), Some(16));
+        assert_eq!(recorder.recorded_frames(), 0);
+        recorder.write(&buffer[20..], 16)?;
+        assert_eq!(recorder.recorded_frames(), 14);
+        recorder.write(&buffer[..36], 0)?;
+        assert_eq!(recorder.recorded_frames(), 32);
+        assert_eq!(recorder.recorded_beats(), 8.0);
+
+        let bytes = recorder.finish()?.into_inner();
+        assert_eq!(bytes.len() as u64, WAV_HEADER_SIZE + 32 * 2 * 4);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[36..40], b"acid");
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let f32_at =
+            |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(u32_at(4) as usize, bytes.len() - 8);
+        // beats, meter and tempo
+        assert_eq!(u32_at(56), 8);
+        assert_eq!(&bytes[60..64], &[4, 0, 4, 0]);
+        assert_eq!(f32_at(64), 120.0);
+        // first recorded sample is the first frame of the bar
+        assert_eq!(&bytes[68..72], b"data");
+        assert_eq!(u32_at(72), 32 * 2 * 4);
+        assert_eq!(f32_at(76), 10.0);
+        assert_eq!(f32_at(80), -10.0);
+        Ok(())
+    }
+
+    #[test]
+    fn record_on_writer_thread() -> io::Result<()> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 8,
+        };
+        let file_path = std::env::temp_dir().join("afseq-record-on-writer-thread.wav");
+        let mut recording = OutputRecording::create(&file_path, &time_base, 2)?;
+        assert!(!recording.records_playback());
+        let buffer = vec![0.5; RECORDING_BLOCK_FRAMES * 3 * 2];
+        recording.write(&buffer, 0)?;
+        assert_eq!(recording.dropped_frames(), 0);
+        recording.finish()?;
+        let bytes = std::fs::read(&file_path)?;
+        std::fs::remove_file(&file_path)?;
+        assert_eq!(bytes.len(), WAV_HEADER_SIZE as usize + buffer.len() * 4);
+        Ok(())
+    }
+
+    #[test]
+    fn record_playback() -> io::Result<()> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        let sample_pool = Arc::new(RwLock::new(SamplePool::new()));
+        let file_path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/assets/tone.wav");
+        let instrument = sample_pool.read().unwrap().load_sample(file_path).unwrap();
+        let file_path = std::env::temp_dir().join("afseq-record-playback.wav");
+        let recording =
+            OutputRecording::create_playback(&file_path, &time_base, 2, Arc::clone(&sample_pool))?;
+        assert!(recording.records_playback());
+        // the sequence starts at output time 1000: play a note from sequence time 100 to 200
+        recording.play_sample(0, instrument, 60, 1.0, 1100);
+        recording.stop_sample(0, 1200);
+        recording.advance(2000, 1000);
+        recording.finish()?;
+        let bytes = std::fs::read(&file_path)?;
+        std::fs::remove_file(&file_path)?;
+        assert_eq!(bytes.len() as u64, WAV_HEADER_SIZE + 1000 * 2 * 4);
+        let samples = bytes[WAV_HEADER_SIZE as usize..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert!(samples[..100 * 2].iter().all(|sample| *sample == 0.0));
+        assert!(samples[100 * 2..200 * 2]
+            .iter()
+            .any(|sample| *sample != 0.0));
+        assert!(samples[200 * 2..].iter().all(|sample| *sample == 0.0));
+        Ok(())
+    }
+}
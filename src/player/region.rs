@@ -0,0 +1,347 @@
+//! Per instrument sample playback regions, as used by the `SamplePlayer`.
+
+use crate::event::{EventData, EventDataValue};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Note event extra data key, which overrides a region's start time in seconds.
+pub const SAMPLE_START_KEY: &str = "sample_start";
+/// Note event extra data key, which overrides a region's end time in seconds.
+pub const SAMPLE_END_KEY: &str = "sample_end";
+/// Note event extra data key, which overrides a region's reverse flag.
+pub const SAMPLE_REVERSE_KEY: &str = "sample_reverse";
+/// Note event extra data key, which overrides a region's loop start time in seconds.
+pub const SAMPLE_LOOP_START_KEY: &str = "sample_loop_start";
+/// Note event extra data key, which overrides a region's loop end time in seconds.
+pub const SAMPLE_LOOP_END_KEY: &str = "sample_loop_end";
+
+/// Max length of rendered looped regions in seconds.
+const MAX_LOOPED_REGION_SECONDS: f64 = 60.0;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Region property, which can be controlled via parameter change events, see
+/// [`SamplePlayer::set_region_parameter`](super::SamplePlayer::set_region_parameter).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SampleRegionParameter {
+    /// Start time in seconds.
+    Start,
+    /// End time in seconds. Values <= 0 remove the end.
+    End,
+    /// Reverse flag: values >= 0.5 play the region backwards.
+    Reverse,
+    /// Loop start time in seconds.
+    LoopStart,
+    /// Loop end time in seconds. Values <= 0 remove the loop.
+    LoopEnd,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Playback region of an instrument's sample, e.g. to play slices of a sample file, or to play
+/// samples backwards or looped.
+///
+/// Regions can be set per instrument in the [`SamplePlayer`](super::SamplePlayer), get changed
+/// via parameter change events, and get overridden by single notes via the note event's
+/// `sample_start`, `sample_end`, `sample_reverse`, `sample_loop_start` and `sample_loop_end`
+/// extra data.
+///
+/// Loop points are sample times, which get clamped to the region. Looped regions play until
+/// the note gets stopped, but at most for a minute.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct SampleRegion {
+    start: f64,
+    end: Option<f64>,
+    reversed: bool,
+    loop_range: Option<(f64, f64)>,
+}
+
+impl SampleRegion {
+    /// Create a new region with the given start and optional end time in seconds. When no end
+    /// is set, the sample plays until its end. Negative start times and ends before the start
+    /// are clamped.
+    pub fn new<E: Into<Option<f64>>>(start: f64, end: E) -> Self {
+        let start = Self::valid_time(start);
+        let end = end.into().map(|end| Self::valid_time(end).max(start));
+        let reversed = false;
+        let loop_range = None;
+        Self {
+            start,
+            end,
+            reversed,
+            loop_range,
+        }
+    }
+
+    /// Return a copy of the region which plays the region backwards.
+    #[must_use]
+    pub fn with_reversed(self, reversed: bool) -> Self {
+        Self { reversed, ..self }
+    }
+
+    /// Return a copy of the region which loops the given loop range in seconds, or which
+    /// doesn't loop. Loop ends before the loop start are clamped.
+    #[must_use]
+    pub fn with_loop(self, loop_range: Option<(f64, f64)>) -> Self {
+        let loop_range = loop_range.map(|(start, end)| {
+            let start = Self::valid_time(start);
+            (start, Self::valid_time(end).max(start))
+        });
+        Self { loop_range, ..self }
+    }
+
+    /// Return a copy of the region with the given region parameter changed.
+    #[must_use]
+    pub fn with_parameter(self, parameter: SampleRegionParameter, value: f64) -> Self {
+        match parameter {
+            SampleRegionParameter::Start => Self::new(value, self.end)
+                .with_reversed(self.reversed)
+                .with_loop(self.loop_range),
+            SampleRegionParameter::End => {
+                let end = if value > 0.0 { Some(value) } else { None };
+                Self::new(self.start, end)
+                    .with_reversed(self.reversed)
+                    .with_loop(self.loop_range)
+            }
+            SampleRegionParameter::Reverse => self.with_reversed(value >= 0.5),
+            SampleRegionParameter::LoopStart => {
+                let loop_end = self.loop_range.map_or(value, |(_, end)| end);
+                self.with_loop(Some((value, loop_end)))
+            }
+            SampleRegionParameter::LoopEnd => {
+                if value > 0.0 {
+                    let loop_start = self.loop_range.map_or(self.start, |(start, _)| start);
+                    self.with_loop(Some((loop_start, value)))
+                } else {
+                    self.with_loop(None)
+                }
+            }
+        }
+    }
+
+    /// Start time in seconds.
+    pub fn start(&self) -> f64 {
+        self.start
+    }
+
+    /// Optional end time in seconds.
+    pub fn end(&self) -> Option<f64> {
+        self.end
+    }
+
+    /// true when the region plays backwards.
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+
+    /// Optional loop start and end time in seconds.
+    pub fn loop_range(&self) -> Option<(f64, f64)> {
+        self.loop_range
+    }
+
+    /// true when the region plays the entire sample.
+    pub fn is_full_sample(&self) -> bool {
+        self.start == 0.0 && self.end.is_none() && !self.reversed && self.loop_range.is_none()
+    }
+
+    /// true when the region plays backwards or looped, which needs the region to be rendered
+    /// via [`render`](Self::render) before it can be played.
+    pub fn needs_rendering(&self) -> bool {
+        self.reversed || self.loop_range.is_some()
+    }
+
+    /// Return a copy of the region with start or end times overridden by the given note
+    /// event extra data, if present.
+    #[must_use]
+    pub fn with_overrides(&self, extra: Option<&EventData>) -> Self {
+        let time_value = |key: &str| match extra.and_then(|extra| extra.get(key)) {
+            Some(EventDataValue::Number(value)) => Some(*value),
+            Some(EventDataValue::Integer(value)) => Some(*value as f64),
+            _ => None,
+        };
+        let start = time_value(SAMPLE_START_KEY).unwrap_or(self.start);
+        let end = time_value(SAMPLE_END_KEY).or(self.end);
+        let reversed = match extra.and_then(|extra| extra.get(SAMPLE_REVERSE_KEY)) {
+            Some(EventDataValue::Boolean(value)) => *value,
+            Some(EventDataValue::Integer(value)) => *value != 0,
+            Some(EventDataValue::Number(value)) => *value >= 0.5,
+            _ => self.reversed,
+        };
+        let loop_range = match (
+            time_value(SAMPLE_LOOP_START_KEY),
+            time_value(SAMPLE_LOOP_END_KEY),
+        ) {
+            (None, None) => self.loop_range,
+            (loop_start, loop_end) => {
+                let (start, end) = self.loop_range.unwrap_or((start, end.unwrap_or(f64::MAX)));
+                Some((loop_start.unwrap_or(start), loop_end.unwrap_or(end)))
+            }
+        };
+        Self::new(start, end)
+            .with_reversed(reversed)
+            .with_loop(loop_range)
+    }
+
+    /// Playback duration of the region in seconds, when playing the sample with the given
+    /// speed factor. None, when the region has no end or is looped.
+    pub fn playback_duration(&self, speed: f64) -> Option<f64> {
+        if speed > 0.0 && self.loop_range.is_none() {
+            self.end.map(|end| (end - self.start) / speed)
+        } else {
+            None
+        }
+    }
+
+    /// Render the region from the given interleaved sample buffer with the given channel count
+    /// and sample rate: trims the buffer to the region, reverses it and applies loops.
+    pub fn render(&self, buffer: &[f32], channel_count: usize, sample_rate: u32) -> Vec<f32> {
+        let channel_count = channel_count.max(1);
+        let frame_count = buffer.len() / channel_count;
+        let to_frame = |time: f64| ((time * sample_rate as f64) as usize).min(frame_count);
+        let start = to_frame(self.start);
+        let end = self.end.map_or(frame_count, to_frame).max(start);
+        let push_frames = |rendered: &mut Vec<f32>, frames: &mut dyn Iterator<Item = usize>| {
+            for frame in frames {
+                let offset = frame * channel_count;
+                rendered.extend_from_slice(&buffer[offset..offset + channel_count]);
+            }
+        };
+        let mut rendered = Vec::new();
+        let loop_range = self.loop_range.map(|(loop_start, loop_end)| {
+            let loop_start = to_frame(loop_start).clamp(start, end);
+            (loop_start, to_frame(loop_end).clamp(loop_start, end))
+        });
+        match loop_range {
+            Some((loop_start, loop_end)) if loop_end > loop_start => {
+                // play until the loop end (or backwards until the loop start), then repeat
+                // the loop until the max looped region length is reached
+                let max_samples =
+                    (MAX_LOOPED_REGION_SECONDS * sample_rate as f64) as usize * channel_count;
+                if self.reversed {
+                    push_frames(&mut rendered, &mut (loop_start..end).rev());
+                } else {
+                    push_frames(&mut rendered, &mut (start..loop_end));
+                }
+                while rendered.len() < max_samples {
+                    if self.reversed {
+                        push_frames(&mut rendered, &mut (loop_start..loop_end).rev());
+                    } else {
+                        push_frames(&mut rendered, &mut (loop_start..loop_end));
+                    }
+                }
+            }
+            _ => {
+                if self.reversed {
+                    push_frames(&mut rendered, &mut (start..end).rev());
+                } else {
+                    push_frames(&mut rendered, &mut (start..end));
+                }
+            }
+        }
+        rendered
+    }
+
+    /// Unique key of the region's settings, e.g. to cache rendered regions.
+    pub(crate) fn cache_key(&self) -> [u64; 5] {
+        let (loop_start, loop_end) = self.loop_range.unwrap_or((-1.0, -1.0));
+        [
+            self.start.to_bits(),
+            self.end.unwrap_or(-1.0).to_bits(),
+            self.reversed as u64,
+            loop_start.to_bits(),
+            loop_end.to_bits(),
+        ]
+    }
+
+    fn valid_time(time: f64) -> f64 {
+        if time.is_finite() {
+            time.max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn region() {
+        assert!(SampleRegion::default().is_full_sample());
+        assert_eq!(SampleRegion::new(-1.0, None), SampleRegion::default());
+        assert_eq!(SampleRegion::new(2.0, 1.0).end(), Some(2.0));
+
+        let region = SampleRegion::new(0.5, 1.5);
+        assert_eq!(region.playback_duration(1.0), Some(1.0));
+        assert_eq!(region.playback_duration(2.0), Some(0.5));
+        assert_eq!(SampleRegion::new(0.5, None).playback_duration(1.0), None);
+
+        let extra = EventData::from([(SAMPLE_START_KEY.to_string(), EventDataValue::Number(1.0))]);
+        assert_eq!(
+            region.with_overrides(Some(&extra)),
+            SampleRegion::new(1.0, 1.5)
+        );
+        let extra = EventData::from([(SAMPLE_END_KEY.to_string(), EventDataValue::Integer(3))]);
+        assert_eq!(
+            region.with_overrides(Some(&extra)),
+            SampleRegion::new(0.5, 3.0)
+        );
+        assert_eq!(region.with_overrides(None), region);
+
+        let extra = EventData::from([
+            (SAMPLE_REVERSE_KEY.to_string(), EventDataValue::from(true)),
+            (SAMPLE_LOOP_END_KEY.to_string(), EventDataValue::Number(1.0)),
+        ]);
+        let region = region.with_overrides(Some(&extra));
+        assert!(region.is_reversed());
+        assert_eq!(region.loop_range(), Some((0.5, 1.0)));
+        assert_eq!(region.playback_duration(1.0), None);
+    }
+
+    #[test]
+    fn region_parameters() {
+        let region = SampleRegion::default()
+            .with_parameter(SampleRegionParameter::Start, 1.0)
+            .with_parameter(SampleRegionParameter::End, 2.0)
+            .with_parameter(SampleRegionParameter::Reverse, 1.0);
+        assert_eq!(region, SampleRegion::new(1.0, 2.0).with_reversed(true));
+        let region = region.with_parameter(SampleRegionParameter::LoopEnd, 1.5);
+        assert_eq!(region.loop_range(), Some((1.0, 1.5)));
+        let region = region.with_parameter(SampleRegionParameter::LoopStart, 1.25);
+        assert_eq!(region.loop_range(), Some((1.25, 1.5)));
+        let region = region
+            .with_parameter(SampleRegionParameter::LoopEnd, 0.0)
+            .with_parameter(SampleRegionParameter::End, 0.0);
+        assert_eq!(region, SampleRegion::new(1.0, None).with_reversed(true));
+    }
+
+    #[test]
+    fn region_render() {
+        // 8 stereo frames at 4 Hz
+        let buffer = (0..8)
+            .flat_map(|frame| [frame as f32, -frame as f32])
+            .collect::<Vec<_>>();
+        let frames = |rendered: Vec<f32>| {
+            rendered
+                .chunks_exact(2)
+                .map(|frame| frame[0] as usize)
+                .collect::<Vec<_>>()
+        };
+        let region = SampleRegion::new(0.5, 1.5);
+        assert_eq!(frames(region.render(&buffer, 2, 4)), vec![2, 3, 4, 5]);
+        let region = region.with_reversed(true);
+        assert_eq!(frames(region.render(&buffer, 2, 4)), vec![5, 4, 3, 2]);
+        assert_eq!(region.render(&buffer, 2, 4)[1], -5.0);
+
+        let region = SampleRegion::new(0.5, 1.5).with_loop(Some((0.75, 1.25)));
+        let rendered = frames(region.render(&buffer, 2, 4));
+        // loops are rendered for up to a minute
+        assert_eq!(rendered.len(), 241);
+        assert_eq!(rendered[..8], [2, 3, 4, 3, 4, 3, 4, 3]);
+        let rendered = frames(region.with_reversed(true).render(&buffer, 2, 4));
+        assert_eq!(rendered[..8], [5, 4, 3, 4, 3, 4, 3, 4]);
+    }
+}
@@ -0,0 +1,238 @@
+//! Offline rendering of bounced event stems into audio stems, using the player's samples.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use afplay::{
+    source::{AudioSource, AudioSourceTime},
+    utils::speed_from_note,
+    FilePlaybackOptions,
+};
+
+use crate::{
+    bounce::Stem,
+    event::InstrumentId,
+    player::{record::OutputRecorder, NewNoteAction, SamplePool},
+    BeatTimeBase, Event, SampleTime,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Number of frames which get rendered at once from a sample source.
+const RENDER_BLOCK_FRAMES: usize = 1024;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Renders [`Stem`]S, as bounced by a [`StemBounce`](crate::bounce::StemBounce), into
+/// interleaved audio buffers or WAV files, playing the stems' note events with the samples of
+/// a [`SamplePool`] just like the [`SamplePlayer`](super::SamplePlayer) does.
+///
+/// Sample regions, choke groups and performance effects of a sample player are not applied.
+pub struct StemRenderer<'a> {
+    sample_pool: &'a SamplePool,
+    channel_count: usize,
+    new_note_action: NewNoteAction,
+}
+
+impl<'a> StemRenderer<'a> {
+    /// Create a new stem renderer which renders samples from the given pool into buffers with
+    /// the given number of interleaved channels.
+    pub fn new(sample_pool: &'a SamplePool, channel_count: usize) -> Self {
+        let channel_count = channel_count.max(1);
+        Self {
+            sample_pool,
+            channel_count,
+            new_note_action: NewNoteAction::Continue,
+        }
+    }
+
+    /// Return a new renderer which uses the given behaviour for new notes on playing voices.
+    #[must_use]
+    pub fn with_new_note_action(self, new_note_action: NewNoteAction) -> Self {
+        Self {
+            new_note_action,
+            ..self
+        }
+    }
+
+    /// Number of interleaved channels in rendered buffers.
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Render the given stem's events into an interleaved buffer of the given length in sample
+    /// frames. Event times are sample times in the given time base's sample rate.
+    pub fn render(&self, stem: &Stem, time_base: &BeatTimeBase, length: SampleTime) -> Vec<f32> {
+        // collect voice index, start and stop time of all played notes
+        let mut played_notes = Vec::<(usize, SampleTime, Option<SampleTime>, StemNote)>::new();
+        for event in stem.events() {
+            if let Event::NoteEvents(notes) = &event.event {
+                for (voice_index, note_event) in notes.iter().enumerate() {
+                    let Some(note_event) = note_event else {
+                        continue;
+                    };
+                    let note_time =
+                        event.time + (note_event.delay * event.duration as f32) as SampleTime;
+                    // stop playing samples on this voice channel
+                    if self.new_note_action == NewNoteAction::Stop || note_event.note.is_note_off()
+                    {
+                        for (voice, _, stop_time, _) in &mut played_notes {
+                            if *voice == voice_index && stop_time.is_none() {
+                                *stop_time = Some(note_time);
+                            }
+                        }
+                    }
+                    if note_event.note.is_note_on() {
+                        if let Some(instrument) = note_event.instrument {
+                            played_notes.push((
+                                voice_index,
+                                note_time,
+                                None,
+                                StemNote {
+                                    instrument,
+                                    note: note_event.note as u8,
+                                    volume: note_event.volume,
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        // render all notes into the stem's buffer
+        let mut buffer = vec![0.0; length as usize * self.channel_count];
+        for (_, start_time, stop_time, note) in played_notes {
+            let stop_time = stop_time.unwrap_or(length).min(length);
+            self.render_note(&mut buffer, time_base, &note, start_time, stop_time);
+        }
+        buffer
+    }
+
+    /// Render all given stems and write them into WAV files in the given directory, using the
+    /// stems' file names. Returns the paths of the written files.
+    pub fn write_files<P: AsRef<Path>>(
+        &self,
+        stems: &[Stem],
+        time_base: &BeatTimeBase,
+        length: SampleTime,
+        directory: P,
+    ) -> io::Result<Vec<PathBuf>> {
+        let mut file_paths = Vec::with_capacity(stems.len());
+        for stem in stems {
+            let file_path = directory.as_ref().join(stem.file_name("wav"));
+            let buffer = self.render(stem, time_base, length);
+            let mut recorder = OutputRecorder::create(&file_path, time_base, self.channel_count)?;
+            recorder.write(&buffer, 0)?;
+            recorder.finish()?;
+            file_paths.push(file_path);
+        }
+        Ok(file_paths)
+    }
+
+    fn render_note(
+        &self,
+        buffer: &mut [f32],
+        time_base: &BeatTimeBase,
+        note: &StemNote,
+        start_time: SampleTime,
+        stop_time: SampleTime,
+    ) {
+        let playback_options = FilePlaybackOptions::default().speed(speed_from_note(note.note));
+        let Ok(mut sample) = self.sample_pool.get_sample(
+            note.instrument,
+            playback_options,
+            time_base.samples_per_sec,
+        ) else {
+            log::warn!(target: "Player", "Failed to fetch sample for instrument {}", note.instrument);
+            return;
+        };
+        sample.set_volume(note.volume);
+        let source_channel_count = sample.channel_count().max(1);
+        let mut source_buffer = vec![0.0; RENDER_BLOCK_FRAMES * source_channel_count];
+        let mut source_position = 0;
+        let mut time = start_time;
+        while time < stop_time && !sample.is_exhausted() {
+            let frames = ((stop_time - time) as usize).min(RENDER_BLOCK_FRAMES);
+            let source_time = AudioSourceTime {
+                pos_in_frames: source_position,
+            };
+            let written = sample.write(
+                &mut source_buffer[..frames * source_channel_count],
+                &source_time,
+            );
+            let written_frames = written / source_channel_count;
+            if written_frames == 0 {
+                break;
+            }
+            // mix into the output buffer, wrapping source channels around output channels
+            for (frame_index, frame) in source_buffer[..written_frames * source_channel_count]
+                .chunks_exact(source_channel_count)
+                .enumerate()
+            {
+                let output_frame = (time as usize + frame_index) * self.channel_count;
+                for channel in 0..self.channel_count {
+                    buffer[output_frame + channel] += frame[channel % source_channel_count];
+                }
+            }
+            source_position += written_frames as u64;
+            time += written_frames as SampleTime;
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A note-on in a rendered stem.
+struct StemNote {
+    instrument: InstrumentId,
+    note: u8,
+    volume: f32,
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn render() {
+        let sample_pool = SamplePool::new();
+        let file_path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/assets/tone.wav");
+        let instrument = sample_pool.load_sample(file_path).unwrap();
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 44100,
+        };
+        let phrase = Phrase::new(
+            time_base,
+            vec![
+                time_base
+                    .every_nth_beat(1.0)
+                    .with_offset(BeatTimeStep::Beats(1.0))
+                    .trigger(new_note_event((Note::C4, Some(instrument)))),
+                time_base
+                    .every_nth_beat(1.0)
+                    .trigger(new_note_event((Note::C4, None))),
+            ],
+            BeatTimeStep::Bar(1.0),
+        );
+        let mut sequence = Sequence::new(time_base, vec![phrase]);
+        let length = time_base.samples_per_bar() as SampleTime;
+        let stems = StemBounce::new().run(&mut sequence, length);
+
+        let renderer = StemRenderer::new(&sample_pool, 2);
+        let first_note_frame = time_base.samples_per_beat() as usize;
+        let buffer = renderer.render(&stems[0], &time_base, length);
+        assert_eq!(buffer.len(), length as usize * 2);
+        assert!(buffer[..first_note_frame * 2].iter().all(|s| *s == 0.0));
+        assert!(buffer[first_note_frame * 2..].iter().any(|s| *s != 0.0));
+        // notes without instruments are silent
+        let buffer = renderer.render(&stems[1], &time_base, length);
+        assert!(buffer.iter().all(|s| *s == 0.0));
+    }
+}
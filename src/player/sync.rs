@@ -0,0 +1,464 @@
+//! Pluggable clock sources and drift correction for the `SamplePlayer`'s transport.
+
+use std::fmt::Debug;
+
+use super::{
+    clock::{MidiClock, MidiClockMessage, MIDI_CLOCKS_PER_BEAT, MIDI_CLOCKS_PER_SIXTEENTH},
+    HostAdvance, HostTransport,
+};
+use crate::SampleTime;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Max amount a [`ClockSync`] speeds up or slows down playback to correct drifts, relative
+/// to the block size.
+const MAX_RATE_CORRECTION: f64 = 0.05;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Position and tempo of a [`ClockSource`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ClockPosition {
+    /// The clock's playback position in samples, relative to the start of the sequence.
+    pub sample_position: f64,
+    /// The clock's tempo. When None, the sequence's tempo is used.
+    pub beats_per_min: Option<f32>,
+}
+
+/// A clock which drives the transport of a [`SamplePlayer`](super::SamplePlayer) via a
+/// [`ClockSync`], e.g. the audio device's clock, an external MIDI clock or a network clock
+/// such as Ableton Link.
+///
+/// Clock sources are queried with the audio output's frame position as reference time, so
+/// sources which receive time stamped messages can extrapolate their position.
+pub trait ClockSource: Debug {
+    /// The clock's position at the given audio output frame position, or None when the clock
+    /// is stopped or not yet running.
+    fn position(&mut self, audio_frame_position: SampleTime) -> Option<ClockPosition>;
+}
+
+impl<S: ClockSource + ?Sized> ClockSource for Box<S> {
+    fn position(&mut self, audio_frame_position: SampleTime) -> Option<ClockPosition> {
+        (**self).position(audio_frame_position)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Follows the audio output device's clock, starting at the first queried frame position.
+#[derive(Debug, Default, Clone)]
+pub struct AudioClockSource {
+    start_frame_position: Option<SampleTime>,
+}
+
+impl AudioClockSource {
+    /// Create a new audio clock, which starts when it gets queried the first time.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restart the clock at the next queried frame position.
+    pub fn restart(&mut self) {
+        self.start_frame_position = None;
+    }
+}
+
+impl ClockSource for AudioClockSource {
+    fn position(&mut self, audio_frame_position: SampleTime) -> Option<ClockPosition> {
+        let start = *self
+            .start_frame_position
+            .get_or_insert(audio_frame_position);
+        Some(ClockPosition {
+            sample_position: audio_frame_position.saturating_sub(start) as f64,
+            beats_per_min: None,
+        })
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A clock which gets set manually by the host, e.g. from a custom network clock.
+#[derive(Debug, Default, Clone)]
+pub struct ManualClockSource {
+    position: Option<ClockPosition>,
+}
+
+impl ManualClockSource {
+    /// Create a new, stopped manual clock.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the clock's current position and tempo. The clock runs until it gets stopped.
+    pub fn set_position(&mut self, sample_position: f64, beats_per_min: Option<f32>) {
+        self.position = Some(ClockPosition {
+            sample_position,
+            beats_per_min,
+        });
+    }
+
+    /// Stop the clock.
+    pub fn stop(&mut self) {
+        self.position = None;
+    }
+}
+
+impl ClockSource for ManualClockSource {
+    fn position(&mut self, _audio_frame_position: SampleTime) -> Option<ClockPosition> {
+        self.position
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Follows an external MIDI clock, which gets fed with time stamped MIDI clock and transport
+/// messages via [`handle_message`](Self::handle_message).
+///
+/// The tempo is measured from clock pulse intervals and smoothed like [`MidiClock`]'s tempo.
+/// Positions advance with the actual time between clock pulses.
+#[derive(Debug, Clone)]
+pub struct MidiClockSource {
+    samples_per_sec: u32,
+    smoothing: f64,
+    running: bool,
+    song_clocks: u64,
+    last_clock_time: Option<SampleTime>,
+    samples_per_clock: Option<f64>,
+    position: f64,
+}
+
+impl MidiClockSource {
+    /// Create a new, stopped MIDI clock source for an audio output with the given sample rate.
+    pub fn new(samples_per_sec: u32) -> Self {
+        Self {
+            samples_per_sec,
+            smoothing: MidiClock::DEFAULT_SMOOTHING,
+            running: false,
+            song_clocks: 0,
+            last_clock_time: None,
+            samples_per_clock: None,
+            position: 0.0,
+        }
+    }
+
+    /// Return a new clock source with the given tempo smoothing factor in range (0, 1].
+    #[must_use]
+    pub fn with_smoothing(self, smoothing: f64) -> Self {
+        let smoothing = smoothing.clamp(0.001, 1.0);
+        Self { smoothing, ..self }
+    }
+
+    /// true when the external clock got started.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// The external clock's measured tempo, if known.
+    pub fn beats_per_min(&self) -> Option<f32> {
+        self.samples_per_clock.map(|samples_per_clock| {
+            (60.0 * self.samples_per_sec as f64 / (samples_per_clock * MIDI_CLOCKS_PER_BEAT as f64))
+                as f32
+        })
+    }
+
+    /// Handle a MIDI clock or transport message, which got received at the given audio output
+    /// frame position.
+    pub fn handle_message(&mut self, audio_frame_position: SampleTime, message: MidiClockMessage) {
+        match message {
+            MidiClockMessage::Start => {
+                self.running = true;
+                self.song_clocks = 0;
+                self.last_clock_time = None;
+            }
+            MidiClockMessage::Continue => {
+                self.running = true;
+                self.last_clock_time = None;
+            }
+            MidiClockMessage::Stop => {
+                self.running = false;
+            }
+            MidiClockMessage::SongPosition(position) => {
+                self.song_clocks = position as u64 * MIDI_CLOCKS_PER_SIXTEENTH;
+                self.last_clock_time = None;
+            }
+            MidiClockMessage::Clock => {
+                if !self.running {
+                    return;
+                }
+                if let Some(last_clock_time) = self.last_clock_time {
+                    let interval = audio_frame_position.saturating_sub(last_clock_time) as f64;
+                    self.samples_per_clock = Some(match self.samples_per_clock {
+                        Some(samples_per_clock) => {
+                            samples_per_clock + (interval - samples_per_clock) * self.smoothing
+                        }
+                        None => interval,
+                    });
+                    self.position += interval;
+                } else {
+                    // first pulse after starting or continuing marks the song position
+                    self.position = self.song_clocks as f64 * self.samples_per_clock.unwrap_or(0.0);
+                }
+                self.last_clock_time = Some(audio_frame_position);
+            }
+        }
+    }
+}
+
+impl ClockSource for MidiClockSource {
+    fn position(&mut self, audio_frame_position: SampleTime) -> Option<ClockPosition> {
+        if !self.running {
+            return None;
+        }
+        match (self.last_clock_time, self.samples_per_clock) {
+            (Some(last_clock_time), Some(samples_per_clock)) => {
+                // extrapolate, but never beyond the next expected clock pulse
+                let elapsed = audio_frame_position.saturating_sub(last_clock_time) as f64;
+                Some(ClockPosition {
+                    sample_position: self.position + elapsed.min(samples_per_clock),
+                    beats_per_min: self.beats_per_min(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Synchronization state of a [`ClockSync`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SyncStatus {
+    /// The clock source is stopped or not yet running.
+    Stopped,
+    /// The transport drifted away from the clock source and gets corrected.
+    Locking,
+    /// The transport follows the clock source within the lock tolerance.
+    Locked,
+}
+
+/// Drives a [`SamplePlayer`](super::SamplePlayer)'s transport from a [`ClockSource`] via
+/// [`SamplePlayer::advance_with_clock`](super::SamplePlayer::advance_with_clock).
+///
+/// Small drifts between the player's transport and the clock source get corrected gently,
+/// PLL style, by slightly speeding up or slowing down the transport, instead of jumping.
+/// Only drifts above the max drift make the transport jump to the clock's position.
+#[derive(Debug, Clone)]
+pub struct ClockSync<S: ClockSource> {
+    source: S,
+    correction: f64,
+    max_drift: SampleTime,
+    lock_tolerance: SampleTime,
+    local_position: Option<SampleTime>,
+    rate_offset: f64,
+    phase_error: f64,
+    status: SyncStatus,
+}
+
+impl<S: ClockSource> ClockSync<S> {
+    /// Default drift correction amount.
+    pub const DEFAULT_CORRECTION: f64 = 0.1;
+    /// Default max drift in samples, above which the transport jumps.
+    pub const DEFAULT_MAX_DRIFT: SampleTime = 8192;
+    /// Default lock tolerance in samples.
+    pub const DEFAULT_LOCK_TOLERANCE: SampleTime = 64;
+
+    /// Create a new clock sync, which follows the given clock source.
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            correction: Self::DEFAULT_CORRECTION,
+            max_drift: Self::DEFAULT_MAX_DRIFT,
+            lock_tolerance: Self::DEFAULT_LOCK_TOLERANCE,
+            local_position: None,
+            rate_offset: 0.0,
+            phase_error: 0.0,
+            status: SyncStatus::Stopped,
+        }
+    }
+
+    /// Return a new clock sync with the given drift correction amount in range (0, 1]: the
+    /// fraction of the drift which gets corrected per block.
+    #[must_use]
+    pub fn with_correction(self, correction: f64) -> Self {
+        let correction = correction.clamp(0.001, 1.0);
+        Self { correction, ..self }
+    }
+
+    /// Return a new clock sync, which jumps to the clock source's position when the transport
+    /// drifted more than the given number of samples.
+    #[must_use]
+    pub fn with_max_drift(self, max_drift: SampleTime) -> Self {
+        Self { max_drift, ..self }
+    }
+
+    /// Return a new clock sync, which reports to be locked when the transport drifted less
+    /// than the given number of samples.
+    #[must_use]
+    pub fn with_lock_tolerance(self, lock_tolerance: SampleTime) -> Self {
+        Self {
+            lock_tolerance,
+            ..self
+        }
+    }
+
+    /// The followed clock source.
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Mut access to the followed clock source, e.g. to feed it with received clock messages.
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Current synchronization state.
+    pub fn status(&self) -> SyncStatus {
+        self.status
+    }
+
+    /// Drift in samples between the clock source and the transport in the last processed
+    /// block. Positive values mean the transport is behind the clock.
+    pub fn phase_error(&self) -> f64 {
+        self.phase_error
+    }
+
+    /// Calculate the transport and advance amount of the next block with the given size,
+    /// starting at the given audio output frame position. Returns None when the clock source
+    /// is stopped.
+    pub fn process(
+        &mut self,
+        audio_frame_position: SampleTime,
+        block_samples: SampleTime,
+    ) -> Option<(HostTransport, HostAdvance)> {
+        let clock = match self.source.position(audio_frame_position) {
+            Some(clock) => clock,
+            None => {
+                self.local_position = None;
+                self.rate_offset = 0.0;
+                self.phase_error = 0.0;
+                self.status = SyncStatus::Stopped;
+                return None;
+            }
+        };
+        // follow the clock or jump to its position when it drifted too much
+        let local_position = match self.local_position {
+            Some(local_position)
+                if (clock.sample_position - local_position as f64).abs()
+                    <= self.max_drift as f64 =>
+            {
+                local_position
+            }
+            _ => {
+                self.rate_offset = 0.0;
+                clock.sample_position.round().max(0.0) as SampleTime
+            }
+        };
+        // correct phase and rate drifts
+        let block_length = block_samples as f64;
+        let phase_error = clock.sample_position - local_position as f64;
+        if block_length > 0.0 {
+            self.rate_offset = (self.rate_offset
+                + phase_error / block_length * self.correction * self.correction / 4.0)
+                .clamp(-MAX_RATE_CORRECTION, MAX_RATE_CORRECTION);
+        }
+        let max_correction = block_length * MAX_RATE_CORRECTION;
+        let correction = (block_length * self.rate_offset + phase_error * self.correction)
+            .clamp(-max_correction, max_correction);
+        let advance = (block_length + correction).round().max(0.0) as SampleTime;
+        self.local_position = Some(local_position + advance);
+        self.phase_error = phase_error;
+        self.status = if phase_error.abs() <= self.lock_tolerance as f64 {
+            SyncStatus::Locked
+        } else {
+            SyncStatus::Locking
+        };
+        let transport = HostTransport {
+            sample_position: local_position,
+            beats_per_min: clock.beats_per_min,
+        };
+        Some((transport, HostAdvance::Samples(advance)))
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn midi_clock_source() {
+        // 125 BPM at 48kHz: 960 samples per clock
+        let mut source = MidiClockSource::new(48000);
+        assert_eq!(source.position(0), None);
+        source.handle_message(1000, MidiClockMessage::Start);
+        for pulse in 0..25 {
+            source.handle_message(1000 + pulse * 960, MidiClockMessage::Clock);
+        }
+        assert!(source.is_running());
+        assert_eq!(source.beats_per_min(), Some(125.0));
+        let position = source.position(1000 + 24 * 960 + 480).unwrap();
+        assert_eq!(position.sample_position, 24.0 * 960.0 + 480.0);
+        assert_eq!(position.beats_per_min, Some(125.0));
+        // extrapolation stops at the next expected pulse
+        let position = source.position(1000 + 30 * 960).unwrap();
+        assert_eq!(position.sample_position, 25.0 * 960.0);
+
+        // song positions are applied with the next pulse
+        source.handle_message(50000, MidiClockMessage::Stop);
+        assert_eq!(source.position(50000), None);
+        source.handle_message(60000, MidiClockMessage::SongPosition(4));
+        source.handle_message(60000, MidiClockMessage::Continue);
+        source.handle_message(60100, MidiClockMessage::Clock);
+        let position = source.position(60100).unwrap();
+        assert_eq!(position.sample_position, 24.0 * 960.0);
+    }
+
+    #[test]
+    fn clock_sync() {
+        let mut sync = ClockSync::new(ManualClockSource::new());
+        assert_eq!(sync.process(0, 100), None);
+        assert_eq!(sync.status(), SyncStatus::Stopped);
+
+        // starts at the clock's position
+        sync.source_mut().set_position(1000.0, Some(120.0));
+        let (transport, advance) = sync.process(0, 100).unwrap();
+        assert_eq!(transport.sample_position, 1000);
+        assert_eq!(transport.beats_per_min, Some(120.0));
+        assert_eq!(advance, HostAdvance::Samples(100));
+        assert_eq!(sync.status(), SyncStatus::Locked);
+
+        // small drifts get corrected gently
+        sync.source_mut().set_position(1200.0, Some(120.0));
+        let (transport, advance) = sync.process(0, 100).unwrap();
+        assert_eq!(transport.sample_position, 1100);
+        assert_eq!(sync.phase_error(), 100.0);
+        assert_eq!(sync.status(), SyncStatus::Locking);
+        assert_eq!(advance, HostAdvance::Samples(105));
+
+        // big drifts jump
+        sync.source_mut().set_position(100000.0, None);
+        let (transport, _) = sync.process(0, 100).unwrap();
+        assert_eq!(transport.sample_position, 100000);
+        assert_eq!(sync.status(), SyncStatus::Locked);
+    }
+
+    #[test]
+    fn drift_correction() {
+        // clock runs 1% faster than the transport's blocks
+        let mut sync = ClockSync::new(ManualClockSource::new());
+        let mut clock_position = 0.0;
+        let mut transport_position = 0;
+        for _ in 0..1000 {
+            sync.source_mut().set_position(clock_position, None);
+            let (transport, advance) = sync.process(0, 1000).unwrap();
+            assert_eq!(transport.sample_position, transport_position);
+            if let HostAdvance::Samples(samples) = advance {
+                transport_position += samples;
+            }
+            clock_position += 1010.0;
+        }
+        assert_eq!(sync.status(), SyncStatus::Locked);
+        assert!(sync.phase_error().abs() < 10.0);
+    }
+}
@@ -0,0 +1,280 @@
+//! Renders trigger pulses, e.g. to sync analog gear via an audio interface's outputs.
+
+use std::collections::VecDeque;
+
+use crate::{phrase::RhythmIndex, player::effects::SequenceEvent, BeatTimeBase, Event, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Source of trigger pulses in a [`TriggerOutput`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TriggerSource {
+    /// Trigger on every beat.
+    Beats,
+    /// Trigger on every bar.
+    Bars,
+    /// Trigger on every note-on which gets emitted by the given rhythm slot.
+    RhythmSlot(RhythmIndex),
+}
+
+/// Shape of a rendered trigger pulse.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TriggerShape {
+    /// A short click, which linearly fades out over the pulse length.
+    Click,
+    /// A DC gate signal, which stays fully open over the pulse length.
+    Gate,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Configures which trigger pulses get rendered into which output channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerOutput {
+    source: TriggerSource,
+    shape: TriggerShape,
+    pulse_length: f64,
+    amplitude: f32,
+    channel: usize,
+}
+
+impl TriggerOutput {
+    /// Create a new trigger output with the given source, rendering 1ms long clicks into the
+    /// given output channel.
+    pub fn new(source: TriggerSource, channel: usize) -> Self {
+        Self {
+            source,
+            shape: TriggerShape::Click,
+            pulse_length: 0.001,
+            amplitude: 1.0,
+            channel,
+        }
+    }
+
+    /// Return a new trigger output with the given pulse shape and pulse length in seconds.
+    #[must_use]
+    pub fn with_shape(self, shape: TriggerShape, pulse_length: f64) -> Self {
+        let pulse_length = pulse_length.max(0.0);
+        Self {
+            shape,
+            pulse_length,
+            ..self
+        }
+    }
+
+    /// Return a new trigger output with the given pulse amplitude.
+    #[must_use]
+    pub fn with_amplitude(self, amplitude: f32) -> Self {
+        Self { amplitude, ..self }
+    }
+
+    /// Source of the trigger pulses.
+    pub fn source(&self) -> TriggerSource {
+        self.source
+    }
+    /// Shape of the rendered pulses.
+    pub fn shape(&self) -> TriggerShape {
+        self.shape
+    }
+    /// Length of the rendered pulses in seconds.
+    pub fn pulse_length(&self) -> f64 {
+        self.pulse_length
+    }
+    /// Amplitude of the rendered pulses.
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+    /// Output channel index the pulses are rendered into.
+    pub fn channel(&self) -> usize {
+        self.channel
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Collects trigger times from a sequence's emitted events and renders them as pulses into
+/// interleaved multi-channel audio buffers.
+#[derive(Debug, Clone)]
+pub struct TriggerRenderer {
+    output: TriggerOutput,
+    triggers: VecDeque<SampleTime>,
+}
+
+impl TriggerRenderer {
+    /// Create a new pulse renderer for the given trigger output.
+    pub fn new(output: TriggerOutput) -> Self {
+        let triggers = VecDeque::new();
+        Self { output, triggers }
+    }
+
+    /// The renderer's trigger output configuration.
+    pub fn output(&self) -> &TriggerOutput {
+        &self.output
+    }
+
+    /// Remove all pending triggers, e.g. after the sequence got seeked.
+    pub fn reset(&mut self) {
+        self.triggers.clear();
+    }
+
+    /// Collect trigger times in range \[start_time, end_time) from the given emitted events.
+    /// Pulses which ended before `start_time` and thus never got rendered are dropped.
+    pub fn add_triggers(
+        &mut self,
+        time_base: &BeatTimeBase,
+        events: &[SequenceEvent],
+        start_time: SampleTime,
+        end_time: SampleTime,
+    ) {
+        let pulse_length = self.pulse_length_in_samples(time_base.samples_per_sec);
+        self.remove_finished_triggers(pulse_length, start_time);
+        let mut add_grid_triggers = |samples_per_step: f64| {
+            if samples_per_step <= 0.0 {
+                return;
+            }
+            let mut step = (start_time as f64 / samples_per_step).ceil() as u64;
+            loop {
+                let time = (step as f64 * samples_per_step) as SampleTime;
+                if time >= end_time {
+                    break;
+                }
+                self.triggers.push_back(time);
+                step += 1;
+            }
+        };
+        match self.output.source {
+            TriggerSource::Beats => add_grid_triggers(time_base.samples_per_beat()),
+            TriggerSource::Bars => add_grid_triggers(time_base.samples_per_bar()),
+            TriggerSource::RhythmSlot(slot_index) => {
+                for (rhythm_index, time, event, _) in events {
+                    if *rhythm_index == slot_index {
+                        if let Some(Event::NoteEvents(note_events)) = event {
+                            if note_events
+                                .iter()
+                                .flatten()
+                                .any(|note_event| note_event.note.is_note_on())
+                            {
+                                self.triggers.push_back(*time);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.triggers.make_contiguous().sort_unstable();
+    }
+
+    /// Render pulses into the trigger output's channel of the given interleaved buffer, which
+    /// starts at the given sequence sample time. Other channels are left untouched.
+    pub fn render(
+        &mut self,
+        buffer: &mut [f32],
+        channel_count: usize,
+        start_time: SampleTime,
+        samples_per_sec: u32,
+    ) {
+        let channel = self.output.channel;
+        if channel_count == 0 || channel >= channel_count {
+            return;
+        }
+        let frame_count = (buffer.len() / channel_count) as SampleTime;
+        let end_time = start_time + frame_count;
+        let pulse_length = self.pulse_length_in_samples(samples_per_sec);
+        for trigger_time in &self.triggers {
+            if *trigger_time >= end_time {
+                break;
+            }
+            let pulse_start = (*trigger_time).max(start_time);
+            let pulse_end = (*trigger_time + pulse_length).min(end_time);
+            for time in pulse_start..pulse_end {
+                let value = match self.output.shape {
+                    TriggerShape::Click => {
+                        let position = (time - *trigger_time) as f32 / pulse_length as f32;
+                        self.output.amplitude * (1.0 - position)
+                    }
+                    TriggerShape::Gate => self.output.amplitude,
+                };
+                let frame = (time - start_time) as usize;
+                buffer[frame * channel_count + channel] = value;
+            }
+        }
+        self.remove_finished_triggers(pulse_length, end_time);
+    }
+
+    fn pulse_length_in_samples(&self, samples_per_sec: u32) -> SampleTime {
+        ((self.output.pulse_length * samples_per_sec as f64) as SampleTime).max(1)
+    }
+
+    fn remove_finished_triggers(&mut self, pulse_length: SampleTime, time: SampleTime) {
+        while self
+            .triggers
+            .front()
+            .is_some_and(|trigger_time| trigger_time + pulse_length <= time)
+        {
+            self.triggers.pop_front();
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    #[test]
+    fn render_pulses() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        // beat gates of 4ms in channel 1 of 2
+        let output = TriggerOutput::new(TriggerSource::Beats, 1)
+            .with_shape(TriggerShape::Gate, 0.004)
+            .with_amplitude(0.5);
+        let mut renderer = TriggerRenderer::new(output);
+        renderer.add_triggers(&time_base, &[], 0, 1000);
+        let mut buffer = vec![0.0; 2 * 502];
+        renderer.render(&mut buffer, 2, 0, 1000);
+        let gate = buffer
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(&gate[0..5], &[0.5, 0.5, 0.5, 0.5, 0.0]);
+        assert_eq!(&gate[499..502], &[0.0, 0.5, 0.5]);
+        assert!(buffer.iter().step_by(2).all(|v| *v == 0.0));
+        // pulses continue in the next block
+        let mut buffer = vec![0.0; 2 * 4];
+        renderer.render(&mut buffer, 2, 502, 1000);
+        let gate = buffer
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(gate, vec![0.5, 0.5, 0.0, 0.0]);
+
+        // clicks on rhythm slot note-ons
+        let output = TriggerOutput::new(TriggerSource::RhythmSlot(1), 0)
+            .with_shape(TriggerShape::Click, 0.004);
+        let mut renderer = TriggerRenderer::new(output);
+        let note_on = Some(Event::NoteEvents(vec![new_note(Note::C4)]));
+        let note_off = Some(Event::NoteEvents(vec![new_note(Note::OFF)]));
+        let events = vec![
+            (0, 2, note_on.clone(), 10),
+            (1, 3, note_on, 10),
+            (1, 8, note_off, 10),
+        ];
+        renderer.add_triggers(&time_base, &events, 0, 10);
+        let mut buffer = vec![0.0; 10];
+        renderer.render(&mut buffer, 1, 0, 1000);
+        assert_eq!(
+            buffer,
+            vec![0.0, 0.0, 0.0, 1.0, 0.75, 0.5, 0.25, 0.0, 0.0, 0.0]
+        );
+    }
+}
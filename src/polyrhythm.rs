@@ -0,0 +1,273 @@
+//! Align rhythms with different pattern lengths to a common hyperperiod.
+
+use crate::{time::BeatTimeStep, BeatTimeBase, Phrase, RhythmSlot};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Resolution of pattern lengths: lengths get rounded to 1/960 beats, which represents all
+/// common binary and triplet subdivisions exactly.
+const TICKS_PER_BEAT: u64 = 960;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Computes the hyperperiod of patterns with different lengths, e.g. a 3 beat and a 4 beat
+/// pattern, which realign after 12 beats, and creates [`Phrase`]S which loop the patterns
+/// against each other for a full hyperperiod.
+///
+/// Optionally, a reset period forces all patterns to realign earlier, e.g. after each bar,
+/// when the hyperperiod gets too long. Phrases then only loop for the reset period.
+///
+/// Pattern lengths are specified in beats and get rounded to 1/960 beats. Pattern indices
+/// match the rhythm slot indices of phrases, so UIs can query when slots realign.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyrhythm {
+    pattern_lengths: Vec<Option<u64>>,
+    hyperperiod: u64,
+    reset_period: Option<u64>,
+}
+
+impl Polyrhythm {
+    /// Create a new polyrhythm from the given pattern lengths in beats.
+    ///
+    /// Returns error when a pattern length is not > 0, or when the hyperperiod gets too long.
+    pub fn new(pattern_lengths: &[f64]) -> Result<Self, String> {
+        let pattern_lengths = pattern_lengths
+            .iter()
+            .map(|length| Self::ticks_from_beats(*length).map(Some))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::with_pattern_ticks(pattern_lengths)
+    }
+
+    /// Create a new polyrhythm from the pattern lengths of the given rhythm slots, e.g. the
+    /// slots of an existing phrase. Stop and continue slots have no pattern length, so they
+    /// never take part in realignments.
+    ///
+    /// Returns error when a pattern length is not > 0, or when the hyperperiod gets too long.
+    pub fn from_rhythm_slots(rhythm_slots: &[RhythmSlot]) -> Result<Self, String> {
+        let pattern_lengths = rhythm_slots
+            .iter()
+            .map(|slot| match slot {
+                RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) => {
+                    let rhythm = rhythm.borrow();
+                    let samples = rhythm.pattern_step_length() * rhythm.pattern_length() as f64;
+                    let beats = samples / rhythm.time_base().samples_per_beat();
+                    Self::ticks_from_beats(beats).map(Some)
+                }
+                RhythmSlot::Stop | RhythmSlot::Continue => Ok(None),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::with_pattern_ticks(pattern_lengths)
+    }
+
+    fn with_pattern_ticks(pattern_lengths: Vec<Option<u64>>) -> Result<Self, String> {
+        let hyperperiod = Self::least_common_multiple(pattern_lengths.iter().flatten().copied())?;
+        let reset_period = None;
+        Ok(Self {
+            pattern_lengths,
+            hyperperiod,
+            reset_period,
+        })
+    }
+
+    /// Return a new polyrhythm which resets all patterns after the given period in beats, so
+    /// they realign earlier than after the hyperperiod. Periods which are not > 0 or longer
+    /// than the hyperperiod disable resets.
+    #[must_use]
+    pub fn with_reset_period(self, beats: f64) -> Self {
+        let reset_period = Self::ticks_from_beats(beats)
+            .ok()
+            .filter(|period| *period < self.hyperperiod);
+        Self {
+            reset_period,
+            ..self
+        }
+    }
+
+    /// Number of patterns, including patterns without length.
+    pub fn pattern_count(&self) -> usize {
+        self.pattern_lengths.len()
+    }
+
+    /// Length of the pattern at the given index in beats, if it has a length.
+    pub fn pattern_length(&self, index: usize) -> Option<f64> {
+        self.pattern_lengths
+            .get(index)
+            .copied()
+            .flatten()
+            .map(Self::beats_from_ticks)
+    }
+
+    /// Length in beats after which all patterns realign without resets.
+    pub fn hyperperiod(&self) -> f64 {
+        Self::beats_from_ticks(self.hyperperiod)
+    }
+
+    /// Period in beats after which all patterns get reset, if any.
+    pub fn reset_period(&self) -> Option<f64> {
+        self.reset_period.map(Self::beats_from_ticks)
+    }
+
+    /// Length of a full polyrhythm loop in beats: the reset period, if set, else the
+    /// hyperperiod.
+    pub fn loop_length(&self) -> f64 {
+        Self::beats_from_ticks(self.loop_ticks())
+    }
+
+    /// Create a new phrase from the given rhythm slots, which loops for the polyrhythm's
+    /// [`loop_length`](Self::loop_length). When played in a [`Sequence`](crate::Sequence),
+    /// the phrase's rhythms get reset at the end of each loop, so they stay aligned.
+    pub fn phrase<R: Into<RhythmSlot>>(
+        &self,
+        time_base: BeatTimeBase,
+        rhythm_slots: Vec<R>,
+    ) -> Phrase {
+        let length = BeatTimeStep::Beats(self.loop_length() as f32);
+        Phrase::new(time_base, rhythm_slots, length)
+    }
+
+    /// Beat positions within a loop at which the pattern with the given index restarts.
+    /// Empty when the pattern has no length.
+    pub fn pattern_restarts(&self, index: usize) -> Vec<f64> {
+        self.realignments(&[index])
+    }
+
+    /// Beat positions within a loop at which all patterns with the given indices restart
+    /// together. Patterns without length get ignored. Empty when none of the patterns has a
+    /// length.
+    pub fn realignments(&self, indices: &[usize]) -> Vec<f64> {
+        let lengths = indices
+            .iter()
+            .filter_map(|index| self.pattern_lengths.get(*index).copied().flatten())
+            .collect::<Vec<_>>();
+        if lengths.is_empty() {
+            return vec![];
+        }
+        let loop_ticks = self.loop_ticks();
+        match Self::least_common_multiple(lengths.into_iter()) {
+            Ok(period) if period < loop_ticks => (0..loop_ticks)
+                .step_by(period as usize)
+                .map(Self::beats_from_ticks)
+                .collect(),
+            _ => vec![0.0],
+        }
+    }
+
+    /// Next beat position at or after the given beat position, at which all patterns realign,
+    /// either after a hyperperiod or a reset.
+    pub fn next_realignment(&self, beat: f64) -> f64 {
+        let loop_length = self.loop_length();
+        let beat = beat.max(0.0);
+        // avoid float rounding issues at loop boundaries
+        (((beat - 1e-9) / loop_length).ceil() * loop_length).max(0.0)
+    }
+
+    fn loop_ticks(&self) -> u64 {
+        self.reset_period.unwrap_or(self.hyperperiod)
+    }
+
+    fn ticks_from_beats(beats: f64) -> Result<u64, String> {
+        let ticks = (beats * TICKS_PER_BEAT as f64).round();
+        if beats.is_finite() && ticks >= 1.0 && ticks <= u64::MAX as f64 {
+            Ok(ticks as u64)
+        } else {
+            Err(format!("pattern length must be > 0 but is: '{}'", beats))
+        }
+    }
+
+    fn beats_from_ticks(ticks: u64) -> f64 {
+        ticks as f64 / TICKS_PER_BEAT as f64
+    }
+
+    fn least_common_multiple<I: Iterator<Item = u64>>(values: I) -> Result<u64, String> {
+        fn greatest_common_divisor(a: u64, b: u64) -> u64 {
+            if b == 0 {
+                a
+            } else {
+                greatest_common_divisor(b, a % b)
+            }
+        }
+        let mut result = 1;
+        for value in values {
+            result = (result / greatest_common_divisor(result, value))
+                .checked_mul(value)
+                .ok_or_else(|| "hyperperiod of pattern lengths is too long".to_string())?;
+        }
+        Ok(result)
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn hyperperiod() -> Result<(), String> {
+        let polyrhythm = Polyrhythm::new(&[3.0, 4.0, 1.5])?;
+        assert_eq!(polyrhythm.hyperperiod(), 12.0);
+        assert_eq!(polyrhythm.loop_length(), 12.0);
+        assert_eq!(polyrhythm.pattern_length(2), Some(1.5));
+        assert_eq!(polyrhythm.pattern_restarts(1), vec![0.0, 4.0, 8.0]);
+        assert_eq!(polyrhythm.realignments(&[0, 2]), vec![0.0, 3.0, 6.0, 9.0]);
+        assert_eq!(polyrhythm.realignments(&[0, 1]), vec![0.0]);
+        assert_eq!(polyrhythm.next_realignment(0.0), 0.0);
+        assert_eq!(polyrhythm.next_realignment(5.0), 12.0);
+        assert_eq!(polyrhythm.next_realignment(12.0), 12.0);
+
+        // triplets
+        let polyrhythm = Polyrhythm::new(&[1.0 / 3.0, 0.5])?;
+        assert_eq!(polyrhythm.hyperperiod(), 1.0);
+
+        // invalid lengths
+        assert!(Polyrhythm::new(&[0.0]).is_err());
+        assert!(Polyrhythm::new(&[f64::NAN]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn reset_period() -> Result<(), String> {
+        let polyrhythm = Polyrhythm::new(&[3.0, 4.0])?.with_reset_period(8.0);
+        assert_eq!(polyrhythm.hyperperiod(), 12.0);
+        assert_eq!(polyrhythm.reset_period(), Some(8.0));
+        assert_eq!(polyrhythm.loop_length(), 8.0);
+        assert_eq!(polyrhythm.pattern_restarts(0), vec![0.0, 3.0, 6.0]);
+        assert_eq!(polyrhythm.next_realignment(5.0), 8.0);
+        // resets after the hyperperiod are ignored
+        let polyrhythm = polyrhythm.with_reset_period(16.0);
+        assert_eq!(polyrhythm.reset_period(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn phrase() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let rhythm_slots: Vec<RhythmSlot> = vec![
+            time_base
+                .every_nth_beat(1.0)
+                .with_pattern([1_u32, 0, 1].to_pattern())
+                .trigger(new_note_event(Note::C4))
+                .into(),
+            time_base
+                .every_nth_eighth(1.0)
+                .with_pattern([1_u32, 0, 0, 0, 1, 0, 0, 0].to_pattern())
+                .trigger(new_note_event(Note::C5))
+                .into(),
+            RhythmSlot::Stop,
+        ];
+        let polyrhythm = Polyrhythm::from_rhythm_slots(&rhythm_slots)?;
+        assert_eq!(polyrhythm.pattern_count(), 3);
+        assert_eq!(polyrhythm.pattern_length(0), Some(3.0));
+        assert_eq!(polyrhythm.pattern_length(1), Some(4.0));
+        assert_eq!(polyrhythm.pattern_length(2), None);
+        assert_eq!(polyrhythm.realignments(&[0, 1, 2]), vec![0.0]);
+        let phrase = polyrhythm.phrase(time_base, rhythm_slots);
+        assert_eq!(phrase.length(), BeatTimeStep::Beats(12.0));
+        Ok(())
+    }
+}
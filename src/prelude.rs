@@ -9,28 +9,80 @@
 
 pub use super::{
     // all public types to create event iters, gates and patterns
+    bounce::{SeedBounce, SeedTake, Stem, StemBounce, StemEvent},
+    diff::{diff_events, diff_pulses, HitChange, HitDiff},
     event::{
-        cycle::{new_cycle_event, CycleEventIter},
+        bassline::BasslineEventIter,
+        cloud::CloudEventIter,
+        cycle::{new_cycle_event, CycleEventIter, CycleGlideMode},
+        echo::EventEcho,
+        fixed::FixedSequenceStep,
         fixed::ToFixedEventIter,
         fixed::ToFixedEventIterSequence,
+        humanizer::EventHumanizer,
+        layering::{InstrumentLayer, InstrumentLayers},
         mutated::ToMutatedEventIter,
         new_empty_note, new_empty_note_event, new_note, new_note_event, new_note_event_sequence,
         new_parameter_change_event, new_polyphonic_note_event, new_polyphonic_note_sequence_event,
-        unique_instrument_id, InstrumentId, NoteEvent, ParameterChangeEvent, ParameterId,
+        panner::{EventPanner, PanningStrategy},
+        piano_roll::PianoRollEventIter,
+        pool::RandomPoolEventIter,
+        quantizer::EventQuantizer,
+        relative::{RelativeNote, RelativeNoteResolver},
+        target::{TargetDefinition, TargetKind, TargetSchema, UnknownTargetAction},
+        transform::{
+            EventTransform, EventTransformPulse, EventTransformStack, GrooveVelocity, NoteDropout,
+            PulseVolume, TimingJitter, VelocityJitter,
+        },
+        transposer::EventTransposer,
+        unique_instrument_id,
+        voicing::{StrumDirection, VoiceSpread},
+        EventData, EventDataValue, InstrumentId, NoteEvent, ParameterChangeEvent, ParameterId,
     },
-    gate::probability::ProbabilityGate,
+    gate::{
+        curve::ProbabilityCurveGate,
+        probability::{MetricEmphasis, ProbabilityGate},
+        sampling::GateSampling,
+        threshold::ThresholdGate,
+    },
+    history::{EventHistory, EventHistoryItem},
+    midi::{MidiFile, MidiKeySignature, MidiNote, MidiTrack},
+    notification::{Notification, NotificationBus, NotificationKind, SubscriptionId},
     pattern::{euclidean, fixed::ToFixedPattern},
-    phrase::RhythmSlot,
-    rhythm::{beat_time::BeatTimeRhythm, second_time::SecondTimeRhythm},
-    time::{BeatTimeStep, SecondTimeStep},
+    performance::{MacroTarget, PerformanceMacro},
+    phrase::{RhythmSlot, SlotDependency, SlotDependencyMode, SlotResumeMode},
+    piano_roll::{PianoRoll, PianoRollNote},
+    polyrhythm::Polyrhythm,
+    registry::{
+        is_registered, new_registered_emitter, new_registered_gate, new_registered_pattern,
+        new_registered_rhythm, register_emitter, register_gate, register_pattern, register_rhythm,
+        registered_names, unregister_implementation, RegistryKind,
+    },
+    rhythm::{
+        beat_time::BeatTimeRhythm, cached::CachedRhythm, chain::ChainRhythm,
+        second_time::SecondTimeRhythm, seed_morph::SeedMorph,
+    },
+    sequence::{
+        CuePoint, EventLimit, EventLimitWindow, EventMerge, EventOrdering, ParameterChangeTime,
+        ParameterRamp, ParameterRampCurve, SequenceParameter, VolumeCurve,
+    },
+    stats::{EventStats, SequenceStats},
+    tempo::TempoFollower,
+    time::{BeatTimeStep, SecondTimeStep, TimingQuantize},
+    warning::{ValueRangePolicy, WarningKind},
     // all public basic types
     BeatTimeBase,
     Chord,
     Event,
     EventIter,
     EventIterItem,
+    ExternalContextValues,
     Gate,
+    Groove,
+    MemoryUsage,
     Note,
+    NoteSpelling,
+    OctaveNumbering,
     Pattern,
     Phrase,
     Pulse,
@@ -39,25 +91,54 @@ pub use super::{
     Rhythm,
     RhythmIter,
     RhythmIterItem,
+    RhythmParameter,
+    RhythmParameterValues,
     SampleTime,
     Scale,
+    Scene,
     SecondTimeBase,
     Sequence,
+    SharedValues,
     TimeBase,
+    Tuning,
+    Warning,
 };
 
 #[cfg(feature = "scripting")]
 // all public scripting types
 pub use super::{
     bindings::{
-        clear_lua_callback_errors, has_lua_callback_errors, lua_callback_errors,
-        new_rhythm_from_file, new_rhythm_from_string,
+        clear_lua_callback_errors, clear_rhythm_script_cache, has_lua_callback_errors,
+        lua_callback_errors, new_performance_macros_from_string, new_rhythm_from_file,
+        new_rhythm_from_file_with_policy, new_rhythm_from_string,
+        new_rhythm_from_string_with_auto_parameters, new_rhythm_from_string_with_policy,
+        rhythm_script_cache_memory_usage, validate_script, AutoParameter, ScriptIssue,
+        ScriptIssueKind, ValidationReport,
     },
     event::{scripted::ScriptedEventIter, scripted_cycle::ScriptedCycleEventIter},
     gate::scripted::ScriptedGate,
     pattern::scripted::ScriptedPattern,
 };
 
+#[cfg(feature = "profiling")]
+// all public profiling types
+pub use super::profiling::{measure_callback, RhythmProfile};
+
 #[cfg(feature = "player")]
 // all public player types
-pub use super::player::{NewNoteAction, SamplePlaybackContext, SamplePlayer, SamplePool};
+pub use super::player::{
+    clock::{MidiClock, MidiClockMessage},
+    effects::{PerformanceEffect, PerformanceEffects},
+    gain::{AutoGain, AutoGainCurve},
+    note_trigger::{
+        trigger_context, NoteTrigger, NoteTriggerAction, NoteTriggerMode, NoteTriggerRule,
+        TriggeredPattern,
+    },
+    record::{OutputRecorder, OutputRecording},
+    sync::{
+        AudioClockSource, ClockPosition, ClockSource, ClockSync, ManualClockSource,
+        MidiClockSource, SyncStatus,
+    },
+    trigger::{TriggerOutput, TriggerShape, TriggerSource},
+    HostAdvance, HostTransport, NewNoteAction, SamplePlaybackContext, SamplePlayer, SamplePool,
+};
@@ -0,0 +1,161 @@
+//! Optional timing instrumentation of rhythm slots, enabled via the `profiling` feature.
+
+use std::{
+    cell::Cell,
+    ops::{Add, AddAssign},
+    time::{Duration, Instant},
+};
+
+// -------------------------------------------------------------------------------------------------
+
+thread_local! {
+    static CALLBACK_TIME: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// Measure the time spent in the given callback function, which then gets reported as callback
+/// time in [`RhythmProfile`]S. Used by scripted Lua callbacks, but custom callbacks, e.g. of
+/// mutated event iters, can be measured as well.
+pub fn measure_callback<R, F: FnOnce() -> R>(func: F) -> R {
+    let start = Instant::now();
+    let result = func();
+    let elapsed = start.elapsed();
+    CALLBACK_TIME.with(|time| time.set(time.get() + elapsed));
+    result
+}
+
+/// Total time spent in measured callbacks in the current thread so far.
+fn callback_time() -> Duration {
+    CALLBACK_TIME.with(Cell::get)
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Measures a single `run_until_time` call of a rhythm slot.
+pub(crate) struct ProfileScope {
+    start: Instant,
+    callback_time: Duration,
+}
+
+impl ProfileScope {
+    /// Start measuring.
+    pub fn start() -> Self {
+        let start = Instant::now();
+        let callback_time = callback_time();
+        Self {
+            start,
+            callback_time,
+        }
+    }
+
+    /// Stop measuring and add the measured times to the given profile.
+    pub fn stop(self, profile: &mut RhythmProfile) {
+        let elapsed = self.start.elapsed();
+        let callback_time = callback_time().saturating_sub(self.callback_time);
+        profile.calls += 1;
+        profile.total_time += elapsed;
+        profile.callback_time += callback_time.min(elapsed);
+        profile.max_call_time = profile.max_call_time.max(elapsed);
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Time spent in the `run_until_time` calls of a single rhythm slot, as measured by
+/// [`Phrase::slot_profiles`](crate::Phrase::slot_profiles) and
+/// [`Sequence::slot_profiles`](crate::Sequence::slot_profiles).
+///
+/// Total times include the time spent in scripted Lua callbacks of patterns, gates and
+/// emitters, so scripted and Rust time can be compared.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RhythmProfile {
+    calls: u64,
+    total_time: Duration,
+    callback_time: Duration,
+    max_call_time: Duration,
+}
+
+impl RhythmProfile {
+    /// Create a new, empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of measured `run_until_time` calls.
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Total time spent in all calls.
+    pub fn total_time(&self) -> Duration {
+        self.total_time
+    }
+
+    /// Time spent in scripted callbacks in all calls.
+    pub fn callback_time(&self) -> Duration {
+        self.callback_time
+    }
+
+    /// Time spent in Rust code in all calls: the total time without callback time.
+    pub fn rust_time(&self) -> Duration {
+        self.total_time.saturating_sub(self.callback_time)
+    }
+
+    /// Longest single call.
+    pub fn max_call_time(&self) -> Duration {
+        self.max_call_time
+    }
+
+    /// Average time of a single call.
+    pub fn average_call_time(&self) -> Duration {
+        if self.calls > 0 {
+            Duration::from_nanos((self.total_time.as_nanos() / self.calls as u128) as u64)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+impl Add for RhythmProfile {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            calls: self.calls + other.calls,
+            total_time: self.total_time + other.total_time,
+            callback_time: self.callback_time + other.callback_time,
+            max_call_time: self.max_call_time.max(other.max_call_time),
+        }
+    }
+}
+
+impl AddAssign for RhythmProfile {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn profile() {
+        let mut profile = RhythmProfile::new();
+        let scope = ProfileScope::start();
+        measure_callback(|| std::thread::sleep(Duration::from_millis(2)));
+        scope.stop(&mut profile);
+        assert_eq!(profile.calls(), 1);
+        assert!(profile.callback_time() >= Duration::from_millis(2));
+        assert!(profile.total_time() >= profile.callback_time());
+        assert_eq!(profile.average_call_time(), profile.total_time());
+        assert_eq!(profile.max_call_time(), profile.total_time());
+
+        let sum = profile + profile;
+        assert_eq!(sum.calls(), 2);
+        assert_eq!(sum.total_time(), profile.total_time() * 2);
+        assert_eq!(sum.rust_time(), profile.rust_time() * 2);
+        assert_eq!(sum.max_call_time(), profile.max_call_time());
+    }
+}
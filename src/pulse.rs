@@ -46,6 +46,16 @@ impl Pulse {
         values
     }
 
+    /// Returns a copy of the pulse with all sub divisions played backwards.
+    pub fn reversed(&self) -> Pulse {
+        match self {
+            Pulse::Pulse(value) => Pulse::Pulse(*value),
+            Pulse::SubDivision(sub_pulses) => {
+                Pulse::SubDivision(sub_pulses.iter().rev().map(Pulse::reversed).collect())
+            }
+        }
+    }
+
     fn expand_into(&self, result: &mut Vec<PulseIterItem>, step_time: f64) {
         match self {
             Pulse::Pulse(value) => {
@@ -0,0 +1,308 @@
+//! Registry of named, third-party pattern, gate, emitter and rhythm implementations.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Display,
+    rc::Rc,
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{BeatTimeBase, EventIter, Gate, Pattern, Rhythm};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Factory of a registered pattern, see [`register_pattern`].
+pub type PatternFactory = dyn Fn(&BeatTimeBase) -> Result<Box<dyn Pattern>, String> + Send + Sync;
+
+/// Factory of a registered gate, see [`register_gate`].
+pub type GateFactory = dyn Fn(&BeatTimeBase) -> Result<Box<dyn Gate>, String> + Send + Sync;
+
+/// Factory of a registered emitter, see [`register_emitter`].
+pub type EmitterFactory = dyn Fn(&BeatTimeBase) -> Result<Box<dyn EventIter>, String> + Send + Sync;
+
+/// Factory of a registered rhythm, see [`register_rhythm`].
+pub type RhythmFactory =
+    dyn Fn(&BeatTimeBase) -> Result<Rc<RefCell<dyn Rhythm>>, String> + Send + Sync;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Kind of a registered implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegistryKind {
+    /// A [`Pattern`], see [`register_pattern`].
+    Pattern,
+    /// A [`Gate`], see [`register_gate`].
+    Gate,
+    /// An emitter [`EventIter`], see [`register_emitter`].
+    Emitter,
+    /// A [`Rhythm`], see [`register_rhythm`].
+    Rhythm,
+}
+
+impl Display for RegistryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Pattern => "pattern",
+            Self::Gate => "gate",
+            Self::Emitter => "emitter",
+            Self::Rhythm => "rhythm",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Thread-safe name to factory map of a single registry kind.
+struct Registry<F: ?Sized> {
+    kind: RegistryKind,
+    factories: RwLock<HashMap<String, Arc<F>>>,
+}
+
+impl<F: ?Sized> Registry<F> {
+    fn new(kind: RegistryKind) -> Self {
+        let factories = RwLock::new(HashMap::new());
+        Self { kind, factories }
+    }
+
+    fn register(&self, name: &str, factory: Arc<F>) -> Result<(), String> {
+        validate_name(name)?;
+        self.factories
+            .write()
+            .expect("Failed to access implementation registry")
+            .insert(name.to_string(), factory);
+        Ok(())
+    }
+
+    fn unregister(&self, name: &str) {
+        self.factories
+            .write()
+            .expect("Failed to access implementation registry")
+            .remove(name);
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.factories
+            .read()
+            .expect("Failed to access implementation registry")
+            .contains_key(name)
+    }
+
+    fn get(&self, name: &str) -> Result<Arc<F>, String> {
+        self.factories
+            .read()
+            .expect("Failed to access implementation registry")
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("unknown {} '{}'", self.kind, name))
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names = self
+            .factories
+            .read()
+            .expect("Failed to access implementation registry")
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+}
+
+lazy_static! {
+    static ref PATTERNS: Registry<PatternFactory> = Registry::new(RegistryKind::Pattern);
+    static ref GATES: Registry<GateFactory> = Registry::new(RegistryKind::Gate);
+    static ref EMITTERS: Registry<EmitterFactory> = Registry::new(RegistryKind::Emitter);
+    static ref RHYTHMS: Registry<RhythmFactory> = Registry::new(RegistryKind::Rhythm);
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Register or replace a named pattern implementation, e.g. to provide custom patterns from
+/// external crates, which can be used by name in Lua scripts: `pattern = "mycrate.polyrhythm"`.
+///
+/// Names must be namespaced with a `.`, e.g. with the name of the crate which registers them,
+/// and may only contain alphanumeric characters, `_` and `-` otherwise. The factory gets
+/// called with the time base of the rhythm which uses the pattern.
+///
+/// Returns error when the name is invalid.
+pub fn register_pattern<F>(name: &str, factory: F) -> Result<(), String>
+where
+    F: Fn(&BeatTimeBase) -> Result<Box<dyn Pattern>, String> + Send + Sync + 'static,
+{
+    PATTERNS.register(name, Arc::new(factory))
+}
+
+/// Register or replace a named gate implementation, which can be used by name in Lua scripts:
+/// `gate = "mycrate.gate"`. See [`register_pattern`] for a description of valid names.
+pub fn register_gate<F>(name: &str, factory: F) -> Result<(), String>
+where
+    F: Fn(&BeatTimeBase) -> Result<Box<dyn Gate>, String> + Send + Sync + 'static,
+{
+    GATES.register(name, Arc::new(factory))
+}
+
+/// Register or replace a named emitter implementation, which can be used by name in Lua scripts:
+/// `emit = "mycrate.arpeggio"`. See [`register_pattern`] for a description of valid names.
+pub fn register_emitter<F>(name: &str, factory: F) -> Result<(), String>
+where
+    F: Fn(&BeatTimeBase) -> Result<Box<dyn EventIter>, String> + Send + Sync + 'static,
+{
+    EMITTERS.register(name, Arc::new(factory))
+}
+
+/// Register or replace a named rhythm implementation, which can be created by name in Lua
+/// scripts: `return rhythm("mycrate.rhythm")`. See [`register_pattern`] for a description of
+/// valid names.
+pub fn register_rhythm<F>(name: &str, factory: F) -> Result<(), String>
+where
+    F: Fn(&BeatTimeBase) -> Result<Rc<RefCell<dyn Rhythm>>, String> + Send + Sync + 'static,
+{
+    RHYTHMS.register(name, Arc::new(factory))
+}
+
+/// Remove a registered implementation, if it got registered.
+pub fn unregister_implementation(kind: RegistryKind, name: &str) {
+    match kind {
+        RegistryKind::Pattern => PATTERNS.unregister(name),
+        RegistryKind::Gate => GATES.unregister(name),
+        RegistryKind::Emitter => EMITTERS.unregister(name),
+        RegistryKind::Rhythm => RHYTHMS.unregister(name),
+    }
+}
+
+/// Returns true when an implementation with the given kind and name got registered.
+pub fn is_registered(kind: RegistryKind, name: &str) -> bool {
+    match kind {
+        RegistryKind::Pattern => PATTERNS.contains(name),
+        RegistryKind::Gate => GATES.contains(name),
+        RegistryKind::Emitter => EMITTERS.contains(name),
+        RegistryKind::Rhythm => RHYTHMS.contains(name),
+    }
+}
+
+/// Names of all registered implementations of the given kind, sorted by name.
+pub fn registered_names(kind: RegistryKind) -> Vec<String> {
+    match kind {
+        RegistryKind::Pattern => PATTERNS.names(),
+        RegistryKind::Gate => GATES.names(),
+        RegistryKind::Emitter => EMITTERS.names(),
+        RegistryKind::Rhythm => RHYTHMS.names(),
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Create a new instance of a registered pattern.
+///
+/// Returns error when no pattern with the given name is registered or the factory failed.
+pub fn new_registered_pattern(
+    name: &str,
+    time_base: &BeatTimeBase,
+) -> Result<Box<dyn Pattern>, String> {
+    // release the registry lock before calling the factory
+    let factory = PATTERNS.get(name)?;
+    factory(time_base)
+}
+
+/// Create a new instance of a registered gate.
+///
+/// Returns error when no gate with the given name is registered or the factory failed.
+pub fn new_registered_gate(name: &str, time_base: &BeatTimeBase) -> Result<Box<dyn Gate>, String> {
+    let factory = GATES.get(name)?;
+    factory(time_base)
+}
+
+/// Create a new instance of a registered emitter.
+///
+/// Returns error when no emitter with the given name is registered or the factory failed.
+pub fn new_registered_emitter(
+    name: &str,
+    time_base: &BeatTimeBase,
+) -> Result<Box<dyn EventIter>, String> {
+    let factory = EMITTERS.get(name)?;
+    factory(time_base)
+}
+
+/// Create a new instance of a registered rhythm.
+///
+/// Returns error when no rhythm with the given name is registered or the factory failed.
+pub fn new_registered_rhythm(
+    name: &str,
+    time_base: &BeatTimeBase,
+) -> Result<Rc<RefCell<dyn Rhythm>>, String> {
+    let factory = RHYTHMS.get(name)?;
+    factory(time_base)
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn validate_name(name: &str) -> Result<(), String> {
+    let is_valid_part = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+    if name.contains('.') && name.split('.').all(is_valid_part) {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid registry name '{}': expected a namespaced name such as 'mycrate.name'",
+            name
+        ))
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn registry() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        // use names, which are not used in other tests, as registries are global
+        assert!(register_pattern("pulse", |_| Ok(Box::new([1].to_pattern()))).is_err());
+        assert!(register_pattern("test.", |_| Ok(Box::new([1].to_pattern()))).is_err());
+        assert!(register_pattern("test.my pulse", |_| Ok(Box::new([1].to_pattern()))).is_err());
+        register_pattern("registry-test.pulse", |_| {
+            Ok(Box::new([1, 0, 1].to_pattern()))
+        })?;
+        register_rhythm("registry-test.rhythm", |time_base| {
+            Ok(Rc::new(RefCell::new(
+                time_base
+                    .every_nth_beat(1.0)
+                    .trigger(new_note_event(Note::C4)),
+            )))
+        })?;
+        assert!(is_registered(RegistryKind::Pattern, "registry-test.pulse"));
+        assert!(!is_registered(RegistryKind::Gate, "registry-test.pulse"));
+        assert!(registered_names(RegistryKind::Rhythm).contains(&"registry-test.rhythm".into()));
+
+        assert_eq!(
+            new_registered_pattern("registry-test.pulse", &time_base)?.len(),
+            3
+        );
+        let rhythm = new_registered_rhythm("registry-test.rhythm", &time_base)?;
+        assert_eq!(
+            rhythm.borrow_mut().run_until_time(1).map(|item| item.time),
+            Some(0)
+        );
+        assert!(new_registered_gate("registry-test.pulse", &time_base).is_err());
+
+        unregister_implementation(RegistryKind::Pattern, "registry-test.pulse");
+        assert!(new_registered_pattern("registry-test.pulse", &time_base).is_err());
+        Ok(())
+    }
+}
@@ -3,10 +3,16 @@
 
 use std::{borrow::Cow, cell::RefCell, fmt::Debug, rc::Rc};
 
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
 use crate::{
     event::{Event, InstrumentId},
+    memory::MemoryUsage,
+    parameter::RhythmParameter,
+    shared::SharedValues,
     time::SampleTimeDisplay,
-    BeatTimeBase, SampleTime,
+    BeatTimeBase, SampleTime, Warning,
 };
 
 // -------------------------------------------------------------------------------------------------
@@ -14,7 +20,10 @@ use crate::{
 pub(crate) mod generic;
 
 pub mod beat_time;
+pub mod cached;
+pub mod chain;
 pub mod second_time;
+pub mod seed_morph;
 
 // -------------------------------------------------------------------------------------------------
 
@@ -118,9 +127,118 @@ pub trait Rhythm: RhythmIter {
     /// Set optional, application specific external context data for the pattern and emitter.
     fn set_external_context(&mut self, data: &[(Cow<str>, f64)]);
 
+    /// Set the shared values of the sequence the rhythm plays in, which get passed to the
+    /// rhythm's pattern, gate and event iter. The default implementation ignores the values.
+    fn set_shared_values(&mut self, _values: &SharedValues) {}
+
+    /// Set a new seed for all random number generators of the rhythm's gate, event iter
+    /// and humanizer. The seeds are also used in all following resets. Random values in
+    /// scripted callbacks are not affected. The default implementation ignores the seed.
+    fn set_seed(&mut self, _seed: [u8; 32]) {}
+
+    /// Play the rhythm's pattern and the events of cycle event iters backwards, e.g. as a live
+    /// performance toggle. The default implementation ignores it.
+    fn set_reversed(&mut self, _reversed: bool) {}
+
+    /// Apply the rhythm's event processing, e.g. its default instrument, transposer, humanizer,
+    /// panner and event transforms, to an externally generated event, such as events which got
+    /// injected into the rhythm's slot in a sequence. The default implementation leaves the
+    /// event as it is.
+    fn transform_event(&mut self, _event: &mut Event) {}
+
+    /// Get the rhythm's user controllable parameters, if any.
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        Vec::new()
+    }
+
+    /// Fetch and clear all parameter value changes, which got applied by the rhythm itself,
+    /// e.g. by scripted callbacks, as (parameter id, value) pairs.
+    fn take_parameter_changes(&mut self) -> Vec<(String, f64)> {
+        Vec::new()
+    }
+
+    /// Fetch and clear all non-fatal issues, which got detected while running the rhythm, e.g.
+    /// unmapped identifiers in cycles, so hosts can show them. Each distinct warning gets
+    /// reported only once. The default implementation reports no warnings.
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        Vec::new()
+    }
+
+    /// Fetch and clear all errors of scripted callbacks, which failed while running the rhythm,
+    /// so sequences can publish them. The default implementation reports no errors.
+    fn take_script_errors(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Set a new value for the parameter with the given id from the outside, e.g. by a host.
+    /// Such changes are not reported in [`Self::take_parameter_changes`]. Returns the applied,
+    /// clamped value.
+    ///
+    /// Returns error when the parameter does not exist, which is what the default
+    /// implementation does.
+    fn set_parameter_value(&mut self, id: &str, _value: f64) -> Result<f64, String> {
+        Err(format!("parameter '{}' does not exist", id))
+    }
+
+    /// Estimated memory usage of the rhythm's pattern, gate and event iter and of its pending
+    /// events, e.g. to monitor long-running sessions. The default implementation reports no
+    /// memory usage.
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::default()
+    }
+
+    /// Release unused memory of the rhythm, e.g. garbage of Lua engines and unused event
+    /// buffer capacities. Hosts may call this between songs. The default implementation does
+    /// nothing.
+    fn trim_memory(&mut self) {}
+
     /// Create a new cloned instance of this rhythm. This actually is a clone(), wrapped into
     /// a `Box<dyn Rhythm>`, but called 'duplicate' to avoid conflicts with possible Clone impls.
-    fn duplicate(&self) -> Rc<RefCell<dyn Rhythm>>;
+    /// Duplicates don't share their playback state, random number generators, parameter values
+    /// and scripted callback state with the original rhythm. Fails when some state can't be
+    /// duplicated, e.g. for scripted coroutines.
+    fn duplicate(&self) -> Result<Rc<RefCell<dyn Rhythm>>, String>;
     /// Resets/rewinds the rhythm to its initial state.
     fn reset(&mut self);
 }
+
+// -------------------------------------------------------------------------------------------------
+
+/// Convert a numeric seed to a random number generator seed. Lua's `math.randomseed` and the
+/// `seed` rhythm option use the same conversion, so numeric seeds can be shared with scripts.
+pub fn seed_from_number(seed: f64) -> [u8; 32] {
+    let bytes = seed.to_le_bytes();
+    let mut new_seed = [0; 32];
+    for (index, byte) in new_seed.iter_mut().enumerate() {
+        *byte = bytes[index % 8];
+    }
+    new_seed
+}
+
+/// Create a new random seed for random number generators which got no explicit seed.
+///
+/// Uses `rand`'s thread local random number generator. With the `fixed-random-seeds` feature,
+/// seeds get derived from a global counter instead: they differ for each generator, but repeat
+/// in each run of the application.
+#[cfg(not(feature = "fixed-random-seeds"))]
+pub(crate) fn new_random_seed() -> [u8; 32] {
+    rand::thread_rng().gen()
+}
+
+#[cfg(feature = "fixed-random-seeds")]
+pub(crate) fn new_random_seed() -> [u8; 32] {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    static SEED_COUNT: AtomicUsize = AtomicUsize::new(0);
+    let index = SEED_COUNT.fetch_add(1, Ordering::Relaxed);
+    derived_seed([0x5a; 32], index as u64)
+}
+
+/// Derive a new, independent random seed from the given seed and index, e.g. to seed multiple
+/// random number generators in a rhythm or phrase from a single seed.
+pub(crate) fn derived_seed(seed: [u8; 32], index: u64) -> [u8; 32] {
+    let mut seed = seed;
+    for (byte, index_byte) in seed.iter_mut().zip(index.to_le_bytes()) {
+        *byte ^= index_byte;
+    }
+    Xoshiro256PlusPlus::from_seed(seed).gen()
+}
@@ -1,9 +1,10 @@
 //! Beat time based `Rhythm` implementation.
 
 use crate::{
+    event::piano_roll::PianoRollEventIter,
     rhythm::generic::{GenericRhythm, GenericRhythmTimeStep},
     time::BeatTimeStep,
-    BeatTimeBase,
+    BeatTimeBase, Note,
 };
 
 // -------------------------------------------------------------------------------------------------
@@ -52,4 +53,11 @@ impl BeatTimeBase {
     generate_step_funcs!(beat, BeatTimeStep::Beats);
     generate_step_funcs!(half, BeatTimeStep::Half);
     generate_step_funcs!(bar, BeatTimeStep::Bar);
+
+    /// Create a rhythm which loops the given piano roll notes, specified as
+    /// `(start_beat, duration, note, velocity)` tuples. See [`PianoRollEventIter`].
+    pub fn piano_roll(&self, notes: &[(f64, f64, Note, f32)]) -> BeatTimeRhythm {
+        self.every_nth_beat(1.0)
+            .trigger(PianoRollEventIter::new(notes))
+    }
 }
@@ -0,0 +1,431 @@
+//! Cache the events of rhythms in looped event clips, to save CPU time of heavy scripted rhythms.
+
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
+
+use crate::{
+    event::{Event, InstrumentId},
+    memory::{event_memory_usage, MemoryUsage},
+    parameter::RhythmParameter,
+    shared::SharedValues,
+    time::SampleTimeDisplay,
+    BeatTimeBase, Rhythm, RhythmIter, RhythmIterItem, SampleTime, Warning,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Caches the events of a rhythm in a looped event clip: the rhythm gets rendered once into a
+/// clip of events, which then is played back instead of running the rhythm's pattern, gate and
+/// emitter again, e.g. to save CPU time of heavy scripted rhythms in a rhythm slot.
+///
+/// Clips contain the rhythm's events, not audio: cached events still get played by the player's
+/// instruments, just like the events of any other rhythm.
+///
+/// The clip covers the rhythm's pattern length times the given repeat count. Random values
+/// and generator states thus repeat in every loop of the clip.
+///
+/// Changes which affect the rhythm's output, like parameter value, seed, instrument, time base
+/// or external context changes, or replacing the rhythm via [`set_rhythm`](Self::set_rhythm),
+/// invalidate the clip. Invalidated clips keep playing while the new clip gets rendered into a
+/// second buffer in slices: every run renders twice the run's duration of the new clip, so a
+/// re-render never blocks a single run for the entire clip's length. The new clip replaces the
+/// playing one at the start of the first loop after it got completed, so edits never interrupt
+/// a playing loop.
+///
+/// Rhythms can't be moved to other threads, so slices get rendered on the thread which runs the
+/// cached rhythm. Only [`new`](Self::new), [`with_repeats`](Self::with_repeats) and
+/// [`reset`](Rhythm::reset) render entire clips at once.
+///
+/// The entire clip is a single pattern step of the clip's length.
+#[derive(Clone, Debug)]
+pub struct CachedRhythm {
+    time_base: BeatTimeBase,
+    rhythm: Rc<RefCell<dyn Rhythm>>,
+    repeats: usize,
+    clip: Vec<RhythmIterItem>,
+    clip_length: SampleTime,
+    clip_index: usize,
+    loop_start: SampleTime,
+    next_clip: Vec<RhythmIterItem>,
+    next_clip_length: SampleTime,
+    next_clip_position: Option<SampleTime>,
+    needs_render: bool,
+    render_count: usize,
+    last_run_time: Option<SampleTime>,
+    sample_offset: SampleTime,
+}
+
+impl CachedRhythm {
+    /// Create a new cached rhythm, which loops a single pattern cycle of the given rhythm.
+    pub fn new(time_base: BeatTimeBase, rhythm: Rc<RefCell<dyn Rhythm>>) -> Self {
+        let mut cached = Self {
+            time_base,
+            rhythm,
+            repeats: 1,
+            clip: Vec::new(),
+            clip_length: 0,
+            clip_index: 0,
+            loop_start: 0,
+            next_clip: Vec::new(),
+            next_clip_length: 0,
+            next_clip_position: None,
+            needs_render: true,
+            render_count: 0,
+            last_run_time: None,
+            sample_offset: 0,
+        };
+        cached.render();
+        cached
+    }
+
+    /// Return a new cached rhythm, which renders the given number of pattern cycles into its
+    /// clip, e.g. to cache rhythms which vary their output in every cycle.
+    #[must_use]
+    pub fn with_repeats(self, repeats: usize) -> Self {
+        let mut cached = Self {
+            repeats: repeats.max(1),
+            ..self
+        };
+        cached.invalidate();
+        cached.render();
+        cached
+    }
+
+    /// The rhythm whose events get cached.
+    pub fn rhythm(&self) -> &Rc<RefCell<dyn Rhythm>> {
+        &self.rhythm
+    }
+
+    /// Replace the rhythm whose events get cached, e.g. after its script got edited. The new
+    /// rhythm gets rendered at the start of the next loop.
+    pub fn set_rhythm(&mut self, rhythm: Rc<RefCell<dyn Rhythm>>) {
+        self.rhythm = rhythm;
+        self.invalidate();
+    }
+
+    /// Number of pattern cycles in the clip.
+    pub fn repeats(&self) -> usize {
+        self.repeats
+    }
+
+    /// The rendered clip's events, with sample times relative to the clip's start.
+    pub fn clip(&self) -> &[RhythmIterItem] {
+        &self.clip
+    }
+
+    /// Length of the rendered clip in samples.
+    pub fn clip_length(&self) -> SampleTime {
+        self.clip_length
+    }
+
+    /// Returns true when the clip got invalidated and did not yet get replaced by a new clip.
+    pub fn needs_render(&self) -> bool {
+        self.needs_render || self.next_clip_position.is_some()
+    }
+
+    /// Number of times the clip got rendered so far.
+    pub fn render_count(&self) -> usize {
+        self.render_count
+    }
+
+    /// Mark the clip as outdated, so it gets re-rendered. Discards partially rendered clips.
+    pub fn invalidate(&mut self) {
+        self.needs_render = true;
+        self.next_clip_position = None;
+    }
+
+    /// Render the entire next clip, when the clip is outdated, and replace the clip with it.
+    fn render(&mut self) {
+        if self.needs_render {
+            self.start_render();
+        }
+        if self.next_clip_position.is_some() {
+            self.render_until(self.next_clip_length);
+            self.swap_clips();
+        }
+    }
+
+    /// Reset the rhythm to start rendering a new next clip.
+    fn start_render(&mut self) {
+        let mut rhythm = self.rhythm.borrow_mut();
+        let pattern_samples = rhythm.pattern_step_length() * rhythm.pattern_length() as f64;
+        self.next_clip_length = (pattern_samples * self.repeats as f64).round() as SampleTime;
+        self.next_clip.clear();
+        self.next_clip_position = Some(0);
+        self.needs_render = false;
+        rhythm.reset();
+        rhythm.set_sample_offset(0);
+    }
+
+    /// Run the rhythm until the given clip position and add its events to the next clip.
+    fn render_until(&mut self, position: SampleTime) {
+        let position = position.min(self.next_clip_length);
+        let mut rhythm = self.rhythm.borrow_mut();
+        while let Some(item) = rhythm.run_until_time(position) {
+            self.next_clip.push(item);
+        }
+        self.next_clip_position = Some(position);
+    }
+
+    /// Render the next slice of an outdated clip, which is twice as long as the time that passed
+    /// since the last run.
+    fn render_slice(&mut self, sample_time: SampleTime) {
+        let run_duration = self
+            .last_run_time
+            .map_or(0, |last_run_time| sample_time.saturating_sub(last_run_time));
+        self.last_run_time = Some(sample_time);
+        if self.needs_render {
+            self.start_render();
+        }
+        if let Some(position) = self.next_clip_position {
+            if position < self.next_clip_length && run_duration > 0 {
+                self.render_until(position + 2 * run_duration);
+            }
+        }
+    }
+
+    /// Returns true when the next clip got rendered completely.
+    fn next_clip_completed(&self) -> bool {
+        self.next_clip_position
+            .is_some_and(|position| position >= self.next_clip_length)
+    }
+
+    /// Replace the playing clip with the fully rendered next clip.
+    fn swap_clips(&mut self) {
+        std::mem::swap(&mut self.clip, &mut self.next_clip);
+        self.clip_length = self.next_clip_length;
+        self.clip_index = 0;
+        self.next_clip.clear();
+        self.next_clip_position = None;
+        self.render_count += 1;
+    }
+}
+
+impl RhythmIter for CachedRhythm {
+    fn sample_time_display(&self) -> Box<dyn SampleTimeDisplay> {
+        Box::new(self.time_base)
+    }
+
+    fn sample_offset(&self) -> SampleTime {
+        self.sample_offset
+    }
+    fn set_sample_offset(&mut self, sample_offset: SampleTime) {
+        self.sample_offset = sample_offset;
+    }
+
+    fn run_until_time(&mut self, sample_time: SampleTime) -> Option<RhythmIterItem> {
+        if self.clip_length == 0 {
+            return None;
+        }
+        self.render_slice(sample_time);
+        loop {
+            let loop_start = self.sample_offset + self.loop_start;
+            if let Some(item) = self.clip.get(self.clip_index) {
+                if loop_start + item.time >= sample_time {
+                    return None;
+                }
+                self.clip_index += 1;
+                return Some(item.clone().with_offset(loop_start));
+            }
+            // move on to the next loop, swapping in completely rendered clips
+            if loop_start + self.clip_length >= sample_time {
+                return None;
+            }
+            self.loop_start += self.clip_length;
+            self.clip_index = 0;
+            if self.next_clip_completed() {
+                self.swap_clips();
+                if self.clip_length == 0 {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl Rhythm for CachedRhythm {
+    fn pattern_step_length(&self) -> f64 {
+        self.clip_length as f64
+    }
+
+    fn pattern_length(&self) -> usize {
+        1
+    }
+
+    fn time_base(&self) -> &BeatTimeBase {
+        &self.time_base
+    }
+
+    fn set_time_base(&mut self, time_base: &BeatTimeBase) {
+        self.time_base = *time_base;
+        self.rhythm.borrow_mut().set_time_base(time_base);
+        self.invalidate();
+    }
+
+    fn set_instrument(&mut self, instrument: Option<InstrumentId>) {
+        self.rhythm.borrow_mut().set_instrument(instrument);
+        self.invalidate();
+    }
+
+    fn set_external_context(&mut self, data: &[(Cow<str>, f64)]) {
+        self.rhythm.borrow_mut().set_external_context(data);
+        self.invalidate();
+    }
+
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        self.rhythm.borrow_mut().set_shared_values(values);
+        self.invalidate();
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.rhythm.borrow_mut().set_seed(seed);
+        self.invalidate();
+    }
+
+    fn set_reversed(&mut self, reversed: bool) {
+        self.rhythm.borrow_mut().set_reversed(reversed);
+        self.invalidate();
+    }
+
+    fn transform_event(&mut self, event: &mut Event) {
+        self.rhythm.borrow_mut().transform_event(event);
+    }
+
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        self.rhythm.borrow().parameters()
+    }
+
+    fn take_parameter_changes(&mut self) -> Vec<(String, f64)> {
+        self.rhythm.borrow_mut().take_parameter_changes()
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        self.rhythm.borrow_mut().take_warnings()
+    }
+
+    fn take_script_errors(&mut self) -> Vec<String> {
+        self.rhythm.borrow_mut().take_script_errors()
+    }
+
+    fn set_parameter_value(&mut self, id: &str, value: f64) -> Result<f64, String> {
+        let value = self.rhythm.borrow_mut().set_parameter_value(id, value)?;
+        self.invalidate();
+        Ok(value)
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let clip_events = self
+            .clip
+            .iter()
+            .chain(self.next_clip.iter())
+            .filter_map(|item| item.event.as_ref())
+            .map(event_memory_usage)
+            .sum();
+        self.rhythm.borrow().memory_usage()
+            + MemoryUsage {
+                events: clip_events,
+                ..MemoryUsage::default()
+            }
+    }
+
+    fn trim_memory(&mut self) {
+        self.rhythm.borrow_mut().trim_memory();
+        self.clip.shrink_to_fit();
+        self.next_clip.shrink_to_fit();
+    }
+
+    fn duplicate(&self) -> Result<Rc<RefCell<dyn Rhythm>>, String> {
+        let mut duplicate = Self {
+            rhythm: self.rhythm.borrow().duplicate()?,
+            ..self.clone()
+        };
+        // restart partial renders, as the duplicated rhythm may not continue them
+        if duplicate.next_clip_position.is_some() {
+            duplicate.invalidate();
+        }
+        Ok(Rc::new(RefCell::new(duplicate)))
+    }
+
+    fn reset(&mut self) {
+        self.sample_offset = 0;
+        self.loop_start = 0;
+        self.clip_index = 0;
+        self.last_run_time = None;
+        self.render();
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn cached() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let rhythm: Rc<RefCell<dyn Rhythm>> = Rc::new(RefCell::new(
+            time_base
+                .every_nth_beat(1.0)
+                .with_pattern([1, 0].to_pattern())
+                .trigger(new_note_event(Note::C4))
+                .with_parameters(vec![RhythmParameter::new("transpose", 0.0..=12.0, 0.0)]),
+        ));
+        let mut cached = CachedRhythm::new(time_base, Rc::clone(&rhythm)).with_repeats(2);
+        assert_eq!(cached.clip_length(), 2000);
+        assert_eq!(cached.clip().len(), 4);
+        let render_count = cached.render_count();
+
+        let run = |cached: &mut CachedRhythm, sample_time| {
+            let mut times = Vec::new();
+            while let Some(item) = cached.run_until_time(sample_time) {
+                if item.event.is_some() {
+                    times.push(item.time);
+                }
+            }
+            times
+        };
+        // loops the clip without running the rhythm again
+        assert_eq!(run(&mut cached, 4500), vec![0, 1000, 2000, 3000, 4000]);
+        assert_eq!(cached.render_count(), render_count);
+
+        // parameter changes re-render in the next loop only
+        cached.set_parameter_value("transpose", 12.0)?;
+        assert!(cached.needs_render());
+        assert_eq!(run(&mut cached, 6000), vec![5000]);
+        assert_eq!(cached.render_count(), render_count);
+        assert_eq!(run(&mut cached, 6001), vec![6000]);
+        assert_eq!(cached.render_count(), render_count + 1);
+        assert!(!cached.needs_render());
+
+        // late changes render in slices and keep looping the outdated clip until completed
+        assert_eq!(run(&mut cached, 7900), vec![7000]);
+        cached.set_parameter_value("transpose", 0.0)?;
+        assert_eq!(run(&mut cached, 8001), vec![8000]);
+        assert!(cached.needs_render());
+        assert_eq!(cached.render_count(), render_count + 1);
+        assert_eq!(run(&mut cached, 10001), vec![9000, 10000]);
+        assert!(!cached.needs_render());
+        assert_eq!(cached.render_count(), render_count + 2);
+
+        // replaced rhythms
+        cached.set_rhythm(Rc::new(RefCell::new(
+            time_base
+                .every_nth_beat(1.0)
+                .trigger(new_note_event(Note::E4)),
+        )));
+        cached.reset();
+        assert_eq!(cached.clip_length(), 500);
+        assert_eq!(run(&mut cached, 2500), vec![0, 500, 1000, 1500, 2000]);
+
+        // offsets
+        cached.reset();
+        cached.set_sample_offset(100);
+        assert_eq!(run(&mut cached, 1200), vec![100, 600, 1100]);
+        Ok(())
+    }
+}
@@ -0,0 +1,412 @@
+//! Play multiple rhythms one after another in a single rhythm slot.
+
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
+
+use crate::{
+    event::{Event, InstrumentId},
+    memory::MemoryUsage,
+    parameter::RhythmParameter,
+    rhythm::derived_seed,
+    shared::SharedValues,
+    time::SampleTimeDisplay,
+    BeatTimeBase, Rhythm, RhythmIter, RhythmIterItem, SampleTime, Warning,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A single rhythm in a [`ChainRhythm`] with its repeat count.
+#[derive(Clone, Debug)]
+struct ChainEntry {
+    rhythm: Rc<RefCell<dyn Rhythm>>,
+    repeats: usize,
+}
+
+impl ChainEntry {
+    /// Length of the entry in samples: the rhythm's pattern length times the repeat count.
+    fn length(&self) -> SampleTime {
+        let rhythm = self.rhythm.borrow();
+        let pattern_samples = rhythm.pattern_step_length() * rhythm.pattern_length() as f64;
+        (pattern_samples * self.repeats as f64).round() as SampleTime
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Plays an ordered chain of rhythms with repeat counts, e.g. A x4, B x2, A x4, in a single
+/// rhythm slot, so simple song structures can be built without phrases or scenes.
+///
+/// Each rhythm plays for its pattern length times its repeat count and gets restarted when
+/// its turn comes. After the last rhythm, the chain starts over with the first one. Rhythms
+/// with empty patterns or repeat counts of 0 are skipped.
+///
+/// The entire chain is a single pattern step of the chain's length.
+#[derive(Clone, Debug)]
+pub struct ChainRhythm {
+    time_base: BeatTimeBase,
+    entries: Vec<ChainEntry>,
+    entry_index: usize,
+    entry_start: SampleTime,
+    sample_offset: SampleTime,
+}
+
+impl ChainRhythm {
+    /// Create a new chain from the given (rhythm, repeat count) pairs.
+    pub fn new(time_base: BeatTimeBase, rhythms: Vec<(Rc<RefCell<dyn Rhythm>>, usize)>) -> Self {
+        let entries = rhythms
+            .into_iter()
+            .map(|(rhythm, repeats)| ChainEntry { rhythm, repeats })
+            .collect();
+        let mut chain = Self {
+            time_base,
+            entries,
+            entry_index: 0,
+            entry_start: 0,
+            sample_offset: 0,
+        };
+        chain.reset();
+        chain
+    }
+
+    /// Number of rhythms in the chain.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true when the chain contains no rhythms.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Index of the currently playing rhythm in the chain.
+    pub fn current_index(&self) -> usize {
+        self.entry_index
+    }
+
+    /// Repeat counts of all rhythms in the chain.
+    pub fn repeat_counts(&self) -> Vec<usize> {
+        self.entries.iter().map(|entry| entry.repeats).collect()
+    }
+
+    /// Length of the entire chain in samples.
+    pub fn length(&self) -> SampleTime {
+        self.entries.iter().map(ChainEntry::length).sum()
+    }
+
+    /// Absolute end time of the currently playing entry.
+    fn entry_end(&self) -> SampleTime {
+        self.sample_offset + self.entry_start + self.entries[self.entry_index].length()
+    }
+
+    /// Move on to the next entry with a non zero length and restart its rhythm.
+    /// Returns false when the chain has no playable entries.
+    fn advance(&mut self) -> bool {
+        let entry_end = self.entry_start + self.entries[self.entry_index].length();
+        for _ in 0..self.entries.len() {
+            self.entry_index = (self.entry_index + 1) % self.entries.len();
+            if self.entries[self.entry_index].length() > 0 {
+                self.entry_start = entry_end;
+                self.restart_entry();
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Reset the current entry's rhythm and move it to the entry's start time.
+    fn restart_entry(&mut self) {
+        let mut rhythm = self.entries[self.entry_index].rhythm.borrow_mut();
+        rhythm.reset();
+        rhythm.set_sample_offset(self.sample_offset + self.entry_start);
+    }
+
+    /// Apply the given function to all distinct rhythms in the chain.
+    fn for_each_rhythm<F: FnMut(usize, &mut dyn Rhythm)>(&self, mut func: F) {
+        let mut visited_rhythms: Vec<&Rc<RefCell<dyn Rhythm>>> = Vec::new();
+        for entry in &self.entries {
+            if visited_rhythms
+                .iter()
+                .any(|other| Rc::ptr_eq(other, &entry.rhythm))
+            {
+                continue;
+            }
+            visited_rhythms.push(&entry.rhythm);
+            func(visited_rhythms.len() - 1, &mut *entry.rhythm.borrow_mut());
+        }
+    }
+}
+
+impl RhythmIter for ChainRhythm {
+    fn sample_time_display(&self) -> Box<dyn SampleTimeDisplay> {
+        Box::new(self.time_base)
+    }
+
+    fn sample_offset(&self) -> SampleTime {
+        self.sample_offset
+    }
+    fn set_sample_offset(&mut self, sample_offset: SampleTime) {
+        self.sample_offset = sample_offset;
+        if let Some(entry) = self.entries.get(self.entry_index) {
+            entry
+                .rhythm
+                .borrow_mut()
+                .set_sample_offset(self.sample_offset + self.entry_start);
+        }
+    }
+
+    fn run_until_time(&mut self, sample_time: SampleTime) -> Option<RhythmIterItem> {
+        if self.length() == 0 {
+            return None;
+        }
+        loop {
+            let entry_end = self.entry_end();
+            let item = self.entries[self.entry_index]
+                .rhythm
+                .borrow_mut()
+                .run_until_time(sample_time.min(entry_end));
+            if item.is_some() || sample_time <= entry_end || !self.advance() {
+                return item;
+            }
+        }
+    }
+
+    fn seek_until_time(&mut self, sample_time: SampleTime) {
+        if self.length() == 0 {
+            return;
+        }
+        loop {
+            let entry_end = self.entry_end();
+            self.entries[self.entry_index]
+                .rhythm
+                .borrow_mut()
+                .seek_until_time(sample_time.min(entry_end));
+            if sample_time <= entry_end || !self.advance() {
+                return;
+            }
+        }
+    }
+}
+
+impl Rhythm for ChainRhythm {
+    fn pattern_step_length(&self) -> f64 {
+        self.length() as f64
+    }
+
+    fn pattern_length(&self) -> usize {
+        1
+    }
+
+    fn time_base(&self) -> &BeatTimeBase {
+        &self.time_base
+    }
+
+    fn set_time_base(&mut self, time_base: &BeatTimeBase) {
+        self.time_base = *time_base;
+        self.for_each_rhythm(|_, rhythm| rhythm.set_time_base(time_base));
+    }
+
+    fn set_instrument(&mut self, instrument: Option<InstrumentId>) {
+        self.for_each_rhythm(|_, rhythm| rhythm.set_instrument(instrument));
+    }
+
+    fn set_external_context(&mut self, data: &[(Cow<str>, f64)]) {
+        self.for_each_rhythm(|_, rhythm| rhythm.set_external_context(data));
+    }
+
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        self.for_each_rhythm(|_, rhythm| rhythm.set_shared_values(values));
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.for_each_rhythm(|index, rhythm| rhythm.set_seed(derived_seed(seed, index as u64)));
+    }
+
+    fn set_reversed(&mut self, reversed: bool) {
+        self.for_each_rhythm(|_, rhythm| rhythm.set_reversed(reversed));
+    }
+
+    fn transform_event(&mut self, event: &mut Event) {
+        // transform with the currently playing rhythm only
+        if let Some(entry) = self.entries.get(self.entry_index) {
+            entry.rhythm.borrow_mut().transform_event(event);
+        }
+    }
+
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        // rhythms may share parameters: list them only once
+        let mut parameters = Vec::<RhythmParameter>::new();
+        self.for_each_rhythm(|_, rhythm| {
+            for parameter in rhythm.parameters() {
+                if !parameters.iter().any(|other| other.id() == parameter.id()) {
+                    parameters.push(parameter);
+                }
+            }
+        });
+        parameters
+    }
+
+    fn take_parameter_changes(&mut self) -> Vec<(String, f64)> {
+        let mut changes = Vec::new();
+        self.for_each_rhythm(|_, rhythm| changes.append(&mut rhythm.take_parameter_changes()));
+        changes
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        self.for_each_rhythm(|_, rhythm| warnings.append(&mut rhythm.take_warnings()));
+        warnings
+    }
+
+    fn take_script_errors(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.for_each_rhythm(|_, rhythm| errors.append(&mut rhythm.take_script_errors()));
+        errors
+    }
+
+    fn set_parameter_value(&mut self, id: &str, value: f64) -> Result<f64, String> {
+        // apply to all rhythms which have a parameter with the given id
+        let mut result = Err(format!("parameter '{}' does not exist", id));
+        self.for_each_rhythm(|_, rhythm| {
+            if let Ok(value) = rhythm.set_parameter_value(id, value) {
+                result = Ok(value);
+            }
+        });
+        result
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage::default();
+        self.for_each_rhythm(|_, rhythm| usage += rhythm.memory_usage());
+        usage
+    }
+
+    fn trim_memory(&mut self) {
+        self.for_each_rhythm(|_, rhythm| rhythm.trim_memory());
+    }
+
+    fn duplicate(&self) -> Result<Rc<RefCell<dyn Rhythm>>, String> {
+        // duplicate shared rhythms only once
+        let mut duplicates: Vec<(&Rc<RefCell<dyn Rhythm>>, Rc<RefCell<dyn Rhythm>>)> = Vec::new();
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let rhythm = match duplicates
+                    .iter()
+                    .find(|(original, _)| Rc::ptr_eq(original, &entry.rhythm))
+                {
+                    Some((_, duplicate)) => Rc::clone(duplicate),
+                    None => {
+                        let duplicate = entry.rhythm.borrow().duplicate()?;
+                        duplicates.push((&entry.rhythm, Rc::clone(&duplicate)));
+                        duplicate
+                    }
+                };
+                Ok(ChainEntry {
+                    rhythm,
+                    repeats: entry.repeats,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Rc::new(RefCell::new(Self {
+            entries,
+            ..self.clone()
+        })))
+    }
+
+    fn reset(&mut self) {
+        self.sample_offset = 0;
+        self.entry_index = 0;
+        self.entry_start = 0;
+        self.for_each_rhythm(|_, rhythm| rhythm.reset());
+        if let Some(index) = self.entries.iter().position(|entry| entry.length() > 0) {
+            self.entry_index = index;
+            self.restart_entry();
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn chain() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        // a: 2 beats long, b: 1 beat long
+        let a: Rc<RefCell<dyn Rhythm>> = Rc::new(RefCell::new(
+            time_base
+                .every_nth_beat(1.0)
+                .with_pattern([1, 1].to_pattern())
+                .trigger(new_note_event(Note::C4)),
+        ));
+        let b: Rc<RefCell<dyn Rhythm>> = Rc::new(RefCell::new(
+            time_base
+                .every_nth_beat(1.0)
+                .trigger(new_note_event(Note::E4)),
+        ));
+        let mut chain = ChainRhythm::new(
+            time_base,
+            vec![
+                (Rc::clone(&a), 2),
+                (Rc::clone(&b), 0),
+                (Rc::clone(&b), 2),
+                (Rc::clone(&a), 1),
+            ],
+        );
+        assert_eq!(chain.len(), 4);
+        assert_eq!(chain.length(), 4000);
+        assert_eq!(
+            chain.pattern_step_length() * chain.pattern_length() as f64,
+            4000.0
+        );
+
+        let run = |chain: &mut ChainRhythm, sample_time| {
+            let mut events = Vec::new();
+            while let Some(item) = chain.run_until_time(sample_time) {
+                if let Some(Event::NoteEvents(note_events)) = item.event {
+                    events.push((item.time, note_events[0].as_ref().unwrap().note));
+                }
+            }
+            events
+        };
+        assert_eq!(
+            run(&mut chain, 4000),
+            vec![
+                (0, Note::C4),
+                (500, Note::C4),
+                (1000, Note::C4),
+                (1500, Note::C4),
+                (2000, Note::E4),
+                (2500, Note::E4),
+                (3000, Note::C4),
+                (3500, Note::C4),
+            ]
+        );
+        assert_eq!(chain.current_index(), 3);
+        // wraps around
+        assert_eq!(
+            run(&mut chain, 5000),
+            vec![(4000, Note::C4), (4500, Note::C4)]
+        );
+        assert_eq!(chain.current_index(), 0);
+
+        // offsets and seeking
+        chain.reset();
+        chain.set_sample_offset(100);
+        chain.seek_until_time(2600);
+        assert_eq!(chain.current_index(), 2);
+        assert_eq!(run(&mut chain, 3100), vec![(2600, Note::E4)]);
+
+        // empty chains
+        let mut chain = ChainRhythm::new(time_base, vec![(a, 0)]);
+        assert!(chain.run_until_time(1000).is_none());
+        assert!(ChainRhythm::new(time_base, vec![]).is_empty());
+    }
+}
@@ -5,6 +5,7 @@ use std::{
     cell::RefCell,
     collections::VecDeque,
     fmt::Debug,
+    ops::RangeInclusive,
     rc::Rc,
 };
 
@@ -14,15 +15,37 @@ use fraction::{ConstOne, ConstZero, Fraction, ToPrimitive};
 use std::borrow::BorrowMut;
 
 use crate::{
-    event::{fixed::FixedEventIter, Event, EventIter, EventIterItem, InstrumentId},
+    event::{
+        echo::EventEcho,
+        fixed::FixedEventIter,
+        humanizer::EventHumanizer,
+        panner::EventPanner,
+        quantizer::EventQuantizer,
+        relative::RelativeNoteResolver,
+        transform::{
+            BuiltinTransform, EventTransform, EventTransformPulse, EventTransformStack,
+            GrooveVelocity, PulseVolume,
+        },
+        transposer::EventTransposer,
+        Event, EventIter, EventIterItem, InstrumentId,
+    },
     gate::probability::ProbabilityGate,
+    memory::{event_memory_usage, MemoryUsage},
+    parameter::{RhythmParameter, RhythmParameterValues},
     pattern::{fixed::FixedPattern, Pattern},
+    rhythm::{derived_seed, seed_morph::SeedMorph},
+    shared::SharedValues,
     time::{BeatTimeBase, SampleTimeDisplay},
-    Gate, PulseIterItem, Rhythm, RhythmIter, RhythmIterItem, SampleTime,
+    Gate, Groove, PulseIterItem, Rhythm, RhythmIter, RhythmIterItem, SampleTime, Warning,
 };
 
 // -------------------------------------------------------------------------------------------------
 
+/// Parameter id of a rhythm's step length factor, see [`GenericRhythm::with_resolution_parameter`].
+pub const RESOLUTION_PARAMETER: &str = "rhythm.resolution";
+
+// -------------------------------------------------------------------------------------------------
+
 /// Time value of a `GenericRhythm`, used either as Step or Offset.
 pub trait GenericRhythmTimeStep: Debug + Clone + Copy + 'static {
     /// The default offset value of the `RhythmTimeStep`. Usually some `0` value.
@@ -49,9 +72,15 @@ pub struct GenericRhythm<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeS
     pattern: Box<dyn Pattern>,
     gate: Box<dyn Gate>,
     event_iter: Box<dyn EventIter>,
+    transforms: EventTransformStack,
+    groove: Option<Groove>,
+    parameters: RhythmParameterValues,
+    resolution_parameter: Option<RhythmParameter>,
     event_iter_sample_time: SampleTime,
     event_iter_next_sample_time: f64,
     event_iter_pulse_item: PulseIterItem,
+    event_iter_step_scale: f64,
+    event_iter_step_count: f64,
     event_iter_items: VecDeque<EventIterItem>,
     sample_offset: SampleTime,
 }
@@ -65,9 +94,19 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> GenericRhythm<S
         let pattern = Box::<FixedPattern>::default();
         let gate = Box::new(ProbabilityGate::new(seed));
         let event_iter = Box::<FixedEventIter>::default();
+        let mut transforms = EventTransformStack::new(seed);
+        transforms.set_builtin(
+            BuiltinTransform::RelativeNotes,
+            Some(Box::new(RelativeNoteResolver::default())),
+        );
+        let groove = None;
+        let parameters = RhythmParameterValues::default();
+        let resolution_parameter = None;
         let event_iter_sample_time = 0;
         let event_iter_next_sample_time = offset.to_samples(&time_base);
         let event_iter_pulse_item = PulseIterItem::default();
+        let event_iter_step_scale = 1.0;
+        let event_iter_step_count = 0.0;
         let event_iter_items = VecDeque::new();
         let sample_offset = 0;
         Self {
@@ -78,9 +117,15 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> GenericRhythm<S
             pattern,
             gate,
             event_iter,
+            transforms,
+            groove,
+            parameters,
+            resolution_parameter,
             event_iter_sample_time,
             event_iter_next_sample_time,
             event_iter_pulse_item,
+            event_iter_step_scale,
+            event_iter_step_count,
             event_iter_items,
             sample_offset,
         }
@@ -114,10 +159,12 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> GenericRhythm<S
         let offset = offset.into().unwrap_or(Offset::default_offset());
         let event_iter_sample_time = 0;
         let event_iter_next_sample_time = offset.to_samples(&self.time_base);
+        let event_iter_step_count = 0.0;
         Self {
             offset,
             event_iter_sample_time,
             event_iter_next_sample_time,
+            event_iter_step_count,
             ..self
         }
     }
@@ -177,24 +224,247 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> GenericRhythm<S
         Self { event_iter, ..self }
     }
 
+    /// Return a new rhythm instance which quantizes all emitted events with the given
+    /// [`EventQuantizer`], after the rhythm's other built-in processing, but before pushed
+    /// transforms. When None, events are emitted as they are.
+    #[must_use]
+    pub fn with_quantizer<Q: Into<Option<EventQuantizer>>>(self, quantizer: Q) -> Self {
+        let quantizer = quantizer.into();
+        let mut rhythm = self;
+        rhythm.transforms.set_builtin(
+            BuiltinTransform::Quantizer,
+            quantizer.map(|quantizer| Box::new(quantizer) as Box<dyn EventTransform>),
+        );
+        rhythm
+    }
+
+    /// Return a new rhythm instance which randomly varies the volume, panning and timing of all
+    /// emitted notes with the given [`EventHumanizer`]. When None, notes are emitted as they are.
+    #[must_use]
+    pub fn with_humanizer<H: Into<Option<EventHumanizer>>>(self, humanizer: H) -> Self {
+        let humanizer = humanizer.into();
+        let mut rhythm = self;
+        rhythm.transforms.set_builtin(
+            BuiltinTransform::Humanizer,
+            humanizer.map(|humanizer| Box::new(humanizer) as Box<dyn EventTransform>),
+        );
+        rhythm
+    }
+
+    /// Return a new rhythm instance which automatically pans all emitted notes with the given
+    /// [`EventPanner`]. When None, notes keep their pannings.
+    #[must_use]
+    pub fn with_panner<P: Into<Option<EventPanner>>>(self, panner: P) -> Self {
+        let panner = panner.into();
+        let mut rhythm = self;
+        rhythm.transforms.set_builtin(
+            BuiltinTransform::Panner,
+            panner.map(|panner| Box::new(panner) as Box<dyn EventTransform>),
+        );
+        rhythm
+    }
+
+    /// Return a new rhythm instance with the given [`EventTransform`] pushed on top of the
+    /// rhythm's transform stack, see [`Self::push_transform`].
+    #[must_use]
+    pub fn with_transform<T: EventTransform + 'static>(self, transform: T) -> Self {
+        let mut rhythm = self;
+        rhythm.push_transform(transform);
+        rhythm
+    }
+
+    /// Push a new [`EventTransform`] on top of the rhythm's transform stack, which gets applied
+    /// to all emitted events after the rhythm's built-in processing, such as humanizing and
+    /// panning. Transforms are seeded with the rhythm's seed, so their output is reproducible.
+    /// The transform's parameters get added to the rhythm's parameters.
+    pub fn push_transform<T: EventTransform + 'static>(&mut self, transform: T) {
+        self.parameters.add_parameters(transform.parameters());
+        self.transforms.push(transform);
+    }
+
+    /// Access to the rhythm's event transform stack.
+    pub fn transforms_mut(&mut self) -> &mut EventTransformStack {
+        &mut self.transforms
+    }
+
+    /// Return a new rhythm instance which re-emits all emitted notes as echoes with the given
+    /// [`EventEcho`]. The echo's controls get added to the rhythm's parameters, so they can be
+    /// automated. When None, no echoes are emitted.
+    #[must_use]
+    pub fn with_echo<E: Into<Option<EventEcho>>>(self, echo: E) -> Self {
+        let echo = echo.into();
+        if let Some(echo) = &echo {
+            self.parameters.add_parameters(echo.parameters());
+        }
+        let mut rhythm = self;
+        rhythm.transforms.set_builtin(
+            BuiltinTransform::Echo,
+            echo.map(|echo| Box::new(echo) as Box<dyn EventTransform>),
+        );
+        rhythm
+    }
+
+    /// Return a new rhythm instance which transposes all emitted notes with the given
+    /// [`EventTransposer`]. The transposer's controls get added to the rhythm's parameters, so
+    /// they can be automated. When None, notes are not transposed.
+    #[must_use]
+    pub fn with_transposer<T: Into<Option<EventTransposer>>>(self, transposer: T) -> Self {
+        let transposer = transposer.into();
+        if let Some(transposer) = &transposer {
+            self.parameters.add_parameters(transposer.parameters());
+        }
+        let mut rhythm = self;
+        rhythm.transforms.set_builtin(
+            BuiltinTransform::Transposer,
+            transposer.map(|transposer| Box::new(transposer) as Box<dyn EventTransform>),
+        );
+        rhythm
+    }
+
+    /// Return a new rhythm instance which resolves relative notes, such as `+3` or `^2`, in
+    /// emitted events with the given [`RelativeNoteResolver`]. By default, relative notes are
+    /// resolved with a C4 reference note in C major.
+    #[must_use]
+    pub fn with_relative_notes(self, relative_notes: RelativeNoteResolver) -> Self {
+        let mut rhythm = self;
+        rhythm.transforms.set_builtin(
+            BuiltinTransform::RelativeNotes,
+            Some(Box::new(relative_notes)),
+        );
+        rhythm
+    }
+
+    /// Return a new rhythm instance which remaps the time positions of all pulses with the given
+    /// [`Groove`], and accents note volumes with the groove's velocities, if it has any. When
+    /// None, pulses are played straight.
+    #[must_use]
+    pub fn with_groove<G: Into<Option<Groove>>>(self, groove: G) -> Self {
+        let groove = groove.into();
+        let mut rhythm = self;
+        rhythm.transforms.set_builtin(
+            BuiltinTransform::GrooveVelocity,
+            groove
+                .as_ref()
+                .filter(|groove| !groove.velocities().is_empty())
+                .map(|groove| {
+                    Box::new(GrooveVelocity::new(groove.clone())) as Box<dyn EventTransform>
+                }),
+        );
+        Self { groove, ..rhythm }
+    }
+
+    /// Return a new rhythm instance which periodically reseeds its gate, event iter, humanizer,
+    /// panner and transforms with the given [`SeedMorph`]. Periods are counted in played steps,
+    /// so they follow resolution changes. The morph's controls get added to the rhythm's
+    /// parameters, so they can be automated. When None, seeds don't change while playing.
+    #[must_use]
+    pub fn with_seed_morph<S: Into<Option<SeedMorph>>>(self, seed_morph: S) -> Self {
+        let seed_morph = seed_morph.into();
+        if let Some(seed_morph) = &seed_morph {
+            self.parameters.add_parameters(seed_morph.parameters());
+        }
+        let mut rhythm = self;
+        rhythm.transforms.set_seed_morph(seed_morph);
+        rhythm
+    }
+
+    /// Return a new rhythm instance which scales the volume of all emitted notes with the value
+    /// of the pulse that triggered them, e.g. to use fractional pulse values of scripted patterns
+    /// as velocity. Use a [`ThresholdGate`](crate::gate::threshold::ThresholdGate) to trigger
+    /// fractional pulses, instead of using their values as probabilities.
+    #[must_use]
+    pub fn with_pulse_volume(self, pulse_volume: bool) -> Self {
+        let mut rhythm = self;
+        rhythm.transforms.set_builtin(
+            BuiltinTransform::PulseVolume,
+            pulse_volume.then(|| Box::new(PulseVolume) as Box<dyn EventTransform>),
+        );
+        rhythm
+    }
+
+    /// Return a new rhythm instance which describes its user controllable parameters with the
+    /// given [`RhythmParameter`]S.
+    #[must_use]
+    pub fn with_parameters(self, parameters: Vec<RhythmParameter>) -> Self {
+        self.with_parameter_values(RhythmParameterValues::new(parameters))
+    }
+
+    /// Return a new rhythm instance which uses the given shared parameter value store, e.g. to
+    /// share the values with the rhythm's scripted callbacks.
+    #[must_use]
+    pub fn with_parameter_values(self, parameters: RhythmParameterValues) -> Self {
+        parameters.add_parameters(self.transforms.parameters());
+        if let Some(resolution_parameter) = &self.resolution_parameter {
+            parameters.add_parameters(vec![resolution_parameter.clone()]);
+        }
+        Self { parameters, ..self }
+    }
+
+    /// Return a new rhythm instance whose step length can be modulated at runtime with a
+    /// [`RESOLUTION_PARAMETER`]: a resolution in the given range, e.g. to progressively halve the
+    /// step length in a build-up. The rhythm's step is the step at the given default resolution,
+    /// so other resolutions scale the step by `resolution / value`. Resolution changes apply
+    /// with the next pulse, so the switch never drops or doubles pulses.
+    #[must_use]
+    pub fn with_resolution_parameter(self, range: RangeInclusive<f64>, value: f64) -> Self {
+        let resolution_parameter =
+            RhythmParameter::new(RESOLUTION_PARAMETER, range, value).with_name("Resolution");
+        self.parameters
+            .add_parameters(vec![resolution_parameter.clone()]);
+        let resolution_parameter = Some(resolution_parameter);
+        Self {
+            resolution_parameter,
+            ..self
+        }
+    }
+
     /// Return current pulse duration in samples
     pub fn current_steps_sample_duration(&self) -> f64 {
-        self.step.to_samples(&self.time_base) * self.event_iter_pulse_item.step_time
+        self.step.to_samples(&self.time_base)
+            * self.event_iter_pulse_item.step_time
+            * self.event_iter_step_scale
     }
 
     /// Return start sample time of the given event iter item
     fn event_iter_item_start_time(&self, start: &Fraction) -> SampleTime {
         let step_time = self.current_steps_sample_duration();
-        let event_iter_time = self.sample_offset as f64 + self.event_iter_next_sample_time;
         let start = start.to_f64().unwrap_or(0.0);
-        (event_iter_time + (step_time * start)) as SampleTime
+        let event_iter_time =
+            self.grooved_sample_time(self.event_iter_next_sample_time + (step_time * start));
+        (self.sample_offset as f64 + event_iter_time) as SampleTime
     }
 
     /// Return duration in sample time of the given event iter item
-    fn event_iter_item_duration(&self, length: &Fraction) -> SampleTime {
+    fn event_iter_item_duration(&self, start: &Fraction, length: &Fraction) -> SampleTime {
         let step_time = self.current_steps_sample_duration();
+        let start = start.to_f64().unwrap_or(0.0);
         let length = length.to_f64().unwrap_or(1.0);
-        (step_time * length) as SampleTime
+        if self.groove.is_some() {
+            let start_time = self.event_iter_next_sample_time + (step_time * start);
+            let end_time = start_time + (step_time * length);
+            (self.grooved_sample_time(end_time) - self.grooved_sample_time(start_time))
+                as SampleTime
+        } else {
+            (step_time * length) as SampleTime
+        }
+    }
+
+    /// Apply groove time remapping to the given, unshifted pulse sample time, if any is set
+    fn grooved_sample_time(&self, sample_time: f64) -> f64 {
+        if let Some(groove) = &self.groove {
+            let offset_time = self.offset.to_samples(&self.time_base);
+            let step_time = self.step.to_samples(&self.time_base);
+            let steps = (sample_time - offset_time) / step_time;
+            offset_time + groove.map(steps) * step_time
+        } else {
+            sample_time
+        }
+    }
+
+    /// Advance to the next pulse, counting the played pulse's steps.
+    fn advance_event_iter_pulse(&mut self) {
+        self.event_iter_next_sample_time += self.current_steps_sample_duration();
+        self.event_iter_step_count += self.event_iter_pulse_item.step_time;
     }
 
     /// Set default instrument to event if none is set, else return the event as it is
@@ -219,6 +489,10 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> Clone
             event_iter: self.event_iter.duplicate(),
             event_iter_items: self.event_iter_items.clone(),
             gate: self.gate.duplicate(),
+            transforms: self.transforms.clone(),
+            groove: self.groove.clone(),
+            parameters: self.parameters.clone(),
+            resolution_parameter: self.resolution_parameter.clone(),
             ..*self
         }
     }
@@ -251,13 +525,21 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> RhythmIter
     fn run_until_time(&mut self, sample_time: SampleTime) -> Option<RhythmIterItem> {
         // quickly check if the next event is due before the given target time
         self.event_iter_sample_time = sample_time;
-        let next_sample_time = self.sample_offset + self.event_iter_next_sample_time as SampleTime;
+        let next_sample_time = self.event_iter_item_start_time(&Fraction::ZERO);
         if next_sample_time >= sample_time {
             // next event is not yet due
             return None;
         }
         // fetch new event iter items, if neccessary
         if self.event_iter_items.is_empty() {
+            // apply the actual parameter values of all transforms
+            self.transforms.apply_parameter_values(&self.parameters);
+            // reseed random number generators when the transforms' seed morph enters a new
+            // period. periods are counted in played steps, so they follow resolution changes.
+            if let Some(seed) = self.transforms.seed_for_step(self.event_iter_step_count) {
+                self.gate.set_seed(derived_seed(seed, 0));
+                self.event_iter.set_seed(derived_seed(seed, 1));
+            }
             // generate a pulse from the pattern and pass the pulse to the gate
             let (new_pulse_item, emit_event) = {
                 if let Some(pulse) = self.pattern.run() {
@@ -269,9 +551,28 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> RhythmIter
                 }
             };
             self.event_iter_pulse_item = new_pulse_item;
+            // apply resolution changes with new pulses only, so running pulses keep their length
+            if let Some(resolution_parameter) = &self.resolution_parameter {
+                if let Some(step_scale) = self
+                    .parameters
+                    .value(RESOLUTION_PARAMETER)
+                    .map(|resolution| resolution / resolution_parameter.default_value())
+                    .filter(|scale| *scale > 0.0 && scale.is_finite())
+                {
+                    self.event_iter_step_scale = step_scale;
+                }
+            }
             // generate new events from the gated pulse
             let slice = self.event_iter.run(new_pulse_item, emit_event);
-            if let Some(slice) = slice {
+            // apply built-in processing and transforms to new events once, as not yet due items
+            // get pushed back
+            let pulse = EventTransformPulse {
+                pulse: new_pulse_item,
+                position: (self.event_iter_next_sample_time
+                    - self.offset.to_samples(&self.time_base))
+                    / self.step.to_samples(&self.time_base),
+            };
+            if let Some(slice) = self.transforms.process(&pulse, slice) {
                 self.event_iter_items = VecDeque::from(slice);
             } else {
                 self.event_iter_items.clear();
@@ -291,10 +592,10 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> RhythmIter
             // return event as sample timed rhythm iter item
             let time = self.event_iter_item_start_time(&event_item.start);
             let event = Some(event_item.event);
-            let duration = self.event_iter_item_duration(&event_item.length);
+            let duration = self.event_iter_item_duration(&event_item.start, &event_item.length);
             // advance to the next pulse in the next iteration when all events got consumed
             if self.event_iter_items.is_empty() {
-                self.event_iter_next_sample_time += self.current_steps_sample_duration();
+                self.advance_event_iter_pulse();
             }
             // return event as rhythm iter item
             Some(RhythmIterItem {
@@ -306,9 +607,9 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> RhythmIter
             // and return a timed None event
             let time = self.event_iter_item_start_time(&Fraction::ZERO);
             let event = None;
-            let duration = self.event_iter_item_duration(&Fraction::ONE);
+            let duration = self.event_iter_item_duration(&Fraction::ZERO, &Fraction::ONE);
             // advance to the next pulse in the next iteration
-            self.event_iter_next_sample_time += self.current_steps_sample_duration();
+            self.advance_event_iter_pulse();
             // return event as rhythm iter item
             Some(RhythmIterItem {
                 time,
@@ -359,8 +660,103 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> Rhythm
         self.event_iter.set_external_context(data);
     }
 
-    fn duplicate(&self) -> Rc<RefCell<dyn Rhythm>> {
-        Rc::new(RefCell::new(self.clone()))
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        self.pattern.set_shared_values(values);
+        self.gate.set_shared_values(values);
+        self.event_iter.set_shared_values(values);
+    }
+
+    fn set_seed(&mut self, seed: [u8; 32]) {
+        self.gate.set_seed(derived_seed(seed, 0));
+        self.event_iter.set_seed(derived_seed(seed, 1));
+        self.transforms.set_seed(derived_seed(seed, 5));
+    }
+
+    fn set_reversed(&mut self, reversed: bool) {
+        self.pattern.set_reversed(reversed);
+        self.event_iter.set_reversed(reversed);
+    }
+
+    fn transform_event(&mut self, event: &mut Event) {
+        // apply the same processing as for emitted events, except pulse related ones
+        self.transforms.apply_parameter_values(&self.parameters);
+        self.transforms.apply(event);
+        if let Some(instrument) = self.instrument {
+            if let Event::NoteEvents(note_events) = event {
+                for note_event in note_events.iter_mut().flatten() {
+                    note_event.instrument = note_event.instrument.or(Some(instrument));
+                }
+            }
+        }
+    }
+
+    fn parameters(&self) -> Vec<RhythmParameter> {
+        self.parameters.parameters()
+    }
+
+    fn take_parameter_changes(&mut self) -> Vec<(String, f64)> {
+        self.parameters.take_changes()
+    }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        let mut warnings = self.pattern.take_warnings();
+        warnings.append(&mut self.gate.take_warnings());
+        warnings.append(&mut self.event_iter.take_warnings());
+        warnings
+    }
+
+    fn take_script_errors(&mut self) -> Vec<String> {
+        let mut errors = self.pattern.take_script_errors();
+        errors.append(&mut self.gate.take_script_errors());
+        errors.append(&mut self.event_iter.take_script_errors());
+        errors
+    }
+
+    fn set_parameter_value(&mut self, id: &str, value: f64) -> Result<f64, String> {
+        self.parameters.apply_value(id, value)
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        // pattern, gate and event iter run in the same Lua engine
+        let mut usage = self
+            .pattern
+            .memory_usage()
+            .merge_shared(self.gate.memory_usage())
+            .merge_shared(self.event_iter.memory_usage());
+        usage.events += self
+            .event_iter_items
+            .iter()
+            .map(|item| event_memory_usage(&item.event))
+            .sum::<usize>();
+        usage.events += self.transforms.memory_usage();
+        usage
+    }
+
+    fn trim_memory(&mut self) {
+        self.pattern.trim_memory();
+        self.gate.trim_memory();
+        self.event_iter.trim_memory();
+        self.transforms.trim_memory();
+        self.event_iter_items.shrink_to_fit();
+    }
+
+    fn duplicate(&self) -> Result<Rc<RefCell<dyn Rhythm>>, String> {
+        let mut duplicate = Self {
+            pattern: self.pattern.try_duplicate()?,
+            gate: self.gate.try_duplicate()?,
+            event_iter: self.event_iter.try_duplicate()?,
+            ..self.clone()
+        };
+        // don't share parameter values with the duplicate
+        duplicate.parameters = self.parameters.duplicate();
+        duplicate
+            .pattern
+            .set_parameter_values(&duplicate.parameters);
+        duplicate.gate.set_parameter_values(&duplicate.parameters);
+        duplicate
+            .event_iter
+            .set_parameter_values(&duplicate.parameters);
+        Ok(Rc::new(RefCell::new(duplicate)))
     }
 
     fn reset(&mut self) {
@@ -369,11 +765,15 @@ impl<Step: GenericRhythmTimeStep, Offset: GenericRhythmTimeStep> Rhythm
         // reset pattern and gate
         self.pattern.reset();
         self.gate.reset();
+        // reset built-in processing and transforms
+        self.transforms.reset();
         // reset iterator state
         self.event_iter.reset();
         self.event_iter_sample_time = 0;
         self.event_iter_next_sample_time = self.offset.to_samples(&self.time_base);
         self.event_iter_pulse_item = PulseIterItem::default();
+        self.event_iter_step_scale = 1.0;
+        self.event_iter_step_count = 0.0;
         self.event_iter_items.clear();
     }
 }
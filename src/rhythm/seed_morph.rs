@@ -0,0 +1,169 @@
+//! Beat synced random seed changes of rhythms.
+
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+use crate::{
+    parameter::{RhythmParameter, RhythmParameterValues},
+    rhythm::{derived_seed, new_random_seed},
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Parameter id of the seed morph's seed change probability.
+pub const SEED_MORPH_AMOUNT_PARAMETER: &str = "seed_morph.amount";
+
+// -------------------------------------------------------------------------------------------------
+
+/// Periodically reseeds the random number generators of a rhythm, e.g. every 16 bars, so
+/// generative rhythms evolve over time, but stay coherent within each period.
+///
+/// At the start of each period, a new seed gets picked with the morph's `amount` probability.
+/// Else the previous period's seed is used again, which replays the previous period's random
+/// values. An amount of 0 thus locks the rhythm into a loop, an amount of 1 creates a new
+/// variation in every period, and values in between slowly morph the rhythm's output.
+///
+/// The period is specified in steps of the rhythm. The amount is exposed as
+/// [`RhythmParameter`], so it can be automated: see [`parameters`](Self::parameters).
+/// When seeded, the morph picks the same seeds after each reset.
+#[derive(Debug, Clone)]
+pub struct SeedMorph {
+    period: f64,
+    amount: f64,
+    seed: [u8; 32],
+    current_seed: [u8; 32],
+    seed_count: u64,
+    period_index: Option<u64>,
+    rand_gen: Xoshiro256PlusPlus,
+}
+
+impl SeedMorph {
+    /// Create a new seed morph which picks a new seed every `period` rhythm steps, using the
+    /// given optional seed to generate seeds.
+    pub fn new(period: f64, seed: Option<[u8; 32]>) -> Self {
+        let period = if period.is_finite() {
+            period.max(1.0)
+        } else {
+            1.0
+        };
+        let seed = seed.unwrap_or_else(new_random_seed);
+        let current_seed = derived_seed(seed, 0);
+        let rand_gen = Xoshiro256PlusPlus::from_seed(seed);
+        Self {
+            period,
+            amount: 1.0,
+            seed,
+            current_seed,
+            seed_count: 0,
+            period_index: None,
+            rand_gen,
+        }
+    }
+
+    /// Return a new seed morph which picks new seeds with the given probability in range
+    /// \[0 - 1\].
+    #[must_use]
+    pub fn with_amount(self, amount: f64) -> Self {
+        let amount = if amount.is_finite() {
+            amount.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        Self { amount, ..self }
+    }
+
+    /// Period length in rhythm steps.
+    pub fn period(&self) -> f64 {
+        self.period
+    }
+
+    /// Probability of picking a new seed in each period.
+    pub fn amount(&self) -> f64 {
+        self.amount
+    }
+
+    /// Describe the morph's controls as parameters, using the morph's current settings as
+    /// default values.
+    pub fn parameters(&self) -> Vec<RhythmParameter> {
+        vec![
+            RhythmParameter::new(SEED_MORPH_AMOUNT_PARAMETER, 0.0..=1.0, self.amount)
+                .with_name("Seed Morph"),
+        ]
+    }
+
+    /// Apply the actual values of the morph's parameters from the given store, if present.
+    pub fn apply_parameter_values(&mut self, values: &RhythmParameterValues) {
+        if let Some(amount) = values.value(SEED_MORPH_AMOUNT_PARAMETER) {
+            self.amount = amount.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Set a new seed, which also gets used in following resets.
+    pub fn set_seed(&mut self, seed: [u8; 32]) {
+        self.seed = seed;
+        self.reset();
+    }
+
+    /// Restart from the first period with the initial seed.
+    pub fn reset(&mut self) {
+        self.current_seed = derived_seed(self.seed, 0);
+        self.seed_count = 0;
+        self.period_index = None;
+        self.rand_gen = Xoshiro256PlusPlus::from_seed(self.seed);
+    }
+
+    /// Returns the seed which should be applied to the rhythm's random number generators,
+    /// when the given rhythm step position enters a new period. Else returns None.
+    pub fn seed_for_step(&mut self, step: f64) -> Option<[u8; 32]> {
+        // avoid float rounding issues at period boundaries
+        let period_index = ((step + 1e-9) / self.period).floor().max(0.0) as u64;
+        if self
+            .period_index
+            .is_some_and(|current| current >= period_index)
+        {
+            return None;
+        }
+        for _ in self.period_index.unwrap_or(0)..period_index {
+            if self.rand_gen.gen_bool(self.amount) {
+                self.seed_count += 1;
+                self.current_seed = derived_seed(self.seed, self.seed_count);
+            }
+        }
+        self.period_index = Some(period_index);
+        Some(self.current_seed)
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seeds() {
+        // periods
+        let mut morph = SeedMorph::new(4.0, Some([1; 32]));
+        let first = morph.seed_for_step(0.0).unwrap();
+        assert!(morph.seed_for_step(3.0).is_none());
+        let second = morph.seed_for_step(4.0).unwrap();
+        assert_ne!(first, second);
+        assert!(morph.seed_for_step(7.0).is_none());
+        morph.reset();
+        assert_eq!(morph.seed_for_step(0.0), Some(first));
+        assert_eq!(morph.seed_for_step(4.0), Some(second));
+
+        // locked seeds replay the first period
+        let mut morph = SeedMorph::new(4.0, Some([1; 32])).with_amount(0.0);
+        let first = morph.seed_for_step(0.0).unwrap();
+        assert_eq!(morph.seed_for_step(4.0), Some(first));
+        assert_eq!(morph.seed_for_step(16.0), Some(first));
+
+        // parameters
+        let values = RhythmParameterValues::new(morph.parameters());
+        values.set_value(SEED_MORPH_AMOUNT_PARAMETER, 1.0).unwrap();
+        morph.apply_parameter_values(&values);
+        assert_eq!(morph.amount(), 1.0);
+        assert_ne!(morph.seed_for_step(20.0), Some(first));
+    }
+}
@@ -1,6 +1,8 @@
 //! Musical scales based on `Note` and custom intervals or common scale names.
 
-use crate::Note;
+use std::fmt::Display;
+
+use crate::{Note, NoteSpelling};
 
 // -------------------------------------------------------------------------------------------------
 
@@ -386,6 +388,27 @@ impl Scale {
         self.key
     }
 
+    /// Accidental spelling of the scale's key signature: uses the spelling which names all
+    /// notes in the scale with as many distinct letters as possible, e.g. flats for D minor
+    /// and sharps for E major.
+    pub fn spelling(&self) -> NoteSpelling {
+        let letter_collisions = |spelling: NoteSpelling| {
+            let mut letters = [0; 7];
+            for step in self.steps() {
+                let key = (self.key as usize + step) as u8 % 12;
+                letters[spelling.letter_index(key) as usize] += 1;
+            }
+            letters.iter().filter(|count| **count > 1).count()
+        };
+        let sharps = letter_collisions(NoteSpelling::Sharps);
+        let flats = letter_collisions(NoteSpelling::Flats);
+        match sharps.cmp(&flats) {
+            std::cmp::Ordering::Less => NoteSpelling::Sharps,
+            std::cmp::Ordering::Greater => NoteSpelling::Flats,
+            std::cmp::Ordering::Equal => NoteSpelling::from_key(self.key),
+        }
+    }
+
     /// List of raw degrees where 0 indicates no step.
     pub fn degrees(&self) -> Vec<usize> {
         self.mode.degrees.to_vec()
@@ -440,6 +463,15 @@ impl Scale {
             .collect()
     }
 
+    /// Names of all notes in the scale, spelled as in the scale's key signature.
+    pub fn note_names(&self) -> Vec<String> {
+        let spelling = self.spelling();
+        self.notes()
+            .into_iter()
+            .map(|note| note.name(spelling))
+            .collect()
+    }
+
     /// Transpose the given note into this scale, using the most strict strictness level.
     pub fn transpose(&self, note: Note, offset: i32) -> Note {
         self.transpose_with_strictness(note, offset, TransposeStrictness::ForceAllNotes)
@@ -576,6 +608,13 @@ impl Scale {
     }
 }
 
+impl Display for Scale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let root_note = Note::from((self.key + 12 * self.octave).min(0x7f));
+        write!(f, "{} {}", root_note.name(self.spelling()), self.mode.name)
+    }
+}
+
 // --------------------------------------------------------------------------------------------------
 
 #[cfg(test)]
@@ -649,6 +688,23 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn spelling() -> Result<(), String> {
+        let scale = Scale::new(Note::F4, Mode::try_from("major")?);
+        assert_eq!(scale.spelling(), NoteSpelling::Flats);
+        assert_eq!(
+            scale.note_names(),
+            vec!["F4", "G4", "A4", "Bb4", "C5", "D5", "E5"]
+        );
+        assert_eq!(scale.to_string(), "F4 natural major");
+        let scale = Scale::new(Note::Fs4, Mode::try_from("minor")?);
+        assert_eq!(scale.spelling(), NoteSpelling::Sharps);
+        assert_eq!(scale.to_string(), "F#4 natural minor");
+        let scale = Scale::new(Note::Ds4, Mode::try_from("minor")?);
+        assert_eq!(scale.to_string(), "Eb4 natural minor");
+        Ok(())
+    }
+
     #[test]
     fn transpose() {
         assert_eq!(
@@ -0,0 +1,57 @@
+//! Switch between named sets of [`RhythmSlot`]S and rhythm parameter values at runtime.
+
+#[cfg(doc)]
+use crate::{phrase::RhythmSlot, Phrase, Sequence};
+
+// -------------------------------------------------------------------------------------------------
+
+/// A named scene in a [`Sequence`]: a [`Phrase`] of the sequence, which assigns rhythms to the
+/// sequence's slots, and a set of rhythm parameter values, which get applied when the scene
+/// gets selected, see [`Sequence::select_scene`].
+///
+/// Parameter value ids are the ids of [`Sequence::parameters`].
+#[derive(Clone, Debug)]
+pub struct Scene {
+    name: String,
+    phrase_index: usize,
+    parameter_values: Vec<(String, f64)>,
+}
+
+impl Scene {
+    /// Create a new scene with the given name, which plays the sequence's phrase with the
+    /// given index.
+    pub fn new<S: Into<String>>(name: S, phrase_index: usize) -> Self {
+        let name = name.into();
+        let parameter_values = Vec::new();
+        Self {
+            name,
+            phrase_index,
+            parameter_values,
+        }
+    }
+
+    /// Return a new scene which applies the given (id, value) parameter values when it gets
+    /// selected.
+    #[must_use]
+    pub fn with_parameter_values(self, parameter_values: Vec<(String, f64)>) -> Self {
+        Self {
+            parameter_values,
+            ..self
+        }
+    }
+
+    /// The scene's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Index of the sequence's phrase which the scene plays.
+    pub fn phrase_index(&self) -> usize {
+        self.phrase_index
+    }
+
+    /// Read-only access to the scene's (id, value) parameter values.
+    pub fn parameter_values(&self) -> &[(String, f64)] {
+        &self.parameter_values
+    }
+}
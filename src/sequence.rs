@@ -1,16 +1,833 @@
 //! Arrange multiple `Phrase`S into a single `Rhythm`.
 
-use crate::{event::Event, phrase::RhythmIndex, BeatTimeBase, Phrase, Rhythm, SampleTime};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fmt::{Debug, Display},
+    mem::size_of,
+    rc::Rc,
+};
+
+use crate::{
+    event::{
+        layering::{InstrumentLayers, LayeredVoices},
+        Event, NoteEvent,
+    },
+    history::EventHistory,
+    memory::{event_memory_usage, MemoryUsage},
+    notification::{Notification, NotificationBus, NotificationKind},
+    performance::{muted_event, MacroTarget, PerformanceMacro},
+    phrase::{RhythmIndex, RhythmSlot, SlotResumeMode},
+    rhythm::derived_seed,
+    scene::Scene,
+    shared::SharedValues,
+    time::{BeatTimeStep, TimingQuantize},
+    warning::{WarningCollector, WarningKind},
+    BeatTimeBase, Phrase, Rhythm, RhythmParameter, SampleTime, Warning,
+};
+
+#[cfg(feature = "profiling")]
+use crate::profiling::RhythmProfile;
 
 #[cfg(doc)]
 use crate::EventIter;
 
 // -------------------------------------------------------------------------------------------------
 
+/// Snapshot of all external context values which got passed to a [`Sequence`].
+///
+/// Hosts can save these values, e.g. along with a session, and restore them after reloading
+/// scripts, so user controlled settings survive script edits. Values can be serialized into a
+/// compact `name=value;name=value` string via `to_string` and parsed again via `try_from`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExternalContextValues {
+    values: Vec<(String, f64)>,
+}
+
+impl ExternalContextValues {
+    /// Create a new, empty set of values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read-only access to all values, in the order they got first set.
+    pub fn values(&self) -> &[(String, f64)] {
+        &self.values
+    }
+
+    /// Get a single value by name.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Set or update a single value.
+    pub fn set(&mut self, name: &str, value: f64) {
+        if let Some((_, existing)) = self.values.iter_mut().find(|(key, _)| key == name) {
+            *existing = value;
+        } else {
+            self.values.push((name.to_string(), value));
+        }
+    }
+
+    /// Set or update all values from the given external context data.
+    pub fn merge(&mut self, data: &[(Cow<str>, f64)]) {
+        for (name, value) in data {
+            self.set(name, *value);
+        }
+    }
+
+    /// Convert values into the external context data format.
+    pub fn to_context_data(&self) -> Vec<(Cow<str>, f64)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (Cow::Borrowed(name.as_str()), *value))
+            .collect()
+    }
+}
+
+impl Display for ExternalContextValues {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let values = self
+            .values
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(";");
+        f.write_str(&values)
+    }
+}
+
+impl TryFrom<&str> for ExternalContextValues {
+    type Error = String;
+
+    /// Try parsing values from a `name=value;name=value` string.
+    fn try_from(s: &str) -> Result<Self, String> {
+        let mut values = Self::new();
+        for entry in s.split(';').filter(|entry| !entry.is_empty()) {
+            let (name, value) = entry
+                .split_once('=')
+                .ok_or(format!("invalid context value entry '{}'", entry))?;
+            if name.is_empty() {
+                return Err(format!("missing context value name in entry '{}'", entry));
+            }
+            let value = value
+                .parse::<f64>()
+                .map_err(|err| format!("invalid context value '{}': {}", value, err))?;
+            values.set(name, value);
+        }
+        Ok(values)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A [`RhythmParameter`] of a rhythm in a [`Sequence`], as listed by [`Sequence::parameters`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceParameter {
+    /// Unique id of the parameter in the sequence: `phrase_index.rhythm_index.parameter_id`.
+    pub id: String,
+    /// Index of the phrase in which the parameter's rhythm first plays.
+    pub phrase_index: usize,
+    /// Index of the rhythm slot in the phrase.
+    pub rhythm_index: RhythmIndex,
+    pub parameter: RhythmParameter,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A named position on a [`Sequence`]'s timeline, e.g. to mark song parts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CuePoint {
+    name: String,
+    bar: f64,
+}
+
+impl CuePoint {
+    /// Create a new cue point with the given name at the given bar position, starting from
+    /// 0 at the sequence's start. Negative bar positions are clamped.
+    pub fn new<S: Into<String>>(name: S, bar: f64) -> Self {
+        let name = name.into();
+        let bar = if bar.is_finite() { bar.max(0.0) } else { 0.0 };
+        Self { name, bar }
+    }
+
+    /// The cue point's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The cue point's position in bars.
+    pub fn bar(&self) -> f64 {
+        self.bar
+    }
+
+    /// The cue point's position in samples for the given time base.
+    pub fn to_samples(&self, time_base: &BeatTimeBase) -> SampleTime {
+        (self.bar * time_base.samples_per_bar()) as SampleTime
+    }
+}
+
+/// Callback which gets called when playback passes a [`CuePoint`], with the sample time at
+/// which the cue point is passed.
+#[derive(Clone)]
+struct CuePointCallback(Rc<dyn Fn(&CuePoint, SampleTime)>);
+
+impl Debug for CuePointCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CuePointCallback").finish()
+    }
+}
+
+/// A scheduled jump to a cue point.
+#[derive(Clone, Debug)]
+struct CuePointJump {
+    name: String,
+    sample_time: SampleTime,
+}
+
+/// A scheduled switch to a [`Scene`].
+#[derive(Clone, Debug)]
+struct SceneSwitch {
+    scene_index: usize,
+    sample_time: SampleTime,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Time at which a batch of parameter changes gets applied, see
+/// [`Sequence::schedule_parameter_changes`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParameterChangeTime {
+    /// Apply changes right away, before the next events get emitted.
+    Now,
+    /// Apply changes at the given sample time. Times in the past apply right away.
+    At(SampleTime),
+    /// Apply changes at the next `quantum`, e.g. at the next bar.
+    Next(BeatTimeStep),
+}
+
+/// A scheduled, not yet applied parameter value change.
+#[derive(Clone, Debug)]
+struct ScheduledParameterChange {
+    sample_time: SampleTime,
+    id: String,
+    value: f64,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Interpolation curve of a [`ParameterRamp`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParameterRampCurve {
+    /// Change values linearly.
+    #[default]
+    Linear,
+    /// Change values exponentially, e.g. for frequencies or gains. Falls back to linear
+    /// interpolation when the start and end value don't have the same sign or one is 0.
+    Exponential,
+}
+
+/// Maximum number of parameter changes a single [`ParameterRamp`] schedules.
+const MAX_PARAMETER_RAMP_STEPS: usize = 1000;
+
+/// Smoothly moves a parameter from its actual to a new value over a given time, see
+/// [`Sequence::ramp_parameter_value`].
+///
+/// Ramps get applied as a series of scheduled parameter changes, one every `resolution`
+/// seconds, so scripts which read parameter values per pulse see smooth motion instead of a
+/// single step. Long ramps change values less often, so a single ramp schedules at most 1000
+/// changes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParameterRamp {
+    duration: f64,
+    curve: ParameterRampCurve,
+    resolution: f64,
+}
+
+impl ParameterRamp {
+    /// Create a new linear ramp with the given duration in seconds, which changes values
+    /// every 10 ms.
+    pub fn new(duration: f64) -> Self {
+        let duration = if duration.is_finite() {
+            duration.max(0.0)
+        } else {
+            0.0
+        };
+        Self {
+            duration,
+            curve: ParameterRampCurve::Linear,
+            resolution: 0.01,
+        }
+    }
+
+    /// Return a new ramp with the given interpolation curve.
+    #[must_use]
+    pub fn with_curve(self, curve: ParameterRampCurve) -> Self {
+        Self { curve, ..self }
+    }
+
+    /// Return a new ramp which changes values every `resolution` seconds. Resolutions are
+    /// limited to 1 ms.
+    #[must_use]
+    pub fn with_resolution(self, resolution: f64) -> Self {
+        let resolution = if resolution.is_finite() {
+            resolution.max(0.001)
+        } else {
+            self.resolution
+        };
+        Self { resolution, ..self }
+    }
+
+    /// Duration of the ramp in seconds.
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    /// Interpolation curve of the ramp.
+    pub fn curve(&self) -> ParameterRampCurve {
+        self.curve
+    }
+
+    /// Time between two value changes in seconds.
+    pub fn resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    /// Interpolated value at the given ramp position in range \[0 - 1\].
+    pub fn value_at(&self, start_value: f64, end_value: f64, position: f64) -> f64 {
+        let position = position.clamp(0.0, 1.0);
+        match self.curve {
+            ParameterRampCurve::Exponential
+                if start_value != 0.0 && (end_value / start_value) > 0.0 =>
+            {
+                start_value * (end_value / start_value).powf(position)
+            }
+            _ => start_value + (end_value - start_value) * position,
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// An externally generated, not yet emitted event, see [`Sequence::inject_event`].
+#[derive(Clone, Debug)]
+struct InjectedEvent {
+    rhythm_index: RhythmIndex,
+    sample_time: SampleTime,
+    event: Event,
+    duration: SampleTime,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Master volume curve of a [`Sequence`], which shapes the volumes of all emitted note-ons,
+/// e.g. to shape the overall dynamics of a set without touching individual rhythms.
+///
+/// Volumes in range \[0 - 1\] first get compressed or expanded, then all volumes get clamped
+/// to the curve's volume range. The default curve leaves all volumes as they are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VolumeCurve {
+    compression: f32,
+    min: f32,
+    max: f32,
+}
+
+impl Default for VolumeCurve {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VolumeCurve {
+    /// Create a new curve, which leaves all volumes as they are.
+    pub fn new() -> Self {
+        let compression = 0.0;
+        let min = 0.0;
+        let max = f32::MAX;
+        Self {
+            compression,
+            min,
+            max,
+        }
+    }
+
+    /// Return a new curve with the given compression amount in range \[-1 - 1\]: positive
+    /// values lift quiet notes (soft curve), negative values lower them (hard curve).
+    #[must_use]
+    pub fn with_compression(self, compression: f32) -> Self {
+        let compression = if compression.is_finite() {
+            compression.clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    /// Return a new curve which clamps volumes to the given range.
+    #[must_use]
+    pub fn with_range(self, min: f32, max: f32) -> Self {
+        let min = if min.is_finite() { min.max(0.0) } else { 0.0 };
+        let max = if max.is_nan() { f32::MAX } else { max.max(min) };
+        Self { min, max, ..self }
+    }
+
+    /// The curve's compression amount.
+    pub fn compression(&self) -> f32 {
+        self.compression
+    }
+
+    /// The curve's volume range.
+    pub fn range(&self) -> (f32, f32) {
+        (self.min, self.max)
+    }
+
+    /// Returns true when the curve leaves all volumes as they are.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::new()
+    }
+
+    /// Apply the curve to the given volume.
+    pub fn apply(&self, volume: f32) -> f32 {
+        let volume = if (0.0..=1.0).contains(&volume) && self.compression != 0.0 {
+            volume.powf(4.0_f32.powf(-self.compression))
+        } else {
+            volume
+        };
+        volume.clamp(self.min, self.max)
+    }
+
+    /// Apply the curve to all note-ons in the given event.
+    pub fn apply_to_event(&self, event: &mut Event) {
+        if let Event::NoteEvents(note_events) = event {
+            for note_event in note_events.iter_mut().flatten() {
+                if note_event.note.is_note_on() {
+                    note_event.volume = self.apply(note_event.volume);
+                }
+            }
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Time window in which an [`EventLimit`] counts events.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EventLimitWindow {
+    /// Count events per second.
+    #[default]
+    Second,
+    /// Count events per beat of the sequence's time base.
+    Beat,
+}
+
+impl Display for EventLimitWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Second => "second",
+            Self::Beat => "beat",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Safety limits for the number of events a [`Sequence`] emits per second or beat, so runaway
+/// scripts or dense cycles can't freeze players or flood downstream MIDI gear.
+///
+/// Limits apply to each rhythm slot and to all slots together. Note-ons and parameter changes
+/// which exceed a limit get dropped and reported as [`Warning`]S, see
+/// [`Sequence::take_warnings`]. Note-offs are never dropped, also not in events which mix them
+/// with note-ons, so limits never leave notes hanging. The default limit does not limit
+/// anything.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventLimit {
+    window: EventLimitWindow,
+    max_slot_events: Option<usize>,
+    max_events: Option<usize>,
+}
+
+impl EventLimit {
+    /// Create a new limit, which does not limit anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new limit which counts events in the given time window.
+    #[must_use]
+    pub fn with_window(self, window: EventLimitWindow) -> Self {
+        Self { window, ..self }
+    }
+
+    /// Return a new limit with the given max number of events per window for each rhythm slot.
+    #[must_use]
+    pub fn with_max_slot_events<N: Into<Option<usize>>>(self, max_slot_events: N) -> Self {
+        let max_slot_events = max_slot_events.into();
+        Self {
+            max_slot_events,
+            ..self
+        }
+    }
+
+    /// Return a new limit with the given max number of events per window for all rhythm slots.
+    #[must_use]
+    pub fn with_max_events<N: Into<Option<usize>>>(self, max_events: N) -> Self {
+        let max_events = max_events.into();
+        Self { max_events, ..self }
+    }
+
+    /// The limit's time window.
+    pub fn window(&self) -> EventLimitWindow {
+        self.window
+    }
+
+    /// Max number of events per window and rhythm slot, if limited.
+    pub fn max_slot_events(&self) -> Option<usize> {
+        self.max_slot_events
+    }
+
+    /// Max number of events per window in all rhythm slots, if limited.
+    pub fn max_events(&self) -> Option<usize> {
+        self.max_events
+    }
+
+    /// Returns true when the limit does not limit anything.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_slot_events.is_none() && self.max_events.is_none()
+    }
+}
+
+/// Applies an [`EventLimit`] to emitted events.
+#[derive(Clone, Debug, Default)]
+struct EventLimiter {
+    limit: EventLimit,
+    window_index: u64,
+    slot_counts: Vec<usize>,
+    count: usize,
+    warnings: WarningCollector,
+}
+
+impl EventLimiter {
+    /// Returns the given event when it may be emitted. Else strips its note-ons, so playing
+    /// notes still get stopped, or drops it when nothing remains.
+    fn apply(
+        &mut self,
+        time_base: &BeatTimeBase,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+        event: Event,
+    ) -> Option<Event> {
+        if self.accept(time_base, rhythm_index, sample_time, &event) {
+            Some(event)
+        } else {
+            muted_event(Some(event))
+        }
+    }
+
+    /// Returns true when the given event may be emitted, else false when it must be limited.
+    fn accept(
+        &mut self,
+        time_base: &BeatTimeBase,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+        event: &Event,
+    ) -> bool {
+        if self.limit.is_unlimited() {
+            return true;
+        }
+        let limited = match event {
+            Event::NoteEvents(note_events) => note_events
+                .iter()
+                .flatten()
+                .any(|note_event| note_event.note.is_note_on()),
+            Event::ParameterChangeEvent(_) => true,
+        };
+        if !limited {
+            return true;
+        }
+        // restart counting in new windows
+        let window_samples = match self.limit.window {
+            EventLimitWindow::Second => time_base.samples_per_sec as f64,
+            EventLimitWindow::Beat => time_base.samples_per_beat(),
+        };
+        let window_index = (sample_time as f64 / window_samples.max(1.0)) as u64;
+        if window_index != self.window_index {
+            self.window_index = window_index;
+            self.slot_counts.fill(0);
+            self.count = 0;
+        }
+        if self.slot_counts.len() <= rhythm_index {
+            self.slot_counts.resize(rhythm_index + 1, 0);
+        }
+        let window = self.limit.window;
+        if let Some(max) = self.limit.max_slot_events {
+            if self.slot_counts[rhythm_index] >= max {
+                self.warnings.add(
+                    WarningKind::Dropped,
+                    format!(
+                        "rhythm slot {} exceeded the limit of {} events per {}: events got dropped",
+                        rhythm_index, max, window
+                    ),
+                );
+                return false;
+            }
+        }
+        if let Some(max) = self.limit.max_events {
+            if self.count >= max {
+                self.warnings.add(
+                    WarningKind::Dropped,
+                    format!(
+                        "sequence exceeded the limit of {} events per {}: events got dropped",
+                        max, window
+                    ),
+                );
+                return false;
+            }
+        }
+        self.slot_counts[rhythm_index] += 1;
+        self.count += 1;
+        true
+    }
+
+    fn reset(&mut self) {
+        self.window_index = 0;
+        self.slot_counts.clear();
+        self.count = 0;
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// How a [`Sequence`] merges identical note-ons, which get emitted at the same sample time for
+/// the same instrument, e.g. when stacking cycles or layers which play the same notes.
+///
+/// Merged note-ons get removed from their events before they reach the consumer, so they don't
+/// trigger doubled samples or overlapping MIDI notes downstream. The first emitted note-on wins.
+/// Events, which got emptied by merging, are emitted as `None` events.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EventMerge {
+    /// Emit all events as they are.
+    #[default]
+    Off,
+    /// Drop duplicated note-ons.
+    DropDuplicates,
+    /// Drop duplicated note-ons and add their volumes to the first note-on's volume, limited
+    /// to the given max volume.
+    SumVolumes { max_volume: f32 },
+}
+
+/// Applies an [`EventMerge`] to emitted events.
+#[derive(Clone, Debug, Default)]
+struct EventMerger {
+    merge: EventMerge,
+    pending: Vec<(RhythmIndex, SampleTime, Option<Event>, SampleTime)>,
+}
+
+impl EventMerger {
+    /// Merge the given event with pending events at the same time. Pending events get passed
+    /// to the consumer as soon as an event at a different time arrives.
+    fn push<F>(
+        &mut self,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+        event: Option<Event>,
+        duration: SampleTime,
+        consumer: &mut F,
+    ) where
+        F: FnMut(RhythmIndex, SampleTime, Option<Event>, SampleTime),
+    {
+        if self.merge == EventMerge::Off {
+            consumer(rhythm_index, sample_time, event, duration);
+            return;
+        }
+        if self
+            .pending
+            .first()
+            .is_some_and(|(_, pending_time, _, _)| *pending_time != sample_time)
+        {
+            self.flush(consumer);
+        }
+        let event = match event {
+            Some(Event::NoteEvents(mut note_events)) => {
+                let mut merged = false;
+                for note_event in &mut note_events {
+                    if note_event
+                        .as_ref()
+                        .is_some_and(|note_event| self.merge_note_event(note_event))
+                    {
+                        *note_event = None;
+                        merged = true;
+                    }
+                }
+                if merged && note_events.iter().all(Option::is_none) {
+                    None
+                } else {
+                    Some(Event::NoteEvents(note_events))
+                }
+            }
+            event => event,
+        };
+        self.pending
+            .push((rhythm_index, sample_time, event, duration));
+    }
+
+    /// Pass all pending events to the consumer.
+    fn flush<F>(&mut self, consumer: &mut F)
+    where
+        F: FnMut(RhythmIndex, SampleTime, Option<Event>, SampleTime),
+    {
+        for (rhythm_index, sample_time, event, duration) in self.pending.drain(..) {
+            consumer(rhythm_index, sample_time, event, duration);
+        }
+    }
+
+    /// Returns true when the given note event duplicates a pending note-on and got merged.
+    fn merge_note_event(&mut self, note_event: &NoteEvent) -> bool {
+        if !note_event.note.is_note_on() {
+            return false;
+        }
+        let merge = self.merge;
+        for (_, _, event, _) in &mut self.pending {
+            if let Some(Event::NoteEvents(pending_note_events)) = event {
+                if let Some(pending) = pending_note_events.iter_mut().flatten().find(|pending| {
+                    pending.note == note_event.note && pending.instrument == note_event.instrument
+                }) {
+                    if let EventMerge::SumVolumes { max_volume } = merge {
+                        pending.volume = (pending.volume + note_event.volume)
+                            .min(max_volume.max(pending.volume));
+                    }
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// How a [`Sequence`] orders events, which fall on the same sample time, before passing them to
+/// the consumer, e.g. when multiple phrases, layers and injected events emit at the same time.
+///
+/// All orderings are deterministic: the same sequence emits events in the same order in every run,
+/// regardless of how hosts split up calls to `consume_events_until_time`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EventOrdering {
+    /// Emit events at the same time in the order the phrases and layers emitted them.
+    Emitted,
+    /// Emit parameter changes before note events at the same time, so notes get played with
+    /// the changed parameters. Events of the same kind keep their emitted order.
+    #[default]
+    ParameterChangesFirst,
+    /// Emit parameter changes before note events at the same time, and order events of the same
+    /// kind by their rhythm index.
+    ByRhythmIndex,
+}
+
+/// Applies an [`EventOrdering`] to emitted events.
+#[derive(Clone, Debug, Default)]
+struct EventOrderer {
+    ordering: EventOrdering,
+    pending: Vec<(RhythmIndex, SampleTime, Option<Event>, SampleTime)>,
+}
+
+impl EventOrderer {
+    /// Memorize the given event along with pending events at the same time. Pending events get
+    /// ordered and passed to the consumer as soon as an event at a different time arrives.
+    fn push<F>(
+        &mut self,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+        event: Option<Event>,
+        duration: SampleTime,
+        consumer: &mut F,
+    ) where
+        F: FnMut(RhythmIndex, SampleTime, Option<Event>, SampleTime),
+    {
+        if self.ordering == EventOrdering::Emitted {
+            consumer(rhythm_index, sample_time, event, duration);
+            return;
+        }
+        if self
+            .pending
+            .first()
+            .is_some_and(|(_, pending_time, _, _)| *pending_time != sample_time)
+        {
+            self.flush(consumer);
+        }
+        self.pending
+            .push((rhythm_index, sample_time, event, duration));
+    }
+
+    /// Order and pass all pending events to the consumer.
+    fn flush<F>(&mut self, consumer: &mut F)
+    where
+        F: FnMut(RhythmIndex, SampleTime, Option<Event>, SampleTime),
+    {
+        // NB: sorting is stable, so events with the same key keep their emitted order
+        let is_note_event =
+            |event: &Option<Event>| !matches!(event, Some(Event::ParameterChangeEvent(_)));
+        match self.ordering {
+            EventOrdering::Emitted => (),
+            EventOrdering::ParameterChangesFirst => self
+                .pending
+                .sort_by_key(|(_, _, event, _)| is_note_event(event)),
+            EventOrdering::ByRhythmIndex => self
+                .pending
+                .sort_by_key(|(rhythm_index, _, event, _)| (is_note_event(event), *rhythm_index)),
+        }
+        for (rhythm_index, sample_time, event, duration) in self.pending.drain(..) {
+            consumer(rhythm_index, sample_time, event, duration);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
 /// Sequentially arrange [`Phrase`] into a new [`EventIter`] to form simple arrangements.
 ///
+/// Additional phrase sequences can be played in parallel as layers via [`Self::with_layer`],
+/// e.g. to combine a one bar drum phrase with a four bar ambient phrase. Each layer cycles
+/// through its own phrases independently, and phrase changes and continue modes only apply
+/// within a layer. Rhythm indices of all layers are merged: the first layer's slots follow the
+/// main phrase slots, the second layer's slots follow the first layer's slots and so on.
+///
+/// Named [`CuePoint`]S mark positions on the sequence's timeline, e.g. song parts. Playback can
+/// jump to cue points live, and hosts can get notified when playback passes a cue point.
+///
+/// Named [`Scene`]S select one of the sequence's phrases along with a set of parameter values.
+/// Selecting a scene switches atomically at the next quantum, e.g. the next bar, and then keeps
+/// looping the scene's phrase, e.g. for live sets.
+///
+/// Hosts can subscribe to typed [`Notification`]S of the sequence, e.g. phrase changes or script
+/// errors, via the sequence's [`NotificationBus`], see [`Self::notifications`].
+///
+/// Hosts can change rhythm parameters immediately or schedule batches of parameter changes, which
+/// get applied exactly at a given sample time or quantized to the beat, e.g. at the next bar.
+///
+/// Hosts can inject externally generated, time-stamped events, which get merged into the
+/// sequence's emitted events, e.g. to fuse the output of another sequencer engine.
+///
+/// A master [`VolumeCurve`] shapes the volumes of all notes in all phrases and layers, and an
+/// optional [`EventLimit`] drops events of runaway rhythms. Identical simultaneous note-ons of
+/// all phrases and layers can optionally be merged via an [`EventMerge`]. Notes of single
+/// instruments can be fanned out to multiple instruments via [`InstrumentLayers`]. Events which
+/// fall on the same sample time get emitted in a deterministic order, see [`EventOrdering`].
+///
 /// The `consume_events_until_time` function can be used to feed the entire sequence into a
-/// player engine.
+/// player engine. The `render_range` function renders a time range ahead of time instead.
 #[derive(Clone, Debug)]
 pub struct Sequence {
     time_base: BeatTimeBase,
@@ -19,15 +836,63 @@ pub struct Sequence {
     sample_position_in_phrase: SampleTime,
     sample_position: SampleTime,
     sample_offset: SampleTime,
+    external_context: ExternalContextValues,
+    shared_values: SharedValues,
+    random_seed: Option<[u8; 32]>,
+    layers: Vec<Sequence>,
+    cue_points: Vec<CuePoint>,
+    cue_point_callback: Option<CuePointCallback>,
+    cue_point_jump: Option<CuePointJump>,
+    scenes: Vec<Scene>,
+    scene_index: Option<usize>,
+    scene_switch: Option<SceneSwitch>,
+    notifications: NotificationBus,
+    time_shift: i64,
+    parameter_changes: Vec<ScheduledParameterChange>,
+    injected_events: Vec<InjectedEvent>,
+    volume_curve: VolumeCurve,
+    instrument_layers: InstrumentLayers,
+    layered_voices: LayeredVoices,
+    event_limiter: EventLimiter,
+    event_orderer: EventOrderer,
+    event_merger: EventMerger,
+    event_history: Option<EventHistory>,
+    macros: Vec<PerformanceMacro>,
 }
 
 impl Sequence {
     /// Create a new sequence from a vector of [`Phrase`].
     pub fn new(time_base: BeatTimeBase, phrases: Vec<Phrase>) -> Self {
+        let mut phrases = phrases;
         let phrase_index = 0;
         let sample_position_in_phrase = 0;
         let sample_position = 0;
         let sample_offset = 0;
+        let external_context = ExternalContextValues::new();
+        let shared_values = SharedValues::new();
+        let random_seed = None;
+        let layers = Vec::new();
+        let cue_points = Vec::new();
+        let cue_point_callback = None;
+        let cue_point_jump = None;
+        let scenes = Vec::new();
+        let scene_index = None;
+        let scene_switch = None;
+        let notifications = NotificationBus::new();
+        let time_shift = 0;
+        let parameter_changes = Vec::new();
+        let injected_events = Vec::new();
+        let volume_curve = VolumeCurve::new();
+        let instrument_layers = InstrumentLayers::new();
+        let layered_voices = LayeredVoices::default();
+        let event_limiter = EventLimiter::default();
+        let event_orderer = EventOrderer::default();
+        let event_merger = EventMerger::default();
+        let event_history = None;
+        let macros = Vec::new();
+        for phrase in &mut phrases {
+            phrase.set_shared_values(&shared_values);
+        }
         Self {
             time_base,
             phrases,
@@ -35,7 +900,169 @@ impl Sequence {
             sample_position_in_phrase,
             sample_position,
             sample_offset,
+            external_context,
+            shared_values,
+            random_seed,
+            layers,
+            cue_points,
+            cue_point_callback,
+            cue_point_jump,
+            scenes,
+            scene_index,
+            scene_switch,
+            notifications,
+            time_shift,
+            parameter_changes,
+            injected_events,
+            volume_curve,
+            instrument_layers,
+            layered_voices,
+            event_limiter,
+            event_orderer,
+            event_merger,
+            event_history,
+            macros,
+        }
+    }
+
+    /// Return a new sequence which plays the given phrases sequentially in parallel to all
+    /// other phrases in the sequence. Layers are reset along with the sequence.
+    #[must_use]
+    pub fn with_layer(self, phrases: Vec<Phrase>) -> Self {
+        let mut layer = Self::new(self.time_base, phrases);
+        layer.set_shared_values(&self.shared_values);
+        layer.restore_external_context(&self.external_context);
+        let mut layers = self.layers;
+        if let Some(seed) = self.random_seed {
+            layer = layer.with_frozen_random_seed(derived_seed(seed, layers.len() as u64 + 1));
         }
+        layers.push(layer);
+        Self { layers, ..self }
+    }
+
+    /// Return a new sequence which freezes random choices within its phrases: random number
+    /// generators of all gates, event iters and humanizers in the phrases, e.g. probability
+    /// gates or random choices in cycles, get reseeded each time a phrase starts playing,
+    /// with a seed that is derived from the given seed and the phrase's index in the sequence.
+    ///
+    /// This way each phrase is internally random, but sounds identical when the sequence
+    /// repeats it. Phrases also get reset when they repeat in single phrase sequences then.
+    /// Random values in scripted callbacks, e.g. Lua's `math.random`, are not affected.
+    #[must_use]
+    pub fn with_frozen_random_seed(self, seed: [u8; 32]) -> Self {
+        let layers = self
+            .layers
+            .into_iter()
+            .enumerate()
+            .map(|(index, layer)| {
+                layer.with_frozen_random_seed(derived_seed(seed, index as u64 + 1))
+            })
+            .collect();
+        let mut sequence = Self {
+            random_seed: Some(seed),
+            layers,
+            ..self
+        };
+        sequence.seed_current_phrase();
+        sequence
+    }
+
+    /// Return a new sequence which applies the given master volume curve to all notes.
+    #[must_use]
+    pub fn with_volume_curve(self, volume_curve: VolumeCurve) -> Self {
+        Self {
+            volume_curve,
+            ..self
+        }
+    }
+
+    /// Return a new sequence which fans out notes of all phrases and layers to the instruments
+    /// of the given instrument layer table.
+    #[must_use]
+    pub fn with_instrument_layers(self, instrument_layers: InstrumentLayers) -> Self {
+        Self {
+            instrument_layers,
+            ..self
+        }
+    }
+
+    /// Return a new sequence which drops events of all phrases and layers which exceed the given
+    /// event limit.
+    #[must_use]
+    pub fn with_event_limit(self, limit: EventLimit) -> Self {
+        let mut sequence = self;
+        sequence.set_event_limit(limit);
+        sequence
+    }
+
+    /// Return a new sequence which merges identical note-ons of all phrases and layers, which
+    /// get emitted at the same time, as specified by the given merge mode.
+    #[must_use]
+    pub fn with_event_merge(self, merge: EventMerge) -> Self {
+        let mut sequence = self;
+        sequence.set_event_merge(merge);
+        sequence
+    }
+
+    /// Return a new sequence which orders events of all phrases and layers, which get emitted at
+    /// the same time, as specified by the given ordering.
+    #[must_use]
+    pub fn with_event_ordering(self, ordering: EventOrdering) -> Self {
+        let mut sequence = self;
+        sequence.set_event_ordering(ordering);
+        sequence
+    }
+
+    /// Return a new sequence which memorizes up to `capacity` recently emitted events of each
+    /// rhythm slot in an [`EventHistory`], so hosts can inspect them without consuming them.
+    #[must_use]
+    pub fn with_event_history(self, capacity: usize) -> Self {
+        Self {
+            event_history: Some(EventHistory::new(capacity)),
+            ..self
+        }
+    }
+
+    /// Create a deep copy of the sequence, which duplicates all rhythms in all phrases, so the
+    /// copy can be run without affecting this sequence. A `clone` shares the rhythms instead.
+    ///
+    /// Duplicates copy the playback state of all patterns, gates and event iters, including
+    /// their random number generators, along with the shared and parameter values, so hosts
+    /// can use duplicates as snapshots for undo, or run a preview copy ahead of time. Scripted
+    /// Lua generator functions get called again for duplicates, so their local state starts
+    /// from scratch in the copy. Duplicates get a new notification bus without subscriptions.
+    ///
+    /// Fails when a rhythm can't be duplicated, e.g. when a scripted callback is a running
+    /// coroutine, which can't be copied.
+    pub fn duplicate(&self) -> Result<Self, String> {
+        let mut duplicates = Vec::new();
+        let shared_values = self.shared_values.duplicate();
+        let phrases = self
+            .phrases
+            .iter()
+            .map(|phrase| {
+                let mut phrase = phrase.duplicate_with(&mut duplicates)?;
+                phrase.set_shared_values(&shared_values);
+                Ok(phrase)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| {
+                let mut layer = layer.duplicate()?;
+                layer.set_shared_values(&shared_values);
+                Ok(layer)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let notifications = NotificationBus::new();
+        Ok(Self {
+            phrases,
+            shared_values,
+            layers,
+            notifications,
+            ..self.clone()
+        })
     }
 
     /// Read-only borrowed access to our time base.
@@ -43,13 +1070,978 @@ impl Sequence {
         &self.time_base
     }
 
+    /// Sample time until which the sequence got run or seeked.
+    pub fn sample_position(&self) -> SampleTime {
+        self.shifted_time(self.sample_position)
+    }
+
+    /// Current position on the sequence's timeline in samples: the start of the current phrase
+    /// plus the position within the phrase. Unlike the sample position, this wraps around
+    /// when the sequence repeats and moves with cue point jumps.
+    pub fn song_position(&self) -> SampleTime {
+        self.phrase_start_time(self.phrase_index) + self.sample_position_in_phrase
+    }
+
+    /// Length of all phrases in the sequence in samples.
+    pub fn song_length(&self) -> SampleTime {
+        self.phrase_start_time(self.phrases.len())
+    }
+
+    /// Return a new sequence with the given cue points.
+    #[must_use]
+    pub fn with_cue_points(self, cue_points: Vec<CuePoint>) -> Self {
+        let mut sequence = self;
+        for cue_point in cue_points {
+            sequence.add_cue_point(cue_point);
+        }
+        sequence
+    }
+
+    /// Read-only access to our cue points, sorted by their position.
+    pub fn cue_points(&self) -> &[CuePoint] {
+        &self.cue_points
+    }
+
+    /// Find a cue point by name.
+    pub fn cue_point(&self, name: &str) -> Option<&CuePoint> {
+        self.cue_points
+            .iter()
+            .find(|cue_point| cue_point.name() == name)
+    }
+
+    /// Add a new cue point or move an existing cue point with the same name.
+    pub fn add_cue_point(&mut self, cue_point: CuePoint) {
+        self.cue_points
+            .retain(|other| other.name() != cue_point.name());
+        let index = self
+            .cue_points
+            .partition_point(|other| other.bar() <= cue_point.bar());
+        self.cue_points.insert(index, cue_point);
+    }
+
+    /// Remove the cue point with the given name, if it exists.
+    pub fn remove_cue_point(&mut self, name: &str) {
+        self.cue_points.retain(|cue_point| cue_point.name() != name);
+    }
+
+    /// Set a callback which gets called when playback passes a cue point while consuming
+    /// events. Seeking the sequence does not call the callback.
+    pub fn set_cue_point_callback<F: Fn(&CuePoint, SampleTime) + 'static>(&mut self, callback: F) {
+        self.cue_point_callback = Some(CuePointCallback(Rc::new(callback)));
+    }
+
+    /// Remove a previously set cue point callback.
+    pub fn clear_cue_point_callback(&mut self) {
+        self.cue_point_callback = None;
+    }
+
+    /// Jump to the cue point with the given name at the next `quantum`, e.g. the next bar.
+    /// Emitted event times continue seamlessly, but the sequence's phrases and layers continue
+    /// playing from the cue point's position. A previously scheduled jump gets replaced.
+    ///
+    /// Returns error when no cue point with the given name exists.
+    pub fn jump_to_cue_point(&mut self, name: &str, quantum: BeatTimeStep) -> Result<(), String> {
+        if self.cue_point(name).is_none() {
+            return Err(format!("cue point '{}' does not exist", name));
+        }
+        let sample_time = self.next_quantized_time(quantum);
+        self.cue_point_jump = Some(CuePointJump {
+            name: name.to_string(),
+            sample_time,
+        });
+        Ok(())
+    }
+
+    /// Name and sample time of a scheduled, but not yet applied cue point jump, if any.
+    pub fn pending_cue_point_jump(&self) -> Option<(&str, SampleTime)> {
+        self.cue_point_jump
+            .as_ref()
+            .map(|jump| (jump.name.as_str(), jump.sample_time))
+    }
+
+    /// Cancel a scheduled cue point jump.
+    pub fn cancel_cue_point_jump(&mut self) {
+        self.cue_point_jump = None;
+    }
+
+    /// Return a new sequence with the given scenes, see [`Self::select_scene`].
+    #[must_use]
+    pub fn with_scenes(self, scenes: Vec<Scene>) -> Self {
+        Self {
+            scenes,
+            scene_index: None,
+            scene_switch: None,
+            ..self
+        }
+    }
+
+    /// Read-only access to our scenes.
+    pub fn scenes(&self) -> &[Scene] {
+        &self.scenes
+    }
+
+    /// The currently playing scene, if a scene got selected.
+    pub fn current_scene(&self) -> Option<&Scene> {
+        self.scene_index
+            .map(|scene_index| &self.scenes[scene_index])
+    }
+
+    /// Switch to the scene with the given name at the next `quantum`, e.g. the next bar.
+    /// Emitted event times continue seamlessly. The scene's phrase starts playing, applying
+    /// the phrase's continue modes, and the scene's parameter values get applied. The phrase
+    /// then loops until another scene gets selected, or until the sequence jumps to a cue
+    /// point or gets reset to its start. A previously scheduled switch gets replaced.
+    ///
+    /// Returns error when no scene with the given name exists, or when the scene's phrase or
+    /// one of its parameters does not exist.
+    pub fn select_scene(&mut self, name: &str, quantum: BeatTimeStep) -> Result<(), String> {
+        let scene_index = self
+            .scenes
+            .iter()
+            .position(|scene| scene.name() == name)
+            .ok_or_else(|| format!("scene '{}' does not exist", name))?;
+        let scene = &self.scenes[scene_index];
+        if scene.phrase_index() >= self.phrases.len() {
+            return Err(format!(
+                "invalid phrase index '{}' in scene '{}': the sequence has {} phrases",
+                scene.phrase_index(),
+                name,
+                self.phrases.len()
+            ));
+        }
+        for (id, _) in scene.parameter_values() {
+            self.check_parameter(id)?;
+        }
+        let sample_time = self.next_quantized_time(quantum);
+        self.scene_switch = Some(SceneSwitch {
+            scene_index,
+            sample_time,
+        });
+        Ok(())
+    }
+
+    /// Name and sample time of a scheduled, but not yet applied scene switch, if any.
+    pub fn pending_scene_switch(&self) -> Option<(&str, SampleTime)> {
+        self.scene_switch
+            .as_ref()
+            .map(|switch| (self.scenes[switch.scene_index].name(), switch.sample_time))
+    }
+
+    /// Cancel a scheduled scene switch.
+    pub fn cancel_scene_switch(&mut self) {
+        self.scene_switch = None;
+    }
+
+    /// The sequence's notification bus, to get notified about phrase changes, passed cue points,
+    /// pattern, parameter and tempo changes and script errors of the sequence's rhythms. Seeking
+    /// the sequence publishes no phrase, pattern and cue point notifications.
+    pub fn notifications(&self) -> &NotificationBus {
+        &self.notifications
+    }
+
+    /// Update the sequence's and all phrase's time base with the new time base.
+    /// The current playback position within the current phrase is moved, so that it keeps its
+    /// musical position in the phrase.
+    pub fn set_time_base(&mut self, time_base: &BeatTimeBase) {
+        if !self.phrases.is_empty() && self.sample_position_in_phrase > 0 {
+            let length = self.current_phrase().length();
+            let old_phrase_length = length.to_samples(&self.time_base);
+            let new_phrase_length = length.to_samples(time_base);
+            if old_phrase_length > 0.0 {
+                self.sample_position_in_phrase =
+                    (self.sample_position_in_phrase as f64 / old_phrase_length * new_phrase_length)
+                        as SampleTime;
+            }
+        }
+        let tempo_changed = self.time_base.beats_per_min != time_base.beats_per_min;
+        self.time_base.clone_from(time_base);
+        for phrase in &mut self.phrases {
+            phrase.set_time_base(time_base);
+        }
+        for layer in &mut self.layers {
+            layer.set_time_base(time_base);
+        }
+        if tempo_changed {
+            self.notifications.publish(Notification::TempoChanged {
+                beats_per_min: time_base.beats_per_min,
+            });
+        }
+    }
+
+    /// Set external context data for all rhythms in all phrases. Values are memorized, so
+    /// they can be saved and restored via [`Self::external_context`] and
+    /// [`Self::restore_external_context`].
+    pub fn set_external_context(&mut self, data: &[(Cow<str>, f64)]) {
+        self.external_context.merge(data);
+        for phrase in &mut self.phrases {
+            phrase.set_external_context(data);
+        }
+        for layer in &mut self.layers {
+            layer.set_external_context(data);
+        }
+    }
+
+    /// Snapshot of all external context values that got set so far.
+    pub fn external_context(&self) -> &ExternalContextValues {
+        &self.external_context
+    }
+
+    /// Restore previously saved external context values, e.g. after the sequence got
+    /// recreated from reloaded scripts.
+    pub fn restore_external_context(&mut self, values: &ExternalContextValues) {
+        self.set_external_context(&values.to_context_data());
+    }
+
+    /// Shared values of all rhythms in the sequence. Hosts may read or modify the values too.
+    pub fn shared_values(&self) -> &SharedValues {
+        &self.shared_values
+    }
+
     /// Read-only borrowed access to our phrases.
     pub fn phrases(&self) -> &Vec<Phrase> {
         &self.phrases
     }
 
-    /// returns maximum rhythm count in all phrases.
+    /// Read-only borrowed access to our layers, which play in parallel to our phrases.
+    pub fn layers(&self) -> &Vec<Sequence> {
+        &self.layers
+    }
+
+    /// Time spent in the rhythm slots of all phrases and layers so far, when the `profiling`
+    /// feature is enabled. Profiles of all phrases are summed up and indexed by rhythm index,
+    /// as used in emitted events, so hosts can show which slots are slow.
+    #[cfg(feature = "profiling")]
+    pub fn slot_profiles(&self) -> Vec<RhythmProfile> {
+        let mut profiles = self.main_slot_profiles();
+        for layer in &self.layers {
+            profiles.extend(layer.main_slot_profiles());
+        }
+        profiles
+    }
+
+    /// Clear the measured slot profiles of all phrases and layers.
+    #[cfg(feature = "profiling")]
+    pub fn clear_slot_profiles(&mut self) {
+        for phrase in &mut self.phrases {
+            phrase.clear_slot_profiles();
+        }
+        for layer in &mut self.layers {
+            layer.clear_slot_profiles();
+        }
+    }
+
+    /// List all user controllable parameters of all rhythms in all phrases, e.g. to create a
+    /// control UI for them. Rhythms which are shared across phrases are listed only once.
+    pub fn parameters(&self) -> Vec<SequenceParameter> {
+        let mut parameters = Vec::new();
+        let mut visited_rhythms = Vec::new();
+        self.collect_parameters(0, &mut parameters, &mut visited_rhythms);
+        let mut rhythm_offset = self.main_rhythm_slot_count();
+        for layer in &self.layers {
+            layer.collect_parameters(rhythm_offset, &mut parameters, &mut visited_rhythms);
+            rhythm_offset += layer.main_rhythm_slot_count();
+        }
+        parameters
+    }
+
+    /// Fetch and clear all parameter value changes, which got applied by the rhythms on their
+    /// own, e.g. by scripts via `context.parameters`, so hosts can update their parameter
+    /// controls. Changes are (id, value) pairs, using the ids of [`Self::parameters`].
+    pub fn take_parameter_changes(&mut self) -> Vec<(String, f64)> {
+        let mut changes = Vec::new();
+        let mut visited_rhythms = Vec::new();
+        self.collect_parameter_changes(0, &mut changes, &mut visited_rhythms);
+        let mut rhythm_offset = self.main_rhythm_slot_count();
+        for layer in &self.layers {
+            layer.collect_parameter_changes(rhythm_offset, &mut changes, &mut visited_rhythms);
+            rhythm_offset += layer.main_rhythm_slot_count();
+        }
+        changes
+    }
+
+    /// Set a new value for the parameter with the given id, using the ids of
+    /// [`Self::parameters`]. The value gets applied right away and is not reported in
+    /// [`Self::take_parameter_changes`]. Returns the applied, clamped value.
+    ///
+    /// Returns error when the parameter does not exist.
+    pub fn set_parameter_value(&mut self, id: &str, value: f64) -> Result<f64, String> {
+        let (rhythm, parameter_id) = self.parameter_rhythm(id)?;
+        let value = rhythm
+            .borrow_mut()
+            .set_parameter_value(&parameter_id, value)?;
+        if self
+            .notifications
+            .has_subscribers(NotificationKind::ParameterChanged)
+        {
+            self.notifications.publish(Notification::ParameterChanged {
+                id: id.to_string(),
+                value,
+            });
+        }
+        Ok(value)
+    }
+
+    /// Schedule a batch of parameter value changes, (id, value) pairs using the ids of
+    /// [`Self::parameters`], which get applied together at the given time while consuming or
+    /// skipping events, so automated parameters line up with the beat. Changes apply to all
+    /// events which start at or after the given time. Returns the sample time at which the
+    /// changes get applied.
+    ///
+    /// Returns error when one of the parameters does not exist. No changes are scheduled then.
+    pub fn schedule_parameter_changes(
+        &mut self,
+        changes: &[(&str, f64)],
+        time: ParameterChangeTime,
+    ) -> Result<SampleTime, String> {
+        for (id, _) in changes {
+            self.check_parameter(id)?;
+        }
+        let sample_time = self.parameter_change_time(time);
+        // keep changes sorted by time and in scheduling order
+        let index = self
+            .parameter_changes
+            .partition_point(|change| change.sample_time <= sample_time);
+        let scheduled = changes.iter().map(|(id, value)| ScheduledParameterChange {
+            sample_time,
+            id: id.to_string(),
+            value: *value,
+        });
+        self.parameter_changes.splice(index..index, scheduled);
+        Ok(sample_time)
+    }
+
+    /// Schedule a smooth change of the parameter with the given id, using the ids of
+    /// [`Self::parameters`], from its actual value to the given value. The ramp starts at the
+    /// given time and gets applied as a series of scheduled parameter changes, see
+    /// [`Self::schedule_parameter_changes`]. Pending changes of the same parameter after the
+    /// ramp's start get replaced. Returns the sample time at which the ramp starts.
+    ///
+    /// Returns error when the parameter does not exist.
+    pub fn ramp_parameter_value(
+        &mut self,
+        id: &str,
+        value: f64,
+        ramp: &ParameterRamp,
+        time: ParameterChangeTime,
+    ) -> Result<SampleTime, String> {
+        let start_time = self.parameter_change_time(time);
+        let (rhythm, parameter_id) = self.parameter_rhythm(id)?;
+        let parameter = rhythm
+            .borrow()
+            .parameters()
+            .into_iter()
+            .find(|parameter| parameter.id() == parameter_id)
+            .ok_or_else(|| format!("parameter '{}' does not exist", id))?;
+        // start with the last pending value before the ramp, else the actual value
+        self.parameter_changes
+            .retain(|change| change.id != id || change.sample_time < start_time);
+        let start_value = self
+            .parameter_changes
+            .iter()
+            .rev()
+            .find(|change| change.id == id)
+            .map_or(parameter.value(), |change| change.value);
+        let samples_per_sec = self.time_base.samples_per_sec as f64;
+        let duration = (ramp.duration() * samples_per_sec).round() as SampleTime;
+        let step_count = (ramp.duration() / ramp.resolution())
+            .ceil()
+            .clamp(1.0, MAX_PARAMETER_RAMP_STEPS as f64) as SampleTime;
+        let mut last_value = start_value;
+        let mut steps = Vec::with_capacity(step_count as usize);
+        for step in 1..=step_count {
+            let position = step as f64 / step_count as f64;
+            let sample_time = start_time + duration * step / step_count;
+            let value = if step == step_count {
+                value
+            } else {
+                ramp.value_at(start_value, value, position)
+            };
+            // skip steps which would not change integer parameters
+            if parameter.is_integer() && value.round() == last_value.round() && step < step_count {
+                continue;
+            }
+            last_value = value;
+            steps.push(ScheduledParameterChange {
+                sample_time,
+                id: id.to_string(),
+                value,
+            });
+        }
+        // keep changes sorted by time and in scheduling order: the sort is stable
+        self.parameter_changes.extend(steps);
+        self.parameter_changes
+            .sort_by_key(|change| change.sample_time);
+        Ok(start_time)
+    }
+
+    /// Number of scheduled, but not yet applied parameter changes.
+    pub fn pending_parameter_changes(&self) -> usize {
+        self.parameter_changes.len()
+    }
+
+    /// Cancel all scheduled parameter changes.
+    pub fn cancel_parameter_changes(&mut self) {
+        self.parameter_changes.clear();
+    }
+
+    /// Read-only access to our performance macros.
+    pub fn macros(&self) -> &[PerformanceMacro] {
+        &self.macros
+    }
+
+    /// Find a performance macro by name, e.g. to query its current value.
+    pub fn performance_macro(&self, name: &str) -> Option<&PerformanceMacro> {
+        self.macros
+            .iter()
+            .find(|performance_macro| performance_macro.name() == name)
+    }
+
+    /// Add a new performance macro or replace an existing macro with the same name. The
+    /// macro's targets get applied with the macro's current value right away.
+    ///
+    /// Returns error when one of the macro's parameter targets does not exist or when a
+    /// target's rhythm index exceeds [`Self::phrase_rhythm_slot_count`]. The macro is not
+    /// added then.
+    pub fn add_macro(&mut self, performance_macro: PerformanceMacro) -> Result<(), String> {
+        let slot_count = self.phrase_rhythm_slot_count();
+        for target in performance_macro.targets() {
+            match target {
+                MacroTarget::Parameter { id, .. } => {
+                    self.parameter_rhythm(id)?;
+                }
+                MacroTarget::Mute { rhythm_index, .. }
+                | MacroTarget::SlotDelay { rhythm_index, .. } => {
+                    if *rhythm_index >= slot_count {
+                        return Err(format!(
+                            "invalid rhythm index '{}' in macro '{}': the sequence has {} slots",
+                            rhythm_index,
+                            performance_macro.name(),
+                            slot_count
+                        ));
+                    }
+                }
+                MacroTarget::Context { .. } => (),
+            }
+        }
+        self.remove_macro(performance_macro.name());
+        self.apply_macro_targets(&performance_macro)?;
+        self.macros.push(performance_macro);
+        Ok(())
+    }
+
+    /// Remove the performance macro with the given name. Targets keep their current values,
+    /// but slots which got muted by the macro get unmuted.
+    pub fn remove_macro(&mut self, name: &str) {
+        self.macros
+            .retain(|performance_macro| performance_macro.name() != name);
+    }
+
+    /// Set the control value of the performance macro with the given name in range \[0 - 1\]
+    /// and apply it to all of the macro's targets right away. Returns the applied, clamped
+    /// value.
+    ///
+    /// Returns error when the macro does not exist.
+    pub fn set_macro_value(&mut self, name: &str, value: f64) -> Result<f64, String> {
+        let index = self
+            .macros
+            .iter()
+            .position(|performance_macro| performance_macro.name() == name)
+            .ok_or_else(|| format!("macro '{}' does not exist", name))?;
+        let mut performance_macro = self.macros[index].clone();
+        performance_macro.set_value(value);
+        self.apply_macro_targets(&performance_macro)?;
+        let value = performance_macro.value();
+        self.macros[index] = performance_macro;
+        Ok(value)
+    }
+
+    /// Returns true when the given rhythm slot is currently muted by a performance macro.
+    pub fn is_slot_muted(&self, rhythm_index: RhythmIndex) -> bool {
+        self.macros
+            .iter()
+            .any(|performance_macro| performance_macro.mutes_slot(rhythm_index))
+    }
+
+    fn apply_macro_targets(&mut self, performance_macro: &PerformanceMacro) -> Result<(), String> {
+        let value = performance_macro.value();
+        for target in performance_macro.targets() {
+            let target_value = target.value_at(value);
+            match target {
+                MacroTarget::Parameter { id, .. } => {
+                    self.set_parameter_value(id, target_value)?;
+                }
+                MacroTarget::SlotDelay { rhythm_index, .. } => {
+                    self.set_slot_delay(*rhythm_index, target_value);
+                }
+                MacroTarget::Context { name, .. } => {
+                    self.set_external_context(&[(Cow::Borrowed(name.as_str()), target_value)]);
+                }
+                MacroTarget::Mute { .. } => {
+                    // applied while consuming events
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn macro_muted_slots(&self) -> Vec<RhythmIndex> {
+        let mut muted_slots = Vec::new();
+        for performance_macro in &self.macros {
+            for target in performance_macro.targets() {
+                if let MacroTarget::Mute { rhythm_index, .. } = *target {
+                    if target.value_at(performance_macro.value()) > 0.0 {
+                        muted_slots.push(rhythm_index);
+                    }
+                }
+            }
+        }
+        muted_slots
+    }
+
+    /// Inject an externally generated event with the given rhythm slot index, start time and
+    /// duration in samples. Injected events get merged into the emitted events while consuming
+    /// events, ordered by their time, so they show up in the same event stream as the
+    /// sequence's own events. Times in the past get emitted right away. Returns the sample time
+    /// at which the event gets emitted.
+    ///
+    /// Events which get injected into existing slots pass through the slot's currently playing
+    /// rhythm, see [`Rhythm::transform_event`], just like the rhythm's own events. All injected
+    /// events then get routed like the sequence's own events: they pass the master volume
+    /// curve, slot mutes, instrument layers, note merging, the event limit and the history.
+    /// Use indices above [`Self::phrase_rhythm_slot_count`] to route injected events separately.
+    ///
+    /// Injected events are dropped when skipping events or when resetting the sequence.
+    pub fn inject_event(
+        &mut self,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+        event: Event,
+        duration: SampleTime,
+    ) -> SampleTime {
+        let sample_time = sample_time.max(self.sample_position());
+        // keep events sorted by time and in injection order
+        let index = self
+            .injected_events
+            .partition_point(|injected| injected.sample_time <= sample_time);
+        self.injected_events.insert(
+            index,
+            InjectedEvent {
+                rhythm_index,
+                sample_time,
+                event,
+                duration,
+            },
+        );
+        sample_time
+    }
+
+    /// Inject an externally generated event like [`Self::inject_event`], partially quantizing
+    /// its start time with the given quantize setting and the sequence's time base first, e.g.
+    /// to move live played notes toward the grid while keeping some of their human timing.
+    /// Returns the sample time at which the event gets emitted.
+    pub fn inject_quantized_event(
+        &mut self,
+        rhythm_index: RhythmIndex,
+        sample_time: SampleTime,
+        event: Event,
+        duration: SampleTime,
+        quantize: &TimingQuantize,
+    ) -> SampleTime {
+        let sample_time = quantize.apply(&self.time_base, sample_time);
+        self.inject_event(rhythm_index, sample_time, event, duration)
+    }
+
+    /// Number of injected, but not yet emitted events.
+    pub fn pending_injected_events(&self) -> usize {
+        self.injected_events.len()
+    }
+
+    /// Drop all injected, but not yet emitted events.
+    pub fn cancel_injected_events(&mut self) {
+        self.injected_events.clear();
+    }
+
+    /// Estimated memory usage of all rhythms in all phrases and layers and of the sequence's
+    /// pending events, e.g. to monitor long-running sessions. Rhythms which are shared across
+    /// phrases are counted only once.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage::default();
+        let mut visited_rhythms = Vec::new();
+        self.collect_memory_usage(&mut usage, &mut visited_rhythms);
+        usage
+    }
+
+    /// Release unused memory of all rhythms in all phrases and layers, e.g. garbage of Lua
+    /// engines and unused event buffer capacities. Hosts may call this between songs.
+    pub fn trim_memory(&mut self) {
+        for phrase in &mut self.phrases {
+            phrase.trim_memory();
+        }
+        for layer in &mut self.layers {
+            layer.trim_memory();
+        }
+        self.parameter_changes.shrink_to_fit();
+        self.injected_events.shrink_to_fit();
+    }
+
+    fn parameter_rhythm(&self, id: &str) -> Result<(Rc<RefCell<dyn Rhythm>>, String), String> {
+        // ids are "phrase_index.rhythm_index.parameter_id"
+        let mut parts = id.splitn(3, '.');
+        let phrase_index = parts.next().and_then(|part| part.parse::<usize>().ok());
+        let rhythm_index = parts
+            .next()
+            .and_then(|part| part.parse::<RhythmIndex>().ok());
+        let parameter_id = parts.next();
+        if let (Some(phrase_index), Some(rhythm_index), Some(parameter_id)) =
+            (phrase_index, rhythm_index, parameter_id)
+        {
+            // find the sequence or layer which plays the rhythm
+            let mut sequence = self;
+            let mut slot_index = rhythm_index;
+            let mut rhythm_offset = self.main_rhythm_slot_count();
+            if slot_index >= rhythm_offset {
+                for layer in &self.layers {
+                    let slot_count = layer.main_rhythm_slot_count();
+                    if (rhythm_offset..rhythm_offset + slot_count).contains(&rhythm_index) {
+                        sequence = layer;
+                        slot_index = rhythm_index - rhythm_offset;
+                        break;
+                    }
+                    rhythm_offset += slot_count;
+                }
+            }
+            let rhythm_slot = sequence
+                .phrases
+                .get(phrase_index)
+                .and_then(|phrase| phrase.rhythm_slots().get(slot_index));
+            if let Some(RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm)) = rhythm_slot
+            {
+                return Ok((Rc::clone(rhythm), parameter_id.to_string()));
+            }
+        }
+        Err(format!("parameter '{}' does not exist", id))
+    }
+
+    fn check_parameter(&self, id: &str) -> Result<(), String> {
+        let (rhythm, parameter_id) = self.parameter_rhythm(id)?;
+        let rhythm = rhythm.borrow();
+        if rhythm
+            .parameters()
+            .iter()
+            .any(|parameter| parameter.id() == parameter_id)
+        {
+            Ok(())
+        } else {
+            Err(format!("parameter '{}' does not exist", id))
+        }
+    }
+
+    fn collect_parameter_changes(
+        &self,
+        rhythm_offset: RhythmIndex,
+        changes: &mut Vec<(String, f64)>,
+        visited_rhythms: &mut Vec<Rc<RefCell<dyn Rhythm>>>,
+    ) {
+        for (phrase_index, phrase) in self.phrases.iter().enumerate() {
+            for (slot_index, rhythm_slot) in phrase.rhythm_slots().iter().enumerate() {
+                let rhythm_index = rhythm_offset + slot_index;
+                if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                    if visited_rhythms
+                        .iter()
+                        .any(|other| Rc::ptr_eq(other, rhythm))
+                    {
+                        continue;
+                    }
+                    visited_rhythms.push(Rc::clone(rhythm));
+                    for (id, value) in rhythm.borrow_mut().take_parameter_changes() {
+                        let id = format!("{}.{}.{}", phrase_index, rhythm_index, id);
+                        changes.push((id, value));
+                    }
+                }
+            }
+        }
+    }
+
+    fn collect_memory_usage(
+        &self,
+        usage: &mut MemoryUsage,
+        visited_rhythms: &mut Vec<Rc<RefCell<dyn Rhythm>>>,
+    ) {
+        for phrase in &self.phrases {
+            for rhythm_slot in phrase.rhythm_slots() {
+                if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                    let visited = visited_rhythms
+                        .iter()
+                        .any(|other| Rc::ptr_eq(other, rhythm));
+                    if visited {
+                        continue;
+                    }
+                    visited_rhythms.push(Rc::clone(rhythm));
+                    *usage += rhythm.borrow().memory_usage();
+                }
+            }
+            usage.events += phrase.pending_events_memory_usage();
+        }
+        usage.events += self
+            .injected_events
+            .iter()
+            .map(|injected| event_memory_usage(&injected.event))
+            .sum::<usize>()
+            + self.parameter_changes.capacity() * size_of::<ScheduledParameterChange>()
+            + self
+                .event_history
+                .as_ref()
+                .map_or(0, EventHistory::memory_usage);
+        for layer in &self.layers {
+            layer.collect_memory_usage(usage, visited_rhythms);
+        }
+    }
+
+    fn collect_parameters(
+        &self,
+        rhythm_offset: RhythmIndex,
+        parameters: &mut Vec<SequenceParameter>,
+        visited_rhythms: &mut Vec<Rc<RefCell<dyn Rhythm>>>,
+    ) {
+        for (phrase_index, phrase) in self.phrases.iter().enumerate() {
+            for (slot_index, rhythm_slot) in phrase.rhythm_slots().iter().enumerate() {
+                let rhythm_index = rhythm_offset + slot_index;
+                if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                    let visited = visited_rhythms
+                        .iter()
+                        .any(|other| Rc::ptr_eq(other, rhythm));
+                    if visited {
+                        continue;
+                    }
+                    visited_rhythms.push(Rc::clone(rhythm));
+                    for parameter in rhythm.borrow().parameters() {
+                        parameters.push(SequenceParameter {
+                            id: format!("{}.{}.{}", phrase_index, rhythm_index, parameter.id()),
+                            phrase_index,
+                            rhythm_index,
+                            parameter,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// The sequence's master volume curve.
+    pub fn volume_curve(&self) -> &VolumeCurve {
+        &self.volume_curve
+    }
+
+    /// Change the sequence's master volume curve at runtime. Applies to all following events.
+    pub fn set_volume_curve(&mut self, volume_curve: VolumeCurve) {
+        self.volume_curve = volume_curve;
+    }
+
+    /// The sequence's instrument layer table.
+    pub fn instrument_layers(&self) -> &InstrumentLayers {
+        &self.instrument_layers
+    }
+
+    /// Change the sequence's instrument layer table at runtime. New layers apply to all
+    /// following events: notes which are already playing are not layered, but note-offs still
+    /// reach their layers.
+    pub fn set_instrument_layers(&mut self, instrument_layers: InstrumentLayers) {
+        self.instrument_layers = instrument_layers;
+    }
+
+    /// The sequence's event limit.
+    pub fn event_limit(&self) -> &EventLimit {
+        &self.event_limiter.limit
+    }
+
+    /// Change the sequence's event limit at runtime. Events get counted from scratch then.
+    pub fn set_event_limit(&mut self, limit: EventLimit) {
+        self.event_limiter.limit = limit;
+        self.event_limiter.reset();
+    }
+
+    /// The sequence's event merge mode.
+    pub fn event_merge(&self) -> EventMerge {
+        self.event_merger.merge
+    }
+
+    /// Change the sequence's event merge mode at runtime.
+    pub fn set_event_merge(&mut self, merge: EventMerge) {
+        self.event_merger.merge = merge;
+        self.event_merger.reset();
+    }
+
+    /// The sequence's event ordering.
+    pub fn event_ordering(&self) -> EventOrdering {
+        self.event_orderer.ordering
+    }
+
+    /// Change the sequence's event ordering at runtime.
+    pub fn set_event_ordering(&mut self, ordering: EventOrdering) {
+        self.event_orderer.ordering = ordering;
+        self.event_orderer.reset();
+    }
+
+    /// Recently emitted events of all rhythm slots in all phrases and layers, if the sequence
+    /// got created with an event history. Events are recorded after the event limit got
+    /// applied and with the master volume curve applied, as passed to consumers.
+    pub fn event_history(&self) -> Option<&EventHistory> {
+        self.event_history.as_ref()
+    }
+
+    /// Forget all recently emitted events of the event history, if any.
+    pub fn clear_event_history(&mut self) {
+        if let Some(event_history) = &mut self.event_history {
+            event_history.clear();
+        }
+    }
+
+    /// Fetch and clear all non-fatal issues of the sequence, e.g. events which got dropped by
+    /// the [`EventLimit`], and of all rhythms in all phrases and layers. Each distinct warning
+    /// gets reported only once.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        let mut warnings = self.event_limiter.warnings.take();
+        self.for_each_rhythm(&mut Vec::new(), &mut |rhythm| {
+            warnings.append(&mut rhythm.take_warnings());
+        });
+        warnings
+    }
+
+    /// Fetch and clear errors of scripted callbacks of all rhythms in all phrases and layers.
+    fn take_script_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.for_each_rhythm(&mut Vec::new(), &mut |rhythm| {
+            errors.append(&mut rhythm.take_script_errors());
+        });
+        errors
+    }
+
+    /// Call the given function once for each distinct rhythm in all phrases and layers.
+    fn for_each_rhythm(
+        &self,
+        visited_rhythms: &mut Vec<Rc<RefCell<dyn Rhythm>>>,
+        func: &mut dyn FnMut(&mut dyn Rhythm),
+    ) {
+        for phrase in &self.phrases {
+            for rhythm_slot in phrase.rhythm_slots() {
+                if let RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm) = rhythm_slot {
+                    let visited = visited_rhythms
+                        .iter()
+                        .any(|other| Rc::ptr_eq(other, rhythm));
+                    if visited {
+                        continue;
+                    }
+                    visited_rhythms.push(Rc::clone(rhythm));
+                    func(&mut *rhythm.borrow_mut());
+                }
+            }
+        }
+        for layer in &self.layers {
+            layer.for_each_rhythm(visited_rhythms, func);
+        }
+    }
+
+    /// Change the delay of the given rhythm slot in all phrases and layers at runtime, see
+    /// [`Phrase::set_slot_delay`]. Rhythm indices of layers follow the main phrase's indices.
+    pub fn set_slot_delay(&mut self, rhythm_index: RhythmIndex, delay: f64) {
+        for phrase in &mut self.phrases {
+            phrase.set_slot_delay(rhythm_index, delay);
+        }
+        let mut rhythm_offset = self.main_rhythm_slot_count();
+        for layer in &mut self.layers {
+            let slot_count = layer.main_rhythm_slot_count();
+            if (rhythm_offset..rhythm_offset + slot_count).contains(&rhythm_index) {
+                layer.set_slot_delay(rhythm_index - rhythm_offset, delay);
+            }
+            rhythm_offset += slot_count;
+        }
+    }
+
+    /// Play the given rhythm slot backwards in all phrases and layers at runtime, see
+    /// [`Phrase::set_slot_reversed`].
+    pub fn set_slot_reversed(&mut self, rhythm_index: RhythmIndex, reversed: bool) {
+        self.apply_to_slot(rhythm_index, &mut |phrase, rhythm_index, _| {
+            phrase.set_slot_reversed(rhythm_index, reversed);
+        });
+    }
+
+    /// Pause the given rhythm slot in all phrases and layers at the current playback position,
+    /// see [`Phrase::pause_slot`]. Rhythm indices of layers follow the main phrase's indices.
+    pub fn pause_slot(&mut self, rhythm_index: RhythmIndex) {
+        self.apply_to_slot(rhythm_index, &mut |phrase, rhythm_index, sample_time| {
+            phrase.pause_slot(rhythm_index, sample_time);
+        });
+    }
+
+    /// Resume the given paused rhythm slot in all phrases and layers at the current playback
+    /// position, see [`Phrase::resume_slot`].
+    pub fn resume_slot(&mut self, rhythm_index: RhythmIndex, mode: SlotResumeMode) {
+        self.apply_to_slot(rhythm_index, &mut |phrase, rhythm_index, sample_time| {
+            phrase.resume_slot(rhythm_index, sample_time, mode);
+        });
+    }
+
+    /// Returns true when the given rhythm slot is paused in the currently playing phrase.
+    pub fn is_slot_paused(&self, rhythm_index: RhythmIndex) -> bool {
+        let main_slot_count = self.main_rhythm_slot_count();
+        if rhythm_index < main_slot_count {
+            return self.current_phrase().is_slot_paused(rhythm_index);
+        }
+        let mut rhythm_offset = main_slot_count;
+        for layer in &self.layers {
+            let slot_count = layer.main_rhythm_slot_count();
+            if (rhythm_offset..rhythm_offset + slot_count).contains(&rhythm_index) {
+                return layer.is_slot_paused(rhythm_index - rhythm_offset);
+            }
+            rhythm_offset += slot_count;
+        }
+        false
+    }
+
+    fn apply_to_slot<F>(&mut self, rhythm_index: RhythmIndex, func: &mut F)
+    where
+        F: FnMut(&mut Phrase, RhythmIndex, SampleTime),
+    {
+        let main_slot_count = self.main_rhythm_slot_count();
+        if rhythm_index < main_slot_count {
+            let sample_position = self.sample_position;
+            for phrase in &mut self.phrases {
+                func(phrase, rhythm_index, sample_position);
+            }
+            return;
+        }
+        let mut rhythm_offset = main_slot_count;
+        for layer in &mut self.layers {
+            let slot_count = layer.main_rhythm_slot_count();
+            if (rhythm_offset..rhythm_offset + slot_count).contains(&rhythm_index) {
+                layer.apply_to_slot(rhythm_index - rhythm_offset, func);
+            }
+            rhythm_offset += slot_count;
+        }
+    }
+
+    /// returns maximum rhythm count in all phrases, plus the rhythm counts of all layers.
     pub fn phrase_rhythm_slot_count(&self) -> usize {
+        self.main_rhythm_slot_count()
+            + self
+                .layers
+                .iter()
+                .map(Self::main_rhythm_slot_count)
+                .sum::<usize>()
+    }
+
+    #[cfg(feature = "profiling")]
+    fn main_slot_profiles(&self) -> Vec<RhythmProfile> {
+        let mut profiles = vec![RhythmProfile::new(); self.main_rhythm_slot_count()];
+        for phrase in &self.phrases {
+            for (profile, phrase_profile) in profiles.iter_mut().zip(phrase.slot_profiles()) {
+                *profile += *phrase_profile;
+            }
+        }
+        profiles
+    }
+
+    fn main_rhythm_slot_count(&self) -> usize {
         let mut count = 0;
         for phrase in &self.phrases {
             count = count.max(phrase.rhythm_slots().len());
@@ -59,7 +2051,187 @@ impl Sequence {
 
     /// Run rhythms until a given sample time is reached, calling the given `visitor`
     /// function for all emitted events to consume them.
+    ///
+    /// Events of layers are merged with the main phrase events, ordered by their time.
     pub fn consume_events_until_time<F>(&mut self, run_until_time: SampleTime, consumer: &mut F)
+    where
+        F: FnMut(RhythmIndex, SampleTime, Option<Event>, SampleTime),
+    {
+        // apply due parameter changes
+        while let Some(sample_time) = self.next_parameter_change_time(run_until_time) {
+            self.consume_events_until_time(sample_time, consumer);
+            self.apply_parameter_changes(sample_time);
+        }
+        // apply due cue point jumps
+        if let Some(jump) = self.take_due_cue_point_jump(run_until_time) {
+            self.consume_events_until_time(jump.sample_time, consumer);
+            self.apply_cue_point_jump(&jump);
+        }
+        // apply due scene switches
+        if let Some(switch) = self.take_due_scene_switch(run_until_time) {
+            self.consume_events_until_time(switch.sample_time, consumer);
+            let (previous_phrase_index, previous_phrase) = self.apply_scene_switch(&switch);
+            self.notify_phrase_change(previous_phrase_index, &previous_phrase);
+        }
+        // fetch due injected events
+        let injected_count = self
+            .injected_events
+            .partition_point(|injected| injected.sample_time < run_until_time);
+        let pending_events = self.injected_events.split_off(injected_count);
+        let mut injected_events = std::mem::replace(&mut self.injected_events, pending_events);
+        for injected in &mut injected_events {
+            self.transform_injected_event(injected.rhythm_index, &mut injected.event);
+        }
+        let mut injected_events = injected_events.into_iter().peekable();
+        // order events at the same time, mute events of muted slots, expand layered instruments
+        // and merge identical note-ons
+        let mut event_orderer = std::mem::take(&mut self.event_orderer);
+        let muted_slots = self.macro_muted_slots();
+        let instrument_layers = std::mem::take(&mut self.instrument_layers);
+        let mut layered_voices = std::mem::take(&mut self.layered_voices);
+        let mut event_merger = std::mem::take(&mut self.event_merger);
+        // drop events which exceed the event limit
+        let time_base = self.time_base;
+        let mut event_limiter = std::mem::take(&mut self.event_limiter);
+        // memorize accepted events in the event history
+        let mut event_history = self.event_history.take();
+        let mut merged_consumer = |rhythm_index, time, event: Option<Event>, duration| {
+            let event =
+                event.and_then(|event| event_limiter.apply(&time_base, rhythm_index, time, event));
+            if let Some(event_history) = &mut event_history {
+                event_history.push(rhythm_index, time, &event, duration);
+            }
+            consumer(rhythm_index, time, event, duration);
+        };
+        let mut layered_consumer = |rhythm_index, time, event: Option<Event>, duration| {
+            let mut event = if muted_slots.contains(&rhythm_index) {
+                muted_event(event)
+            } else {
+                event
+            };
+            if let Some(event) = &mut event {
+                instrument_layers.apply_to_event(&mut layered_voices, rhythm_index, event);
+            }
+            event_merger.push(rhythm_index, time, event, duration, &mut merged_consumer);
+        };
+        let mut consumer = |rhythm_index, time, event: Option<Event>, duration| {
+            event_orderer.push(rhythm_index, time, event, duration, &mut layered_consumer);
+        };
+        // run phrases in unshifted time, shift emitted events and apply the volume curve
+        let time_shift = self.time_shift;
+        let volume_curve = self.volume_curve;
+        let run_until_time = self.unshifted_time(run_until_time);
+        self.consume_unshifted_events_until_time(
+            run_until_time,
+            &mut |rhythm_index, time, mut event, duration| {
+                let time = (time as i64 + time_shift).max(0) as SampleTime;
+                // merge in injected events which are due before this event
+                while let Some(injected) =
+                    injected_events.next_if(|injected| injected.sample_time <= time)
+                {
+                    Self::emit_injected_event(&volume_curve, injected, &mut consumer);
+                }
+                if let Some(event) = &mut event {
+                    if !volume_curve.is_identity() {
+                        volume_curve.apply_to_event(event);
+                    }
+                }
+                consumer(rhythm_index, time, event, duration);
+            },
+        );
+        for injected in injected_events {
+            Self::emit_injected_event(&volume_curve, injected, &mut consumer);
+        }
+        event_orderer.flush(&mut layered_consumer);
+        event_merger.flush(&mut merged_consumer);
+        self.event_orderer = event_orderer;
+        self.instrument_layers = instrument_layers;
+        self.layered_voices = layered_voices;
+        self.event_merger = event_merger;
+        self.event_limiter = event_limiter;
+        self.event_history = event_history;
+        // publish errors of scripts which ran while consuming
+        if self
+            .notifications
+            .has_subscribers(NotificationKind::ScriptError)
+        {
+            let script_errors = self.take_script_errors();
+            self.notifications.publish_script_errors(script_errors);
+        }
+    }
+
+    /// Apply the transforms of the rhythm which currently plays in the given slot, if any, to
+    /// an injected event.
+    fn transform_injected_event(&self, rhythm_index: RhythmIndex, event: &mut Event) {
+        let main_slot_count = self.main_rhythm_slot_count();
+        if rhythm_index < main_slot_count {
+            if let Some(RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm)) =
+                self.current_phrase().rhythm_slots().get(rhythm_index)
+            {
+                rhythm.borrow_mut().transform_event(event);
+            }
+            return;
+        }
+        let mut rhythm_offset = main_slot_count;
+        for layer in &self.layers {
+            let slot_count = layer.main_rhythm_slot_count();
+            if (rhythm_offset..rhythm_offset + slot_count).contains(&rhythm_index) {
+                layer.transform_injected_event(rhythm_index - rhythm_offset, event);
+                return;
+            }
+            rhythm_offset += slot_count;
+        }
+    }
+
+    fn emit_injected_event<F>(volume_curve: &VolumeCurve, injected: InjectedEvent, consumer: &mut F)
+    where
+        F: FnMut(RhythmIndex, SampleTime, Option<Event>, SampleTime),
+    {
+        let mut event = injected.event;
+        if !volume_curve.is_identity() {
+            volume_curve.apply_to_event(&mut event);
+        }
+        consumer(
+            injected.rhythm_index,
+            injected.sample_time,
+            Some(event),
+            injected.duration,
+        );
+    }
+
+    fn consume_unshifted_events_until_time<F>(
+        &mut self,
+        run_until_time: SampleTime,
+        consumer: &mut F,
+    ) where
+        F: FnMut(RhythmIndex, SampleTime, Option<Event>, SampleTime),
+    {
+        if self.layers.is_empty() {
+            self.consume_main_events_until_time(run_until_time, consumer);
+            return;
+        }
+        // collect events of all layers and pass them to the consumer in time order
+        let mut events = Vec::new();
+        self.consume_main_events_until_time(run_until_time, &mut |index, time, event, duration| {
+            events.push((index, time, event, duration));
+        });
+        let mut rhythm_offset = self.main_rhythm_slot_count();
+        for layer in &mut self.layers {
+            layer.consume_main_events_until_time(
+                run_until_time,
+                &mut |index, time, event, duration| {
+                    events.push((rhythm_offset + index, time, event, duration));
+                },
+            );
+            rhythm_offset += layer.main_rhythm_slot_count();
+        }
+        events.sort_by_key(|(_, time, _, _)| *time);
+        for (rhythm_index, time, event, duration) in events {
+            consumer(rhythm_index, time, event, duration);
+        }
+    }
+
+    fn consume_main_events_until_time<F>(&mut self, run_until_time: SampleTime, consumer: &mut F)
     where
         F: FnMut(RhythmIndex, SampleTime, Option<Event>, SampleTime),
     {
@@ -70,6 +2242,7 @@ impl Sequence {
         while run_until_time - self.sample_position > 0 {
             let (next_phrase_start, samples_to_run) =
                 self.samples_until_next_phrase(run_until_time);
+            self.notify_passed_cue_points(next_phrase_start.min(samples_to_run));
             if next_phrase_start <= samples_to_run {
                 // run current phrase until it ends
                 let sample_position = self.sample_position;
@@ -77,15 +2250,18 @@ impl Sequence {
                     .consume_events_until_time(sample_position + next_phrase_start, consumer);
                 // select next phrase in the sequence
                 let previous_phrase = self.current_phrase_mut().clone();
-                self.phrase_index = (self.phrase_index + 1) % self.phrases().len();
+                let previous_phrase_index = self.phrase_index;
+                self.phrase_index = self.next_phrase_index();
                 self.sample_position_in_phrase = 0;
                 self.sample_position += next_phrase_start;
                 // reset the new phrase or apply continues modes
-                if self.phrases().len() > 1 {
+                if self.phrases().len() > 1 || self.random_seed.is_some() {
                     let sample_offset = self.sample_position;
                     self.current_phrase_mut()
                         .reset_with_offset(sample_offset, &previous_phrase);
+                    self.seed_current_phrase();
                 }
+                self.notify_phrase_change(previous_phrase_index, &previous_phrase);
             } else {
                 // keep running the current phrase
                 let sample_position = self.sample_position;
@@ -99,6 +2275,61 @@ impl Sequence {
 
     /// Seek sequence until a given sample time is reached, ignoring all events.
     pub fn skip_events_until_time(&mut self, run_until_time: SampleTime) {
+        // apply due parameter changes
+        while let Some(sample_time) = self.next_parameter_change_time(run_until_time) {
+            self.skip_events_until_time(sample_time);
+            self.apply_parameter_changes(sample_time);
+        }
+        // apply due cue point jumps
+        if let Some(jump) = self.take_due_cue_point_jump(run_until_time) {
+            self.skip_events_until_time(jump.sample_time);
+            self.apply_cue_point_jump(&jump);
+        }
+        // apply due scene switches
+        if let Some(switch) = self.take_due_scene_switch(run_until_time) {
+            self.skip_events_until_time(switch.sample_time);
+            self.apply_scene_switch(&switch);
+        }
+        // drop due injected events
+        self.injected_events
+            .retain(|injected| injected.sample_time >= run_until_time);
+        let run_until_time = self.unshifted_time(run_until_time);
+        self.skip_unshifted_events_until_time(run_until_time);
+    }
+
+    /// Render all events of the sequence in the given sample time range ahead of time, without
+    /// a player, e.g. to export the sequence or to schedule events in hosts. Events get returned
+    /// in time order, along with their sample times.
+    ///
+    /// Renders a duplicate of the sequence from its start, so rendering does not affect this
+    /// sequence's playback state or notify any subscribers. See [`Self::duplicate`] for details.
+    /// Range bounds work like the sample times of `consume_events_until_time`: events at the end
+    /// time are not included. Fails when the sequence can't be duplicated.
+    pub fn render_range(
+        &self,
+        start_sample: SampleTime,
+        end_sample: SampleTime,
+    ) -> Result<Vec<(SampleTime, Event)>, String> {
+        let mut events = Vec::new();
+        if end_sample <= start_sample {
+            return Ok(events);
+        }
+        let mut sequence = self.duplicate()?;
+        sequence.clear_cue_point_callback();
+        sequence.reset_to_start();
+        sequence.skip_events_until_time(start_sample);
+        sequence.consume_events_until_time(end_sample, &mut |_, time, event, _| {
+            if let Some(event) = event {
+                events.push((time, event));
+            }
+        });
+        Ok(events)
+    }
+
+    fn skip_unshifted_events_until_time(&mut self, run_until_time: SampleTime) {
+        for layer in &mut self.layers {
+            layer.skip_unshifted_events_until_time(run_until_time);
+        }
         debug_assert!(
             run_until_time >= self.sample_position,
             "can not rewind playback here"
@@ -113,14 +2344,15 @@ impl Sequence {
                     .skip_events_until_time(sample_position + next_phrase_start);
                 // select next phrase in the sequence
                 let previous_phrase = self.current_phrase_mut().clone();
-                self.phrase_index = (self.phrase_index + 1) % self.phrases().len();
+                self.phrase_index = self.next_phrase_index();
                 self.sample_position_in_phrase = 0;
                 self.sample_position += next_phrase_start;
                 // reset the new phrase or apply continues modes
-                if self.phrases().len() > 1 {
+                if self.phrases().len() > 1 || self.random_seed.is_some() {
                     let sample_offset = self.sample_position;
                     self.current_phrase_mut()
                         .reset_with_offset(sample_offset, &previous_phrase);
+                    self.seed_current_phrase();
                 }
             } else {
                 // keep running the current phrase
@@ -135,6 +2367,38 @@ impl Sequence {
 
     /// Reset all rhythms in our phrases to their initial state.
     pub fn reset(&mut self) {
+        // reset shared values
+        self.shared_values.clear();
+        // reset cue point jumps, scene switches, scheduled parameter changes and injected events
+        self.cue_point_jump = None;
+        self.scene_switch = None;
+        self.time_shift = 0;
+        self.parameter_changes.clear();
+        self.injected_events.clear();
+        self.event_limiter.reset();
+        self.event_orderer.reset();
+        self.event_merger.reset();
+        self.layered_voices.clear();
+        self.clear_event_history();
+        // reset phrases and layers
+        self.rewind();
+    }
+
+    /// Reset the sequence and move playback to the start of the first phrase in all layers.
+    pub(crate) fn reset_to_start(&mut self) {
+        self.reset_phrase_index();
+        self.reset();
+    }
+
+    fn reset_phrase_index(&mut self) {
+        self.phrase_index = 0;
+        self.scene_index = None;
+        for layer in &mut self.layers {
+            layer.reset_phrase_index();
+        }
+    }
+
+    fn rewind(&mut self) {
         // reset sample offset
         self.sample_offset = 0;
         // reset our own iter state
@@ -144,6 +2408,211 @@ impl Sequence {
         for phrase in &mut self.phrases {
             phrase.reset();
         }
+        // and reseed the first phrase when random values are frozen
+        self.seed_current_phrase();
+        // reset all layers
+        for layer in &mut self.layers {
+            layer.rewind();
+        }
+    }
+
+    fn shifted_time(&self, sample_time: SampleTime) -> SampleTime {
+        (sample_time as i64 + self.time_shift).max(0) as SampleTime
+    }
+
+    fn unshifted_time(&self, sample_time: SampleTime) -> SampleTime {
+        (sample_time as i64 - self.time_shift).max(0) as SampleTime
+    }
+
+    fn phrase_start_time(&self, phrase_index: usize) -> SampleTime {
+        self.phrases
+            .iter()
+            .take(phrase_index)
+            .map(|phrase| phrase.length().to_samples(&self.time_base) as SampleTime)
+            .sum()
+    }
+
+    fn next_quantized_time(&self, quantum: BeatTimeStep) -> SampleTime {
+        let sample_position = self.sample_position();
+        let quantum = quantum.to_samples(&self.time_base);
+        if quantum > 0.0 {
+            let time = ((sample_position as f64 / quantum).ceil() * quantum) as SampleTime;
+            time.max(sample_position)
+        } else {
+            sample_position
+        }
+    }
+
+    fn parameter_change_time(&self, time: ParameterChangeTime) -> SampleTime {
+        let sample_position = self.sample_position();
+        match time {
+            ParameterChangeTime::Now => sample_position,
+            ParameterChangeTime::At(sample_time) => sample_time.max(sample_position),
+            ParameterChangeTime::Next(quantum) => self.next_quantized_time(quantum),
+        }
+    }
+
+    fn next_parameter_change_time(&self, run_until_time: SampleTime) -> Option<SampleTime> {
+        self.parameter_changes
+            .first()
+            .map(|change| change.sample_time)
+            .filter(|sample_time| *sample_time < run_until_time)
+    }
+
+    fn apply_parameter_changes(&mut self, sample_time: SampleTime) {
+        let count = self
+            .parameter_changes
+            .partition_point(|change| change.sample_time <= sample_time);
+        let changes = self.parameter_changes.drain(..count).collect::<Vec<_>>();
+        for change in changes {
+            // parameters may have vanished since the changes got scheduled, so ignore errors
+            let _ = self.set_parameter_value(&change.id, change.value);
+        }
+    }
+
+    fn take_due_cue_point_jump(&mut self, run_until_time: SampleTime) -> Option<CuePointJump> {
+        if self
+            .cue_point_jump
+            .as_ref()
+            .is_some_and(|jump| jump.sample_time < run_until_time)
+        {
+            self.cue_point_jump.take()
+        } else {
+            None
+        }
+    }
+
+    fn apply_cue_point_jump(&mut self, jump: &CuePointJump) {
+        let cue_point_time = self
+            .cue_point(&jump.name)
+            .map(|cue_point| cue_point.to_samples(&self.time_base));
+        if let Some(cue_point_time) = cue_point_time {
+            // rewind and seek to the cue point, then shift time to continue where we are
+            let song_length = self.song_length();
+            let song_position = if song_length > 0 {
+                cue_point_time % song_length
+            } else {
+                0
+            };
+            let sample_position = self.sample_position();
+            self.reset_phrase_index();
+            self.rewind();
+            self.skip_unshifted_events_until_time(song_position);
+            self.time_shift = sample_position as i64 - song_position as i64;
+        }
+    }
+
+    fn take_due_scene_switch(&mut self, run_until_time: SampleTime) -> Option<SceneSwitch> {
+        if self
+            .scene_switch
+            .as_ref()
+            .is_some_and(|switch| switch.sample_time < run_until_time)
+        {
+            self.scene_switch.take()
+        } else {
+            None
+        }
+    }
+
+    /// Start playing the scene's phrase and apply its parameter values. Returns the previous
+    /// phrase and its index.
+    fn apply_scene_switch(&mut self, switch: &SceneSwitch) -> (usize, Phrase) {
+        let scene = self.scenes[switch.scene_index].clone();
+        let previous_phrase = self.current_phrase().clone();
+        let previous_phrase_index = self.phrase_index;
+        self.phrase_index = scene.phrase_index();
+        self.scene_index = Some(switch.scene_index);
+        self.sample_position_in_phrase = 0;
+        // reset the scene's phrase and apply continue modes
+        let sample_offset = self.sample_position;
+        self.current_phrase_mut()
+            .reset_with_offset(sample_offset, &previous_phrase);
+        self.seed_current_phrase();
+        for (id, value) in scene.parameter_values() {
+            // parameters may have vanished since the switch got scheduled, so ignore errors
+            let _ = self.set_parameter_value(id, *value);
+        }
+        (previous_phrase_index, previous_phrase)
+    }
+
+    fn notify_passed_cue_points(&self, samples_to_run: SampleTime) {
+        let publish = self
+            .notifications
+            .has_subscribers(NotificationKind::CuePointPassed);
+        if self.cue_point_callback.is_none() && !publish {
+            return;
+        }
+        let start_time = self.song_position();
+        let end_time = start_time + samples_to_run;
+        for cue_point in &self.cue_points {
+            let time = cue_point.to_samples(&self.time_base);
+            if time >= start_time && time < end_time {
+                let sample_time = self.shifted_time(self.sample_position + time - start_time);
+                if let Some(callback) = &self.cue_point_callback {
+                    (callback.0)(cue_point, sample_time);
+                }
+                if publish {
+                    self.notifications.publish(Notification::CuePointPassed {
+                        name: cue_point.name().to_string(),
+                        sample_time,
+                    });
+                }
+            }
+        }
+    }
+
+    fn notify_phrase_change(&self, previous_phrase_index: usize, previous_phrase: &Phrase) {
+        let phrase_index = self.phrase_index;
+        let sample_time = self.shifted_time(self.sample_position);
+        if phrase_index == previous_phrase_index {
+            self.notifications.publish(Notification::PhraseLooped {
+                phrase_index,
+                sample_time,
+            });
+        } else {
+            self.notifications.publish(Notification::PhraseChanged {
+                phrase_index,
+                sample_time,
+            });
+        }
+        if !self
+            .notifications
+            .has_subscribers(NotificationKind::PatternChanged)
+        {
+            return;
+        }
+        // compare slot rhythms, after continue slots took over the previous phrase's rhythms
+        let slot_rhythm = |slot: Option<&RhythmSlot>| match slot {
+            Some(RhythmSlot::Rhythm(rhythm) | RhythmSlot::FreeRunning(rhythm)) => {
+                Some(Rc::clone(rhythm))
+            }
+            _ => None,
+        };
+        let previous_slots = previous_phrase.rhythm_slots();
+        let slots = self.current_phrase().rhythm_slots();
+        for rhythm_index in 0..slots.len().max(previous_slots.len()) {
+            let changed = match (
+                slot_rhythm(previous_slots.get(rhythm_index)),
+                slot_rhythm(slots.get(rhythm_index)),
+            ) {
+                (Some(previous_rhythm), Some(rhythm)) => !Rc::ptr_eq(&previous_rhythm, &rhythm),
+                (None, None) => false,
+                _ => true,
+            };
+            if changed {
+                self.notifications.publish(Notification::PatternChanged {
+                    rhythm_index,
+                    sample_time,
+                });
+            }
+        }
+    }
+
+    fn set_shared_values(&mut self, values: &SharedValues) {
+        self.shared_values = values.clone();
+        for phrase in &mut self.phrases {
+            phrase.set_shared_values(values);
+        }
     }
 
     fn current_phrase(&self) -> &Phrase {
@@ -154,6 +2623,25 @@ impl Sequence {
         &mut self.phrases[self.phrase_index]
     }
 
+    fn seed_current_phrase(&mut self) {
+        if let Some(seed) = self.random_seed {
+            let phrase_index = self.phrase_index;
+            if let Some(phrase) = self.phrases.get_mut(phrase_index) {
+                phrase.set_seed(derived_seed(seed, phrase_index as u64));
+            }
+        }
+    }
+
+    /// Index of the phrase which plays after the current phrase: the current phrase loops
+    /// while a scene is selected.
+    fn next_phrase_index(&self) -> usize {
+        if self.scene_index.is_some() {
+            self.phrase_index
+        } else {
+            (self.phrase_index + 1) % self.phrases().len()
+        }
+    }
+
     fn samples_until_next_phrase(&self, run_until_time: u64) -> (u64, u64) {
         let phrase_length_in_samples =
             self.current_phrase().length().to_samples(&self.time_base) as SampleTime;
@@ -162,3 +2650,1334 @@ impl Sequence {
         (next_phrase_start, samples_to_run)
     }
 }
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn external_context_values() -> Result<(), String> {
+        let mut values = ExternalContextValues::new();
+        values.merge(&[("cutoff".into(), 0.5), ("gain".into(), 1.0)]);
+        values.set("cutoff", 0.25);
+        assert_eq!(values.get("cutoff"), Some(0.25));
+        assert_eq!(values.get("wurst"), None);
+        assert_eq!(values.to_string(), "cutoff=0.25;gain=1");
+        assert_eq!(
+            ExternalContextValues::try_from(values.to_string().as_str())?,
+            values
+        );
+        assert_eq!(
+            ExternalContextValues::try_from("")?,
+            ExternalContextValues::new()
+        );
+        assert!(ExternalContextValues::try_from("cutoff").is_err());
+        assert!(ExternalContextValues::try_from("=1").is_err());
+        assert!(ExternalContextValues::try_from("cutoff=x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn free_running_slots() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let new_rhythm = || -> Rc<RefCell<dyn Rhythm>> {
+            Rc::new(RefCell::new(
+                time_base
+                    .every_nth_seconds(0.75)
+                    .trigger(new_note_event(Note::C4)),
+            ))
+        };
+        let run = |sequence: &mut Sequence| {
+            let mut times = Vec::new();
+            sequence.consume_events_until_time(6000, &mut |_, time, _, _| times.push(time));
+            times
+        };
+        // regular slots restart with each phrase
+        let rhythm = new_rhythm();
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![
+                Phrase::new(time_base, vec![rhythm.clone()], BeatTimeStep::Bar(1.0)),
+                Phrase::new(time_base, vec![rhythm], BeatTimeStep::Bar(1.0)),
+            ],
+        );
+        assert_eq!(
+            run(&mut sequence),
+            vec![0, 750, 1500, 2000, 2750, 3500, 4000, 4750, 5500]
+        );
+        // free-running slots keep running
+        let rhythm = new_rhythm();
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![
+                Phrase::new(
+                    time_base,
+                    vec![RhythmSlot::FreeRunning(rhythm.clone())],
+                    BeatTimeStep::Bar(1.0),
+                ),
+                Phrase::new(
+                    time_base,
+                    vec![RhythmSlot::FreeRunning(rhythm)],
+                    BeatTimeStep::Bar(1.0),
+                ),
+            ],
+        );
+        assert_eq!(
+            run(&mut sequence),
+            vec![0, 750, 1500, 2250, 3000, 3750, 4500, 5250]
+        );
+        // and skip events while they are not playing
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![
+                Phrase::new(
+                    time_base,
+                    vec![RhythmSlot::FreeRunning(new_rhythm())],
+                    BeatTimeStep::Bar(1.0),
+                ),
+                Phrase::new(time_base, vec![RhythmSlot::Stop], BeatTimeStep::Bar(1.0)),
+            ],
+        );
+        assert_eq!(run(&mut sequence), vec![0, 750, 1500, 4500, 5250]);
+    }
+
+    #[test]
+    fn parameters() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let offset = RhythmParameter::new("offset", 0.0..=4.0, 1.0);
+        let shared_rhythm: Rc<RefCell<dyn Rhythm>> = Rc::new(RefCell::new(
+            time_base.every_nth_beat(1.0).with_parameters(vec![offset]),
+        ));
+        let volume = RhythmParameter::new("volume", 0.0..=1.0, 2.0).with_name("Volume");
+        let rhythm = time_base.every_nth_beat(1.0).with_parameters(vec![volume]);
+        let sequence = Sequence::new(
+            time_base,
+            vec![
+                Phrase::new(
+                    time_base,
+                    vec![RhythmSlot::from(Rc::clone(&shared_rhythm))],
+                    BeatTimeStep::Bar(1.0),
+                ),
+                Phrase::new(
+                    time_base,
+                    vec![RhythmSlot::from(shared_rhythm), RhythmSlot::from(rhythm)],
+                    BeatTimeStep::Bar(1.0),
+                ),
+            ],
+        );
+        let parameters = sequence.parameters();
+        assert_eq!(
+            parameters
+                .iter()
+                .map(|parameter| (
+                    parameter.id.as_str(),
+                    parameter.rhythm_index,
+                    parameter.parameter.name(),
+                    parameter.parameter.default_value()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("0.0.offset", 0, "offset", 1.0),
+                ("1.1.volume", 1, "Volume", 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let density = RhythmParameter::new("density", 0.0..=1.0, 0.5);
+        let rhythm = time_base
+            .every_nth_sixteenth(1.0)
+            .with_pattern([0.5_f32].to_pattern())
+            .with_parameters(vec![density])
+            .trigger(new_note_event(Note::C4));
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(rhythm)],
+                BeatTimeStep::Bar(1.0),
+            )],
+        );
+        let run = |sequence: &mut Sequence, run_until_time| {
+            let mut times = Vec::new();
+            sequence.consume_events_until_time(run_until_time, &mut |_, time, event, _| {
+                if event.is_some() {
+                    times.push(time);
+                }
+            });
+            times
+        };
+        run(&mut sequence, 1000);
+        // duplicates continue with the same state
+        let mut duplicate = sequence.duplicate()?;
+        assert_eq!(duplicate.sample_position(), sequence.sample_position());
+        let duplicate_times = run(&mut duplicate, 4000);
+        assert!(!duplicate_times.is_empty());
+        assert_eq!(run(&mut sequence, 4000), duplicate_times);
+        // and don't share parameter values
+        duplicate.set_parameter_value("0.0.density", 1.0)?;
+        assert_eq!(sequence.parameters()[0].parameter.value(), 0.5);
+        assert_eq!(duplicate.parameters()[0].parameter.value(), 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn render_range() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![
+                    RhythmSlot::from(
+                        time_base
+                            .every_nth_beat(1.0)
+                            .trigger(new_note_event(Note::C4)),
+                    ),
+                    RhythmSlot::from(
+                        time_base
+                            .every_nth_beat(2.0)
+                            .trigger(new_note_event(Note::E4)),
+                    ),
+                ],
+                BeatTimeStep::Bar(1.0),
+            )],
+        );
+        let events = sequence.render_range(0, 2000)?;
+        assert_eq!(
+            events.iter().map(|(time, _)| *time).collect::<Vec<_>>(),
+            vec![0, 0, 500, 1000, 1000, 1500]
+        );
+        assert_eq!(events[1].1, Event::NoteEvents(vec![new_note(Note::E4)]));
+        // ranges render from the sequence's start
+        assert_eq!(sequence.render_range(1000, 1600)?, events[3..].to_vec());
+        assert!(sequence.render_range(1000, 1000)?.is_empty());
+        // rendering does not affect playback
+        sequence.consume_events_until_time(750, &mut |_, _, _, _| {});
+        assert_eq!(sequence.render_range(0, 2000)?, events);
+        assert_eq!(sequence.sample_position(), 750);
+        let mut played = Vec::new();
+        sequence.consume_events_until_time(2000, &mut |_, time, event, _| {
+            if let Some(event) = event {
+                played.push((time, event));
+            }
+        });
+        assert_eq!(played, events[3..].to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn frozen_random_seed() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let new_phrase = || {
+            let rhythm = time_base
+                .every_nth_sixteenth(1.0)
+                .with_pattern([0.5_f32].to_pattern())
+                .trigger(new_note_event(Note::C4));
+            Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(rhythm)],
+                BeatTimeStep::Bar(1.0),
+            )
+        };
+        // returns event times of each bar, relative to the bar's start
+        let run = |sequence: &mut Sequence| {
+            let mut bars = vec![Vec::new(); 4];
+            sequence.consume_events_until_time(8000, &mut |_, time, _, _| {
+                bars[(time / 2000) as usize].push(time % 2000);
+            });
+            bars
+        };
+        let seed = [1; 32];
+        // phrases repeat their random choices with each sequence repetition
+        let mut sequence = Sequence::new(time_base, vec![new_phrase(), new_phrase()])
+            .with_frozen_random_seed(seed);
+        let bars = run(&mut sequence);
+        assert_eq!(bars[0], bars[2]);
+        assert_eq!(bars[1], bars[3]);
+        assert_ne!(bars[0], bars[1]);
+        // also after resets
+        sequence.reset();
+        assert_eq!(run(&mut sequence), bars);
+        // single phrases repeat in every bar
+        let mut sequence =
+            Sequence::new(time_base, vec![new_phrase()]).with_frozen_random_seed(seed);
+        let bars = run(&mut sequence);
+        assert!(bars.iter().all(|bar| bar == &bars[0]));
+    }
+
+    #[test]
+    fn layers() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let drums = time_base
+            .every_nth_beat(1.0)
+            .trigger(new_note_event(Note::C4));
+        let ambient = time_base
+            .every_nth_seconds(0.75)
+            .trigger(new_note_event(Note::C5));
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(drums)],
+                BeatTimeStep::Bar(1.0),
+            )],
+        )
+        .with_layer(vec![Phrase::new(
+            time_base,
+            vec![RhythmSlot::Stop, RhythmSlot::from(ambient)],
+            BeatTimeStep::Bar(2.0),
+        )]);
+        assert_eq!(sequence.layers().len(), 1);
+        assert_eq!(sequence.phrase_rhythm_slot_count(), 3);
+        let run = |sequence: &mut Sequence| {
+            let mut events = Vec::new();
+            sequence.consume_events_until_time(2000, &mut |rhythm_index, time, _, _| {
+                events.push((rhythm_index, time));
+            });
+            events
+        };
+        let events = run(&mut sequence);
+        assert_eq!(
+            events,
+            vec![
+                (0, 0),
+                (2, 0),
+                (0, 500),
+                (2, 750),
+                (0, 1000),
+                (0, 1500),
+                (2, 1500)
+            ]
+        );
+        // layers get reset along with the sequence
+        sequence.reset();
+        assert_eq!(run(&mut sequence), events);
+        // and get seeked too
+        sequence.skip_events_until_time(3000);
+        let mut layer_times = Vec::new();
+        sequence.consume_events_until_time(4000, &mut |rhythm_index, time, _, _| {
+            if rhythm_index == 2 {
+                layer_times.push(time);
+            }
+        });
+        assert_eq!(layer_times, vec![3000, 3750]);
+    }
+
+    #[test]
+    fn cue_points() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let new_phrase = |note: Note| {
+            let rhythm = time_base.every_nth_beat(1.0).trigger(new_note_event(note));
+            Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(rhythm)],
+                BeatTimeStep::Bar(1.0),
+            )
+        };
+        let mut sequence =
+            Sequence::new(time_base, vec![new_phrase(Note::C4), new_phrase(Note::E4)])
+                .with_cue_points(vec![
+                    CuePoint::new("verse", 1.0),
+                    CuePoint::new("intro", 0.0),
+                ]);
+        assert_eq!(
+            sequence
+                .cue_points()
+                .iter()
+                .map(CuePoint::name)
+                .collect::<Vec<_>>(),
+            vec!["intro", "verse"]
+        );
+        assert_eq!(sequence.song_length(), 4000);
+        let passed_cue_points = Rc::new(RefCell::new(Vec::new()));
+        sequence.set_cue_point_callback({
+            let passed_cue_points = passed_cue_points.clone();
+            move |cue_point, time| {
+                passed_cue_points
+                    .borrow_mut()
+                    .push((cue_point.name().to_string(), time));
+            }
+        });
+        let run = |sequence: &mut Sequence, run_until_time: SampleTime| {
+            let mut events = Vec::new();
+            sequence.consume_events_until_time(run_until_time, &mut |_, time, event, _| {
+                if let Some(Event::NoteEvents(notes)) = event {
+                    events.push((time, notes[0].as_ref().map(|n| n.note)));
+                }
+            });
+            events
+        };
+        assert_eq!(run(&mut sequence, 4250).len(), 9);
+        assert_eq!(
+            passed_cue_points.take(),
+            vec![
+                ("intro".to_string(), 0),
+                ("verse".to_string(), 2000),
+                ("intro".to_string(), 4000)
+            ]
+        );
+        // jumps are quantized
+        assert!(sequence
+            .jump_to_cue_point("wurst", BeatTimeStep::Beats(1.0))
+            .is_err());
+        sequence
+            .jump_to_cue_point("verse", BeatTimeStep::Beats(1.0))
+            .unwrap();
+        assert_eq!(sequence.pending_cue_point_jump(), Some(("verse", 4500)));
+        assert_eq!(
+            run(&mut sequence, 6000),
+            vec![
+                (4500, Some(Note::E4)),
+                (5000, Some(Note::E4)),
+                (5500, Some(Note::E4))
+            ]
+        );
+        assert_eq!(sequence.pending_cue_point_jump(), None);
+        assert_eq!(passed_cue_points.take(), vec![("verse".to_string(), 4500)]);
+        assert_eq!(sequence.sample_position(), 6000);
+        assert_eq!(sequence.song_position(), 3500);
+        // and continue with the next phrase
+        assert_eq!(run(&mut sequence, 6750), vec![(6500, Some(Note::C4))]);
+        assert_eq!(passed_cue_points.take(), vec![("intro".to_string(), 6500)]);
+    }
+
+    #[test]
+    fn scenes() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let new_phrase = |note: Note| {
+            let gain = RhythmParameter::new("gain", 0.0..=1.0, 1.0);
+            let rhythm = time_base
+                .every_nth_beat(1.0)
+                .with_parameters(vec![gain])
+                .trigger(new_note_event(note));
+            Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(rhythm)],
+                BeatTimeStep::Bar(1.0),
+            )
+        };
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![
+                new_phrase(Note::C4),
+                new_phrase(Note::E4),
+                new_phrase(Note::G4),
+            ],
+        )
+        .with_scenes(vec![
+            Scene::new("a", 0).with_parameter_values(vec![("0.0.gain".to_string(), 0.5)]),
+            Scene::new("b", 2),
+            Scene::new("c", 3),
+            Scene::new("d", 1).with_parameter_values(vec![("1.0.wurst".to_string(), 0.5)]),
+        ]);
+        assert_eq!(sequence.scenes().len(), 4);
+        assert!(sequence.current_scene().is_none());
+        assert!(sequence
+            .select_scene("wurst", BeatTimeStep::Beats(1.0))
+            .is_err());
+        assert!(sequence
+            .select_scene("c", BeatTimeStep::Beats(1.0))
+            .is_err());
+        assert!(sequence
+            .select_scene("d", BeatTimeStep::Beats(1.0))
+            .is_err());
+        assert_eq!(sequence.pending_scene_switch(), None);
+
+        let run = |sequence: &mut Sequence, run_until_time: SampleTime| {
+            let mut events = Vec::new();
+            sequence.consume_events_until_time(run_until_time, &mut |_, time, event, _| {
+                if let Some(Event::NoteEvents(notes)) = event {
+                    events.push((time, notes[0].as_ref().map(|n| n.note)));
+                }
+            });
+            events
+        };
+        assert_eq!(run(&mut sequence, 1250).len(), 3);
+        // switches are quantized and loop the scene's phrase
+        sequence
+            .select_scene("b", BeatTimeStep::Beats(1.0))
+            .unwrap();
+        assert_eq!(sequence.pending_scene_switch(), Some(("b", 1500)));
+        let events = run(&mut sequence, 6000);
+        assert_eq!(events.len(), 9);
+        assert_eq!(events[0], (1500, Some(Note::G4)));
+        assert!(events.iter().all(|(_, note)| *note == Some(Note::G4)));
+        assert_eq!(sequence.pending_scene_switch(), None);
+        assert_eq!(sequence.current_scene().map(Scene::name), Some("b"));
+        // and apply parameter values
+        sequence.select_scene("a", BeatTimeStep::Bar(1.0)).unwrap();
+        assert_eq!(sequence.pending_scene_switch(), Some(("a", 6000)));
+        assert_eq!(
+            run(&mut sequence, 7000),
+            vec![(6000, Some(Note::C4)), (6500, Some(Note::C4))]
+        );
+        assert_eq!(
+            sequence
+                .parameters()
+                .iter()
+                .find(|parameter| parameter.id == "0.0.gain")
+                .map(|parameter| parameter.parameter.value()),
+            Some(0.5)
+        );
+        // resets to the start release scenes
+        sequence.select_scene("b", BeatTimeStep::Bar(1.0)).unwrap();
+        sequence.reset_to_start();
+        assert!(sequence.current_scene().is_none());
+        assert_eq!(sequence.pending_scene_switch(), None);
+    }
+
+    #[test]
+    fn notifications() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let new_phrase = || {
+            let rhythm = time_base
+                .every_nth_beat(1.0)
+                .trigger(new_note_event(Note::C4))
+                .with_parameters(vec![RhythmParameter::new("volume", 0.0..=1.0, 1.0)]);
+            Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(rhythm)],
+                BeatTimeStep::Bar(1.0),
+            )
+        };
+        let mut sequence = Sequence::new(time_base, vec![new_phrase(), new_phrase()])
+            .with_cue_points(vec![CuePoint::new("verse", 1.0)]);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        sequence
+            .notifications()
+            .subscribe_channel(&NotificationKind::ALL, sender);
+        let tempo_changes = Rc::new(RefCell::new(Vec::new()));
+        sequence
+            .notifications()
+            .subscribe(&[NotificationKind::TempoChanged], {
+                let tempo_changes = Rc::clone(&tempo_changes);
+                move |notification| tempo_changes.borrow_mut().push(notification.clone())
+            });
+
+        sequence.consume_events_until_time(4250, &mut |_, _, _, _| {});
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                Notification::PhraseChanged {
+                    phrase_index: 1,
+                    sample_time: 2000
+                },
+                Notification::PatternChanged {
+                    rhythm_index: 0,
+                    sample_time: 2000
+                },
+                Notification::CuePointPassed {
+                    name: "verse".to_string(),
+                    sample_time: 2000
+                },
+                Notification::PhraseChanged {
+                    phrase_index: 0,
+                    sample_time: 4000
+                },
+                Notification::PatternChanged {
+                    rhythm_index: 0,
+                    sample_time: 4000
+                },
+            ]
+        );
+
+        // host changes
+        sequence.set_parameter_value("0.0.volume", 2.0)?;
+        sequence.set_time_base(&BeatTimeBase {
+            beats_per_min: 100.0,
+            ..time_base
+        });
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![
+                Notification::ParameterChanged {
+                    id: "0.0.volume".to_string(),
+                    value: 1.0
+                },
+                Notification::TempoChanged {
+                    beats_per_min: 100.0
+                },
+            ]
+        );
+        assert_eq!(tempo_changes.borrow().len(), 1);
+
+        // duplicates don't notify subscribers of the original sequence
+        sequence
+            .duplicate()?
+            .consume_events_until_time(10000, &mut |_, _, _, _| {});
+        assert_eq!(receiver.try_iter().count(), 0);
+
+        // single phrases loop
+        let mut sequence = Sequence::new(time_base, vec![new_phrase()]);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        sequence
+            .notifications()
+            .subscribe_channel(&[NotificationKind::PhraseLooped], sender);
+        sequence.consume_events_until_time(2001, &mut |_, _, _, _| {});
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            vec![Notification::PhraseLooped {
+                phrase_index: 0,
+                sample_time: 2000
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scheduled_parameter_changes() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let rhythm = time_base
+            .every_nth_beat(1.0)
+            .trigger(new_note_event(Note::C4))
+            .with_echo(EventEcho::new().with_delay(0.5).with_repeats(0));
+        let phrase = Phrase::new(
+            time_base,
+            vec![RhythmSlot::from(rhythm)],
+            BeatTimeStep::Bar(1.0),
+        );
+        let mut sequence = Sequence::new(time_base, vec![phrase]);
+        let run = |sequence: &mut Sequence, run_until_time: SampleTime| {
+            let mut events = Vec::new();
+            sequence.consume_events_until_time(run_until_time, &mut |_, time, event, _| {
+                if let Some(Event::NoteEvents(note_events)) = event {
+                    events.push((time, note_events.len()));
+                }
+            });
+            events
+        };
+        assert_eq!(run(&mut sequence, 500), vec![(0, 1)]);
+        // changes are validated
+        assert!(sequence
+            .schedule_parameter_changes(&[("0.0.wurst", 1.0)], ParameterChangeTime::Now)
+            .is_err());
+        assert!(sequence
+            .schedule_parameter_changes(&[("0.1.echo.repeats", 1.0)], ParameterChangeTime::Now)
+            .is_err());
+        assert_eq!(sequence.pending_parameter_changes(), 0);
+        // and get applied at the next bar
+        assert_eq!(
+            sequence.schedule_parameter_changes(
+                &[("0.0.echo.repeats", 1.0)],
+                ParameterChangeTime::Next(BeatTimeStep::Bar(1.0))
+            ),
+            Ok(2000)
+        );
+        assert_eq!(
+            run(&mut sequence, 3000),
+            vec![
+                (500, 1),
+                (1000, 1),
+                (1500, 1),
+                (2000, 1),
+                (2250, 2),
+                (2500, 1),
+                (2750, 2)
+            ]
+        );
+        assert_eq!(sequence.pending_parameter_changes(), 0);
+        assert_eq!(
+            sequence
+                .parameters()
+                .iter()
+                .find(|parameter| parameter.id == "0.0.echo.repeats")
+                .map(|parameter| parameter.parameter.value()),
+            Some(1.0)
+        );
+        // host changes are not reported back
+        assert!(sequence.take_parameter_changes().is_empty());
+        // at exact sample times
+        sequence
+            .schedule_parameter_changes(&[("0.0.echo.repeats", 0.0)], ParameterChangeTime::At(3500))
+            .unwrap();
+        assert_eq!(
+            run(&mut sequence, 4000),
+            vec![(3000, 1), (3250, 2), (3500, 1)]
+        );
+        // or immediately
+        assert_eq!(
+            sequence.set_parameter_value("0.0.echo.repeats", 5.0),
+            Ok(5.0)
+        );
+        assert!(sequence
+            .set_parameter_value("1.0.echo.repeats", 1.0)
+            .is_err());
+        assert_eq!(run(&mut sequence, 4500), vec![(4000, 1), (4250, 2)]);
+    }
+
+    #[test]
+    fn parameter_ramps() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let rhythm = time_base
+            .every_nth_beat(1.0)
+            .trigger(new_note_event(Note::C4))
+            .with_parameters(vec![RhythmParameter::new("cutoff", 0.0..=1000.0, 100.0)]);
+        let phrase = Phrase::new(
+            time_base,
+            vec![RhythmSlot::from(rhythm)],
+            BeatTimeStep::Bar(1.0),
+        );
+        let mut sequence = Sequence::new(time_base, vec![phrase]);
+        let value_at = |sequence: &mut Sequence, sample_time: SampleTime| {
+            sequence.consume_events_until_time(sample_time + 1, &mut |_, _, _, _| {});
+            sequence
+                .parameters()
+                .iter()
+                .find(|parameter| parameter.id == "0.0.cutoff")
+                .map(|parameter| parameter.parameter.value())
+                .unwrap()
+        };
+        assert!(sequence
+            .ramp_parameter_value(
+                "0.0.wurst",
+                1.0,
+                &ParameterRamp::new(1.0),
+                ParameterChangeTime::Now
+            )
+            .is_err());
+        // linear
+        let ramp = ParameterRamp::new(1.0).with_resolution(0.25);
+        assert_eq!(
+            sequence.ramp_parameter_value(
+                "0.0.cutoff",
+                500.0,
+                &ramp,
+                ParameterChangeTime::At(1000)
+            ),
+            Ok(1000)
+        );
+        assert_eq!(sequence.pending_parameter_changes(), 4);
+        assert_eq!(value_at(&mut sequence, 1000), 100.0);
+        assert_eq!(value_at(&mut sequence, 1250), 200.0);
+        assert_eq!(value_at(&mut sequence, 1500), 300.0);
+        assert_eq!(value_at(&mut sequence, 2000), 500.0);
+        // exponential
+        let ramp = ramp.with_curve(ParameterRampCurve::Exponential);
+        sequence
+            .ramp_parameter_value("0.0.cutoff", 125.0, &ramp, ParameterChangeTime::At(2500))
+            .unwrap();
+        assert!((value_at(&mut sequence, 3000) - 250.0).abs() < 1e-9);
+        // new ramps replace pending ones
+        sequence
+            .ramp_parameter_value(
+                "0.0.cutoff",
+                1000.0,
+                &ParameterRamp::new(0.0),
+                ParameterChangeTime::Now,
+            )
+            .unwrap();
+        assert_eq!(sequence.pending_parameter_changes(), 1);
+        assert_eq!(value_at(&mut sequence, 3100), 1000.0);
+        // long ramps use a limited number of steps
+        sequence
+            .ramp_parameter_value(
+                "0.0.cutoff",
+                0.0,
+                &ParameterRamp::new(1.0e6).with_resolution(0.001),
+                ParameterChangeTime::At(4000),
+            )
+            .unwrap();
+        assert_eq!(
+            sequence.pending_parameter_changes(),
+            MAX_PARAMETER_RAMP_STEPS
+        );
+    }
+
+    #[test]
+    fn injected_events() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(
+                    time_base
+                        .every_nth_beat(1.0)
+                        .trigger(new_note_event(Note::C4)),
+                )],
+                BeatTimeStep::Bar(1.0),
+            )],
+        )
+        .with_volume_curve(VolumeCurve::new().with_range(0.0, 0.5));
+        let run = |sequence: &mut Sequence, run_until_time| {
+            let mut events = Vec::new();
+            sequence.consume_events_until_time(run_until_time, &mut |index, time, event, _| {
+                if let Some(Event::NoteEvents(notes)) = event {
+                    let note = notes[0].as_ref().unwrap();
+                    events.push((index, time, note.note, note.volume));
+                }
+            });
+            events
+        };
+        let injected_note = |note: Note| Event::NoteEvents(vec![new_note(note)]);
+        assert_eq!(
+            sequence.inject_event(1, 750, injected_note(Note::E4), 100),
+            750
+        );
+        assert_eq!(
+            sequence.inject_event(1, 250, injected_note(Note::D4), 100),
+            250
+        );
+        assert_eq!(
+            sequence.inject_event(1, 2000, injected_note(Note::G4), 100),
+            2000
+        );
+        assert_eq!(sequence.pending_injected_events(), 3);
+        // injected events get merged in time order and pass the volume curve
+        assert_eq!(
+            run(&mut sequence, 1000),
+            vec![
+                (0, 0, Note::C4, 0.5),
+                (1, 250, Note::D4, 0.5),
+                (0, 500, Note::C4, 0.5),
+                (1, 750, Note::E4, 0.5)
+            ]
+        );
+        assert_eq!(sequence.pending_injected_events(), 1);
+        // past events get emitted right away
+        assert_eq!(
+            sequence.inject_event(2, 0, injected_note(Note::A4), 100),
+            1000
+        );
+        assert_eq!(
+            run(&mut sequence, 1500),
+            vec![(2, 1000, Note::A4, 0.5), (0, 1000, Note::C4, 0.5)]
+        );
+        // skipped events are dropped
+        sequence.skip_events_until_time(2500);
+        assert_eq!(sequence.pending_injected_events(), 0);
+        sequence.inject_event(1, 3000, injected_note(Note::B4), 100);
+        sequence.reset();
+        assert_eq!(sequence.pending_injected_events(), 0);
+        // quantized events
+        let quantize = TimingQuantize::new(BeatTimeStep::Beats(1.0)).with_strength(0.5);
+        assert_eq!(
+            sequence.inject_quantized_event(1, 2100, injected_note(Note::B4), 100, &quantize),
+            2050
+        );
+        // events in existing slots pass the slot rhythm's transforms
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(
+                    time_base
+                        .every_nth_bar(1.0)
+                        .with_instrument(InstrumentId::from(3))
+                        .with_transposer(EventTransposer::new().with_transpose(12))
+                        .trigger(new_note_event(Note::C4)),
+                )],
+                BeatTimeStep::Bar(1.0),
+            )],
+        );
+        sequence.inject_event(0, 100, injected_note(Note::D4), 100);
+        sequence.inject_event(1, 200, injected_note(Note::D4), 100);
+        let mut events = Vec::new();
+        sequence.consume_events_until_time(1000, &mut |index, time, event, _| {
+            if let Some(Event::NoteEvents(notes)) = event {
+                let note = notes[0].as_ref().unwrap();
+                events.push((index, time, note.note, note.instrument));
+            }
+        });
+        assert_eq!(
+            events,
+            vec![
+                (0, 0, Note::C5, Some(InstrumentId::from(3))),
+                (0, 100, Note::D5, Some(InstrumentId::from(3))),
+                (1, 200, Note::D4, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn event_limit() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![
+                    time_base
+                        .every_nth_sixteenth(1.0)
+                        .trigger(new_note_event(Note::C4)),
+                    time_base
+                        .every_nth_sixteenth(1.0)
+                        .trigger(new_note_event(Note::E4)),
+                ],
+                BeatTimeStep::Bar(1.0),
+            )],
+        )
+        .with_event_limit(EventLimit::new().with_max_slot_events(2));
+        let run = |sequence: &mut Sequence, run_until_time| {
+            let mut events = 0;
+            sequence.consume_events_until_time(run_until_time, &mut |_, _, event, _| {
+                if event.is_some() {
+                    events += 1;
+                }
+            });
+            events
+        };
+        // slot limits apply per second
+        assert_eq!(run(&mut sequence, 2000), 8);
+        assert_eq!(
+            sequence.take_warnings(),
+            vec![
+                Warning::new(
+                    WarningKind::Dropped,
+                    "rhythm slot 0 exceeded the limit of 2 events per second: events got dropped"
+                ),
+                Warning::new(
+                    WarningKind::Dropped,
+                    "rhythm slot 1 exceeded the limit of 2 events per second: events got dropped"
+                ),
+            ]
+        );
+        // global limits apply to all slots
+        sequence.set_event_limit(EventLimit::new().with_max_events(3));
+        assert_eq!(run(&mut sequence, 3000), 3);
+        assert_eq!(sequence.take_warnings().len(), 1);
+        // beat windows
+        sequence.set_event_limit(
+            EventLimit::new()
+                .with_window(EventLimitWindow::Beat)
+                .with_max_events(3),
+        );
+        assert_eq!(run(&mut sequence, 4000), 6);
+        // note-offs are never dropped
+        for _ in 0..10 {
+            sequence.inject_event(0, 4500, Event::NoteEvents(vec![new_note(Note::OFF)]), 0);
+        }
+        assert_eq!(run(&mut sequence, 5000), 16);
+        // limited events keep their note-offs
+        sequence.set_event_limit(EventLimit::new().with_max_slot_events(3));
+        for note in 0..10_u8 {
+            let note_events = vec![new_note(Note::from(60 + note)), new_note(Note::OFF)];
+            sequence.inject_event(2, 5600, Event::NoteEvents(note_events), 0);
+        }
+        let (mut note_ons, mut note_offs) = (0, 0);
+        sequence.consume_events_until_time(6000, &mut |index, _, event, _| {
+            if let (2, Some(Event::NoteEvents(note_events))) = (index, event) {
+                for note_event in note_events.iter().flatten() {
+                    if note_event.note.is_note_on() {
+                        note_ons += 1;
+                    } else {
+                        note_offs += 1;
+                    }
+                }
+            }
+        });
+        assert_eq!((note_ons, note_offs), (3, 10));
+    }
+
+    #[test]
+    fn event_merge() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![
+                    time_base.every_nth_beat(1.0).trigger(new_note_event((
+                        Note::C4,
+                        None::<InstrumentId>,
+                        0.5,
+                    ))),
+                    time_base.every_nth_beat(1.0).trigger(new_note_event((
+                        Note::C4,
+                        None::<InstrumentId>,
+                        0.25,
+                    ))),
+                    time_base.every_nth_beat(2.0).trigger(new_note_event((
+                        Note::E4,
+                        None::<InstrumentId>,
+                        1.0,
+                    ))),
+                ],
+                BeatTimeStep::Bar(1.0),
+            )],
+        );
+        let run = |sequence: &mut Sequence, run_until_time| {
+            let mut notes = Vec::new();
+            sequence.consume_events_until_time(run_until_time, &mut |index, time, event, _| {
+                if let Some(Event::NoteEvents(note_events)) = event {
+                    for note_event in note_events.into_iter().flatten() {
+                        notes.push((index, time, note_event.note, note_event.volume));
+                    }
+                }
+            });
+            notes
+        };
+        // merging is disabled by default
+        assert_eq!(sequence.event_merge(), EventMerge::Off);
+        assert_eq!(run(&mut sequence, 500).len(), 3);
+        // drop duplicates
+        sequence.set_event_merge(EventMerge::DropDuplicates);
+        assert_eq!(
+            run(&mut sequence, 1500),
+            vec![
+                (0, 500, Note::C4, 0.5),
+                (0, 1000, Note::C4, 0.5),
+                (2, 1000, Note::E4, 1.0)
+            ]
+        );
+        // sum volumes
+        sequence.set_event_merge(EventMerge::SumVolumes { max_volume: 0.6 });
+        assert_eq!(run(&mut sequence, 2000), vec![(0, 1500, Note::C4, 0.6)]);
+        sequence.set_event_merge(EventMerge::SumVolumes { max_volume: 1.0 });
+        assert_eq!(
+            run(&mut sequence, 2500),
+            vec![(0, 2000, Note::C4, 0.75), (2, 2000, Note::E4, 1.0)]
+        );
+    }
+
+    #[test]
+    fn event_ordering() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let new_sequence = |ordering: EventOrdering| {
+            let mut sequence = Sequence::new(
+                time_base,
+                vec![Phrase::new(
+                    time_base,
+                    vec![
+                        time_base
+                            .every_nth_beat(1.0)
+                            .trigger(new_note_event(Note::E4)),
+                        time_base
+                            .every_nth_beat(1.0)
+                            .trigger(new_parameter_change_event(ParameterId::from(1), 0.5)),
+                        time_base
+                            .every_nth_beat(1.0)
+                            .trigger(new_note_event(Note::C4)),
+                    ],
+                    BeatTimeStep::Bar(1.0),
+                )],
+            )
+            .with_event_ordering(ordering);
+            sequence.inject_event(4, 0, Event::NoteEvents(vec![new_note(Note::G4)]), 100);
+            sequence
+        };
+        let run = |sequence: &mut Sequence, run_until_time, block_size| {
+            let mut events = Vec::new();
+            let mut block_end = 0;
+            while block_end < run_until_time {
+                block_end = (block_end + block_size).min(run_until_time);
+                sequence.consume_events_until_time(block_end, &mut |index, time, event, _| {
+                    let is_note = matches!(event, Some(Event::NoteEvents(_)));
+                    events.push((index, time, is_note));
+                });
+            }
+            events
+        };
+        // parameter changes come first by default
+        let mut sequence = new_sequence(EventOrdering::default());
+        assert_eq!(
+            sequence.event_ordering(),
+            EventOrdering::ParameterChangesFirst
+        );
+        assert_eq!(
+            run(&mut sequence, 500, 500),
+            vec![(1, 0, false), (4, 0, true), (0, 0, true), (2, 0, true)]
+        );
+        // emitted order
+        let mut sequence = new_sequence(EventOrdering::Emitted);
+        assert_eq!(
+            run(&mut sequence, 500, 500),
+            vec![(4, 0, true), (0, 0, true), (1, 0, false), (2, 0, true)]
+        );
+        // rhythm index order
+        let mut sequence = new_sequence(EventOrdering::ByRhythmIndex);
+        assert_eq!(
+            run(&mut sequence, 500, 500),
+            vec![(1, 0, false), (0, 0, true), (2, 0, true), (4, 0, true)]
+        );
+        // orders do not depend on block sizes
+        for ordering in [
+            EventOrdering::Emitted,
+            EventOrdering::ParameterChangesFirst,
+            EventOrdering::ByRhythmIndex,
+        ] {
+            let events = run(&mut new_sequence(ordering), 2000, 2000);
+            assert_eq!(events.len(), 13);
+            assert_eq!(run(&mut new_sequence(ordering), 2000, 1), events);
+            assert_eq!(run(&mut new_sequence(ordering), 2000, 333), events);
+        }
+    }
+
+    #[test]
+    fn event_history() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![
+                    time_base
+                        .every_nth_sixteenth(1.0)
+                        .trigger(new_note_event(Note::C4)),
+                    time_base
+                        .every_nth_sixteenth(1.0)
+                        .trigger(new_note_event(Note::E4)),
+                ],
+                BeatTimeStep::Bar(1.0),
+            )],
+        )
+        .with_event_history(4);
+        let mut emitted_events = Vec::new();
+        sequence.consume_events_until_time(1000, &mut |rhythm_index, time, event, _| {
+            if event.is_some() {
+                emitted_events.push((rhythm_index, time));
+            }
+        });
+        // history does not consume events
+        assert_eq!(emitted_events.len(), 16);
+        let history = sequence.event_history().unwrap();
+        assert_eq!(history.slot_count(), 2);
+        assert_eq!(
+            history.events(0).map(|item| item.time).collect::<Vec<_>>(),
+            vec![500, 625, 750, 875]
+        );
+        assert_eq!(history.events_since(1, 750).count(), 2);
+        assert_eq!(
+            history.last_event(1).map(|item| item.event.clone()),
+            Some(Event::NoteEvents(vec![new_note(Note::E4)]))
+        );
+        // reset clears the history
+        sequence.reset();
+        assert_eq!(sequence.event_history().unwrap().events(0).count(), 0);
+        assert!(Sequence::new(time_base, vec![]).event_history().is_none());
+    }
+
+    #[test]
+    fn performance_macros() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let volume = RhythmParameter::new("volume", 0.0..=1.0, 1.0);
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![
+                    time_base
+                        .every_nth_beat(1.0)
+                        .with_parameters(vec![volume])
+                        .trigger(new_note_event(Note::C4)),
+                    time_base
+                        .every_nth_beat(1.0)
+                        .trigger(new_note_event(Note::E4)),
+                ],
+                BeatTimeStep::Bar(1.0),
+            )],
+        );
+        let performance_macro = PerformanceMacro::new(
+            "morph",
+            vec![
+                MacroTarget::parameter("0.0.volume", 0.2, 1.0),
+                MacroTarget::mute(1, 0.0, 0.5),
+                MacroTarget::context("intensity", 0.0, 10.0),
+            ],
+        );
+        // invalid targets
+        assert!(sequence
+            .add_macro(PerformanceMacro::new(
+                "invalid",
+                vec![MacroTarget::parameter("0.0.unknown", 0.0, 1.0)]
+            ))
+            .is_err());
+        assert!(sequence
+            .add_macro(PerformanceMacro::new(
+                "invalid",
+                vec![MacroTarget::mute(2, 0.0, 1.0)]
+            ))
+            .is_err());
+        assert!(sequence.macros().is_empty());
+        // adding applies the current value
+        sequence.add_macro(performance_macro)?;
+        assert_eq!(sequence.parameters()[0].parameter.value(), 0.2);
+        assert_eq!(sequence.external_context().get("intensity"), Some(0.0));
+        assert!(sequence.is_slot_muted(1));
+        let run = |sequence: &mut Sequence, run_until_time| {
+            let mut slot_events = [0, 0];
+            sequence.consume_events_until_time(run_until_time, &mut |rhythm_index, _, event, _| {
+                if event.is_some() {
+                    slot_events[rhythm_index] += 1;
+                }
+            });
+            slot_events
+        };
+        assert_eq!(run(&mut sequence, 1000), [2, 0]);
+        // changing the value morphs all targets
+        assert_eq!(sequence.set_macro_value("morph", 2.0), Ok(1.0));
+        assert_eq!(sequence.performance_macro("morph").unwrap().value(), 1.0);
+        assert_eq!(sequence.parameters()[0].parameter.value(), 1.0);
+        assert_eq!(sequence.external_context().get("intensity"), Some(10.0));
+        assert!(!sequence.is_slot_muted(1));
+        assert_eq!(run(&mut sequence, 2000), [2, 2]);
+        assert!(sequence.set_macro_value("unknown", 1.0).is_err());
+        // removing unmutes slots
+        sequence.set_macro_value("morph", 0.0)?;
+        sequence.remove_macro("morph");
+        assert!(!sequence.is_slot_muted(1));
+        assert!(sequence.performance_macro("morph").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn memory_usage() -> Result<(), String> {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let rhythm = RhythmSlot::from(
+            time_base
+                .every_nth_bar(1.0)
+                .trigger(new_cycle_event("c4 [e4 g4]")?),
+        );
+        // shared rhythms are counted once
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![
+                Phrase::new(time_base, vec![rhythm.clone()], BeatTimeStep::Bar(1.0)),
+                Phrase::new(time_base, vec![rhythm], BeatTimeStep::Bar(1.0)),
+            ],
+        );
+        let usage = sequence.memory_usage();
+        assert_eq!(usage.lua_heap, 0);
+        assert!(usage.cycles > 0);
+        assert_eq!(sequence.phrases()[0].memory_usage().cycles, usage.cycles);
+
+        for _ in 0..16 {
+            sequence.inject_event(1, 5000, Event::NoteEvents(vec![new_note(Note::C4)]), 0);
+        }
+        assert!(sequence.memory_usage().events > usage.events);
+        sequence.cancel_injected_events();
+        sequence.trim_memory();
+        assert_eq!(sequence.memory_usage(), usage);
+        Ok(())
+    }
+
+    #[test]
+    fn volume_curve() {
+        let curve = VolumeCurve::new();
+        assert!(curve.is_identity());
+        assert_eq!(curve.apply(0.25), 0.25);
+        assert_eq!(curve.apply(2.0), 2.0);
+        let curve = VolumeCurve::new().with_compression(0.5);
+        assert!((curve.apply(0.25) - 0.5).abs() < 1e-6);
+        assert_eq!(curve.apply(1.0), 1.0);
+        let curve = VolumeCurve::new().with_compression(-0.5);
+        assert!((curve.apply(0.5) - 0.25).abs() < 1e-6);
+        let curve = VolumeCurve::new().with_range(0.1, 0.8);
+        assert_eq!(curve.range(), (0.1, 0.8));
+        assert_eq!(curve.apply(0.0), 0.1);
+        assert_eq!(curve.apply(2.0), 0.8);
+
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let mut sequence = Sequence::new(
+            time_base,
+            vec![Phrase::new(
+                time_base,
+                vec![RhythmSlot::from(time_base.every_nth_beat(1.0).trigger(
+                    new_note_event((Note::C4, None::<InstrumentId>, 0.25)),
+                ))],
+                BeatTimeStep::Bar(1.0),
+            )],
+        )
+        .with_volume_curve(
+            VolumeCurve::new()
+                .with_compression(0.5)
+                .with_range(0.0, 0.4),
+        );
+        let run = |sequence: &mut Sequence, run_until_time| {
+            let mut volumes = Vec::new();
+            sequence.consume_events_until_time(run_until_time, &mut |_, _, event, _| {
+                if let Some(Event::NoteEvents(notes)) = event {
+                    volumes.extend(notes.iter().flatten().map(|note| note.volume));
+                }
+            });
+            volumes
+        };
+        assert_eq!(run(&mut sequence, 1000), vec![0.4, 0.4]);
+        // curves can be changed live
+        sequence.set_volume_curve(VolumeCurve::new());
+        assert_eq!(run(&mut sequence, 2000), vec![0.25, 0.25]);
+    }
+}
@@ -0,0 +1,81 @@
+//! Shared key-value store for the rhythms of a `Sequence`.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::event::{EventData, EventDataValue};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Small key-value store which is shared by all rhythms in a [`Sequence`](`crate::Sequence`),
+/// e.g. to let patterns in different slots communicate with each other via counters and flags:
+/// a lead rhythm may only play, when a drum rhythm sets a flag.
+///
+/// Clones of the store share their values. Scripted callbacks access the values as
+/// `context.shared`. Values are read and written while rhythms generate their events, which
+/// happens in order of the rhythm's pulse times, so changes are visible to all pulses which
+/// get generated afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct SharedValues {
+    values: Rc<RefCell<EventData>>,
+}
+
+impl SharedValues {
+    /// Create a new empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a deep copy of the store, which no longer shares its values with this store.
+    pub fn duplicate(&self) -> Self {
+        let values = Rc::new(RefCell::new(self.values.borrow().clone()));
+        Self { values }
+    }
+
+    /// Get a copy of the value with the given key, if it exists.
+    pub fn get(&self, key: &str) -> Option<EventDataValue> {
+        self.values.borrow().get(key).cloned()
+    }
+
+    /// Set or replace the value with the given key.
+    pub fn set<K: Into<String>, V: Into<EventDataValue>>(&self, key: K, value: V) {
+        self.values.borrow_mut().insert(key.into(), value.into());
+    }
+
+    /// Remove the value with the given key. Returns the removed value, if it existed.
+    pub fn remove(&self, key: &str) -> Option<EventDataValue> {
+        self.values.borrow_mut().remove(key)
+    }
+
+    /// Remove all values.
+    pub fn clear(&self) {
+        self.values.borrow_mut().clear();
+    }
+
+    /// Snapshot of all current values.
+    pub fn values(&self) -> EventData {
+        self.values.borrow().clone()
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shared_values() {
+        let values = SharedValues::new();
+        let shared = values.clone();
+        let duplicate = values.duplicate();
+        shared.set("count", 2_i64);
+        shared.set("flag", true);
+        assert_eq!(values.get("count"), Some(EventDataValue::Integer(2)));
+        assert_eq!(values.get("flag"), Some(EventDataValue::Boolean(true)));
+        assert_eq!(duplicate.get("count"), None);
+        assert_eq!(values.remove("flag"), Some(EventDataValue::Boolean(true)));
+        assert_eq!(shared.values().len(), 1);
+        shared.clear();
+        assert!(values.values().is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+//! Event statistics and density metrics of rendered sequences and rhythms.
+
+use crate::{
+    bounce::{Stem, StemEvent},
+    phrase::RhythmIndex,
+    BeatTimeBase, Event, Note, SampleTime, Sequence,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Number of bins in [`EventStats::velocity_histogram`].
+pub const VELOCITY_HISTOGRAM_BINS: usize = 10;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Statistics of a list of events within a fixed time window, e.g. to drive activity meters
+/// or auto-mixing decisions in hosts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventStats {
+    beats: f64,
+    note_count: usize,
+    note_off_count: usize,
+    parameter_change_count: usize,
+    velocity_histogram: [usize; VELOCITY_HISTOGRAM_BINS],
+    pitch_range: Option<(Note, Note)>,
+}
+
+impl EventStats {
+    /// Create new, empty stats for a time window with the given length in beats.
+    pub fn new(beats: f64) -> Self {
+        Self {
+            beats,
+            note_count: 0,
+            note_off_count: 0,
+            parameter_change_count: 0,
+            velocity_histogram: [0; VELOCITY_HISTOGRAM_BINS],
+            pitch_range: None,
+        }
+    }
+
+    /// Create stats from the given bounced events in a time window with the given length
+    /// in beats.
+    pub fn from_events(events: &[StemEvent], beats: f64) -> Self {
+        let mut stats = Self::new(beats);
+        for stem_event in events {
+            stats.add(&stem_event.event);
+        }
+        stats
+    }
+
+    /// Length of the stats' time window in beats.
+    pub fn beats(&self) -> f64 {
+        self.beats
+    }
+
+    /// Number of note-on events. Notes of chords are counted separately.
+    pub fn note_count(&self) -> usize {
+        self.note_count
+    }
+
+    /// Number of note-off events.
+    pub fn note_off_count(&self) -> usize {
+        self.note_off_count
+    }
+
+    /// Number of parameter change events.
+    pub fn parameter_change_count(&self) -> usize {
+        self.parameter_change_count
+    }
+
+    /// Average number of note-on events per beat.
+    pub fn density(&self) -> f64 {
+        if self.beats > 0.0 {
+            self.note_count as f64 / self.beats
+        } else {
+            0.0
+        }
+    }
+
+    /// Note-on counts of volumes in equally sized bins in range \[0 - 1\]. Volumes above 1
+    /// are counted in the last bin.
+    pub fn velocity_histogram(&self) -> &[usize; VELOCITY_HISTOGRAM_BINS] {
+        &self.velocity_histogram
+    }
+
+    /// Lowest and highest note-on note, if there are any notes.
+    pub fn pitch_range(&self) -> Option<(Note, Note)> {
+        self.pitch_range
+    }
+
+    /// Add a single event to the stats.
+    pub fn add(&mut self, event: &Event) {
+        match event {
+            Event::NoteEvents(note_events) => {
+                for note_event in note_events.iter().flatten() {
+                    if note_event.note.is_note_on() {
+                        self.note_count += 1;
+                        let bin =
+                            (note_event.volume.max(0.0) * VELOCITY_HISTOGRAM_BINS as f32) as usize;
+                        self.velocity_histogram[bin.min(VELOCITY_HISTOGRAM_BINS - 1)] += 1;
+                        self.add_pitch_range(note_event.note, note_event.note);
+                    } else if note_event.note.is_note_off() {
+                        self.note_off_count += 1;
+                    }
+                }
+            }
+            Event::ParameterChangeEvent(_) => self.parameter_change_count += 1,
+        }
+    }
+
+    /// Merge the given stats into this stats. Both should cover the same time window.
+    pub fn merge(&mut self, other: &Self) {
+        self.note_count += other.note_count;
+        self.note_off_count += other.note_off_count;
+        self.parameter_change_count += other.parameter_change_count;
+        for (bin, count) in self.velocity_histogram.iter_mut().enumerate() {
+            *count += other.velocity_histogram[bin];
+        }
+        if let Some((lowest, highest)) = other.pitch_range {
+            self.add_pitch_range(lowest, highest);
+        }
+    }
+
+    fn add_pitch_range(&mut self, lowest: Note, highest: Note) {
+        self.pitch_range = Some(match self.pitch_range {
+            Some((min, max)) => (min.min(lowest), max.max(highest)),
+            None => (lowest, highest),
+        });
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Per rhythm slot and global [`EventStats`] of a [`Sequence`] in a fixed time window.
+///
+/// Stats can be collected in a single pass while consuming the sequence's events via
+/// [`add`](Self::add), from already bounced stems, or by running the sequence offline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceStats {
+    beats: f64,
+    slots: Vec<EventStats>,
+}
+
+impl SequenceStats {
+    /// Create new, empty stats for a time window with the given length in samples.
+    pub fn new(time_base: &BeatTimeBase, length: SampleTime) -> Self {
+        let beats = time_base.samples_to_beats(length as f64);
+        let slots = Vec::new();
+        Self { beats, slots }
+    }
+
+    /// Create stats from the given bounced stems, which got bounced with the given length.
+    pub fn from_stems(stems: &[Stem], time_base: &BeatTimeBase, length: SampleTime) -> Self {
+        let mut stats = Self::new(time_base, length);
+        for stem in stems {
+            for stem_event in stem.events() {
+                stats.add(stem.rhythm_index(), &stem_event.event);
+            }
+        }
+        stats
+    }
+
+    /// Reset the given sequence and collect stats of all its rhythm slots from the start until
+    /// the given sample time is reached.
+    pub fn run(sequence: &mut Sequence, length: SampleTime) -> Self {
+        let mut stats = Self::new(sequence.time_base(), length);
+        stats.resize(sequence.phrase_rhythm_slot_count());
+        sequence.reset();
+        sequence.consume_events_until_time(
+            length,
+            &mut |rhythm_index, _time, event: Option<Event>, _duration| {
+                if let Some(event) = event {
+                    stats.add(rhythm_index, &event);
+                }
+            },
+        );
+        stats
+    }
+
+    /// Length of the stats' time window in beats.
+    pub fn beats(&self) -> f64 {
+        self.beats
+    }
+
+    /// Stats of all rhythm slots, indexed by their rhythm index.
+    pub fn slots(&self) -> &[EventStats] {
+        &self.slots
+    }
+
+    /// Stats of a single rhythm slot, if the slot has stats.
+    pub fn slot(&self, rhythm_index: RhythmIndex) -> Option<&EventStats> {
+        self.slots.get(rhythm_index)
+    }
+
+    /// Merged stats of all rhythm slots.
+    pub fn total(&self) -> EventStats {
+        let mut total = EventStats::new(self.beats);
+        for slot in &self.slots {
+            total.merge(slot);
+        }
+        total
+    }
+
+    /// Add a single event of the given rhythm slot to the stats.
+    pub fn add(&mut self, rhythm_index: RhythmIndex, event: &Event) {
+        self.resize(rhythm_index + 1);
+        self.slots[rhythm_index].add(event);
+    }
+
+    fn resize(&mut self, slot_count: usize) {
+        if self.slots.len() < slot_count {
+            self.slots.resize(slot_count, EventStats::new(self.beats));
+        }
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn event_stats() {
+        let note_event = |note: Note, volume: f32| {
+            let mut note_event = NoteEvent::from(note);
+            note_event.volume = volume;
+            Some(note_event)
+        };
+        let mut stats = EventStats::new(2.0);
+        stats.add(&Event::NoteEvents(vec![
+            note_event(Note::C4, 0.25),
+            note_event(Note::G4, 1.0),
+            None,
+        ]));
+        stats.add(&Event::NoteEvents(vec![new_note(Note::OFF)]));
+        stats.add(&Event::NoteEvents(vec![note_event(Note::A3, 2.0)]));
+        assert_eq!(stats.note_count(), 3);
+        assert_eq!(stats.note_off_count(), 1);
+        assert_eq!(stats.parameter_change_count(), 0);
+        assert_eq!(stats.density(), 1.5);
+        assert_eq!(stats.pitch_range(), Some((Note::A3, Note::G4)));
+        assert_eq!(stats.velocity_histogram(), &[0, 0, 1, 0, 0, 0, 0, 0, 0, 2]);
+
+        let mut merged = EventStats::new(2.0);
+        merged.add(&Event::NoteEvents(vec![new_note(Note::C6)]));
+        merged.merge(&stats);
+        assert_eq!(merged.note_count(), 4);
+        assert_eq!(merged.pitch_range(), Some((Note::A3, Note::C6)));
+        assert_eq!(EventStats::new(0.0).density(), 0.0);
+    }
+
+    #[test]
+    fn sequence_stats() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let phrase = Phrase::new(
+            time_base,
+            vec![
+                time_base
+                    .every_nth_beat(1.0)
+                    .trigger(new_note_event(Note::C4)),
+                time_base
+                    .every_nth_beat(2.0)
+                    .trigger(new_note_event(Note::D4)),
+            ],
+            BeatTimeStep::Bar(1.0),
+        );
+        let mut sequence = Sequence::new(time_base, vec![phrase]);
+
+        let stats = SequenceStats::run(&mut sequence, 4000);
+        assert_eq!(stats.beats(), 8.0);
+        assert_eq!(
+            stats
+                .slots()
+                .iter()
+                .map(EventStats::note_count)
+                .collect::<Vec<_>>(),
+            vec![8, 4]
+        );
+        assert_eq!(stats.slot(1).map(EventStats::density), Some(0.5));
+        assert_eq!(stats.total().density(), 1.5);
+        assert_eq!(stats.total().pitch_range(), Some((Note::C4, Note::D4)));
+
+        // stats from bounced stems match
+        let stems = StemBounce::new().run(&mut sequence, 4000);
+        assert_eq!(SequenceStats::from_stems(&stems, &time_base, 4000), stats);
+    }
+}
@@ -0,0 +1,297 @@
+//! Estimate tempo and beat phase from tapped or played input events.
+
+use std::collections::VecDeque;
+
+use crate::{BeatTimeBase, Event, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Largest subdivision or multiple of a beat, which gets folded into a single beat.
+const MAX_BEAT_FOLDING: f64 = 4.0;
+
+// -------------------------------------------------------------------------------------------------
+
+/// Estimates tempo and beat phase from a stream of timestamped input events, e.g. tapped
+/// beats or MIDI notes of a drummer, so a [`Sequence`](crate::Sequence) can loosely follow
+/// a live player by updating its time base with [`TempoFollower::time_base`].
+///
+/// The tempo is the median of the most recent intervals between taps. Intervals which are
+/// close to subdivisions or multiples of the current beat length, e.g. eighth notes, get
+/// folded into a single beat, so they don't double or halve the tempo. The first interval
+/// after a reset or a pause of more than two beats at the minimum tempo is used as is.
+///
+/// Tempo and phase changes are smoothed over a few taps. Set a smoothing factor of 1.0 to
+/// disable smoothing.
+#[derive(Clone, Debug)]
+pub struct TempoFollower {
+    time_base: BeatTimeBase,
+    beat_length: f64,
+    smoothing: f64,
+    tempo_range: (f32, f32),
+    history_length: usize,
+    intervals: VecDeque<f64>,
+    last_tap: Option<SampleTime>,
+    beat_anchor: Option<f64>,
+}
+
+impl TempoFollower {
+    /// Default tempo smoothing factor.
+    pub const DEFAULT_SMOOTHING: f64 = 0.5;
+    /// Default range of estimated tempos in beats per minute.
+    pub const DEFAULT_TEMPO_RANGE: (f32, f32) = (40.0, 300.0);
+    /// Default number of intervals the tempo gets estimated from.
+    pub const DEFAULT_HISTORY_LENGTH: usize = 8;
+
+    /// Create a new tempo follower, which starts with the given time base's tempo.
+    pub fn new(time_base: BeatTimeBase) -> Self {
+        let beat_length = time_base.samples_per_beat();
+        Self {
+            time_base,
+            beat_length,
+            smoothing: Self::DEFAULT_SMOOTHING,
+            tempo_range: Self::DEFAULT_TEMPO_RANGE,
+            history_length: Self::DEFAULT_HISTORY_LENGTH,
+            intervals: VecDeque::new(),
+            last_tap: None,
+            beat_anchor: None,
+        }
+    }
+
+    /// Return a new tempo follower with the given smoothing factor in range (0, 1]: the
+    /// amount tempo and phase move towards the estimated tempo and phase per tap.
+    #[must_use]
+    pub fn with_smoothing(self, smoothing: f64) -> Self {
+        let smoothing = smoothing.clamp(0.001, 1.0);
+        Self { smoothing, ..self }
+    }
+
+    /// Return a new tempo follower, which only estimates tempos in the given range in beats
+    /// per minute.
+    #[must_use]
+    pub fn with_tempo_range(self, min: f32, max: f32) -> Self {
+        let min = min.max(1.0);
+        let tempo_range = (min, max.max(min));
+        Self {
+            tempo_range,
+            ..self
+        }
+    }
+
+    /// Return a new tempo follower, which estimates tempos from the given number of most
+    /// recent intervals.
+    #[must_use]
+    pub fn with_history_length(self, history_length: usize) -> Self {
+        let history_length = history_length.max(1);
+        Self {
+            history_length,
+            ..self
+        }
+    }
+
+    /// Tempo smoothing factor.
+    pub fn smoothing(&self) -> f64 {
+        self.smoothing
+    }
+
+    /// Range of estimated tempos in beats per minute.
+    pub fn tempo_range(&self) -> (f32, f32) {
+        self.tempo_range
+    }
+
+    /// The input's time base with the currently estimated tempo.
+    pub fn time_base(&self) -> &BeatTimeBase {
+        &self.time_base
+    }
+
+    /// The currently estimated tempo in beats per minute.
+    pub fn beats_per_min(&self) -> f32 {
+        self.time_base.beats_per_min
+    }
+
+    /// Sample time of the most recently estimated beat, if any taps got received.
+    pub fn beat_anchor(&self) -> Option<SampleTime> {
+        self.beat_anchor.map(|anchor| anchor.round() as SampleTime)
+    }
+
+    /// Position within the estimated beat at the given sample time in range \[0 - 1),
+    /// if any taps got received.
+    pub fn beat_phase(&self, sample_time: SampleTime) -> Option<f64> {
+        let anchor = self.beat_anchor?;
+        Some(((sample_time as f64 - anchor) / self.beat_length).rem_euclid(1.0))
+    }
+
+    /// Sample time of the next estimated beat at or after the given sample time, if any taps
+    /// got received.
+    pub fn next_beat_time(&self, sample_time: SampleTime) -> Option<SampleTime> {
+        let anchor = self.beat_anchor?;
+        let beats = ((sample_time as f64 - anchor) / self.beat_length).ceil();
+        Some((anchor + beats * self.beat_length).round().max(0.0) as SampleTime)
+    }
+
+    /// Feed a tap at the given sample time and update the estimated tempo and phase.
+    /// Taps must be fed in order: taps at or before the previous tap are ignored.
+    ///
+    /// Returns the estimated tempo in beats per minute.
+    pub fn tap(&mut self, sample_time: SampleTime) -> f32 {
+        let Some(last_tap) = self.last_tap else {
+            self.last_tap = Some(sample_time);
+            self.beat_anchor = Some(sample_time as f64);
+            return self.beats_per_min();
+        };
+        if sample_time <= last_tap {
+            return self.beats_per_min();
+        }
+        self.last_tap = Some(sample_time);
+        let (min_beat_length, max_beat_length) = self.beat_length_range();
+        let interval = (sample_time - last_tap) as f64;
+        if interval > max_beat_length * 2.0 {
+            // restart after pauses, keeping the current tempo
+            self.intervals.clear();
+            self.beat_anchor = Some(sample_time as f64);
+            return self.beats_per_min();
+        }
+        // fold the interval into a single beat
+        let beat_interval = if self.intervals.is_empty() {
+            let mut beat_interval = interval;
+            while beat_interval < min_beat_length {
+                beat_interval *= 2.0;
+            }
+            while beat_interval > max_beat_length {
+                beat_interval /= 2.0;
+            }
+            Some(beat_interval)
+        } else {
+            let ratio = interval / self.beat_length;
+            if ratio >= 1.0 {
+                let multiple = ratio.round();
+                (multiple <= MAX_BEAT_FOLDING).then_some(interval / multiple)
+            } else {
+                let subdivision = ratio.recip().round();
+                (subdivision <= MAX_BEAT_FOLDING).then_some(interval * subdivision)
+            }
+        };
+        // update tempo from the median of the recent intervals
+        if let Some(beat_interval) = beat_interval {
+            if self.intervals.len() >= self.history_length {
+                self.intervals.pop_front();
+            }
+            self.intervals.push_back(beat_interval);
+            let mut intervals = self.intervals.iter().copied().collect::<Vec<_>>();
+            intervals.sort_by(f64::total_cmp);
+            let median = if intervals.len() % 2 == 0 {
+                (intervals[intervals.len() / 2 - 1] + intervals[intervals.len() / 2]) / 2.0
+            } else {
+                intervals[intervals.len() / 2]
+            };
+            self.beat_length += (median - self.beat_length) * self.smoothing;
+            self.beat_length = self.beat_length.clamp(min_beat_length, max_beat_length);
+            self.time_base.beats_per_min = (self.samples_per_minute() / self.beat_length) as f32;
+        }
+        // update phase from taps which are close to a beat
+        if let Some(anchor) = self.beat_anchor {
+            let beats = ((sample_time as f64 - anchor) / self.beat_length).round();
+            let predicted = anchor + beats * self.beat_length;
+            let error = sample_time as f64 - predicted;
+            if error.abs() < self.beat_length / 4.0 {
+                self.beat_anchor = Some(predicted + error * self.smoothing);
+            }
+        }
+        self.beats_per_min()
+    }
+
+    /// Feed a tap at the given sample time when the given event contains note-ons, e.g. to
+    /// follow MIDI notes. Other events are ignored.
+    ///
+    /// Returns the estimated tempo in beats per minute.
+    pub fn tap_event(&mut self, sample_time: SampleTime, event: &Event) -> f32 {
+        if let Event::NoteEvents(note_events) = event {
+            if note_events
+                .iter()
+                .flatten()
+                .any(|note_event| note_event.note.is_note_on())
+            {
+                return self.tap(sample_time);
+            }
+        }
+        self.beats_per_min()
+    }
+
+    /// Forget all taps and restart with the given time base's tempo.
+    pub fn reset(&mut self, time_base: BeatTimeBase) {
+        self.beat_length = time_base.samples_per_beat();
+        self.time_base = time_base;
+        self.intervals.clear();
+        self.last_tap = None;
+        self.beat_anchor = None;
+    }
+
+    fn samples_per_minute(&self) -> f64 {
+        self.time_base.samples_per_sec as f64 * 60.0
+    }
+
+    fn beat_length_range(&self) -> (f64, f64) {
+        let (min_tempo, max_tempo) = self.tempo_range;
+        (
+            self.samples_per_minute() / max_tempo as f64,
+            self.samples_per_minute() / min_tempo as f64,
+        )
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{event::new_note, Note};
+
+    fn time_base() -> BeatTimeBase {
+        BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        }
+    }
+
+    #[test]
+    fn tempo() {
+        let mut follower = TempoFollower::new(time_base()).with_smoothing(1.0);
+        assert_eq!(follower.beat_phase(0), None);
+        assert_eq!(follower.tap(1000), 120.0);
+        assert_eq!(follower.tap(1600), 100.0);
+        assert_eq!(follower.tap(2200), 100.0);
+        // eighth notes and skipped beats don't change the tempo
+        assert_eq!(follower.tap(2500), 100.0);
+        assert_eq!(follower.tap(2800), 100.0);
+        assert_eq!(follower.tap(4000), 100.0);
+        assert_eq!(follower.time_base().beats_per_min, 100.0);
+        // phase
+        assert_eq!(follower.beat_anchor(), Some(4000));
+        assert_eq!(follower.beat_phase(4150), Some(0.25));
+        assert_eq!(follower.next_beat_time(4150), Some(4600));
+        assert_eq!(follower.next_beat_time(4600), Some(4600));
+        // ignore unordered taps, restart after pauses
+        assert_eq!(follower.tap(3000), 100.0);
+        assert_eq!(follower.tap(20000), 100.0);
+        assert_eq!(follower.beat_anchor(), Some(20000));
+        assert_eq!(follower.tap(20400), 150.0);
+
+        // tap events
+        let mut follower = TempoFollower::new(time_base()).with_smoothing(1.0);
+        let note_on = Event::NoteEvents(vec![new_note(Note::C4)]);
+        let note_off = Event::NoteEvents(vec![new_note(Note::OFF)]);
+        follower.tap_event(0, &note_on);
+        follower.tap_event(100, &note_off);
+        assert_eq!(follower.tap_event(750, &note_on), 80.0);
+
+        // smoothing and tempo ranges
+        let mut follower = TempoFollower::new(time_base())
+            .with_smoothing(0.5)
+            .with_tempo_range(60.0, 180.0);
+        follower.tap(0);
+        assert_eq!(follower.tap(600), (60000.0 / 550.0) as f32);
+        follower.reset(time_base());
+        follower.tap(0);
+        assert_eq!(follower.tap(100), (60000.0 / 450.0) as f32);
+    }
+}
@@ -2,3 +2,9 @@
 
 mod cycle;
 pub use cycle::{Cycle, Event, Pitch, Span, Target, Value};
+
+mod json;
+pub use json::{cycles_to_json, mini_to_json};
+
+mod operator;
+pub use operator::{register_operator, unregister_operator, OperatorCallback};
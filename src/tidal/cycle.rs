@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::{mem::size_of, rc::Rc};
 
 #[cfg(test)]
 use std::fmt::Display;
@@ -6,13 +6,14 @@ use std::fmt::Display;
 use pest::{iterators::Pair, Parser};
 use pest_derive::Parser;
 
-use rand::{thread_rng, Rng, SeedableRng};
+use rand::{Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
 
 use fraction::ToPrimitive;
 use fraction::{Fraction, One, Zero};
 
-use crate::pattern::euclidean::euclidean;
+use super::operator::CustomOperator;
+use crate::{pattern::euclidean::euclidean, rhythm::new_random_seed, Note, Tuning};
 
 // -------------------------------------------------------------------------------------------------
 
@@ -22,6 +23,7 @@ pub struct Cycle {
     event_limit: usize,
     input: String,
     seed: Option<[u8; 32]>,
+    reversed: bool,
     state: CycleState,
 }
 impl Cycle {
@@ -47,13 +49,15 @@ impl Cycle {
                         step: 0,
                         events: 0,
                         iteration: 0,
-                        rng: Xoshiro256PlusPlus::from_seed(thread_rng().gen()),
+                        rng: Xoshiro256PlusPlus::from_seed(new_random_seed()),
                     };
                     let seed = None;
                     let event_limit = Self::EVENT_LIMIT_DEFAULT;
+                    let reversed = false;
                     let cycle = Self {
                         input,
                         seed,
+                        reversed,
                         root,
                         state,
                         event_limit,
@@ -88,6 +92,13 @@ impl Cycle {
         }
     }
 
+    /// Change the seed of a possibly already running cycle. The random number generator
+    /// gets reseeded immediately and will also use the new seed in following resets.
+    pub fn set_seed(&mut self, seed: [u8; 32]) {
+        self.state.rng = Xoshiro256PlusPlus::from_seed(seed);
+        self.seed = Some(seed);
+    }
+
     /// Rebuild/configure cycle to use the given custom event count limit.
     pub fn with_event_limit(self, event_limit: usize) -> Self {
         Self {
@@ -96,6 +107,24 @@ impl Cycle {
         }
     }
 
+    /// Rebuild/configure cycle to play its events backwards within each cycle.
+    #[must_use]
+    pub fn with_reversed(self, reversed: bool) -> Self {
+        Self { reversed, ..self }
+    }
+
+    /// Returns true when the cycle plays its events backwards.
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+
+    /// Play the cycle's events backwards within each cycle, starting with the next cycle.
+    /// Event times get mirrored, so events which ended at the end of the cycle start at the
+    /// beginning of the cycle, and vice versa.
+    pub fn set_reversed(&mut self, reversed: bool) {
+        self.reversed = reversed;
+    }
+
     // TODO remove this or improve, * and / can change the output, <1> does not etc..
     /// check if a cycle will give different outputs between cycles
     pub fn is_stateful(&self) -> bool {
@@ -104,6 +133,18 @@ impl Cycle {
             .any(|&c| self.input.contains(c))
     }
 
+    /// Estimated memory usage of the parsed cycle in bytes.
+    pub fn memory_usage(&self) -> usize {
+        fn step_count(step: &Step) -> usize {
+            1 + step
+                .inner_steps()
+                .into_iter()
+                .map(step_count)
+                .sum::<usize>()
+        }
+        size_of::<Self>() + self.input.capacity() + step_count(&self.root) * size_of::<Step>()
+    }
+
     /// Query for the next iteration of output.
     ///
     /// Returns error when the number of generated events exceed the configured event limit.
@@ -114,7 +155,16 @@ impl Cycle {
         let mut events = Self::output(&self.root, &mut self.state, cycle, self.event_limit)?;
         self.state.iteration += 1;
         events.transform_spans(&Span::default());
-        Ok(events.export())
+        let mut channels = events.export();
+        if self.reversed {
+            for channel in &mut channels {
+                channel.reverse();
+                for event in channel.iter_mut() {
+                    event.span.reverse(&Span::default());
+                }
+            }
+        }
+        Ok(channels)
     }
 
     /// reset state to initial state
@@ -122,8 +172,7 @@ impl Cycle {
         self.state.iteration = 0;
         self.state.step = 0;
         self.state.events = 0;
-        self.state.rng =
-            Xoshiro256PlusPlus::from_seed(self.seed.unwrap_or_else(|| thread_rng().gen()));
+        self.state.rng = Xoshiro256PlusPlus::from_seed(self.seed.unwrap_or_else(new_random_seed));
     }
 }
 
@@ -173,6 +222,16 @@ impl Event {
     pub fn target(&self) -> &Target {
         &self.target
     }
+
+    /// Replace the step's value, e.g. in custom operators. The original value string is kept.
+    pub fn set_value(&mut self, value: Value) {
+        self.value = value;
+    }
+
+    /// Replace the step's value target, e.g. in custom operators.
+    pub fn set_target(&mut self, target: Target) {
+        self.target = target;
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -204,6 +263,7 @@ pub enum Value {
     Integer(i32),
     Pitch(Pitch),
     Chord(Pitch, Rc<str>),
+    Glide(Pitch, Pitch),
     Name(Rc<str>),
 }
 
@@ -225,6 +285,16 @@ impl Pitch {
     pub fn midi_note(&self) -> u8 {
         (self.octave as u32 * 12 + self.note as u32).min(0x7f) as u8
     }
+
+    /// The pitch as fractional MIDI note number in the given tuning.
+    pub fn tuned_note(&self, tuning: &Tuning) -> f64 {
+        tuning.pitch(Note::from(self.midi_note()))
+    }
+
+    /// The pitch's frequency in Hz in the given tuning.
+    pub fn frequency(&self, tuning: &Tuning) -> f64 {
+        tuning.frequency(Note::from(self.midi_note()))
+    }
 }
 
 // -------------------------------------------------------------------------------------------------
@@ -239,13 +309,13 @@ enum Step {
     Choices(Choices),
     DynamicExpression(DynamicExpression),
     StaticExpression(StaticExpression),
+    CustomExpression(CustomExpression),
     Bjorklund(Bjorklund),
     Range(Range),
     Repeat,
 }
 
 impl Step {
-    #[allow(dead_code)]
     fn inner_steps(&self) -> Vec<&Step> {
         match self {
             Step::Repeat => vec![],
@@ -257,6 +327,7 @@ impl Step {
             Step::Stack(st) => st.stack.iter().collect(),
             Step::DynamicExpression(e) => vec![&e.left, &e.right],
             Step::StaticExpression(e) => vec![&e.left],
+            Step::CustomExpression(e) => vec![&e.left],
             Step::Range(_) => vec![],
             Step::Bjorklund(b) => {
                 if let Some(rotation) = &b.rotation {
@@ -364,6 +435,7 @@ impl StaticOp {
 enum Operator {
     Static(StaticOp),
     Dynamic(DynamicOp),
+    Custom(CustomOperator),
 }
 
 impl Operator {
@@ -376,6 +448,15 @@ impl Operator {
             Rule::op_fast => Ok(Self::Dynamic(DynamicOp::Fast())),
             Rule::op_slow => Ok(Self::Dynamic(DynamicOp::Slow())),
             Rule::op_bjorklund => Ok(Self::Dynamic(DynamicOp::Bjorklund())),
+            Rule::op_custom => {
+                let name_pair = pair
+                    .clone()
+                    .into_inner()
+                    .next()
+                    .ok_or_else(|| format!("missing operator name\n{:?}", pair))?;
+                let name = name_pair.as_str().trim_start_matches('^');
+                Ok(Self::Custom(CustomOperator::from_name(name)?))
+            }
             _ => Err(format!("unsupported operator: {:?}", pair.as_rule())),
         }
     }
@@ -395,6 +476,13 @@ struct StaticExpression {
     right: Value,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+struct CustomExpression {
+    op: CustomOperator,
+    left: Box<Step>,
+    right: Value,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 struct Bjorklund {
     left: Box<Step>,
@@ -415,6 +503,8 @@ impl Pitch {
     fn parse(pair: Pair<Rule>) -> Pitch {
         let mut pitch = Pitch { note: 0, octave: 4 };
         let mut mark: i8 = 0;
+        // octaves are named in the engine-wide note octave numbering
+        let octave_offset = Note::octave_numbering().octave_offset();
         for p in pair.into_inner() {
             match p.as_rule() {
                 Rule::note => {
@@ -422,7 +512,11 @@ impl Pitch {
                         pitch.note = Self::as_note_value(c).unwrap_or(pitch.note)
                     }
                 }
-                Rule::octave => pitch.octave = p.as_str().parse::<u8>().unwrap_or(pitch.octave),
+                Rule::octave => {
+                    if let Ok(octave) = p.as_str().parse::<i32>() {
+                        pitch.octave = (octave - octave_offset).clamp(0, 10) as u8;
+                    }
+                }
                 Rule::mark => match p.as_str() {
                     "#" => mark = 1,
                     "b" => mark = -1,
@@ -484,7 +578,8 @@ impl Display for Pitch {
         if self.octave == 4 {
             f.write_str(n)
         } else {
-            f.write_fmt(format_args!("{}{}", n, self.octave))
+            let octave_offset = Note::octave_numbering().octave_offset();
+            f.write_fmt(format_args!("{}{}", n, self.octave as i32 + octave_offset))
         }
     }
 }
@@ -498,6 +593,7 @@ impl Value {
             Value::Float(f) => Target::Index(*f as i32),
             Value::Pitch(p) => Target::Name(Rc::from(format!("{:?}", p))), // TODO might not be the best conversion idea
             Value::Chord(p, m) => Target::Name(Rc::from(format!("{:?}'{}", p, m))),
+            Value::Glide(p, t) => Target::Name(Rc::from(format!("{:?}~{:?}", p, t))),
             Value::Name(n) => Target::Name(Rc::clone(n)),
         }
     }
@@ -509,6 +605,7 @@ impl Value {
             Value::Float(f) => Some(*f as i32),
             Value::Pitch(n) => Some(n.midi_note() as i32),
             Value::Chord(p, _m) => Some(p.midi_note() as i32),
+            Value::Glide(p, _t) => Some(p.midi_note() as i32),
             Value::Name(_n) => None,
         }
     }
@@ -521,6 +618,7 @@ impl Value {
             Value::Float(f) => Some(*f),
             Value::Pitch(n) => Some(n.midi_note() as f64),
             Value::Chord(n, _m) => Some(n.midi_note() as f64),
+            Value::Glide(n, _t) => Some(n.midi_note() as f64),
             Value::Name(_n) => None,
         }
     }
@@ -533,6 +631,7 @@ impl Value {
             Value::Float(f) => Some(f.clamp(0.0, 1.0)),
             Value::Pitch(p) => Some((p.midi_note() as f64).clamp(0.0, 128.0) / 128.0),
             Value::Chord(p, _m) => Some((p.midi_note() as f64).clamp(0.0, 128.0) / 128.0),
+            Value::Glide(p, _t) => Some((p.midi_note() as f64).clamp(0.0, 128.0) / 128.0),
             Value::Name(_n) => None,
         }
     }
@@ -567,6 +666,13 @@ impl Span {
         }
     }
 
+    /// mirrors the span within the outer span, so it plays backwards in the outer span
+    fn reverse(&mut self, outer: &Span) {
+        let start = outer.start + outer.end - self.end;
+        self.end = outer.start + outer.end - self.start;
+        self.start = start;
+    }
+
     /// transforms the span to 0..1 based on an outer span
     /// assumes self is inside outer
     fn normalize(&mut self, outer: &Span) {
@@ -654,6 +760,23 @@ impl Event {
         }
     }
 
+    #[cfg(test)]
+    fn with_glide(&self, from: (u8, u8), to: (u8, u8)) -> Self {
+        let from = Pitch {
+            note: from.0,
+            octave: from.1,
+        };
+        let to = Pitch {
+            note: to.0,
+            octave: to.1,
+        };
+        Self {
+            string: Rc::from(format!("{}~{}", from, to)),
+            value: Value::Glide(from, to),
+            ..self.clone()
+        }
+    }
+
     #[cfg(test)]
     fn with_name(&self, n: &'static str) -> Self {
         Self {
@@ -1063,7 +1186,14 @@ impl CycleParser {
                 }
                 Ok(Value::Chord(pitch, Rc::from(mode)))
             }
-            Rule::name => Ok(Value::Name(Rc::from(pair.as_str()))),
+            Rule::glide => {
+                let mut pitches = pair.into_inner().map(Pitch::parse);
+                match (pitches.next(), pitches.next()) {
+                    (Some(from), Some(to)) => Ok(Value::Glide(from, to)),
+                    _ => Err("invalid glide, expecting two pitches".to_string()),
+                }
+            }
+            Rule::name | Rule::relative => Ok(Value::Name(Rc::from(pair.as_str()))),
             _ => Err(format!("unrecognized pair in single\n{:?}", pair)),
         }
     }
@@ -1379,16 +1509,21 @@ impl CycleParser {
 
     fn static_expression(left: Step, op: StaticOp, pair: Pair<Rule>) -> Result<Step, String> {
         let right = if let Some(right_pair) = pair.into_inner().next() {
-            let value = right_pair
-                .clone()
-                .into_inner()
-                .next()
-                .ok_or_else(|| format!("invalid right hand {:?}", right_pair))
-                .and_then(Self::value)?;
-            if matches!(op, StaticOp::Target()) && matches!(value, Value::Pitch(_)) {
+            if right_pair.as_rule() == Rule::target_value {
+                // pass named target values as they are, e.g. "v=0.5"
                 Value::Name(Rc::from(right_pair.as_str()))
             } else {
-                value
+                let value = right_pair
+                    .clone()
+                    .into_inner()
+                    .next()
+                    .ok_or_else(|| format!("invalid right hand {:?}", right_pair))
+                    .and_then(Self::value)?;
+                if matches!(op, StaticOp::Target()) && matches!(value, Value::Pitch(_)) {
+                    Value::Name(Rc::from(right_pair.as_str()))
+                } else {
+                    value
+                }
             }
         } else {
             op.default_value()
@@ -1401,6 +1536,26 @@ impl CycleParser {
         }))
     }
 
+    fn custom_expression(left: Step, op: CustomOperator, pair: Pair<Rule>) -> Result<Step, String> {
+        // first inner pair is the operator name, the optional second one its value
+        let right = if let Some(right_pair) = pair.into_inner().nth(1) {
+            right_pair
+                .clone()
+                .into_inner()
+                .next()
+                .ok_or_else(|| format!("invalid right hand {:?}", right_pair))
+                .and_then(Self::value)?
+        } else {
+            Value::Rest
+        };
+
+        Ok(Step::CustomExpression(CustomExpression {
+            left: Box::new(left),
+            right,
+            op,
+        }))
+    }
+
     fn expression(pair: Pair<Rule>) -> Result<Step, String> {
         let mut inner = pair.clone().into_inner();
 
@@ -1419,6 +1574,7 @@ impl CycleParser {
                 DynamicOp::Bjorklund() => Self::bjorklund(left, op_pair),
                 _ => Self::speed_expression(left, op, op_pair),
             },
+            Operator::Custom(op) => Self::custom_expression(left, op, op_pair),
         }
     }
 }
@@ -1585,6 +1741,11 @@ impl Cycle {
                     }
                 }
             }
+            Step::CustomExpression(e) => {
+                let mut out = Self::output(e.left.as_ref(), state, cycle, limit)?;
+                out.mutate_events(&mut |event| e.op.apply(event, &e.right));
+                out
+            }
             Step::DynamicExpression(e) => {
                 #[allow(clippy::single_match)]
                 // TODO support something other than Step::Single as the right hand side
@@ -1682,6 +1843,9 @@ impl Cycle {
             Step::StaticExpression(e) => {
                 format!("SingleExpression {:?} : {:?}", e.op, e.right)
             }
+            Step::CustomExpression(e) => {
+                format!("CustomExpression {:?} : {:?}", e.op, e.right)
+            }
             Step::Bjorklund(_b) => format!("Bjorklund {}", ""),
         };
         println!("{} {}", Self::indent_lines(level), name);
@@ -1729,6 +1893,20 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn grammar_copies() {
+        // the macros crate is not part of published afseq packages
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let macros_grammar = root.join("macros/src/cycle.pest");
+        if let Ok(macros_grammar) = std::fs::read_to_string(macros_grammar) {
+            let grammar = std::fs::read_to_string(root.join("src/tidal/cycle.pest")).unwrap();
+            assert_eq!(
+                grammar, macros_grammar,
+                "cycle grammar copies of afseq and afseq-macros differ"
+            );
+        }
+    }
+
     #[test]
 
     pub fn cycle() -> Result<(), String> {
@@ -1782,6 +1960,14 @@ mod test {
                 Event::at(F::new(5u8, 6u8), F::new(1u8, 6u8)).with_note(5, 8),
             ]]
         );
+        // negative octaves get clamped in the default octave numbering
+        assert_eq!(
+            Cycle::from("c-1 d#-2")?.generate()?,
+            [[
+                Event::at(F::from(0), F::new(1u8, 2u8)).with_note(0, 0),
+                Event::at(F::new(1u8, 2u8), F::new(1u8, 2u8)).with_note(3, 0),
+            ]]
+        );
         assert_eq!(
             Cycle::from("[R [e [n o]]] , [[[i s] e ] _]")?.generate()?,
             vec![
@@ -1940,6 +2126,16 @@ mod test {
             ],
         )?;
 
+        assert!(Cycle::from("c4 ~g4").is_err());
+        assert_cycles(
+            "c4~g4 a3 ~ c4~g3",
+            vec![vec![vec![
+                Event::at(F::from(0), F::new(1u8, 4u8)).with_glide((0, 4), (7, 4)),
+                Event::at(F::new(1u8, 4u8), F::new(1u8, 4u8)).with_note(9, 3),
+                Event::at(F::new(3u8, 4u8), F::new(1u8, 4u8)).with_glide((0, 4), (7, 3)),
+            ]]],
+        )?;
+
         assert_cycles(
             "[1 2] [3 4,[5 6]:42]",
             vec![vec![
@@ -2188,4 +2384,144 @@ mod test {
         assert!(Cycle::from("#c $").is_err());
         Ok(())
     }
+
+    #[test]
+    pub fn reversed() -> Result<(), String> {
+        let mut cycle = Cycle::from("a [b c] <d e>")?.with_reversed(true);
+        assert!(cycle.is_reversed());
+        assert_eq!(
+            cycle.generate()?,
+            [[
+                Event::at(F::from(0), F::new(1u8, 3u8)).with_note(2, 4),
+                Event::at(F::new(1u8, 3u8), F::new(1u8, 6u8)).with_note(0, 4),
+                Event::at(F::new(1u8, 2u8), F::new(1u8, 6u8)).with_note(11, 4),
+                Event::at(F::new(2u8, 3u8), F::new(1u8, 3u8)).with_note(9, 4),
+            ]]
+        );
+        // toggle live, starting with the next cycle
+        cycle.set_reversed(false);
+        assert_eq!(
+            cycle.generate()?,
+            [[
+                Event::at(F::from(0), F::new(1u8, 3u8)).with_note(9, 4),
+                Event::at(F::new(1u8, 3u8), F::new(1u8, 6u8)).with_note(11, 4),
+                Event::at(F::new(1u8, 2u8), F::new(1u8, 6u8)).with_note(0, 4),
+                Event::at(F::new(2u8, 3u8), F::new(1u8, 3u8)).with_note(4, 4),
+            ]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    pub fn custom_operators() -> Result<(), String> {
+        use crate::tidal::{register_operator, unregister_operator};
+
+        register_operator("§", |event, value| {
+            event.set_target(Target::Index(value.to_integer().unwrap_or(1)))
+        })?;
+        register_operator("octave_up", |event, _value| {
+            if let Value::Pitch(pitch) = event.value().clone() {
+                event.set_value(Value::Pitch(Pitch {
+                    octave: pitch.octave + 1,
+                    ..pitch
+                }))
+            }
+        })?;
+        assert_eq!(
+            Cycle::from("a§ [b c]§3 d^octave_up")?.generate()?,
+            [[
+                Event::at(F::from(0), F::new(1u8, 3u8))
+                    .with_note(9, 4)
+                    .with_target(Target::Index(1)),
+                Event::at(F::new(1u8, 3u8), F::new(1u8, 6u8))
+                    .with_note(11, 4)
+                    .with_target(Target::Index(3)),
+                Event::at(F::new(1u8, 2u8), F::new(1u8, 6u8))
+                    .with_note(0, 4)
+                    .with_target(Target::Index(3)),
+                Event::at(F::new(2u8, 3u8), F::new(1u8, 3u8)).with_note(2, 5),
+            ]]
+        );
+        assert!(Cycle::from("a¤").is_err());
+        assert!(Cycle::from("a^unknown").is_err());
+        unregister_operator("§");
+        assert!(Cycle::from("a§").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn relative_values() -> Result<(), String> {
+        let events = Cycle::from("+3 +-5 ^2 ^-3 -5 v3")?.generate()?;
+        assert_eq!(
+            events[0]
+                .iter()
+                .map(|event| (event.string(), event.value().clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("+3", Value::Name(Rc::from("+3"))),
+                ("+-5", Value::Name(Rc::from("+-5"))),
+                ("^2", Value::Name(Rc::from("^2"))),
+                ("^-3", Value::Name(Rc::from("^-3"))),
+                // negative integers and names keep their meaning
+                ("-5", Value::Integer(-5)),
+                ("v3", Value::Name(Rc::from("v3"))),
+            ]
+        );
+        // degrees are no custom operators
+        assert_eq!(Cycle::from("c4 ^1")?.generate()?[0].len(), 2);
+        assert_eq!(Cycle::from("c4 ^-1")?.generate()?[0].len(), 2);
+        assert!(Cycle::from("^x").is_err());
+        assert!(Cycle::from("++3").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn target_values() -> Result<(), String> {
+        assert_eq!(
+            Cycle::from("bd:v=0.5 sn:cut=-2")?.generate()?,
+            [[
+                Event::at(F::from(0), F::new(1u8, 2u8))
+                    .with_name("bd")
+                    .with_target(Target::Name(Rc::from("v=0.5"))),
+                Event::at(F::new(1u8, 2u8), F::new(1u8, 2u8))
+                    .with_name("sn")
+                    .with_target(Target::Name(Rc::from("cut=-2"))),
+            ]]
+        );
+        assert!(Cycle::from("bd:v=").is_err());
+        assert!(Cycle::from("bd:v=x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn memory_usage() -> Result<(), String> {
+        let simple = Cycle::from("a b")?.memory_usage();
+        let nested = Cycle::from("a [b c] <d e>")?.memory_usage();
+        assert!(simple > std::mem::size_of::<Cycle>());
+        assert!(nested > simple);
+        Ok(())
+    }
+
+    #[test]
+    pub fn tuned_pitches() -> Result<(), String> {
+        let events = Cycle::from("c5 c#5 a5 c6")?.generate()?;
+        let pitches = events[0]
+            .iter()
+            .filter_map(|event| match event.value() {
+                Value::Pitch(pitch) => Some(pitch.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pitches.len(), 4);
+
+        let tuning = Tuning::equal_temperament(24)?;
+        let notes = pitches
+            .iter()
+            .map(|pitch| (pitch.tuned_note(&tuning) * 100.0).round() / 100.0)
+            .collect::<Vec<_>>();
+        assert_eq!(notes, vec![60.0, 60.5, 64.5, 66.0]);
+        let frequency = pitches[0].frequency(&Tuning::default().with_root(Note::C5, 256.0));
+        assert!((frequency - 256.0).abs() < 1e-9);
+        Ok(())
+    }
 }
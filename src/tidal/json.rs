@@ -0,0 +1,152 @@
+//! JSON serialization of evaluated cycle events, e.g. to debug or preview cycles.
+
+use fraction::{Fraction, ToPrimitive};
+
+use super::{Cycle, Event, Target, Value};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Parse the given mini-notation string, evaluate the given number of cycles and serialize the
+/// evaluated events as JSON, e.g. to debug cycles or to preview them in external editors.
+///
+/// See [`cycles_to_json`] for the JSON layout.
+///
+/// Returns error when the string failed to parse or evaluating the cycle failed.
+pub fn mini_to_json(input: &str, cycle_count: usize) -> Result<String, String> {
+    let mut cycle = Cycle::from(input)?;
+    cycles_to_json(&mut cycle, cycle_count)
+}
+
+/// Evaluate the given number of cycles from the given cycle and serialize the evaluated events
+/// as JSON: an array of cycles, where each cycle is an array of channels with event objects.
+///
+/// Events have the properties `string`, the step's original string, `value`, the parsed value
+/// object with a `type` and type specific properties, `start` and `end`, the event's span in
+/// cycles, and `target`, which is null, an integer or a string.
+///
+/// Returns error when evaluating the cycle failed.
+pub fn cycles_to_json(cycle: &mut Cycle, cycle_count: usize) -> Result<String, String> {
+    let mut cycles = Vec::with_capacity(cycle_count);
+    for index in 0..cycle_count {
+        let channels = cycle
+            .generate()?
+            .iter()
+            .map(|events| {
+                let events = events
+                    .iter()
+                    .map(|event| event_json(event, index))
+                    .collect::<Vec<_>>();
+                format!("[{}]", events.join(","))
+            })
+            .collect::<Vec<_>>();
+        cycles.push(format!("[{}]", channels.join(",")));
+    }
+    Ok(format!("[{}]", cycles.join(",")))
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn event_json(event: &Event, cycle_index: usize) -> String {
+    let time = |fraction: Fraction| cycle_index as f64 + fraction.to_f64().unwrap_or(0.0);
+    format!(
+        r#"{{"string":{},"value":{},"start":{},"end":{},"target":{}}}"#,
+        json_string(event.string()),
+        value_json(event.value()),
+        json_number(time(event.span().start())),
+        json_number(time(event.span().end())),
+        target_json(event.target())
+    )
+}
+
+fn value_json(value: &Value) -> String {
+    match value {
+        Value::Rest => r#"{"type":"rest"}"#.to_string(),
+        Value::Hold => r#"{"type":"hold"}"#.to_string(),
+        Value::Float(f) => format!(r#"{{"type":"float","value":{}}}"#, json_number(*f)),
+        Value::Integer(i) => format!(r#"{{"type":"integer","value":{}}}"#, i),
+        Value::Pitch(p) => format!(r#"{{"type":"pitch","note":{}}}"#, p.midi_note()),
+        Value::Chord(p, m) => format!(
+            r#"{{"type":"chord","note":{},"mode":{}}}"#,
+            p.midi_note(),
+            json_string(m)
+        ),
+        Value::Glide(p, t) => format!(
+            r#"{{"type":"glide","note":{},"target":{}}}"#,
+            p.midi_note(),
+            t.midi_note()
+        ),
+        Value::Name(n) => format!(r#"{{"type":"name","name":{}}}"#, json_string(n)),
+    }
+}
+
+fn target_json(target: &Target) -> String {
+    match target {
+        Target::None => "null".to_string(),
+        Target::Index(i) => i.to_string(),
+        Target::Name(n) => json_string(n),
+    }
+}
+
+// JSON has no representation for NaN or infinite numbers: write them as null.
+fn json_number(number: f64) -> String {
+    if number.is_finite() {
+        number.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut json = String::with_capacity(string.len() + 2);
+    json.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json() -> Result<(), String> {
+        assert_eq!(
+            mini_to_json("c4 bd:1", 1)?,
+            concat!(
+                r#"[[[{"string":"c4","value":{"type":"pitch","note":48},"start":0,"end":0.5,"target":null},"#,
+                r#"{"string":"bd","value":{"type":"name","name":"bd"},"start":0.5,"end":1,"target":1}]]]"#
+            )
+        );
+        assert_eq!(
+            mini_to_json("<1 2>, e'maj", 2)?,
+            concat!(
+                r#"[[[{"string":"1","value":{"type":"integer","value":1},"start":0,"end":1,"target":null}],"#,
+                r#"[{"string":"e'maj","value":{"type":"chord","note":52,"mode":"maj"},"start":0,"end":1,"target":null}]],"#,
+                r#"[[{"string":"2","value":{"type":"integer","value":2},"start":1,"end":2,"target":null}],"#,
+                r#"[{"string":"e'maj","value":{"type":"chord","note":52,"mode":"maj"},"start":1,"end":2,"target":null}]]]"#
+            )
+        );
+        assert_eq!(json_string("a\"b\\\n"), r#""a\"b\\\n""#);
+        assert_eq!(json_number(0.25), "0.25");
+        assert_eq!(json_number(f64::NAN), "null");
+        assert_eq!(json_number(f64::INFINITY), "null");
+        assert_eq!(
+            value_json(&Value::Float(f64::NEG_INFINITY)),
+            r#"{"type":"float","value":null}"#
+        );
+        assert!(mini_to_json("[", 1).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+
+use super::{Event, Value};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Callback of a custom cycle operator, see [`register_operator`]. Gets called with each event
+/// of the operator's left hand side and the operator's right hand side value, which is
+/// [`Value::Rest`] when the operator got used without a value.
+pub type OperatorCallback = dyn Fn(&mut Event, &Value) + Send + Sync;
+
+lazy_static! {
+    static ref OPERATORS: RwLock<HashMap<String, Arc<OperatorCallback>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Characters which are part of the built-in mini-notation syntax and thus can't be used as
+/// single character operators.
+const RESERVED_CHARS: &str = "[]<>{}(),|.%~-_!@?:*/#'\"^=+";
+
+// -------------------------------------------------------------------------------------------------
+
+/// Register or replace a custom mini-notation operator, which transforms the events of the
+/// step it's applied to, e.g. to add domain-specific syntax to cycles.
+///
+/// Operators are either single characters, which are not part of the built-in mini-notation
+/// syntax, and get used like the built-in operators: `a§` or `a§4`, or alphanumeric names,
+/// which get used with a `^` prefix and an optional value: `a^slice` or `a^slice=4`.
+///
+/// Registered operators apply to all cycles which get parsed after the registration.
+///
+/// Returns error when the operator name is neither a valid operator character nor a valid name.
+pub fn register_operator<F>(name: &str, callback: F) -> Result<(), String>
+where
+    F: Fn(&mut Event, &Value) + Send + Sync + 'static,
+{
+    validate_operator_name(name)?;
+    OPERATORS
+        .write()
+        .expect("Failed to access cycle operator registry")
+        .insert(name.to_string(), Arc::new(callback));
+    Ok(())
+}
+
+/// Remove a custom mini-notation operator, if it got registered.
+pub fn unregister_operator(name: &str) {
+    OPERATORS
+        .write()
+        .expect("Failed to access cycle operator registry")
+        .remove(name);
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A resolved custom operator in a parsed cycle.
+#[derive(Clone)]
+pub(crate) struct CustomOperator {
+    name: String,
+    callback: Arc<OperatorCallback>,
+}
+
+impl CustomOperator {
+    /// Try resolving a registered operator by its name.
+    ///
+    /// Returns error when no operator with the given name is registered.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        let callback = OPERATORS
+            .read()
+            .expect("Failed to access cycle operator registry")
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("unknown cycle operator '{}'", name))?;
+        let name = name.to_string();
+        Ok(Self { name, callback })
+    }
+
+    pub fn apply(&self, event: &mut Event, value: &Value) {
+        (self.callback)(event, value)
+    }
+}
+
+impl Debug for CustomOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CustomOperator")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl PartialEq for CustomOperator {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Arc::ptr_eq(&self.callback, &other.callback)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+fn validate_operator_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_alphanumeric() => {
+            if c.is_whitespace() || c.is_control() || RESERVED_CHARS.contains(c) {
+                Err(format!(
+                    "invalid cycle operator '{}': the character is part of the mini-notation",
+                    name
+                ))
+            } else {
+                Ok(())
+            }
+        }
+        (Some(c), _)
+            if !c.is_ascii_digit()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+        {
+            Ok(())
+        }
+        _ => Err(format!(
+            "invalid cycle operator '{}': expected a single character or an alphanumeric name, \
+            which does not start with a digit",
+            name
+        )),
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn operator_names() {
+        assert!(validate_operator_name("§").is_ok());
+        assert!(validate_operator_name("$").is_ok());
+        assert!(validate_operator_name("slice").is_ok());
+        assert!(validate_operator_name("slice_2").is_ok());
+        assert!(validate_operator_name("").is_err());
+        assert!(validate_operator_name("*").is_err());
+        assert!(validate_operator_name(" ").is_err());
+        assert!(validate_operator_name("§§").is_err());
+        assert!(validate_operator_name("sl ice").is_err());
+        assert!(validate_operator_name("2x").is_err());
+        assert!(CustomOperator::from_name("not_registered").is_err());
+    }
+}
@@ -8,6 +8,9 @@ pub use beats::{BeatTimeBase, BeatTimeStep};
 mod seconds;
 pub use seconds::{SecondTimeBase, SecondTimeStep};
 
+mod quantize;
+pub use quantize::TimingQuantize;
+
 // -------------------------------------------------------------------------------------------------
 
 /// Sample time value type as emitted by [`RhythmIter`](crate::RhythmIter).
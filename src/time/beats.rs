@@ -22,6 +22,26 @@ impl BeatTimeBase {
     pub fn samples_per_bar(&self) -> f64 {
         self.samples_per_sec as f64 * 60.0 / self.beats_per_min as f64 * self.beats_per_bar as f64
     }
+
+    /// Convert the given beat amount to samples, using the time base's current tempo.
+    pub fn beats_to_samples(&self, beats: f64) -> f64 {
+        beats * self.samples_per_beat()
+    }
+
+    /// Convert the given sample amount to beats, using the time base's current tempo.
+    pub fn samples_to_beats(&self, samples: f64) -> f64 {
+        samples / self.samples_per_beat()
+    }
+
+    /// Convert the given beat amount to seconds, using the time base's current tempo.
+    pub fn beats_to_seconds(&self, beats: f64) -> f64 {
+        beats * 60.0 / self.beats_per_min as f64
+    }
+
+    /// Convert the given second duration to beats, using the time base's current tempo.
+    pub fn seconds_to_beats(&self, seconds: f64) -> f64 {
+        seconds * self.beats_per_min as f64 / 60.0
+    }
 }
 
 impl From<BeatTimeBase> for SecondTimeBase {
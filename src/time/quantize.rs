@@ -0,0 +1,102 @@
+use crate::{time::BeatTimeStep, BeatTimeBase, SampleTime};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Partially quantizes event times of recorded or injected events to a beat time grid.
+///
+/// Instead of snapping events to the grid, events get moved by the quantize strength towards
+/// their nearest grid position, so some of the human timing is preserved. Each event's distance
+/// to the grid additionally decides if it gets moved at all: events outside the quantize
+/// window keep their original feel, e.g. intentionally laid back or pushed notes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TimingQuantize {
+    grid: BeatTimeStep,
+    strength: f32,
+    window: f32,
+}
+
+impl TimingQuantize {
+    /// Create a new quantize setting, which fully snaps all events to the given grid.
+    pub fn new(grid: BeatTimeStep) -> Self {
+        Self {
+            grid,
+            strength: 1.0,
+            window: 1.0,
+        }
+    }
+
+    /// Return a new quantize setting with the given strength in range \[0 - 1\]: the amount
+    /// events move towards the grid, e.g. 0.6 to move events 60% toward the grid.
+    #[must_use]
+    pub fn with_strength(self, strength: f32) -> Self {
+        let strength = strength.clamp(0.0, 1.0);
+        Self { strength, ..self }
+    }
+
+    /// Return a new quantize setting with the given window in range \[0 - 1\]: the max
+    /// distance of events to the grid, relative to half of the grid step, which get moved.
+    /// Events further away from the grid keep their original timing.
+    #[must_use]
+    pub fn with_window(self, window: f32) -> Self {
+        let window = window.clamp(0.0, 1.0);
+        Self { window, ..self }
+    }
+
+    /// The quantize grid.
+    pub fn grid(&self) -> BeatTimeStep {
+        self.grid
+    }
+
+    /// The quantize strength in range \[0 - 1\].
+    pub fn strength(&self) -> f32 {
+        self.strength
+    }
+
+    /// The quantize window in range \[0 - 1\].
+    pub fn window(&self) -> f32 {
+        self.window
+    }
+
+    /// Quantize the given sample time, using the given time base to resolve the grid.
+    pub fn apply(&self, time_base: &BeatTimeBase, sample_time: SampleTime) -> SampleTime {
+        let step = self.grid.to_samples(time_base);
+        if step <= 0.0 || !step.is_finite() {
+            return sample_time;
+        }
+        let time = sample_time as f64;
+        let distance = (time / step).round() * step - time;
+        if distance.abs() > self.window as f64 * step / 2.0 {
+            return sample_time;
+        }
+        (time + distance * self.strength as f64).round().max(0.0) as SampleTime
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quantize() {
+        let time_base = BeatTimeBase {
+            beats_per_min: 120.0,
+            beats_per_bar: 4,
+            samples_per_sec: 1000,
+        };
+        let quantize = TimingQuantize::new(BeatTimeStep::Sixteenth(1.0));
+        assert_eq!(quantize.apply(&time_base, 130), 125);
+        assert_eq!(quantize.apply(&time_base, 180), 125);
+        assert_eq!(quantize.apply(&time_base, 190), 250);
+
+        let quantize = quantize.with_strength(0.6);
+        assert_eq!(quantize.apply(&time_base, 130), 127);
+        assert_eq!(quantize.apply(&time_base, 240), 246);
+
+        let quantize = quantize.with_window(0.5);
+        assert_eq!(quantize.apply(&time_base, 130), 127);
+        assert_eq!(quantize.apply(&time_base, 160), 160);
+        assert_eq!(quantize.with_strength(0.0).apply(&time_base, 130), 130);
+    }
+}
@@ -0,0 +1,169 @@
+//! Non-fatal issues which got detected while running rhythms.
+
+use std::{collections::HashSet, fmt::Display};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Kind of a [`Warning`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    /// A deprecated syntax or feature got used.
+    Deprecated,
+    /// An out of range value got clamped to its valid range.
+    Clamped,
+    /// A fractional value got rounded to an integer value.
+    Rounded,
+    /// An identifier had no mapping, so it got ignored or played as rest.
+    Unmapped,
+    /// Events exceeded a safety limit, so they got dropped.
+    Dropped,
+}
+
+impl Display for WarningKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Deprecated => "deprecated",
+            Self::Clamped => "clamped",
+            Self::Rounded => "rounded",
+            Self::Unmapped => "unmapped",
+            Self::Dropped => "dropped",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// How out of range values, e.g. note, instrument or volume values in scripts and cycles, get
+/// handled by rhythms.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ValueRangePolicy {
+    /// Out of range values are errors. This is the default.
+    #[default]
+    Strict,
+    /// Out of range values get clamped to their valid range and are reported as warnings,
+    /// e.g. for live performances, where continuing playback matters more than strictness.
+    Clamp,
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// A non-fatal issue in a rhythm, e.g. unmapped identifiers in cycles, which hosts may want
+/// to show in their UI. Fetch them via [`Rhythm::take_warnings`](crate::Rhythm::take_warnings).
+///
+/// Displays as kind, followed by the warning's message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Warning {
+    kind: WarningKind,
+    message: String,
+}
+
+impl Warning {
+    /// Create a new warning of the given kind with the given human-readable message.
+    pub fn new<S: Into<String>>(kind: WarningKind, message: S) -> Self {
+        let message = message.into();
+        Self { kind, message }
+    }
+
+    /// The warning's kind.
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    /// The warning's human-readable message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Maximum number of distinct warnings a [`WarningCollector`] reports. Further warnings get
+/// ignored, so rhythms which e.g. generate new random values in every cycle can't grow the
+/// collector's memory without bounds.
+const MAX_REPORTED_WARNINGS: usize = 256;
+
+/// Collects warnings until they get taken, reporting each distinct warning only once, so
+/// issues which happen in every cycle don't flood hosts.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WarningCollector {
+    reported: HashSet<Warning>,
+    pending: Vec<Warning>,
+    overflowed: bool,
+}
+
+impl WarningCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a new warning, unless the same warning got added before or the maximum number of
+    /// distinct warnings got reported already.
+    pub fn add<S: Into<String>>(&mut self, kind: WarningKind, message: S) {
+        if self.overflowed {
+            return;
+        }
+        if self.reported.len() >= MAX_REPORTED_WARNINGS {
+            self.overflowed = true;
+            self.pending.push(Warning::new(
+                WarningKind::Dropped,
+                "too many warnings: further warnings get ignored",
+            ));
+            return;
+        }
+        let warning = Warning::new(kind, message);
+        if self.reported.insert(warning.clone()) {
+            self.pending.push(warning);
+        }
+    }
+
+    /// Fetch and clear all pending warnings.
+    pub fn take(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn collector() {
+        let mut collector = WarningCollector::new();
+        collector.add(WarningKind::Unmapped, "unmapped cycle identifier 'bd'");
+        collector.add(WarningKind::Unmapped, "unmapped cycle identifier 'bd'");
+        collector.add(WarningKind::Clamped, "note value 200 got clamped to 127");
+        let warnings = collector.take();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[0].to_string(),
+            "unmapped: unmapped cycle identifier 'bd'"
+        );
+        assert_eq!(warnings[1].kind(), WarningKind::Clamped);
+        // distinct warnings are reported only once
+        collector.add(WarningKind::Unmapped, "unmapped cycle identifier 'bd'");
+        assert!(collector.take().is_empty());
+    }
+
+    #[test]
+    fn collector_limit() {
+        let mut collector = WarningCollector::new();
+        for value in 0..MAX_REPORTED_WARNINGS * 2 {
+            collector.add(WarningKind::Clamped, format!("value {} got clamped", value));
+        }
+        let warnings = collector.take();
+        assert_eq!(warnings.len(), MAX_REPORTED_WARNINGS + 1);
+        assert_eq!(warnings.last().unwrap().kind(), WarningKind::Dropped);
+        // further warnings get ignored
+        collector.add(WarningKind::Unmapped, "unmapped cycle identifier 'bd'");
+        assert!(collector.take().is_empty());
+    }
+}
@@ -0,0 +1,358 @@
+//! WebAssembly API to use afseq in web apps.
+//!
+//! A [`WasmEngine`] plays rhythms from cycles (mini-notation) or fixed pulse patterns in
+//! parallel, each rhythm in its own rhythm slot. Apps advance the engine's time in blocks of
+//! sample frames and pull the generated events, e.g. to schedule them with WebAudio. The
+//! engine does not produce any audio.
+//!
+//! Enable the `wasm` feature to build the API via wasm-bindgen. Lua scripting and the player
+//! are not supported on `wasm32-unknown-unknown`, so build without default features, e.g. via
+//! `cargo build --release --target wasm32-unknown-unknown --no-default-features --features wasm`.
+//! Unseeded random cycle operators get seeded from the browser's entropy source. Enable the
+//! `fixed-random-seeds` feature to get the same random output in each run instead.
+//!
+//! All functions are plain Rust functions too, so the API can also be used and tested
+//! on other targets.
+
+use std::{borrow::Cow, cell::RefCell, collections::VecDeque, rc::Rc};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    event::{cycle::new_cycle_event, new_note_event},
+    pattern::fixed::ToFixedPattern,
+    phrase::RhythmSlot,
+    time::BeatTimeStep,
+    BeatTimeBase, Event, Note, Phrase, Rhythm, SampleTime, Sequence,
+};
+
+// -------------------------------------------------------------------------------------------------
+
+/// Kind of a [`WasmEvent`].
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WasmEventKind {
+    /// A note-on event.
+    NoteOn = 0,
+    /// A note-off event.
+    NoteOff = 1,
+    /// A parameter change event.
+    ParameterChange = 2,
+}
+
+/// A single event, as pulled via [`WasmEngine::poll_event`]. Note events with multiple
+/// voices are split up into one event per voice.
+///
+/// Times are in seconds, relative to the start of the engine's playback, so they can be
+/// scheduled relative to the AudioContext time at which playback started.
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WasmEvent {
+    /// Event kind.
+    pub kind: WasmEventKind,
+    /// Rhythm slot index of the rhythm which emitted the event.
+    pub slot: u32,
+    /// Event time in seconds.
+    pub time: f64,
+    /// Event duration in seconds.
+    pub duration: f64,
+    /// Voice index of note events.
+    pub voice: u32,
+    /// MIDI note number of note events.
+    pub note: u8,
+    /// Instrument id of note events or -1 when no instrument is set.
+    pub instrument: i32,
+    /// Volume of note events.
+    pub volume: f32,
+    /// Panning of note events.
+    pub panning: f32,
+    /// Delay of note events, relative to the event's duration.
+    pub delay: f32,
+    /// Parameter id of parameter change events or -1 when no parameter is set.
+    pub parameter: i32,
+    /// Value of parameter change events.
+    pub value: f32,
+}
+
+impl WasmEvent {
+    fn new(kind: WasmEventKind, slot: usize, time: f64, duration: f64) -> Self {
+        Self {
+            kind,
+            slot: slot as u32,
+            time,
+            duration,
+            voice: 0,
+            note: 0,
+            instrument: -1,
+            volume: 0.0,
+            panning: 0.0,
+            delay: 0.0,
+            parameter: -1,
+            value: 0.0,
+        }
+    }
+}
+
+// -------------------------------------------------------------------------------------------------
+
+/// Event generator for web apps, see the [module docs](self).
+#[wasm_bindgen]
+pub struct WasmEngine {
+    time_base: BeatTimeBase,
+    rhythms: Vec<Rc<RefCell<dyn Rhythm>>>,
+    sequence: Sequence,
+    playing: bool,
+    events: VecDeque<WasmEvent>,
+}
+
+#[wasm_bindgen]
+impl WasmEngine {
+    /// Create a new engine with the given time base.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        beats_per_min: f32,
+        beats_per_bar: u32,
+        samples_per_sec: u32,
+    ) -> Result<WasmEngine, String> {
+        if !(beats_per_min > 0.0 && beats_per_min.is_finite()) {
+            return Err(format!("invalid tempo: '{}'", beats_per_min));
+        }
+        if beats_per_bar == 0 || samples_per_sec == 0 {
+            return Err("beats per bar and sample rate must be larger than 0".to_string());
+        }
+        let time_base = BeatTimeBase {
+            beats_per_min,
+            beats_per_bar,
+            samples_per_sec,
+        };
+        Ok(Self {
+            time_base,
+            rhythms: Vec::new(),
+            sequence: Sequence::new(time_base, vec![]),
+            playing: false,
+            events: VecDeque::new(),
+        })
+    }
+
+    /// Add a new rhythm slot which plays the given cycle in mini-notation, e.g. "c4 [e4 g4]",
+    /// once every given number of bars. Returns the new slot's index.
+    #[wasm_bindgen(js_name = addCycle)]
+    pub fn add_cycle(&mut self, cycle: &str, bars: f32) -> Result<usize, String> {
+        if !(bars > 0.0 && bars.is_finite()) {
+            return Err(format!("invalid cycle length: '{}'", bars));
+        }
+        let rhythm = self
+            .time_base
+            .every_nth_bar(bars)
+            .trigger(new_cycle_event(cycle)?);
+        Ok(self.add_rhythm(Rc::new(RefCell::new(rhythm))))
+    }
+
+    /// Add a new rhythm slot which plays the given note on all non zero pulses of the given
+    /// pulse pattern, with one pulse every given number of beats. Returns the new slot's index.
+    #[wasm_bindgen(js_name = addPattern)]
+    pub fn add_pattern(
+        &mut self,
+        pulses: Vec<f32>,
+        beats: f32,
+        note: &str,
+    ) -> Result<usize, String> {
+        if !(beats > 0.0 && beats.is_finite()) {
+            return Err(format!("invalid pulse length: '{}'", beats));
+        }
+        let note = Note::try_from(note)?;
+        let rhythm = self
+            .time_base
+            .every_nth_beat(beats)
+            .with_pattern(pulses.to_pattern())
+            .trigger(new_note_event(note));
+        Ok(self.add_rhythm(Rc::new(RefCell::new(rhythm))))
+    }
+
+    /// Remove all rhythms from the engine and rewind the playback position. Parameter values
+    /// are kept.
+    pub fn clear(&mut self) {
+        self.rhythms.clear();
+        self.events.clear();
+        let external_context = self.sequence.external_context().clone();
+        self.sequence = Sequence::new(self.time_base, vec![]);
+        self.sequence.restore_external_context(&external_context);
+    }
+
+    /// Set an external context parameter value, e.g. to control cycle operators. Values are
+    /// memorized and also applied to rhythms which get added later on.
+    #[wasm_bindgen(js_name = setParameter)]
+    pub fn set_parameter(&mut self, name: &str, value: f64) {
+        self.sequence
+            .set_external_context(&[(Cow::Borrowed(name), value)]);
+    }
+
+    /// Change the engine's tempo.
+    #[wasm_bindgen(js_name = setTempo)]
+    pub fn set_tempo(&mut self, beats_per_min: f32) -> Result<(), String> {
+        if !(beats_per_min > 0.0 && beats_per_min.is_finite()) {
+            return Err(format!("invalid tempo: '{}'", beats_per_min));
+        }
+        self.time_base.beats_per_min = beats_per_min;
+        self.sequence.set_time_base(&self.time_base);
+        Ok(())
+    }
+
+    /// Start or continue playback from the current playback position.
+    pub fn start(&mut self) {
+        self.playing = true;
+    }
+
+    /// Pause playback. The playback position is kept, already generated events can still be
+    /// pulled.
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    /// Returns true when the engine is playing.
+    #[wasm_bindgen(js_name = isPlaying)]
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Rewind playback to the start and drop all pending events.
+    pub fn rewind(&mut self) {
+        self.events.clear();
+        self.sequence.reset();
+    }
+
+    /// Current playback position in seconds.
+    pub fn position(&self) -> f64 {
+        self.seconds(self.sequence.sample_position())
+    }
+
+    /// Run a playing engine for the given number of sample frames and queue all generated
+    /// events. Returns the number of pending events, which can be pulled via
+    /// [`Self::poll_event`].
+    pub fn run(&mut self, frames: u32) -> usize {
+        if !self.playing || self.rhythms.is_empty() {
+            return self.events.len();
+        }
+        let run_until_time = self.sequence.sample_position() + frames as SampleTime;
+        let samples_per_sec = self.time_base.samples_per_sec as f64;
+        let events = &mut self.events;
+        self.sequence.consume_events_until_time(
+            run_until_time,
+            &mut |slot, time, event, duration| {
+                let time = time as f64 / samples_per_sec;
+                let duration = duration as f64 / samples_per_sec;
+                match event {
+                    Some(Event::NoteEvents(note_events)) => {
+                        for (voice, note_event) in note_events.iter().enumerate() {
+                            if let Some(note_event) = note_event {
+                                let kind = if note_event.note.is_note_on() {
+                                    WasmEventKind::NoteOn
+                                } else if note_event.note.is_note_off() {
+                                    WasmEventKind::NoteOff
+                                } else {
+                                    continue;
+                                };
+                                let mut event = WasmEvent::new(kind, slot, time, duration);
+                                event.voice = voice as u32;
+                                event.note = u8::from(note_event.note);
+                                event.instrument = note_event
+                                    .instrument
+                                    .map_or(-1, |id| usize::from(id) as i32);
+                                event.volume = note_event.volume;
+                                event.panning = note_event.panning;
+                                event.delay = note_event.delay;
+                                events.push_back(event);
+                            }
+                        }
+                    }
+                    Some(Event::ParameterChangeEvent(change)) => {
+                        let kind = WasmEventKind::ParameterChange;
+                        let mut event = WasmEvent::new(kind, slot, time, duration);
+                        event.parameter = change.parameter.map_or(-1, |id| usize::from(id) as i32);
+                        event.value = change.value;
+                        events.push_back(event);
+                    }
+                    None => (),
+                }
+            },
+        );
+        self.events.len()
+    }
+
+    /// Pull the next pending event, if any.
+    #[wasm_bindgen(js_name = pollEvent)]
+    pub fn poll_event(&mut self) -> Option<WasmEvent> {
+        self.events.pop_front()
+    }
+}
+
+impl WasmEngine {
+    fn add_rhythm(&mut self, rhythm: Rc<RefCell<dyn Rhythm>>) -> usize {
+        self.rhythms.push(rhythm);
+        self.rebuild_sequence();
+        self.rhythms.len() - 1
+    }
+
+    fn rebuild_sequence(&mut self) {
+        // recreate the sequence with all rhythms and move it to the old sequence's position
+        let sample_position = self.sequence.sample_position();
+        let external_context = self.sequence.external_context().clone();
+        let slots = self
+            .rhythms
+            .iter()
+            .map(|rhythm| RhythmSlot::from(Rc::clone(rhythm)))
+            .collect::<Vec<_>>();
+        let phrase = Phrase::new(self.time_base, slots, BeatTimeStep::Bar(1.0));
+        self.sequence = Sequence::new(self.time_base, vec![phrase]);
+        self.sequence.reset();
+        self.sequence.restore_external_context(&external_context);
+        self.sequence.skip_events_until_time(sample_position);
+    }
+
+    fn seconds(&self, sample_time: SampleTime) -> f64 {
+        sample_time as f64 / self.time_base.samples_per_sec as f64
+    }
+}
+
+// --------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn engine() -> Result<(), String> {
+        assert!(WasmEngine::new(0.0, 4, 44100).is_err());
+        let mut engine = WasmEngine::new(120.0, 4, 1000)?;
+        assert!(engine.add_cycle("c4 [", 1.0).is_err());
+        assert!(engine.add_pattern(vec![1.0], 1.0, "x4").is_err());
+        assert_eq!(engine.add_cycle("c4 e4", 1.0)?, 0);
+        assert_eq!(engine.add_pattern(vec![1.0, 0.0], 1.0, "g4")?, 1);
+
+        // stopped engines don't run
+        assert_eq!(engine.run(1000), 0);
+        engine.start();
+        assert!(engine.is_playing());
+        assert_eq!(engine.run(1000), 2);
+        assert_eq!(engine.position(), 1.0);
+
+        let event = engine.poll_event().ok_or("missing event")?;
+        assert_eq!(event.kind, WasmEventKind::NoteOn);
+        assert_eq!((event.slot, event.time, event.note), (0, 0.0, 48));
+        let event = engine.poll_event().ok_or("missing event")?;
+        assert_eq!((event.slot, event.time, event.note), (1, 0.0, 55));
+        assert!(engine.poll_event().is_none());
+
+        // transport
+        engine.rewind();
+        assert_eq!(engine.position(), 0.0);
+        assert!(engine.set_tempo(60.0).is_ok());
+        assert!(engine.set_tempo(-1.0).is_err());
+        engine.stop();
+        assert!(!engine.is_playing());
+        engine.clear();
+        engine.start();
+        assert_eq!(engine.run(1000), 0);
+        Ok(())
+    }
+}
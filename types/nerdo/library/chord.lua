@@ -26,6 +26,12 @@
 ---- "9 -> "nine"
 ---- "11" -> "eleven"
 ---
+---Extended chord symbols, which are not part of the chord names above, are parsed from a base
+---quality ("maj", "m", "dim", "aug", ...), a degree (6, 7, 9, 11, 13), suspensions ("sus2",
+---"sus4"), alterations ("b5", "#5", "b9", "#9", "#11", "b13") and additions ("add9", ...).
+---A slash bass note can be appended with "/", e.g. "maj7/g". Bass notes without an octave
+---are placed below the chord's root note.
+---
 ---### examples:
 ---```lua
 ---chord("c4", "minor") --> {"c4", "d#4", "f4"}
@@ -36,6 +42,9 @@
 -----or:
 ---note("c4'major")
 ---note("c4'major v0.5")
+-----or with extended chord symbols and slash bass notes:
+---note("c4'maj7#11")
+---note("c4'm9/g")
 -----or:
 ---note(scale("c4", "major"):chord("i", 3))
 ---note(scale("c4", "major"):chord("i", 3)):with_volume(0.5)
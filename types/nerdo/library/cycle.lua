@@ -17,6 +17,49 @@
 ---@field step integer
 ---step length fraction within the cycle, where 1 is the total duration of a single cycle run.
 ---@field step_length number
+---step start fraction within the cycle, where 1 is the total duration of a single cycle run.
+---@field step_start number
+---The step's target, if any: an integer for instrument targets such as `"bd:1"`, or a string for
+---named targets such as `"bd:v=0.5"`, which then is `"v=0.5"`.
+---@field target (integer|string)?
+
+---Parameter change, which can be returned from `cycle:map` and `emit` functions.
+---@class CycleMapParameterChange
+---Parameter id of the changed parameter.
+---@field parameter integer?
+---The parameter's new value.
+---@field value number
+---Custom user data, passed as it is to the host.
+---@field extra table<string, boolean|number|string>?
+
+----------------------------------------------------------------------------------------------------
+
+---Optional options passed to `cycle`.
+---@class CycleOptions
+---
+---Instruments which get assigned to the cycle's stack channels. The first channel uses the
+---first instrument, the second channel the second one and so on. When there are more channels
+---than instruments, instruments wrap around. Steps with explicit targets keep their targets.
+---@field instruments integer[]?
+---When true, float values in the cycle are played as fractional MIDI notes, e.g. for
+---microtonal instruments: `60.5` plays MIDI note 60, detuned by 50 cents. The detune amount
+---gets passed as `detune` note data in cents. By default, float values play nothing.
+---@field fractional_notes boolean?
+---Declares named targets with values, such as `"bd:v=0.5"` or `"sn:cut=2"`. Values get
+---validated and applied when the cycle's events are converted: targets named `v`, `p` and `d`
+---set the note's volume, panning and delay, all other targets get passed as note data.
+---Targets without value, e.g. `"bd:v"`, use the target's default value.
+---@field targets table<string, CycleTargetDefinition>?
+---How to handle named targets which are not declared in `targets`. By default "ignore".
+---@field unknown_targets "ignore"|"warn"|"error"?
+
+---@class CycleTargetDefinition
+---Value type of the target. "integer" values get rounded. By default "number".
+---@field type "number"|"integer"?
+---Value which is used, when the target is specified without a value.
+---@field default number?
+---Min and max value of the target. Out of range values get clamped.
+---@field range number[]?
 
 ----------------------------------------------------------------------------------------------------
 
@@ -26,7 +69,8 @@ local Cycle = {}
 ----------------------------------------------------------------------------------------------------
 
 ---@alias CycleMapNoteValue NoteValue|(NoteValue[])|Note
----@alias CycleMapFunction fun(context: CycleMapContext, value: string):CycleMapNoteValue
+---@alias CycleMapFunctionValue CycleMapNoteValue|Sequence|CycleMapParameterChange
+---@alias CycleMapFunction fun(context: CycleMapContext, value: string):CycleMapFunctionValue
 ---@alias CycleMapGenerator fun(context: CycleMapContext, value: string):CycleMapFunction
 
 ---Map names in in the cycle to custom note events.
@@ -35,6 +79,9 @@ local Cycle = {}
 ---values. Custom identifiers such as "bd" are undefined and will result into a rest, when
 ---they are not mapped explicitly.
 ---
+---Map functions may also return a `sequence`, which plays its notes one after another within
+---the mapped step, or a parameter change table such as `{ parameter = 1, value = 0.5 }`.
+---
 ---### examples:
 ---```lua
 -----Using a fixed mapping table
@@ -61,6 +108,14 @@ local Cycle = {}
 ---    return { key = note + octave * 12 }
 ---  end
 ---end)
+-----Using a dynamic map function to emit note sequences and parameter changes
+---cycle("a b:2 a"):map(function(context, value)
+---  if value == "a" then
+---    return sequence("c4", "e4") -- plays two notes within a single step
+---  else
+---    return { parameter = context.target, value = 0.5 }
+---  end
+---end)
 -----Using a dynamic map function to map values to chord degrees
 ---cycle("1 5 1 [6|7]"):map(function(context)
 ---  local cmin = scale("c", "minor")
@@ -75,6 +130,16 @@ local Cycle = {}
 ---@overload fun(self, function: CycleMapFunction|CycleMapGenerator): Cycle
 function Cycle:map(map) end
 
+---Play the cycle's events backwards within each cycle.
+---
+---### examples:
+---```lua
+---cycle("c4 [e4 g4] b4"):reverse() -- plays "b4 [g4 e4] c4"
+---```
+---@return Cycle
+---@nodiscard
+function Cycle:reverse() end
+
 ----------------------------------------------------------------------------------------------------
 
 --- Create a note sequence from a Tidal Cycles mini-notation string.
@@ -83,6 +148,8 @@ function Cycle:map(map) end
 --- * Stacks and random choices are valid without brackets (`a | b` is parsed as `[a | b]`)
 --- * Operators currently only accept numbers on the right side (`a3*2` is valid, `a3*<1 2>` is not)
 --- * `:` - Sets the instrument or remappable target instead of selecting samples
+--- * `c4~g4` - Glides from the first to the second pitch across the step's span
+--- * `+3 +-5 ^2 ^-3` - Relative notes in semitones or scale degrees, see rhythm `relative_notes`
 --- [Tidal Cycles Reference](https://tidalcycles.org/docs/reference/mini_notation/)
 ---
 ---### examples:
@@ -93,12 +160,19 @@ function Cycle:map(map) end
 ---cycle("<c4 e4 g4> <e4 g4> <g4 b4 d5> <b4 f5>")
 -----Euclidean Rhythms
 ---cycle("c4(3,8) e4(5,8) g4(7,8)")
+-----Transposable riff with relative notes
+---cycle("c4 +3 +4 +-7")
 -----Polyrhythm
 ---cycle("{c4 e4 g4 b4}%2, {f4 d4 a4}%4")
 -----Map custom identifiers to notes
 ---cycle("bd(3,8)"):map({ bd = "c4 #1" })
+-----Assign instruments to stacked channels
+---cycle("bd*4, [~ sn]*2, hh*8", { instruments = { 1, 2, 3 } }):map({
+---  bd = "c4", sn = "c4", hh = "c4"
+---})
 --- ```
 ---@param input string
+---@param options CycleOptions?
 ---@return Cycle
 ---@nodiscard
-function cycle(input) end
+function cycle(input, options) end
@@ -0,0 +1,48 @@
+---@meta
+---Do not try to execute this file. It's just a type definition file.
+---
+---Part of the afseq trait: Defines LuaLS annotations for the afseq groove function.
+---
+
+----------------------------------------------------------------------------------------------------
+
+---@class Groove
+---Step positions of the pulses in a single groove group, in ascending order.
+---@field positions number[]
+---Length of a single groove group in steps.
+---@field length number
+local Groove = {}
+
+----------------------------------------------------------------------------------------------------
+
+---Available groove presets.
+---@alias GroovePreset "straight"|"swing"|"quintuplet swing"|"septuplet swing"|"tresillo"|"cinquillo"|"triplet"|"quintuplet"|"septuplet"|"nonuplet"|string
+
+---Create a new microrhythm template from a preset name, which can be applied to rhythms via
+---the rhythm's `groove` property.
+---
+---Grooves remap the time positions of pulses in a rhythm: each group of pulses gets moved to
+---the groove's step positions within the groove's length.
+---
+---### examples:
+---```lua
+---groove("tresillo") --> 3 pulses spread as 3+3+2 over 8 steps
+---groove("septuplet") --> 7 pulses over 4 steps, e.g. a septuplet over a beat in 1/16 rhythms
+---```
+---@param preset GroovePreset
+---@return Groove
+---@nodiscard
+function groove(preset) end
+
+---Create a new microrhythm template from custom step positions.
+---
+---### examples:
+---```lua
+---groove({0, 1.5}) --> a heavy swing over 2 steps
+---groove({0, 1, 3}, 4) --> 3 pulses spread over 4 steps
+---```
+---@param positions number[] ascending list of step positions, starting with 0
+---@param length number? length of the groove in steps. By default the number of positions.
+---@return Groove
+---@nodiscard
+function groove(positions, length) end
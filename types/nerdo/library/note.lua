@@ -12,6 +12,9 @@
 ---@field volume number? Volume in range [0.0 - 1.0]
 ---@field panning number? Panning factor in range [-1.0 - 1.0] where 0 is center
 ---@field delay number? Delay factor in range [0.0 - 1.0]
+---@field strum number? Chord keys only: additional delay per chord note in range [0.0 - 1.0]
+---@field strum_direction "up"|"down"|"center"? Chord keys only: order of strummed notes
+---@field extra table<string, boolean|number|string>? Custom user data, passed as it is to the host
 local NoteTable = {}
 
 ----------------------------------------------------------------------------------------------------
@@ -83,6 +86,13 @@ function Note:with_delay(delay) end
 --- Create a new monophonic or polyphonic note (a chord) from a number value, 
 --- a note string, chord string or array of note values.
 ---
+--- Note strings and `key`s can also be relative notes, such as `+3` or `+-5` (semitones) 
+--- and `^2` or `^-3` (scale degrees), which get resolved from the previously emitted note.
+---
+--- Octave numbers in note strings follow the octave numbering of the host: by default
+--- note 60 is "c5". When the host names middle C "c4" or "c3", notes in the lowest octaves
+--- use negative octaves, e.g. "c-1". Notes without an octave, e.g. "c", always are note 48.
+---
 --- In note strings the following prefixes are used to specify optional note 
 --- attributes: 
 ---```md
@@ -90,6 +100,8 @@ function Note:with_delay(delay) end
 --- -'v' -> volume (float in range [0-1])
 --- -'p' -> panning (float in range [-1-1])
 --- -'d' -> delay (float in range [0-1])
+--- -'s' -> strum (chords only: float in range [0-1] with optional 'u' (up),
+---          'd' (down) or 'c' (center out) direction suffix)
 ---```
 ---
 ---### examples:
@@ -99,6 +111,8 @@ function Note:with_delay(delay) end
 --- note("c4 #2 v0.5 d0.3") -- middle C with additional properties
 --- note({key="c4", volume=0.5}) -- middle C with volume 0.5
 --- note("c4'maj v0.7") -- C4 major chord with volume 0.7
+--- note("c4'maj s0.1d") -- C4 major chord, strummed down with 0.1 delays per note
+--- note({key="c4'maj", strum=0.1}) -- C4 major chord, strummed up
 --- note("c4", "e4 v0.5", "off") -- custom chord with a c4, e4 and 'off' note
 --- ```
 ---@param ... NoteValue
@@ -219,6 +219,42 @@ function pattern.euclidean(steps, length, offset, empty_value)
   end
 end
 
+---Create a new euclidean rhythm pattern with accents. On steps get accented by a second
+---euclidean distribution of `accents` in `accent_length`, which repeats over the on steps.
+---Resulting values are velocities: 1 for accented, `velocity` for unaccented on steps and
+---0 for off steps, which can be passed to rhythms using `pulse_volume`.
+---
+---### examples:
+---```lua
+----- accents(2, 3) over pulses(5, 8): { 1, 0, 1, 0.5, 0, 1, 1, 0 }
+---pattern.euclidean_accents(5, 8, 2, 3)
+---```
+---@param steps integer Number of on steps in the pattern.
+---@param length integer Number of total steps in the pattern.
+---@param accents integer Number of accented steps in the accent pattern.
+---@param accent_length integer Number of total steps in the accent pattern.
+---@param offset integer? Optional rotation offset of the pattern.
+---@param velocity number? Velocity of unaccented on steps in range [0 - 1] (by default 0.5).
+function pattern.euclidean_accents(steps, length, accents, accent_length, offset, velocity)
+  assert(type(steps) == "number" and steps >= 0,
+    "invalid steps argument (must be an integer >= 0)")
+  assert(type(accents) == "number" and accents >= 0,
+    "invalid accents argument (must be an integer >= 0)")
+  assert(type(velocity) == "number" or velocity == nil,
+    "invalid velocity argument (must be a number or nil)")
+  velocity = math.max(0, math.min(1, velocity or 0.5))
+  local hits = pattern.euclidean(steps, length, offset, 0)
+  local accent_hits = pattern.euclidean(accents, accent_length, 0, 0)
+  local accent_index = 0
+  return hits:map(function(value)
+    if value == 0 then
+      return 0
+    end
+    accent_index = accent_index % #accent_hits + 1
+    return accent_hits[accent_index] == 1 and 1 or velocity
+  end)
+end
+
 ----------------------------------------------------------------------------------------------------
 --- Access sub ranges
 ----------------------------------------------------------------------------------------------------
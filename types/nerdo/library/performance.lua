@@ -0,0 +1,66 @@
+---@meta
+---Do not try to execute this file. It's just a type definition file.
+---
+---Part of the afseq trait: Defines LuaLS annotations for the afseq performance_macro function.
+---
+
+----------------------------------------------------------------------------------------------------
+
+---A single target of a performance macro. Specify exactly one of `parameter`, `mute`, `delay`
+---or `context`. Target values move within `min` and `max` along with the macro's value.
+---@class PerformanceMacroTarget
+---Rhythm parameter id, as listed by the host's sequence, e.g. "0.1.density".
+---@field parameter string?
+---Rhythm slot index, starting with 1: mutes new notes of the slot while the macro's value is
+---in range [min - max].
+---@field mute integer?
+---Rhythm slot index, starting with 1: delays the slot by [min - max] seconds.
+---@field delay integer?
+---External context value name, e.g. to drive transform amounts in scripts.
+---@field context string?
+---Target value when the macro's value is 0. By default 0.
+---@field min number?
+---Target value when the macro's value is 1. By default 1.
+---@field max number?
+---Interpolation curve of parameter and context targets. By default "linear".
+---@field curve "linear"|"exponential"?
+
+---Definition of a performance macro.
+---@class PerformanceMacroDefinition
+---Unique name of the macro.
+---@field name string
+---Initial value of the macro in range [0 - 1]. By default 0.
+---@field value number?
+---Targets which follow the macro's value.
+---@field targets PerformanceMacroTarget[]
+
+---@class PerformanceMacro
+---Name of the macro.
+---@field name string
+---Current value of the macro in range [0 - 1].
+---@field value number
+---Current values of all targets. Mute targets are 1 when muted, else 0.
+---@field target_values number[]
+local PerformanceMacro = {}
+
+----------------------------------------------------------------------------------------------------
+
+---Create a new performance macro: a single control in range [0 - 1], which drives multiple
+---targets, so one knob can morph an entire sequence. Hosts load macros from scripts which
+---return a single macro or an array of macros.
+---
+---### examples:
+---```lua
+---return performance_macro {
+---  name = "intensity",
+---  targets = {
+---    { parameter = "0.0.density", min = 0.25, max = 1 },
+---    { mute = 3, max = 0.5 }, -- hihats come in after 50%
+---    { context = "cutoff", min = 200, max = 8000, curve = "exponential" },
+---  }
+---}
+---```
+---@param definition PerformanceMacroDefinition
+---@return PerformanceMacro
+---@nodiscard
+function performance_macro(definition) end
@@ -0,0 +1,38 @@
+---@meta
+---Do not try to execute this file. It's just a type definition file.
+---
+---Part of the afseq trait: Defines LuaLS annotations for the afseq Pool class.
+---
+
+----------------------------------------------------------------------------------------------------
+
+---Weighted random note pool, as created by the `pool` function.
+---@class Pool : userdata
+
+---A single pool entry: a note value and its relative weight. Entries without a weight use 1.
+---@alias PoolEntry { [1]: NoteValue, [2]: number? }|NoteValue
+
+---@class PoolOptions
+---Avoid picking the same pool entry twice in a row. By default false.
+---@field avoid_repetition boolean?
+---Fit all picked notes into the given scale.
+---@field scale Scale?
+
+----------------------------------------------------------------------------------------------------
+
+---Create an emitter which randomly picks notes from a weighted pool of notes for each pulse.
+---
+---Picks are seeded via `math.randomseed`, so seeded pools generate the same notes after a reset.
+---
+---### examples:
+---```lua
+-----Mostly c4, sometimes e4 or g4
+---pool{ {"c4", 3}, {"e4", 1}, {"g4", 1} }
+-----Never repeat the same chord twice, fitting notes into a scale
+---pool{ {"c4'maj", 2}, {"f4'maj", 1}, {"g4'maj", 1},
+---  avoid_repetition = true, scale = scale("c4", "major") }
+---```
+---@param entries PoolEntry[]|PoolOptions
+---@return Pool
+---@nodiscard
+function pool(entries) end
@@ -27,6 +27,49 @@ error("Do not try to execute this file. It's just a type definition file.")
 ---@field beats_per_bar integer
 -----Project's sample rate in samples per second.
 ---@field samples_per_sec integer
+---Key-value store which is shared by all rhythms in a sequence. Values can be booleans,
+---numbers or strings. Assign nil to remove a value.
+---
+---### examples:
+---```lua
+---context.shared.drums_playing = true -- set a flag in a drum rhythm
+---if context.shared.drums_playing then end -- read the flag in some other rhythm
+---```
+---@field shared table<string, boolean|number|string>
+---Current values of the rhythm's parameters, as declared in the rhythm's `parameters`. Assigned
+---values get clamped to the parameter's range and are reported to the host.
+---
+---### examples:
+---```lua
+---if context.parameters.section == 1 then end -- read a parameter value
+---context.parameters.section = 2 -- move on to the next section
+---```
+---@field parameters table<string, number>
+---Set the value of one of the rhythm's `parameters`, like assigning it via `context.parameters`.
+---Returns the applied, clamped value. Raises an error when the parameter does not exist.
+---
+---### examples:
+---```lua
+---if context.pulse_step % 32 == 0 then
+---  context:set_parameter("section", context.parameters.section + 1)
+---end
+---```
+---@field set_parameter fun(self: TimeContext, id: string, value: number): number
+---Time conversion helpers, which use the context's current tempo and sample rate, so they keep
+---working when the host changes the tempo while running. Use them instead of hard-coding
+---`60 / context.beats_per_min` math.
+---
+---### examples:
+---```lua
+---local delay = context:beats_to_seconds(0.5) -- length of an eighth note in seconds
+---local beats = context:samples_to_beats(1024) -- length of 1024 samples in beats
+---```
+---@field beats_to_seconds fun(self: TimeContext, beats: number): number
+---@field seconds_to_beats fun(self: TimeContext, seconds: number): number
+---@field beats_to_samples fun(self: TimeContext, beats: number): number
+---@field samples_to_beats fun(self: TimeContext, samples: number): number
+---@field seconds_to_samples fun(self: TimeContext, seconds: number): number
+---@field samples_to_seconds fun(self: TimeContext, samples: number): number
 
 ----------------------------------------------------------------------------------------------------
 
@@ -43,6 +86,20 @@ error("Do not try to execute this file. It's just a type definition file.")
 
 ----------------------------------------------------------------------------------------------------
 
+---How probability gates draw their random values. "white" uses independent random values, which
+---may clump up. "golden" uses a low-discrepancy golden ratio sequence and "shuffle" draws from
+---shuffled bags of 8 evenly spaced values, so probabilities result in evenly spread triggers.
+---@alias GateSampling "white"|"golden"|"shuffle"
+
+---Probability curve for a rhythm's `gate`.
+---@class ProbabilityCurve
+---List of `{position, probability}` points with positions and probabilities in range [0 - 1].
+---@field curve number[][]
+---Length of the curve in pattern steps.
+---@field length number
+---Random value sampling mode. By default "white".
+---@field sampling GateSampling?
+
 ---Context passed to `gate` functions.
 ---@class GateContext : PatternContext
 ---
@@ -94,7 +151,16 @@ error("Do not try to execute this file. It's just a type definition file.")
 ---unit = "beats", resolution = 1.01 --> slightly off beat pulse
 ---unit = "1/16", resolution = 4/3 --> triplet
 ---```
----@field resolution number?
+---
+---To modulate the resolution over time, pass a `{default, min, max}` table instead. The resolution
+---then is exposed as `rhythm.resolution` parameter, which hosts or scripts via
+---`context.parameters` can change. Changes apply with the next pulse, so running pulses never
+---get cut, dropped or doubled. `offset` is applied with the default resolution.
+---### examples:
+---```lua
+---unit = "1/16", resolution = { default = 1, min = 0.25, max = 1 } --> build-ups
+---```
+---@field resolution number|{ default: number?, min: number, max: number }?
 ---
 ---Optional offset in `unit * resolution` time units. By default 0.
 ---When set, the rhythm's event output will be delayed by the given offset value.
@@ -149,8 +215,10 @@ error("Do not try to execute this file. It's just a type definition file.")
 ---  end
 ---end
 ---
+----- a pattern implementation, which got registered by the host application
+---pattern = "mycrate.polyrhythm"
 ---```
----@field pattern Pulse[]|(fun(context: PatternContext):Pulse)|(fun(context: PatternContext):fun(context: PatternContext):Pulse)?
+---@field pattern Pulse[]|string|(fun(context: PatternContext):Pulse)|(fun(context: PatternContext):fun(context: PatternContext):Pulse)?
 ---
 ---If and how many times a pattern should repeat. When 0 or false, the pattern does not repeat
 ---and plays back only once. When true, the pattern repeats endlessly, which is the default.
@@ -169,6 +237,102 @@ error("Do not try to execute this file. It's just a type definition file.")
 ---```
 ---@field repeats (integer|boolean)?
 ---
+---Optional microrhythm template, which remaps the time positions of the pulses in the pattern.
+---Can either be a groove preset name or a groove created via `groove`.
+---
+---### examples:
+---```lua
+---unit = "1/16",
+---groove = "tresillo" -- spread 3 pulses as 3+3+2 over 8 sixteenths
+---
+---unit = "1/16",
+---groove = groove("septuplet") -- play 7 pulses over a single beat
+---```
+---@field groove (Groove|GroovePreset)?
+---
+---Optionally humanize emitted notes. Randomly shifts note volumes and pannings by up to the
+---given amounts and delays notes by up to the given amount of the event's duration. The
+---correlation controls whether notes in a chord share the same variation (1, tight chords) or
+---vary independently (0, loose chords). All amounts are in range [0 - 1].
+---
+---### examples:
+---```lua
+---humanize = { volume = 0.1, timing = 0.05 } -- slightly vary volumes and timings
+---humanize = { timing = 0.1, correlation = 1 } -- shift chords as a whole
+---```
+---@field humanize { volume: number?, panning: number?, timing: number?, correlation: number? }?
+---
+---Optionally pan emitted notes automatically. `strategy` is one of "alternate", which alternates
+---notes between left and right, "spread", which spreads chord notes from left to right, or
+---"random", which pans notes randomly. `width` in range [0 - 1] sets how far notes get panned,
+---by default 1. Pannings get added to the emitted note's panning.
+---
+---### examples:
+---```lua
+---panning = { strategy = "alternate", width = 0.5 } -- ping pong notes
+---panning = { strategy = "spread" } -- spread chords across the stereo field
+---```
+---@field panning { strategy: "alternate"|"spread"|"random", width: number? }?
+---
+---Optionally re-emit emitted notes as echoes. The `delay` is specified in steps of the rhythm's
+---unit in range [0 - 16], each repeat's volume gets multiplied by the `feedback` amount in range
+---[0 - 1] and gets transposed by `transpose` semitones in range [-12 - 12]. `repeats` sets the
+---max number of repeats in range [0 - 16]. The echo's controls are exposed as `echo.delay`,
+---`echo.feedback`, `echo.transpose` and `echo.repeats` parameters, so they can be automated.
+---
+---### examples:
+---```lua
+---echo = { delay = 3, feedback = 0.5 } -- dotted eighth echoes with "1/16" units
+---echo = { delay = 2, feedback = 0.7, transpose = 12, repeats = 2 } -- octave climbing echoes
+---```
+---@field echo { delay: number?, feedback: number?, transpose: integer?, repeats: integer? }?
+---
+---Optionally transpose all emitted notes by the given amount of semitones in range [-48 - 48].
+---The transposition is exposed as `transpose` parameter, so it can be automated.
+---
+---### examples:
+---```lua
+---transpose = 7 -- shift everything up a fifth
+---```
+---@field transpose integer?
+---
+---Optionally shift all emitted notes by scale degrees within the given `scale`. Notes get fit
+---into the scale first, then move along the scale's notes by `degrees` in range [-14 - 14].
+---The shift is exposed as `degree_shift.degrees` parameter, so it can be automated.
+---Degree shifts apply before `transpose`.
+---
+---### examples:
+---```lua
+---degree_shift = { scale = scale("c", "major"), degrees = 2 } -- shift up a third in key
+---```
+---@field degree_shift { scale: Scale, degrees: integer? }?
+---
+---Relative notes in emitted notes, sequences and cycles, such as `+3` or `+-5` (semitones) and
+---`^2` or `^-3` (scale degrees), get resolved at emit time from the previously emitted note.
+---Optionally set the `reference` note, which the first relative note refers to, and the
+---`scale` of scale degrees. By default, relative notes start from C4 in C major.
+---
+---### examples:
+---```lua
+---relative_notes = { reference = "a3", scale = scale("a", "minor") },
+---emit = { "+0", "^2", "^2", "^-1" } -- a3, c4, e4, d4
+---```
+---@field relative_notes { reference: NoteValue, scale: Scale? }?
+---
+---Optionally reseed the rhythm's random gates, emitters, humanizer and panner every `period`
+---steps of the rhythm's unit. At the start of each period, a new seed gets picked with the
+---`amount` probability in range [0 - 1], else the previous period's random values replay.
+---An amount of 0 thus loops the first period, 1 creates a new variation in every period.
+---The amount is exposed as `seed_morph.amount` parameter, so it can be automated.
+---Random values in Lua callbacks are not affected.
+---
+---### examples:
+---```lua
+---unit = "1/16",
+---seed_morph = { period = 256, amount = 0.25 } -- slowly evolve every 16 bars
+---```
+---@field seed_morph { period: number, amount: number? }?
+---
 ---Set optional pulse train filter between pattern and emitter. By default a probability
 ---gate is used, which passes 1s directly, skips 0s, and applies values in range (0 - 1) using
 ---the pulse value as probability, like:
@@ -177,14 +341,59 @@ error("Do not try to execute this file. It's just a type definition file.")
 ---  return context.pulse_value >= 1 or context.pulse_value > math.random()
 ---end
 ---```
----@field gate Pulse[]|(fun(context: GateContext):boolean)|(fun(context: GateContext):fun(context: GateContext):boolean)?
+---
+---Alternatively a probability curve can be passed, which scales pulse values depending on the
+---position within a given number of steps. Curve points are `{position, probability}` tables,
+---with positions relative to the length in range [0 - 1]:
+---```lua
+----- raise the chance of extra hits in the last bar of 4 bars
+---unit = "1/16",
+---gate = { curve = { {0, 0.25}, {0.74, 0.25}, {0.75, 1} }, length = 64 }
+---```
+---
+---Probability gates by default use white noise random values. To spread triggers more evenly, a
+---different sampling mode can be set, with or without a curve:
+---```lua
+---pattern = { 0.5 },
+---gate = { sampling = "shuffle" } -- triggers exactly 4 out of 8 pulses
+---```
+---
+---A threshold gate triggers all pulses with values above the given threshold in range [0 - 1]
+---instead, so fractional pulse values can be used as velocity, see `pulse_volume`:
+---```lua
+---pattern = { 1, 0.25, 0.5, 0.25 },
+---gate = { threshold = 0 }, -- triggers all non zero pulses
+---pulse_volume = true
+---```
+---
+---Gate implementations, which got registered by the host application, are used by name:
+---```lua
+---gate = "mycrate.gate"
+---```
+---@field gate ProbabilityCurve|{ sampling: GateSampling }|{ threshold: number }|string|(fun(context: GateContext):boolean)|(fun(context: GateContext):fun(context: GateContext):boolean)?
+---
+---Optionally scale the volume of emitted notes with the pulse values of the pattern, so
+---patterns can specify velocities. Pulse values > 1 boost the volume. Use together with a
+---`threshold` gate, as probability gates skip fractional pulse values randomly.
+---
+---### examples:
+---```lua
+---pattern = { 1, 0, 0.5, 0, 0.75, 0, 0.5, 0.25 },
+---gate = { threshold = 0 },
+---pulse_volume = true
+---```
+---@field pulse_volume boolean?
 ---
 ---Specify the melodic pattern of the rhythm. For every pulse in the rhythmical pattern, the event
 ---from the specified emit sequence. When the end of the sequence is reached, it starts again from
 ---the beginning.<br>
 ---
 ---To generate notes dynamically, you can pass a function or a function iterator, instead of a
----fixed array or sequence of notes.<br>
+---fixed array or sequence of notes. Generators can also return a coroutine, which yields one
+---note at a time: the coroutine gets resumed with the context for every pulse, so its local
+---state is naturally preserved between pulses. When the coroutine finishes, this gets reported
+---as error once, and it emits nothing until the rhythm gets reset. Generators may also return
+---parameter change tables such as `{ parameter = 1, value = 0.5 }` instead of notes.<br>
 ---
 ---Events can also be generated using the tidal cycle mini-notation. Cycles are repeated endlessly
 ---by default, and have the duration of a single pulse in the pattern. Patterns can be used to
@@ -216,6 +425,19 @@ error("Do not try to execute this file. It's just a type definition file.")
 ---  end
 ---end
 ---
+----- stateful coroutine generator
+---emit = function(initial_context)
+---  ---@param context EmitterContext
+---  return coroutine.create(function(context)
+---    local notes = scale("c5", "minor").notes
+---    while true do
+---      for _, key in ipairs(notes) do
+---        coroutine.yield { key = key, volume = context.pulse_value }
+---      end
+---    end
+---  end)
+---end
+---
 ----- a note pattern
 ---local tritone = scale("c5", "tritone")
 ---...
@@ -225,8 +447,35 @@ error("Do not try to execute this file. It's just a type definition file.")
 ----- a tidal cycle
 ---emit = cycle("<[a3 c4 e4 a4]*3 [d4 g3 g4 c4]>")
 -----
+----- a weighted random note pool
+---emit = pool{ {"c4", 3}, {"e4", 1}, {"g4", 1}, avoid_repetition = true }
+-----
+----- an emitter implementation, which got registered by the host application
+---emit = "mycrate.arpeggio"
+---```
+---@field emit Cycle|Pool|Sequence|Note|NoteValue|(NoteValue|Note)[]|(fun(context: EmitterContext):NoteValue)|(fun(context: EmitterContext):fun(context: EmitterContext):NoteValue)|(fun(context: EmitterContext):thread)
+---
+---Optionally declare parameters of the rhythm which the host can automate and which `pattern`,
+---`gate` and `emit` functions can read and change via `context.parameters`. Values either are
+---default numbers, or tables with `default`, `min` and `max` and optional `name` and `integer`
+---properties.
+---
+---### examples:
+---```lua
+---parameters = { density = 0.5, section = { default = 1, min = 1, max = 4, integer = true } }
+---```
+---@field parameters table<string, number|{ default: number, min: number, max: number, name: string?, integer: boolean? }>?
+---
+---Optionally seed the rhythm's random number generators, which are used by probability gates,
+---humanization and random emitters, so the rhythm plays back the same way each time.
+---Overrides seeds set via `math.randomseed`, but does not affect `math.random` calls in
+---functions. Seeds are compatible with seeds auditioned in the host's seed bounces.
+---
+---### examples:
+---```lua
+---seed = 1234
 ---```
----@field emit Cycle|Sequence|Note|NoteValue|(NoteValue|Note)[]|(fun(context: EmitterContext):NoteValue)|(fun(context: EmitterContext):fun(context: EmitterContext):NoteValue)
+---@field seed number?
 
 
 ----------------------------------------------------------------------------------------------------
@@ -279,9 +528,30 @@ error("Do not try to execute this file. It's just a type definition file.")
 ---  unit = "bars", -- emit one cycle per bar
 ---  emit = cycle("[c4 [f5 f4]*2]|[c4 [g5 g4]*3]")
 ---}
+---
+-----create a rhythm implementation, which got registered by the host application
+---return rhythm("mycrate.rhythm")
 -----
 ---```
----@param options RhythmOptions
+---@param options RhythmOptions|string
 ---@return userdata
 ---@nodiscard
 function rhythm(options) end
+
+----------------------------------------------------------------------------------------------------
+
+---Chain multiple rhythms with repeat counts, which play one after another in a single rhythm
+---slot. Each rhythm plays for its pattern length times its repeat count. Repeat counts default
+---to 1 when omitted. After the last rhythm, the chain starts over with the first one.
+---
+---### examples:
+---```lua
+---local verse = rhythm { unit = "1/4", pattern = { 1, 0, 1, 1 }, emit = "c4" }
+---local chorus = rhythm { unit = "1/8", pattern = { 1, 1, 0, 1 }, emit = "e4" }
+----- play the verse 4 times, then the chorus 2 times, then start over
+---return chain { verse, 4, chorus, 2 }
+---```
+---@param rhythms (userdata|integer)[]
+---@return userdata
+---@nodiscard
+function chain(rhythms) end
@@ -65,6 +65,46 @@ function Sequence:with_panning(panning) end
 ---@nodiscard
 function Sequence:with_delay(delay) end
 
+---Create a copy of all notes in the sequence with new per step volume values. Unlike in
+---`with_volume`, value arrays are repeated until they cover all steps of the sequence.
+---
+---### examples:
+---```lua
+---sequence("c4", "d4", "e4", "f4"):volumes({1.0, 0.5}) -- accent every other step
+---```
+---@param volumes number|number[]
+---@return Sequence
+---@nodiscard
+function Sequence:volumes(volumes) end
+
+---Create a copy of all notes in the sequence with new per step panning values. Value arrays
+---are repeated until they cover all steps of the sequence.
+---@param pannings number|number[]
+---@return Sequence
+---@nodiscard
+function Sequence:pannings(pannings) end
+
+---Create a copy of all notes in the sequence with new per step delay values. Value arrays
+---are repeated until they cover all steps of the sequence.
+---@param delays number|number[]
+---@return Sequence
+---@nodiscard
+function Sequence:delays(delays) end
+
+---Create a copy of the sequence in which each step is repeated the given number of times.
+---Count arrays are repeated until they cover all steps of the sequence. Counts must be in
+---range [1..=256].
+---
+---### examples:
+---```lua
+---sequence("c4", "e4"):repeat_steps(2) -- c4, c4, e4, e4
+---sequence("c4", "e4", "g4"):repeat_steps({2, 1}) -- c4, c4, e4, g4, g4
+---```
+---@param count integer|integer[]
+---@return Sequence
+---@nodiscard
+function Sequence:repeat_steps(count) end
+
 ----------------------------------------------------------------------------------------------------
 
 ---Create a sequence from an array of note values or note value varargs.